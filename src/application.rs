@@ -14,49 +14,100 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
+use itertools::Itertools;
 use libc::pid_t;
 use log::info;
 use std::{
     borrow::Cow,
     io::Write,
-    time::{Duration, SystemTime},
+    path::{Path, PathBuf},
+    rc::Rc,
+    time::{Duration, Instant},
 };
 use strum::{EnumMessage, IntoEnumIterator};
 
 use crate::{
-    cfg::{DisplayMode, ExportSettings, ExportType, MetricFormat, Settings},
-    clock::{DriftMonitor, Timer},
-    console::BuiltinTheme,
+    cfg::{
+        CpuBasis, DisplayMode, ExportSettings, ExportType, MetricFormat, NotificationSettings,
+        Settings, TextLayout,
+    },
+    clock::{
+        format_duration, DriftMonitor, OverloadMonitor, SampleClock, SampleTime, Timer, Workload,
+    },
+    console::is_foreground,
     display::{
-        DataKind, DisplayDevice, Interaction, NullDevice, PaneData, PaneKind, PauseStatus,
-        TerminalDevice, TextDevice,
+        DataKind, DisplayDevice, Interaction, NullDevice, PaneData, PaneEvent, PaneKind,
+        PaneNavigator, PauseStatus, Tabs, TextDevice,
+    },
+    dump::{write_dump, Json},
+    export::{
+        clear_truncated, mark_truncated, write_bundle, Broadcaster, CsvExporter, ExecExporter,
+        Exporter, NagiosExporter, ProcfsCapture, StatsdExporter,
     },
-    export::{CsvExporter, Exporter, RrdExporter},
+    overhead::{Guardrail, OverheadMonitor},
+    privileges::{drop_privileges, lookup_user, UserSpec},
     process::{
-        Collector, FlatProcessManager, ForestProcessManager, FormattedMetric, MetricDataType,
-        MetricId, MetricNamesParser, ProcessDetails, ProcessManager, SystemConf, TargetId,
+        count_by_name, format, matchers::ProcessExclusion, read_oom_kills, Collector,
+        ExternalFormatter, FlatProcessManager, ForestProcessManager, FormattedMetric, MemoryUsage,
+        MemoryWatch, MetricDataType, MetricId, MetricNamesParser, MetricSelection, ProcMount,
+        ProcessDetails, ProcessFilter, ProcessIdentity, ProcessManager, ShortLivedMonitor,
+        StateEvent, StateMonitor, SystemConf, TargetId,
     },
     sighdr::SignalHandler,
 };
 
+#[cfg(feature = "tui")]
+use crate::{
+    cfg::SearchSettings,
+    console::{AlertSignal, BuiltinTheme},
+    display::{Permissions, TerminalDevice},
+};
+
+#[cfg(feature = "rrd")]
+use crate::export::RrdExporter;
+
 /// Delay in seconds between two notifications for time drift
 const DRIFT_NOTIFICATION_DELAY: u64 = 300;
 
+/// Delay in seconds between two notifications for overload
+const OVERLOAD_NOTIFICATION_DELAY: u64 = 60;
+
+/// Maximum number of processes with details open in tabs.
+const MAX_DETAILS_TABS: usize = 5;
+
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
     #[error("no target specified in non-terminal mode")]
     NoTargets,
     #[error("terminal not available")]
     TerminalNotAvailable,
+    #[cfg(not(feature = "rrd"))]
+    #[error("rrd export not available in this build")]
+    RrdNotAvailable,
 }
 
 pub type ApplicationResult<T> = Result<T, Error>;
 
+/// Function returning the settings when the configuration is reloaded.
+pub type Reloader = Box<dyn Fn() -> anyhow::Result<Settings>>;
+
+/// Return the first error and log the others.
+fn first_error(results: Vec<anyhow::Result<()>>) -> anyhow::Result<()> {
+    let mut errors = results.into_iter().filter_map(Result::err);
+    match errors.next() {
+        Some(err) => {
+            errors.for_each(|other| log::error!("{other:#}"));
+            Err(err)
+        }
+        None => Ok(()),
+    }
+}
+
 /// List available metrics
 pub fn list_metrics() {
     for metric_id in MetricId::iter() {
         println!(
-            "{:<18}\t{:<9}\t{}",
+            "{:<19}\t{:<9}\t{}",
             metric_id.as_str(),
             match metric_id.data_type() {
                 MetricDataType::Counter => "[counter]",
@@ -68,19 +119,17 @@ pub fn list_metrics() {
 }
 
 /// Return the best available display
-fn resolve_display_mode(
-    mode: DisplayMode,
-    theme: Option<BuiltinTheme>,
-) -> ApplicationResult<(DisplayMode, Option<BuiltinTheme>)> {
+#[cfg(feature = "tui")]
+fn resolve_display_mode(mode: DisplayMode) -> ApplicationResult<DisplayMode> {
     match mode {
-        DisplayMode::None | DisplayMode::Text => Ok((mode, None)),
+        DisplayMode::None | DisplayMode::Text => Ok(mode),
         _ => {
             if TerminalDevice::is_available() {
-                Ok((DisplayMode::Terminal, theme.or_else(BuiltinTheme::guess)))
+                Ok(DisplayMode::Terminal)
             } else {
                 match mode {
                     DisplayMode::Terminal => Err(Error::TerminalNotAvailable),
-                    DisplayMode::Any => Ok((DisplayMode::Text, None)),
+                    DisplayMode::Any => Ok(DisplayMode::Text),
                     _ => panic!("already handled in outter match."),
                 }
             }
@@ -88,15 +137,95 @@ fn resolve_display_mode(
     }
 }
 
+/// Return the best available display without terminal user interface.
+#[cfg(not(feature = "tui"))]
+fn resolve_display_mode(mode: DisplayMode) -> ApplicationResult<DisplayMode> {
+    match mode {
+        DisplayMode::Terminal => Err(Error::TerminalNotAvailable),
+        DisplayMode::Any => Ok(DisplayMode::Text),
+        _ => Ok(mode),
+    }
+}
+
+/// Sample the targets and check the thresholds.
+///
+/// The thresholds are checked on the last of `count` samples taken `every` interval.
+pub fn check(
+    target_ids: &[TargetId],
+    checker: &mut NagiosExporter,
+    count: u64,
+    every: Duration,
+) -> anyhow::Result<()> {
+    let metrics = MetricNamesParser::new(false).parse(&checker.metric_names())?;
+    let sysconf = SystemConf::new()?;
+    let mut collector = Collector::new(Cow::Borrowed(&metrics));
+    let mut tmgt = FlatProcessManager::new(&sysconf, &metrics, target_ids)?;
+    checker.open(metrics.iter())?;
+    for loop_number in 0..count {
+        if loop_number > 0 {
+            std::thread::sleep(every);
+        }
+        tmgt.refresh(&mut collector)?;
+    }
+    checker.export(&collector, &SampleClock::new().now(None))?;
+    checker.close()
+}
+
 /// Application displaying the details metrics
 pub struct Application<'s> {
     display_mode: DisplayMode,
+    layout: TextLayout,
+    timestamps: bool,
     every: Duration,
+    /// Delay between two refreshes of the process panes if not the same as the samples.
+    details_every: Option<Duration>,
+    align: bool,
+    adaptive: bool,
+    #[cfg(feature = "tui")]
+    permissions: Permissions,
+    background: Option<Duration>,
+    /// Depth of the tree below which the processes are folded.
+    max_depth: Option<usize>,
+    /// Basis of the CPU time ratio.
+    cpu_basis: CpuBasis,
+    /// Number of samples kept by the terminal.
+    #[cfg(feature = "tui")]
+    history: usize,
+    /// Maximum number of frames per second in the terminal.
+    #[cfg(feature = "tui")]
+    max_fps: u32,
+    /// Whether the wait channel of the blocked processes is displayed.
+    wchan: bool,
+    /// Whether the counters of the exited processes are added to their parent.
+    fold_exited: bool,
+    reloader: Option<Reloader>,
     count: Option<u64>,
     metrics: Vec<FormattedMetric>,
+    /// Metrics collected by target if some targets have specific metrics.
+    metric_selection: Option<MetricSelection>,
     export_settings: &'s ExportSettings,
+    #[cfg(feature = "tui")]
     theme: Option<BuiltinTheme>,
+    #[cfg(feature = "tui")]
+    describe: bool,
+    /// Fraction of the interval that oprs may spend on the CPU when it monitors itself.
+    myself_cpu_limit: Option<f64>,
+    #[cfg(feature = "tui")]
+    fit: Option<Vec<String>>,
+    #[cfg(feature = "tui")]
+    title: bool,
+    #[cfg(feature = "tui")]
+    bell: AlertSignal,
+    #[cfg(feature = "tui")]
+    searches: &'s SearchSettings,
+    notifications: &'s NotificationSettings,
+    exclusion: ProcessExclusion,
     human: bool,
+    user: Option<UserSpec>,
+    share: Option<PathBuf>,
+    capture: Option<PathBuf>,
+    record_input: bool,
+    replay_input: Option<PathBuf>,
 }
 
 impl<'s> Application<'s> {
@@ -104,23 +233,128 @@ impl<'s> Application<'s> {
         settings: &'s Settings,
         metric_names: &[&'m str],
     ) -> anyhow::Result<Application<'s>> {
-        let every = Duration::from_millis((settings.display.every * 1000.0) as u64);
+        let every = settings.display.every;
         let human = matches!(settings.display.format, MetricFormat::Human);
         let mut metrics_parser = MetricNamesParser::new(human);
-        let (display_mode, theme) =
-            resolve_display_mode(settings.display.mode, settings.display.theme)?;
+        metrics_parser.set_derived(settings.derived.metrics()?);
+        metrics_parser.set_filesystems(settings.filesystems.metrics()?);
+        let display_mode = resolve_display_mode(settings.display.mode)?;
+        let mut metrics = metrics_parser.parse(metric_names)?;
+        let metric_selection = if settings.target_metrics.is_empty() {
+            None
+        } else {
+            let targets = settings
+                .target_metrics
+                .iter()
+                .map(|(name, names)| Ok((name.to_string(), metrics_parser.parse(names)?)))
+                .collect::<anyhow::Result<Vec<(String, Vec<FormattedMetric>)>>>()?;
+            Some(MetricSelection::merge(&mut metrics, targets))
+        };
+        for metric in metrics.iter_mut() {
+            if let Some(command) = settings.formatters.get(metric.id) {
+                metric.external = Some(Rc::new(ExternalFormatter::new(command)));
+            }
+            if let Some(column) = settings.columns.get(metric.id) {
+                metric.column = column;
+            }
+        }
+
+        let excluded_uids = settings
+            .exclusion
+            .users
+            .iter()
+            .map(|user| match user.parse::<u32>() {
+                Ok(uid) => Ok(uid),
+                Err(_) => lookup_user(user).map(|(uid, _)| uid),
+            })
+            .collect::<Result<Vec<u32>, _>>()?;
+        let exclusion = ProcessExclusion::new(&settings.exclusion.names, &excluded_uids)?;
+        if !exclusion.is_empty() {
+            info!(
+                "excluded processes: names {:?}, users {:?}",
+                settings.exclusion.names, settings.exclusion.users
+            );
+        }
 
         Ok(Application {
             display_mode,
+            layout: settings.display.layout,
+            timestamps: settings.display.timestamps,
             every,
+            details_every: settings.display.details_every,
+            align: settings.display.align,
+            adaptive: settings.display.adaptive,
+            #[cfg(feature = "tui")]
+            permissions: Permissions::new(
+                settings.display.read_only,
+                settings.display.read_only_delay,
+            ),
+            background: settings.display.background,
+            max_depth: settings.display.max_depth,
+            cpu_basis: settings.display.cpu_basis,
+            #[cfg(feature = "tui")]
+            history: settings.display.history,
+            #[cfg(feature = "tui")]
+            max_fps: settings.display.max_fps,
+            wchan: settings.display.wchan,
+            fold_exited: settings.targets.fold_exited,
+            reloader: None,
             count: settings.display.count,
-            metrics: metrics_parser.parse(metric_names)?,
+            metrics,
+            metric_selection,
             export_settings: &settings.export,
-            theme,
+            #[cfg(feature = "tui")]
+            theme: match display_mode {
+                DisplayMode::Terminal => settings.display.theme.or_else(BuiltinTheme::guess),
+                _ => None,
+            },
+            #[cfg(feature = "tui")]
+            describe: settings.display.describe,
+            myself_cpu_limit: (settings.targets.myself_cpu_limit > 0)
+                .then(|| f64::from(settings.targets.myself_cpu_limit) / 100.0),
+            #[cfg(feature = "tui")]
+            fit: settings
+                .display
+                .fit
+                .then(|| settings.display.fit_priority.clone()),
+            #[cfg(feature = "tui")]
+            title: settings.display.title,
+            #[cfg(feature = "tui")]
+            bell: settings.display.bell,
+            #[cfg(feature = "tui")]
+            searches: &settings.searches,
+            notifications: &settings.notifications,
+            exclusion,
             human,
+            user: settings.privileges.user.as_deref().map(UserSpec::parse),
+            share: None,
+            capture: None,
+            record_input: false,
+            replay_input: None,
         })
     }
 
+    /// Reload the configuration on SIGHUP instead of exiting.
+    pub fn set_reloader(&mut self, reloader: Reloader) {
+        self.reloader = Some(reloader);
+    }
+
+    /// Publish the samples on a Unix socket for followers.
+    pub fn set_share(&mut self, path: PathBuf) {
+        self.share = Some(path);
+    }
+
+    /// Capture the procfs files of the monitored processes in a directory.
+    pub fn set_capture(&mut self, path: PathBuf) {
+        self.capture = Some(path);
+    }
+
+    /// Log the input events in the terminal and replay the events of a log file.
+    pub fn set_input_recording(&mut self, record: bool, replay: Option<PathBuf>) {
+        self.record_input = record;
+        self.replay_input = replay;
+    }
+
     pub fn run(
         &self,
         target_ids: &[TargetId],
@@ -128,14 +362,36 @@ impl<'s> Application<'s> {
         root_pid: Option<pid_t>,
     ) -> anyhow::Result<()> {
         info!("starting");
-        let mut is_interactive = false;
-        let device: Box<dyn DisplayDevice> = match self.display_mode {
-            DisplayMode::Terminal => {
-                is_interactive = true;
-                Box::new(TerminalDevice::new(self.every, self.theme)?)
-            }
-            DisplayMode::Text => Box::new(TextDevice::new()),
-            _ => Box::new(NullDevice::new()),
+        let (device, is_interactive): (Box<dyn DisplayDevice>, bool) = match self.display_mode {
+            #[cfg(feature = "tui")]
+            DisplayMode::Terminal => (
+                Box::new(
+                    TerminalDevice::new(
+                        self.every,
+                        self.theme,
+                        self.permissions,
+                        self.describe,
+                        self.searches.clone(),
+                        self.fit.clone(),
+                        match self.replay_input {
+                            Some(ref path) => crate::console::read_recorded_events(path)
+                                .map_err(|err| anyhow::anyhow!("{}: {err}", path.display()))?,
+                            None => Vec::new(),
+                        },
+                    )?
+                    .with_input_recording(self.record_input)
+                    .with_signals(self.title, self.bell)
+                    .with_cpu_basis(self.cpu_basis)
+                    .with_history(self.history)
+                    .with_max_fps(self.max_fps),
+                ),
+                true,
+            ),
+            DisplayMode::Text => (
+                Box::new(TextDevice::new(self.layout, self.timestamps)),
+                false,
+            ),
+            _ => (Box::new(NullDevice::new()), false),
         };
         if target_ids.is_empty() && !is_interactive {
             Err(anyhow::anyhow!(Error::NoTargets))
@@ -144,8 +400,24 @@ impl<'s> Application<'s> {
         }
     }
 
+    /// Apply the root, the owner, the maximum depth and the filter to a manager of the process tree.
+    fn configure_manager(
+        &self,
+        tmgt: &mut dyn ProcessManager,
+        root_pid: Option<pid_t>,
+        owner: Option<u32>,
+        filter: ProcessFilter,
+    ) {
+        if let Some(context) = tmgt.context() {
+            context.set_root_pid(root_pid);
+            context.set_owner(owner);
+            context.set_max_depth(self.max_depth);
+            context.set_filter(filter);
+        }
+    }
+
     /// Get process details.
-    fn get_details(&self, pid: pid_t, sysconf: &'_ SystemConf) -> Option<ProcessDetails> {
+    fn get_details(&self, pid: pid_t, sysconf: &'_ SystemConf) -> Option<ProcessDetails<'_>> {
         match ProcessDetails::new(pid, self.human) {
             Ok(mut details) => details.refresh(sysconf).ok().map(|_| details),
             Err(_) => {
@@ -157,19 +429,178 @@ impl<'s> Application<'s> {
 
     /// Get parent process details.
     fn get_parent_details<'a>(
-        details: Option<ProcessDetails<'a>>,
+        details: &ProcessDetails<'a>,
         sysconf: &'_ SystemConf,
     ) -> Option<ProcessDetails<'a>> {
-        details.and_then(|details| match details.parent() {
+        match details.parent() {
             Ok(mut details) => details.refresh(sysconf).ok().map(|_| details),
             Err(_) => {
                 log::error!(
                     "{}: details of parent cannot be selected",
                     details.process().pid()
                 );
-                Some(details)
+                None
             }
-        })
+        }
+    }
+
+    /// Flush the exports and mark the session as truncated before oprs is killed.
+    ///
+    /// Return true if the marker has been written.
+    fn on_memory_pressure(
+        &self,
+        usage: MemoryUsage,
+        device: &mut dyn DisplayDevice,
+        exporters: &mut [Box<dyn Exporter>],
+    ) -> anyhow::Result<bool> {
+        let msg = format!(
+            "memory close to the limit: {} of {}",
+            format::size(usage.current),
+            format::size(usage.limit)
+        );
+        log::warn!("{msg}");
+        device.notify(&msg);
+        for exporter in exporters.iter_mut() {
+            exporter.flush()?;
+        }
+        let has_files = self.export_settings.kind.has_files();
+        if has_files {
+            mark_truncated(&self.export_settings.dir, &msg)?;
+        }
+        Ok(has_files)
+    }
+
+    /// Refresh the processes in tabs and forget the ones that have exited.
+    fn refresh_tabs(
+        tabs: &mut Tabs<ProcessDetails>,
+        navigator: &mut PaneNavigator,
+        sysconf: &SystemConf,
+    ) {
+        tabs.retain_mut(|details| details.refresh(sysconf).is_ok());
+        if tabs.is_empty() {
+            navigator.close_processes();
+        }
+    }
+
+    /// Notify the changes of state of the processes.
+    ///
+    /// Only the first event is displayed, all of them are logged.
+    fn notify_events(
+        &self,
+        events: &[StateEvent],
+        device: &mut dyn DisplayDevice,
+        exporters: &mut [Box<dyn Exporter>],
+        time: &SampleTime,
+    ) -> anyhow::Result<()> {
+        for event in events {
+            log::warn!("{event}");
+            if self.notifications.export {
+                for exporter in exporters.iter_mut() {
+                    exporter.event(event, time)?;
+                }
+            }
+        }
+        match events {
+            [] => (),
+            [event] => device.alert(&event.to_string()),
+            [event, others @ ..] => device.alert(&format!("{event} (+{} more)", others.len())),
+        }
+        Ok(())
+    }
+
+    /// Report the processes that started and exited since the previous sample.
+    ///
+    /// They are counted by name in the notification, each one is logged.
+    fn notify_short_lived(
+        &self,
+        events: &[StateEvent],
+        device: &mut dyn DisplayDevice,
+        exporters: &mut [Box<dyn Exporter>],
+        time: &SampleTime,
+    ) -> anyhow::Result<()> {
+        for event in events {
+            log::debug!("{event}");
+            if self.notifications.export {
+                for exporter in exporters.iter_mut() {
+                    exporter.event(event, time)?;
+                }
+            }
+        }
+        if !events.is_empty() {
+            let counts = count_by_name(events)
+                .iter()
+                .map(|(name, count)| format!("{name} ({count})"))
+                .join(", ");
+            let msg = format!("short-lived processes: {counts}");
+            info!("{msg}");
+            device.notify(&msg);
+        }
+        Ok(())
+    }
+
+    /// Internal state for a debug dump.
+    fn dump_state(
+        target_ids: &[TargetId],
+        collector: &Collector,
+        navigator: &PaneNavigator,
+        tabs: &Tabs<ProcessDetails>,
+        timer: &Timer,
+        device: &dyn DisplayDevice,
+    ) -> Json {
+        let processes = collector
+            .lines()
+            .map(|pstat| {
+                Json::Object(vec![
+                    ("pid", Json::from(pstat.pid())),
+                    ("parent", Json::from(pstat.parent_pid())),
+                    ("name", Json::from(pstat.name())),
+                    ("state", Json::from(pstat.state().to_string())),
+                    (
+                        "values",
+                        Json::array(
+                            pstat
+                                .samples()
+                                .flat_map(|sample| sample.strings())
+                                .map(String::as_str),
+                        ),
+                    ),
+                ])
+            })
+            .collect();
+        Json::Object(vec![
+            ("version", Json::from(env!("CARGO_PKG_VERSION"))),
+            ("delay_ms", Json::from(timer.get_delay().as_millis() as u64)),
+            ("targets", Json::array(target_ids.iter().map(Json::debug))),
+            (
+                "metrics",
+                Json::array(collector.metrics().map(|m| m.name())),
+            ),
+            ("navigator", Json::debug(navigator)),
+            (
+                "tabs",
+                Json::array(tabs.iter().map(|details| details.process().pid())),
+            ),
+            ("processes", Json::Array(processes)),
+            ("device", device.dump_state()),
+        ])
+    }
+
+    /// Read the configuration again and apply the new delay.
+    fn reload(
+        reloader: &Reloader,
+        timer: &mut Timer,
+        exporters: &mut [Box<dyn Exporter>],
+    ) -> anyhow::Result<()> {
+        let settings = reloader()?;
+        let every = settings.display.every;
+        if every != timer.get_delay() {
+            info!("delay changed to {}", format_duration(every));
+            timer.set_delay(every);
+        }
+        for exporter in exporters.iter_mut() {
+            exporter.flush()?;
+        }
+        Ok(())
     }
 
     fn run_loop(
@@ -181,69 +612,289 @@ impl<'s> Application<'s> {
         is_interactive: bool,
     ) -> anyhow::Result<()> {
         let mut collector = Collector::new(Cow::Borrowed(&self.metrics));
+        collector.set_selection(self.metric_selection.clone());
+        collector.set_cpu_basis(self.cpu_basis);
+        collector.set_with_units(target_ids.iter().any(|id| matches!(id, TargetId::Unit(_))));
+        collector.set_with_wchan(self.wchan);
+        collector.set_with_file_usage(target_ids.iter().any(|id| matches!(id, TargetId::File(_))));
+        collector.set_fold_exited(self.fold_exited);
+        collector
+            .set_with_credentials(self.notifications.enabled && self.notifications.credentials);
         let mut tmgt: Box<dyn ProcessManager> = if target_ids.is_empty() {
-            Box::new(ForestProcessManager::new(sysconf)?)
+            Box::new(ForestProcessManager::new(sysconf, &self.exclusion)?)
         } else {
             Box::new(FlatProcessManager::new(sysconf, &self.metrics, target_ids)?)
         };
-        tmgt.context().map(|c| c.set_root_pid(root_pid));
-        let mut details: Option<ProcessDetails> = None;
-        let mut pane_kind = PaneKind::Main;
+        let mut filter = ProcessFilter::default();
+        let mut tabs: Tabs<ProcessDetails> = Tabs::new(MAX_DETAILS_TABS);
+        let mut navigator = PaneNavigator::new();
+        // PID of the process compared to the current one.
+        let mut compared: Option<pid_t> = None;
 
         device.open(self.metrics.iter())?;
-        let mut exporter: Option<Box<dyn Exporter>> = match self.export_settings.kind {
+        let mut exporters: Vec<Box<dyn Exporter>> = match self.export_settings.kind {
             ExportType::Csv | ExportType::Tsv => {
-                Some(Box::new(CsvExporter::new(self.export_settings)?))
+                vec![Box::new(CsvExporter::new(self.export_settings)?)]
             }
-            ExportType::Rrd | ExportType::RrdGraph => Some(Box::new(RrdExporter::new(
+            #[cfg(feature = "rrd")]
+            ExportType::Rrd | ExportType::RrdGraph => vec![Box::new(RrdExporter::new(
                 self.export_settings,
                 self.every,
-            )?)),
-            ExportType::None => None,
+            )?)],
+            #[cfg(not(feature = "rrd"))]
+            ExportType::Rrd | ExportType::RrdGraph => {
+                return Err(anyhow::anyhow!(Error::RrdNotAvailable))
+            }
+            ExportType::Exec => vec![Box::new(ExecExporter::new(self.export_settings)?)],
+            ExportType::Statsd | ExportType::Dogstatsd => vec![Box::new(StatsdExporter::new(
+                self.export_settings,
+                self.export_settings.kind == ExportType::Dogstatsd,
+            )?)],
+            ExportType::None => Vec::new(),
         };
+        if let Some(ref share) = self.share {
+            exporters.push(Box::new(Broadcaster::new(share)?));
+        }
+        if let Some(ref capture) = self.capture {
+            exporters.push(Box::new(ProcfsCapture::new(capture)?));
+        }
 
-        if let Some(ref mut exporter) = exporter {
+        for exporter in exporters.iter_mut() {
             exporter.open(self.metrics.iter())?;
         }
 
-        let sighdr = SignalHandler::new()?;
+        // The subscription to the process events requires the privileges.
+        let mut short_lived = if self.notifications.short_lived {
+            match ShortLivedMonitor::new() {
+                Ok(monitor) => Some(monitor),
+                Err(err) => {
+                    let msg = format!("cannot count short-lived processes: {err}");
+                    log::warn!("{msg}");
+                    device.notify(&msg);
+                    None
+                }
+            }
+        } else {
+            None
+        };
+        if let Some(ref user) = self.user {
+            drop_privileges(user)?;
+        }
+        let mut owner = None;
+        if let Some(mount) = ProcMount::current() {
+            if let Some(uid) = mount.restricted_uid() {
+                let limitations = mount.limitations();
+                log::warn!("{limitations}");
+                device.notify(&limitations);
+                owner = Some(uid);
+            }
+        }
+        self.configure_manager(tmgt.as_mut(), root_pid, owner, filter);
+
+        let sighdr = SignalHandler::new(self.reloader.is_some())?;
         let mut loop_number: u64 = 0;
-        let mut timer = Timer::new(self.every, true);
+        let mut timer = if self.align {
+            Timer::with_alignment(self.every)
+        } else {
+            Timer::new(self.every, true)
+        };
+        // The process panes are refreshed with the samples if there is no specific timer.
+        let mut details_timer = self
+            .details_every
+            .filter(|_| is_interactive)
+            .map(|every| Timer::new(every, true));
+        // Whether the process panes must be drawn again.
+        let mut details_changed = true;
+        let mut clock = SampleClock::new();
         let mut drift = DriftMonitor::new(timer.start_time(), DRIFT_NOTIFICATION_DELAY);
+        let mut overload = OverloadMonitor::new(self.adaptive, OVERLOAD_NOTIFICATION_DELAY);
+        // The CPU of oprs is limited when it monitors itself with other targets.
+        let myself = std::process::id() as pid_t;
+        let with_myself = target_ids.len() > 1
+            && target_ids
+                .iter()
+                .any(|id| matches!(id, TargetId::Pid(pid, _) if *pid == myself));
+        let mut overhead = OverheadMonitor::new(
+            self.myself_cpu_limit.filter(|_| with_myself),
+            self.adaptive,
+            OVERLOAD_NOTIFICATION_DELAY,
+        );
+        // The exports are flushed if oprs may be killed for exceeding its memory limit.
+        let mut memory_watch = if exporters.is_empty() {
+            None
+        } else {
+            MemoryWatch::new(myself)
+        };
+        // Whether this session has written a marker.
+        let mut truncated = false;
+        let mut state_monitor = self.notifications.enabled.then(|| {
+            StateMonitor::new(self.notifications.uninterruptible)
+                .with_renames(self.notifications.renames)
+                .with_credentials(self.notifications.credentials)
+        });
+        // Delay in the foreground while the process is in the background.
+        let mut foreground_delay = None;
 
         while !sighdr.caught() {
+            if let Some(ref reloader) = self.reloader {
+                if sighdr.hangup() {
+                    info!("reloading configuration");
+                    if let Err(err) = Application::reload(reloader, &mut timer, &mut exporters) {
+                        log::error!("cannot reload configuration: {err}");
+                    }
+                }
+            }
+            let continued = sighdr.continued();
+            if continued {
+                device.resume()?;
+            }
+            if let Some(background) = self.background {
+                match (is_foreground(), foreground_delay) {
+                    (false, None) => {
+                        let delay = timer.get_delay();
+                        info!(
+                            "in background, delay set to {:.1}s",
+                            background.as_secs_f64()
+                        );
+                        timer.set_delay(background.max(delay));
+                        foreground_delay = Some(delay);
+                    }
+                    (true, Some(delay)) => {
+                        info!("in foreground, delay set to {:.1}s", delay.as_secs_f64());
+                        timer.set_delay(delay);
+                        foreground_delay = None;
+                    }
+                    _ => (),
+                }
+            }
+            let mut workload = None;
             let targets_updated = if timer.expired() {
-                let timestamp = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH)?;
+                let start = Instant::now();
+                let time = clock.now(timer.is_aligned().then(|| timer.get_delay()));
                 let targets_updated = tmgt.refresh(&mut collector)?;
-                if match &mut details {
-                    Some(details) => details.refresh(sysconf).is_err(),
-                    None => false,
-                } {
-                    details = None;
-                    pane_kind = PaneKind::Main;
+                if details_timer.is_none() {
+                    Application::refresh_tabs(&mut tabs, &mut navigator, sysconf);
+                    details_changed = true;
+                }
+                let collect = start.elapsed();
+                device.record(&collector);
+                let start = Instant::now();
+                for exporter in exporters.iter_mut() {
+                    exporter.export(&collector, &time)?;
                 }
-                if let Some(ref mut exporter) = exporter {
-                    exporter.export(&collector, &timestamp)?;
+                if let Some(ref mut state_monitor) = state_monitor {
+                    let events = state_monitor.update(
+                        collector.lines().map(|pstat| {
+                            (
+                                pstat.pid(),
+                                pstat.name(),
+                                pstat.state(),
+                                pstat.credentials(),
+                            )
+                        }),
+                        read_oom_kills(),
+                        |pid| Path::new(&format!("/proc/{pid}")).exists(),
+                    );
+                    self.notify_events(&events, device.as_mut(), &mut exporters, &time)?;
                 }
+                if let Some(usage) = memory_watch.as_mut().and_then(MemoryWatch::update) {
+                    truncated |= self.on_memory_pressure(usage, device.as_mut(), &mut exporters)?;
+                }
+                if let Some(ref mut monitor) = short_lived {
+                    match monitor.take() {
+                        Ok(events) => self.notify_short_lived(
+                            &events,
+                            device.as_mut(),
+                            &mut exporters,
+                            &time,
+                        )?,
+                        Err(err) => {
+                            let msg = format!("cannot count short-lived processes: {err}");
+                            log::warn!("{msg}");
+                            device.notify(&msg);
+                            short_lived = None;
+                        }
+                    }
+                }
+                workload = Some(Workload {
+                    collect,
+                    export: start.elapsed(),
+                    ..Default::default()
+                });
                 timer.reset();
                 targets_updated
             } else {
                 false
             };
-            device.render(
-                pane_kind,
-                match pane_kind {
-                    PaneKind::Main => PaneData::Collector(&collector),
-                    PaneKind::Process(DataKind::Details) => {
-                        PaneData::Details(details.as_ref().unwrap())
+            if let Some(ref mut details_timer) = details_timer {
+                if details_timer.expired() {
+                    Application::refresh_tabs(&mut tabs, &mut navigator, sysconf);
+                    details_timer.reset();
+                    details_changed = true;
+                }
+            }
+            let start = Instant::now();
+            let pane_kind = navigator.current();
+            // The process panes are only drawn again if they may have changed.
+            let skip_render = matches!(pane_kind, PaneKind::Process(_))
+                && !(details_changed || targets_updated || continued);
+            details_changed = false;
+            if foreground_delay.is_none() && !skip_render {
+                device.render(
+                    pane_kind,
+                    match pane_kind {
+                        PaneKind::Main => PaneData::Collector(&collector),
+                        PaneKind::Process(DataKind::Details | DataKind::Threads) => {
+                            PaneData::Details(&tabs)
+                        }
+                        PaneKind::Process(DataKind::EnvironmentDiff) => {
+                            let current = tabs.current().unwrap();
+                            match compared.and_then(|pid| {
+                                tabs.iter().find(|details| details.process().pid() == pid)
+                            }) {
+                                Some(other) => PaneData::Pair(current, other),
+                                None => PaneData::Process(current),
+                            }
+                        }
+                        PaneKind::Process(_) => PaneData::Process(tabs.current().unwrap()),
+                        PaneKind::Help => PaneData::None,
+                    },
+                    targets_updated || continued,
+                )?;
+            }
+            if sighdr.dump() {
+                let state = Application::dump_state(
+                    target_ids,
+                    &collector,
+                    &navigator,
+                    &tabs,
+                    &timer,
+                    device.as_ref(),
+                );
+                match write_dump(&self.export_settings.dir, &state) {
+                    Ok(path) => {
+                        info!("internal state dumped in {}", path.display());
+                        device.notify(&format!("State dumped in {}", path.display()));
                     }
-                    PaneKind::Process(_) => {
-                        PaneData::Process(details.as_ref().unwrap().process().process())
+                    Err(err) => log::error!("cannot dump internal state: {err}"),
+                }
+            }
+            if let Some(mut workload) = workload {
+                workload.render = start.elapsed();
+                device.set_workload(&workload);
+                if let Some(delay) = overload.update(&workload, timer.get_delay()) {
+                    timer.set_delay(delay);
+                }
+                match overhead.update(timer.get_delay()) {
+                    Some(Guardrail::Warn(msg)) => device.notify(&msg),
+                    Some(Guardrail::Lengthen(msg, delay)) => {
+                        device.notify(&msg);
+                        timer.set_delay(delay);
                     }
-                    PaneKind::Help => PaneData::None,
-                },
-                targets_updated,
-            )?;
+                    None => (),
+                }
+                device.set_overhead(overhead.overhead());
+            }
 
             if let Some(count) = self.count {
                 loop_number += 1;
@@ -252,39 +903,99 @@ impl<'s> Application<'s> {
                 }
             }
             if is_interactive {
+                device.set_wakeup(
+                    details_timer
+                        .as_mut()
+                        .filter(|_| matches!(navigator.current(), PaneKind::Process(_)))
+                        .and_then(Timer::remaining),
+                );
                 if let PauseStatus::Action(action) = device.pause(&mut timer)? {
+                    details_changed = true;
                     match action {
                         Interaction::Quit => break,
-                        Interaction::Filter(filter) => {
+                        Interaction::Filter(new_filter) => {
+                            filter = new_filter;
                             tmgt.context().map(|c| c.set_filter(filter));
                             tmgt.refresh(&mut collector)?;
                         }
-                        Interaction::SwitchBack => match (pane_kind, &details) {
-                            (PaneKind::Process(DataKind::Details), Some(_)) => {
-                                details = None;
-                                pane_kind = PaneKind::Main;
+                        Interaction::SwitchBack => {
+                            navigator.navigate(PaneEvent::Back, !tabs.is_empty());
+                        }
+                        Interaction::SwitchToHelp => {
+                            navigator.navigate(PaneEvent::Help, !tabs.is_empty());
+                        }
+                        Interaction::SwitchTo(kind) => {
+                            navigator.navigate(PaneEvent::Data(kind), !tabs.is_empty());
+                        }
+                        Interaction::SelectPid(pid)
+                            if !collector.lines().any(|pstat| pstat.pid() == pid) =>
+                        {
+                            device.notify(&format!("PID {pid} is not monitored"));
+                        }
+                        Interaction::SelectPid(pid) => {
+                            if !tabs.select(|details| details.process().pid() == pid) {
+                                if let Some(details) = self.get_details(pid, sysconf) {
+                                    tabs.open(details);
+                                }
                             }
-                            (PaneKind::Help | PaneKind::Process(_), Some(_)) => {
-                                pane_kind = PaneKind::Process(DataKind::Details)
+                            navigator.navigate(PaneEvent::Open, !tabs.is_empty());
+                        }
+                        Interaction::CompareEnvironments(first, second) => {
+                            // The first process is opened last to be the current tab.
+                            let opened = [second, first].iter().all(|pid| {
+                                tabs.select(|details| details.process().pid() == *pid)
+                                    || match self.get_details(*pid, sysconf) {
+                                        Some(details) => {
+                                            tabs.open(details);
+                                            true
+                                        }
+                                        None => false,
+                                    }
+                            });
+                            if opened {
+                                compared = Some(second);
+                                navigator.navigate(PaneEvent::Open, true);
+                                navigator
+                                    .navigate(PaneEvent::Data(DataKind::EnvironmentDiff), true);
+                            } else {
+                                device.notify("Cannot compare processes that are gone");
                             }
-                            (_, _) => pane_kind = PaneKind::Main,
-                        },
-                        Interaction::SwitchToHelp => pane_kind = PaneKind::Help,
-                        Interaction::SwitchTo(kind) => {
-                            if matches!(pane_kind, PaneKind::Process(_)) {
-                                pane_kind = PaneKind::Process(kind);
+                        }
+                        Interaction::NextTab => {
+                            if navigator.current() == PaneKind::Process(DataKind::Details) {
+                                tabs.next();
                             }
+                            navigator.navigate(PaneEvent::NextTab, !tabs.is_empty());
                         }
-                        Interaction::SelectPid(pid) => {
-                            details = self.get_details(pid, sysconf);
-                            if details.is_some() {
-                                pane_kind = PaneKind::Process(DataKind::Details);
+                        Interaction::CloseTab => {
+                            tabs.close();
+                            if tabs.is_empty() {
+                                navigator.close_processes();
+                            }
+                        }
+                        Interaction::Dump => {
+                            if let Some(details) = tabs.current() {
+                                let pid = details.process().pid();
+                                match write_bundle(&self.export_settings.dir, pid, details.name()) {
+                                    Ok(path) => {
+                                        info!("{pid}: snapshot saved in {}", path.display());
+                                        device.notify(&format!("Saved in {}", path.display()));
+                                    }
+                                    Err(err) => {
+                                        log::error!("{pid}: cannot save snapshot: {err}");
+                                        device.notify(&format!("Cannot save snapshot: {err}"));
+                                    }
+                                }
                             }
                         }
                         Interaction::SelectParent => {
-                            details = Application::get_parent_details(details, sysconf);
-                            if details.is_some() {
-                                pane_kind = PaneKind::Process(DataKind::Details);
+                            if let Some(parent) = tabs.current().and_then(|details| {
+                                Application::get_parent_details(details, sysconf)
+                            }) {
+                                let parent_pid = parent.process().pid();
+                                if !tabs.select(|details| details.process().pid() == parent_pid) {
+                                    tabs.replace(parent);
+                                }
                             }
                         }
                         Interaction::SelectRootPid(new_root_pid) => {
@@ -299,20 +1010,35 @@ impl<'s> Application<'s> {
                         }
                         Interaction::Wide => {
                             log::debug!("switch to explorer mode");
-                            tmgt = Box::new(ForestProcessManager::new(sysconf)?);
-                            tmgt.context().map(|c| c.set_root_pid(root_pid));
+                            tmgt = Box::new(ForestProcessManager::new(sysconf, &self.exclusion)?);
+                            self.configure_manager(tmgt.as_mut(), root_pid, owner, filter);
+                            tmgt.refresh(&mut collector)?;
+                        }
+                        Interaction::WideAll => {
+                            log::debug!("switch to explorer mode with all processes");
+                            root_pid = None;
+                            filter = ProcessFilter::None;
+                            tmgt = Box::new(ForestProcessManager::new(sysconf, &self.exclusion)?);
+                            self.configure_manager(tmgt.as_mut(), root_pid, owner, filter);
                             tmgt.refresh(&mut collector)?;
                         }
                         Interaction::None => (),
                     }
                 }
             } else {
-                let mut remaining = Some(self.every);
+                let mut remaining = if timer.is_aligned() {
+                    timer.remaining()
+                } else {
+                    Some(timer.get_delay())
+                };
                 while let Some(delay) = remaining {
                     remaining = timer.sleep(delay);
                     std::io::stdout().flush()?; // hack: signal not caught otherwise
-                    if sighdr.caught() {
-                        info!("signal caught, exiting.");
+                    if sighdr.caught()
+                        || sighdr.hangup_pending()
+                        || sighdr.continued_pending()
+                        || sighdr.dump_pending()
+                    {
                         break;
                     }
                 }
@@ -320,11 +1046,30 @@ impl<'s> Application<'s> {
             drift.update(timer.get_delay());
         }
 
-        device.close()?;
-        if let Some(ref mut exporter) = exporter {
-            exporter.close()?;
+        // All the steps are run even if one fails so that the exports are complete
+        // and the terminal is restored.
+        let mut results = Vec::new();
+        if sighdr.caught() {
+            info!("signal caught, exiting.");
+            if !exporters.is_empty() {
+                // Final sample so that the last interval is not lost.
+                let time = clock.now(None);
+                match tmgt.refresh(&mut collector) {
+                    Ok(_) => results.extend(
+                        exporters
+                            .iter_mut()
+                            .map(|exporter| exporter.export(&collector, &time)),
+                    ),
+                    Err(err) => results.push(Err(err.into())),
+                }
+            }
         }
+        results.extend(exporters.iter_mut().map(|exporter| exporter.close()));
+        if truncated {
+            results.push(clear_truncated(&self.export_settings.dir).map_err(Into::into));
+        }
+        results.push(device.close());
         info!("stopping");
-        Ok(())
+        first_error(results)
     }
 }
@@ -15,16 +15,40 @@
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
 use light_ini::{IniHandler, IniParser};
-use std::{path::PathBuf, str::FromStr};
+use std::{
+    fmt,
+    fs::OpenOptions,
+    io::{self, Write},
+    path::PathBuf,
+    str::FromStr,
+    time::Duration,
+};
 use strum_macros::{EnumString, IntoStaticStr};
 
-use crate::process::parsers::parse_size;
+use crate::clock::{format_duration, parse_duration};
+use crate::export::FileNameTemplate;
+use crate::process::{
+    derived::{check_name, parse_expression, DerivedError, DerivedMetric},
+    filesystems::{self, Filesystem, FilesystemError},
+    format::ColumnFormat,
+    parsers::{
+        parse_column_format, parse_histogram, parse_intervals, parse_metric_spec,
+        parse_saved_search, parse_size, HistogramBounds, SavedSearch,
+    },
+    MetricId,
+};
 
-pub use crate::console::BuiltinTheme;
+pub use crate::console::{AlertSignal, BuiltinTheme};
+pub(crate) use crate::process::CpuBasis;
 
-pub const DEFAULT_DELAY: f64 = 5.0;
+pub const DEFAULT_DELAY: Duration = Duration::from_secs(5);
+/// Number of samples kept by the terminal, the copies are only made on demand.
+const DEFAULT_HISTORY: usize = 0;
 pub const LOG_FILE_NAME: &str = "settings";
 
+/// Commented configuration file with the default values.
+pub const DEFAULT_SETTINGS: &str = include_str!("settings.ini");
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq, EnumString, IntoStaticStr)]
 pub enum LoggingLevel {
     #[strum(serialize = "error")]
@@ -37,6 +61,12 @@ pub enum LoggingLevel {
     Debug,
 }
 
+impl LoggingLevel {
+    pub fn as_str(self) -> &'static str {
+        self.into()
+    }
+}
+
 #[derive(Clone, Copy, Debug, EnumString, IntoStaticStr, PartialEq, Eq)]
 pub enum DisplayMode {
     #[strum(serialize = "none")]
@@ -55,6 +85,22 @@ impl DisplayMode {
     }
 }
 
+#[derive(Clone, Copy, Debug, EnumString, IntoStaticStr, PartialEq, Eq)]
+pub enum TextLayout {
+    #[strum(serialize = "table")]
+    Table,
+    #[strum(serialize = "tsv")]
+    Tsv,
+    #[strum(serialize = "csv")]
+    Csv,
+}
+
+impl TextLayout {
+    pub fn as_str(self) -> &'static str {
+        self.into()
+    }
+}
+
 #[derive(Clone, Copy, Debug, EnumString, IntoStaticStr, PartialEq, Eq)]
 pub enum ExportType {
     #[strum(serialize = "none")]
@@ -67,12 +113,26 @@ pub enum ExportType {
     Rrd,
     #[strum(serialize = "rrd-graph")]
     RrdGraph,
+    #[strum(serialize = "exec")]
+    Exec,
+    #[strum(serialize = "statsd")]
+    Statsd,
+    #[strum(serialize = "dogstatsd")]
+    Dogstatsd,
 }
 
 impl ExportType {
     pub fn as_str(self) -> &'static str {
         self.into()
     }
+
+    /// Whether the export writes files in the export directory.
+    pub fn has_files(self) -> bool {
+        !matches!(
+            self,
+            ExportType::None | ExportType::Exec | ExportType::Statsd | ExportType::Dogstatsd
+        )
+    }
 }
 
 #[derive(Clone, Copy, Debug, EnumString, IntoStaticStr, PartialEq, Eq)]
@@ -99,13 +159,44 @@ pub enum ConfigError {
     InvalidParameter(String),
     #[error("{0}: unknown export type")]
     UnknownExportType(String),
+    #[error("{0}: configuration file already exists")]
+    FileExists(String),
 }
 
 /// Parameters for display
 #[derive(Debug)]
 pub struct DisplaySettings {
     pub mode: DisplayMode,
-    pub every: f64,
+    pub layout: TextLayout,
+    pub timestamps: bool,
+    pub every: Duration,
+    /// Delay between two refreshes of the process panes if not the same as the samples.
+    pub details_every: Option<Duration>,
+    pub align: bool,
+    pub reload: bool,
+    pub adaptive: bool,
+    pub read_only: bool,
+    pub read_only_delay: bool,
+    pub describe: bool,
+    /// Whether the columns are reduced to fit in the width of the terminal.
+    pub fit: bool,
+    /// Most important metrics, kept when the columns must fit.
+    pub fit_priority: Vec<String>,
+    /// Whether the title of the terminal window shows a status.
+    pub title: bool,
+    /// Signal sent to the terminal on alerts.
+    pub bell: AlertSignal,
+    pub background: Option<Duration>,
+    /// Depth of the process tree below which the processes are folded into their ancestor.
+    pub max_depth: Option<usize>,
+    /// Whether the CPU time ratio is relative to a single CPU or to all CPUs.
+    pub cpu_basis: CpuBasis,
+    /// Number of samples kept to step through them while the terminal is paused.
+    pub history: usize,
+    /// Whether the wait channel of the processes in uninterruptible sleep is displayed.
+    pub wchan: bool,
+    /// Maximum number of frames drawn per second in the terminal (0 for no limit).
+    pub max_fps: u32,
     pub count: Option<u64>,
     pub format: MetricFormat,
     pub theme: Option<BuiltinTheme>,
@@ -115,7 +206,26 @@ impl DisplaySettings {
     fn new() -> DisplaySettings {
         DisplaySettings {
             mode: DisplayMode::Any,
+            layout: TextLayout::Table,
+            timestamps: false,
             every: DEFAULT_DELAY,
+            details_every: None,
+            align: false,
+            reload: false,
+            adaptive: false,
+            read_only: false,
+            read_only_delay: true,
+            describe: false,
+            fit: false,
+            fit_priority: Vec::new(),
+            title: false,
+            bell: AlertSignal::None,
+            background: None,
+            max_depth: None,
+            cpu_basis: CpuBasis::All,
+            history: DEFAULT_HISTORY,
+            wchan: false,
+            max_fps: 0,
             count: None,
             format: MetricFormat::Human,
             theme: None,
@@ -128,8 +238,28 @@ impl DisplaySettings {
 pub struct ExportSettings {
     pub kind: ExportType,
     pub dir: PathBuf,
+    pub template: FileNameTemplate,
     pub size: Option<u64>,
     pub count: Option<usize>,
+    pub histograms: Vec<HistogramBounds>,
+    pub max_age: Option<Duration>,
+    pub max_total_size: Option<u64>,
+    /// Intervals of the averaged series written along the samples.
+    pub downsample: Vec<Duration>,
+    /// Shell command reading the samples in JSON on its input.
+    pub command: Option<String>,
+    /// Address of the statsd server.
+    pub address: Option<String>,
+    /// Prefix of the statsd gauges.
+    pub prefix: Option<String>,
+    /// Minimum delay between two samples sent to the statsd server.
+    pub send_every: Option<Duration>,
+    /// Delay between two flushes of the CSV files.
+    pub flush: Option<Duration>,
+    /// Number of rows written before the CSV files are flushed.
+    pub flush_rows: Option<usize>,
+    /// Export the durations in seconds and the ratios as fractions.
+    pub base_units: bool,
 }
 
 impl ExportSettings {
@@ -137,8 +267,20 @@ impl ExportSettings {
         ExportSettings {
             kind: ExportType::None,
             dir: PathBuf::from("."),
+            template: FileNameTemplate::default(),
             size: None,
             count: None,
+            histograms: Vec::new(),
+            max_age: None,
+            max_total_size: None,
+            downsample: Vec::new(),
+            command: None,
+            address: None,
+            prefix: None,
+            send_every: None,
+            flush: None,
+            flush_rows: None,
+            base_units: false,
         }
     }
 }
@@ -161,37 +303,467 @@ impl LoggingSettings {
 /// Parameters for special targets
 pub struct TargetSettings {
     pub system: bool,
+    pub system_breakdown: bool,
     pub myself: bool,
+    /// Percentage of the interval that oprs may spend on the CPU when it monitors itself.
+    pub myself_cpu_limit: u8,
+    /// Add the CPU times and faults of the exited processes to their parent.
+    pub fold_exited: bool,
 }
 
 impl TargetSettings {
     fn new() -> TargetSettings {
         TargetSettings {
             system: false,
+            system_breakdown: false,
             myself: false,
+            myself_cpu_limit: 10,
+            fold_exited: false,
+        }
+    }
+}
+
+/// Parameters for privileges
+pub struct PrivilegeSettings {
+    /// User and optional group to switch to after startup (`user[:group]`).
+    pub user: Option<String>,
+}
+
+impl PrivilegeSettings {
+    fn new() -> PrivilegeSettings {
+        PrivilegeSettings { user: None }
+    }
+}
+
+/// Parameters for the notifications of changes of state of the processes
+pub struct NotificationSettings {
+    /// Whether the changes of state are notified.
+    pub enabled: bool,
+    /// Number of intervals in uninterruptible sleep before notifying. Zero to disable.
+    pub uninterruptible: usize,
+    /// Whether the events are written in the export directory.
+    pub export: bool,
+    /// Whether the processes that start and exit between two samples are counted.
+    pub short_lived: bool,
+    /// Whether the changes of process names are notified.
+    pub renames: bool,
+    /// Whether the changes of effective user or group are notified.
+    pub credentials: bool,
+}
+
+impl NotificationSettings {
+    fn new() -> NotificationSettings {
+        NotificationSettings {
+            enabled: true,
+            uninterruptible: 3,
+            export: false,
+            short_lived: false,
+            renames: false,
+            credentials: false,
+        }
+    }
+}
+
+/// Processes that are never monitored in system-wide mode
+pub struct ExclusionSettings {
+    /// Patterns of process names.
+    pub names: Vec<String>,
+    /// Names or IDs of users.
+    pub users: Vec<String>,
+}
+
+impl ExclusionSettings {
+    fn new() -> ExclusionSettings {
+        ExclusionSettings {
+            names: Vec::new(),
+            users: Vec::new(),
         }
     }
 }
 
+/// External commands to format the values of metrics
+pub struct FormatterSettings {
+    commands: Vec<(MetricId, String)>,
+}
+
+impl FormatterSettings {
+    fn new() -> FormatterSettings {
+        FormatterSettings {
+            commands: Vec::new(),
+        }
+    }
+
+    /// Set the command of a metric, replacing the previous one.
+    pub fn set(&mut self, id: MetricId, command: &str) {
+        self.commands.retain(|(other, _)| *other != id);
+        self.commands.push((id, command.to_string()));
+    }
+
+    /// Command of a metric.
+    pub fn get(&self, id: MetricId) -> Option<&str> {
+        self.commands
+            .iter()
+            .find(|(other, _)| *other == id)
+            .map(|(_, command)| command.as_str())
+    }
+}
+
+/// Presentation of the values of the metrics
+pub struct ColumnSettings {
+    formats: Vec<(MetricId, ColumnFormat)>,
+}
+
+impl ColumnSettings {
+    fn new() -> ColumnSettings {
+        ColumnSettings {
+            formats: Vec::new(),
+        }
+    }
+
+    /// Set the presentation of a metric, replacing the previous one.
+    pub fn set(&mut self, id: MetricId, column: ColumnFormat) {
+        self.formats.retain(|(other, _)| *other != id);
+        self.formats.push((id, column));
+    }
+
+    /// Presentation of a metric.
+    pub fn get(&self, id: MetricId) -> Option<ColumnFormat> {
+        self.formats
+            .iter()
+            .find(|(other, _)| *other == id)
+            .map(|(_, column)| *column)
+    }
+}
+
+/// Metrics computed with an expression over other metrics
+pub struct DerivedSettings {
+    expressions: Vec<(String, String)>,
+}
+
+impl DerivedSettings {
+    fn new() -> DerivedSettings {
+        DerivedSettings {
+            expressions: Vec::new(),
+        }
+    }
+
+    /// Set the expression of a derived metric, replacing the previous one.
+    pub fn set(&mut self, name: &str, expression: &str) {
+        self.expressions.retain(|(other, _)| other != name);
+        self.expressions
+            .push((name.to_string(), expression.to_string()));
+    }
+
+    /// Parse the expressions.
+    pub fn metrics(&self) -> Result<Vec<DerivedMetric>, DerivedError> {
+        self.expressions
+            .iter()
+            .map(|(name, expression)| DerivedMetric::new(name, expression))
+            .collect()
+    }
+}
+
+/// Mount points whose filesystem usage can be selected as a system metric
+pub struct FilesystemSettings {
+    mount_points: Vec<(String, String)>,
+}
+
+impl FilesystemSettings {
+    fn new() -> FilesystemSettings {
+        FilesystemSettings {
+            mount_points: Vec::new(),
+        }
+    }
+
+    /// Set the mount point of a filesystem, replacing the previous one.
+    pub fn set(&mut self, name: &str, mount_point: &str) {
+        self.mount_points.retain(|(other, _)| other != name);
+        self.mount_points
+            .push((name.to_string(), mount_point.to_string()));
+    }
+
+    /// Filesystems with their metrics.
+    pub fn metrics(&self) -> Result<Vec<Filesystem>, FilesystemError> {
+        self.mount_points
+            .iter()
+            .map(|(name, mount_point)| Filesystem::new(name, mount_point))
+            .collect()
+    }
+}
+
+/// Metrics collected for some targets instead of the metrics of the command line
+pub struct TargetMetricSettings {
+    metrics: Vec<(String, Vec<String>)>,
+}
+
+impl TargetMetricSettings {
+    fn new() -> TargetMetricSettings {
+        TargetMetricSettings {
+            metrics: Vec::new(),
+        }
+    }
+
+    /// Set the metrics of a target, replacing the previous ones.
+    pub fn set(&mut self, name: &str, metrics: Vec<String>) {
+        self.metrics.retain(|(other, _)| other != name);
+        self.metrics.push((name.to_string(), metrics));
+    }
+
+    /// Target names with their metrics.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &[String])> {
+        self.metrics
+            .iter()
+            .map(|(name, metrics)| (name.as_str(), metrics.as_slice()))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.metrics.is_empty()
+    }
+}
+
+/// Searches applied with the keys 1 to 9 in the terminal
+#[derive(Clone)]
+pub struct SearchSettings {
+    saved: Vec<(char, SavedSearch)>,
+}
+
+impl SearchSettings {
+    fn new() -> SearchSettings {
+        SearchSettings { saved: Vec::new() }
+    }
+
+    /// Set the search of a key, replacing the previous one.
+    pub fn set(&mut self, key: char, search: SavedSearch) {
+        self.saved.retain(|(other, _)| *other != key);
+        self.saved.push((key, search));
+    }
+
+    /// Search of a key.
+    #[cfg(any(feature = "tui", test))]
+    pub fn get(&self, key: char) -> Option<&SavedSearch> {
+        self.saved
+            .iter()
+            .find(|(other, _)| *other == key)
+            .map(|(_, search)| search)
+    }
+
+    #[cfg(feature = "tui")]
+    pub fn is_empty(&self) -> bool {
+        self.saved.is_empty()
+    }
+}
+
 /// Parameters for the application
 pub struct Settings {
     pub display: DisplaySettings,
     pub export: ExportSettings,
     pub logging: LoggingSettings,
     pub targets: TargetSettings,
+    pub privileges: PrivilegeSettings,
+    pub notifications: NotificationSettings,
+    pub exclusion: ExclusionSettings,
+    pub formatters: FormatterSettings,
+    pub columns: ColumnSettings,
+    pub derived: DerivedSettings,
+    pub filesystems: FilesystemSettings,
+    pub target_metrics: TargetMetricSettings,
+    pub searches: SearchSettings,
 }
 
 impl Settings {
-    fn new() -> Settings {
+    pub fn new() -> Settings {
         Settings {
             display: DisplaySettings::new(),
             export: ExportSettings::new(),
             logging: LoggingSettings::new(),
             targets: TargetSettings::new(),
+            privileges: PrivilegeSettings::new(),
+            notifications: NotificationSettings::new(),
+            exclusion: ExclusionSettings::new(),
+            formatters: FormatterSettings::new(),
+            columns: ColumnSettings::new(),
+            derived: DerivedSettings::new(),
+            filesystems: FilesystemSettings::new(),
+            target_metrics: TargetMetricSettings::new(),
+            searches: SearchSettings::new(),
         }
     }
 }
 
+/// Value of a boolean parameter.
+fn yes_or_no(value: bool) -> &'static str {
+    if value {
+        "yes"
+    } else {
+        "no"
+    }
+}
+
+/// Write the settings in the format of the configuration file.
+impl fmt::Display for Settings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let display = &self.display;
+        writeln!(f, "[display]")?;
+        writeln!(f, "mode = {}", display.mode.as_str())?;
+        writeln!(f, "layout = {}", display.layout.as_str())?;
+        writeln!(f, "timestamps = {}", yes_or_no(display.timestamps))?;
+        writeln!(f, "every = {}", format_duration(display.every))?;
+        if let Some(details_every) = display.details_every {
+            writeln!(f, "details_every = {}", format_duration(details_every))?;
+        }
+        writeln!(f, "align = {}", yes_or_no(display.align))?;
+        writeln!(f, "reload = {}", yes_or_no(display.reload))?;
+        writeln!(f, "adaptive = {}", yes_or_no(display.adaptive))?;
+        writeln!(f, "read_only = {}", yes_or_no(display.read_only))?;
+        writeln!(
+            f,
+            "read_only_delay = {}",
+            yes_or_no(display.read_only_delay)
+        )?;
+        writeln!(f, "describe = {}", yes_or_no(display.describe))?;
+        writeln!(f, "fit = {}", yes_or_no(display.fit))?;
+        if !display.fit_priority.is_empty() {
+            writeln!(f, "fit_priority = {}", display.fit_priority.join(", "))?;
+        }
+        writeln!(f, "title = {}", yes_or_no(display.title))?;
+        writeln!(f, "bell = {}", display.bell.as_str())?;
+        if let Some(background) = display.background {
+            writeln!(f, "background = {}", format_duration(background))?;
+        }
+        if let Some(max_depth) = display.max_depth {
+            writeln!(f, "max_depth = {max_depth}")?;
+        }
+        writeln!(f, "cpu_basis = {}", display.cpu_basis.as_str())?;
+        writeln!(f, "history = {}", display.history)?;
+        writeln!(f, "max_fps = {}", display.max_fps)?;
+        writeln!(f, "wchan = {}", yes_or_no(display.wchan))?;
+        writeln!(f, "format = {}", display.format.as_str())?;
+        if let Some(theme) = display.theme {
+            writeln!(f, "theme = {}", theme.as_str())?;
+        }
+
+        let export = &self.export;
+        writeln!(f, "\n[export]")?;
+        writeln!(f, "kind = {}", export.kind.as_str())?;
+        writeln!(f, "dir = {}", export.dir.display())?;
+        writeln!(f, "template = {}", export.template)?;
+        if let Some(size) = export.size {
+            writeln!(f, "size = {size}")?;
+        }
+        if let Some(count) = export.count {
+            writeln!(f, "count = {count}")?;
+        }
+        for histogram in &export.histograms {
+            writeln!(f, "histogram = {histogram}")?;
+        }
+        if let Some(max_age) = export.max_age {
+            writeln!(f, "max_age = {}", max_age.as_secs())?;
+        }
+        if let Some(max_total_size) = export.max_total_size {
+            writeln!(f, "max_total_size = {max_total_size}")?;
+        }
+        if !export.downsample.is_empty() {
+            let intervals = export
+                .downsample
+                .iter()
+                .map(|interval| interval.as_secs().to_string())
+                .collect::<Vec<String>>();
+            writeln!(f, "downsample = {}", intervals.join(","))?;
+        }
+        if let Some(ref command) = export.command {
+            writeln!(f, "command = {command}")?;
+        }
+        if let Some(ref address) = export.address {
+            writeln!(f, "address = {address}")?;
+        }
+        if let Some(ref prefix) = export.prefix {
+            writeln!(f, "prefix = {prefix}")?;
+        }
+        if let Some(send_every) = export.send_every {
+            writeln!(f, "send_every = {}", format_duration(send_every))?;
+        }
+        if let Some(flush) = export.flush {
+            writeln!(f, "flush = {}", format_duration(flush))?;
+        }
+        if let Some(flush_rows) = export.flush_rows {
+            writeln!(f, "flush_rows = {flush_rows}")?;
+        }
+        writeln!(f, "base_units = {}", yes_or_no(export.base_units))?;
+
+        let logging = &self.logging;
+        writeln!(f, "\n[logging]")?;
+        if let Some(ref file) = logging.file {
+            writeln!(f, "file = {}", file.display())?;
+        }
+        writeln!(f, "level = {}", logging.level.as_str())?;
+
+        let targets = &self.targets;
+        writeln!(f, "\n[targets]")?;
+        writeln!(f, "system = {}", yes_or_no(targets.system))?;
+        writeln!(
+            f,
+            "system_breakdown = {}",
+            yes_or_no(targets.system_breakdown)
+        )?;
+        writeln!(f, "myself = {}", yes_or_no(targets.myself))?;
+        writeln!(f, "myself_cpu_limit = {}", targets.myself_cpu_limit)?;
+        writeln!(f, "fold_exited = {}", yes_or_no(targets.fold_exited))?;
+
+        writeln!(f, "\n[privileges]")?;
+        if let Some(ref user) = self.privileges.user {
+            writeln!(f, "user = {user}")?;
+        }
+
+        let notifications = &self.notifications;
+        writeln!(f, "\n[notifications]")?;
+        writeln!(f, "enabled = {}", yes_or_no(notifications.enabled))?;
+        writeln!(f, "uninterruptible = {}", notifications.uninterruptible)?;
+        writeln!(f, "export = {}", yes_or_no(notifications.export))?;
+        writeln!(f, "short_lived = {}", yes_or_no(notifications.short_lived))?;
+        writeln!(f, "renames = {}", yes_or_no(notifications.renames))?;
+        writeln!(f, "credentials = {}", yes_or_no(notifications.credentials))?;
+
+        writeln!(f, "\n[exclude]")?;
+        for name in &self.exclusion.names {
+            writeln!(f, "name = {name}")?;
+        }
+        for user in &self.exclusion.users {
+            writeln!(f, "user = {user}")?;
+        }
+
+        writeln!(f, "\n[formatters]")?;
+        for (id, command) in &self.formatters.commands {
+            writeln!(f, "{} = {command}", id.as_str())?;
+        }
+
+        writeln!(f, "\n[columns]")?;
+        for (id, column) in &self.columns.formats {
+            writeln!(f, "{} = {column}", id.as_str())?;
+        }
+
+        writeln!(f, "\n[derived]")?;
+        for (name, expression) in &self.derived.expressions {
+            writeln!(f, "{name} = {expression}")?;
+        }
+
+        writeln!(f, "\n[filesystems]")?;
+        for (name, mount_point) in &self.filesystems.mount_points {
+            writeln!(f, "{name} = {mount_point}")?;
+        }
+
+        writeln!(f, "\n[target_metrics]")?;
+        for (name, metrics) in &self.target_metrics.metrics {
+            writeln!(f, "{name} = {}", metrics.join(", "))?;
+        }
+
+        writeln!(f, "\n[searches]")?;
+        for (key, search) in &self.searches.saved {
+            writeln!(f, "{key} = {search}")?;
+        }
+        Ok(())
+    }
+}
+
 #[derive(Clone, Copy, Debug, EnumString)]
 enum ConfigSection {
     #[strum(serialize = "display")]
@@ -202,6 +774,24 @@ enum ConfigSection {
     Logging,
     #[strum(serialize = "targets")]
     Targets,
+    #[strum(serialize = "privileges")]
+    Privileges,
+    #[strum(serialize = "notifications")]
+    Notifications,
+    #[strum(serialize = "exclude")]
+    Exclusion,
+    #[strum(serialize = "formatters")]
+    Formatters,
+    #[strum(serialize = "columns")]
+    Columns,
+    #[strum(serialize = "derived")]
+    Derived,
+    #[strum(serialize = "filesystems")]
+    Filesystems,
+    #[strum(serialize = "target_metrics")]
+    TargetMetrics,
+    #[strum(serialize = "searches")]
+    Searches,
 }
 
 /// Configuration handler
@@ -225,6 +815,45 @@ impl<'a> ConfigHandler<'a> {
             _ => Err(ConfigError::InvalidParameter(key.to_string())),
         }
     }
+
+    /// Comma-separated list of metrics with aggregations and units.
+    fn parse_metric_specs(key: &str, value: &str) -> Result<Vec<String>, ConfigError> {
+        let specs = value
+            .split(',')
+            .map(str::trim)
+            .filter(|spec| !spec.is_empty())
+            .map(|spec| {
+                if parse_metric_spec(spec).is_ok()
+                    || check_name(spec).is_ok()
+                    || filesystems::split_metric_name(spec).is_some()
+                {
+                    Ok(spec.to_string())
+                } else {
+                    Err(ConfigError::InvalidParameter(key.to_string()))
+                }
+            })
+            .collect::<Result<Vec<String>, ConfigError>>()?;
+        if specs.is_empty() {
+            return Err(ConfigError::InvalidParameter(key.to_string()));
+        }
+        Ok(specs)
+    }
+
+    /// Comma-separated list of metric names.
+    fn parse_metric_names(key: &str, value: &str) -> Result<Vec<String>, ConfigError> {
+        value
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(|name| {
+                if MetricId::from_str(name).is_ok() || check_name(name).is_ok() {
+                    Ok(name.to_string())
+                } else {
+                    Err(ConfigError::InvalidParameter(key.to_string()))
+                }
+            })
+            .collect()
+    }
 }
 
 macro_rules! from_param {
@@ -254,7 +883,36 @@ impl IniHandler for ConfigHandler<'_> {
                 let settings = &mut self.settings.display;
                 match key {
                     "mode" => settings.mode = from_param!(DisplayMode, key, value)?,
-                    "every" => settings.every = from_param!(key, value.parse::<f64>())?,
+                    "layout" => settings.layout = from_param!(TextLayout, key, value)?,
+                    "timestamps" => settings.timestamps = ConfigHandler::parse_bool(key, value)?,
+                    "every" => settings.every = from_param!(key, parse_duration(value))?,
+                    "details_every" => {
+                        settings.details_every = Some(from_param!(key, parse_duration(value))?)
+                    }
+                    "align" => settings.align = ConfigHandler::parse_bool(key, value)?,
+                    "reload" => settings.reload = ConfigHandler::parse_bool(key, value)?,
+                    "adaptive" => settings.adaptive = ConfigHandler::parse_bool(key, value)?,
+                    "read_only" => settings.read_only = ConfigHandler::parse_bool(key, value)?,
+                    "read_only_delay" => {
+                        settings.read_only_delay = ConfigHandler::parse_bool(key, value)?
+                    }
+                    "describe" => settings.describe = ConfigHandler::parse_bool(key, value)?,
+                    "fit" => settings.fit = ConfigHandler::parse_bool(key, value)?,
+                    "title" => settings.title = ConfigHandler::parse_bool(key, value)?,
+                    "bell" => settings.bell = from_param!(AlertSignal, key, value)?,
+                    "fit_priority" => {
+                        settings.fit_priority = ConfigHandler::parse_metric_names(key, value)?
+                    }
+                    "background" => {
+                        settings.background = Some(from_param!(key, parse_duration(value))?)
+                    }
+                    "max_depth" => {
+                        settings.max_depth = Some(from_param!(key, value.parse::<usize>())?)
+                    }
+                    "cpu_basis" => settings.cpu_basis = from_param!(CpuBasis, key, value)?,
+                    "history" => settings.history = from_param!(key, value.parse::<usize>())?,
+                    "max_fps" => settings.max_fps = from_param!(key, value.parse::<u32>())?,
+                    "wchan" => settings.wchan = ConfigHandler::parse_bool(key, value)?,
                     "format" => settings.format = from_param!(MetricFormat, key, value)?,
                     "theme" => settings.theme = Some(from_param!(BuiltinTheme, key, value)?),
                     _ => return Err(ConfigError::InvalidOption(key.to_string())),
@@ -268,8 +926,28 @@ impl IniHandler for ConfigHandler<'_> {
                             .map_err(|_| ConfigError::UnknownExportType(value.to_string()))?
                     }
                     "dir" | "directory" => settings.dir = PathBuf::from(value),
+                    "template" => settings.template = from_param!(FileNameTemplate, key, value)?,
                     "size" => settings.size = Some(from_param!(key, parse_size(value))?),
                     "count" => settings.count = Some(from_param!(key, value.parse::<usize>())?),
+                    "histogram" => settings
+                        .histograms
+                        .push(from_param!(key, parse_histogram(value))?),
+                    "max_age" => settings.max_age = Some(from_param!(key, parse_duration(value))?),
+                    "max_total_size" => {
+                        settings.max_total_size = Some(from_param!(key, parse_size(value))?)
+                    }
+                    "downsample" => settings.downsample = from_param!(key, parse_intervals(value))?,
+                    "command" => settings.command = Some(value.to_string()),
+                    "address" => settings.address = Some(value.to_string()),
+                    "prefix" => settings.prefix = Some(value.to_string()),
+                    "send_every" => {
+                        settings.send_every = Some(from_param!(key, parse_duration(value))?)
+                    }
+                    "flush" => settings.flush = Some(from_param!(key, parse_duration(value))?),
+                    "flush_rows" => {
+                        settings.flush_rows = Some(from_param!(key, value.parse::<usize>())?)
+                    }
+                    "base_units" => settings.base_units = ConfigHandler::parse_bool(key, value)?,
                     _ => return Err(ConfigError::InvalidOption(key.to_string())),
                 }
             }
@@ -285,10 +963,80 @@ impl IniHandler for ConfigHandler<'_> {
                 let settings = &mut self.settings.targets;
                 match key {
                     "system" => settings.system = ConfigHandler::parse_bool(key, value)?,
+                    "system_breakdown" => {
+                        settings.system_breakdown = ConfigHandler::parse_bool(key, value)?
+                    }
                     "myself" => settings.myself = ConfigHandler::parse_bool(key, value)?,
+                    "myself_cpu_limit" => match from_param!(key, value.parse::<u8>())? {
+                        percent if percent <= 100 => settings.myself_cpu_limit = percent,
+                        _ => return Err(ConfigError::InvalidParameter(key.to_string())),
+                    },
+                    "fold_exited" => settings.fold_exited = ConfigHandler::parse_bool(key, value)?,
+                    _ => return Err(ConfigError::InvalidOption(key.to_string())),
+                }
+            }
+            Some(ConfigSection::Privileges) => {
+                let settings = &mut self.settings.privileges;
+                match key {
+                    "user" => settings.user = Some(value.to_string()),
+                    _ => return Err(ConfigError::InvalidOption(key.to_string())),
+                }
+            }
+            Some(ConfigSection::Notifications) => {
+                let settings = &mut self.settings.notifications;
+                match key {
+                    "enabled" => settings.enabled = ConfigHandler::parse_bool(key, value)?,
+                    "uninterruptible" => {
+                        settings.uninterruptible = from_param!(key, value.parse::<usize>())?
+                    }
+                    "export" => settings.export = ConfigHandler::parse_bool(key, value)?,
+                    "short_lived" => settings.short_lived = ConfigHandler::parse_bool(key, value)?,
+                    "renames" => settings.renames = ConfigHandler::parse_bool(key, value)?,
+                    "credentials" => settings.credentials = ConfigHandler::parse_bool(key, value)?,
+                    _ => return Err(ConfigError::InvalidOption(key.to_string())),
+                }
+            }
+            Some(ConfigSection::Exclusion) => {
+                let settings = &mut self.settings.exclusion;
+                match key {
+                    "name" => settings.names.push(value.to_string()),
+                    "user" => settings.users.push(value.to_string()),
                     _ => return Err(ConfigError::InvalidOption(key.to_string())),
                 }
             }
+            Some(ConfigSection::Formatters) => {
+                let id = MetricId::from_str(key)
+                    .map_err(|_| ConfigError::InvalidOption(key.to_string()))?;
+                self.settings.formatters.set(id, value);
+            }
+            Some(ConfigSection::Columns) => {
+                let id = MetricId::from_str(key)
+                    .map_err(|_| ConfigError::InvalidOption(key.to_string()))?;
+                let column = from_param!(key, parse_column_format(value))?;
+                self.settings.columns.set(id, column);
+            }
+            Some(ConfigSection::Derived) => {
+                check_name(key).map_err(|_| ConfigError::InvalidOption(key.to_string()))?;
+                from_param!(key, parse_expression(value))?;
+                self.settings.derived.set(key, value);
+            }
+            Some(ConfigSection::Filesystems) => {
+                filesystems::check_name(key)
+                    .map_err(|_| ConfigError::InvalidOption(key.to_string()))?;
+                from_param!(key, filesystems::check(key, value))?;
+                self.settings.filesystems.set(key, value);
+            }
+            Some(ConfigSection::TargetMetrics) => {
+                let metrics = ConfigHandler::parse_metric_specs(key, value)?;
+                self.settings.target_metrics.set(key, metrics);
+            }
+            Some(ConfigSection::Searches) => match key.chars().collect::<Vec<char>>()[..] {
+                [c @ '1'..='9'] => self
+                    .settings
+                    .searches
+                    .set(c, from_param!(key, parse_saved_search(value))?),
+                _ => return Err(ConfigError::InvalidOption(key.to_string())),
+            },
         }
         Ok(())
     }
@@ -312,6 +1060,34 @@ impl Directories {
         self.xdg_dirs.find_config_file(basename)
     }
 
+    /// Return the config file in use or the user config file if there is none.
+    pub fn config_file(&self, name: &str) -> PathBuf {
+        self.first_config_file(name)
+            .unwrap_or_else(|| self.xdg_dirs.get_config_home().join(format!("{name}.ini")))
+    }
+
+    /// Write the default settings in the user config file.
+    ///
+    /// An existing file is only replaced if `force` is true.
+    pub fn write_default_config_file(&self, name: &str, force: bool) -> anyhow::Result<PathBuf> {
+        let path = self.xdg_dirs.place_config_file(format!("{name}.ini"))?;
+        let mut options = OpenOptions::new();
+        options.write(true);
+        if force {
+            options.create(true).truncate(true);
+        } else {
+            options.create_new(true);
+        }
+        let mut file = options.open(&path).map_err(|err| match err.kind() {
+            io::ErrorKind::AlreadyExists => {
+                anyhow::anyhow!(ConfigError::FileExists(path.display().to_string()))
+            }
+            _ => err.into(),
+        })?;
+        file.write_all(DEFAULT_SETTINGS.as_bytes())?;
+        Ok(path)
+    }
+
     /// Read INI configuration file
     pub fn read_config_file(&self, name: &str) -> anyhow::Result<Settings> {
         let mut settings = Settings::new();
@@ -329,23 +1105,59 @@ mod tests {
 
     use std::io::{self, Seek, Write};
     use std::path::PathBuf;
+    use std::time::Duration;
 
     use super::{
-        BuiltinTheme, ConfigHandler, DisplayMode, ExportType, IniParser, LoggingLevel,
-        MetricFormat, Settings,
+        AlertSignal, BuiltinTheme, ConfigHandler, CpuBasis, DisplayMode, ExportType, IniParser,
+        LoggingLevel, MetricFormat, MetricId, Settings, TextLayout, DEFAULT_SETTINGS,
+    };
+    use crate::process::{
+        format::{ColumnAlignment, ColumnFormat},
+        ProcessFilter,
     };
 
     const VALID_INI: &str = "[display]
 mode = term
-every = 10
+layout = tsv
+timestamps = yes
+every = 1.5s
+details_every = 5s
+align = yes
+reload = yes
+adaptive = yes
+read_only = yes
+read_only_delay = no
+describe = yes
+fit = yes
+fit_priority = mem:rss, time:cpu
+title = yes
+bell = osc9
+background = 2m
+max_depth = 3
+cpu_basis = single
+history = 30
+max_fps = 20
+wchan = yes
 format = human
 theme = light
 
 [export]
 kind = rrd
 dir = /tmp
+template = {hostname}/{target}.csv
 size = 10m
 count = 5
+histogram = mem:rss=100m,10m
+max_age = 7d
+max_total_size = 1g
+downsample = 1h,1m
+command = nc -q0 localhost 9000
+address = statsd:8125
+prefix = host1.oprs
+send_every = 5s
+flush = 10s
+flush_rows = 100
+base_units = yes
 
 [logging]
 file = /var/log/oprs.log
@@ -353,9 +1165,68 @@ level = info
 
 [targets]
 system = true
+system_breakdown = yes
 myself = yes
+myself_cpu_limit = 25
+fold_exited = yes
+
+[privileges]
+user = nobody:nogroup
+
+[notifications]
+uninterruptible = 5
+export = yes
+short_lived = yes
+renames = yes
+credentials = yes
+
+[exclude]
+name = backup*
+name = zabbix_agentd
+user = 998
+
+[formatters]
+mem:rss = numfmt --to=iec
+
+[columns]
+time:cpu = right, decimals=1, width=6
+
+[derived]
+derived:rss_per_thread = mem:rss / thread:count
+
+[filesystems]
+root = /
+
+[target_metrics]
+db = io:*:total, time:cpu+ratio, derived:rss_per_thread
+
+[searches]
+1 = name ~ postgres
+2 = user ~ www-data; filter = active
 ";
 
+    fn parse_settings(content: &str) -> Settings {
+        let mut settings = Settings::new();
+        let mut handler = ConfigHandler::new(&mut settings);
+        let mut parser = IniParser::new(&mut handler);
+        parser.parse(io::Cursor::new(content)).unwrap();
+        settings
+    }
+
+    #[test]
+    fn parse_default_settings() {
+        let settings = parse_settings(DEFAULT_SETTINGS);
+        assert_eq!(Settings::new().to_string(), settings.to_string());
+    }
+
+    #[test]
+    fn display_settings() {
+        let settings = parse_settings(VALID_INI);
+        let output = settings.to_string();
+        assert!(output.contains("histogram = mem:rss=10000000,100000000\n"));
+        assert_eq!(output, parse_settings(&output).to_string());
+    }
+
     #[test]
     fn parse_valid_ini() -> io::Result<()> {
         let mut buf = io::Cursor::new(Vec::<u8>::new());
@@ -363,35 +1234,187 @@ myself = yes
         buf.rewind()?;
         let mut settings = Settings::new();
         assert_eq!(DisplayMode::Any, settings.display.mode);
+        assert_eq!(TextLayout::Table, settings.display.layout);
+        assert!(!settings.display.timestamps);
         assert_eq!(super::DEFAULT_DELAY, settings.display.every);
+        assert_eq!(None, settings.display.details_every);
+        assert!(!settings.display.align);
+        assert!(!settings.display.reload);
+        assert!(!settings.display.adaptive);
+        assert!(!settings.display.read_only);
+        assert!(settings.display.read_only_delay);
+        assert!(!settings.display.describe);
+        assert!(!settings.display.fit);
+        assert!(settings.display.fit_priority.is_empty());
+        assert!(!settings.display.title);
+        assert_eq!(AlertSignal::None, settings.display.bell);
+        assert_eq!(None, settings.display.background);
+        assert_eq!(None, settings.display.max_depth);
+        assert_eq!(CpuBasis::All, settings.display.cpu_basis);
+        assert_eq!(0, settings.display.history);
+        assert_eq!(0, settings.display.max_fps);
+        assert!(!settings.display.wchan);
         assert_eq!(MetricFormat::Human, settings.display.format);
         assert_eq!(None, settings.display.theme);
         assert_eq!(ExportType::None, settings.export.kind);
         assert_eq!(PathBuf::from("."), settings.export.dir);
+        assert_eq!("{target}", settings.export.template.to_string());
         assert_eq!(None, settings.export.size);
+        assert!(settings.export.histograms.is_empty());
+        assert_eq!(None, settings.export.max_age);
+        assert_eq!(None, settings.export.max_total_size);
+        assert!(settings.export.downsample.is_empty());
+        assert_eq!(None, settings.export.command);
+        assert_eq!(None, settings.export.address);
+        assert_eq!(None, settings.export.prefix);
+        assert_eq!(None, settings.export.send_every);
+        assert_eq!(None, settings.export.flush);
+        assert_eq!(None, settings.export.flush_rows);
+        assert!(!settings.export.base_units);
         assert_eq!(None, settings.logging.file);
         assert_eq!(LoggingLevel::Warning, settings.logging.level);
         assert!(!settings.targets.system);
+        assert!(!settings.targets.system_breakdown);
         assert!(!settings.targets.myself);
+        assert_eq!(10, settings.targets.myself_cpu_limit);
+        assert!(!settings.targets.fold_exited);
+        assert_eq!(None, settings.privileges.user);
+        assert!(settings.notifications.enabled);
+        assert_eq!(3, settings.notifications.uninterruptible);
+        assert!(!settings.notifications.export);
+        assert!(!settings.notifications.short_lived);
+        assert!(!settings.notifications.renames);
+        assert!(!settings.notifications.credentials);
+        assert!(settings.exclusion.names.is_empty());
+        assert!(settings.exclusion.users.is_empty());
+        assert_eq!(None, settings.formatters.get(MetricId::MemRss));
+        assert_eq!(None, settings.columns.get(MetricId::TimeCpu));
+        assert!(settings.derived.metrics().unwrap().is_empty());
+        assert!(settings.filesystems.metrics().unwrap().is_empty());
+        assert!(settings.target_metrics.is_empty());
+        assert!(settings.searches.get('1').is_none());
 
         let mut handler = ConfigHandler::new(&mut settings);
         let mut parser = IniParser::new(&mut handler);
         parser.parse(buf).unwrap();
 
         assert_eq!(DisplayMode::Terminal, settings.display.mode);
-        assert_eq!(10.0, settings.display.every);
+        assert_eq!(TextLayout::Tsv, settings.display.layout);
+        assert!(settings.display.timestamps);
+        assert_eq!(Duration::from_millis(1500), settings.display.every);
+        assert_eq!(Some(Duration::from_secs(5)), settings.display.details_every);
+        assert!(settings.display.align);
+        assert!(settings.display.reload);
+        assert!(settings.display.adaptive);
+        assert!(settings.display.read_only);
+        assert!(!settings.display.read_only_delay);
+        assert!(settings.display.describe);
+        assert!(settings.display.title);
+        assert_eq!(AlertSignal::Osc9, settings.display.bell);
+        assert!(settings.display.fit);
+        assert_eq!(
+            vec!["mem:rss".to_string(), "time:cpu".to_string()],
+            settings.display.fit_priority
+        );
+        assert_eq!(Some(Duration::from_secs(120)), settings.display.background);
+        assert_eq!(Some(3), settings.display.max_depth);
+        assert_eq!(CpuBasis::Single, settings.display.cpu_basis);
+        assert_eq!(30, settings.display.history);
+        assert_eq!(20, settings.display.max_fps);
+        assert!(settings.display.wchan);
         assert_eq!(MetricFormat::Human, settings.display.format);
         assert_eq!(Some(BuiltinTheme::Light), settings.display.theme);
         assert_eq!(ExportType::Rrd, settings.export.kind);
         assert_eq!(PathBuf::from("/tmp"), settings.export.dir);
+        assert_eq!(
+            "{hostname}/{target}.csv",
+            settings.export.template.to_string()
+        );
         assert_eq!(Some(10_000_000), settings.export.size);
+        assert_eq!(1, settings.export.histograms.len());
+        assert_eq!(
+            vec![10_000_000, 100_000_000],
+            settings.export.histograms[0].bounds
+        );
+        assert_eq!(Some(Duration::from_secs(604_800)), settings.export.max_age);
+        assert_eq!(Some(1_000_000_000), settings.export.max_total_size);
+        assert_eq!(
+            vec![Duration::from_secs(60), Duration::from_secs(3_600)],
+            settings.export.downsample
+        );
+        assert_eq!(
+            Some("nc -q0 localhost 9000"),
+            settings.export.command.as_deref()
+        );
+        assert_eq!(Some("statsd:8125"), settings.export.address.as_deref());
+        assert_eq!(Some("host1.oprs"), settings.export.prefix.as_deref());
+        assert_eq!(Some(Duration::from_secs(5)), settings.export.send_every);
+        assert_eq!(Some(Duration::from_secs(10)), settings.export.flush);
+        assert_eq!(Some(100), settings.export.flush_rows);
+        assert!(settings.export.base_units);
         assert_eq!(
             Some(PathBuf::from("/var/log/oprs.log")),
             settings.logging.file
         );
         assert_eq!(LoggingLevel::Info, settings.logging.level);
         assert!(settings.targets.system);
+        assert!(settings.targets.system_breakdown);
         assert!(settings.targets.myself);
+        assert_eq!(25, settings.targets.myself_cpu_limit);
+        assert!(settings.targets.fold_exited);
+        assert_eq!(Some("nobody:nogroup".to_string()), settings.privileges.user);
+        assert!(settings.notifications.enabled);
+        assert_eq!(5, settings.notifications.uninterruptible);
+        assert!(settings.notifications.export);
+        assert!(settings.notifications.short_lived);
+        assert!(settings.notifications.renames);
+        assert!(settings.notifications.credentials);
+        assert_eq!(
+            vec!["backup*".to_string(), "zabbix_agentd".to_string()],
+            settings.exclusion.names
+        );
+        assert_eq!(vec!["998".to_string()], settings.exclusion.users);
+        assert_eq!(
+            Some("numfmt --to=iec"),
+            settings.formatters.get(MetricId::MemRss)
+        );
+        assert_eq!(
+            Some(ColumnFormat {
+                alignment: Some(ColumnAlignment::Right),
+                decimals: Some(1),
+                width: Some(6),
+            }),
+            settings.columns.get(MetricId::TimeCpu)
+        );
+        let derived = settings.derived.metrics().unwrap();
+        assert_eq!(1, derived.len());
+        assert_eq!("derived:rss_per_thread", derived[0].name());
+        let filesystems = settings.filesystems.metrics().unwrap();
+        assert_eq!(1, filesystems.len());
+        assert_eq!("fs:root:used", filesystems[0].metric_name(MetricId::FsUsed));
+        assert_eq!(
+            vec![(
+                "db",
+                &[
+                    "io:*:total".to_string(),
+                    "time:cpu+ratio".to_string(),
+                    "derived:rss_per_thread".to_string()
+                ][..]
+            )],
+            settings.target_metrics.iter().collect::<Vec<_>>()
+        );
+        assert_eq!(
+            Some("postgres"),
+            settings
+                .searches
+                .get('1')
+                .and_then(|search| search.pattern.as_deref())
+        );
+        assert_eq!(
+            Some(ProcessFilter::Active),
+            settings.searches.get('2').and_then(|search| search.filter)
+        );
+        assert!(settings.searches.get('3').is_none());
         Ok(())
     }
 }
@@ -14,17 +14,188 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
-use libc::{nanosleep, timespec};
-use std::time::{Duration, Instant};
+use libc::{clock_gettime, nanosleep, timespec, CLOCK_BOOTTIME};
+use std::{
+    fmt,
+    time::{Duration, Instant, SystemTime},
+};
+
+/// Time since the Unix epoch.
+fn now_since_epoch() -> Duration {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+}
+
+/// Time from a timestamp to the next wall-clock boundary multiple of delay.
+///
+/// The result is in ]0, delay].
+fn until_boundary(timestamp: Duration, delay: Duration) -> Duration {
+    let delay_nanos = delay.as_nanos();
+    if delay_nanos == 0 {
+        return delay;
+    }
+    let offset = timestamp.as_nanos() % delay_nanos;
+    Duration::from_nanos((delay_nanos - offset) as u64)
+}
+
+/// Round a timestamp to the closest wall-clock boundary multiple of delay.
+pub fn round_to_boundary(timestamp: Duration, delay: Duration) -> Duration {
+    let delay_nanos = delay.as_nanos();
+    if delay_nanos == 0 {
+        return timestamp;
+    }
+    let nanos = timestamp.as_nanos();
+    let rounded = (nanos + delay_nanos / 2) / delay_nanos * delay_nanos;
+    Duration::from_nanos(rounded as u64)
+}
+
+/// Time since boot including the time suspended.
+fn boot_time() -> Duration {
+    let mut ts = timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    if unsafe { clock_gettime(CLOCK_BOOTTIME, &mut ts) } == 0 {
+        Duration::new(ts.tv_sec as u64, ts.tv_nsec as u32)
+    } else {
+        Duration::ZERO
+    }
+}
+
+/// Time when a sample is collected.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SampleTime {
+    /// Time since the start including the time suspended. It never goes backward.
+    pub monotonic: Duration,
+    /// Time since the UNIX epoch. It may jump when the clock is set.
+    pub wall: Duration,
+}
+
+/// Minimum change in seconds of the offset between the clocks to report a step.
+const CLOCK_STEP_THRESHOLD: f64 = 1.0;
+
+/// Clock giving the time of the samples.
+pub struct SampleClock {
+    start: Duration,
+    /// Last difference in seconds between the wall clock and the monotonic clock.
+    offset: Option<f64>,
+}
+
+impl SampleClock {
+    pub fn new() -> SampleClock {
+        SampleClock {
+            start: boot_time(),
+            offset: None,
+        }
+    }
+
+    /// Time of a sample taken now.
+    ///
+    /// If there is an alignment, the wall-clock time is rounded to a multiple of it.
+    pub fn now(&mut self, alignment: Option<Duration>) -> SampleTime {
+        let time = SampleTime {
+            monotonic: boot_time().saturating_sub(self.start),
+            wall: now_since_epoch(),
+        };
+        self.check_step(&time);
+        match alignment {
+            Some(delay) => SampleTime {
+                wall: round_to_boundary(time.wall, delay),
+                ..time
+            },
+            None => time,
+        }
+    }
+
+    /// Report when the wall clock has been set.
+    fn check_step(&mut self, time: &SampleTime) -> Option<f64> {
+        let offset = time.wall.as_secs_f64() - time.monotonic.as_secs_f64();
+        let step = self
+            .offset
+            .map(|last_offset| offset - last_offset)
+            .filter(|step| step.abs() >= CLOCK_STEP_THRESHOLD);
+        if let Some(step) = step {
+            log::warn!("wall clock stepped by {step:+.3}s");
+        }
+        self.offset = Some(offset);
+        step
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+#[error("{0}: invalid duration")]
+pub struct InvalidDuration(String);
+
+/// Parse a duration with units d, h, m, s or ms (ex: 500ms, 2m, 1h30m, 7d).
+///
+/// A number without unit is a number of seconds.
+pub fn parse_duration(input: &str) -> Result<Duration, InvalidDuration> {
+    let invalid = || InvalidDuration(input.to_string());
+    if let Ok(seconds) = input.parse::<f64>() {
+        return Duration::try_from_secs_f64(seconds).map_err(|_| invalid());
+    }
+    let mut total = Duration::ZERO;
+    let mut rest = input;
+    if rest.is_empty() {
+        return Err(invalid());
+    }
+    while !rest.is_empty() {
+        let end = rest
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .unwrap_or(rest.len());
+        let (number, tail) = rest.split_at(end);
+        let value = number.parse::<f64>().map_err(|_| invalid())?;
+        let (factor, tail) = if let Some(tail) = tail.strip_prefix("ms") {
+            (0.001, tail)
+        } else if let Some(tail) = tail.strip_prefix('d') {
+            (86_400.0, tail)
+        } else if let Some(tail) = tail.strip_prefix('h') {
+            (3600.0, tail)
+        } else if let Some(tail) = tail.strip_prefix('m') {
+            (60.0, tail)
+        } else if let Some(tail) = tail.strip_prefix('s') {
+            (1.0, tail)
+        } else {
+            return Err(invalid());
+        };
+        let part = Duration::try_from_secs_f64(value * factor).map_err(|_| invalid())?;
+        total = total.checked_add(part).ok_or_else(invalid)?;
+        rest = tail;
+    }
+    Ok(total)
+}
+
+/// Format a duration with the units accepted by `parse_duration` (ex: 1h30m).
+pub fn format_duration(duration: Duration) -> String {
+    const UNITS: [(u128, &str); 4] = [(3_600_000, "h"), (60_000, "m"), (1_000, "s"), (1, "ms")];
+    let mut millis = duration.as_millis();
+    if millis == 0 {
+        return String::from("0s");
+    }
+    let mut result = String::new();
+    for (unit_millis, unit) in UNITS {
+        let count = millis / unit_millis;
+        if count > 0 {
+            result.push_str(&format!("{count}{unit}"));
+            millis -= count * unit_millis;
+        }
+    }
+    result
+}
 
 /// Timer that expired at constant time
 ///
 /// The stop watch records the time when the timer was started. It's used to
 /// correct the remaining time.
+///
+/// An aligned timer expires on wall-clock boundaries that are multiple of the
+/// delay (e.g. :00, :10, :20 for 10 seconds).
 pub struct Timer {
     delay: Duration,
     stop_watch: Instant,
     remaining: Option<Duration>,
+    aligned: bool,
 }
 
 impl Timer {
@@ -34,9 +205,25 @@ impl Timer {
             delay,
             stop_watch: Instant::now(),
             remaining: if expired { None } else { Some(delay) },
+            aligned: false,
         }
     }
 
+    /// Create a new timer that expires on the next wall-clock boundary.
+    pub fn with_alignment(delay: Duration) -> Timer {
+        Timer {
+            delay,
+            stop_watch: Instant::now(),
+            remaining: Some(until_boundary(now_since_epoch(), delay)),
+            aligned: true,
+        }
+    }
+
+    /// Whether the timer is aligned on wall-clock boundaries.
+    pub fn is_aligned(&self) -> bool {
+        self.aligned
+    }
+
     /// Delay before the timer expires.
     pub fn get_delay(&self) -> Duration {
         self.delay
@@ -69,8 +256,14 @@ impl Timer {
     /// Reset the timer.
     ///
     /// The timer reference is not the current time but the last time it actually expired.
+    /// For an aligned timer, the next expiration is the next wall-clock boundary.
     pub fn reset(&mut self) {
-        self.remaining = Some(self.delay);
+        self.remaining = if self.aligned {
+            self.stop_watch = Instant::now();
+            Some(until_boundary(now_since_epoch(), self.delay))
+        } else {
+            Some(self.delay)
+        };
     }
 
     /// Return the remaining time or None if it has expired.
@@ -160,19 +353,104 @@ impl DriftMonitor {
     }
 }
 
+/// Time spent in a tick.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Workload {
+    pub collect: Duration,
+    pub render: Duration,
+    pub export: Duration,
+}
+
+impl Workload {
+    pub fn total(&self) -> Duration {
+        self.collect + self.render + self.export
+    }
+}
+
+impl fmt::Display for Workload {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        write!(
+            f,
+            "collect:{}ms render:{}ms export:{}ms",
+            self.collect.as_millis(),
+            self.render.as_millis(),
+            self.export.as_millis()
+        )
+    }
+}
+
+/// Report ticks that take longer than the interval.
+pub struct OverloadMonitor {
+    adaptive: bool,
+    notification_time: Option<Instant>,
+    notification_delay: u64,
+}
+
+impl OverloadMonitor {
+    /// New monitor with a delay between two notifications.
+    ///
+    /// If adaptive, the interval is lengthened on overload.
+    pub fn new(adaptive: bool, notification_delay: u64) -> OverloadMonitor {
+        OverloadMonitor {
+            adaptive,
+            notification_time: None,
+            notification_delay,
+        }
+    }
+
+    /// Check the workload and return the new delay if it must be lengthened.
+    pub fn update(&mut self, workload: &Workload, delay: Duration) -> Option<Duration> {
+        let total = workload.total();
+        if total < delay || delay.is_zero() {
+            return None;
+        }
+        if self.adaptive {
+            let mut new_delay = delay;
+            while new_delay <= total {
+                new_delay *= 2;
+            }
+            log::warn!(
+                "tick took {}ms ({workload}), interval lengthened to {}ms",
+                total.as_millis(),
+                new_delay.as_millis()
+            );
+            Some(new_delay)
+        } else {
+            let now = Instant::now();
+            let must_notify = self
+                .notification_time
+                .and_then(|time| now.checked_duration_since(time))
+                .map(|elapsed| elapsed.as_secs() >= self.notification_delay)
+                .unwrap_or(true);
+            if must_notify {
+                self.notification_time = Some(now);
+                log::warn!(
+                    "tick took {}ms ({workload}), longer than the interval",
+                    total.as_millis()
+                );
+            }
+            None
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
 
     use std::thread::sleep;
     use std::time::{Duration, Instant};
 
-    use super::Timer;
+    use super::{
+        format_duration, parse_duration, round_to_boundary, until_boundary, OverloadMonitor,
+        SampleClock, SampleTime, Timer, Workload,
+    };
 
     pub fn new_in_the_past(delay: Duration, past_offset: Duration) -> Timer {
         Timer {
             delay,
             stop_watch: Instant::now().checked_sub(past_offset).unwrap(),
             remaining: Some(delay),
+            aligned: false,
         }
     }
 
@@ -237,4 +515,116 @@ mod tests {
         let mut timer2 = new_in_the_past(delay, delay);
         assert!(timer2.remaining().is_none()); // expired
     }
+
+    #[test]
+    fn boundaries() {
+        let delay = Duration::from_secs(10);
+        assert_eq!(
+            Duration::from_secs(7),
+            until_boundary(Duration::from_secs(1003), delay)
+        );
+        assert_eq!(delay, until_boundary(Duration::from_secs(1000), delay));
+        assert_eq!(
+            Duration::from_millis(500),
+            until_boundary(Duration::from_millis(1500), Duration::from_secs(1))
+        );
+        assert_eq!(
+            Duration::from_secs(1000),
+            round_to_boundary(Duration::from_millis(1_000_004), delay)
+        );
+        assert_eq!(
+            Duration::from_secs(1010),
+            round_to_boundary(Duration::from_millis(1_006_000), delay)
+        );
+    }
+
+    #[test]
+    fn aligned_timer() {
+        let delay = Duration::from_secs(60);
+        let mut timer = Timer::with_alignment(delay);
+        assert!(timer.is_aligned());
+        assert!(timer.remaining().unwrap() <= delay);
+        timer.reset();
+        assert!(timer.remaining().unwrap() <= delay);
+    }
+
+    #[test]
+    fn overload() {
+        let delay = Duration::from_millis(100);
+        let workload = Workload {
+            collect: Duration::from_millis(150),
+            render: Duration::from_millis(60),
+            export: Duration::from_millis(10),
+        };
+        assert_eq!(Duration::from_millis(220), workload.total());
+        let mut monitor = OverloadMonitor::new(false, 60);
+        assert!(monitor.update(&Workload::default(), delay).is_none());
+        assert!(monitor.update(&workload, delay).is_none());
+        let mut monitor = OverloadMonitor::new(true, 60);
+        assert!(monitor.update(&Workload::default(), delay).is_none());
+        assert_eq!(
+            Some(Duration::from_millis(400)),
+            monitor.update(&workload, delay)
+        );
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        let parse = |input| parse_duration(input).ok();
+        assert_eq!(Some(Duration::from_secs(5)), parse("5"));
+        assert_eq!(Some(Duration::from_millis(500)), parse("0.5"));
+        assert_eq!(Some(Duration::from_millis(500)), parse("500ms"));
+        assert_eq!(Some(Duration::from_secs(120)), parse("2m"));
+        assert_eq!(Some(Duration::from_secs(5400)), parse("1h30m"));
+        assert_eq!(Some(Duration::from_millis(1500)), parse("1.5s"));
+        assert_eq!(Some(Duration::from_millis(90_250)), parse("1m30s250ms"));
+        assert_eq!(Some(Duration::from_secs(604_800)), parse("7d"));
+        assert_eq!(None, parse("7w"));
+        assert_eq!(None, parse("999999999999999999d"));
+        assert_eq!(None, parse(""));
+        assert_eq!(None, parse("-1"));
+        assert_eq!(None, parse("10x"));
+        assert_eq!(None, parse("h"));
+        assert_eq!(None, parse("1h30"));
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!("0s", format_duration(Duration::ZERO));
+        assert_eq!("500ms", format_duration(Duration::from_millis(500)));
+        assert_eq!("5s", format_duration(Duration::from_secs(5)));
+        assert_eq!("1s500ms", format_duration(Duration::from_millis(1500)));
+        assert_eq!("2m", format_duration(Duration::from_secs(120)));
+        assert_eq!("1h30m", format_duration(Duration::from_secs(5400)));
+        let duration = Duration::from_millis(3_723_004);
+        assert_eq!(
+            Some(duration),
+            parse_duration(&format_duration(duration)).ok()
+        );
+    }
+
+    #[test]
+    fn test_sample_clock() {
+        let mut clock = SampleClock::new();
+        let first = clock.now(None);
+        let second = clock.now(Some(Duration::from_secs(10)));
+        assert!(second.monotonic >= first.monotonic);
+        assert_eq!(0, second.wall.as_secs() % 10);
+        assert_eq!(0, second.wall.subsec_nanos());
+    }
+
+    #[test]
+    fn test_clock_step() {
+        let mut clock = SampleClock::new();
+        let time = |monotonic, wall| SampleTime {
+            monotonic: Duration::from_secs(monotonic),
+            wall: Duration::from_secs(wall),
+        };
+        assert_eq!(None, clock.check_step(&time(0, 1000)));
+        assert_eq!(None, clock.check_step(&time(10, 1010)));
+        // NTP step backward.
+        assert_eq!(Some(-5.0), clock.check_step(&time(20, 1015)));
+        // Suspend is counted in both clocks.
+        assert_eq!(None, clock.check_step(&time(3620, 4615)));
+    }
 }
@@ -36,7 +36,8 @@ const UTF8_TABLE_CHARS_: [&str; 13] = [
 /// Character types to draw a table
 pub enum TableChar {
     Horizontal,
-    _Vertical,
+    #[cfg(feature = "tui")]
+    Vertical,
     DownRight,
     DownLeft,
     UpRight,
@@ -51,6 +52,7 @@ pub enum TableChar {
 }
 
 /// Characters to draw a table.
+#[derive(Debug)]
 pub struct TableCharSet(&'static [&'static str; 13]);
 
 impl TableCharSet {
@@ -67,7 +69,8 @@ impl TableCharSet {
         let Self(chars) = self;
         chars[match kind {
             TableChar::Horizontal => 0usize,
-            TableChar::_Vertical => 1usize,
+            #[cfg(feature = "tui")]
+            TableChar::Vertical => 1usize,
             TableChar::DownRight => 2usize,
             TableChar::DownLeft => 3usize,
             TableChar::UpRight => 4usize,
@@ -14,32 +14,435 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
-use std::io;
+//! Read the events on the terminal input.
+//!
+//! The bytes are decoded here rather than by termion to handle the bracketed
+//! paste and to drop the escape sequences that are not understood (responses
+//! to queries, mouse reports, modified keys) as a whole instead of turning
+//! their tail into spurious keys.
+
+use std::fs;
+use std::io::{self, BufRead};
+use std::path::Path;
 use std::sync::mpsc;
 use std::thread;
-use std::time::Duration;
-use termion::input::TermRead;
+use std::time::{Duration, Instant};
+
+pub use termion::{event::Key, is_tty};
+
+/// Escape sequence to enable the bracketed paste.
+pub const ENABLE_PASTE: &str = "\x1b[?2004h";
+
+/// Escape sequence to disable the bracketed paste.
+pub const DISABLE_PASTE: &str = "\x1b[?2004l";
+
+const ESC: u8 = 0x1b;
 
-pub use termion::{
-    event::{Event, Key},
-    is_tty,
-};
+/// End of the pasted text.
+const PASTE_END: &[u8] = b"\x1b[201~";
+
+/// Tag of the log lines of the recorded events followed by the time since the start.
+const RECORD_TAG: &str = "input event +";
+
+/// Input event
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Event {
+    /// Key pressed.
+    Key(Key),
+    /// Text pasted in the terminal.
+    Paste(String),
+}
 
 type InputResult = io::Result<Event>;
 
 type InputOptionalResult = io::Result<Option<Event>>;
 
+/// Token at the beginning of the input.
+enum Token {
+    Event(Event),
+    PasteStart,
+    /// Sequence that is not understood.
+    Ignored,
+}
+
+/// Length of a UTF-8 character given its first byte.
+fn utf8_len(first: u8) -> usize {
+    match first {
+        0xc0..=0xdf => 2,
+        0xe0..=0xef => 3,
+        0xf0..=0xf7 => 4,
+        _ => 1,
+    }
+}
+
+/// Decode a UTF-8 character.
+///
+/// Returns None if the character is incomplete.
+fn decode_char(bytes: &[u8]) -> Option<(usize, Option<char>)> {
+    let len = utf8_len(bytes[0]);
+    if bytes.len() < len {
+        return None;
+    }
+    let ch = std::str::from_utf8(&bytes[..len])
+        .ok()
+        .and_then(|s| s.chars().next());
+    Some((if ch.is_some() { len } else { 1 }, ch))
+}
+
+/// Key of a CSI sequence with its parameters and final byte.
+fn csi_key(params: &[u8], last: u8) -> Option<Key> {
+    match (params, last) {
+        (b"", b'A') => Some(Key::Up),
+        (b"", b'B') => Some(Key::Down),
+        (b"", b'C') => Some(Key::Right),
+        (b"", b'D') => Some(Key::Left),
+        (b"", b'H') => Some(Key::Home),
+        (b"", b'F') => Some(Key::End),
+        (b"", b'Z') => Some(Key::BackTab),
+        (b"1;2", b'A') => Some(Key::ShiftUp),
+        (b"1;2", b'B') => Some(Key::ShiftDown),
+        (b"1;2", b'C') => Some(Key::ShiftRight),
+        (b"1;2", b'D') => Some(Key::ShiftLeft),
+        (b"1;3", b'A') => Some(Key::AltUp),
+        (b"1;3", b'B') => Some(Key::AltDown),
+        (b"1;3", b'C') => Some(Key::AltRight),
+        (b"1;3", b'D') => Some(Key::AltLeft),
+        (b"1;5", b'A') => Some(Key::CtrlUp),
+        (b"1;5", b'B') => Some(Key::CtrlDown),
+        (b"1;5", b'C') => Some(Key::CtrlRight),
+        (b"1;5", b'D') => Some(Key::CtrlLeft),
+        (b"1;5", b'H') => Some(Key::CtrlHome),
+        (b"1;5", b'F') => Some(Key::CtrlEnd),
+        (_, b'~') => match params {
+            b"1" | b"7" => Some(Key::Home),
+            b"2" => Some(Key::Insert),
+            b"3" => Some(Key::Delete),
+            b"4" | b"8" => Some(Key::End),
+            b"5" => Some(Key::PageUp),
+            b"6" => Some(Key::PageDown),
+            b"11" | b"12" | b"13" | b"14" | b"15" => Some(Key::F(params[1] - b'0')),
+            b"17" | b"18" | b"19" | b"20" | b"21" => Some(Key::F(
+                std::str::from_utf8(params).ok()?.parse::<u8>().ok()? - 11,
+            )),
+            b"23" => Some(Key::F(11)),
+            b"24" => Some(Key::F(12)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Length of a string sequence (OSC, DCS, ...) terminated by BEL or ST.
+fn string_len(bytes: &[u8]) -> Option<usize> {
+    bytes
+        .windows(2)
+        .position(|w| w[0] == 0x07 || w == [ESC, b'\\'])
+        .map(|pos| if bytes[pos] == 0x07 { pos + 1 } else { pos + 2 })
+        .or_else(|| (bytes.last() == Some(&0x07)).then_some(bytes.len()))
+}
+
+/// Decode an escape sequence.
+fn next_escape(bytes: &[u8]) -> Option<(usize, Token)> {
+    match *bytes.get(1)? {
+        b'[' => {
+            let end = 2 + bytes[2..].iter().position(|c| !(0x20..=0x3f).contains(c))?;
+            let params = &bytes[2..end];
+            match bytes[end] {
+                b'M' if params.is_empty() => {
+                    // X10 mouse report followed by 3 bytes.
+                    (bytes.len() >= end + 4).then_some((end + 4, Token::Ignored))
+                }
+                b'[' if params.is_empty() => {
+                    // Function keys of the Linux console.
+                    let key = match *bytes.get(end + 1)? {
+                        c @ b'A'..=b'E' => Token::Event(Event::Key(Key::F(1 + c - b'A'))),
+                        _ => Token::Ignored,
+                    };
+                    Some((end + 2, key))
+                }
+                b'~' if params == b"200" => Some((end + 1, Token::PasteStart)),
+                last @ 0x40..=0x7e => Some((
+                    end + 1,
+                    csi_key(params, last)
+                        .map_or(Token::Ignored, |key| Token::Event(Event::Key(key))),
+                )),
+                _ => Some((end, Token::Ignored)),
+            }
+        }
+        b'O' => {
+            let key = match *bytes.get(2)? {
+                c @ b'P'..=b'S' => Some(Key::F(1 + c - b'P')),
+                b'A' => Some(Key::Up),
+                b'B' => Some(Key::Down),
+                b'C' => Some(Key::Right),
+                b'D' => Some(Key::Left),
+                b'H' => Some(Key::Home),
+                b'F' => Some(Key::End),
+                _ => None,
+            };
+            Some((
+                3,
+                key.map_or(Token::Ignored, |key| Token::Event(Event::Key(key))),
+            ))
+        }
+        b']' | b'P' | b'_' | b'^' => string_len(&bytes[2..]).map(|len| (len + 2, Token::Ignored)),
+        ESC => Some((1, Token::Event(Event::Key(Key::Esc)))),
+        _ => {
+            let (len, ch) = decode_char(&bytes[1..])?;
+            Some((
+                len + 1,
+                ch.map_or(Token::Ignored, |ch| Token::Event(Event::Key(Key::Alt(ch)))),
+            ))
+        }
+    }
+}
+
+/// Decode the token at the beginning of the input.
+///
+/// Returns None if the token is incomplete.
+fn next_token(bytes: &[u8]) -> Option<(usize, Token)> {
+    let key = match bytes[0] {
+        ESC => return next_escape(bytes),
+        b'\n' | b'\r' => Key::Char('\n'),
+        b'\t' => Key::Char('\t'),
+        0x7f => Key::Backspace,
+        c @ 0x01..=0x1a => Key::Ctrl((c - 0x01 + b'a') as char),
+        c @ 0x1c..=0x1f => Key::Ctrl((c - 0x1c + b'4') as char),
+        0 => Key::Null,
+        _ => {
+            let (len, ch) = decode_char(bytes)?;
+            return Some((
+                len,
+                ch.map_or(Token::Ignored, |ch| Token::Event(Event::Key(Key::Char(ch)))),
+            ));
+        }
+    };
+    Some((1, Token::Event(Event::Key(key))))
+}
+
+/// Decoder of the bytes read on the terminal.
+#[derive(Debug, Default)]
+struct Decoder {
+    /// Bytes of an incomplete sequence.
+    pending: Vec<u8>,
+    /// Text pasted so far if a paste is in progress.
+    paste: Option<Vec<u8>>,
+}
+
+impl Decoder {
+    /// Decode the bytes of a read.
+    ///
+    /// An escape alone at the end of a read is the escape key.
+    fn decode(&mut self, bytes: &[u8]) -> Vec<Event> {
+        self.pending.extend_from_slice(bytes);
+        let mut events = Vec::new();
+        let mut start = 0;
+        while start < self.pending.len() {
+            let input = &self.pending[start..];
+            if let Some(ref mut paste) = self.paste {
+                match input.windows(PASTE_END.len()).position(|w| w == PASTE_END) {
+                    Some(pos) => {
+                        paste.extend_from_slice(&input[..pos]);
+                        let text = String::from_utf8_lossy(paste).to_string();
+                        events.push(Event::Paste(text));
+                        self.paste = None;
+                        start += pos + PASTE_END.len();
+                    }
+                    None => {
+                        // Keep what may be the beginning of the end marker.
+                        let len = input.len().saturating_sub(PASTE_END.len() - 1);
+                        paste.extend_from_slice(&input[..len]);
+                        start += len;
+                        break;
+                    }
+                }
+                continue;
+            }
+            match next_token(input) {
+                Some((len, token)) => {
+                    match token {
+                        Token::Event(event) => events.push(event),
+                        Token::PasteStart => self.paste = Some(Vec::new()),
+                        Token::Ignored => (),
+                    }
+                    start += len;
+                }
+                None => break,
+            }
+        }
+        self.pending.drain(..start);
+        if self.paste.is_none() && self.pending == [ESC] {
+            self.pending.clear();
+            events.push(Event::Key(Key::Esc));
+        }
+        events
+    }
+}
+
+/// Log an event with the time since the start and its outcome.
+pub fn log_event(elapsed: Duration, event: &Event, outcome: &str) {
+    log::info!(
+        "{RECORD_TAG}{:.3}s {event:?} {outcome}",
+        elapsed.as_secs_f64()
+    );
+}
+
+/// Parse a quoted literal with the escape sequences of the debug format.
+fn parse_quoted(input: &str, quote: char) -> Option<(String, &str)> {
+    let mut chars = input.strip_prefix(quote)?.char_indices();
+    let mut text = String::new();
+    while let Some((index, c)) = chars.next() {
+        match c {
+            _ if c == quote => return Some((text, &input[index + 2..])),
+            '\\' => match chars.next()?.1 {
+                'n' => text.push('\n'),
+                'r' => text.push('\r'),
+                't' => text.push('\t'),
+                '0' => text.push('\0'),
+                'u' => {
+                    let hex = chars
+                        .by_ref()
+                        .map(|(_, c)| c)
+                        .skip_while(|c| *c == '{')
+                        .take_while(|c| *c != '}')
+                        .collect::<String>();
+                    text.push(char::from_u32(u32::from_str_radix(&hex, 16).ok()?)?);
+                }
+                c => text.push(c),
+            },
+            c => text.push(c),
+        }
+    }
+    None
+}
+
+/// Parse a key in the debug format (ex: `Char('q')`, `F(1)`, `PageDown`).
+fn parse_key(input: &str) -> Option<(Key, &str)> {
+    let name_len = input
+        .find(|c: char| !c.is_ascii_alphanumeric())
+        .unwrap_or(input.len());
+    let (name, rest) = input.split_at(name_len);
+    if let Some(rest) = rest.strip_prefix('(') {
+        let (key, rest) = match name {
+            "F" => {
+                let digits = rest.find(')').unwrap_or(rest.len());
+                (Key::F(rest[..digits].parse().ok()?), &rest[digits..])
+            }
+            "Char" | "Alt" | "Ctrl" => {
+                let (text, rest) = parse_quoted(rest, '\'')?;
+                let c = text.chars().next()?;
+                let key = match name {
+                    "Char" => Key::Char(c),
+                    "Alt" => Key::Alt(c),
+                    _ => Key::Ctrl(c),
+                };
+                (key, rest)
+            }
+            _ => return None,
+        };
+        return Some((key, rest.strip_prefix(')')?));
+    }
+    let key = match name {
+        "Backspace" => Key::Backspace,
+        "Left" => Key::Left,
+        "ShiftLeft" => Key::ShiftLeft,
+        "AltLeft" => Key::AltLeft,
+        "CtrlLeft" => Key::CtrlLeft,
+        "Right" => Key::Right,
+        "ShiftRight" => Key::ShiftRight,
+        "AltRight" => Key::AltRight,
+        "CtrlRight" => Key::CtrlRight,
+        "Up" => Key::Up,
+        "ShiftUp" => Key::ShiftUp,
+        "AltUp" => Key::AltUp,
+        "CtrlUp" => Key::CtrlUp,
+        "Down" => Key::Down,
+        "ShiftDown" => Key::ShiftDown,
+        "AltDown" => Key::AltDown,
+        "CtrlDown" => Key::CtrlDown,
+        "Home" => Key::Home,
+        "CtrlHome" => Key::CtrlHome,
+        "End" => Key::End,
+        "CtrlEnd" => Key::CtrlEnd,
+        "PageUp" => Key::PageUp,
+        "PageDown" => Key::PageDown,
+        "BackTab" => Key::BackTab,
+        "Delete" => Key::Delete,
+        "Insert" => Key::Insert,
+        "Null" => Key::Null,
+        "Esc" => Key::Esc,
+        _ => return None,
+    };
+    Some((key, rest))
+}
+
+/// Parse an event in the debug format (ex: `Key(Char('q'))`, `Paste("text")`).
+fn parse_event(input: &str) -> Option<Event> {
+    if let Some(rest) = input.strip_prefix("Key(") {
+        let (key, rest) = parse_key(rest)?;
+        rest.starts_with(')').then_some(Event::Key(key))
+    } else {
+        let (text, rest) = parse_quoted(input.strip_prefix("Paste(")?, '"')?;
+        rest.starts_with(')').then_some(Event::Paste(text))
+    }
+}
+
+/// Parse a log line of a recorded event.
+fn parse_recorded_event(line: &str) -> Option<(Duration, Event)> {
+    let (_, rest) = line.split_once(RECORD_TAG)?;
+    let (seconds, rest) = rest.split_once("s ")?;
+    let elapsed = Duration::try_from_secs_f64(seconds.parse().ok()?).ok()?;
+    Some((elapsed, parse_event(rest)?))
+}
+
+/// Events recorded in a log file with the time since the start.
+pub fn read_recorded_events(path: &Path) -> io::Result<Vec<(Duration, Event)>> {
+    Ok(fs::read_to_string(path)?
+        .lines()
+        .filter_map(parse_recorded_event)
+        .collect())
+}
+
 pub struct EventChannel {
     chin: mpsc::Receiver<InputResult>,
 }
 
 impl EventChannel {
-    pub fn new() -> EventChannel {
+    /// Read the terminal input and send the recorded events at the time they were received.
+    pub fn new(recorded: Vec<(Duration, Event)>) -> EventChannel {
         let (chout, chin) = mpsc::channel();
+        if !recorded.is_empty() {
+            let start = Instant::now();
+            let chout = chout.clone();
+            thread::spawn(move || {
+                for (elapsed, event) in recorded {
+                    thread::sleep((start + elapsed).saturating_duration_since(Instant::now()));
+                    if chout.send(Ok(event)).is_err() {
+                        return;
+                    }
+                }
+            });
+        }
         thread::spawn(move || {
-            for res in io::stdin().events() {
-                if chout.send(res).is_err() {
-                    break;
+            let stdin = io::stdin();
+            let mut stdin = stdin.lock();
+            let mut decoder = Decoder::default();
+            loop {
+                let events = match stdin.fill_buf() {
+                    Ok([]) => break,
+                    Ok(bytes) => {
+                        let count = bytes.len();
+                        let events = decoder.decode(bytes);
+                        stdin.consume(count);
+                        events.into_iter().map(Ok).collect()
+                    }
+                    Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                    Err(err) => vec![Err(err)],
+                };
+                for res in events {
+                    if chout.send(res).is_err() {
+                        return;
+                    }
                 }
             }
         });
@@ -58,3 +461,66 @@ impl EventChannel {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+
+    use rstest::*;
+
+    use super::{parse_recorded_event, Decoder, Event, Key, RECORD_TAG};
+
+    fn keys(keys: &[Key]) -> Vec<Event> {
+        keys.iter().map(|key| Event::Key(*key)).collect()
+    }
+
+    #[rstest]
+    #[case(b"a\x1b[A\x7f\x03", keys(&[Key::Char('a'), Key::Up, Key::Backspace, Key::Ctrl('c')]))]
+    #[case(b"\x1b[5~\x1b[6~\x1bOP\x1b[15~\x1b[1;5C", keys(&[Key::PageUp, Key::PageDown, Key::F(1), Key::F(5), Key::CtrlRight]))]
+    #[case(b"\xc3\xa9\x1bx\x1b", keys(&[Key::Char('\u{e9}'), Key::Alt('x'), Key::Esc]))]
+    #[case(b"\x1b[?1;2cq", keys(&[Key::Char('q')]))]
+    #[case(b"\x1b]11;rgb:0000/0000/0000\x1b\\q", keys(&[Key::Char('q')]))]
+    #[case(b"\x1b[<0;10;5Mq\x1b[300~", keys(&[Key::Char('q')]))]
+    #[case(b"/\x1b[200~1234\n\x1b[201~\r", vec![Event::Key(Key::Char('/')), Event::Paste("1234\n".to_string()), Event::Key(Key::Char('\n'))])]
+    fn test_decode(#[case] bytes: &[u8], #[case] expected: Vec<Event>) {
+        let mut decoder = Decoder::default();
+        assert_eq!(expected, decoder.decode(bytes));
+    }
+
+    #[test]
+    fn test_decode_split() {
+        let mut decoder = Decoder::default();
+        assert!(decoder.decode(b"\x1b[").is_empty());
+        assert_eq!(keys(&[Key::Down]), decoder.decode(b"B"));
+        assert!(decoder.decode(b"\xe2\x82").is_empty());
+        assert_eq!(keys(&[Key::Char('\u{20ac}')]), decoder.decode(b"\xac"));
+        assert!(decoder.decode(b"\x1b[200~12").is_empty());
+        assert!(decoder.decode(b"34\x1b[20").is_empty());
+        assert_eq!(
+            vec![Event::Paste("1234".to_string()), Event::Key(Key::Char('x'))],
+            decoder.decode(b"1~x")
+        );
+    }
+
+    #[test]
+    fn test_parse_recorded_event() {
+        let events = [
+            Event::Key(Key::Char('j')),
+            Event::Key(Key::Char('\'')),
+            Event::Key(Key::Char('\\')),
+            Event::Key(Key::Alt('x')),
+            Event::Key(Key::Ctrl('c')),
+            Event::Key(Key::F(12)),
+            Event::Key(Key::CtrlHome),
+            Event::Key(Key::Esc),
+            Event::Paste("a \"b\"\n\té\u{1b}".to_string()),
+        ];
+        for (index, event) in events.into_iter().enumerate() {
+            let line = format!("[INFO] {RECORD_TAG}{index}.250s {event:?} action=None");
+            let (elapsed, parsed) = parse_recorded_event(&line).unwrap();
+            assert_eq!(index as f64 + 0.25, elapsed.as_secs_f64());
+            assert_eq!(event, parsed);
+        }
+        assert!(parse_recorded_event("[INFO] starting").is_none());
+        assert!(parse_recorded_event(&format!("{RECORD_TAG}1.0s Key(Unknown)")).is_none());
+    }
+}
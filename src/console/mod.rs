@@ -14,16 +14,48 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
-use strum_macros::EnumString;
+use strum_macros::{EnumString, IntoStaticStr};
+#[cfg(feature = "tui")]
 use supports_color::Stream;
 
-pub use self::input::{is_tty, Event, EventChannel, Key};
+#[cfg(feature = "tui")]
+pub use self::input::{
+    is_tty, log_event, read_recorded_events, Event, EventChannel, Key, DISABLE_PASTE, ENABLE_PASTE,
+};
 
 pub mod charset;
 
+#[cfg(feature = "tui")]
 mod input;
 
-#[derive(Clone, Copy, Debug, EnumString, PartialEq, Eq)]
+/// Whether the process is in the foreground of the terminal.
+///
+/// If the standard output is not a terminal, the process is considered in the foreground.
+pub fn is_foreground() -> bool {
+    let pgrp = unsafe { libc::tcgetpgrp(libc::STDOUT_FILENO) };
+    pgrp < 0 || pgrp == unsafe { libc::getpgrp() }
+}
+
+/// Signal sent to the terminal when an alert is raised.
+#[derive(Clone, Copy, Debug, EnumString, IntoStaticStr, PartialEq, Eq)]
+pub enum AlertSignal {
+    #[strum(serialize = "none")]
+    None,
+    /// Ring the bell.
+    #[strum(serialize = "bell")]
+    Bell,
+    /// Desktop notification with the escape sequence OSC 9.
+    #[strum(serialize = "osc9")]
+    Osc9,
+}
+
+impl AlertSignal {
+    pub fn as_str(self) -> &'static str {
+        self.into()
+    }
+}
+
+#[derive(Clone, Copy, Debug, EnumString, IntoStaticStr, PartialEq, Eq)]
 pub enum BuiltinTheme {
     #[strum(serialize = "light")]
     Light,
@@ -33,10 +65,19 @@ pub enum BuiltinTheme {
     Light16,
     #[strum(serialize = "dark16")]
     Dark16,
+    #[strum(serialize = "high-contrast")]
+    HighContrast,
+    #[strum(serialize = "mono")]
+    Mono,
 }
 
 impl BuiltinTheme {
+    pub fn as_str(self) -> &'static str {
+        self.into()
+    }
+
     /// Guess the theme
+    #[cfg(feature = "tui")]
     pub fn guess() -> Option<BuiltinTheme> {
         let timeout = std::time::Duration::from_millis(100);
         match termbg::theme(timeout) {
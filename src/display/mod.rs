@@ -14,35 +14,97 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
-use std::slice::Iter as SliceIter;
+use libc::pid_t;
+use std::{slice::Iter as SliceIter, time::Duration};
 
 use crate::{
-    clock::Timer,
-    process::{Collector, FormattedMetric, Process, ProcessDetails},
+    clock::{Timer, Workload},
+    dump::Json,
+    overhead::Overhead,
+    process::{Collector, FormattedMetric, ProcessDetails, ProcessFilter},
 };
 
+mod navigator;
 pub mod null;
+mod tabs;
+#[cfg(feature = "tui")]
 pub mod term;
 pub mod text;
 
+/// User action that has an impact on the application.
+///
+/// Only the terminal returns interactions.
+#[derive(Clone, Debug)]
+#[cfg_attr(not(feature = "tui"), allow(dead_code))]
+pub enum Interaction {
+    None,
+    Filter(ProcessFilter),
+    SwitchBack,
+    SwitchToHelp,
+    SwitchTo(DataKind),
+    SelectPid(pid_t),
+    SelectParent,
+    /// Save a snapshot of the process in the export directory.
+    Dump,
+    /// Show the next tab of details or the current one from the main pane.
+    NextTab,
+    /// Close the current tab of details.
+    CloseTab,
+    /// Compare the environments of two processes.
+    CompareEnvironments(pid_t, pid_t),
+    SelectRootPid(Option<pid_t>),
+    Narrow(Vec<pid_t>),
+    Wide,
+    /// Show all the processes without root nor filter.
+    WideAll,
+    Quit,
+}
+
+/// Interactions allowed in an interactive session.
+#[derive(Clone, Copy, Debug)]
+#[cfg(feature = "tui")]
+pub struct Permissions {
+    /// The session cannot be used to affect the processes.
+    pub read_only: bool,
+    /// The delay between two samples can be changed.
+    pub change_delay: bool,
+}
+
+#[cfg(feature = "tui")]
+impl Permissions {
+    /// Permissions of a session that may be read-only with or without delay changes.
+    pub fn new(read_only: bool, read_only_delay: bool) -> Self {
+        Self {
+            read_only,
+            change_delay: !read_only || read_only_delay,
+        }
+    }
+}
+
 /// Status of the device when returning from a pause.
 #[derive(Debug)]
+#[cfg_attr(not(feature = "tui"), allow(dead_code))]
 pub enum PauseStatus {
     TimeOut,
     Action(Interaction),
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(not(feature = "tui"), allow(dead_code))]
 pub enum DataKind {
     Details,
     Environment,
-    _Files,
+    EnvironmentDiff,
+    Files,
+    IoDevices,
     Limits,
-    _Maps,
-    _Threads,
+    Maps,
+    Mounts,
+    Namespaces,
+    Threads,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum PaneKind {
     Main,
     Process(DataKind),
@@ -50,15 +112,20 @@ pub enum PaneKind {
 }
 
 /// Data to display the pane.
+///
+/// Only the terminal displays the process panes.
+#[cfg_attr(not(feature = "tui"), allow(dead_code))]
 pub enum PaneData<'a, 'p> {
     /// No data.
     None,
     /// The collector for all processes.
     Collector(&'p Collector<'a>),
-    /// The details for one process.
-    Details(&'p ProcessDetails<'a>),
-    /// The process.
-    Process(&'p Process),
+    /// The details for the processes in tabs.
+    Details(&'p Tabs<ProcessDetails<'a>>),
+    /// The details of the current process in tabs.
+    Process(&'p ProcessDetails<'a>),
+    /// The current process in tabs and the one it is compared to.
+    Pair(&'p ProcessDetails<'a>, &'p ProcessDetails<'a>),
 }
 
 pub trait DisplayDevice {
@@ -70,16 +137,49 @@ pub trait DisplayDevice {
 
     /// Render the metrics on the device.
     ///
-    /// If `redraw` is true, it is a hint to tell to the device to redraw
-    /// entirely the output.
+    /// If `redraw` is true, the targets have changed. The device must not skip
+    /// this output and may write it again entirely.
     fn render(&mut self, pane_kind: PaneKind, data: PaneData, redraw: bool) -> anyhow::Result<()>;
 
+    /// Record the samples of a new tick.
+    fn record(&mut self, _: &Collector) {}
+
+    /// Report the time spent in the last tick.
+    fn set_workload(&mut self, _: &Workload) {}
+
+    /// Report the resources used by oprs.
+    fn set_overhead(&mut self, _: &Overhead) {}
+
+    /// Display a message until the next interaction.
+    fn notify(&mut self, _: &str) {}
+
+    /// Internal state for a debug dump.
+    fn dump_state(&self) -> Json {
+        Json::Null
+    }
+
+    /// Display a message that requires attention.
+    fn alert(&mut self, msg: &str) {
+        self.notify(msg)
+    }
+
+    /// Restore the device after the process has been stopped.
+    fn resume(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    /// Interrupt the next pause after a delay if it is shorter than the timer.
+    fn set_wakeup(&mut self, _: Option<Duration>) {}
+
     /// Pause for the given duration.
     fn pause(&mut self, _: &mut Timer) -> anyhow::Result<PauseStatus> {
         panic!("not available");
     }
 }
 
+pub use navigator::{PaneEvent, PaneNavigator};
 pub use null::NullDevice;
-pub use term::{Interaction, TerminalDevice};
+pub use tabs::Tabs;
+#[cfg(feature = "tui")]
+pub use term::TerminalDevice;
 pub use text::TextDevice;
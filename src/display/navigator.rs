@@ -0,0 +1,180 @@
+// Oprs -- process monitor for Linux
+// Copyright (C) 2025  Laurent Pelecq
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Navigation between the panes of the interactive display.
+//!
+//! The transitions are defined in a table. The panes that are left to open
+//! another one are kept in a history to go back.
+
+use super::{DataKind, PaneKind};
+
+/// Event changing the displayed pane.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PaneEvent {
+    /// Return to the previous pane.
+    Back,
+    /// Show the help.
+    Help,
+    /// Show other data of the current process.
+    Data(DataKind),
+    /// Show the details of the selected process.
+    Open,
+    /// Show the tabs of details.
+    NextTab,
+}
+
+/// Change of the pane.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Transition {
+    /// Keep the current pane.
+    Stay,
+    /// Open a pane and keep the current one in the history.
+    Push(PaneKind),
+    /// Replace the current pane.
+    Replace(PaneKind),
+    /// Return to the last pane of the history.
+    Pop,
+}
+
+/// Transition table.
+///
+/// Transitions that are not meaningful in a pane leave it unchanged.
+fn transition(from: PaneKind, event: PaneEvent, has_tabs: bool) -> Transition {
+    match (from, event) {
+        (PaneKind::Help, PaneEvent::Help) => Transition::Stay,
+        (_, PaneEvent::Help) => Transition::Push(PaneKind::Help),
+        (PaneKind::Main, PaneEvent::Back) => Transition::Stay,
+        (PaneKind::Help | PaneKind::Process(DataKind::Details), PaneEvent::Back) => Transition::Pop,
+        (PaneKind::Process(_), PaneEvent::Back) => {
+            Transition::Replace(PaneKind::Process(DataKind::Details))
+        }
+        (PaneKind::Process(_), PaneEvent::Data(kind)) => {
+            Transition::Replace(PaneKind::Process(kind))
+        }
+        (PaneKind::Main, PaneEvent::Open | PaneEvent::NextTab) if has_tabs => {
+            Transition::Push(PaneKind::Process(DataKind::Details))
+        }
+        (PaneKind::Process(_), PaneEvent::Open) => {
+            Transition::Replace(PaneKind::Process(DataKind::Details))
+        }
+        (PaneKind::Main | PaneKind::Help, PaneEvent::Data(_))
+        | (PaneKind::Main, PaneEvent::Open | PaneEvent::NextTab)
+        | (PaneKind::Help, PaneEvent::Open | PaneEvent::NextTab)
+        | (PaneKind::Process(_), PaneEvent::NextTab) => Transition::Stay,
+    }
+}
+
+/// Current pane and the history of the previous ones.
+#[derive(Debug)]
+pub struct PaneNavigator {
+    current: PaneKind,
+    history: Vec<PaneKind>,
+}
+
+impl PaneNavigator {
+    pub fn new() -> Self {
+        Self {
+            current: PaneKind::Main,
+            history: Vec::new(),
+        }
+    }
+
+    pub fn current(&self) -> PaneKind {
+        self.current
+    }
+
+    /// Apply an event and return true if the pane has changed.
+    ///
+    /// Without tabs, the panes of processes are removed from the history.
+    pub fn navigate(&mut self, event: PaneEvent, has_tabs: bool) -> bool {
+        if !has_tabs {
+            self.close_processes();
+        }
+        let previous = self.current;
+        match transition(self.current, event, has_tabs) {
+            Transition::Stay => (),
+            Transition::Push(pane) => {
+                self.history.push(self.current);
+                self.current = pane;
+            }
+            Transition::Replace(pane) => self.current = pane,
+            Transition::Pop => self.current = self.history.pop().unwrap_or(PaneKind::Main),
+        }
+        self.current != previous
+    }
+
+    /// Leave the panes of processes when there are no more tabs.
+    pub fn close_processes(&mut self) {
+        self.history
+            .retain(|pane| !matches!(pane, PaneKind::Process(_)));
+        if matches!(self.current, PaneKind::Process(_)) {
+            self.current = self.history.pop().unwrap_or(PaneKind::Main);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::{DataKind, PaneEvent, PaneKind, PaneNavigator};
+
+    const DETAILS: PaneKind = PaneKind::Process(DataKind::Details);
+    const LIMITS: PaneKind = PaneKind::Process(DataKind::Limits);
+
+    #[test]
+    fn test_details_and_back() {
+        let mut nav = PaneNavigator::new();
+        assert!(!nav.navigate(PaneEvent::Back, false));
+        assert!(!nav.navigate(PaneEvent::NextTab, false));
+        assert!(nav.navigate(PaneEvent::Open, true));
+        assert_eq!(DETAILS, nav.current());
+        assert!(nav.navigate(PaneEvent::Data(DataKind::Limits), true));
+        assert_eq!(LIMITS, nav.current());
+        assert!(nav.navigate(PaneEvent::Back, true));
+        assert_eq!(DETAILS, nav.current());
+        assert!(nav.navigate(PaneEvent::Back, true));
+        assert_eq!(PaneKind::Main, nav.current());
+    }
+
+    #[test]
+    fn test_help_returns_to_previous_pane() {
+        let mut nav = PaneNavigator::new();
+        nav.navigate(PaneEvent::Open, true);
+        nav.navigate(PaneEvent::Data(DataKind::Threads), true);
+        assert!(nav.navigate(PaneEvent::Help, true));
+        assert!(!nav.navigate(PaneEvent::Help, true));
+        // Data of a process can't be shown from the help.
+        assert!(!nav.navigate(PaneEvent::Data(DataKind::Limits), true));
+        assert!(nav.navigate(PaneEvent::Back, true));
+        assert_eq!(PaneKind::Process(DataKind::Threads), nav.current());
+    }
+
+    #[test]
+    fn test_without_tabs() {
+        let mut nav = PaneNavigator::new();
+        nav.navigate(PaneEvent::NextTab, true);
+        assert_eq!(DETAILS, nav.current());
+        nav.navigate(PaneEvent::Help, true);
+        // The tabs have been closed while in the help.
+        assert!(nav.navigate(PaneEvent::Back, false));
+        assert_eq!(PaneKind::Main, nav.current());
+
+        nav.navigate(PaneEvent::Open, true);
+        nav.close_processes();
+        assert_eq!(PaneKind::Main, nav.current());
+        assert!(!nav.navigate(PaneEvent::Data(DataKind::Limits), false));
+    }
+}
@@ -0,0 +1,179 @@
+// Oprs -- process monitor for Linux
+// Copyright (C) 2025  Laurent Pelecq
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use std::slice::Iter as SliceIter;
+
+/// Open panes with one of them selected.
+///
+/// When the capacity is reached, opening a new tab closes the oldest one.
+#[derive(Debug)]
+pub struct Tabs<T> {
+    items: Vec<T>,
+    current: usize,
+    capacity: usize,
+}
+
+impl<T> Tabs<T> {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            items: Vec::new(),
+            current: 0,
+            capacity: capacity.max(1),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    #[cfg(any(feature = "tui", test))]
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn iter(&self) -> SliceIter<'_, T> {
+        self.items.iter()
+    }
+
+    /// Index of the selected tab.
+    #[cfg(any(feature = "tui", test))]
+    pub fn current_index(&self) -> usize {
+        self.current
+    }
+
+    pub fn current(&self) -> Option<&T> {
+        self.items.get(self.current)
+    }
+
+    /// Select the first tab matching the predicate.
+    pub fn select<P>(&mut self, predicate: P) -> bool
+    where
+        P: Fn(&T) -> bool,
+    {
+        match self.items.iter().position(predicate) {
+            Some(index) => {
+                self.current = index;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Open a new tab and select it.
+    pub fn open(&mut self, item: T) {
+        if self.items.len() >= self.capacity {
+            self.items.remove(0);
+        }
+        self.items.push(item);
+        self.current = self.items.len() - 1;
+    }
+
+    /// Replace the selected tab.
+    pub fn replace(&mut self, item: T) {
+        match self.items.get_mut(self.current) {
+            Some(current) => *current = item,
+            None => self.open(item),
+        }
+    }
+
+    /// Close the selected tab and select the next one.
+    pub fn close(&mut self) {
+        if self.current < self.items.len() {
+            self.items.remove(self.current);
+            self.clamp();
+        }
+    }
+
+    /// Select the next tab, the first one after the last.
+    pub fn next(&mut self) {
+        if !self.items.is_empty() {
+            self.current = (self.current + 1) % self.items.len();
+        }
+    }
+
+    /// Keep only the tabs for which the function returns true.
+    ///
+    /// If the selected tab is removed, the next one is selected.
+    pub fn retain_mut<F>(&mut self, mut func: F)
+    where
+        F: FnMut(&mut T) -> bool,
+    {
+        let current = self.current;
+        let mut index = 0;
+        let mut removed_before = 0;
+        self.items.retain_mut(|item| {
+            let keep = func(item);
+            if !keep && index < current {
+                removed_before += 1;
+            }
+            index += 1;
+            keep
+        });
+        self.current = current - removed_before;
+        self.clamp();
+    }
+
+    fn clamp(&mut self) {
+        self.current = self.current.min(self.items.len().saturating_sub(1));
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::Tabs;
+
+    fn names(tabs: &Tabs<&str>) -> Vec<String> {
+        tabs.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_open_and_cycle() {
+        let mut tabs = Tabs::new(3);
+        assert!(tabs.current().is_none());
+        tabs.open("a");
+        tabs.open("b");
+        assert_eq!(Some(&"b"), tabs.current());
+        tabs.next();
+        assert_eq!(Some(&"a"), tabs.current());
+        assert!(tabs.select(|s| *s == "b"));
+        assert_eq!(1, tabs.current_index());
+        assert!(!tabs.select(|s| *s == "z"));
+        tabs.open("c");
+        tabs.open("d");
+        assert_eq!(vec!["b", "c", "d"], names(&tabs));
+        assert_eq!(Some(&"d"), tabs.current());
+        tabs.replace("e");
+        assert_eq!(vec!["b", "c", "e"], names(&tabs));
+    }
+
+    #[test]
+    fn test_close_and_retain() {
+        let mut tabs = Tabs::new(5);
+        ["a", "b", "c", "d"].into_iter().for_each(|s| tabs.open(s));
+        tabs.select(|s| *s == "c");
+        tabs.retain_mut(|s| *s != "a");
+        assert_eq!(Some(&"c"), tabs.current());
+        tabs.retain_mut(|s| *s != "c");
+        assert_eq!(Some(&"d"), tabs.current());
+        tabs.close();
+        assert_eq!(Some(&"b"), tabs.current());
+        tabs.close();
+        assert!(tabs.is_empty());
+        tabs.close();
+        assert_eq!(0, tabs.len());
+    }
+}
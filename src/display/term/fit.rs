@@ -0,0 +1,153 @@
+// Oprs -- process monitor for Linux
+// Copyright (C) 2025  Laurent Pelecq
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Fit the columns of the metrics in the width of the terminal.
+//!
+//! When the table is too wide, the least important metrics are first reduced
+//! to their first column, then removed. The columns are restored as soon as
+//! there is enough space.
+
+/// Metrics ordered by importance.
+#[derive(Debug)]
+pub(crate) struct ColumnFit {
+    /// First body column and number of columns of each metric in display order.
+    spans: Vec<(usize, usize)>,
+    /// Indexes of the metrics from the most to the least important.
+    priority: Vec<usize>,
+}
+
+impl ColumnFit {
+    /// Metrics are given by name with their number of columns in display order.
+    ///
+    /// The metrics in `priority` come first in this order, then the others in
+    /// display order.
+    pub(crate) fn new<'a, I>(metrics: I, priority: &[String]) -> Self
+    where
+        I: IntoIterator<Item = (&'a str, usize)>,
+    {
+        let mut start = 0;
+        let (names, spans): (Vec<&str>, Vec<(usize, usize)>) = metrics
+            .into_iter()
+            .map(|(name, span)| {
+                let column = (start, span);
+                start += span;
+                (name, column)
+            })
+            .unzip();
+        let mut order = Vec::with_capacity(names.len());
+        priority
+            .iter()
+            .filter_map(|name| names.iter().position(|other| other == name))
+            .chain(0..names.len())
+            .for_each(|index| {
+                if !order.contains(&index) {
+                    order.push(index);
+                }
+            });
+        Self {
+            spans,
+            priority: order,
+        }
+    }
+
+    /// Flags telling if each body column is visible.
+    ///
+    /// Each column takes its width plus the spacing that precedes it.
+    pub(crate) fn visible_columns(
+        &self,
+        widths: &[u16],
+        available: usize,
+        spacing: usize,
+    ) -> Vec<bool> {
+        let cost = |start: usize, end: usize| {
+            widths[start..end]
+                .iter()
+                .map(|width| *width as usize + spacing)
+                .sum::<usize>()
+        };
+        let mut kept = self
+            .spans
+            .iter()
+            .map(|(_, span)| *span)
+            .collect::<Vec<usize>>();
+        let mut total = cost(0, widths.len());
+        for index in self.priority.iter().rev() {
+            let (start, span) = self.spans[*index];
+            if total <= available {
+                break;
+            } else if span > 1 {
+                total -= cost(start + 1, start + span);
+                kept[*index] = 1;
+            }
+        }
+        for index in self.priority.iter().rev() {
+            let (start, _) = self.spans[*index];
+            if total <= available {
+                break;
+            } else if kept[*index] > 0 {
+                total -= cost(start, start + 1);
+                kept[*index] = 0;
+            }
+        }
+        self.spans
+            .iter()
+            .zip(kept)
+            .flat_map(|((_, span), kept)| (0..*span).map(move |column| column < kept))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::ColumnFit;
+
+    const METRICS: [(&str, usize); 3] = [("time:cpu", 1), ("mem:rss", 3), ("mem:vm", 1)];
+
+    const WIDTHS: [u16; 5] = [4, 6, 6, 6, 6];
+
+    #[test]
+    fn test_priority() {
+        let fit = ColumnFit::new(METRICS, &["mem:vm".to_string(), "io:read:call".to_string()]);
+        assert_eq!(vec![2, 0, 1], fit.priority);
+        assert_eq!(vec![(0, 1), (1, 3), (4, 1)], fit.spans);
+    }
+
+    #[test]
+    fn test_visible_columns() {
+        let fit = ColumnFit::new(METRICS, &["mem:vm".to_string()]);
+        assert_eq!(vec![true; 5], fit.visible_columns(&WIDTHS, 100, 1));
+        assert_eq!(
+            vec![true, true, true, true, true],
+            fit.visible_columns(&WIDTHS, 33, 1)
+        );
+        // The least important metric is shrunk first.
+        assert_eq!(
+            vec![true, true, false, false, true],
+            fit.visible_columns(&WIDTHS, 32, 1)
+        );
+        // Then removed.
+        assert_eq!(
+            vec![true, false, false, false, true],
+            fit.visible_columns(&WIDTHS, 18, 1)
+        );
+        assert_eq!(
+            vec![false, false, false, false, true],
+            fit.visible_columns(&WIDTHS, 9, 1)
+        );
+        assert_eq!(vec![false; 5], fit.visible_columns(&WIDTHS, 6, 1));
+    }
+}
@@ -0,0 +1,89 @@
+// Oprs -- process monitor for Linux
+// Copyright (C) 2025  Laurent Pelecq
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Restore the terminal when the application panics.
+//!
+//! In release mode, panics abort the program without running the destructors
+//! that leave the alternate screen and the raw mode. The panic hook restores the
+//! terminal itself before printing the message.
+
+use std::{
+    io::{self, Write},
+    mem,
+    os::fd::AsRawFd,
+    panic,
+    sync::{Mutex, Once},
+};
+use termion::{cursor, screen::ToMainScreen};
+
+use crate::console::DISABLE_PASTE;
+
+/// Terminal attributes before entering raw mode.
+static SAVED_ATTRIBUTES: Mutex<Option<libc::termios>> = Mutex::new(None);
+
+static INSTALL_HOOK: Once = Once::new();
+
+/// Disable the bracketed paste, leave the alternate screen, show the cursor and
+/// reset the terminal attributes.
+///
+/// Does nothing if the terminal has already been restored.
+fn restore() {
+    let saved = match SAVED_ATTRIBUTES.try_lock() {
+        Ok(mut saved) => saved.take(),
+        Err(_) => None,
+    };
+    if let Some(termios) = saved {
+        let mut stdout = io::stdout().lock();
+        let _ = write!(stdout, "{DISABLE_PASTE}{ToMainScreen}{}", cursor::Show);
+        let _ = stdout.flush();
+        unsafe {
+            libc::tcsetattr(stdout.as_raw_fd(), libc::TCSANOW, &termios);
+        }
+    }
+}
+
+/// Guard that restores the terminal on panic or when it is dropped.
+pub(crate) struct TerminalGuard;
+
+impl TerminalGuard {
+    /// Save the terminal attributes. Must be called before entering raw mode.
+    pub(crate) fn new() -> io::Result<Self> {
+        let termios = unsafe {
+            let mut termios = mem::zeroed();
+            if libc::tcgetattr(io::stdout().as_raw_fd(), &mut termios) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            termios
+        };
+        if let Ok(mut saved) = SAVED_ATTRIBUTES.lock() {
+            *saved = Some(termios);
+        }
+        INSTALL_HOOK.call_once(|| {
+            let previous_hook = panic::take_hook();
+            panic::set_hook(Box::new(move |panic_info| {
+                restore();
+                previous_hook(panic_info);
+            }));
+        });
+        Ok(Self)
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        restore();
+    }
+}
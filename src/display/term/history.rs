@@ -0,0 +1,168 @@
+// Oprs -- process monitor for Linux
+// Copyright (C) 2025  Laurent Pelecq
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Last samples kept in memory to step through them while the display is paused.
+//!
+//! The samples are still recorded during the pause. The displayed sample stays
+//! the same until it's too old to be kept.
+
+use chrono::{DateTime, Local};
+use std::{collections::VecDeque, rc::Rc};
+
+#[derive(Debug)]
+pub(crate) struct SampleHistory<T> {
+    /// Samples from the oldest to the newest with the time they were recorded.
+    samples: VecDeque<(DateTime<Local>, Rc<T>)>,
+    /// Maximum number of samples.
+    capacity: usize,
+    /// Index of the displayed sample if paused.
+    position: Option<usize>,
+}
+
+impl<T> SampleHistory<T> {
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            samples: VecDeque::with_capacity(capacity),
+            capacity,
+            position: None,
+        }
+    }
+
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.capacity > 0
+    }
+
+    pub(crate) fn is_paused(&self) -> bool {
+        self.position.is_some()
+    }
+
+    /// Record a new sample, the oldest one is dropped if the history is full.
+    pub(crate) fn push(&mut self, time: DateTime<Local>, sample: T) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.samples.len() == self.capacity {
+            self.samples.pop_front();
+            self.position = self.position.map(|pos| pos.saturating_sub(1));
+        }
+        self.samples.push_back((time, Rc::new(sample)));
+    }
+
+    /// Pause on the newest sample or resume.
+    pub(crate) fn toggle_pause(&mut self) {
+        self.position = match self.position {
+            Some(_) => None,
+            None => self.samples.len().checked_sub(1),
+        };
+    }
+
+    /// Move to an older sample. Return false if there is none.
+    pub(crate) fn step_backward(&mut self) -> bool {
+        match self.position {
+            Some(pos) if pos > 0 => {
+                self.position = Some(pos - 1);
+                true
+            }
+            Some(_) => false,
+            None => {
+                // Pause on the sample before the one displayed.
+                self.position = self.samples.len().checked_sub(2);
+                self.position.is_some()
+            }
+        }
+    }
+
+    /// Move to a newer sample. Return false if there is none.
+    pub(crate) fn step_forward(&mut self) -> bool {
+        match self.position {
+            Some(pos) if pos + 1 < self.samples.len() => {
+                self.position = Some(pos + 1);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Displayed sample if paused.
+    pub(crate) fn current(&self) -> Option<(DateTime<Local>, Rc<T>)> {
+        self.position
+            .and_then(|pos| self.samples.get(pos))
+            .map(|(time, sample)| (*time, Rc::clone(sample)))
+    }
+
+    /// Number of samples between the displayed one and the newest.
+    pub(crate) fn age(&self) -> Option<usize> {
+        self.position.map(|pos| self.samples.len() - pos - 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use chrono::{Local, TimeZone};
+
+    use super::SampleHistory;
+
+    fn push(history: &mut SampleHistory<u32>, value: u32) {
+        let time = Local.timestamp_opt(i64::from(value), 0).unwrap();
+        history.push(time, value);
+    }
+
+    fn current(history: &SampleHistory<u32>) -> Option<u32> {
+        history.current().map(|(_, value)| *value)
+    }
+
+    #[test]
+    fn test_step_through() {
+        let mut history = SampleHistory::new(3);
+        (1..=4).for_each(|value| push(&mut history, value));
+        assert!(!history.is_paused());
+        assert_eq!(None, current(&history));
+        history.toggle_pause();
+        assert_eq!(Some(4), current(&history));
+        assert_eq!(Some(0), history.age());
+        assert!(!history.step_forward());
+        assert!(history.step_backward());
+        assert!(history.step_backward());
+        assert_eq!(Some(2), current(&history));
+        assert_eq!(Some(2), history.age());
+        // Only the last 3 samples are kept.
+        assert!(!history.step_backward());
+        assert!(history.step_forward());
+        assert_eq!(Some(3), current(&history));
+        history.toggle_pause();
+        assert_eq!(None, current(&history));
+    }
+
+    #[test]
+    fn test_record_while_paused() {
+        let mut history = SampleHistory::new(2);
+        push(&mut history, 1);
+        push(&mut history, 2);
+        // Stepping backward pauses the display.
+        assert!(history.step_backward());
+        assert_eq!(Some(1), current(&history));
+        push(&mut history, 3);
+        // The displayed sample is dropped, the oldest is shown.
+        assert_eq!(Some(2), current(&history));
+        assert_eq!(Some(1), history.age());
+        let mut history = SampleHistory::new(0);
+        push(&mut history, 1);
+        history.toggle_pause();
+        assert!(!history.is_enabled());
+        assert!(!history.is_paused());
+    }
+}
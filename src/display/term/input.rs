@@ -14,38 +14,61 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
-use getset::{Getters, Setters};
-use libc::pid_t;
+use getset::{CopyGetters, Getters, Setters};
+use libc::{pid_t, uid_t};
 use smart_default::SmartDefault;
-use std::{collections::BTreeSet, fmt};
-use strum::Display as StrumDisplay;
+use std::{
+    cmp::Ordering,
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+};
+use strum::{Display as StrumDisplay, EnumMessage};
 
 use crate::{
     console::{Event, Key},
-    process::ProcessIdentity,
+    display::Permissions,
+    privileges::lookup_user,
+    process::{parsers::parse_size, ProcessIdentity},
 };
 
 use super::types::BoundedFifo;
 
 /// Standard keys
+const KEY_BREADCRUMBS: Key = Key::Char('b');
+const KEY_CLOSE_TAB: Key = Key::Char('x');
+const KEY_DUMP: Key = Key::Char('d');
 const KEY_ENTER: Key = Key::Char('\n');
 const KEY_ENV: Key = Key::Char('e');
+const KEY_ENV_DIFF: Key = Key::Char('E');
 const KEY_ESCAPE: Key = Key::Esc;
 const KEY_FASTER: Key = Key::Char(KEY_FASTER_CHAR);
 const KEY_FASTER_CHAR: char = '+';
+const KEY_FILES: Key = Key::Char('o');
 const KEY_FILTERS: Key = Key::Char('f');
 const KEY_FILTER_ACTIVE: Key = Key::Char('a');
 const KEY_FILTER_NONE: Key = Key::Char('n');
+const KEY_FILTER_REALTIME: Key = Key::Char('r');
 const KEY_FILTER_USER: Key = Key::Char('u');
 const KEY_GOTO_TBL_BOTTOM: Key = Key::CtrlEnd;
 const KEY_GOTO_TBL_LEFT: Key = Key::Home;
 const KEY_GOTO_TBL_RIGHT: Key = Key::End;
 const KEY_GOTO_TBL_TOP: Key = Key::CtrlHome;
+const KEY_GOTO_PID: Key = Key::Char(':');
+const KEY_GROUP: Key = Key::Char('g');
 const KEY_HELP: Key = Key::Char('?');
+const KEY_IO_DEVICES: Key = Key::Char('i');
 const KEY_LIMITS: Key = Key::Char('l');
+const KEY_LOG_ERROR: Key = Key::Char('L');
+const KEY_MAPS: Key = Key::Char('M');
+const KEY_MOUNTS: Key = Key::Char('m');
+const KEY_NAMESPACES: Key = Key::Char('n');
 const KEY_MARK_CLEAR: Key = Key::Ctrl('c');
 const KEY_MARK_TOGGLE: Key = Key::Char(' ');
+const KEY_NEXT_TAB: Key = Key::Char('\t');
+const KEY_PAUSE: Key = Key::Char('z');
+const KEY_PIN_TOGGLE: Key = Key::Char('P');
 const KEY_QUIT: Key = Key::Char('q');
+const KEY_RELATIVE: Key = Key::Char('%');
 const KEY_SCOPE: Key = Key::Char('s');
 const KEY_SEARCH: Key = Key::Char('/');
 const KEY_SEARCH_CANCEL: Key = Key::Ctrl('c');
@@ -56,6 +79,13 @@ const KEY_SELECT_PREVIOUS: Key = Key::Char(KEY_SELECT_PREVIOUS_CHAR);
 const KEY_SELECT_PREVIOUS_CHAR: char = 'N';
 const KEY_SELECT_ROOT_PID: Key = Key::Char('r');
 const KEY_UNSELECT_ROOT_PID: Key = Key::Char('R');
+const KEY_STEP_BACKWARD: Key = Key::Char(KEY_STEP_BACKWARD_CHAR);
+const KEY_STEP_BACKWARD_CHAR: char = '<';
+const KEY_STEP_FORWARD: Key = Key::Char(KEY_STEP_FORWARD_CHAR);
+const KEY_STEP_FORWARD_CHAR: char = '>';
+const KEY_THREADS: Key = Key::Char('t');
+const KEY_WATERMARKS: Key = Key::Char('w');
+const KEY_WIDEN: Key = Key::Char('y');
 const KEY_SLOWER: Key = Key::Char(KEY_SLOWER_CHAR);
 const KEY_SLOWER_CHAR: char = '-';
 
@@ -72,24 +102,52 @@ macro_rules! try_return {
 #[derive(Clone, Debug)]
 pub enum Action {
     None,
+    Breadcrumbs,
+    BreadcrumbNext,
+    BreadcrumbPrevious,
     ChangeScope,
+    CloseTab,
     DivideTimeout(u16),
+    Dump,
     Filters,
     FilterNone,
     FilterUser,
     FilterActive,
+    FilterRealTime,
+    FollowLink,
     GotoTableBottom,
     GotoTableLeft,
     GotoTableRight,
     GotoTableTop,
+    GotoPidCancel,
+    GotoPidEnter,
+    GotoPidExit,
+    GotoPidPop,
+    GotoPidPush(char),
+    GotoPidPaste(String),
+    LinkNext,
+    LinkPrevious,
+    LogError,
     SwitchToHelp,
     SwitchBack,
     SwitchToDetails,
     SwitchToLimits,
     SwitchToEnvironment,
+    SwitchToFiles,
+    SwitchToMaps,
+    SwitchToIoDevices,
+    SwitchToMounts,
+    SwitchToNamespaces,
+    SwitchToThreads,
     ClearMarks,
+    CompareEnvironments,
     ToggleMarks,
+    ToggleGroups,
+    TogglePin,
+    ToggleRelative,
+    ToggleWatermarks,
     MultiplyTimeout(u16),
+    NextTab,
     Quit,
     ScrollLeft,
     ScrollLineDown,
@@ -97,6 +155,7 @@ pub enum Action {
     ScrollPageDown,
     ScrollPageUp,
     ScrollRight,
+    SavedSearch(char),
     SearchCancel,
     SearchEnter,
     SearchExit,
@@ -105,25 +164,66 @@ pub enum Action {
     SelectPrevious,
     SelectParent,
     SelectRootPid,
+    SelectBreadcrumb,
     UnselectRootPid,
     SearchPush(char),
+    SearchPaste(String),
+    StepBackward,
+    StepForward,
+    TogglePause,
+    Widen,
 }
 
 /// Keymap
-#[derive(Clone, Copy, Debug, StrumDisplay, PartialEq)]
+///
+/// The message is a hint displayed in the status bar while the keymap is active.
+#[derive(Clone, Copy, Debug, StrumDisplay, EnumMessage, PartialEq)]
 pub enum KeyMap {
     #[strum(serialize = "main")]
     Main,
     #[strum(serialize = "help")]
     Help,
-    #[strum(serialize = "filters")]
+    #[strum(
+        serialize = "filters",
+        message = "Filters: show all processes, only the user's, the active or the real-time ones"
+    )]
     Filters,
-    #[strum(serialize = "incremental search")]
+    #[strum(
+        serialize = "incremental search",
+        message = "Search: type a name, a PID or user:NAME, enter to keep the matches"
+    )]
     IncrementalSearch,
+    #[strum(
+        serialize = "go to PID",
+        message = "Go to PID: type a PID, enter to select it"
+    )]
+    GotoPid,
+    #[strum(
+        serialize = "widen",
+        message = "Widen: show all the processes to reach the PID"
+    )]
+    Widen,
     #[strum(serialize = "details")]
     Details,
     #[strum(serialize = "process")]
     Process,
+    #[strum(
+        serialize = "links",
+        message = "Links: up and down to select a process, enter to show its details"
+    )]
+    Links,
+    #[strum(
+        serialize = "breadcrumbs",
+        message = "Breadcrumbs: move to an ancestor and enter to make it the root"
+    )]
+    Breadcrumbs,
+}
+
+impl KeyMap {
+    /// Hint describing what the keymap does.
+    pub fn hint(self) -> Option<&'static str> {
+        self.get_message()
+    }
 }
 
 impl KeyMap {
@@ -134,12 +234,36 @@ impl KeyMap {
             KeyMap::IncrementalSearch => match evt {
                 Event::Key(KEY_ENTER) => Action::SearchExit,
                 Event::Key(Key::Char(c)) => Action::SearchPush(c),
+                Event::Paste(text) => Action::SearchPaste(text),
                 Event::Key(Key::Backspace) => Action::SearchPop,
                 Event::Key(KEY_SEARCH_CANCEL) => Action::SearchCancel,
                 _ => Action::None,
             },
+            KeyMap::GotoPid => match evt {
+                Event::Key(KEY_ENTER) => Action::GotoPidExit,
+                Event::Key(Key::Char(c)) if c.is_ascii_digit() => Action::GotoPidPush(c),
+                Event::Paste(text) => Action::GotoPidPaste(text),
+                Event::Key(Key::Backspace) => Action::GotoPidPop,
+                Event::Key(KEY_SEARCH_CANCEL) | Event::Key(KEY_ESCAPE) => Action::GotoPidCancel,
+                _ => Action::None,
+            },
+            KeyMap::Widen => match evt {
+                Event::Key(KEY_WIDEN) => Action::Widen,
+                _ => Action::GotoPidCancel,
+            },
+            KeyMap::Links => match evt {
+                Event::Key(KEY_QUIT) | Event::Key(KEY_ESCAPE) => Action::SwitchBack,
+                Event::Key(KEY_ENTER) => Action::FollowLink,
+                Event::Key(KEY_LOG_ERROR) => Action::LogError,
+                Event::Key(Key::Down) => Action::LinkNext,
+                Event::Key(Key::Up) => Action::LinkPrevious,
+                Event::Key(Key::PageDown) => Action::ScrollPageDown,
+                Event::Key(Key::PageUp) => Action::ScrollPageUp,
+                _ => Action::None,
+            },
             KeyMap::Help | KeyMap::Process => match evt {
                 Event::Key(KEY_QUIT) | Event::Key(KEY_ESCAPE) => Action::SwitchBack,
+                Event::Key(KEY_LOG_ERROR) if self == KeyMap::Process => Action::LogError,
                 Event::Key(Key::PageDown) => Action::ScrollPageDown,
                 Event::Key(Key::PageUp) => Action::ScrollPageUp,
                 _ => Action::None,
@@ -149,26 +273,55 @@ impl KeyMap {
                 Event::Key(KEY_SELECT_PARENT) => Action::SelectParent,
                 Event::Key(KEY_LIMITS) => Action::SwitchToLimits,
                 Event::Key(KEY_ENV) => Action::SwitchToEnvironment,
+                Event::Key(KEY_MOUNTS) => Action::SwitchToMounts,
+                Event::Key(KEY_IO_DEVICES) => Action::SwitchToIoDevices,
+                Event::Key(KEY_FILES) => Action::SwitchToFiles,
+                Event::Key(KEY_MAPS) => Action::SwitchToMaps,
+                Event::Key(KEY_NAMESPACES) => Action::SwitchToNamespaces,
+                Event::Key(KEY_THREADS) => Action::SwitchToThreads,
+                Event::Key(KEY_DUMP) => Action::Dump,
+                Event::Key(KEY_NEXT_TAB) => Action::NextTab,
+                Event::Key(KEY_CLOSE_TAB) => Action::CloseTab,
+                Event::Key(KEY_LOG_ERROR) => Action::LogError,
                 Event::Key(Key::PageDown) => Action::ScrollPageDown,
                 Event::Key(Key::PageUp) => Action::ScrollPageUp,
                 _ => Action::None,
             },
+            KeyMap::Breadcrumbs => match evt {
+                Event::Key(KEY_QUIT) | Event::Key(KEY_ESCAPE) => Action::SwitchBack,
+                Event::Key(KEY_ENTER) => Action::SelectBreadcrumb,
+                Event::Key(Key::Left) => Action::BreadcrumbPrevious,
+                Event::Key(Key::Right) => Action::BreadcrumbNext,
+                _ => Action::None,
+            },
             KeyMap::Filters => match evt {
                 Event::Key(KEY_FILTER_NONE) => Action::FilterNone,
                 Event::Key(KEY_FILTER_USER) => Action::FilterUser,
                 Event::Key(KEY_FILTER_ACTIVE) => Action::FilterActive,
+                Event::Key(KEY_FILTER_REALTIME) => Action::FilterRealTime,
                 _ => Action::None,
             },
             KeyMap::Main => match evt {
+                Event::Key(KEY_BREADCRUMBS) => Action::Breadcrumbs,
                 Event::Key(KEY_FASTER) => Action::DivideTimeout(2),
                 Event::Key(KEY_GOTO_TBL_BOTTOM) => Action::GotoTableBottom,
                 Event::Key(KEY_GOTO_TBL_LEFT) => Action::GotoTableLeft,
                 Event::Key(KEY_GOTO_TBL_RIGHT) => Action::GotoTableRight,
                 Event::Key(KEY_GOTO_TBL_TOP) => Action::GotoTableTop,
+                Event::Key(KEY_GOTO_PID) => Action::GotoPidEnter,
                 Event::Key(KEY_ENTER) => Action::SwitchToDetails,
+                Event::Key(KEY_NEXT_TAB) => Action::NextTab,
                 Event::Key(KEY_HELP) => Action::SwitchToHelp,
                 Event::Key(KEY_MARK_CLEAR) => Action::ClearMarks,
                 Event::Key(KEY_MARK_TOGGLE) => Action::ToggleMarks,
+                Event::Key(KEY_ENV_DIFF) => Action::CompareEnvironments,
+                Event::Key(KEY_PIN_TOGGLE) => Action::TogglePin,
+                Event::Key(KEY_PAUSE) => Action::TogglePause,
+                Event::Key(KEY_STEP_BACKWARD) => Action::StepBackward,
+                Event::Key(KEY_STEP_FORWARD) => Action::StepForward,
+                Event::Key(KEY_WATERMARKS) => Action::ToggleWatermarks,
+                Event::Key(KEY_GROUP) => Action::ToggleGroups,
+                Event::Key(KEY_RELATIVE) => Action::ToggleRelative,
                 Event::Key(KEY_FILTERS) => Action::Filters,
                 Event::Key(KEY_SCOPE) => Action::ChangeScope,
                 Event::Key(KEY_SEARCH) => Action::SearchEnter,
@@ -184,6 +337,7 @@ impl KeyMap {
                 Event::Key(Key::Up) => Action::ScrollLineUp,
                 Event::Key(Key::Left) => Action::ScrollLeft,
                 Event::Key(Key::Right) => Action::ScrollRight,
+                Event::Key(Key::Char(c @ '1'..='9')) => Action::SavedSearch(c),
                 _ => Action::None,
             },
         }
@@ -251,6 +405,7 @@ impl MenuEntry {
             Key::Insert => "Ins".to_string(),
             Key::F(num) => format!("F{num}"),
             Key::Char('\t') => "⇥".to_string(),
+            Key::Char('\n') => "⏎".to_string(),
             Key::Char(' ') => "Spc".to_string(),
             Key::Char(ch) => format!("{ch}"),
             Key::Alt(ch) => format!("M-{ch}"),
@@ -261,9 +416,14 @@ impl MenuEntry {
     }
 }
 
+/// Menu entry to copy the error displayed in a pane to the log.
+pub fn log_error_entry(keymap: KeyMap) -> MenuEntry {
+    MenuEntry::with_key(KEY_LOG_ERROR, "Log", KeyMapSet::OnlyIn(keymap))
+}
+
 /// Return the menu
-pub fn menu() -> Vec<MenuEntry> {
-    vec![
+pub fn menu(permissions: &Permissions, saved_searches: bool, history: bool) -> Vec<MenuEntry> {
+    let mut entries = vec![
         MenuEntry::with_key(KEY_QUIT, "Quit", KeyMapSet::ExceptIn(KeyMap::Filters)),
         MenuEntry::with_key(KEY_HELP, "Help", KeyMapSet::OnlyIn(KeyMap::Main)),
         MenuEntry::new(
@@ -272,8 +432,22 @@ pub fn menu() -> Vec<MenuEntry> {
             KeyMapSet::OnlyIn(KeyMap::Main),
         ),
         MenuEntry::with_key(KEY_SEARCH, "Search", KeyMapSet::OnlyIn(KeyMap::Main)),
+        MenuEntry::with_key(KEY_GOTO_PID, "Go to PID", KeyMapSet::OnlyIn(KeyMap::Main)),
         MenuEntry::with_key(KEY_LIMITS, "Limits", KeyMapSet::OnlyIn(KeyMap::Details)),
         MenuEntry::with_key(KEY_ENV, "Environment", KeyMapSet::OnlyIn(KeyMap::Details)),
+        MenuEntry::with_key(KEY_MOUNTS, "Mounts", KeyMapSet::OnlyIn(KeyMap::Details)),
+        MenuEntry::with_key(KEY_IO_DEVICES, "I/O", KeyMapSet::OnlyIn(KeyMap::Details)),
+        MenuEntry::with_key(KEY_FILES, "Files", KeyMapSet::OnlyIn(KeyMap::Details)),
+        MenuEntry::with_key(KEY_MAPS, "Maps", KeyMapSet::OnlyIn(KeyMap::Details)),
+        MenuEntry::with_key(
+            KEY_NAMESPACES,
+            "Namespaces",
+            KeyMapSet::OnlyIn(KeyMap::Details),
+        ),
+        MenuEntry::with_key(KEY_THREADS, "Threads", KeyMapSet::OnlyIn(KeyMap::Details)),
+        MenuEntry::with_key(KEY_DUMP, "Dump", KeyMapSet::OnlyIn(KeyMap::Details)),
+        MenuEntry::with_key(KEY_NEXT_TAB, "Next tab", KeyMapSet::OnlyIn(KeyMap::Details)),
+        MenuEntry::with_key(KEY_CLOSE_TAB, "Close", KeyMapSet::OnlyIn(KeyMap::Details)),
         MenuEntry::with_key(KEY_FILTERS, "Filters", KeyMapSet::OnlyIn(KeyMap::Main)),
         MenuEntry::with_key(
             KEY_SELECT_PARENT,
@@ -281,11 +455,20 @@ pub fn menu() -> Vec<MenuEntry> {
             KeyMapSet::OnlyIn(KeyMap::Details),
         ),
         MenuEntry::with_key(KEY_SELECT_ROOT_PID, "Root", KeyMapSet::OnlyIn(KeyMap::Main)),
+        MenuEntry::with_key(KEY_PIN_TOGGLE, "Pin", KeyMapSet::OnlyIn(KeyMap::Main)),
+        MenuEntry::with_key(KEY_WATERMARKS, "Peaks", KeyMapSet::OnlyIn(KeyMap::Main)),
+        MenuEntry::with_key(KEY_GROUP, "Group", KeyMapSet::OnlyIn(KeyMap::Main)),
+        MenuEntry::with_key(KEY_RELATIVE, "Relative", KeyMapSet::OnlyIn(KeyMap::Main)),
         MenuEntry::new(
-            format!("{KEY_FASTER_CHAR}/{KEY_SLOWER_CHAR}"),
-            "Speed",
-            KeyMapSet::OnlyIn(KeyMap::Main),
+            format!(
+                "{}/{}",
+                MenuEntry::key_name(Key::Left),
+                MenuEntry::key_name(Key::Right)
+            ),
+            "Move",
+            KeyMapSet::OnlyIn(KeyMap::Breadcrumbs),
         ),
+        MenuEntry::with_key(KEY_ENTER, "Root", KeyMapSet::OnlyIn(KeyMap::Breadcrumbs)),
         MenuEntry::with_key(KEY_FILTER_NONE, "None", KeyMapSet::OnlyIn(KeyMap::Filters)),
         MenuEntry::with_key(KEY_FILTER_USER, "User", KeyMapSet::OnlyIn(KeyMap::Filters)),
         MenuEntry::with_key(
@@ -293,7 +476,39 @@ pub fn menu() -> Vec<MenuEntry> {
             "Active",
             KeyMapSet::OnlyIn(KeyMap::Filters),
         ),
-    ]
+        MenuEntry::with_key(
+            KEY_FILTER_REALTIME,
+            "Real-time",
+            KeyMapSet::OnlyIn(KeyMap::Filters),
+        ),
+    ];
+    if permissions.change_delay {
+        entries.push(MenuEntry::new(
+            format!("{KEY_FASTER_CHAR}/{KEY_SLOWER_CHAR}"),
+            "Speed",
+            KeyMapSet::OnlyIn(KeyMap::Main),
+        ));
+    }
+    if history {
+        entries.push(MenuEntry::with_key(
+            KEY_PAUSE,
+            "Pause",
+            KeyMapSet::OnlyIn(KeyMap::Main),
+        ));
+        entries.push(MenuEntry::new(
+            format!("{KEY_STEP_BACKWARD_CHAR}/{KEY_STEP_FORWARD_CHAR}"),
+            "History",
+            KeyMapSet::OnlyIn(KeyMap::Main),
+        ));
+    }
+    if saved_searches {
+        entries.push(MenuEntry::new(
+            String::from("1-9"),
+            "Saved",
+            KeyMapSet::OnlyIn(KeyMap::Main),
+        ));
+    }
+    entries
 }
 
 /// Search bar state
@@ -314,6 +529,178 @@ impl fmt::Display for SearchState {
     }
 }
 
+/// State of the prompt to go to a PID.
+#[derive(Debug, Default)]
+pub enum PidPrompt {
+    #[default]
+    None,
+    /// The PID is being typed.
+    Input(String),
+    /// The process exists but is not in the table.
+    Missing(pid_t),
+    /// The process doesn't exist.
+    Unknown(pid_t),
+}
+
+impl fmt::Display for PidPrompt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        match self {
+            Self::None => Ok(()),
+            Self::Input(digits) => write!(f, "Go to PID: {digits}"),
+            Self::Missing(pid) => write!(
+                f,
+                "{pid}: not visible—shown under different scope, widen? (y/n)"
+            ),
+            Self::Unknown(pid) => write!(f, "{pid}: no such process"),
+        }
+    }
+}
+
+/// Prefix of a search pattern matching the owner of the processes.
+const SEARCH_USER_PREFIX: &str = "user:";
+
+/// Field of a process matched by a search.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SearchField {
+    Name,
+    Pid,
+    Owner,
+    /// Computed value at the given index.
+    Value(usize),
+}
+
+/// Column of the table where the values can be searched.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SearchColumn {
+    /// Name of the metric with the aggregation (ex: mem:vm+max).
+    pub name: String,
+    /// Whether the values are in per thousand and the threshold in percent.
+    pub per_mille: bool,
+}
+
+/// Line of the table that can be searched.
+pub trait SearchLine: ProcessIdentity {
+    /// Computed value at the given index.
+    fn value(&self, _index: usize) -> Option<u64> {
+        None
+    }
+}
+
+/// Search pattern.
+#[derive(Debug, PartialEq, Eq)]
+enum SearchPattern {
+    /// Substring of the process name.
+    Name(String),
+    /// Prefix of the PID.
+    Pid(String),
+    /// Owner of the process by name or ID. None if the user doesn't exist.
+    Owner(Option<uid_t>),
+    /// Values greater or less than a threshold in the column of a metric or in
+    /// the first visible column. The threshold is None while it's incomplete.
+    /// The column is the index of the computed value and whether it's in per
+    /// thousand.
+    Value {
+        ordering: Ordering,
+        threshold: Option<u64>,
+        metric: Option<String>,
+        column: Option<(usize, bool)>,
+    },
+}
+
+impl SearchPattern {
+    fn new(pattern: &str) -> Self {
+        if let Some(user) = pattern.strip_prefix(SEARCH_USER_PREFIX) {
+            SearchPattern::Owner(
+                user.parse::<uid_t>()
+                    .ok()
+                    .or_else(|| lookup_user(user).ok().map(|(uid, _)| uid)),
+            )
+        } else if let Some(pos) = pattern.find(['>', '<']) {
+            let (metric, value) = pattern.split_at(pos);
+            let metric = metric.trim();
+            SearchPattern::Value {
+                ordering: if value.starts_with('>') {
+                    Ordering::Greater
+                } else {
+                    Ordering::Less
+                },
+                threshold: Self::threshold(&value[1..]),
+                metric: (!metric.is_empty()).then(|| metric.to_string()),
+                column: None,
+            }
+        } else if pattern.starts_with(|c: char| c.is_ascii_digit()) {
+            SearchPattern::Pid(pattern.to_string())
+        } else {
+            SearchPattern::Name(pattern.to_string())
+        }
+    }
+
+    /// Threshold with an optional unit (ex: 1G).
+    fn threshold(value: &str) -> Option<u64> {
+        parse_size(&value.trim().to_lowercase()).ok()
+    }
+
+    /// Find the column of the values given the first visible column.
+    ///
+    /// A metric without aggregation is the first column of the metric.
+    fn resolve(self, columns: &[SearchColumn], visible: Option<usize>) -> Self {
+        match self {
+            SearchPattern::Value {
+                ordering,
+                threshold,
+                metric,
+                ..
+            } => {
+                let index = match metric {
+                    Some(ref metric) => columns.iter().position(|column| {
+                        column.name == *metric || column.name.split('+').next() == Some(metric)
+                    }),
+                    None => visible,
+                };
+                let column = index
+                    .and_then(|index| columns.get(index).map(|column| (index, column.per_mille)));
+                SearchPattern::Value {
+                    ordering,
+                    threshold,
+                    metric,
+                    column,
+                }
+            }
+            _ => self,
+        }
+    }
+
+    /// The field of the process that matches if any.
+    fn matches<P: SearchLine>(&self, pi: &P) -> Option<SearchField> {
+        let (matched, field) = match self {
+            SearchPattern::Value {
+                ordering,
+                threshold: Some(threshold),
+                column: Some((index, per_mille)),
+                ..
+            } => {
+                let threshold = if *per_mille {
+                    threshold.saturating_mul(10)
+                } else {
+                    *threshold
+                };
+                let matched = pi
+                    .value(*index)
+                    .is_some_and(|value| value.cmp(&threshold) == *ordering);
+                (matched, SearchField::Value(*index))
+            }
+            SearchPattern::Value { .. } => return None,
+            SearchPattern::Name(name) => (pi.name().contains(name.as_str()), SearchField::Name),
+            SearchPattern::Pid(prefix) => (
+                pi.pid().to_string().starts_with(prefix.as_str()),
+                SearchField::Pid,
+            ),
+            SearchPattern::Owner(uid) => (uid.is_some() && pi.uid() == *uid, SearchField::Owner),
+        };
+        matched.then_some(field)
+    }
+}
+
 /// Search action
 #[derive(Clone, Copy, Debug, SmartDefault)]
 pub enum BookmarkAction {
@@ -339,6 +726,8 @@ pub enum BookmarkAction {
     ClosestMatch,
     /// Invert the marks of the matched lines or the current selection.
     ToggleMarks,
+    /// Select the line of a PID.
+    Pid(pid_t),
 }
 
 /// Action to edit search bar
@@ -410,11 +799,7 @@ impl LinePid {
     }
 
     fn distance(a: usize, b: usize) -> usize {
-        if a < b {
-            b - a
-        } else {
-            a - b
-        }
+        a.abs_diff(b)
     }
 
     fn pid_index_in(&self, v: &[LinePid]) -> Option<usize> {
@@ -477,7 +862,7 @@ impl LinePid {
 }
 
 /// Search bar
-#[derive(Debug, Default, Getters, Setters)]
+#[derive(Debug, Default, CopyGetters, Getters, Setters)]
 pub struct Bookmarks {
     /// PID at the line under the cursor.
     #[getset(get = "pub")]
@@ -488,9 +873,21 @@ pub struct Bookmarks {
     /// PIDs marked in the selection.
     #[getset(get = "pub")]
     marks: BTreeSet<pid_t>,
+    /// PIDs pinned at the top of the table.
+    #[getset(get = "pub")]
+    pins: BTreeSet<pid_t>,
     /// Action for next round.
     #[getset(get = "pub", set = "pub")]
     action: BookmarkAction,
+    /// PID that couldn't be selected because it is not in the table.
+    #[getset(get_copy = "pub")]
+    missing_pid: Option<pid_t>,
+    /// Columns where the values can be searched.
+    #[getset(set = "pub")]
+    search_columns: Vec<SearchColumn>,
+    /// First visible column where the values are searched by default.
+    #[getset(set = "pub")]
+    visible_column: Option<usize>,
 }
 
 impl Bookmarks {
@@ -526,6 +923,55 @@ impl Bookmarks {
         self.marks.clear();
     }
 
+    /// Check if PID is pinned.
+    pub fn is_pinned(&self, pid: pid_t) -> bool {
+        self.pins.contains(&pid)
+    }
+
+    /// Pin or unpin the selected PID.
+    pub fn toggle_pin(&mut self) {
+        if let Some(selected) = self.selected {
+            if !self.pins.remove(&selected.pid) {
+                self.pins.insert(selected.pid);
+            }
+        }
+    }
+
+    /// Keep only pins on existing PIDs.
+    ///
+    /// The pins on processes hidden by a filter or a search are kept as long as the
+    /// process is alive.
+    pub fn retain_pins<F>(&mut self, is_alive: F)
+    where
+        F: Fn(pid_t) -> bool,
+    {
+        self.pins.retain(|pid| is_alive(*pid));
+    }
+
+    /// Number of pinned lines at the top.
+    ///
+    /// The pinned processes that are hidden by a filter or a search are not counted.
+    pub fn pinned_count<P: ProcessIdentity>(&self, lines: &[P]) -> usize {
+        lines
+            .iter()
+            .take_while(|line| self.is_pinned(line.pid()))
+            .count()
+    }
+
+    /// Reorder the lines with pinned processes first.
+    ///
+    /// The relative order of pinned and unpinned lines is preserved.
+    pub fn pinned_first<I, P>(&self, lines: I) -> Vec<P>
+    where
+        I: Iterator<Item = P>,
+        P: ProcessIdentity,
+    {
+        let (mut pinned, others): (Vec<P>, Vec<P>) =
+            lines.partition(|pi| self.pins.contains(&pi.pid()));
+        pinned.extend(others);
+        pinned
+    }
+
     /// Start an incremental search.
     pub fn incremental_search(&mut self) {
         match self.search {
@@ -543,6 +989,13 @@ impl Bookmarks {
         }
     }
 
+    /// Replace the search by a fixed string search.
+    pub fn set_fixed_search(&mut self, pattern: &str) {
+        self.search = Some(SearchBar {
+            state: SearchState::Fixed(pattern.to_string()),
+        });
+    }
+
     /// Clear search
     pub fn clear_search(&mut self) {
         self.search = None;
@@ -663,22 +1116,23 @@ impl Bookmarks {
 
     /// Execute the action and return the vertical offset.
     ///
-    /// * `occurrences` - The set of matching pid in case of search.
+    /// * `occurrences` - The matching pid and field in case of search.
     /// * `lines` - The lines of process identities.
     /// * `top` - The first visible line (current vertical offset).
     /// * `height` - The height of the visible area.
     pub fn execute<I, P>(
         &mut self,
-        occurrences: &mut BTreeSet<pid_t>,
+        occurrences: &mut BTreeMap<pid_t, SearchField>,
         lines: I,
         top: usize,
         height: usize,
     ) -> usize
     where
         I: Iterator<Item = P>,
-        P: ProcessIdentity,
+        P: SearchLine,
     {
         let action = self.action;
+        self.missing_pid = None;
         self.action = match self.search {
             Some(_) => BookmarkAction::ClosestMatch,
             None => BookmarkAction::None,
@@ -693,7 +1147,9 @@ impl Bookmarks {
         let mut previous_pids = BoundedFifo::new(page_size);
         let mut matches = Vec::new();
         let mut marks = Vec::new();
-        let pattern = self.search_pattern();
+        let pattern = self.search_pattern().map(|pattern| {
+            SearchPattern::new(&pattern).resolve(&self.search_columns, self.visible_column)
+        });
 
         for (lineno, pi) in lines.enumerate() {
             let pid = pi.pid();
@@ -722,6 +1178,10 @@ impl Bookmarks {
                 },
                 BookmarkAction::FirstLine => return self.select(lineno, pid, top, height, true),
                 BookmarkAction::LastLine => last_lineno = Some(lineno),
+                BookmarkAction::Pid(target_pid) if pid == target_pid => {
+                    return self.select(lineno, pid, top, height, true)
+                }
+                BookmarkAction::Pid(_) => (),
                 BookmarkAction::PreviousLine | BookmarkAction::PreviousPage => {
                     try_return!(self.select_previous(&previous_pids, lineno, pid, top, height))
                 }
@@ -738,11 +1198,9 @@ impl Bookmarks {
                     {
                         return self.select(lineno, pid, top, height, true);
                     }
-                    if let Some(pattern) = pattern.as_ref() {
-                        if pi.name().contains(pattern) {
-                            matches.push(LinePid::new(lineno, pid));
-                            occurrences.insert(pid);
-                        }
+                    if let Some(field) = pattern.as_ref().and_then(|pattern| pattern.matches(&pi)) {
+                        matches.push(LinePid::new(lineno, pid));
+                        occurrences.insert(pid, field);
                     }
                 }
             }
@@ -777,6 +1235,10 @@ impl Bookmarks {
                     0
                 }
             },
+            BookmarkAction::Pid(pid) => {
+                self.missing_pid = Some(pid);
+                top
+            }
             BookmarkAction::Previous => {
                 self.change_selection_in_ring(ring, |s, ring| s.previous_in(ring).copied())
             }
@@ -792,7 +1254,7 @@ impl Bookmarks {
                         self.toggle_mark(selected.pid);
                     }
                 } else {
-                    occurrences.iter().for_each(|pid| self.toggle_mark(*pid));
+                    occurrences.keys().for_each(|pid| self.toggle_mark(*pid));
                     self.clear_search();
                     occurrences.clear();
                 }
@@ -802,3 +1264,159 @@ impl Bookmarks {
         Bookmarks::recenter(new_top, top, height, match_count > 0)
     }
 }
+
+#[cfg(test)]
+mod tests {
+
+    use libc::pid_t;
+    use std::{cmp::Ordering, collections::BTreeMap};
+
+    use super::{
+        BookmarkAction, Bookmarks, KeyMap, SearchColumn, SearchField, SearchLine, SearchPattern,
+    };
+    use crate::process::ProcessIdentity;
+
+    struct Identity(&'static str, pid_t, Option<u32>);
+
+    impl ProcessIdentity for Identity {
+        fn name(&self) -> &str {
+            self.0
+        }
+
+        fn pid(&self) -> pid_t {
+            self.1
+        }
+
+        fn uid(&self) -> Option<u32> {
+            self.2
+        }
+    }
+
+    impl SearchLine for Identity {
+        fn value(&self, index: usize) -> Option<u64> {
+            (index == 0).then_some(self.1 as u64 * 1_000_000)
+        }
+    }
+
+    #[test]
+    fn test_keymap_hint() {
+        assert!(KeyMap::Main.hint().is_none());
+        for keymap in [
+            KeyMap::Filters,
+            KeyMap::IncrementalSearch,
+            KeyMap::GotoPid,
+            KeyMap::Widen,
+            KeyMap::Breadcrumbs,
+        ] {
+            assert!(keymap.hint().is_some(), "{keymap}: no hint");
+        }
+    }
+
+    #[test]
+    fn test_search_pattern() {
+        let pi = Identity("bash123", 4567, Some(0));
+        assert_eq!(
+            Some(SearchField::Name),
+            SearchPattern::new("sh1").matches(&pi)
+        );
+        assert_eq!(
+            Some(SearchField::Pid),
+            SearchPattern::new("45").matches(&pi)
+        );
+        assert_eq!(None, SearchPattern::new("123").matches(&pi));
+        assert_eq!(
+            SearchPattern::Owner(Some(0)),
+            SearchPattern::new("user:root")
+        );
+        assert_eq!(
+            Some(SearchField::Owner),
+            SearchPattern::new("user:0").matches(&pi)
+        );
+        assert_eq!(None, SearchPattern::new("user:1000").matches(&pi));
+        assert_eq!(None, SearchPattern::new("user:").matches(&pi));
+    }
+
+    #[test]
+    fn test_value_pattern() {
+        let pi = Identity("bash", 4567, None);
+        assert_eq!(
+            SearchPattern::Value {
+                ordering: Ordering::Greater,
+                threshold: Some(1_000_000_000),
+                metric: None,
+                column: None
+            },
+            SearchPattern::new(">1G")
+        );
+        assert_eq!(
+            SearchPattern::Value {
+                ordering: Ordering::Less,
+                threshold: None,
+                metric: Some("mem:vm".to_string()),
+                column: None
+            },
+            SearchPattern::new("mem:vm <")
+        );
+        let columns = [
+            SearchColumn {
+                name: "mem:vm".to_string(),
+                per_mille: false,
+            },
+            SearchColumn {
+                name: "time:cpu+ratio".to_string(),
+                per_mille: true,
+            },
+        ];
+        let matches = |pattern: &str, visible| {
+            SearchPattern::new(pattern)
+                .resolve(&columns, visible)
+                .matches(&pi)
+        };
+        assert_eq!(None, matches(">4g", None));
+        assert_eq!(Some(SearchField::Value(0)), matches(">4g", Some(0)));
+        assert_eq!(None, matches("<4g", Some(0)));
+        assert_eq!(None, matches(">4g", Some(1)));
+        assert_eq!(Some(SearchField::Value(0)), matches("mem:vm>4g", Some(1)));
+        assert_eq!(None, matches("time:cpu>1g", Some(0)));
+        assert_eq!(None, matches("unknown>1", Some(0)));
+    }
+
+    #[test]
+    fn test_goto_pid() {
+        let lines = || (1..=100).map(|pid| Identity("proc", pid, None));
+        let mut occurrences = BTreeMap::new();
+        let mut bookmarks = Bookmarks::default();
+        bookmarks.set_action(BookmarkAction::Pid(80));
+        let top = bookmarks.execute(&mut occurrences, lines(), 0, 10);
+        assert_eq!(Some(80), bookmarks.selected().map(|lp| lp.pid));
+        assert_eq!(74, top);
+        assert_eq!(None, bookmarks.missing_pid());
+
+        bookmarks.set_action(BookmarkAction::Pid(200));
+        let top = bookmarks.execute(&mut occurrences, lines(), top, 10);
+        assert_eq!(Some(80), bookmarks.selected().map(|lp| lp.pid));
+        assert_eq!(74, top);
+        assert_eq!(Some(200), bookmarks.missing_pid());
+    }
+
+    #[test]
+    fn test_pinned_count() {
+        let mut bookmarks = Bookmarks::default();
+        bookmarks.pins.extend([3, 5, 7]);
+        // Process 5 is pinned but hidden.
+        let lines = bookmarks.pinned_first(
+            [1, 2, 3, 4, 7]
+                .map(|pid| Identity("proc", pid, None))
+                .into_iter(),
+        );
+        assert_eq!(
+            vec![3, 7, 1, 2, 4],
+            lines
+                .iter()
+                .map(ProcessIdentity::pid)
+                .collect::<Vec<pid_t>>()
+        );
+        assert_eq!(2, bookmarks.pinned_count(&lines));
+        assert_eq!(0, bookmarks.pinned_count(&lines[2..]));
+    }
+}
@@ -24,54 +24,87 @@ use ratatui::{
     widgets::Clear,
     Terminal,
 };
-use std::{convert::TryFrom, fmt, io, rc::Rc, time::Duration};
+use std::{
+    collections::BTreeSet,
+    convert::TryFrom,
+    fmt,
+    io::{self, Write},
+    iter,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+use strum::IntoEnumIterator;
 use termion::{
     raw::{IntoRawMode, RawTerminal},
-    screen::{AlternateScreen, IntoAlternateScreen},
+    screen::{AlternateScreen, IntoAlternateScreen, ToAlternateScreen},
 };
 
 use crate::{
-    clock::Timer,
-    console::{is_tty, BuiltinTheme, EventChannel},
+    cfg::SearchSettings,
+    clock::{format_duration, Timer, Workload},
+    console::{
+        is_tty, log_event, AlertSignal, BuiltinTheme, Event, EventChannel, DISABLE_PASTE,
+        ENABLE_PASTE,
+    },
+    dump::Json,
+    overhead::Overhead,
+    privileges::user_name,
     process::{
-        self, format::human_duration, Aggregation, Collector, FormattedMetric, Process,
-        ProcessDetails, ProcessFilter,
+        self,
+        format::{human_duration, size},
+        Aggregation, Collector, CpuBasis, FormattedMetric, MetricId, Process, ProcessDetails,
+        ProcessFilter, ProcessIdentity,
     },
 };
 
-use super::{DataKind, DisplayDevice, PaneData, PaneKind, PauseStatus, SliceIter};
+use super::{
+    DataKind, DisplayDevice, Interaction, PaneData, PaneKind, PauseStatus, Permissions, SliceIter,
+    Tabs,
+};
 
+mod fit;
+mod guard;
+mod history;
 mod input;
 mod panes;
+#[cfg(test)]
+mod snapshots;
 mod tables;
 
 #[macro_use]
 mod types;
 
-use input::{menu, Action, BookmarkAction, KeyMap, MenuEntry, SearchEdit};
+use fit::ColumnFit;
+use guard::TerminalGuard;
+use history::SampleHistory;
+use input::{
+    log_error_entry, menu, Action, BookmarkAction, KeyMap, MenuEntry, PidPrompt, SearchColumn,
+    SearchEdit,
+};
 use panes::{
-    BigTableState, BigTableStateGenerator, BigTableWidget, FieldsWidget, GridPane, MarkdownWidget,
-    OneLineWidget, OptionalRenderer, Pane, SingleScrollablePane, TableGenerator, TableStyle, Zoom,
+    BigTableState, BigTableStateGenerator, BigTableWidget, ColumnGroup, FieldsWidget, GridPane,
+    MarkdownWidget, OneLineWidget, OptionalRenderer, Pane, SingleScrollablePane, TableGenerator,
+    TableStyle, Zoom,
+};
+use tables::{
+    cell_alignment, state_title, EnvironmentDiffTable, EnvironmentTable, IoDevicesTable,
+    LimitsTable, LinksTable, MountsTable, NamespacesTable, ProcessTreeTable, Styles, ThreadsTable,
+    TreeData,
 };
-use tables::{EnvironmentTable, LimitsTable, ProcessTreeTable, Styles, TreeData};
-use types::{Area, UnboundedArea};
+use types::{Area, FrameLimiter, PaneOffsets, SizeClass, UnboundedArea};
 
 const HELP: &str = include_str!("help_en.md");
 
-/// User action that has an impact on the application.
-#[derive(Clone, Debug)]
-pub enum Interaction {
-    None,
-    Filter(ProcessFilter),
-    SwitchBack,
-    SwitchToHelp,
-    SwitchTo(DataKind),
-    SelectPid(pid_t),
-    SelectParent,
-    SelectRootPid(Option<pid_t>),
-    Narrow(Vec<pid_t>),
-    Wide,
-    Quit,
+/// Save the title of the window on the stack of the terminal.
+const SAVE_TITLE: &str = "\x1b[22;0t";
+
+/// Restore the title of the window from the stack of the terminal.
+const RESTORE_TITLE: &str = "\x1b[23;0t";
+
+/// Operating system command with the control characters removed from the text.
+fn osc(code: u8, text: &str) -> String {
+    let text = text.chars().filter(|c| !c.is_control()).collect::<String>();
+    format!("\x1b]{code};{text}\x07")
 }
 
 impl TryFrom<&Action> for Interaction {
@@ -81,6 +114,9 @@ impl TryFrom<&Action> for Interaction {
     fn try_from(value: &Action) -> Result<Self, Self::Error> {
         match value {
             Action::SelectParent => Ok(Interaction::SelectParent),
+            Action::Dump => Ok(Interaction::Dump),
+            Action::NextTab => Ok(Interaction::NextTab),
+            Action::CloseTab => Ok(Interaction::CloseTab),
             Action::SwitchToHelp => Ok(Interaction::SwitchToHelp),
             Action::SwitchBack => Ok(Interaction::SwitchBack),
             Action::Quit => Ok(Interaction::Quit),
@@ -113,6 +149,119 @@ macro_rules! format_metric {
     };
 }
 
+/// Draw the main table with the breadcrumbs on top, the status bar and the menu at the bottom.
+///
+/// If the cursor is shown, it's positioned on the last line.
+fn draw_tree<T: TableGenerator>(
+    frame: &mut Frame,
+    main: BigTableWidget<T>,
+    state: &mut BigTableState,
+    [breadcrumbs, status_bar, menu]: [OneLineWidget; 3],
+    show_cursor: bool,
+) {
+    let area = frame.area();
+    let mut rects = SingleScrollablePane::new(area, 4)
+        .with_top(&breadcrumbs)
+        .with(&status_bar)
+        .with(&menu)
+        .build();
+    let mut cursor = if show_cursor {
+        Some(Position::new(0, area.y + area.height - 1))
+    } else {
+        None
+    };
+    let mut r = OptionalRenderer::new(frame, &mut rects);
+    r.render_stateful_widget(main, state);
+    r.render_widget(breadcrumbs);
+    r.render_widget(status_bar);
+    r.render_stateful_widget(menu, &mut cursor);
+    if let Some(cursor) = cursor {
+        frame.set_cursor_position(cursor);
+    }
+}
+
+/// Draw a table with a header on top and the menu at the bottom.
+fn draw_table<T: TableGenerator>(
+    frame: &mut Frame,
+    header: OneLineWidget,
+    main: BigTableWidget<T>,
+    state: &mut BigTableState,
+    menu: OneLineWidget,
+) {
+    let mut rects = SingleScrollablePane::new(frame.area(), 3)
+        .with_top(&header)
+        .with(&menu)
+        .build();
+    let mut r = OptionalRenderer::new(frame, &mut rects);
+    r.render_stateful_widget(main, state);
+    r.render_widget(header);
+    r.render_widget(menu);
+}
+
+/// Draw a scrollable error message with the menu at the bottom.
+fn draw_error(frame: &mut Frame, msg: MarkdownWidget, state: &mut Zoom, menu: OneLineWidget) {
+    let mut rects = SingleScrollablePane::new(frame.area(), 2)
+        .with(&menu)
+        .build();
+    let mut r = OptionalRenderer::new(frame, &mut rects);
+    r.render_stateful_widget(msg, state);
+    r.render_widget(menu);
+}
+
+/// Widgets of the details pane.
+struct DetailsPane<'a> {
+    header: OneLineWidget<'a>,
+    cmdline: OneLineWidget<'a>,
+    cwd: OneLineWidget<'a>,
+    process: FieldsWidget<'a>,
+    files: FieldsWidget<'a>,
+    time: FieldsWidget<'a>,
+    memory: FieldsWidget<'a>,
+    tab_bar: OneLineWidget<'a>,
+    with_tab_bar: bool,
+    menu: OneLineWidget<'a>,
+}
+
+impl DetailsPane<'_> {
+    /// Number of blocks under the header that can be scrolled.
+    const BLOCK_COUNT: u16 = 4;
+
+    /// Draw the pane with the first `offset` blocks hidden.
+    fn draw(self, frame: &mut Frame, offset: u16) {
+        let with_cmdline = offset < 1;
+        let with_cwd = offset < 2;
+        let with_proc_file = offset < 3;
+        let mut rects = GridPane::new(frame.area())
+            .with_row(&[&self.header])
+            .with_row_if(&[&self.cmdline], with_cmdline)
+            .with_row_if(&[&self.cwd], with_cwd)
+            .with_row_if(&[&self.process, &self.files], with_proc_file)
+            .with_row(&[&self.time, &self.memory])
+            .with_line_if(&self.tab_bar, self.with_tab_bar)
+            .with_line(&self.menu)
+            .build();
+        let mut r = OptionalRenderer::new(frame, &mut rects);
+        r.render_widget(self.header);
+        if with_cmdline {
+            r.render_widget(self.cmdline);
+        }
+        if with_cwd {
+            r.render_widget(self.cwd);
+        }
+        if with_proc_file {
+            r.render_widget(self.process);
+            r.render_widget(self.files);
+        }
+        r.render_widget(self.time);
+        r.render_widget(self.memory);
+        r.render_widget(Clear);
+        if self.with_tab_bar {
+            r.render_widget(self.tab_bar);
+        }
+        r.render_widget(self.menu);
+    }
+}
+
 /// Print on standard output as a table
 pub struct TerminalDevice<'t> {
     /// Interval to update the screen
@@ -127,10 +276,14 @@ pub struct TerminalDevice<'t> {
     table_offset: UnboundedArea,
     /// Pane offset (except for the table)
     pane_offset: u16,
+    /// Offsets of the panes per terminal size class
+    pane_offsets: PaneOffsets<PaneKind>,
     /// Number of lines to scroll vertically up and down
     vertical_scroll: VerticalScroll,
     /// Horizontal and vertical overflow (whether the table is bigger than the screen)
     overflow: Area<bool>,
+    /// First visible column where the values are searched by default.
+    visible_column: Option<usize>,
     /// Slots where limits are displayed under the metric (only for raw metrics).
     limit_slots: Vec<bool>,
     /// Number of available lines to display the table
@@ -139,36 +292,161 @@ pub struct TerminalDevice<'t> {
     filter: ProcessFilter,
     /// Menu
     menu: Vec<MenuEntry>,
+    /// Allowed interactions.
+    permissions: Permissions,
     /// Pane kind.
     pane_kind: PaneKind,
     /// Key map
     keymap: KeyMap,
+    /// Time spent in the last tick.
+    workload: Option<Workload>,
+    /// Resources used by oprs.
+    overhead: Option<Overhead>,
+    /// PIDs from the top of the tree down to the root process when it is selected.
+    lineage: Vec<pid_t>,
+    /// Index of the selected process in the breadcrumbs.
+    breadcrumb: usize,
+    /// Prompt to go to a PID.
+    pid_prompt: PidPrompt,
+    /// Describe the selected process in the status bar.
+    describe: bool,
+    /// Message displayed until the next interaction.
+    notice: Option<String>,
+    /// Error displayed in the pane that can be copied to the log.
+    error: Option<String>,
+    /// Searches applied with the keys 1 to 9.
+    searches: SearchSettings,
+    /// Most important metrics if the columns must fit in the width of the terminal.
+    fit: Option<Vec<String>>,
+    /// Whether the title of the window shows a status.
+    title: bool,
+    /// Last title written in the window.
+    window_title: Option<String>,
+    /// Signal sent on alerts.
+    bell: AlertSignal,
+    /// Basis of the CPU time ratio, shown in the header.
+    cpu_basis: CpuBasis,
+    /// Last samples to step through while paused.
+    history: SampleHistory<Collector<'static>>,
+    /// Limit of the frame rate.
+    frames: FrameLimiter,
+    /// Delay after which the next pause is interrupted.
+    wakeup: Option<Duration>,
+    /// Whether the screen must be updated on the next frame after an interaction.
+    dirty: bool,
+    /// Processes referred to by the open files in the pane of files.
+    links: Vec<pid_t>,
+    /// Index of the selected process in the links.
+    link_index: usize,
+    /// Start of the recording of the input events in the log file.
+    recording: Option<Instant>,
+    /// Restore the terminal on panic. Must be dropped after the terminal.
+    _guard: TerminalGuard,
 }
 
 impl TerminalDevice<'_> {
-    pub fn new(every: Duration, theme: Option<BuiltinTheme>) -> anyhow::Result<Self> {
+    pub fn new(
+        every: Duration,
+        theme: Option<BuiltinTheme>,
+        permissions: Permissions,
+        describe: bool,
+        searches: SearchSettings,
+        fit: Option<Vec<String>>,
+        replay: Vec<(Duration, Event)>,
+    ) -> anyhow::Result<Self> {
+        let guard = TerminalGuard::new()?;
         let screen = io::stdout().into_raw_mode()?.into_alternate_screen()?;
         let backend = TermionBackend::new(Box::new(screen));
         let terminal = Terminal::new(backend)?;
 
         Ok(TerminalDevice {
             every,
-            events: EventChannel::new(),
+            events: EventChannel::new(replay),
             terminal,
             tree_data: Rc::new(TreeData::new(Styles::new(theme))),
             table_offset: Default::default(),
             pane_offset: 0,
+            pane_offsets: PaneOffsets::new(),
             vertical_scroll: VerticalScroll::Line(1),
             overflow: Area::default(),
+            visible_column: None,
             limit_slots: Vec::new(),
             body_height: 0,
             filter: ProcessFilter::default(),
-            menu: menu(),
+            menu: menu(&permissions, !searches.is_empty(), false),
+            permissions,
             pane_kind: PaneKind::Main,
             keymap: KeyMap::Main,
+            workload: None,
+            overhead: None,
+            lineage: Vec::new(),
+            breadcrumb: 0,
+            pid_prompt: PidPrompt::None,
+            describe,
+            notice: None,
+            error: None,
+            searches,
+            fit,
+            title: false,
+            window_title: None,
+            bell: AlertSignal::None,
+            cpu_basis: CpuBasis::All,
+            history: SampleHistory::new(0),
+            frames: FrameLimiter::default(),
+            wakeup: None,
+            dirty: true,
+            links: Vec::new(),
+            link_index: 0,
+            recording: None,
+            _guard: guard,
         })
     }
 
+    /// Set the status in the title of the window and the signal sent on alerts.
+    pub fn with_signals(mut self, title: bool, bell: AlertSignal) -> Self {
+        self.title = title;
+        self.bell = bell;
+        self
+    }
+
+    /// Set the basis of the CPU time ratio.
+    pub fn with_cpu_basis(mut self, cpu_basis: CpuBasis) -> Self {
+        self.cpu_basis = cpu_basis;
+        self
+    }
+
+    /// Keep the last samples to step through them while paused.
+    pub fn with_history(mut self, capacity: usize) -> Self {
+        self.history = SampleHistory::new(capacity);
+        self.menu = menu(&self.permissions, !self.searches.is_empty(), capacity > 0);
+        self
+    }
+
+    /// Limit the number of frames per second (0 for no limit).
+    pub fn with_max_fps(mut self, max_fps: u32) -> Self {
+        self.frames = FrameLimiter::new(max_fps);
+        self
+    }
+
+    /// Log the input events with the resulting actions and transitions.
+    pub fn with_input_recording(mut self, record: bool) -> Self {
+        self.recording = record.then(Instant::now);
+        self
+    }
+
+    /// Label of the ratio of a metric.
+    ///
+    /// The ratio of the CPU times tells whether it's relative to a single CPU or to all CPUs.
+    fn ratio_label(&self, id: MetricId) -> &'static str {
+        match id {
+            MetricId::TimeCpu | MetricId::TimeSystem | MetricId::TimeUser => match self.cpu_basis {
+                CpuBasis::All => "%all",
+                CpuBasis::Single => "%cpu",
+            },
+            _ => "%",
+        }
+    }
+
     pub fn is_available() -> bool {
         is_tty(&io::stdin())
     }
@@ -183,11 +461,30 @@ impl TerminalDevice<'_> {
 
     /// Content of the status bar
     fn status_bar(&self) -> String {
-        let time_string = format!("{}", Local::now().format("%X"));
-        let delay = human_duration(self.every);
+        let mut time_string = match self.history.current() {
+            Some((time, _)) => format!(
+                "{} [paused -{}]",
+                time.format("%X"),
+                self.history.age().unwrap_or(0)
+            ),
+            None => format!("{}", Local::now().format("%X")),
+        };
+        if self.permissions.read_only {
+            time_string.push_str(" [read-only]");
+        }
+        let delay = match self.workload {
+            Some(workload) => format!(
+                "{} (tick:{})",
+                format_duration(self.every),
+                human_duration(workload.total())
+            ),
+            None => format_duration(self.every),
+        };
         let matches_count = self.tree_data.occurrences.len();
         let marks_count = self.tree_data.bookmarks.marks().len();
-        if matches_count > 0 {
+        if let Some(hint) = self.keymap.hint() {
+            format!("{time_string} -- {hint}")
+        } else if matches_count > 0 {
             format!("{time_string} -- interval:{delay} -- matches:{matches_count}",)
         } else if marks_count > 0 {
             format!("{time_string} -- interval:{delay} -- marks:{marks_count}",)
@@ -199,6 +496,64 @@ impl TerminalDevice<'_> {
         }
     }
 
+    /// Values of the selected process as a sentence that a screen reader can read.
+    fn describe_selection(&self, collector: &Collector) -> Option<String> {
+        let pid = self.tree_data.bookmarks.selected().as_ref()?.pid;
+        let pstat = collector.lines().find(|pstat| pstat.pid() == pid)?;
+        let values = collector
+            .metrics()
+            .zip(pstat.samples())
+            .map(|(metric, sample)| {
+                let strings = Aggregation::iter()
+                    .filter(|ag| metric.aggregations.has(*ag))
+                    .zip(sample.strings())
+                    .map(|(ag, s)| match ag {
+                        Aggregation::None | Aggregation::Ratio => s.to_string(),
+                        Aggregation::Min => format!("min {s}"),
+                        Aggregation::Max => format!("max {s}"),
+                    })
+                    .collect::<Vec<String>>();
+                format!("{} {}", metric.name(), strings.join(" "))
+            })
+            .collect::<Vec<String>>();
+        Some(format!(
+            "{} PID {pid}: {}.",
+            pstat.name(),
+            values.join(", ")
+        ))
+    }
+
+    /// Short status for the title of the window.
+    ///
+    /// It shows the first metric of the selected process or of the first one.
+    fn window_title(&self, collector: &Collector) -> String {
+        let mut title = format!("oprs: {} processes", collector.line_count());
+        let selected = self.tree_data.bookmarks.selected().as_ref().map(|s| s.pid);
+        let pstat = selected
+            .and_then(|pid| collector.lines().find(|pstat| pstat.pid() == pid))
+            .or_else(|| collector.lines().next());
+        if let (Some(pstat), Some(metric)) = (pstat, collector.metrics().next()) {
+            if let Some(value) = pstat.samples().next().and_then(|s| s.strings().next()) {
+                title.push_str(&format!(", {} {} {value}", pstat.name(), metric.name()));
+            }
+        }
+        title
+    }
+
+    /// Write the status in the title of the window if it changed.
+    fn update_window_title(&mut self, collector: &Collector) -> io::Result<()> {
+        if self.title {
+            let title = self.window_title(collector);
+            if self.window_title.as_ref() != Some(&title) {
+                let screen = self.terminal.backend_mut().writer_mut();
+                write!(screen, "{}", osc(2, &title))?;
+                screen.flush()?;
+                self.window_title = Some(title);
+            }
+        }
+        Ok(())
+    }
+
     /// Clear marks.
     fn clear_bookmarks(&mut self) {
         void!(Rc::get_mut(&mut self.tree_data).map(|data| data.bookmarks.clear_marks()))
@@ -238,24 +593,99 @@ impl TerminalDevice<'_> {
         self.clear_and_set_bookmarks_action_if(action, true);
     }
 
+    /// Breadcrumbs of the root process lineage or a placeholder if the targets are not started.
+    ///
+    /// The selected process is highlighted when the breadcrumbs keymap is active.
+    fn breadcrumbs<'a>(&self, collector: &'a Collector) -> Line<'a> {
+        const SEPARATOR: &str = " › ";
+        if collector.is_waiting() {
+            return Line::styled(
+                "Waiting for target…",
+                Style::default().add_modifier(Modifier::BOLD),
+            );
+        }
+        let is_active = self.keymap == KeyMap::Breadcrumbs;
+        let selected_style = self.tree_data.styles.selected;
+        let mut spans = Vec::new();
+        collector
+            .lineage()
+            .enumerate()
+            .for_each(|(index, (pid, name))| {
+                if index > 0 {
+                    spans.push(Span::raw(SEPARATOR));
+                }
+                let crumb = format!("{name}({pid})");
+                if is_active && index == self.breadcrumb {
+                    spans.push(Span::styled(crumb, selected_style));
+                } else {
+                    spans.push(Span::raw(crumb));
+                }
+            });
+        Line::from(spans)
+    }
+
+    /// Names of the processes in tabs with the current one highlighted.
+    fn tab_bar<'a>(&self, tabs: &Tabs<ProcessDetails>) -> Line<'a> {
+        const SEPARATOR: &str = " │ ";
+        let selected_style = self.tree_data.styles.selected;
+        let current = tabs.current_index();
+        let mut spans = Vec::new();
+        tabs.iter().enumerate().for_each(|(index, details)| {
+            if index > 0 {
+                spans.push(Span::raw(SEPARATOR));
+            }
+            let label = format!("{}({})", details.name(), details.process().pid());
+            if index == current {
+                spans.push(Span::styled(label, selected_style));
+            } else {
+                spans.push(Span::raw(label));
+            }
+        });
+        Line::from(spans)
+    }
+
     /// Execute an interactive action.
     fn react(&mut self, action: Action, timer: &mut Timer) -> io::Result<Action> {
         const MAX_TIMEOUT_SECS: u64 = 24 * 3_600; // 24 hours
         const MIN_TIMEOUT_MSECS: u128 = 1;
+        if matches!(self.pid_prompt, PidPrompt::Unknown(_)) {
+            self.pid_prompt = PidPrompt::None;
+        }
+        self.notice = None;
+        self.dirty = true;
         match action {
             Action::None
             | Action::ChangeScope
+            | Action::CompareEnvironments
             | Action::SelectParent
+            | Action::Dump
+            | Action::NextTab
+            | Action::CloseTab
             | Action::SelectRootPid
+            | Action::SelectBreadcrumb
             | Action::SwitchToHelp
             | Action::SwitchToDetails
             | Action::SwitchToLimits
             | Action::SwitchToEnvironment
+            | Action::FollowLink
+            | Action::SwitchToIoDevices
+            | Action::SwitchToMounts
+            | Action::SwitchToNamespaces
+            | Action::SwitchToThreads
             | Action::UnselectRootPid
             | Action::Quit => (),
-            Action::SwitchBack => {
-                self.set_keymap(KeyMap::Main);
-                self.pane_offset = 0;
+            Action::SwitchBack => self.set_keymap(KeyMap::Main),
+            Action::Breadcrumbs => {
+                if self.lineage.len() > 1 {
+                    self.breadcrumb = self.lineage.len() - 2; // the parent of the root
+                    self.set_keymap(KeyMap::Breadcrumbs);
+                }
+            }
+            Action::BreadcrumbPrevious => self.breadcrumb = self.breadcrumb.saturating_sub(1),
+            Action::BreadcrumbNext => {
+                if self.breadcrumb + 1 < self.lineage.len() {
+                    self.breadcrumb += 1;
+                }
             }
             Action::Filters => self.set_keymap(KeyMap::Filters),
             Action::FilterNone => {
@@ -270,6 +700,24 @@ impl TerminalDevice<'_> {
                 self.filter = ProcessFilter::Active;
                 self.set_keymap(KeyMap::Main);
             }
+            Action::FilterRealTime => {
+                self.filter = ProcessFilter::RealTime;
+                self.set_keymap(KeyMap::Main);
+            }
+            Action::SavedSearch(key) => {
+                if let Some(search) = self.searches.get(key) {
+                    if let Some(filter) = search.filter {
+                        self.filter = filter;
+                    }
+                    if let Some(ref pattern) = search.pattern {
+                        if let Some(data) = Rc::get_mut(&mut self.tree_data) {
+                            data.bookmarks.set_fixed_search(pattern);
+                        }
+                    }
+                }
+            }
+            Action::MultiplyTimeout(_) | Action::DivideTimeout(_)
+                if !self.permissions.change_delay => {}
             Action::MultiplyTimeout(factor) => {
                 let delay = timer.get_delay();
                 if delay.as_secs() * (factor as u64) < MAX_TIMEOUT_SECS {
@@ -330,7 +778,53 @@ impl TerminalDevice<'_> {
                     data.bookmarks.fixed_search();
                 }
             }
+            Action::GotoPidEnter => {
+                self.set_keymap(KeyMap::GotoPid);
+                self.pid_prompt = PidPrompt::Input(String::new());
+            }
+            Action::GotoPidExit => {
+                self.terminal.hide_cursor()?;
+                self.set_keymap(KeyMap::Main);
+                if let PidPrompt::Input(ref digits) = self.pid_prompt {
+                    if let Ok(pid) = digits.parse::<pid_t>() {
+                        self.set_bookmarks_action(BookmarkAction::Pid(pid));
+                    }
+                }
+                self.pid_prompt = PidPrompt::None;
+            }
+            Action::GotoPidCancel => {
+                self.terminal.hide_cursor()?;
+                self.set_keymap(KeyMap::Main);
+                self.pid_prompt = PidPrompt::None;
+            }
+            Action::GotoPidPush(c) => {
+                if let PidPrompt::Input(ref mut digits) = self.pid_prompt {
+                    digits.push(c);
+                }
+            }
+            Action::GotoPidPaste(ref text) => {
+                if let PidPrompt::Input(ref mut digits) = self.pid_prompt {
+                    digits.extend(text.trim().chars().filter(char::is_ascii_digit));
+                }
+            }
+            Action::GotoPidPop => {
+                if let PidPrompt::Input(ref mut digits) = self.pid_prompt {
+                    digits.pop();
+                }
+            }
+            Action::Widen => {
+                self.set_keymap(KeyMap::Main);
+                if let PidPrompt::Missing(pid) = self.pid_prompt {
+                    self.set_bookmarks_action(BookmarkAction::Pid(pid));
+                }
+                self.pid_prompt = PidPrompt::None;
+                self.filter = ProcessFilter::None;
+            }
             Action::SearchPush(c) => self.edit_search(SearchEdit::Push(c)),
+            Action::SearchPaste(ref text) => text
+                .chars()
+                .filter(|c| !c.is_control())
+                .for_each(|c| self.edit_search(SearchEdit::Push(c))),
             Action::SearchPop => self.edit_search(SearchEdit::Pop),
             Action::SearchCancel => self.clear_search(),
             Action::SelectPrevious => {
@@ -339,6 +833,52 @@ impl TerminalDevice<'_> {
             Action::SelectNext => void!(self.set_bookmarks_action(BookmarkAction::Next)),
             Action::ClearMarks => self.clear_bookmarks(),
             Action::ToggleMarks => void!(self.set_bookmarks_action(BookmarkAction::ToggleMarks)),
+            Action::TogglePin => {
+                if let Some(data) = Rc::get_mut(&mut self.tree_data) {
+                    data.bookmarks.toggle_pin();
+                }
+            }
+            Action::SwitchToFiles | Action::SwitchToMaps => self.link_index = 0,
+            Action::LinkNext => {
+                if self.link_index + 1 < self.links.len() {
+                    self.link_index += 1;
+                }
+            }
+            Action::LinkPrevious => self.link_index = self.link_index.saturating_sub(1),
+            Action::ToggleWatermarks => {
+                if let Some(data) = Rc::get_mut(&mut self.tree_data) {
+                    data.watermarks = !data.watermarks;
+                }
+            }
+            Action::ToggleRelative => {
+                if let Some(data) = Rc::get_mut(&mut self.tree_data) {
+                    data.relative = !data.relative;
+                }
+            }
+            Action::ToggleGroups => {
+                if let Some(data) = Rc::get_mut(&mut self.tree_data) {
+                    data.grouped = !data.grouped;
+                }
+            }
+            Action::TogglePause => self.history.toggle_pause(),
+            Action::StepBackward => {
+                if self.history.is_enabled() && !self.history.step_backward() {
+                    self.notify("No older sample");
+                }
+            }
+            Action::StepForward => {
+                if self.history.is_paused() && !self.history.step_forward() {
+                    self.notify("No newer sample");
+                }
+            }
+            Action::LogError => {
+                if let Some(error) = self.error.clone() {
+                    log::error!("{error}");
+                    // The log may be written on the terminal.
+                    self.terminal.clear()?;
+                    self.notify("Error copied to the log");
+                }
+            }
         }
         Ok(action)
     }
@@ -358,13 +898,39 @@ impl TerminalDevice<'_> {
                 Interaction::Narrow(pids)
             }
             Action::ChangeScope => Interaction::Wide,
-            Action::FilterNone | Action::FilterUser | Action::FilterActive => {
-                Interaction::Filter(self.filter)
+            Action::CompareEnvironments => {
+                let marks = self.tree_data.bookmarks.marks();
+                match marks.iter().copied().collect::<Vec<pid_t>>()[..] {
+                    [first, second] => {
+                        self.clear_bookmarks();
+                        Interaction::CompareEnvironments(first, second)
+                    }
+                    _ => {
+                        self.notify("Mark two processes to compare");
+                        Interaction::None
+                    }
+                }
             }
+            Action::Widen => Interaction::WideAll,
+            Action::FilterNone
+            | Action::FilterUser
+            | Action::FilterActive
+            | Action::FilterRealTime => Interaction::Filter(self.filter),
+            Action::SavedSearch(key) => match self.searches.get(key).and_then(|s| s.filter) {
+                Some(filter) => Interaction::Filter(filter),
+                None => Interaction::None,
+            },
             Action::SelectRootPid => match self.tree_data.bookmarks.selected() {
                 Some(selected) => Interaction::SelectRootPid(Some(selected.pid)),
                 None => Interaction::None,
             },
+            Action::SelectBreadcrumb => {
+                self.set_keymap(KeyMap::Main);
+                match self.lineage.get(self.breadcrumb) {
+                    Some(pid) => Interaction::SelectRootPid(Some(*pid)),
+                    None => Interaction::None,
+                }
+            }
             Action::UnselectRootPid => Interaction::SelectRootPid(None),
             Action::SwitchToDetails => match self.tree_data.bookmarks.selected() {
                 Some(selected) => Interaction::SelectPid(selected.pid),
@@ -372,6 +938,16 @@ impl TerminalDevice<'_> {
             },
             Action::SwitchToLimits => Interaction::SwitchTo(DataKind::Limits),
             Action::SwitchToEnvironment => Interaction::SwitchTo(DataKind::Environment),
+            Action::SwitchToFiles => Interaction::SwitchTo(DataKind::Files),
+            Action::SwitchToMaps => Interaction::SwitchTo(DataKind::Maps),
+            Action::FollowLink => match self.links.get(self.link_index) {
+                Some(pid) => Interaction::SelectPid(*pid),
+                None => Interaction::None,
+            },
+            Action::SwitchToIoDevices => Interaction::SwitchTo(DataKind::IoDevices),
+            Action::SwitchToMounts => Interaction::SwitchTo(DataKind::Mounts),
+            Action::SwitchToNamespaces => Interaction::SwitchTo(DataKind::Namespaces),
+            Action::SwitchToThreads => Interaction::SwitchTo(DataKind::Threads),
             _ => Interaction::None,
         })
     }
@@ -393,32 +969,57 @@ impl TerminalDevice<'_> {
     fn render_tree(&mut self, collector: &Collector) -> anyhow::Result<()> {
         self.pane_kind = PaneKind::Main;
 
-        let metric_headers_len = self.tree_data.metric_headers.len();
-        let line_count = collector.line_count();
+        if let Some(data) = Rc::get_mut(&mut self.tree_data) {
+            if !data.bookmarks.pins().is_empty() {
+                let visible = BTreeSet::from_iter(collector.lines().map(|ps| ps.pid()));
+                data.bookmarks
+                    .retain_pins(|pid| visible.contains(&pid) || Process::new(pid).is_ok());
+            }
+            data.bookmarks.set_visible_column(self.visible_column);
+        }
+        let lines = self.tree_data.lines(collector);
+        let line_count = lines.len();
         let top = self.top(line_count);
         let voffset = Rc::get_mut(&mut self.tree_data)
             .map(|data| {
                 data.bookmarks.execute(
                     &mut data.occurrences,
-                    collector.lines(),
+                    lines.into_iter(),
                     top,
                     self.body_height,
                 )
             })
             .unwrap_or(0);
         self.table_offset.set_vertical(voffset);
-        self.table_offset.set_bounds(
-            metric_headers_len.saturating_sub(1),
-            line_count.saturating_sub(self.body_height),
-        );
+        if let Some(pid) = self.tree_data.bookmarks.missing_pid() {
+            if Process::new(pid).is_ok() {
+                self.pid_prompt = PidPrompt::Missing(pid);
+                self.set_keymap(KeyMap::Widen);
+            } else {
+                self.pid_prompt = PidPrompt::Unknown(pid);
+            }
+        }
         let column_spacing = self.tree_data.styles.column_spacing;
         let even_row_style = self.tree_data.styles.even_row;
         let odd_row_style = self.tree_data.styles.odd_row;
         let status_style = self.tree_data.styles.status;
         let is_search = self.tree_data.bookmarks.is_incremental_search();
-        let mut body_height = 0;
-        let show_cursor = is_search;
-        let status_bar = OneLineWidget::new(Text::from(self.status_bar()), status_style, None);
+        let show_cursor = is_search || matches!(self.pid_prompt, PidPrompt::Input(_));
+        let description = if self.describe && self.keymap.hint().is_none() {
+            self.describe_selection(collector)
+        } else {
+            None
+        };
+        let status_bar = OneLineWidget::new(
+            Text::from(description.unwrap_or_else(|| self.status_bar())),
+            status_style,
+            None,
+        );
+        let breadcrumbs = OneLineWidget::new(
+            Text::from(self.breadcrumbs(collector)),
+            Style::default(),
+            None,
+        );
         let menu = if is_search {
             OneLineWidget::new(
                 Text::from(format!(
@@ -428,72 +1029,96 @@ impl TerminalDevice<'_> {
                 Style::default(),
                 None,
             )
+        } else if !matches!(self.pid_prompt, PidPrompt::None) {
+            OneLineWidget::new(
+                Text::from(self.pid_prompt.to_string()),
+                Style::default(),
+                None,
+            )
+        } else if let Some(ref notice) = self.notice {
+            OneLineWidget::new(Text::from(notice.as_str()), Style::default(), None)
         } else {
             OneLineWidget::with_menu(self.menu.iter(), self.keymap)
         };
 
-        let table = ProcessTreeTable::new(collector, Rc::clone(&self.tree_data));
+        let size = self.terminal.size()?;
+        let width = size.width.saturating_sub(2 * panes::BORDER_SIZE);
+        // The body cannot be higher than the terminal.
+        let window = voffset..voffset + usize::from(size.height);
+        let table = ProcessTreeTable::new(collector, Rc::clone(&self.tree_data), width, window);
+        self.table_offset.set_bounds(
+            table.body_column_count().saturating_sub(1),
+            line_count.saturating_sub(self.body_height),
+        );
+        self.visible_column = table.visible_column(self.table_offset.horizontal.value_or_zero());
         let main = BigTableWidget::new(
             &table,
             TableStyle::new(column_spacing, even_row_style, odd_row_style),
         );
 
-        let mut new_overflow = Area::default();
+        let mut state = BigTableState::new(
+            Zoom::new(
+                self.table_offset.horizontal.value_or_zero(),
+                0,
+                table.body_column_count(),
+            ),
+            Zoom::new(
+                self.table_offset.vertical.value_or_zero(),
+                0,
+                table.body_row_count(),
+            ),
+        );
         self.terminal.draw(|frame| {
-            let area = frame.area();
-            let mut rects = SingleScrollablePane::new(area, 3)
-                .with(&status_bar)
-                .with(&menu)
-                .build();
-
-            let mut state = BigTableState::new(
-                Zoom::new(
-                    self.table_offset.horizontal.value_or_zero(),
-                    0,
-                    table.body_column_count(),
-                ),
-                Zoom::new(
-                    self.table_offset.vertical.value_or_zero(),
-                    0,
-                    table.body_row_count(),
-                ),
+            draw_tree(
+                frame,
+                main,
+                &mut state,
+                [breadcrumbs, status_bar, menu],
+                show_cursor,
             );
-            let mut cursor = if show_cursor {
-                Some(Position::new(0, area.y + area.height - 1))
-            } else {
-                None
-            };
-            let mut r = OptionalRenderer::new(frame, &mut rects);
-            r.render_stateful_widget(main, &mut state);
-            r.render_widget(status_bar);
-            r.render_stateful_widget(menu, &mut cursor);
-            let zoom = state.zoom;
-            body_height = zoom.vertical.visible_length - table.headers_size().vertical;
-            new_overflow = Area::new(!zoom.horizontal.at_end(), !zoom.vertical.at_end());
-            if let Some(cursor) = cursor {
-                frame.set_cursor_position(cursor);
-            }
         })?;
-        self.overflow = new_overflow;
+        let zoom = state.zoom;
+        let body_height = zoom.vertical.visible_length - table.headers_size().vertical;
+        self.overflow = Area::new(!zoom.horizontal.at_end(), !zoom.vertical.at_end());
         self.vertical_scroll = VerticalScroll::Line(body_height.div_ceil(2));
         self.body_height = body_height;
         Ok(())
     }
 
-    fn render_scrollable_pane<W>(&mut self, widget: W) -> anyhow::Result<()>
-    where
-        W: StatefulWidget<State = Zoom>,
-    {
+    /// Version and resources used by oprs.
+    fn about(&self) -> Vec<(&'static str, String)> {
+        let mut lines = vec![("Version", env!("CARGO_PKG_VERSION").to_string())];
+        if let Some(ref overhead) = self.overhead {
+            lines.push((
+                "Samples",
+                format!("{} ({:.2}/s)", overhead.samples, overhead.sample_rate()),
+            ));
+            lines.push(("CPU time", human_duration(overhead.cpu_time)));
+            lines.push(("CPU usage", format!("{:.1}%", overhead.cpu_ratio * 100.0)));
+            if let Some((count, bytes)) = overhead.allocations {
+                lines.push(("Allocations", format!("{count} ({})", size(bytes))));
+            }
+        }
+        lines
+    }
+
+    fn render_help(&mut self) -> anyhow::Result<()> {
+        self.pane_kind = PaneKind::Help;
+        let about = self.about();
+        let about = FieldsWidget::new("About", &about);
+        let widget = MarkdownWidget::new("OPRS", HELP);
         let mut state = Zoom::with_position(self.pane_offset as usize);
         let menu = OneLineWidget::with_menu(self.menu.iter(), self.keymap);
 
         self.terminal.draw(|frame| {
-            let mut rects = SingleScrollablePane::new(frame.area(), 2)
+            let mut rects = SingleScrollablePane::new(frame.area(), 3)
+                .with_top(&about)
                 .with(&menu)
                 .build();
 
             let mut r = OptionalRenderer::new(frame, &mut rects);
             r.render_stateful_widget(widget, &mut state);
+            r.render_widget(about);
             r.render_widget(menu);
             self.pane_offset = state.position as u16;
             self.vertical_scroll = VerticalScroll::Line(state.visible_length.div_ceil(2));
@@ -501,11 +1126,6 @@ impl TerminalDevice<'_> {
         Ok(())
     }
 
-    fn render_help(&mut self) -> anyhow::Result<()> {
-        self.pane_kind = PaneKind::Help;
-        self.render_scrollable_pane(MarkdownWidget::new("OPRS", HELP))
-    }
-
     fn format_option<D: fmt::Display>(option: Option<D>) -> String {
         match option {
             Some(value) => value.to_string(),
@@ -513,28 +1133,54 @@ impl TerminalDevice<'_> {
         }
     }
 
-    fn render_details(&mut self, details: &ProcessDetails) -> anyhow::Result<()> {
+    /// Line identifying the process that stays on top of the process panes.
+    fn process_header<'a>(&self, details: &ProcessDetails) -> OneLineWidget<'a> {
+        let pinfo = details.process();
+        let user = details
+            .credentials()
+            .map(|credentials| credentials.uid)
+            .or_else(|| pinfo.uid())
+            .map(|uid| user_name(uid).unwrap_or_else(|| uid.to_string()));
+        let text = format!(
+            " {} │ PID {} │ {} ({}) │ {}",
+            details.name(),
+            pinfo.pid(),
+            state_title(pinfo.state()),
+            pinfo.state(),
+            TerminalDevice::format_option(user)
+        );
+        OneLineWidget::new(Text::from(text), self.tree_data.styles.status, None)
+    }
+
+    fn render_details(&mut self, tabs: &Tabs<ProcessDetails>) -> anyhow::Result<()> {
         self.pane_kind = PaneKind::Process(DataKind::Details);
+        let Some(details) = tabs.current() else {
+            return self.render_error("no process selected");
+        };
         let offset = self.pane_offset;
         let pinfo = details.process();
         let cmdline = pinfo.cmdline();
         let metrics = details.metrics();
+        let header = self.process_header(details);
 
-        let mut block_count = 0;
         let cmdline_widget =
             OneLineWidget::new(Text::from(cmdline), Style::default(), Some("Command"));
-        block_count += 1;
         let cwd_widget = OneLineWidget::new(
             Text::from(process::format_result(pinfo.process().cwd())),
             Style::default(),
             Some("Working Directory"),
         );
-        block_count += 1;
         let proc_fields = [
             ("Name", format!(" {} ", details.name())),
             ("Process ID", format!("{}", pinfo.pid())),
             ("Parent ID", format!("{}", pinfo.parent_pid())),
-            ("Owner", TerminalDevice::format_option(pinfo.uid())),
+            (
+                "Owner",
+                match details.credentials() {
+                    Some(credentials) => credentials.to_string(),
+                    None => TerminalDevice::format_option(pinfo.uid()),
+                },
+            ),
             ("Threads", format_metric!(metrics, thread_count)),
         ];
         let proc_widget = FieldsWidget::new("Process", &proc_fields);
@@ -545,7 +1191,6 @@ impl TerminalDevice<'_> {
             ("I/O Write", format_metric!(metrics, io_write_total)),
         ];
         let file_widget = FieldsWidget::new("Files", &file_fields);
-        block_count += 1;
         let cpu_fields = [
             ("CPU", format_metric!(metrics, time_cpu)),
             ("Elapsed", format_metric!(metrics, time_elapsed)),
@@ -557,138 +1202,267 @@ impl TerminalDevice<'_> {
             ("Data", format_metric!(metrics, mem_data)),
         ];
         let mem_widget = FieldsWidget::new("Memory", &mem_fields);
-        block_count += 1;
 
-        let menu = OneLineWidget::with_menu(self.menu.iter(), self.keymap);
-
-        self.terminal.draw(|frame| {
-            let with_cmdline = offset < 1;
-            let with_cwd = offset < 2;
-            let with_proc_file = offset < 3;
-            let mut rects = GridPane::new(frame.area())
-                .with_row_if(&[&cmdline_widget], with_cmdline)
-                .with_row_if(&[&cwd_widget], with_cwd)
-                .with_row_if(&[&proc_widget, &file_widget], with_proc_file)
-                .with_row(&[&cpu_widget, &mem_widget])
-                .with_line(&menu)
-                .build();
-            let mut r = OptionalRenderer::new(frame, &mut rects);
-            if with_cmdline {
-                r.render_widget(cmdline_widget);
-            }
-            if with_cwd {
-                r.render_widget(cwd_widget);
-            }
-            if with_proc_file {
-                r.render_widget(proc_widget);
-                r.render_widget(file_widget);
+        let menu = match self.notice {
+            Some(ref notice) => {
+                OneLineWidget::new(Text::from(notice.as_str()), Style::default(), None)
             }
-            r.render_widget(cpu_widget);
-            r.render_widget(mem_widget);
-            r.render_widget(Clear);
-            r.render_widget(menu);
-        })?;
-        if self.pane_offset >= block_count {
-            self.pane_offset = block_count.saturating_sub(1);
+            None => OneLineWidget::with_menu(self.menu.iter(), self.keymap),
+        };
+        let tab_bar = OneLineWidget::new(Text::from(self.tab_bar(tabs)), Style::default(), None);
+        let pane = DetailsPane {
+            header,
+            cmdline: cmdline_widget,
+            cwd: cwd_widget,
+            process: proc_widget,
+            files: file_widget,
+            time: cpu_widget,
+            memory: mem_widget,
+            tab_bar,
+            with_tab_bar: tabs.len() > 1,
+            menu,
+        };
+
+        self.terminal.draw(|frame| pane.draw(frame, offset))?;
+        if self.pane_offset >= DetailsPane::BLOCK_COUNT {
+            self.pane_offset = DetailsPane::BLOCK_COUNT - 1;
         }
         self.vertical_scroll = VerticalScroll::Block; // scrolling by block not by line.
         Ok(())
     }
 
-    fn render_table<T>(&mut self, table: T) -> anyhow::Result<()>
+    fn render_table<T>(&mut self, header: OneLineWidget, table: T) -> anyhow::Result<()>
     where
         T: BigTableStateGenerator + TableGenerator,
     {
         let column_spacing = self.tree_data.styles.column_spacing;
         let even_row_style = self.tree_data.styles.even_row;
         let odd_row_style = self.tree_data.styles.odd_row;
-        let menu = OneLineWidget::with_menu(self.menu.iter(), self.keymap);
+        let menu = match self.notice {
+            Some(ref notice) => {
+                OneLineWidget::new(Text::from(notice.as_str()), Style::default(), None)
+            }
+            None => OneLineWidget::with_menu(self.menu.iter(), self.keymap),
+        };
         let main = BigTableWidget::new(
             &table,
             TableStyle::new(column_spacing, even_row_style, odd_row_style),
         );
 
-        let mut inner_height = 0;
-        self.terminal.draw(|frame| {
-            let area = frame.area();
-            let mut rects = SingleScrollablePane::new(area, 2).with(&menu).build();
-            let mut r = OptionalRenderer::new(frame, &mut rects);
-            let mut state = table.state();
-            state.zoom.vertical.position = self.pane_offset as usize;
-            r.render_stateful_widget(main, &mut state);
-            r.render_widget(menu);
-            inner_height = state.zoom.vertical.visible_length;
-        })?;
+        let mut state = table.state();
+        state.zoom.vertical.position = self.pane_offset as usize;
+        self.terminal
+            .draw(|frame| draw_table(frame, header, main, &mut state, menu))?;
+        let inner_height = state.zoom.vertical.visible_length;
         self.vertical_scroll = VerticalScroll::Line(inner_height.div_ceil(2));
         Ok(())
     }
 
+    /// Render an error wrapped in a scrollable pane.
     fn render_error<S: AsRef<str>>(&mut self, err: S) -> anyhow::Result<()> {
-        let msg = OneLineWidget::new(Text::from(err.as_ref()), Style::default(), None);
-        let menu = OneLineWidget::with_menu(self.menu.iter(), self.keymap);
+        let err = err.as_ref();
+        let msg = MarkdownWidget::plain("Error", err);
+        let mut state = Zoom::with_position(self.pane_offset as usize);
+        let log_entry = log_error_entry(self.keymap);
+        let menu = match self.notice {
+            Some(ref notice) => {
+                OneLineWidget::new(Text::from(notice.as_str()), Style::default(), None)
+            }
+            None => OneLineWidget::with_menu(
+                self.menu.iter().chain(iter::once(&log_entry)),
+                self.keymap,
+            ),
+        };
 
-        self.terminal.draw(|frame| {
-            let area = frame.area();
-            let mut rects = SingleScrollablePane::new(area, 2).with(&menu).build();
-            let mut r = OptionalRenderer::new(frame, &mut rects);
-            r.render_widget(msg);
-            r.render_widget(menu);
-        })?;
+        self.terminal
+            .draw(|frame| draw_error(frame, msg, &mut state, menu))?;
+        self.pane_offset = state.position as u16;
+        self.vertical_scroll = VerticalScroll::Line(state.visible_length.div_ceil(2));
+        self.error = Some(err.to_string());
         Ok(())
     }
 
-    fn render_process(&mut self, kind: DataKind, process: &Process) -> anyhow::Result<()> {
+    /// Keep the processes the entries of a pane refer to, in order.
+    fn set_links<I>(&mut self, peers: I)
+    where
+        I: Iterator<Item = Option<pid_t>>,
+    {
+        self.links = peers.flatten().collect();
+        self.link_index = self.link_index.min(self.links.len().saturating_sub(1));
+    }
+
+    fn render_process(&mut self, kind: DataKind, details: &ProcessDetails) -> anyhow::Result<()> {
         self.pane_kind = PaneKind::Process(kind);
+        let header = self.process_header(details);
+        let process = details.process().process();
         match kind {
             DataKind::Limits => match process.limits() {
-                Ok(limits) => self.render_table(LimitsTable::new(limits)),
+                Ok(limits) => self.render_table(header, LimitsTable::new(limits)),
                 Err(err) => self.render_error(err.to_string()),
             },
             DataKind::Environment => match process.environ() {
-                Ok(env) => self.render_table(EnvironmentTable::new(env)),
+                Ok(env) => self.render_table(header, EnvironmentTable::new(env)),
+                Err(err) => self.render_error(err.to_string()),
+            },
+            DataKind::Files => match details.open_files() {
+                Ok(files) => {
+                    self.set_links(files.iter().map(|file| file.peer));
+                    let style = self.tree_data.styles.selected;
+                    self.render_table(
+                        header,
+                        LinksTable::with_files(files, self.link_index, style),
+                    )
+                }
+                Err(err) => self.render_error(err.to_string()),
+            },
+            DataKind::Maps => match details.memory_maps() {
+                Ok(maps) => {
+                    self.set_links(maps.iter().map(|map| map.peer));
+                    let style = self.tree_data.styles.selected;
+                    self.render_table(header, LinksTable::with_maps(maps, self.link_index, style))
+                }
+                Err(err) => self.render_error(err.to_string()),
+            },
+            DataKind::IoDevices => match process::io_by_device(process) {
+                Ok(io) => self.render_table(header, IoDevicesTable::new(io)),
+                Err(err) => self.render_error(err.to_string()),
+            },
+            DataKind::Mounts => match process::mount_usage(process) {
+                Ok(usages) => self.render_table(header, MountsTable::new(usages)),
+                Err(err) => self.render_error(err.to_string()),
+            },
+            DataKind::Namespaces => match process.namespaces() {
+                Ok(namespaces) => {
+                    let own = Process::myself()
+                        .and_then(|myself| myself.namespaces())
+                        .ok();
+                    let style = self.tree_data.styles.increase.add_modifier(Modifier::BOLD);
+                    self.render_table(
+                        header,
+                        NamespacesTable::new(&namespaces, own.as_ref(), style),
+                    )
+                }
                 Err(err) => self.render_error(err.to_string()),
             },
             _ => self.render_error("not implemented"),
         }
     }
+
+    /// Variables that differ in the environments of the current process and another one.
+    fn render_environment_diff(
+        &mut self,
+        details: &ProcessDetails,
+        other: &ProcessDetails,
+    ) -> anyhow::Result<()> {
+        self.pane_kind = PaneKind::Process(DataKind::EnvironmentDiff);
+        let header = self.process_header(details);
+        let environ = |details: &ProcessDetails| {
+            let title = format!("{} ({})", details.name(), details.process().pid());
+            details
+                .process()
+                .process()
+                .environ()
+                .map(|env| (title, env))
+        };
+        match (environ(details), environ(other)) {
+            (Ok(left), Ok(right)) => {
+                self.render_table(header, EnvironmentDiffTable::new(left, right))
+            }
+            (Err(err), _) | (_, Err(err)) => self.render_error(err.to_string()),
+        }
+    }
 }
 
 impl DisplayDevice for TerminalDevice<'_> {
     fn open(&mut self, metrics: SliceIter<FormattedMetric>) -> anyhow::Result<()> {
-        let mut last_id = None;
+        let mut columns = Vec::new();
+        let mut limit_columns = Vec::new();
+        let mut alignments = Vec::new();
+        let mut search_columns = Vec::new();
+        Collector::for_each_computed_metric(metrics, |metric, ag| {
+            columns.push((metric.name(), metric.id, ag));
+            alignments.push(cell_alignment(metric.column.alignment));
+            limit_columns.push(metric.id.is_per_mille() && ag != Aggregation::Ratio);
+            search_columns.push(SearchColumn {
+                name: match ag {
+                    Aggregation::None => metric.name().to_string(),
+                    Aggregation::Min => format!("{}+min", metric.name()),
+                    Aggregation::Max => format!("{}+max", metric.name()),
+                    Aggregation::Ratio => format!("{}+ratio", metric.name()),
+                },
+                per_mille: metric.id.is_per_mille() || ag == Aggregation::Ratio,
+            });
+        });
+        if let Some(data) = Rc::get_mut(&mut self.tree_data) {
+            data.limit_columns = limit_columns;
+            data.alignments = alignments;
+            data.bookmarks.set_search_columns(search_columns);
+        }
 
-        Collector::for_each_computed_metric(metrics, |id, ag| {
-            let mut header = id
-                .as_str()
+        let groups = columns
+            .chunk_by(|(name1, _, _), (name2, _, _)| name1 == name2)
+            .collect::<Vec<_>>();
+        if let Some(ref priority) = self.fit {
+            if let Some(data) = Rc::get_mut(&mut self.tree_data) {
+                data.fit = Some(ColumnFit::new(
+                    groups.iter().map(|group| (group[0].0, group.len())),
+                    priority,
+                ));
+            }
+        }
+
+        // Metrics with several aggregations have a title spanning the columns.
+        let mut start = 0;
+        for group in groups {
+            let (name, id, _) = group[0];
+            let mut title = name
                 .split(":")
-                .map(str::to_string)
-                .collect::<Vec<String>>();
-            if last_id.is_none() || last_id.unwrap() != id {
-                last_id = Some(id);
-                self.limit_slots.push(true);
+                .map(|s| Line::from(s.to_string()))
+                .collect::<Vec<Line>>();
+            let title_height = title.len();
+            let headers = if group.len() == 1 {
+                if let (_, _, Aggregation::Ratio) = group[0] {
+                    title.push(Line::from(self.ratio_label(id)));
+                }
+                vec![Text::from(title)]
             } else {
-                let name = format!(
-                    "{} ({})",
-                    header.pop().unwrap(),
-                    match ag {
-                        Aggregation::None => "none", // never used
-                        Aggregation::Min => "min",
-                        Aggregation::Max => "max",
-                        Aggregation::Ratio => "%",
-                    }
-                );
-                header.push(name);
-                self.limit_slots.push(false);
-            }
+                if let Some(data) = Rc::get_mut(&mut self.tree_data) {
+                    data.metric_groups.push(ColumnGroup::new(
+                        Text::from(title),
+                        start,
+                        group.len(),
+                    ));
+                }
+                group
+                    .iter()
+                    .map(|(_, id, ag)| {
+                        let label = match ag {
+                            Aggregation::None => "raw",
+                            Aggregation::Min => "min",
+                            Aggregation::Max => "max",
+                            Aggregation::Ratio => self.ratio_label(*id),
+                        };
+                        Text::from(
+                            std::iter::repeat_n(Line::default(), title_height)
+                                .chain(std::iter::once(Line::from(label)))
+                                .collect::<Vec<Line>>(),
+                        )
+                    })
+                    .collect::<Vec<Text>>()
+            };
+            self.limit_slots.push(true);
+            self.limit_slots
+                .extend(std::iter::repeat_n(false, group.len() - 1));
             if let Some(data) = Rc::get_mut(&mut self.tree_data) {
-                data.metric_headers.push(Text::from(
-                    header
-                        .iter()
-                        .map(|s| Line::from(s.to_string()))
-                        .collect::<Vec<Line>>(),
-                ));
+                data.metric_headers.extend(headers);
             }
-        });
+            start += group.len();
+        }
+        if self.title {
+            let screen = self.terminal.backend_mut().writer_mut();
+            write!(screen, "{SAVE_TITLE}")?;
+        }
+        write!(self.terminal.backend_mut().writer_mut(), "{ENABLE_PASTE}")?;
         self.terminal.hide_cursor()?;
         Ok(())
     }
@@ -696,18 +1470,58 @@ impl DisplayDevice for TerminalDevice<'_> {
     /// Show the cursor on exit.
     fn close(&mut self) -> anyhow::Result<()> {
         self.terminal.show_cursor()?;
+        write!(self.terminal.backend_mut().writer_mut(), "{DISABLE_PASTE}")?;
+        if self.title {
+            let screen = self.terminal.backend_mut().writer_mut();
+            write!(screen, "{RESTORE_TITLE}")?;
+            screen.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Restore raw mode and the alternate screen that the shell may have reset.
+    fn resume(&mut self) -> anyhow::Result<()> {
+        let screen = self.terminal.backend_mut().writer_mut();
+        screen.activate_raw_mode()?;
+        write!(screen, "{ToAlternateScreen}{ENABLE_PASTE}")?;
+        self.terminal.clear()?;
         Ok(())
     }
 
     /// Render the current pane.
-    fn render(&mut self, kind: PaneKind, data: PaneData, _redraw: bool) -> anyhow::Result<()> {
+    ///
+    /// The frames are skipped if they are too frequent, except after an
+    /// interaction, when the pane changes or when the targets have changed.
+    fn render(&mut self, kind: PaneKind, data: PaneData, redraw: bool) -> anyhow::Result<()> {
+        if !self.frames.allow(
+            Instant::now(),
+            redraw || self.dirty || kind != self.pane_kind,
+        ) {
+            return Ok(());
+        }
+        self.dirty = false;
+        self.error = None;
+        let size = self.terminal.size()?;
+        self.pane_offset = self.pane_offsets.switch(
+            kind,
+            SizeClass::new(size.width, size.height),
+            self.pane_offset,
+        );
         match (kind, data) {
             (PaneKind::Main, PaneData::Collector(collector)) => {
+                let paused = self.history.current().map(|(_, sample)| sample);
+                let collector = paused.as_deref().unwrap_or(collector);
+                self.lineage.clear();
+                self.lineage
+                    .extend(collector.lineage().map(|(pid, _)| *pid));
+                self.breadcrumb = self.breadcrumb.min(self.lineage.len().saturating_sub(1));
                 let is_incremental_search = self.tree_data.bookmarks.is_incremental_search();
                 match self.keymap {
                     KeyMap::IncrementalSearch if is_incremental_search => (),
                     KeyMap::Main if !is_incremental_search => (),
-                    KeyMap::Filters => (),
+                    KeyMap::Filters | KeyMap::GotoPid | KeyMap::Widen => (),
+                    KeyMap::Breadcrumbs if self.lineage.len() > 1 => (),
+                    KeyMap::Breadcrumbs => self.set_keymap(KeyMap::Main),
                     _ if is_incremental_search => {
                         log::error!("{}: wrong keymap for incremental search", self.keymap);
                         self.set_keymap(KeyMap::IncrementalSearch);
@@ -717,14 +1531,40 @@ impl DisplayDevice for TerminalDevice<'_> {
                         self.set_keymap(KeyMap::Main);
                     }
                 }
+                self.update_window_title(collector)?;
                 self.render_tree(collector)
             }
-            (PaneKind::Process(DataKind::Details), PaneData::Details(details)) => {
+            (PaneKind::Process(DataKind::Details), PaneData::Details(tabs)) => {
                 self.set_keymap(KeyMap::Details);
-                self.render_details(details)
+                self.render_details(tabs)
             }
-            (PaneKind::Process(kind), PaneData::Process(proc)) => {
+            (PaneKind::Process(DataKind::Threads), PaneData::Details(tabs)) => {
                 self.set_keymap(KeyMap::Process);
+                self.pane_kind = PaneKind::Process(DataKind::Threads);
+                let hot_style = self.tree_data.styles.increase.add_modifier(Modifier::BOLD);
+                match tabs.current() {
+                    Some(details) => self.render_table(
+                        self.process_header(details),
+                        ThreadsTable::new(details.threads(), hot_style),
+                    ),
+                    None => self.render_error("no process selected"),
+                }
+            }
+            (PaneKind::Process(DataKind::EnvironmentDiff), PaneData::Pair(proc, other)) => {
+                self.set_keymap(KeyMap::Process);
+                self.render_environment_diff(proc, other)
+            }
+            (PaneKind::Process(DataKind::EnvironmentDiff), PaneData::Process(proc)) => {
+                self.set_keymap(KeyMap::Process);
+                self.pane_kind = PaneKind::Process(DataKind::EnvironmentDiff);
+                self.render_error(format!("{}: no process to compare", proc.name()))
+            }
+            (PaneKind::Process(kind), PaneData::Process(proc)) => {
+                self.set_keymap(if matches!(kind, DataKind::Files | DataKind::Maps) {
+                    KeyMap::Links
+                } else {
+                    KeyMap::Process
+                });
                 self.render_process(kind, proc)
             }
             (PaneKind::Help, _) => {
@@ -735,12 +1575,95 @@ impl DisplayDevice for TerminalDevice<'_> {
         }
     }
 
+    fn record(&mut self, collector: &Collector) {
+        if self.history.is_enabled() {
+            self.history.push(Local::now(), collector.snapshot());
+        }
+    }
+
+    fn set_workload(&mut self, workload: &Workload) {
+        self.workload = Some(*workload);
+    }
+
+    fn set_overhead(&mut self, overhead: &Overhead) {
+        self.overhead = Some(*overhead);
+    }
+
+    fn notify(&mut self, msg: &str) {
+        self.notice = Some(msg.to_string());
+        self.dirty = true;
+    }
+
+    fn dump_state(&self) -> Json {
+        let bookmarks = &self.tree_data.bookmarks;
+        Json::Object(vec![
+            ("pane", Json::debug(self.pane_kind)),
+            ("keymap", Json::from(self.keymap.to_string())),
+            ("filter", Json::from(self.filter.to_string())),
+            ("table_offset", Json::debug(self.table_offset)),
+            ("pane_offset", Json::from(self.pane_offset as usize)),
+            ("overflow", Json::debug(self.overflow)),
+            ("body_height", Json::from(self.body_height)),
+            (
+                "selected",
+                Json::from(bookmarks.selected().map(Json::debug)),
+            ),
+            ("action", Json::debug(bookmarks.action())),
+            (
+                "search",
+                Json::from(bookmarks.search().as_ref().map(|search| search.pattern())),
+            ),
+            ("marks", Json::array(bookmarks.marks().iter().copied())),
+            ("pins", Json::array(bookmarks.pins().iter().copied())),
+            ("grouped", Json::from(self.tree_data.grouped)),
+            ("missing_pid", Json::from(bookmarks.missing_pid())),
+            ("lineage", Json::array(self.lineage.iter().copied())),
+            ("breadcrumb", Json::from(self.breadcrumb)),
+            ("notice", Json::from(self.notice.clone())),
+        ])
+    }
+
+    fn alert(&mut self, msg: &str) {
+        self.notify(msg);
+        let signal = match self.bell {
+            AlertSignal::None => return,
+            AlertSignal::Bell => String::from("\x07"),
+            AlertSignal::Osc9 => osc(9, msg),
+        };
+        let screen = self.terminal.backend_mut().writer_mut();
+        if let Err(err) = write!(screen, "{signal}").and_then(|_| screen.flush()) {
+            log::error!("cannot signal alert: {err}");
+        }
+    }
+
+    fn set_wakeup(&mut self, wakeup: Option<Duration>) {
+        self.wakeup = wakeup;
+    }
+
     /// Wait for a user input or a timeout.
     fn pause(&mut self, timer: &mut Timer) -> anyhow::Result<PauseStatus> {
+        self.every = timer.get_delay();
         if let Some(timeout) = timer.remaining() {
+            let timeout = self.wakeup.map_or(timeout, |wakeup| wakeup.min(timeout));
             if let Some(evt) = self.events.receive_timeout(timeout)? {
+                let recorded = self
+                    .recording
+                    .map(|start| (start.elapsed(), evt.clone(), self.pane_kind, self.keymap));
                 let action = self.react(self.keymap.action_from_event(evt), timer)?;
-                Ok(PauseStatus::Action(self.interaction(action)))
+                if let Some((elapsed, evt, pane_kind, keymap)) = recorded {
+                    let interaction = self.interaction(action.clone());
+                    log_event(
+                        elapsed,
+                        &evt,
+                        &format!(
+                            "action={action:?} interaction={interaction:?} pane={pane_kind:?}->{:?} keymap={keymap}->{}",
+                            self.pane_kind, self.keymap
+                        ),
+                    );
+                    Ok(PauseStatus::Action(interaction))
+                } else {
+                    Ok(PauseStatus::Action(self.interaction(action)))
+                }
             } else {
                 Ok(PauseStatus::TimeOut)
             }
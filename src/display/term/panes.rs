@@ -296,11 +296,17 @@ pub(crate) struct MarkdownWidget<'l> {
     text: Vec<Line<'l>>,
 }
 
-impl MarkdownWidget<'_> {
+impl<'l> MarkdownWidget<'l> {
     pub(crate) fn new(title: &'static str, text: &'static str) -> Self {
         let text = format_text(text);
         Self { title, text }
     }
+
+    /// Text displayed as is such as an error message.
+    pub(crate) fn plain(title: &'static str, text: &'l str) -> Self {
+        let text = text.lines().map(Line::from).collect();
+        Self { title, text }
+    }
 }
 
 impl StatefulWidget for MarkdownWidget<'_> {
@@ -312,7 +318,14 @@ impl StatefulWidget for MarkdownWidget<'_> {
     {
         let borders = BORDER_SIZE * 2;
         let inner_height = area.height - borders;
-        let max_offset = self.text.len().saturating_sub(inner_height as usize / 2);
+        // Number of rows once the lines are wrapped, assuming they are cut anywhere.
+        let inner_width = area.width.saturating_sub(borders).max(1) as usize;
+        let rows = self
+            .text
+            .iter()
+            .map(|line| line.width().div_ceil(inner_width).max(1))
+            .sum::<usize>();
+        let max_offset = rows.saturating_sub(inner_height as usize / 2);
         state.position = cmp::min(state.position, max_offset);
         state.visible_length = inner_height as usize;
         let mut scroll_state = ScrollbarState::new(max_offset).position(state.position);
@@ -348,25 +361,68 @@ impl BigTableState {
     }
 }
 
+/// Title on the top lines of the headers spanning several body columns.
+#[derive(Debug)]
+pub(crate) struct ColumnGroup<'t> {
+    /// Title of the group.
+    pub(crate) title: Text<'t>,
+    /// Index of the first body column.
+    pub(crate) start: usize,
+    /// Number of columns in the group.
+    pub(crate) span: usize,
+}
+
+impl<'t> ColumnGroup<'t> {
+    pub(crate) fn new(title: Text<'t>, start: usize, span: usize) -> Self {
+        Self { title, start, span }
+    }
+
+    /// Width of the title.
+    pub(crate) fn width(&self) -> usize {
+        self.title.width()
+    }
+}
+
+/// Keep the frozen columns on the left and the visible part of the others.
+pub(crate) fn frozen_columns<'c, I>(cells: I, frozen: usize, zoom: &Zoom) -> Vec<Cell<'c>>
+where
+    I: IntoIterator<Item = Cell<'c>>,
+{
+    let mut cells = cells.into_iter();
+    let mut visible = cells.by_ref().take(frozen).collect::<Vec<Cell>>();
+    visible.extend(cells.skip(zoom.position).take(zoom.visible_length));
+    visible
+}
+
 /// Table generator
+///
+/// The headers and the columns on the left are frozen panes. They stay in place
+/// while the rest of the table scrolls horizontally and vertically.
 pub(crate) trait TableGenerator {
-    /// The number of fixed columns on the left and fixed rows on the top.
+    /// The number of frozen columns on the left and frozen rows on the top.
     ///
     /// If the width is not zero, it's a crosstab.
     fn headers_size(&self) -> Area<usize>;
 
     /// The visible headers.
     ///
-    /// The fixed columns must always be included.
-    fn top_headers(&self, zoom: &Zoom) -> Vec<Cell>;
+    /// The frozen columns must always be included, see `frozen_columns`.
+    fn top_headers(&self, zoom: &Zoom) -> Vec<Cell<'_>>;
 
     /// The visible rows.
     ///
-    /// The fixed columns must always be included.
-    fn rows(&self, zoom: &BigTableState) -> Vec<Vec<Cell>>;
+    /// The frozen columns must always be included, see `frozen_columns`.
+    fn rows(&self, zoom: &BigTableState) -> Vec<Vec<Cell<'_>>>;
 
     /// The width of each column.
     fn widths(&self) -> &[u16];
+
+    /// Titles spanning several body columns.
+    ///
+    /// The headers of the columns must leave the top lines empty for the titles.
+    fn column_groups(&self) -> &[ColumnGroup<'_>] {
+        &[]
+    }
 }
 
 /// StateGenerator
@@ -423,6 +479,51 @@ impl<'a, T: TableGenerator> BigTableWidget<'a, T> {
         }
         (cc.constraints, start, index, headers_width)
     }
+
+    /// Render the titles of the column groups over the headers.
+    ///
+    /// Columns are laid out from the left since all constraints are lengths.
+    fn render_column_groups(
+        &self,
+        inner: Rect,
+        buf: &mut Buffer,
+        constraints: &[Constraint],
+        first: usize,
+    ) {
+        let fixed_count = self.table.headers_size().horizontal;
+        let mut x = inner.x;
+        let columns = constraints
+            .iter()
+            .map(|constraint| {
+                let width = match constraint {
+                    Constraint::Length(width) => *width,
+                    _ => 0,
+                };
+                let column = (x, width);
+                x = x.saturating_add(width + self.style.column_spacing);
+                column
+            })
+            .collect::<Vec<(u16, u16)>>();
+        let body_columns = &columns[cmp::min(fixed_count, columns.len())..];
+        let body_offset = first.saturating_sub(fixed_count);
+        for group in self.table.column_groups() {
+            let start = cmp::max(group.start, body_offset) - body_offset;
+            let end = cmp::min(
+                (group.start + group.span).saturating_sub(body_offset),
+                body_columns.len(),
+            );
+            if start < end {
+                let left = body_columns[start].0;
+                let (last_x, last_width) = body_columns[end - 1];
+                let right = cmp::min(last_x + last_width, inner.right());
+                let height = cmp::min(group.title.height() as u16, inner.height);
+                let area = Rect::new(left, inner.y, right.saturating_sub(left), height);
+                Paragraph::new(group.title.clone())
+                    .alignment(Alignment::Center)
+                    .render(area, buf);
+            }
+        }
+    }
 }
 
 impl<T: TableGenerator> StatefulWidget for BigTableWidget<'_, T> {
@@ -447,7 +548,7 @@ impl<T: TableGenerator> StatefulWidget for BigTableWidget<'_, T> {
         let rows = self.style.apply(self.table.rows(state));
 
         let table = {
-            let table = Table::new(rows, constraints)
+            let table = Table::new(rows, &constraints)
                 .block(Block::default().borders(Borders::ALL))
                 .column_spacing(self.style.column_spacing);
             if headers.is_empty() {
@@ -457,6 +558,10 @@ impl<T: TableGenerator> StatefulWidget for BigTableWidget<'_, T> {
             }
         };
         Widget::render(table, area, buf);
+        if !self.table.column_groups().is_empty() {
+            let inner = area.inner(Margin::new(BORDER_SIZE, BORDER_SIZE));
+            self.render_column_groups(inner, buf, &constraints, first);
+        }
         if let Some(mut bar_state) = state.zoom.horizontal.scrollbar_state() {
             let x = area.x + BORDER_SIZE + headers_width;
             let width = area.width.saturating_sub(x + BORDER_SIZE);
@@ -543,19 +648,36 @@ impl SingleScrollablePane {
         Self { area, rects }
     }
 
+    /// Push a fixed height widget at the top.
+    pub(crate) fn with_top<W: ReactiveWidget>(mut self, widget: &W) -> Self {
+        let main_rect = self.rects.first_mut().expect("must have a first rectangle");
+        let height = widget.min_height(self.area).min(main_rect.height);
+        let y = main_rect.y;
+        main_rect.y += height;
+        main_rect.height -= height;
+        self.rects
+            .push(Rect::new(self.area.x, y, self.area.width, height));
+        self
+    }
+
     /// Push a fixed height widget at the bottom.
     pub(crate) fn with<W: ReactiveWidget>(mut self, widget: &W) -> Self {
         let height = widget.min_height(self.area);
         let main_rect = self.rects.first_mut().expect("must have a first rectangle");
         main_rect.height = main_rect.height.saturating_sub(height);
-        self.rects.iter_mut().skip(1).for_each(|r| {
-            if r.y < height {
-                r.height -= height.saturating_sub(r.y);
-                r.y = 0;
-            } else {
-                r.y -= height
-            }
-        });
+        let main_y = main_rect.y;
+        self.rects
+            .iter_mut()
+            .skip(1)
+            .filter(|r| r.y >= main_y)
+            .for_each(|r| {
+                if r.y < height {
+                    r.height = r.height.saturating_sub(height.saturating_sub(r.y));
+                    r.y = 0;
+                } else {
+                    r.y -= height
+                }
+            });
         self.rects.push(Rect::new(
             self.area.x,
             self.area.height.saturating_sub(height),
@@ -629,6 +751,14 @@ impl GridPane {
         self.lines.push(GridLine::Line(height));
         self
     }
+
+    pub(crate) fn with_line_if<W: ReactiveWidget>(self, widget: &W, cond: bool) -> Self {
+        if cond {
+            self.with_line(widget)
+        } else {
+            self
+        }
+    }
 }
 
 impl Pane for GridPane {
@@ -739,8 +869,14 @@ mod test {
     use rstest::*;
     use std::cmp;
 
+    use ratatui::{
+        style::Style,
+        widgets::{Cell, StatefulWidget},
+    };
+
     use super::{
-        ColumnConstraints, ColumnStatus, GridPane, Pane, ReactiveWidget, SingleScrollablePane,
+        frozen_columns, Area, BigTableState, BigTableWidget, ColumnConstraints, ColumnStatus,
+        GridPane, Pane, ReactiveWidget, SingleScrollablePane, TableGenerator, TableStyle, Zoom,
     };
 
     /// Create a column constraints object and feed it.
@@ -953,6 +1089,55 @@ mod test {
         assert_eq!(expected, rects);
     }
 
+    /// SingleScrollablePane with a widget on top
+    ///
+    /// 0 w0
+    /// 1 main
+    /// ...
+    /// 6 main
+    /// 7 w1
+    /// 8 w1
+    /// 9 w2
+    #[test]
+    fn test_single_scrollable_pane_with_top() {
+        let screen = Rect::new(0, 0, 15, 10);
+        let w0 = MockWidget(1);
+        let w1 = MockWidget(2);
+        let w2 = MockWidget(1);
+        let rects = SingleScrollablePane::new(screen, 4)
+            .with_top(&w0)
+            .with(&w1)
+            .with(&w2)
+            .build();
+        assert_eq!(
+            vec![
+                Some(Rect::new(0, 1, 15, 6)),
+                Some(Rect::new(0, 0, 15, 1)),
+                Some(Rect::new(0, 7, 15, 2)),
+                Some(Rect::new(0, 9, 15, 1))
+            ],
+            rects
+        );
+    }
+
+    /// SingleScrollablePane with an empty widget on top
+    #[test]
+    fn test_single_scrollable_pane_with_empty_top() {
+        let screen = Rect::new(0, 0, 15, 10);
+        let rects = SingleScrollablePane::new(screen, 3)
+            .with_top(&MockWidget(0))
+            .with(&MockWidget(2))
+            .build();
+        assert_eq!(
+            vec![
+                Some(Rect::new(0, 0, 15, 8)),
+                None,
+                Some(Rect::new(0, 8, 15, 2))
+            ],
+            rects
+        );
+    }
+
     /// GridPane
     ///
     /// Case 1: large height with a gap between the last widget and the bottom line.
@@ -993,4 +1178,56 @@ mod test {
             .build();
         assert_eq!(expected, rects);
     }
+
+    /// Table with a frozen column and rows such as `k0 a0 b0 c0`.
+    struct MockTable {
+        widths: Vec<u16>,
+    }
+
+    impl TableGenerator for MockTable {
+        fn headers_size(&self) -> Area<usize> {
+            Area::new(1, 1)
+        }
+
+        fn top_headers(&self, zoom: &Zoom) -> Vec<Cell<'_>> {
+            frozen_columns(["K", "A", "B", "C"].map(Cell::from), 1, zoom)
+        }
+
+        fn rows(&self, state: &BigTableState) -> Vec<Vec<Cell<'_>>> {
+            (0..5)
+                .skip(state.zoom.vertical.position)
+                .take(state.zoom.vertical.visible_length)
+                .map(|n| {
+                    frozen_columns(
+                        ["k", "a", "b", "c"].map(|s| Cell::from(format!("{s}{n}"))),
+                        1,
+                        &state.zoom.horizontal,
+                    )
+                })
+                .collect()
+        }
+
+        fn widths(&self) -> &[u16] {
+            &self.widths
+        }
+    }
+
+    fn buffer_line(buf: &Buffer, y: u16) -> String {
+        (1..buf.area.width - 1)
+            .map(|x| buf[(x, y)].symbol())
+            .collect()
+    }
+
+    #[test]
+    fn test_big_table_frozen_panes() {
+        let table = MockTable { widths: vec![2; 4] };
+        let area = Rect::new(0, 0, 10, 5);
+        let mut buf = Buffer::empty(area);
+        let mut state = BigTableState::new(Zoom::new(1, 0, 3), Zoom::new(2, 0, 5));
+        let style = TableStyle::new(1, Style::default(), Style::default());
+        BigTableWidget::new(&table, style).render(area, &mut buf, &mut state);
+        assert_eq!("K  B  C ", buffer_line(&buf, 1));
+        assert_eq!("k2 b2 c2", buffer_line(&buf, 2));
+        assert_eq!("k3 b3 c3", buffer_line(&buf, 3));
+    }
 }
@@ -0,0 +1,324 @@
+// Oprs -- process monitor for Linux
+// Copyright (C) 2025  Laurent Pelecq
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Snapshots of the panes rendered in a test backend.
+//!
+//! The panes are drawn by the same functions as the terminal device. The
+//! snapshots are the lines of the screen with the trailing spaces removed.
+
+use ratatui::{
+    backend::TestBackend,
+    buffer::Buffer,
+    style::Style,
+    text::{Line, Text},
+    Frame, Terminal,
+};
+use std::{borrow::Cow, collections::HashMap, ffi::OsString, rc::Rc};
+
+use super::{
+    draw_error, draw_table, draw_tree,
+    input::BookmarkAction,
+    panes::{BigTableWidget, FieldsWidget, MarkdownWidget, OneLineWidget, TableStyle},
+    tables::{EnvironmentTable, ProcessTreeTable, Styles, TreeData},
+    BigTableState, BigTableStateGenerator, DetailsPane, Zoom,
+};
+use crate::process::{Collector, MetricNamesParser};
+
+/// Draw in a terminal of the given size and return the buffer.
+fn render<F>(width: u16, height: u16, draw: F) -> Buffer
+where
+    F: FnOnce(&mut Frame),
+{
+    let mut terminal = Terminal::new(TestBackend::new(width, height)).unwrap();
+    terminal.draw(draw).unwrap();
+    terminal.backend().buffer().clone()
+}
+
+/// Lines of the buffer without trailing spaces.
+fn snapshot(buffer: &Buffer) -> Vec<String> {
+    let area = buffer.area;
+    (area.top()..area.bottom())
+        .map(|y| {
+            (area.left()..area.right())
+                .map(|x| buffer[(x, y)].symbol())
+                .collect::<String>()
+                .trim_end()
+                .to_string()
+        })
+        .collect()
+}
+
+fn assert_snapshot(expected: &[&str], buffer: &Buffer) {
+    let actual = snapshot(buffer);
+    assert_eq!(expected, actual, "\n{}", actual.join("\n"));
+}
+
+fn one_line(text: &str) -> OneLineWidget<'_> {
+    OneLineWidget::new(Text::from(text), Style::default(), None)
+}
+
+fn table_style(styles: &Styles) -> TableStyle {
+    TableStyle::new(styles.column_spacing, styles.even_row, styles.odd_row)
+}
+
+/// Draw the main table with the first line selected.
+fn render_tree(width: u16, height: u16, count: usize) -> (Buffer, Style) {
+    let metrics = MetricNamesParser::new(false).parse(&["fd:all"]).unwrap();
+    let mut collector = Collector::new(Cow::Borrowed(&metrics));
+    for index in 0..count {
+        let pid = -2 - index as i32;
+        collector.record_pseudo(&format!("proc{index}"), pid, &[index as u64 * 10]);
+    }
+    let mut data = TreeData::new(Styles::new(None));
+    data.metric_headers = vec![Text::from(vec![Line::from("fd"), Line::from("all")])];
+    data.bookmarks.set_action(BookmarkAction::FirstLine);
+    let lines = data.lines(&collector);
+    data.bookmarks
+        .execute(&mut data.occurrences, lines.into_iter(), 0, 1);
+    let selected = data.styles.selected;
+    let data = Rc::new(data);
+    let table = ProcessTreeTable::new(&collector, Rc::clone(&data), width - 2, 0..height as usize);
+    let mut state = BigTableState::new(
+        Zoom::new(0, 0, table.body_column_count()),
+        Zoom::new(0, 0, table.body_row_count()),
+    );
+    let main = BigTableWidget::new(&table, table_style(&data.styles));
+    let buffer = render(width, height, |frame| {
+        draw_tree(
+            frame,
+            main,
+            &mut state,
+            [one_line("proc0"), one_line("status"), one_line("q Quit")],
+            false,
+        )
+    });
+    (buffer, selected)
+}
+
+#[test]
+fn test_tree_pane() {
+    let (buffer, selected) = render_tree(30, 10, 3);
+    assert_snapshot(
+        &[
+            "proc0",
+            "┌────────────────────────────┐",
+            "│Process  PID  S  fd         │",
+            "│                 all        │",
+            "│proc0     -2       0        │",
+            "│proc1     -3      10        │",
+            "│proc2     -4      20        │",
+            "└────────────────────────────┘",
+            "status",
+            "q Quit",
+        ],
+        &buffer,
+    );
+    assert!(buffer[(1, 4)].modifier.contains(selected.add_modifier));
+    assert!(!buffer[(1, 5)].modifier.contains(selected.add_modifier));
+}
+
+#[test]
+fn test_tree_pane_scrollbar() {
+    let (buffer, _) = render_tree(24, 10, 12);
+    assert_snapshot(
+        &[
+            "proc0",
+            "┌──────────────────────┐",
+            "│Process  PID  S  fd   │",
+            "│                 all  │",
+            "│proc0     -2       0  █",
+            "│proc1     -3      10  ║",
+            "│proc2     -4      20  ║",
+            "└──────────────────────┘",
+            "status",
+            "q Quit",
+        ],
+        &buffer,
+    );
+}
+
+#[test]
+fn test_details_pane() {
+    let process = [
+        ("Name", " sleep ".to_string()),
+        ("Process ID", "42".to_string()),
+    ];
+    let files = [("Descriptors", "3".to_string())];
+    let time = [("CPU", "0.1".to_string())];
+    let memory = [("RSS", "1M".to_string())];
+    let pane = |with_tab_bar| DetailsPane {
+        header: one_line(" sleep │ PID 42 │ Sleeping (S) │ root"),
+        cmdline: OneLineWidget::new(Text::from("sleep 60"), Style::default(), Some("Command")),
+        cwd: OneLineWidget::new(
+            Text::from("/tmp"),
+            Style::default(),
+            Some("Working Directory"),
+        ),
+        process: FieldsWidget::new("Process", &process),
+        files: FieldsWidget::new("Files", &files),
+        time: FieldsWidget::new("Time", &time),
+        memory: FieldsWidget::new("Memory", &memory),
+        tab_bar: one_line("[sleep] bash"),
+        with_tab_bar,
+        menu: one_line("q Quit"),
+    };
+    let buffer = render(40, 16, |frame| pane(false).draw(frame, 0));
+    assert_snapshot(
+        &[
+            " sleep │ PID 42 │ Sleeping (S) │ root",
+            "┌ Command ─────────────────────────────┐",
+            "│sleep 60                              │",
+            "└──────────────────────────────────────┘",
+            "┌ Working Directory ───────────────────┐",
+            "│/tmp                                  │",
+            "└──────────────────────────────────────┘",
+            "┌Process───────────┐┌Files─────────────┐",
+            "│Name        sleep ││Descriptors      3│",
+            "│Process ID      42││                  │",
+            "└──────────────────┘└──────────────────┘",
+            "┌Time──────────────┐┌Memory────────────┐",
+            "│CPU            0.1││RSS             1M│",
+            "└──────────────────┘└──────────────────┘",
+            "",
+            "q Quit",
+        ],
+        &buffer,
+    );
+    // The header stays on top when the blocks are scrolled.
+    let buffer = render(40, 16, |frame| pane(true).draw(frame, 2));
+    assert_snapshot(
+        &[
+            " sleep │ PID 42 │ Sleeping (S) │ root",
+            "┌Process───────────┐┌Files─────────────┐",
+            "│Name        sleep ││Descriptors      3│",
+            "│Process ID      42││                  │",
+            "└──────────────────┘└──────────────────┘",
+            "┌Time──────────────┐┌Memory────────────┐",
+            "│CPU            0.1││RSS             1M│",
+            "└──────────────────┘└──────────────────┘",
+            "",
+            "",
+            "",
+            "",
+            "",
+            "",
+            "[sleep] bash",
+            "q Quit",
+        ],
+        &buffer,
+    );
+}
+
+#[test]
+fn test_table_pane() {
+    let env = (0..8)
+        .map(|index| {
+            (
+                OsString::from(format!("VAR{index}")),
+                OsString::from(format!("value{index}")),
+            )
+        })
+        .collect::<HashMap<OsString, OsString>>();
+    let table = EnvironmentTable::new(env);
+    let styles = Styles::new(None);
+    let render_at = |offset| {
+        let mut state = table.state();
+        state.zoom.vertical.position = offset;
+        let main = BigTableWidget::new(&table, table_style(&styles));
+        render(30, 8, |frame| {
+            draw_table(
+                frame,
+                one_line(" env"),
+                main,
+                &mut state,
+                one_line("q Quit"),
+            )
+        })
+    };
+    assert_snapshot(
+        &[
+            " env",
+            "┌────────────────────────────┐",
+            "│VAR0  value0                █",
+            "│VAR1  value1                █",
+            "│VAR2  value2                ║",
+            "│VAR3  value3                ║",
+            "└────────────────────────────┘",
+            "q Quit",
+        ],
+        &render_at(0),
+    );
+    // The offset beyond the end is reframed on the last rows.
+    assert_snapshot(
+        &[
+            " env",
+            "┌────────────────────────────┐",
+            "│VAR4  value4                ║",
+            "│VAR5  value5                ║",
+            "│VAR6  value6                █",
+            "│VAR7  value7                █",
+            "└────────────────────────────┘",
+            "q Quit",
+        ],
+        &render_at(10),
+    );
+}
+
+#[test]
+fn test_error_pane() {
+    let err = "/proc/1/fd: permission denied\nrun as root or with CAP_SYS_PTRACE to see the files";
+    let render_at = |offset| {
+        let mut state = Zoom::with_position(offset);
+        let buffer = render(24, 7, |frame| {
+            draw_error(
+                frame,
+                MarkdownWidget::plain("Error", err),
+                &mut state,
+                one_line("q Quit L Log"),
+            )
+        });
+        (buffer, state.position)
+    };
+    let (buffer, position) = render_at(0);
+    assert_eq!(0, position);
+    assert_snapshot(
+        &[
+            "┌─────── Error ────────┐",
+            "│/proc/1/fd: permission█",
+            "│denied                █",
+            "│run as root or with   █",
+            "│CAP_SYS_PTRACE to see ║",
+            "└──────────────────────┘",
+            "q Quit L Log",
+        ],
+        &buffer,
+    );
+    // The offset is limited to keep the end of the message visible.
+    let (buffer, position) = render_at(10);
+    assert_eq!(3, position);
+    assert_snapshot(
+        &[
+            "┌─────── Error ────────┐",
+            "│CAP_SYS_PTRACE to see ║",
+            "│the files             █",
+            "│                      █",
+            "│                      █",
+            "└──────────────────────┘",
+            "q Quit L Log",
+        ],
+        &buffer,
+    );
+}
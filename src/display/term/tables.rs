@@ -17,7 +17,7 @@
 use getset::Getters;
 use itertools::izip;
 use libc::pid_t;
-use procfs::process::{Limit, LimitValue, Limits};
+use procfs::process::{Limit, LimitValue, Limits, Namespaces};
 use ratatui::{
     layout::Alignment,
     style::{Color, Modifier, Style, Stylize},
@@ -25,26 +25,40 @@ use ratatui::{
     widgets::Cell,
 };
 use std::{
-    cmp::Ordering,
-    collections::{BTreeSet, HashMap},
+    borrow::Cow,
+    cmp::{self, Ordering},
+    collections::{BTreeMap, BTreeSet, HashMap},
     ffi::OsString,
+    fs,
+    ops::Range,
     rc::Rc,
 };
 
 use super::{
-    input::Bookmarks,
-    panes::{BigTableState, BigTableStateGenerator, TableGenerator, Zoom},
+    fit::ColumnFit,
+    input::{Bookmarks, SearchField, SearchLine},
+    panes::{
+        frozen_columns, BigTableState, BigTableStateGenerator, ColumnGroup, TableGenerator, Zoom,
+    },
     types::{Area, MaxLength},
 };
 
 use crate::{
-    console::BuiltinTheme,
+    console::{
+        charset::{TableChar, TableCharSet},
+        BuiltinTheme,
+    },
     process::{
-        format::{human_format, Unit},
-        Collector, ProcessIdentity, ProcessSamples,
+        format::{self, human_format, human_milliseconds, size, ColumnAlignment, Unit},
+        process_name, Collector, IoByDevice, LimitBreach, MappedRegion, MountUsage, OpenFile,
+        ProcessIdentity, ProcessSamples, Sample, ThreadMonitor,
     },
 };
 
+/// Indicators of the highest and lowest values.
+const WATERMARK_HIGH: &str = "▲";
+const WATERMARK_LOW: &str = "▼";
+
 /// Aligned cell.
 macro_rules! aligned_cell {
     ($s:expr, $align:expr) => {
@@ -77,6 +91,10 @@ pub(crate) enum PidStatus {
     Marked,
     /// Search match.
     Matching,
+    /// Pinned at the top.
+    Pinned,
+    /// Real-time scheduling policy.
+    RealTime,
 }
 
 /// Theme styles
@@ -90,6 +108,10 @@ pub(crate) struct Styles {
     pub(crate) increase: Style,
     /// Decreasing value
     pub(crate) decrease: Style,
+    /// Usage close to a limit
+    pub(crate) near_limit: Style,
+    /// Usage at a limit
+    pub(crate) at_limit: Style,
     /// Unselected line
     pub(crate) unselected: Style,
     /// Selected line
@@ -98,6 +120,10 @@ pub(crate) struct Styles {
     pub(crate) marked: Style,
     /// Matching line
     pub(crate) matching: Style,
+    /// Pinned line
+    pub(crate) pinned: Style,
+    /// Process with a real-time scheduling policy
+    pub(crate) realtime: Style,
     /// Status line
     pub(crate) status: Style,
     /// Space between columns in number of characters
@@ -116,10 +142,14 @@ impl Styles {
                 odd_row: Style::default().bg(Color::Indexed(238)),
                 increase: Style::default().fg(Color::Indexed(196)),
                 decrease: Style::default().fg(Color::Indexed(46)),
+                near_limit: Style::default().fg(Color::Indexed(214)),
+                at_limit: bold.fg(Color::White).bg(Color::Indexed(160)),
                 unselected: bold,
                 selected: Style::default().fg(Color::Black).bg(Color::LightMagenta),
                 marked: Style::default().fg(Color::LightCyan),
                 matching: Style::default().fg(Color::LightMagenta),
+                pinned: bold.fg(Color::LightYellow),
+                realtime: bold.fg(Color::Indexed(201)),
                 status: white_on_blue,
                 column_spacing: 2,
             },
@@ -128,10 +158,14 @@ impl Styles {
                 odd_row: Style::default().bg(Color::Indexed(254)),
                 increase: Style::default().fg(Color::Indexed(124)),
                 decrease: Style::default().fg(Color::Indexed(40)),
+                near_limit: Style::default().fg(Color::Indexed(166)),
+                at_limit: bold.fg(Color::White).bg(Color::Indexed(160)),
                 unselected: bold,
                 selected: Style::default().fg(Color::White).bg(Color::Magenta),
                 marked: Style::default().fg(Color::Cyan),
                 matching: Style::default().fg(Color::Magenta),
+                pinned: bold.fg(Color::Yellow),
+                realtime: bold.fg(Color::Indexed(127)),
                 status: white_on_blue,
                 column_spacing: 2,
             },
@@ -140,10 +174,14 @@ impl Styles {
                 odd_row: default_style,
                 increase: Style::default().fg(Color::LightMagenta),
                 decrease: Style::default().fg(Color::LightGreen),
+                near_limit: Style::default().fg(Color::LightYellow),
+                at_limit: bold.fg(Color::White).bg(Color::Red),
                 unselected: bold,
                 selected: Style::default().fg(Color::Black).bg(Color::LightMagenta),
                 marked: Style::default().fg(Color::LightCyan),
                 matching: Style::default().fg(Color::LightMagenta),
+                pinned: bold.fg(Color::LightYellow),
+                realtime: bold.fg(Color::LightRed),
                 status: white_on_blue,
                 column_spacing: 2,
             },
@@ -152,22 +190,48 @@ impl Styles {
                 odd_row: default_style,
                 increase: Style::default().fg(Color::Red),
                 decrease: Style::default().fg(Color::Green),
+                near_limit: Style::default().fg(Color::Yellow),
+                at_limit: bold.fg(Color::White).bg(Color::Red),
                 unselected: bold,
                 selected: Style::default().fg(Color::White).bg(Color::Magenta),
                 marked: Style::default().fg(Color::Cyan),
                 matching: Style::default().fg(Color::Magenta),
+                pinned: bold.fg(Color::Yellow),
+                realtime: bold.fg(Color::Red),
                 status: white_on_blue,
                 column_spacing: 2,
             },
-            None => Styles {
+            Some(BuiltinTheme::HighContrast) => Styles {
+                even_row: Style::default().fg(Color::White).bg(Color::Black),
+                odd_row: Style::default().fg(Color::White).bg(Color::Black),
+                increase: bold.fg(Color::LightYellow),
+                decrease: bold.fg(Color::LightCyan),
+                near_limit: bold.fg(Color::LightRed),
+                at_limit: bold.fg(Color::Black).bg(Color::LightRed),
+                unselected: bold.fg(Color::White),
+                selected: bold.fg(Color::Black).bg(Color::LightYellow),
+                marked: bold.fg(Color::LightCyan).add_modifier(Modifier::UNDERLINED),
+                matching: bold
+                    .fg(Color::LightYellow)
+                    .add_modifier(Modifier::UNDERLINED),
+                pinned: bold.fg(Color::White).add_modifier(Modifier::ITALIC),
+                realtime: bold.fg(Color::LightRed).add_modifier(Modifier::UNDERLINED),
+                status: bold.fg(Color::Black).bg(Color::White),
+                column_spacing: 2,
+            },
+            Some(BuiltinTheme::Mono) | None => Styles {
                 even_row: default_style,
                 odd_row: default_style,
                 increase: bold,
                 decrease: bold,
+                near_limit: bold.add_modifier(Modifier::UNDERLINED),
+                at_limit: bold_reversed,
                 unselected: bold,
                 selected: bold_reversed,
                 marked: bold.add_modifier(Modifier::UNDERLINED),
                 matching: Style::default().add_modifier(Modifier::UNDERLINED),
+                pinned: bold.add_modifier(Modifier::ITALIC),
+                realtime: Style::default().add_modifier(Modifier::ITALIC | Modifier::UNDERLINED),
                 status: bold_reversed,
                 column_spacing: 2,
             },
@@ -180,6 +244,17 @@ impl Styles {
             PidStatus::Selected => self.selected,
             PidStatus::Marked => self.marked,
             PidStatus::Matching => self.matching,
+            PidStatus::Pinned => self.pinned,
+            PidStatus::RealTime => self.realtime,
+        }
+    }
+
+    /// Style of a usage of a limit in per thousand, if it's high.
+    fn limit_style(&self, value: u64) -> Option<Style> {
+        match LimitBreach::with_per_mille(value) {
+            LimitBreach::At => Some(self.at_limit),
+            LimitBreach::Near => Some(self.near_limit),
+            LimitBreach::None => None,
         }
     }
 
@@ -220,37 +295,267 @@ impl PidStack {
     }
 }
 
+/// Prefixes to draw the branches of a tree given the depth of each line.
+///
+/// Lines at depth 0 are roots without prefix. Only the prefixes of the lines in
+/// the window are returned.
+fn tree_prefixes(depths: &[usize], window: Range<usize>, charset: &TableCharSet) -> Vec<String> {
+    // A line is the last child if no sibling follows before a line of lower depth.
+    let mut is_last = vec![false; depths.len()];
+    let mut seen = Vec::new();
+    for (index, depth) in depths.iter().enumerate().rev() {
+        seen.resize(depth + 1, false);
+        is_last[index] = !seen[*depth];
+        seen[*depth] = true;
+    }
+    let horizontal = charset.get(TableChar::Horizontal);
+    let branch = format!("{}{horizontal}", charset.get(TableChar::VerticalRight));
+    let last_branch = format!("{}{horizontal}", charset.get(TableChar::UpRight));
+    let vertical = format!("{} ", charset.get(TableChar::Vertical));
+    let mut ancestors: Vec<bool> = Vec::new();
+    depths
+        .iter()
+        .zip(is_last)
+        .enumerate()
+        .take(window.end)
+        .filter_map(|(index, (depth, is_last))| {
+            ancestors.truncate(*depth);
+            let prefix = if *depth == 0 || !window.contains(&index) {
+                String::new()
+            } else {
+                ancestors
+                    .iter()
+                    .skip(1)
+                    .map(|last| if *last { "  " } else { vertical.as_str() })
+                    .chain(std::iter::once(if is_last {
+                        last_branch.as_str()
+                    } else {
+                        branch.as_str()
+                    }))
+                    .collect::<String>()
+            };
+            ancestors.resize(*depth, false);
+            ancestors.push(is_last);
+            window.contains(&index).then_some(prefix)
+        })
+        .collect()
+}
+
+/// Groups of process states.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum StateGroup {
+    Running,
+    Blocked,
+    Sleeping,
+    Idle,
+    Stopped,
+    Zombie,
+    Other,
+}
+
+impl StateGroup {
+    fn new(state: char) -> Self {
+        match state {
+            'R' => StateGroup::Running,
+            'D' => StateGroup::Blocked,
+            'S' => StateGroup::Sleeping,
+            'I' => StateGroup::Idle,
+            'T' | 't' => StateGroup::Stopped,
+            'Z' | 'X' | 'x' => StateGroup::Zombie,
+            _ => StateGroup::Other,
+        }
+    }
+
+    fn title(self) -> &'static str {
+        match self {
+            StateGroup::Running => "Running",
+            StateGroup::Blocked => "Blocked",
+            StateGroup::Sleeping => "Sleeping",
+            StateGroup::Idle => "Idle",
+            StateGroup::Stopped => "Stopped",
+            StateGroup::Zombie => "Zombie",
+            StateGroup::Other => "Other",
+        }
+    }
+}
+
+/// Name of the group of a process state.
+pub(crate) fn state_title(state: char) -> &'static str {
+    StateGroup::new(state).title()
+}
+
+/// Alignment of a cell.
+pub(crate) fn cell_alignment(alignment: Option<ColumnAlignment>) -> Alignment {
+    match alignment {
+        Some(ColumnAlignment::Left) => Alignment::Left,
+        Some(ColumnAlignment::Center) => Alignment::Center,
+        Some(ColumnAlignment::Right) | None => Alignment::Right,
+    }
+}
+
+/// Line of the main table.
+///
+/// The header of a group has PID 0 like the system so that it cannot be selected.
+#[derive(Debug)]
+pub(crate) enum TreeLine<'b> {
+    Process(&'b ProcessSamples),
+    /// Header of a group with the number of processes.
+    Group(String),
+}
+
+impl TreeLine<'_> {
+    /// Name with the number of folded descendants if any.
+    fn display_name(&self) -> Cow<'_, str> {
+        match self {
+            TreeLine::Process(ps) if ps.folded() > 0 => {
+                Cow::Owned(format!("{} (+{})", ps.name(), ps.folded()))
+            }
+            _ => Cow::Borrowed(self.name()),
+        }
+    }
+}
+
+impl ProcessIdentity for TreeLine<'_> {
+    fn name(&self) -> &str {
+        match self {
+            TreeLine::Process(ps) => ps.name(),
+            TreeLine::Group(title) => title.as_str(),
+        }
+    }
+
+    fn pid(&self) -> pid_t {
+        match self {
+            TreeLine::Process(ps) => ps.pid(),
+            TreeLine::Group(_) => 0,
+        }
+    }
+
+    fn uid(&self) -> Option<u32> {
+        match self {
+            TreeLine::Process(ps) => ps.uid(),
+            TreeLine::Group(_) => None,
+        }
+    }
+}
+
+impl SearchLine for TreeLine<'_> {
+    fn value(&self, index: usize) -> Option<u64> {
+        match self {
+            TreeLine::Process(ps) => ps
+                .samples()
+                .flat_map(Sample::collected_values)
+                .nth(index)
+                .flatten(),
+            TreeLine::Group(_) => None,
+        }
+    }
+}
+
 /// Data used to generate the tree as a table.
 #[derive(Debug)]
 pub(crate) struct TreeData<'t> {
     /// Column headers for metrics
     pub(crate) metric_headers: Vec<Text<'t>>,
+    /// Metrics spanning several aggregation columns.
+    pub(crate) metric_groups: Vec<ColumnGroup<'t>>,
     /// Display styles
     pub(crate) styles: Styles,
     /// Bookmarks for PIDs.
     pub(crate) bookmarks: Bookmarks,
-    /// PID matched by a search.
-    pub(crate) occurrences: BTreeSet<pid_t>,
+    /// PID and field matched by a search.
+    pub(crate) occurrences: BTreeMap<pid_t, SearchField>,
+    /// Characters to draw the tree.
+    charset: TableCharSet,
+    /// Whether the lowest and highest values are indicated.
+    pub(crate) watermarks: bool,
+    /// Whether the memory and the storage I/O are a percentage of the system.
+    pub(crate) relative: bool,
+    /// Importance of the metrics if the columns must fit in the width.
+    pub(crate) fit: Option<ColumnFit>,
+    /// Body columns that are the usage of a limit in per thousand.
+    pub(crate) limit_columns: Vec<bool>,
+    /// Alignment of the body columns, right by default.
+    pub(crate) alignments: Vec<Alignment>,
+    /// Whether the processes are grouped by state instead of a tree.
+    pub(crate) grouped: bool,
 }
 
 impl TreeData<'_> {
     pub(crate) fn new(styles: Styles) -> Self {
         Self {
             metric_headers: Vec::new(),
+            metric_groups: Vec::new(),
             styles,
             bookmarks: Bookmarks::default(),
-            occurrences: BTreeSet::default(),
+            occurrences: BTreeMap::default(),
+            charset: TableCharSet::new(),
+            watermarks: false,
+            relative: false,
+            fit: None,
+            limit_columns: Vec::new(),
+            alignments: Vec::new(),
+            grouped: false,
         }
     }
 
+    /// First value of a sample as a percentage of the system if enabled.
+    fn relative_string(&self, sample: &Sample) -> Option<String> {
+        self.relative
+            .then(|| sample.relative())
+            .flatten()
+            .map(format::ratio)
+    }
+
+    /// Lines in display order.
+    ///
+    /// The pinned processes are first. If the processes are grouped, the
+    /// others follow by state with a header per group.
+    pub(crate) fn lines<'b>(&self, collector: &'b Collector) -> Vec<TreeLine<'b>> {
+        let lines = self.bookmarks.pinned_first(collector.lines());
+        if !self.grouped {
+            return lines.into_iter().map(TreeLine::Process).collect();
+        }
+        let mut result = Vec::with_capacity(lines.len());
+        let mut groups: BTreeMap<StateGroup, Vec<&ProcessSamples>> = BTreeMap::new();
+        for ps in lines {
+            if ps.pid() == 0 || self.bookmarks.is_pinned(ps.pid()) {
+                result.push(TreeLine::Process(ps));
+            } else {
+                groups
+                    .entry(StateGroup::new(ps.state()))
+                    .or_default()
+                    .push(ps);
+            }
+        }
+        for (group, members) in groups {
+            result.push(TreeLine::Group(format!(
+                "{} ({})",
+                group.title(),
+                members.len()
+            )));
+            result.extend(members.into_iter().map(TreeLine::Process));
+        }
+        result
+    }
+
     /// Status of a process.
-    fn pid_status(&self, pid: pid_t) -> PidStatus {
+    ///
+    /// The process is matching if the search matched its name or owner.
+    fn pid_status(&self, ps: &ProcessSamples) -> PidStatus {
+        let pid = ps.pid();
         if self.bookmarks.is_selected(pid) {
             PidStatus::Selected
-        } else if self.occurrences.contains(&pid) {
+        } else if matches!(
+            self.occurrences.get(&pid),
+            Some(SearchField::Name | SearchField::Owner)
+        ) {
             PidStatus::Matching
         } else if self.bookmarks.is_marked(pid) {
             PidStatus::Marked
+        } else if self.bookmarks.is_pinned(pid) {
+            PidStatus::Pinned
+        } else if ps.is_realtime() {
+            PidStatus::RealTime
         } else {
             PidStatus::Unknown
         }
@@ -259,9 +564,13 @@ impl TreeData<'_> {
 
 /// Table generator for a tree of processes.
 #[derive(Getters)]
-pub(crate) struct ProcessTreeTable<'a, 'b, 't> {
-    /// Sample collector.
-    collector: &'b Collector<'a>,
+pub(crate) struct ProcessTreeTable<'b, 't> {
+    /// Lines in display order.
+    lines: Vec<TreeLine<'b>>,
+    /// Number of pinned lines at the top.
+    pinned_count: usize,
+    /// Index of the first line with a prefix.
+    first_line: usize,
     /// Tree data.
     data: Rc<TreeData<'t>>,
     /// Headers size.
@@ -269,21 +578,60 @@ pub(crate) struct ProcessTreeTable<'a, 'b, 't> {
     headers_size: Area<usize>,
     /// Column widths
     widths: Vec<u16>,
-    /// Indentation
-    indents: Vec<usize>,
+    /// Whether each body column is displayed.
+    shown: Vec<bool>,
+    /// Metrics spanning several displayed columns.
+    groups: Vec<ColumnGroup<'t>>,
+    /// Branches of the tree before the process names of the lines in the window.
+    prefixes: Vec<String>,
+    /// Whether the systemd unit is displayed.
+    with_units: bool,
+    /// Whether the wait channel is displayed.
+    with_wchan: bool,
+    /// Whether the use of the watched files is displayed.
+    with_file_usage: bool,
 }
 
-impl<'a, 'b, 't> ProcessTreeTable<'a, 'b, 't> {
+impl<'b, 't> ProcessTreeTable<'b, 't> {
     const TITLE_PROCESS: &'static str = "Process";
     const TITLE_PID: &'static str = "PID";
     const TITLE_STATE: &'static str = "S";
-    const FIXED_HEADERS: [&'static str; 3] =
-        [Self::TITLE_PROCESS, Self::TITLE_PID, Self::TITLE_STATE];
+    const TITLE_UNIT: &'static str = "Unit";
+    const TITLE_WCHAN: &'static str = "Wait";
+    const TITLE_FILE: &'static str = "File";
+
+    /// Headers before the metrics, the unit, the wait channel and the file are optional.
+    fn fixed_headers(
+        with_units: bool,
+        with_wchan: bool,
+        with_file_usage: bool,
+    ) -> Vec<&'static str> {
+        [Self::TITLE_PROCESS, Self::TITLE_PID, Self::TITLE_STATE]
+            .into_iter()
+            .chain(with_units.then_some(Self::TITLE_UNIT))
+            .chain(with_wchan.then_some(Self::TITLE_WCHAN))
+            .chain(with_file_usage.then_some(Self::TITLE_FILE))
+            .collect()
+    }
 
-    pub(crate) fn new(collector: &'b Collector<'a>, data: Rc<TreeData<'t>>) -> Self {
+    /// Create the table for a given inner width and a window of lines.
+    ///
+    /// The width is only used if the columns must fit. Only the lines in the
+    /// window are measured and can be displayed so that the cost doesn't depend
+    /// on the number of processes.
+    pub(crate) fn new(
+        collector: &'b Collector,
+        data: Rc<TreeData<'t>>,
+        width: u16,
+        window: Range<usize>,
+    ) -> Self {
+        let with_units = collector.with_units();
+        let with_wchan = collector.with_wchan();
+        let with_file_usage = collector.with_file_usage();
+        let fixed_headers = Self::fixed_headers(with_units, with_wchan, with_file_usage);
         let mut pids = PidStack::default();
         let mut headers_height = 0;
-        let mut widths = Self::FIXED_HEADERS
+        let mut widths = fixed_headers
             .iter()
             .map(|s| MaxLength::from(*s))
             .chain(data.metric_headers.iter().map(|text| {
@@ -293,53 +641,167 @@ impl<'a, 'b, 't> ProcessTreeTable<'a, 'b, 't> {
                 MaxLength::from(text.iter().map(|line| line.width()).max().unwrap_or(0))
             }))
             .collect::<Vec<MaxLength>>();
-        let headers_size = Area::new(Self::FIXED_HEADERS.len(), headers_height);
-        let mut indents = Vec::with_capacity(collector.line_count());
+        let headers_size = Area::new(fixed_headers.len(), headers_height);
+        let mut tree_indents = HashMap::with_capacity(collector.line_count());
         collector.lines().for_each(|ps| {
             pids.push(ps);
-            let indent = pids.len().saturating_sub(1);
-            indents.push(indent);
-            widths[0].set_min(indent + ps.name().len());
+            let indent = if data.bookmarks.is_pinned(ps.pid()) {
+                0
+            } else {
+                pids.len().saturating_sub(1)
+            };
+            tree_indents.insert(ps.pid(), indent);
+        });
+        let lines = data.lines(collector);
+        let window = window.start.min(lines.len())..window.end.min(lines.len());
+        let watermark_width = usize::from(data.watermarks);
+        lines[window.clone()].iter().for_each(|line| {
+            let TreeLine::Process(ps) = line else {
+                return;
+            };
             widths[1].set_min(ps.pid().to_string().len());
             // widths[2].set_min(1);
-            ps.samples().enumerate().for_each(|(i, s)| {
-                widths[i + headers_size.horizontal]
-                    .set_min(s.strings().map(|s| s.len()).max().unwrap_or(0))
-            });
+            if with_units {
+                widths[3].set_min(ps.unit().as_ref().map(String::len).unwrap_or(0));
+            }
+            if with_wchan {
+                let index = 3 + usize::from(with_units);
+                widths[index].set_min(ps.wchan().as_ref().map(String::len).unwrap_or(0));
+            }
+            if with_file_usage {
+                let index = fixed_headers.len() - 1;
+                widths[index].set_min(ps.file_usage().as_ref().map(String::len).unwrap_or(0));
+            }
+            ps.samples()
+                .flat_map(|sample| {
+                    let relative_len = data.relative_string(sample).map(|s| s.len());
+                    sample
+                        .strings()
+                        .enumerate()
+                        .map(move |(index, s)| match index {
+                            0 => relative_len.unwrap_or(s.len()) + watermark_width,
+                            _ => s.len(),
+                        })
+                })
+                .enumerate()
+                .for_each(|(i, len)| widths[i + headers_size.horizontal].set_min(len));
+        });
+        let column_spacing = data.styles.column_spacing as usize;
+        data.metric_groups.iter().for_each(|group| {
+            // The last column is enlarged if the title is wider than the columns.
+            let first = headers_size.horizontal + group.start;
+            let last = first + group.span - 1;
+            let width = widths[first..=last]
+                .iter()
+                .map(|ml| ml.len() as usize)
+                .sum::<usize>()
+                + column_spacing * (group.span - 1);
+            if width < group.width() {
+                let last_width = widths[last].len() as usize;
+                widths[last].set_min(last_width + group.width() - width);
+            }
         });
+        let pinned_count = data.bookmarks.pinned_count(&lines);
+        let indents = lines
+            .iter()
+            .map(|line| match line {
+                TreeLine::Process(ps) if !data.grouped => {
+                    tree_indents.get(&ps.pid()).copied().unwrap_or(0)
+                }
+                _ => 0,
+            })
+            .collect::<Vec<usize>>();
+        let prefixes = tree_prefixes(&indents, window.clone(), &data.charset);
+        izip!(lines[window.clone()].iter(), prefixes.iter()).for_each(|(line, prefix)| {
+            widths[0].set_min(prefix.chars().count() + line.display_name().len())
+        });
+        let widths = widths.iter().map(|ml| ml.len()).collect::<Vec<u16>>();
+        let (fixed_widths, body_widths) = widths.split_at(headers_size.horizontal);
+        let shown = match data.fit {
+            Some(ref fit) => {
+                // Fixed columns are limited to half the width as in the table widget.
+                let fixed_width = fixed_widths
+                    .iter()
+                    .map(|w| cmp::min(*w, width / 2) as usize + column_spacing)
+                    .sum::<usize>()
+                    .saturating_sub(column_spacing);
+                fit.visible_columns(
+                    body_widths,
+                    (width as usize).saturating_sub(fixed_width),
+                    column_spacing,
+                )
+            }
+            None => vec![true; body_widths.len()],
+        };
+        let groups = data
+            .metric_groups
+            .iter()
+            .filter_map(|group| {
+                let start = shown[..group.start].iter().filter(|s| **s).count();
+                let span = shown[group.start..group.start + group.span]
+                    .iter()
+                    .filter(|s| **s)
+                    .count();
+                (span > 0).then(|| ColumnGroup::new(group.title.clone(), start, span))
+            })
+            .collect::<Vec<ColumnGroup>>();
+        let widths = fixed_widths
+            .iter()
+            .copied()
+            .chain(
+                izip!(body_widths.iter(), shown.iter())
+                    .filter_map(|(width, shown)| shown.then_some(*width)),
+            )
+            .collect::<Vec<u16>>();
         Self {
-            collector,
+            lines,
+            pinned_count,
+            first_line: window.start,
             headers_size,
             data,
-            widths: widths.iter().map(|ml| ml.len()).collect::<Vec<u16>>(),
-            indents,
+            widths,
+            shown,
+            groups,
+            prefixes,
+            with_units,
+            with_wchan,
+            with_file_usage,
         }
     }
 
     /// Number of columns in the body.
     pub(crate) fn body_column_count(&self) -> usize {
-        self.data.metric_headers.len()
+        self.widths.len() - self.headers_size.horizontal
+    }
+
+    /// Index of the computed value in the first visible body column.
+    pub(crate) fn visible_column(&self, position: usize) -> Option<usize> {
+        self.shown
+            .iter()
+            .enumerate()
+            .filter(|(_, shown)| **shown)
+            .nth(position)
+            .map(|(index, _)| index)
     }
 
     /// Number of rows in the body.
     pub(crate) fn body_row_count(&self) -> usize {
-        self.collector.line_count()
+        self.lines.len()
     }
 }
 
-impl TableGenerator for ProcessTreeTable<'_, '_, '_> {
+impl TableGenerator for ProcessTreeTable<'_, '_> {
     fn headers_size(&self) -> Area<usize> {
         self.headers_size
     }
 
-    fn top_headers(&self, zoom: &Zoom) -> Vec<Cell> {
-        Self::FIXED_HEADERS
-            .iter()
-            .map(|s| lcell!(*s))
+    fn top_headers(&self, zoom: &Zoom) -> Vec<Cell<'_>> {
+        Self::fixed_headers(self.with_units, self.with_wchan, self.with_file_usage)
+            .into_iter()
+            .map(|s| lcell!(s))
             .chain(
-                self.data
-                    .metric_headers
-                    .iter()
+                izip!(self.data.metric_headers.iter(), self.shown.iter())
+                    .filter_map(|(text, shown)| shown.then_some(text))
                     .skip(zoom.position)
                     .take(zoom.visible_length)
                     .map(|text| Cell::from(text.clone().alignment(Alignment::Center))),
@@ -347,39 +809,115 @@ impl TableGenerator for ProcessTreeTable<'_, '_, '_> {
             .collect::<Vec<Cell>>()
     }
 
-    fn rows(&self, state: &BigTableState) -> Vec<Vec<Cell>> {
-        self.collector
-            .lines()
-            .skip(state.zoom.vertical.position)
-            .take(state.zoom.vertical.visible_length)
+    fn rows(&self, state: &BigTableState) -> Vec<Vec<Cell<'_>>> {
+        let position = state.zoom.vertical.position;
+        self.lines
+            .iter()
             .enumerate()
-            .map(|(n, ps)| {
-                let pid_status = self.data.pid_status(ps.pid());
-                let name = {
-                    let name = ps.name();
-                    format!("{:>width$}", name, width = self.indents[n] + name.len())
+            .skip(position)
+            .take(state.zoom.vertical.visible_length)
+            .map(|(lineno, line)| {
+                let ps = match line {
+                    TreeLine::Process(ps) => ps,
+                    TreeLine::Group(title) => {
+                        return vec![Cell::from(title.as_str()).style(self.data.styles.unselected)]
+                    }
                 };
-                let name_style = self.data.styles.name_style(pid_status);
-                vec![
-                    Cell::from(name).style(name_style),
-                    rcell!(ps.pid().to_string()),
-                    rcell!(ps.state().to_string()),
-                ]
-                .drain(..)
-                .chain(
-                    ps.samples()
-                        .flat_map(|sample| {
-                            izip!(sample.strings(), sample.trends()).map(|(value, trend)| {
-                                Cell::from(
-                                    Text::from(value.as_str())
-                                        .style(self.data.styles.trend_style(trend))
-                                        .alignment(Alignment::Right),
+                let pid_status = self.data.pid_status(ps);
+                let prefix = lineno
+                    .checked_sub(self.first_line)
+                    .and_then(|index| self.prefixes.get(index))
+                    .map(String::as_str)
+                    .unwrap_or_default();
+                let name = format!("{prefix}{}", line.display_name());
+                // The last pinned line is underlined to separate it from the tree.
+                let separator = if lineno + 1 == self.pinned_count {
+                    Style::default().add_modifier(Modifier::UNDERLINED)
+                } else {
+                    Style::default()
+                };
+                let name_style = self.data.styles.name_style(pid_status).patch(separator);
+                let occurrence = self.data.occurrences.get(&ps.pid());
+                let pid_style = match occurrence {
+                    Some(SearchField::Pid) => self.data.styles.name_style(PidStatus::Matching),
+                    _ => Style::default(),
+                };
+                let matching_column = match occurrence {
+                    Some(SearchField::Value(index)) => Some(*index),
+                    _ => None,
+                };
+                let unit = self
+                    .with_units
+                    .then(|| lcell!(ps.unit().clone().unwrap_or_default()));
+                let wchan = self
+                    .with_wchan
+                    .then(|| lcell!(ps.wchan().clone().unwrap_or_default()));
+                let file_usage = self
+                    .with_file_usage
+                    .then(|| lcell!(ps.file_usage().clone().unwrap_or_default()));
+                let cells = std::iter::once(rcell!(ps.state().to_string()))
+                    .chain(unit)
+                    .chain(wchan)
+                    .chain(file_usage)
+                    .chain(
+                        ps.samples()
+                            .flat_map(|sample| {
+                                let watermark = match sample.watermark() {
+                                    _ if !self.data.watermarks => "",
+                                    Ordering::Greater => WATERMARK_HIGH,
+                                    Ordering::Less => WATERMARK_LOW,
+                                    Ordering::Equal => " ",
+                                };
+                                let relative = self.data.relative_string(sample);
+                                sample.computed().enumerate().map(
+                                    move |(index, (raw, value, trend))| {
+                                        let value = if index == 0 {
+                                            let value = relative.as_deref().unwrap_or(value);
+                                            Cow::Owned(format!("{value}{watermark}"))
+                                        } else {
+                                            Cow::Borrowed(value)
+                                        };
+                                        (value, raw, trend)
+                                    },
                                 )
                             })
-                        })
-                        .skip(state.zoom.horizontal.position)
-                        .take(state.zoom.horizontal.visible_length),
-                )
+                            .zip(
+                                self.data
+                                    .limit_columns
+                                    .iter()
+                                    .copied()
+                                    .chain(std::iter::repeat(false)),
+                            )
+                            .enumerate()
+                            .map(|(index, ((value, raw, trend), is_limit))| {
+                                let style = if matching_column == Some(index) {
+                                    self.data.styles.matching
+                                } else {
+                                    is_limit
+                                        .then(|| self.data.styles.limit_style(raw))
+                                        .flatten()
+                                        .unwrap_or_else(|| self.data.styles.trend_style(&trend))
+                                };
+                                let alignment = self
+                                    .data
+                                    .alignments
+                                    .get(index)
+                                    .copied()
+                                    .unwrap_or(Alignment::Right);
+                                Cell::from(Text::from(value).style(style).alignment(alignment))
+                            })
+                            .zip(self.shown.iter())
+                            .filter_map(|(cell, shown)| shown.then_some(cell))
+                            .skip(state.zoom.horizontal.position)
+                            .take(state.zoom.horizontal.visible_length),
+                    )
+                    .map(|cell| cell.style(separator));
+                [
+                    Cell::from(name).style(name_style),
+                    rcell!(ps.pid().to_string()).style(pid_style.patch(separator)),
+                ]
+                .into_iter()
+                .chain(cells)
                 .collect::<Vec<Cell>>()
             })
             .collect::<Vec<Vec<Cell>>>()
@@ -388,6 +926,10 @@ impl TableGenerator for ProcessTreeTable<'_, '_, '_> {
     fn widths(&self) -> &[u16] {
         &self.widths
     }
+
+    fn column_groups(&self) -> &[ColumnGroup<'_>] {
+        &self.groups
+    }
 }
 
 /// A soft and hard limit with a name.
@@ -483,12 +1025,10 @@ impl TableGenerator for LimitsTable {
         Area::new(1, 1)
     }
 
-    fn top_headers(&self, zoom: &Zoom) -> Vec<Cell> {
+    fn top_headers(&self, zoom: &Zoom) -> Vec<Cell<'_>> {
         let bold = Style::default().bold();
-        self.headers
-            .iter()
-            .enumerate()
-            .map(|(i, s)| {
+        frozen_columns(
+            self.headers.iter().enumerate().map(|(i, s)| {
                 Cell::from(
                     Text::styled(*s, bold)
                         .alignment(if i == 0 {
@@ -498,24 +1038,27 @@ impl TableGenerator for LimitsTable {
                         })
                         .bold(),
                 )
-            })
-            .skip(zoom.position)
-            .collect::<Vec<Cell>>()
+            }),
+            1,
+            zoom,
+        )
     }
 
-    fn rows(&self, state: &BigTableState) -> Vec<Vec<Cell>> {
+    fn rows(&self, state: &BigTableState) -> Vec<Vec<Cell<'_>>> {
         self.limits
             .iter()
             .skip(state.zoom.vertical.position)
+            .take(state.zoom.vertical.visible_length)
             .map(|limit| {
-                vec![
-                    lcell!(limit.name),
-                    rcell!(limit.soft.to_string()),
-                    rcell!(limit.hard.to_string()),
-                ]
-                .drain(..)
-                .skip(state.zoom.horizontal.position)
-                .collect::<Vec<Cell>>()
+                frozen_columns(
+                    [
+                        lcell!(limit.name),
+                        rcell!(limit.soft.to_string()),
+                        rcell!(limit.hard.to_string()),
+                    ],
+                    1,
+                    &state.zoom.horizontal,
+                )
             })
             .collect::<Vec<Vec<Cell>>>()
     }
@@ -553,28 +1096,381 @@ impl EnvironmentTable {
 impl BigTableStateGenerator for EnvironmentTable {
     fn state(&self) -> BigTableState {
         let vlen = self.env.len();
-        BigTableState::new(Zoom::new(0, 0, 2), Zoom::new(0, 0, vlen))
+        BigTableState::new(Zoom::new(0, 0, 1), Zoom::new(0, 0, vlen))
     }
 }
 
 impl TableGenerator for EnvironmentTable {
     fn headers_size(&self) -> Area<usize> {
-        Area::new(0, 1)
+        Area::new(1, 0)
     }
 
-    fn top_headers(&self, _zoom: &Zoom) -> Vec<Cell> {
+    fn top_headers(&self, _zoom: &Zoom) -> Vec<Cell<'_>> {
         Vec::new()
     }
 
-    fn rows(&self, state: &BigTableState) -> Vec<Vec<Cell>> {
+    fn rows(&self, state: &BigTableState) -> Vec<Vec<Cell<'_>>> {
         self.env
             .iter()
             .skip(state.zoom.vertical.position)
+            .take(state.zoom.vertical.visible_length)
             .map(|(k, v)| {
-                vec![lcell!(k.to_string()), lcell!(v.to_string())]
-                    .drain(..)
-                    .skip(state.zoom.horizontal.position)
-                    .collect::<Vec<Cell>>()
+                frozen_columns(
+                    [lcell!(k.to_string()), lcell!(v.to_string())],
+                    1,
+                    &state.zoom.horizontal,
+                )
+            })
+            .collect::<Vec<Vec<Cell>>>()
+    }
+
+    fn widths(&self) -> &[u16] {
+        &self.widths
+    }
+}
+
+/// Table generator for the variables that differ in the environments of two processes.
+pub(crate) struct EnvironmentDiffTable {
+    headers: [String; 3],
+    rows: Vec<[String; 3]>,
+    widths: Vec<u16>,
+}
+
+impl EnvironmentDiffTable {
+    /// Value displayed for a variable that is not set in an environment.
+    const UNSET: &'static str = "<unset>";
+
+    pub(crate) fn new(
+        left: (String, HashMap<OsString, OsString>),
+        right: (String, HashMap<OsString, OsString>),
+    ) -> Self {
+        let headers = ["Variable".to_string(), left.0, right.0];
+        let rows = EnvironmentDiffTable::compare(&left.1, &right.1);
+        let widths = (0..headers.len())
+            .map(|index| {
+                MaxLength::with_lines(
+                    std::iter::once(headers[index].as_str())
+                        .chain(rows.iter().map(|row| row[index].as_str())),
+                )
+                .len()
+            })
+            .collect();
+        Self {
+            headers,
+            rows,
+            widths,
+        }
+    }
+
+    /// Variables that are different or only set in one environment, sorted by name.
+    fn compare(
+        left: &HashMap<OsString, OsString>,
+        right: &HashMap<OsString, OsString>,
+    ) -> Vec<[String; 3]> {
+        let value = |env: &HashMap<OsString, OsString>, name| {
+            env.get(name)
+                .map(|value: &OsString| value.to_string_lossy().to_string())
+                .unwrap_or_else(|| EnvironmentDiffTable::UNSET.to_string())
+        };
+        let names = left
+            .keys()
+            .chain(right.keys())
+            .filter(|name| left.get(*name) != right.get(*name))
+            .collect::<BTreeSet<&OsString>>();
+        names
+            .into_iter()
+            .map(|name| {
+                [
+                    name.to_string_lossy().to_string(),
+                    value(left, name),
+                    value(right, name),
+                ]
+            })
+            .collect()
+    }
+}
+
+impl BigTableStateGenerator for EnvironmentDiffTable {
+    fn state(&self) -> BigTableState {
+        let hlen = self.widths.len() - 1;
+        let vlen = self.rows.len();
+        BigTableState::new(Zoom::new(0, 0, hlen), Zoom::new(0, 0, vlen))
+    }
+}
+
+impl TableGenerator for EnvironmentDiffTable {
+    fn headers_size(&self) -> Area<usize> {
+        Area::new(1, 1)
+    }
+
+    fn top_headers(&self, zoom: &Zoom) -> Vec<Cell<'_>> {
+        let bold = Style::default().bold();
+        frozen_columns(
+            self.headers
+                .iter()
+                .map(|s| Cell::from(Text::styled(s.as_str(), bold))),
+            1,
+            zoom,
+        )
+    }
+
+    fn rows(&self, state: &BigTableState) -> Vec<Vec<Cell<'_>>> {
+        self.rows
+            .iter()
+            .skip(state.zoom.vertical.position)
+            .take(state.zoom.vertical.visible_length)
+            .map(|row| {
+                frozen_columns(
+                    row.iter().map(|s| lcell!(s.as_str())),
+                    1,
+                    &state.zoom.horizontal,
+                )
+            })
+            .collect::<Vec<Vec<Cell>>>()
+    }
+
+    fn widths(&self) -> &[u16] {
+        &self.widths
+    }
+}
+
+/// Table generator for the size of the files open by a process by mount point.
+pub(crate) struct MountsTable {
+    headers: Vec<&'static str>,
+    rows: Vec<[String; 5]>,
+    widths: Vec<u16>,
+}
+
+impl MountsTable {
+    pub(crate) fn new(usages: Vec<MountUsage>) -> Self {
+        let headers = vec!["Mount point", "Type", "Source", "Files", "Size"];
+        let rows = usages
+            .into_iter()
+            .map(|usage| {
+                [
+                    usage
+                        .mount_point
+                        .map(|path| path.to_string_lossy().to_string())
+                        .unwrap_or_else(|| "?".to_string()),
+                    usage.fs_type,
+                    usage.source,
+                    usage.files.to_string(),
+                    size(usage.size),
+                ]
+            })
+            .collect::<Vec<[String; 5]>>();
+        let widths = headers
+            .iter()
+            .enumerate()
+            .map(|(index, header)| {
+                MaxLength::with_lines(
+                    std::iter::once(*header).chain(rows.iter().map(|row| row[index].as_str())),
+                )
+                .len()
+            })
+            .collect();
+        Self {
+            headers,
+            rows,
+            widths,
+        }
+    }
+}
+
+impl BigTableStateGenerator for MountsTable {
+    fn state(&self) -> BigTableState {
+        let hlen = self.widths.len() - 1;
+        let vlen = self.rows.len();
+        BigTableState::new(Zoom::new(0, 0, hlen), Zoom::new(0, 0, vlen))
+    }
+}
+
+impl TableGenerator for MountsTable {
+    fn headers_size(&self) -> Area<usize> {
+        Area::new(1, 1)
+    }
+
+    fn top_headers(&self, zoom: &Zoom) -> Vec<Cell<'_>> {
+        let bold = Style::default().bold();
+        frozen_columns(
+            self.headers
+                .iter()
+                .map(|s| Cell::from(Text::styled(*s, bold).bold())),
+            1,
+            zoom,
+        )
+    }
+
+    fn rows(&self, state: &BigTableState) -> Vec<Vec<Cell<'_>>> {
+        self.rows
+            .iter()
+            .skip(state.zoom.vertical.position)
+            .take(state.zoom.vertical.visible_length)
+            .map(|[mount_point, fs_type, source, files, size]| {
+                frozen_columns(
+                    [
+                        lcell!(mount_point.as_str()),
+                        lcell!(fs_type.as_str()),
+                        lcell!(source.as_str()),
+                        rcell!(files.as_str()),
+                        rcell!(size.as_str()),
+                    ],
+                    1,
+                    &state.zoom.horizontal,
+                )
+            })
+            .collect::<Vec<Vec<Cell>>>()
+    }
+
+    fn widths(&self) -> &[u16] {
+        &self.widths
+    }
+}
+
+/// Table generator for the entries of a process that may refer to other processes.
+///
+/// The entries that refer to another process are links and the selected one is highlighted.
+pub(crate) struct LinksTable {
+    headers: Vec<&'static str>,
+    /// Cells of the rows and whether the row is the selected link.
+    rows: Vec<(Vec<String>, bool)>,
+    selected_style: Style,
+    widths: Vec<u16>,
+}
+
+impl LinksTable {
+    /// Table with the given columns followed by the process referred to.
+    fn new<I>(
+        mut headers: Vec<&'static str>,
+        entries: I,
+        selected_link: usize,
+        selected_style: Style,
+    ) -> Self
+    where
+        I: Iterator<Item = (Vec<String>, Option<pid_t>)>,
+    {
+        headers.push("Process");
+        let mut links = 0;
+        let rows = entries
+            .map(|(mut row, peer)| {
+                let process = peer.map(|pid| {
+                    let name = process_name(pid).unwrap_or_else(|| "?".to_string());
+                    format!("{name} ({pid})")
+                });
+                let selected = match peer {
+                    Some(_) => {
+                        links += 1;
+                        links == selected_link + 1
+                    }
+                    None => false,
+                };
+                row.push(process.unwrap_or_default());
+                (row, selected)
+            })
+            .collect::<Vec<(Vec<String>, bool)>>();
+        let widths = headers
+            .iter()
+            .enumerate()
+            .map(|(index, header)| {
+                MaxLength::with_lines(
+                    std::iter::once(*header).chain(rows.iter().map(|(row, _)| row[index].as_str())),
+                )
+                .len()
+            })
+            .collect();
+        Self {
+            headers,
+            rows,
+            selected_style,
+            widths,
+        }
+    }
+
+    /// File descriptors of a process.
+    pub(crate) fn with_files(
+        files: &[OpenFile],
+        selected_link: usize,
+        selected_style: Style,
+    ) -> Self {
+        Self::new(
+            vec!["FD", "Target"],
+            files
+                .iter()
+                .map(|file| (vec![file.fd.to_string(), file.target.clone()], file.peer)),
+            selected_link,
+            selected_style,
+        )
+    }
+
+    /// Memory mappings of a process.
+    pub(crate) fn with_maps(
+        maps: &[MappedRegion],
+        selected_link: usize,
+        selected_style: Style,
+    ) -> Self {
+        Self::new(
+            vec!["Address", "Perms", "Path"],
+            maps.iter().map(|map| {
+                (
+                    vec![map.address.clone(), map.perms.clone(), map.path.clone()],
+                    map.peer,
+                )
+            }),
+            selected_link,
+            selected_style,
+        )
+    }
+}
+
+impl BigTableStateGenerator for LinksTable {
+    fn state(&self) -> BigTableState {
+        let hlen = self.widths.len() - 1;
+        let vlen = self.rows.len();
+        BigTableState::new(Zoom::new(0, 0, hlen), Zoom::new(0, 0, vlen))
+    }
+}
+
+impl TableGenerator for LinksTable {
+    fn headers_size(&self) -> Area<usize> {
+        Area::new(1, 1)
+    }
+
+    fn top_headers(&self, zoom: &Zoom) -> Vec<Cell<'_>> {
+        let bold = Style::default().bold();
+        frozen_columns(
+            self.headers
+                .iter()
+                .map(|s| Cell::from(Text::styled(*s, bold))),
+            1,
+            zoom,
+        )
+    }
+
+    fn rows(&self, state: &BigTableState) -> Vec<Vec<Cell<'_>>> {
+        self.rows
+            .iter()
+            .skip(state.zoom.vertical.position)
+            .take(state.zoom.vertical.visible_length)
+            .map(|(row, selected)| {
+                let style = if *selected {
+                    self.selected_style
+                } else {
+                    Style::default()
+                };
+                frozen_columns(
+                    row.iter()
+                        .enumerate()
+                        .map(|(index, cell)| {
+                            if index == 0 {
+                                rcell!(cell.as_str())
+                            } else {
+                                lcell!(cell.as_str())
+                            }
+                        })
+                        .map(|cell| cell.style(style)),
+                    1,
+                    &state.zoom.horizontal,
+                )
             })
             .collect::<Vec<Vec<Cell>>>()
     }
@@ -583,3 +1479,496 @@ impl TableGenerator for EnvironmentTable {
         &self.widths
     }
 }
+
+/// Table generator for the bytes read and written by a process on each device.
+///
+/// The last row holds what is not attributed to a device.
+pub(crate) struct IoDevicesTable {
+    headers: Vec<&'static str>,
+    rows: Vec<[String; 6]>,
+    widths: Vec<u16>,
+}
+
+impl IoDevicesTable {
+    pub(crate) fn new(io: IoByDevice) -> Self {
+        let headers = vec![
+            "Mount point",
+            "Source",
+            "Device",
+            "Files",
+            "Read (est.)",
+            "Written (est.)",
+        ];
+        let mut rows = io
+            .devices
+            .into_iter()
+            .map(|device| {
+                [
+                    device
+                        .mount_point
+                        .map(|path| path.to_string_lossy().to_string())
+                        .unwrap_or_else(|| "?".to_string()),
+                    device.source,
+                    device.device,
+                    device.files.to_string(),
+                    size(device.read),
+                    size(device.written),
+                ]
+            })
+            .collect::<Vec<[String; 6]>>();
+        rows.push([
+            "other".to_string(),
+            String::new(),
+            String::new(),
+            String::new(),
+            size(io.other_read),
+            size(io.other_written),
+        ]);
+        let widths = headers
+            .iter()
+            .enumerate()
+            .map(|(index, header)| {
+                MaxLength::with_lines(
+                    std::iter::once(*header).chain(rows.iter().map(|row| row[index].as_str())),
+                )
+                .len()
+            })
+            .collect();
+        Self {
+            headers,
+            rows,
+            widths,
+        }
+    }
+}
+
+impl BigTableStateGenerator for IoDevicesTable {
+    fn state(&self) -> BigTableState {
+        let hlen = self.widths.len() - 1;
+        let vlen = self.rows.len();
+        BigTableState::new(Zoom::new(0, 0, hlen), Zoom::new(0, 0, vlen))
+    }
+}
+
+impl TableGenerator for IoDevicesTable {
+    fn headers_size(&self) -> Area<usize> {
+        Area::new(1, 1)
+    }
+
+    fn top_headers(&self, zoom: &Zoom) -> Vec<Cell<'_>> {
+        let bold = Style::default().bold();
+        frozen_columns(
+            self.headers
+                .iter()
+                .map(|s| Cell::from(Text::styled(*s, bold))),
+            1,
+            zoom,
+        )
+    }
+
+    fn rows(&self, state: &BigTableState) -> Vec<Vec<Cell<'_>>> {
+        self.rows
+            .iter()
+            .skip(state.zoom.vertical.position)
+            .take(state.zoom.vertical.visible_length)
+            .map(|[mount_point, source, device, files, read, written]| {
+                frozen_columns(
+                    [
+                        lcell!(mount_point.as_str()),
+                        lcell!(source.as_str()),
+                        lcell!(device.as_str()),
+                        rcell!(files.as_str()),
+                        rcell!(read.as_str()),
+                        rcell!(written.as_str()),
+                    ],
+                    1,
+                    &state.zoom.horizontal,
+                )
+            })
+            .collect::<Vec<Vec<Cell>>>()
+    }
+
+    fn widths(&self) -> &[u16] {
+        &self.widths
+    }
+}
+
+/// Table generator for the namespaces of a process.
+///
+/// The namespaces that differ from those of oprs are highlighted.
+pub(crate) struct NamespacesTable {
+    headers: Vec<&'static str>,
+    rows: Vec<(String, String, Option<bool>)>,
+    differ_style: Style,
+    widths: Vec<u16>,
+}
+
+impl NamespacesTable {
+    pub(crate) fn new(
+        namespaces: &Namespaces,
+        own: Option<&Namespaces>,
+        differ_style: Style,
+    ) -> Self {
+        let headers = vec!["Namespace", "Identifier", "oprs"];
+        let rows = Self::compare(namespaces, own);
+        let widths = vec![
+            MaxLength::with_lines(
+                std::iter::once(headers[0]).chain(rows.iter().map(|(name, _, _)| name.as_str())),
+            )
+            .len(),
+            MaxLength::with_lines(
+                std::iter::once(headers[1]).chain(rows.iter().map(|(_, id, _)| id.as_str())),
+            )
+            .len(),
+            MaxLength::with_lines([headers[2], "different"]).len(),
+        ];
+        Self {
+            headers,
+            rows,
+            differ_style,
+            widths,
+        }
+    }
+
+    /// Namespaces sorted by type with the link target and whether it differs from oprs.
+    ///
+    /// If the link cannot be read, the target is built from the type and the identifier.
+    fn compare(
+        namespaces: &Namespaces,
+        own: Option<&Namespaces>,
+    ) -> Vec<(String, String, Option<bool>)> {
+        let mut rows = namespaces
+            .0
+            .iter()
+            .map(|(name, ns)| {
+                let name = name.to_string_lossy().to_string();
+                let differs = own.map(|own| own.0.get(&ns.ns_type) != Some(ns));
+                let target = fs::read_link(&ns.path)
+                    .map(|target| target.to_string_lossy().to_string())
+                    .unwrap_or_else(|_| format!("{name}:[{}]", ns.identifier));
+                (name, target, differs)
+            })
+            .collect::<Vec<(String, String, Option<bool>)>>();
+        rows.sort();
+        rows
+    }
+}
+
+impl BigTableStateGenerator for NamespacesTable {
+    fn state(&self) -> BigTableState {
+        let hlen = self.widths.len() - 1;
+        let vlen = self.rows.len();
+        BigTableState::new(Zoom::new(0, 0, hlen), Zoom::new(0, 0, vlen))
+    }
+}
+
+impl TableGenerator for NamespacesTable {
+    fn headers_size(&self) -> Area<usize> {
+        Area::new(1, 1)
+    }
+
+    fn top_headers(&self, zoom: &Zoom) -> Vec<Cell<'_>> {
+        let bold = Style::default().bold();
+        frozen_columns(
+            self.headers
+                .iter()
+                .map(|s| Cell::from(Text::styled(*s, bold).bold())),
+            1,
+            zoom,
+        )
+    }
+
+    fn rows(&self, state: &BigTableState) -> Vec<Vec<Cell<'_>>> {
+        self.rows
+            .iter()
+            .skip(state.zoom.vertical.position)
+            .take(state.zoom.vertical.visible_length)
+            .map(|(name, target, differs)| {
+                let (status, style) = match differs {
+                    Some(true) => ("different", self.differ_style),
+                    Some(false) => ("same", Style::default()),
+                    None => ("?", Style::default()),
+                };
+                frozen_columns(
+                    [
+                        lcell!(name.as_str()),
+                        lcell!(target.as_str()),
+                        lcell!(status),
+                    ]
+                    .into_iter()
+                    .map(|cell| cell.style(style)),
+                    1,
+                    &state.zoom.horizontal,
+                )
+            })
+            .collect::<Vec<Vec<Cell>>>()
+    }
+
+    fn widths(&self) -> &[u16] {
+        &self.widths
+    }
+}
+
+/// Table generator for the threads of a process.
+///
+/// The threads that consumed the most CPU in the last interval are on top and highlighted.
+pub(crate) struct ThreadsTable {
+    headers: Vec<&'static str>,
+    rows: Vec<[String; 5]>,
+    hot_count: usize,
+    hot_style: Style,
+    widths: Vec<u16>,
+}
+
+impl ThreadsTable {
+    pub(crate) fn new(monitor: &ThreadMonitor, hot_style: Style) -> Self {
+        let headers = vec!["TID", "Name", "State", "CPU", "Total CPU"];
+        let rows = monitor
+            .threads()
+            .map(|thread| {
+                [
+                    thread.tid().to_string(),
+                    thread.name().to_string(),
+                    thread.state().to_string(),
+                    human_milliseconds(thread.cpu_delta()),
+                    human_milliseconds(thread.cpu_time()),
+                ]
+            })
+            .collect::<Vec<[String; 5]>>();
+        let hot_count = (0..rows.len())
+            .take_while(|index| monitor.is_hot(*index))
+            .count();
+        let widths = headers
+            .iter()
+            .enumerate()
+            .map(|(index, header)| {
+                MaxLength::with_lines(
+                    std::iter::once(*header).chain(rows.iter().map(|row| row[index].as_str())),
+                )
+                .len()
+            })
+            .collect();
+        Self {
+            headers,
+            rows,
+            hot_count,
+            hot_style,
+            widths,
+        }
+    }
+}
+
+impl BigTableStateGenerator for ThreadsTable {
+    fn state(&self) -> BigTableState {
+        let hlen = self.widths.len() - 1;
+        let vlen = self.rows.len();
+        BigTableState::new(Zoom::new(0, 0, hlen), Zoom::new(0, 0, vlen))
+    }
+}
+
+impl TableGenerator for ThreadsTable {
+    fn headers_size(&self) -> Area<usize> {
+        Area::new(1, 1)
+    }
+
+    fn top_headers(&self, zoom: &Zoom) -> Vec<Cell<'_>> {
+        let bold = Style::default().bold();
+        frozen_columns(
+            self.headers.iter().enumerate().map(|(i, s)| {
+                Cell::from(
+                    Text::styled(*s, bold)
+                        .alignment(if i == 1 {
+                            Alignment::Left
+                        } else {
+                            Alignment::Right
+                        })
+                        .bold(),
+                )
+            }),
+            1,
+            zoom,
+        )
+    }
+
+    fn rows(&self, state: &BigTableState) -> Vec<Vec<Cell<'_>>> {
+        self.rows
+            .iter()
+            .enumerate()
+            .skip(state.zoom.vertical.position)
+            .take(state.zoom.vertical.visible_length)
+            .map(|(index, row)| {
+                let style = if index < self.hot_count {
+                    self.hot_style
+                } else {
+                    Style::default()
+                };
+                frozen_columns(
+                    row.iter().enumerate().map(|(i, s)| {
+                        if i == 1 {
+                            lcell!(s.as_str())
+                        } else {
+                            rcell!(s.as_str())
+                        }
+                        .style(style)
+                    }),
+                    1,
+                    &state.zoom.horizontal,
+                )
+            })
+            .collect::<Vec<Vec<Cell>>>()
+    }
+
+    fn widths(&self) -> &[u16] {
+        &self.widths
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use procfs::process::Namespaces;
+    use std::{borrow::Cow, collections::HashMap, ffi::OsString, fs};
+
+    use super::{
+        tree_prefixes, EnvironmentDiffTable, NamespacesTable, ProcessTreeTable, StateGroup, Styles,
+        TableChar, TableCharSet, TreeData,
+    };
+    use crate::{
+        console::BuiltinTheme,
+        process::{Collector, MetricNamesParser, ProcessIdentity},
+    };
+
+    #[test]
+    fn test_fixed_headers() {
+        assert_eq!(
+            vec!["Process", "PID", "S"],
+            ProcessTreeTable::fixed_headers(false, false, false)
+        );
+        assert_eq!(
+            vec!["Process", "PID", "S", "Unit", "Wait"],
+            ProcessTreeTable::fixed_headers(true, true, false)
+        );
+        assert_eq!(
+            vec!["Process", "PID", "S", "Wait", "File"],
+            ProcessTreeTable::fixed_headers(false, true, true)
+        );
+    }
+
+    #[test]
+    fn test_tree_prefixes() {
+        let charset = TableCharSet::new();
+        let hline = charset.get(TableChar::Horizontal);
+        let branch = format!("{}{hline}", charset.get(TableChar::VerticalRight));
+        let last = format!("{}{hline}", charset.get(TableChar::UpRight));
+        let vline = format!("{} ", charset.get(TableChar::Vertical));
+        let expected = vec![
+            String::new(),
+            branch.clone(),
+            format!("{vline}{branch}"),
+            format!("{vline}{last}"),
+            last.clone(),
+            format!("  {last}"),
+            String::new(),
+            last.clone(),
+        ];
+        let depths = [0, 1, 2, 2, 1, 2, 0, 1];
+        assert_eq!(expected, tree_prefixes(&depths, 0..8, &charset));
+        assert_eq!(expected[2..5], tree_prefixes(&depths, 2..5, &charset));
+    }
+
+    #[test]
+    fn test_limit_style() {
+        let styles = Styles::new(Some(BuiltinTheme::Dark));
+        assert_eq!(None, styles.limit_style(799));
+        assert_eq!(Some(styles.near_limit), styles.limit_style(800));
+        assert_eq!(Some(styles.at_limit), styles.limit_style(950));
+        assert_eq!(Some(styles.at_limit), styles.limit_style(1200));
+    }
+
+    #[test]
+    fn test_state_groups() {
+        assert_eq!(StateGroup::Running, StateGroup::new('R'));
+        assert_eq!(StateGroup::Blocked, StateGroup::new('D'));
+        assert_eq!(StateGroup::Stopped, StateGroup::new('t'));
+        assert_eq!(StateGroup::Other, StateGroup::new(' '));
+        assert!(StateGroup::Running < StateGroup::Blocked);
+        assert!(StateGroup::Blocked < StateGroup::Sleeping);
+    }
+
+    #[test]
+    fn test_grouped_lines() {
+        let metrics = MetricNamesParser::new(false).parse(&["fd:all"]).unwrap();
+        let mut collector = Collector::new(Cow::Borrowed(&metrics));
+        collector.record_pseudo("a", -2, &[1]);
+        collector.record_pseudo("b", -3, &[2]);
+        let mut data = TreeData::new(Styles::new(None));
+        let names = |data: &TreeData, collector: &Collector| {
+            data.lines(collector)
+                .iter()
+                .map(|line| (line.name().to_string(), line.pid()))
+                .collect::<Vec<(String, i32)>>()
+        };
+        assert_eq!(
+            vec![("a".to_string(), -2), ("b".to_string(), -3)],
+            names(&data, &collector)
+        );
+        data.grouped = true;
+        assert_eq!(
+            vec![
+                ("Other (2)".to_string(), 0),
+                ("a".to_string(), -2),
+                ("b".to_string(), -3)
+            ],
+            names(&data, &collector)
+        );
+    }
+
+    #[test]
+    fn test_environment_compare() {
+        let environ = |vars: &[(&str, &str)]| {
+            vars.iter()
+                .map(|(name, value)| (OsString::from(name), OsString::from(value)))
+                .collect::<HashMap<OsString, OsString>>()
+        };
+        let left = environ(&[("PATH", "/bin"), ("LANG", "C"), ("HOME", "/root")]);
+        let right = environ(&[("PATH", "/bin"), ("LANG", "fr_FR"), ("TZ", "UTC")]);
+        let row = |name: &str, left: &str, right: &str| {
+            [name.to_string(), left.to_string(), right.to_string()]
+        };
+        assert_eq!(
+            vec![
+                row("HOME", "/root", "<unset>"),
+                row("LANG", "C", "fr_FR"),
+                row("TZ", "<unset>", "UTC"),
+            ],
+            EnvironmentDiffTable::compare(&left, &right)
+        );
+        assert!(EnvironmentDiffTable::compare(&left, &left).is_empty());
+    }
+
+    #[test]
+    fn test_namespaces_compare() {
+        let own = procfs::process::Process::myself()
+            .and_then(|myself| myself.namespaces())
+            .unwrap();
+        let mut namespaces = Namespaces(own.0.clone());
+        if let Some(ns) = namespaces.0.get_mut(&OsString::from("net")) {
+            ns.identifier += 1;
+        }
+        let mut expected = own
+            .0
+            .iter()
+            .map(|(name, ns)| {
+                let name = name.to_string_lossy().to_string();
+                let target = fs::read_link(&ns.path).unwrap();
+                let differs = name == "net";
+                (name, target.to_string_lossy().to_string(), Some(differs))
+            })
+            .collect::<Vec<(String, String, Option<bool>)>>();
+        expected.sort();
+        assert_eq!(expected, NamespacesTable::compare(&namespaces, Some(&own)));
+        assert!(NamespacesTable::compare(&own, None)
+            .into_iter()
+            .all(|(_, _, differs)| differs.is_none()));
+    }
+}
@@ -17,8 +17,10 @@
 use num_traits::{ConstZero, Saturating, Zero};
 use std::{
     cmp::Ordering,
-    collections::VecDeque,
+    collections::{HashMap, VecDeque},
+    hash::Hash,
     ops::{Add, Sub},
+    time::{Duration, Instant},
 };
 
 macro_rules! void {
@@ -216,6 +218,83 @@ impl<T> Area<T> {
     }
 }
 
+/// Class of terminal size: narrow, normal or wide and short, normal or tall.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub(crate) struct SizeClass(u8, u8);
+
+impl SizeClass {
+    pub fn new(width: u16, height: u16) -> Self {
+        let class =
+            |value, limits: [u16; 2]| limits.iter().filter(|limit| value >= **limit).count() as u8;
+        Self(class(width, [80, 132]), class(height, [24, 50]))
+    }
+}
+
+/// Offsets of the panes remembered per class of terminal size.
+#[derive(Debug)]
+pub(crate) struct PaneOffsets<K> {
+    offsets: HashMap<(K, SizeClass), u16>,
+    current: Option<(K, SizeClass)>,
+}
+
+impl<K: Copy + Eq + Hash> PaneOffsets<K> {
+    pub fn new() -> Self {
+        Self {
+            offsets: HashMap::new(),
+            current: None,
+        }
+    }
+
+    /// Return the offset of a pane in a terminal size class.
+    ///
+    /// If the pane or the size class changes, the offset of the previous one
+    /// is saved and the last offset of the new one is restored.
+    pub fn switch(&mut self, key: K, size: SizeClass, offset: u16) -> u16 {
+        let next = (key, size);
+        match self.current.replace(next) {
+            Some(current) if current != next => {
+                self.offsets.insert(current, offset);
+                self.offsets.get(&next).copied().unwrap_or(0)
+            }
+            _ => offset,
+        }
+    }
+}
+
+/// Limit of the number of frames drawn per second.
+///
+/// A frame is skipped if the previous one is too recent, unless it is forced.
+#[derive(Debug, Default)]
+pub(crate) struct FrameLimiter {
+    /// Minimum delay between two frames.
+    delay: Option<Duration>,
+    /// Time of the last frame drawn.
+    last: Option<Instant>,
+}
+
+impl FrameLimiter {
+    /// Frame limiter with a maximum number of frames per second (0 for no limit).
+    pub fn new(max_fps: u32) -> Self {
+        Self {
+            delay: (max_fps > 0).then(|| Duration::from_secs(1) / max_fps),
+            last: None,
+        }
+    }
+
+    /// Whether a frame can be drawn at the given time. If so, it's the time of the last frame.
+    pub fn allow(&mut self, now: Instant, forced: bool) -> bool {
+        let allowed = forced
+            || match (self.delay, self.last) {
+                (Some(delay), Some(last)) => now.saturating_duration_since(last) >= delay,
+                _ => true,
+            };
+        if allowed {
+            self.last = Some(now);
+        }
+        allowed
+    }
+}
+
 /// FIFO with a bounded size.
 pub struct BoundedFifo<T>(VecDeque<T>);
 
@@ -304,7 +383,9 @@ impl From<&str> for MaxLength {
 #[cfg(test)]
 mod test {
 
-    use super::{BoundedFifo, UnboundedSize};
+    use std::time::{Duration, Instant};
+
+    use super::{BoundedFifo, FrameLimiter, PaneOffsets, SizeClass, UnboundedSize};
 
     #[test]
     fn test_add() {
@@ -332,4 +413,42 @@ mod test {
         assert_eq!(2, v.len());
         assert_eq!(2, *v.front().unwrap());
     }
+
+    #[test]
+    fn test_size_class() {
+        assert_eq!(SizeClass::new(80, 24), SizeClass::new(100, 40));
+        assert_ne!(SizeClass::new(79, 24), SizeClass::new(80, 24));
+        assert_ne!(SizeClass::new(80, 24), SizeClass::new(80, 50));
+        assert_eq!(SizeClass::new(200, 60), SizeClass::new(132, 50));
+    }
+
+    #[test]
+    fn test_pane_offsets() {
+        let small = SizeClass::new(80, 24);
+        let large = SizeClass::new(200, 60);
+        let mut offsets = PaneOffsets::new();
+        assert_eq!(0, offsets.switch('a', small, 0));
+        assert_eq!(3, offsets.switch('a', small, 3));
+        assert_eq!(0, offsets.switch('b', small, 3));
+        assert_eq!(3, offsets.switch('a', small, 5));
+        assert_eq!(0, offsets.switch('a', large, 3));
+        assert_eq!(3, offsets.switch('a', small, 7));
+        assert_eq!(7, offsets.switch('a', large, 3));
+        assert_eq!(0, offsets.switch('b', large, 7));
+        assert_eq!(5, offsets.switch('b', small, 1));
+    }
+
+    #[test]
+    fn test_frame_limiter() {
+        let start = Instant::now();
+        let mut frames = FrameLimiter::new(10);
+        assert!(frames.allow(start, false));
+        assert!(!frames.allow(start + Duration::from_millis(50), false));
+        assert!(frames.allow(start + Duration::from_millis(60), true));
+        assert!(!frames.allow(start + Duration::from_millis(100), false));
+        assert!(frames.allow(start + Duration::from_millis(160), false));
+        let mut frames = FrameLimiter::new(0);
+        assert!(frames.allow(start, false));
+        assert!(frames.allow(start, false));
+    }
 }
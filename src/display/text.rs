@@ -14,14 +14,19 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
+use chrono::Local;
+use std::{borrow::Cow, time::Instant};
+
 use crate::{
+    cfg::TextLayout,
     console::charset::{TableChar, TableCharSet},
-    process::{Aggregation, Collector, FormattedMetric, ProcessIdentity},
+    process::{format::ColumnAlignment, Aggregation, Collector, FormattedMetric, ProcessIdentity},
 };
 
 use super::{DisplayDevice, PaneData, PaneKind, SliceIter};
 
 const REPEAT_HEADER_EVERY: u16 = 20;
+const TIME_FORMAT: &str = "%Y-%m-%dT%H:%M:%S%:z";
 const RESIZE_IF_COLUMNS_SHRINK: usize = 2;
 
 fn divide(numerator: usize, denominator: usize) -> (usize, usize) {
@@ -31,8 +36,10 @@ fn divide(numerator: usize, denominator: usize) -> (usize, usize) {
 
 /// A subtitle with a name and a short name
 struct SubTitle {
-    name: &'static str,
+    name: String,
     short_name: Option<&'static str>,
+    /// Alignment of the values, centered by default.
+    alignment: Option<ColumnAlignment>,
 }
 
 /// Information to close a table
@@ -67,6 +74,8 @@ struct Table {
     hrule: Option<HorizontalRule>,
     charset: TableCharSet,
     vertical_padding: String,
+    /// Blank space on the left of the header lines to align with the timestamps.
+    margin: String,
 }
 
 impl Table {
@@ -82,6 +91,7 @@ impl Table {
             charset: TableCharSet::new(),
             hrule: None,
             vertical_padding: " ".repeat(VERTICAL_PADDING),
+            margin: String::new(),
         }
     }
 
@@ -93,8 +103,17 @@ impl Table {
         self.titles.push(title);
     }
 
-    fn push_subtitle(&mut self, name: &'static str, short_name: Option<&'static str>) {
-        self.subtitles.push(SubTitle { name, short_name });
+    fn push_subtitle(
+        &mut self,
+        name: &str,
+        short_name: Option<&'static str>,
+        alignment: Option<ColumnAlignment>,
+    ) {
+        self.subtitles.push(SubTitle {
+            name: name.to_string(),
+            short_name,
+            alignment,
+        });
     }
 
     fn clear_values(&mut self) {
@@ -119,6 +138,7 @@ impl Table {
         }) = &self.hrule
         {
             let column_count = subtitle_count * title_count;
+            print!("{}", self.margin);
             for index in 0..column_count {
                 let separator = if index == 0 {
                     left
@@ -153,6 +173,7 @@ impl Table {
         );
         // Titles
         let vline = self.charset.get(TableChar::VerticalInner);
+        print!("{}", self.margin);
         for title in &self.titles {
             print!(
                 "{}{}{:^width$}{}",
@@ -171,6 +192,7 @@ impl Table {
             self.charset.get(TableChar::VerticalLeft),
         );
         // Subtitles
+        print!("{}", self.margin);
         for _ in 0..self.title_count {
             for subtitle in &self.subtitles {
                 print!(
@@ -182,7 +204,7 @@ impl Table {
                             .short_name
                             .expect("cannot have sub-title larger than column width")
                     } else {
-                        subtitle.name
+                        &subtitle.name
                     },
                     self.vertical_padding,
                     width = self.column_width
@@ -201,16 +223,24 @@ impl Table {
         );
     }
 
-    fn print_values(&self) {
+    fn print_values(&self, prefix: &str) {
         let vline = self.charset.get(TableChar::VerticalInner);
-        for value in &self.values {
+        print!("{prefix}");
+        let alignments = self
+            .subtitles
+            .iter()
+            .map(|subtitle| subtitle.alignment)
+            .cycle();
+        for (value, alignment) in self.values.iter().zip(alignments) {
+            let width = self.column_width;
+            let value = match alignment {
+                Some(ColumnAlignment::Left) => format!("{value:<width$}"),
+                Some(ColumnAlignment::Right) => format!("{value:>width$}"),
+                Some(ColumnAlignment::Center) | None => format!("{value:^width$}"),
+            };
             print!(
-                "{}{}{:^width$}{}",
-                vline,
-                self.vertical_padding,
-                value,
-                self.vertical_padding,
-                width = self.column_width
+                "{}{}{}{}",
+                vline, self.vertical_padding, value, self.vertical_padding,
             );
         }
         println!("{}", vline);
@@ -256,12 +286,17 @@ impl Table {
         column_width
     }
 
-    fn print(&mut self, with_header: bool) {
+    /// Print the values with an optional prefix such as a timestamp.
+    fn print(&mut self, with_header: bool, prefix: &str) {
         let column_width = self.resize();
+        if prefix.len() != self.margin.len() {
+            self.margin = " ".repeat(prefix.len());
+            self.repeat = 0;
+        }
         if with_header || self.repeat == 0 {
             self.print_header(column_width);
         }
-        self.print_values();
+        self.print_values(prefix);
         self.repeat += 1;
         if self.repeat >= REPEAT_HEADER_EVERY {
             self.repeat = 0;
@@ -269,26 +304,164 @@ impl Table {
     }
 }
 
-/// Print on standard output as a table
+/// Print one line per process with values separated by a delimiter
+struct Delimited {
+    separator: char,
+    header: Vec<String>,
+    header_printed: bool,
+}
+
+impl Delimited {
+    fn new(separator: char, with_elapsed: bool) -> Delimited {
+        let mut header = vec![String::from("time")];
+        if with_elapsed {
+            header.push(String::from("elapsed"));
+        }
+        header.push(String::from("name"));
+        header.push(String::from("pid"));
+        Delimited {
+            separator,
+            header,
+            header_printed: false,
+        }
+    }
+
+    fn push_column(&mut self, name: String) {
+        self.header.push(name);
+    }
+
+    /// Escape a field: quoted in CSV, without tabs in TSV.
+    fn escape<'a>(&self, field: &'a str) -> Cow<'a, str> {
+        match self.separator {
+            '\t' if field.contains(['\t', '\n']) => Cow::Owned(field.replace(['\t', '\n'], " ")),
+            '\t' => Cow::Borrowed(field),
+            sep if field.contains([sep, '"', '\n']) => {
+                Cow::Owned(format!("\"{}\"", field.replace('"', "\"\"")))
+            }
+            _ => Cow::Borrowed(field),
+        }
+    }
+
+    fn join<'a, I>(&self, fields: I) -> String
+    where
+        I: Iterator<Item = &'a str>,
+    {
+        fields
+            .map(|field| self.escape(field))
+            .collect::<Vec<Cow<str>>>()
+            .join(&self.separator.to_string())
+    }
+
+    fn print(&mut self, collector: &Collector, elapsed: Option<&str>) {
+        if !self.header_printed {
+            if let Some(index) = self.header.iter().position(|name| name == "pid") {
+                if collector.with_file_usage() {
+                    self.header.insert(index + 1, String::from("file"));
+                }
+                if collector.with_wchan() {
+                    self.header.insert(index + 1, String::from("wchan"));
+                }
+                if collector.with_units() {
+                    self.header.insert(index + 1, String::from("unit"));
+                }
+            }
+            println!("{}", self.join(self.header.iter().map(String::as_str)));
+            self.header_printed = true;
+        }
+        let timestamp = Local::now().format(TIME_FORMAT).to_string();
+        collector.lines().for_each(|pstat| {
+            let pid = pstat.pid().to_string();
+            let fields = std::iter::once(timestamp.as_str())
+                .chain(elapsed)
+                .chain([pstat.name(), pid.as_str()])
+                .chain(
+                    collector
+                        .with_units()
+                        .then(|| pstat.unit().as_deref().unwrap_or_default()),
+                )
+                .chain(
+                    collector
+                        .with_wchan()
+                        .then(|| pstat.wchan().as_deref().unwrap_or_default()),
+                )
+                .chain(
+                    collector
+                        .with_file_usage()
+                        .then(|| pstat.file_usage().as_deref().unwrap_or_default()),
+                )
+                .chain(
+                    pstat
+                        .samples()
+                        .flat_map(|sample| sample.strings())
+                        .map(String::as_str),
+                );
+            println!("{}", self.join(fields));
+        });
+    }
+}
+
+/// Layout of the text output
+enum Layout {
+    Table(Table),
+    Delimited(Delimited),
+}
+
+/// Print on standard output as a table or as delimiter-separated values
 pub struct TextDevice {
-    table: Table,
+    layout: Layout,
+    /// Start time if the elapsed time is printed.
+    start: Option<Instant>,
+    /// Whether the targets were not started at the last render.
+    waiting: bool,
 }
 
 impl TextDevice {
-    pub fn new() -> TextDevice {
+    /// Create a text device. If `timestamps` is true, the lines are prefixed with
+    /// the wall-clock time and the elapsed time.
+    pub fn new(layout: TextLayout, timestamps: bool) -> TextDevice {
         TextDevice {
-            table: Table::new(),
+            layout: match layout {
+                TextLayout::Table => Layout::Table(Table::new()),
+                TextLayout::Tsv => Layout::Delimited(Delimited::new('\t', timestamps)),
+                TextLayout::Csv => Layout::Delimited(Delimited::new(',', timestamps)),
+            },
+            start: if timestamps {
+                Some(Instant::now())
+            } else {
+                None
+            },
+            waiting: false,
         }
     }
+
+    /// Elapsed time in seconds since the device has been created.
+    fn elapsed(&self) -> Option<String> {
+        self.start
+            .map(|start| format!("{:.3}", start.elapsed().as_secs_f64()))
+    }
 }
 
 impl DisplayDevice for TextDevice {
     fn open(&mut self, metrics: SliceIter<FormattedMetric>) -> anyhow::Result<()> {
-        let mut last_id = None;
-        Collector::for_each_computed_metric(metrics, |id, ag| {
-            if last_id.is_none() || last_id.unwrap() != id {
-                last_id = Some(id);
-                self.table.push_subtitle(id.as_str(), id.to_short_str());
+        let table = match self.layout {
+            Layout::Table(ref mut table) => table,
+            Layout::Delimited(ref mut delimited) => {
+                Collector::for_each_computed_metric(metrics, |metric, ag| {
+                    delimited.push_column(match ag {
+                        Aggregation::None => metric.name().to_string(),
+                        Aggregation::Min => format!("{}+min", metric.name()),
+                        Aggregation::Max => format!("{}+max", metric.name()),
+                        Aggregation::Ratio => format!("{}+ratio", metric.name()),
+                    })
+                });
+                return Ok(());
+            }
+        };
+        let mut last_name = None;
+        Collector::for_each_computed_metric(metrics, |metric, ag| {
+            if last_name != Some(metric.name()) {
+                last_name = Some(metric.name());
+                table.push_subtitle(metric.name(), metric.short_name(), metric.column.alignment);
             } else {
                 let subtitle = match ag {
                     Aggregation::None => "none", // never used
@@ -296,35 +469,55 @@ impl DisplayDevice for TextDevice {
                     Aggregation::Max => "max",
                     Aggregation::Ratio => "ratio",
                 };
-                self.table.push_subtitle(subtitle, None);
+                table.push_subtitle(subtitle, None, metric.column.alignment);
             }
         });
         Ok(())
     }
 
     fn close(&mut self) -> anyhow::Result<()> {
-        self.table.print_footer();
+        if let Layout::Table(ref table) = self.layout {
+            table.print_footer();
+        }
         Ok(())
     }
 
     fn render(&mut self, kind: PaneKind, data: PaneData, redraw: bool) -> anyhow::Result<()> {
         match (kind, data) {
             (PaneKind::Main, PaneData::Collector(collector)) => {
-                if collector.is_empty() {
+                let was_waiting = self.waiting;
+                self.waiting = collector.is_waiting();
+                if self.waiting {
+                    if !was_waiting {
+                        eprintln!("waiting for target");
+                    }
+                } else if collector.is_empty() {
                     eprintln!("no process found")
                 } else {
-                    self.table.clear_titles();
-                    self.table.clear_values();
-                    collector.lines().for_each(|pstat| {
-                        let name = format!("{} [{}]", pstat.name(), pstat.pid());
-                        self.table.push_title(name);
-                        pstat.samples().for_each(|sample| {
-                            sample
-                                .strings()
-                                .for_each(|value| self.table.push_value(value))
-                        });
-                    });
-                    self.table.print(redraw);
+                    let elapsed = self.elapsed();
+                    match self.layout {
+                        Layout::Table(ref mut table) => {
+                            table.clear_titles();
+                            table.clear_values();
+                            collector.lines().for_each(|pstat| {
+                                let name = format!("{} [{}]", pstat.name(), pstat.pid());
+                                table.push_title(name);
+                                pstat.samples().for_each(|sample| {
+                                    sample.strings().for_each(|value| table.push_value(value))
+                                });
+                            });
+                            let prefix = match elapsed {
+                                Some(elapsed) => {
+                                    format!("{} {elapsed:>12} ", Local::now().format(TIME_FORMAT))
+                                }
+                                None => String::new(),
+                            };
+                            table.print(redraw, &prefix);
+                        }
+                        Layout::Delimited(ref mut delimited) => {
+                            delimited.print(collector, elapsed.as_deref())
+                        }
+                    }
                 }
             }
             (_, _) => panic!("invalid pane for text device"),
@@ -336,9 +529,22 @@ impl DisplayDevice for TextDevice {
 #[cfg(test)]
 mod tests {
 
+    use super::Delimited;
+
     #[test]
     fn test_divide() {
         assert_eq!((2, 0), super::divide(8, 4));
         assert_eq!((3, 2), super::divide(11, 3));
     }
+
+    #[test]
+    fn test_escape() {
+        let csv = Delimited::new(',', false);
+        assert_eq!(
+            "a,\"b,c\",\"d\"\"e\"",
+            csv.join(["a", "b,c", "d\"e"].into_iter())
+        );
+        let tsv = Delimited::new('\t', false);
+        assert_eq!("a b\tc,d", tsv.join(["a\tb", "c,d"].into_iter()));
+    }
 }
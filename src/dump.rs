@@ -0,0 +1,194 @@
+// Oprs -- process monitor for Linux
+// Copyright (C) 2025  Laurent Pelecq
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Dump of the internal state in JSON to make bug reports actionable.
+
+use chrono::{DateTime, Local};
+use std::{
+    fmt, fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+/// JSON value.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(i64),
+    Float(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(&'static str, Json)>),
+}
+
+impl Json {
+    /// String from the debug representation of a value.
+    pub fn debug<T: fmt::Debug>(value: T) -> Self {
+        Json::String(format!("{value:?}"))
+    }
+
+    /// Array of values.
+    pub fn array<I, T>(values: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<Json>,
+    {
+        Json::Array(values.into_iter().map(Into::into).collect())
+    }
+}
+
+impl From<bool> for Json {
+    fn from(value: bool) -> Self {
+        Json::Bool(value)
+    }
+}
+
+impl From<i32> for Json {
+    fn from(value: i32) -> Self {
+        Json::Number(value.into())
+    }
+}
+
+impl From<usize> for Json {
+    fn from(value: usize) -> Self {
+        Json::Number(value as i64)
+    }
+}
+
+impl From<u64> for Json {
+    fn from(value: u64) -> Self {
+        Json::Number(value as i64)
+    }
+}
+
+impl From<&str> for Json {
+    fn from(value: &str) -> Self {
+        Json::String(value.to_string())
+    }
+}
+
+impl From<String> for Json {
+    fn from(value: String) -> Self {
+        Json::String(value)
+    }
+}
+
+impl<T: Into<Json>> From<Option<T>> for Json {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(Json::Null, Into::into)
+    }
+}
+
+/// Write a string with the special characters escaped.
+fn write_string(f: &mut fmt::Formatter<'_>, value: &str) -> fmt::Result {
+    write!(f, "\"")?;
+    for c in value.chars() {
+        match c {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\r' => write!(f, "\\r")?,
+            '\t' => write!(f, "\\t")?,
+            c if c.is_control() => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{c}")?,
+        }
+    }
+    write!(f, "\"")
+}
+
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Json::Null => write!(f, "null"),
+            Json::Bool(value) => write!(f, "{value}"),
+            Json::Number(value) => write!(f, "{value}"),
+            Json::Float(value) => write!(f, "{value}"),
+            Json::String(value) => write_string(f, value),
+            Json::Array(values) => {
+                write!(f, "[")?;
+                for (index, value) in values.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{value}")?;
+                }
+                write!(f, "]")
+            }
+            Json::Object(fields) => {
+                write!(f, "{{")?;
+                for (index, (name, value)) in fields.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ",")?;
+                    }
+                    write_string(f, name)?;
+                    write!(f, ":{value}")?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+/// Write the state in a timestamped file of the directory.
+///
+/// Return the path of the file.
+pub fn write_dump(dir: &Path, state: &Json) -> io::Result<PathBuf> {
+    write_dump_at(dir, state, Local::now())
+}
+
+fn write_dump_at(dir: &Path, state: &Json, now: DateTime<Local>) -> io::Result<PathBuf> {
+    fs::create_dir_all(dir)?;
+    let path = dir.join(format!("oprs-dump_{}.json", now.format("%Y%m%dT%H%M%S")));
+    let mut file = fs::File::create(&path)?;
+    writeln!(file, "{state}")?;
+    file.sync_all()?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use chrono::{Local, TimeZone};
+    use std::fs;
+
+    use super::{write_dump_at, Json};
+
+    #[test]
+    fn test_json() {
+        let state = Json::Object(vec![
+            ("name", Json::from("a \"b\"\n\u{1}")),
+            ("pids", Json::array([1, 2])),
+            ("parent", Json::from(None::<i32>)),
+            ("empty", Json::Object(Vec::new())),
+            ("flag", Json::from(true)),
+        ]);
+        assert_eq!(
+            r#"{"name":"a \"b\"\n\u0001","pids":[1,2],"parent":null,"empty":{},"flag":true}"#,
+            state.to_string()
+        );
+    }
+
+    #[test]
+    fn test_write_dump() -> std::io::Result<()> {
+        let dir = std::env::temp_dir().join(format!("oprs-test-dump-{}", std::process::id()));
+        let now = Local.with_ymd_and_hms(2025, 3, 4, 5, 6, 7).unwrap();
+        let path = write_dump_at(&dir, &Json::array([1]), now)?;
+        assert_eq!(dir.join("oprs-dump_20250304T050607.json"), path);
+        assert_eq!("[1]\n", fs::read_to_string(&path)?);
+        fs::remove_dir_all(&dir)
+    }
+}
@@ -0,0 +1,293 @@
+// Oprs -- process monitor for Linux
+// Copyright (C) 2025  Laurent Pelecq
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Share a monitoring session with read-only followers on a Unix socket.
+//!
+//! The protocol is line oriented with tab-separated fields:
+//! - the header `#  name  pid  metric...` is sent once when a follower connects,
+//! - each sample starts with `@  timestamp`, followed by one line per process
+//!   and an empty line.
+
+use std::{
+    borrow::Cow,
+    fs,
+    io::{self, BufRead, BufReader, Write},
+    os::unix::{
+        fs::FileTypeExt,
+        net::{UnixListener, UnixStream},
+    },
+    path::{Path, PathBuf},
+};
+use termion::{clear, cursor};
+
+use crate::{
+    clock::SampleTime,
+    process::{Aggregation, Collector, FormattedMetric, ProcessIdentity},
+};
+
+use super::{Exporter, SliceIter};
+
+const HEADER_TAG: &str = "#";
+const FRAME_TAG: &str = "@";
+
+/// Mask of the permissions of the socket: only the owner can connect.
+const SOCKET_UMASK: libc::mode_t = 0o177;
+
+/// Remove the separators from a field.
+fn escape(field: &str) -> Cow<'_, str> {
+    if field.contains(['\t', '\n']) {
+        Cow::Owned(field.replace(['\t', '\n'], " "))
+    } else {
+        Cow::Borrowed(field)
+    }
+}
+
+/// Publish the formatted samples to the connected followers.
+pub struct Broadcaster {
+    path: PathBuf,
+    listener: UnixListener,
+    header: String,
+    followers: Vec<UnixStream>,
+}
+
+impl Broadcaster {
+    pub fn new(path: &Path) -> anyhow::Result<Self> {
+        let listener = match Self::bind(path) {
+            Err(err) if err.kind() == io::ErrorKind::AddrInUse && Self::is_stale(path) => {
+                log::info!("{}: removing stale socket", path.display());
+                fs::remove_file(path)?;
+                Self::bind(path)
+            }
+            res => res,
+        }
+        .map_err(|err| anyhow::anyhow!("{}: {err}", path.display()))?;
+        listener.set_nonblocking(true)?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            listener,
+            header: String::new(),
+            followers: Vec::new(),
+        })
+    }
+
+    /// Create the socket that only the owner can connect to.
+    fn bind(path: &Path) -> io::Result<UnixListener> {
+        let mask = unsafe { libc::umask(SOCKET_UMASK) };
+        let res = UnixListener::bind(path);
+        unsafe { libc::umask(mask) };
+        res
+    }
+
+    /// Whether the path is a socket left by a previous instance.
+    fn is_stale(path: &Path) -> bool {
+        fs::symlink_metadata(path).is_ok_and(|md| md.file_type().is_socket())
+            && UnixStream::connect(path)
+                .is_err_and(|err| err.kind() == io::ErrorKind::ConnectionRefused)
+    }
+
+    /// Accept the pending followers and send them the header.
+    ///
+    /// The writes never block. A follower that doesn't read fast enough is
+    /// disconnected.
+    fn accept(&mut self) -> io::Result<()> {
+        loop {
+            match self.listener.accept() {
+                Ok((mut stream, _)) => {
+                    log::info!("follower connected");
+                    stream.set_nonblocking(true)?;
+                    if stream.write_all(self.header.as_bytes()).is_ok() {
+                        self.followers.push(stream);
+                    }
+                }
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => return Ok(()),
+                Err(err) => return Err(err),
+            }
+        }
+    }
+}
+
+impl Exporter for Broadcaster {
+    fn open(&mut self, metrics: SliceIter<FormattedMetric>) -> anyhow::Result<()> {
+        let mut columns = vec![
+            HEADER_TAG.to_string(),
+            "name".to_string(),
+            "pid".to_string(),
+        ];
+        Collector::for_each_computed_metric(metrics, |metric, ag| {
+            columns.push(match ag {
+                Aggregation::None => metric.name().to_string(),
+                Aggregation::Min => format!("{}+min", metric.name()),
+                Aggregation::Max => format!("{}+max", metric.name()),
+                Aggregation::Ratio => format!("{}+ratio", metric.name()),
+            })
+        });
+        self.header = format!("{}\n", columns.join("\t"));
+        Ok(())
+    }
+
+    fn close(&mut self) -> anyhow::Result<()> {
+        self.followers.clear();
+        fs::remove_file(&self.path)?;
+        Ok(())
+    }
+
+    fn export(&mut self, collector: &Collector, time: &SampleTime) -> anyhow::Result<()> {
+        self.accept()?;
+        if self.followers.is_empty() {
+            return Ok(());
+        }
+        let mut frame = format!("{FRAME_TAG}\t{:.3}\n", time.wall.as_secs_f64());
+        collector.lines().for_each(|pstat| {
+            let pid = pstat.pid().to_string();
+            let fields = [escape(pstat.name()), Cow::Borrowed(pid.as_str())]
+                .into_iter()
+                .chain(
+                    pstat
+                        .samples()
+                        .flat_map(|sample| sample.strings())
+                        .map(|value| escape(value)),
+                )
+                .collect::<Vec<Cow<str>>>();
+            frame.push_str(&fields.join("\t"));
+            frame.push('\n');
+        });
+        frame.push('\n');
+        self.followers
+            .retain_mut(|stream| match stream.write_all(frame.as_bytes()) {
+                Ok(()) => true,
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => {
+                    log::warn!("follower too slow, disconnected");
+                    false
+                }
+                Err(_) => {
+                    log::info!("follower disconnected");
+                    false
+                }
+            });
+        Ok(())
+    }
+}
+
+/// Width of each column.
+fn column_widths(rows: &[Vec<String>]) -> Vec<usize> {
+    let mut widths = Vec::new();
+    for row in rows {
+        for (index, field) in row.iter().enumerate() {
+            let len = field.chars().count();
+            match widths.get_mut(index) {
+                Some(width) if *width < len => *width = len,
+                Some(_) => (),
+                None => widths.push(len),
+            }
+        }
+    }
+    widths
+}
+
+/// Format the rows as a table with left aligned names and right aligned values.
+fn format_table(rows: &[Vec<String>]) -> Vec<String> {
+    let widths = column_widths(rows);
+    rows.iter()
+        .map(|row| {
+            row.iter()
+                .zip(widths.iter())
+                .enumerate()
+                .map(|(index, (field, width))| {
+                    if index == 0 {
+                        format!("{field:<width$}")
+                    } else {
+                        format!("{field:>width$}")
+                    }
+                })
+                .collect::<Vec<String>>()
+                .join("  ")
+        })
+        .collect()
+}
+
+/// Display the samples published by another instance.
+pub fn follow(path: &Path) -> anyhow::Result<()> {
+    let stream =
+        UnixStream::connect(path).map_err(|err| anyhow::anyhow!("{}: {err}", path.display()))?;
+    let mut stdout = io::stdout();
+    let mut header = Vec::new();
+    let mut rows = Vec::new();
+    let mut time = String::new();
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        let mut fields = line.split('\t').map(str::to_string);
+        match fields.next().as_deref() {
+            Some(HEADER_TAG) => header = fields.collect(),
+            Some(FRAME_TAG) => {
+                time = fields.next().unwrap_or_default();
+                rows.clear();
+                rows.push(header.clone());
+            }
+            Some("") => {
+                write!(stdout, "{}{}", clear::All, cursor::Goto(1, 1))?;
+                writeln!(stdout, "{} -- time:{time}", path.display())?;
+                for line in format_table(&rows) {
+                    writeln!(stdout, "{line}")?;
+                }
+                stdout.flush()?;
+            }
+            Some(name) => rows.push(std::iter::once(name.to_string()).chain(fields).collect()),
+            None => (),
+        }
+    }
+    println!("session closed");
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::{
+        env, fs,
+        os::unix::{fs::PermissionsExt, net::UnixListener},
+        process,
+    };
+
+    use super::{escape, format_table, Broadcaster};
+
+    #[test]
+    fn test_escape() {
+        assert_eq!("a b c", escape("a\tb\nc"));
+        assert_eq!("abc", escape("abc"));
+    }
+
+    #[test]
+    fn test_format_table() {
+        let rows = vec![
+            vec!["name".to_string(), "pid".to_string()],
+            vec!["bash".to_string(), "12345".to_string()],
+        ];
+        assert_eq!(vec!["name    pid", "bash  12345"], format_table(&rows));
+    }
+
+    #[test]
+    fn test_stale_socket() {
+        let path = env::temp_dir().join(format!("oprs-test-share-{}.sock", process::id()));
+        let _ = fs::remove_file(&path);
+        drop(UnixListener::bind(&path).unwrap());
+        let broadcaster = Broadcaster::new(&path).unwrap();
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(0o600, mode & 0o777);
+        assert!(Broadcaster::new(&path).is_err());
+        drop(broadcaster);
+        fs::remove_file(&path).unwrap();
+    }
+}
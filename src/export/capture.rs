@@ -0,0 +1,140 @@
+// Oprs -- process monitor for Linux
+// Copyright (C) 2025  Laurent Pelecq
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Capture the procfs files of the monitored processes to replay them in tests.
+//!
+//! Each sample is written in a numbered directory with the same layout as /proc,
+//! for example `DIR/0/meminfo` and `DIR/0/PID/stat`. The target of the link
+//! `/proc/PID/exe` is written in the regular file `DIR/0/PID/exe`.
+//!
+//! The environment of the processes is not captured since it may contain secrets.
+
+use libc::pid_t;
+use std::{
+    fs, io,
+    os::unix::ffi::OsStrExt,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    clock::SampleTime,
+    process::{Collector, FormattedMetric, ProcessIdentity},
+};
+
+use super::{Exporter, SliceIter};
+
+/// Files of /proc describing the system.
+const SYSTEM_FILES: &[&str] = &["loadavg", "meminfo", "stat", "uptime"];
+
+/// Files of /proc/PID describing a process.
+const PROCESS_FILES: &[&str] = &["cmdline", "io", "limits", "maps", "stat", "statm", "status"];
+
+/// Link to the executable of a process.
+const EXE_LINK: &str = "exe";
+
+/// Copy procfs files of the system and the monitored processes.
+pub struct ProcfsCapture {
+    dir: PathBuf,
+    proc_root: PathBuf,
+    count: usize,
+}
+
+impl ProcfsCapture {
+    pub fn new(dir: &Path) -> io::Result<Self> {
+        fs::create_dir_all(dir)?;
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            proc_root: PathBuf::from("/proc"),
+            count: 0,
+        })
+    }
+
+    /// Copy the files that can be read. Processes may terminate in between.
+    fn copy_files(src: &Path, dst: &Path, filenames: &[&str]) -> io::Result<()> {
+        fs::create_dir_all(dst)?;
+        for filename in filenames {
+            match fs::read(src.join(filename)) {
+                Ok(content) => fs::write(dst.join(filename), content)?,
+                Err(err) => log::debug!("{}: {err}", src.join(filename).display()),
+            }
+        }
+        Ok(())
+    }
+
+    /// Capture a sample for the given processes.
+    fn capture<I>(&mut self, pids: I) -> io::Result<()>
+    where
+        I: Iterator<Item = pid_t>,
+    {
+        let sample_dir = self.dir.join(self.count.to_string());
+        ProcfsCapture::copy_files(&self.proc_root, &sample_dir, SYSTEM_FILES)?;
+        for pid in pids {
+            let src = self.proc_root.join(pid.to_string());
+            let dst = sample_dir.join(pid.to_string());
+            ProcfsCapture::copy_files(&src, &dst, PROCESS_FILES)?;
+            if let Ok(target) = fs::read_link(src.join(EXE_LINK)) {
+                fs::write(dst.join(EXE_LINK), target.as_os_str().as_bytes())?;
+            }
+        }
+        self.count += 1;
+        Ok(())
+    }
+}
+
+impl Exporter for ProcfsCapture {
+    fn open(&mut self, _: SliceIter<FormattedMetric>) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn close(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn export(&mut self, collector: &Collector, _: &SampleTime) -> anyhow::Result<()> {
+        self.capture(
+            collector
+                .lines()
+                .map(|pstat| pstat.pid())
+                .filter(|pid| *pid > 0),
+        )?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::{fs, io};
+
+    use super::ProcfsCapture;
+
+    #[test]
+    fn test_capture() -> io::Result<()> {
+        let dir = std::env::temp_dir().join(format!("oprs-capture-{}", std::process::id()));
+        let pid = std::process::id() as libc::pid_t;
+        let mut capture = ProcfsCapture::new(&dir)?;
+        capture.capture([pid].into_iter())?;
+        capture.capture(std::iter::empty())?;
+        let sample_dir = dir.join("0");
+        assert!(sample_dir.join("meminfo").exists());
+        assert!(sample_dir.join(pid.to_string()).join("stat").exists());
+        assert!(!sample_dir.join(pid.to_string()).join("environ").exists());
+        assert!(fs::read_to_string(sample_dir.join(pid.to_string()).join("exe"))?.starts_with('/'));
+        assert!(dir.join("1").join("stat").exists());
+        assert!(!dir.join("1").join(pid.to_string()).exists());
+        fs::remove_dir_all(dir)
+    }
+}
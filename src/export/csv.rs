@@ -19,18 +19,37 @@ use memchr::memchr;
 use std::{
     borrow::Cow,
     collections::{HashMap, HashSet},
-    fs::{self, File},
-    io::{self, Seek, Write},
+    fs::{self, File, OpenOptions},
+    io::{self, BufWriter, Write},
     path::{Path, PathBuf},
     time::Duration,
 };
 
 use crate::{
     cfg::{ExportSettings, ExportType},
-    process::{Aggregation, Collector, FormattedMetric, ProcessIdentity},
+    clock::SampleTime,
+    process::{
+        parsers::HistogramBounds, Aggregation, Collector, FormattedMetric, ProcessIdentity, Sample,
+        StateEvent,
+    },
 };
 
-use super::{Exporter, SliceIter};
+use super::{
+    downsample::{AveragedRow, Downsampler},
+    flush::FlushPolicy,
+    histogram::{HistogramCollector, ProcessHistograms},
+    index::RotationIndex,
+    retention::Retention,
+    schema,
+    units::BaseUnits,
+    Exporter, FileNameTemplate, SliceIter,
+};
+
+/// Name of the file of events without the extension.
+const EVENTS_FILE_STEM: &str = "events";
+
+/// Column of the breach of the resource limits.
+const LIMIT_BREACH_COLUMN: &str = "limit:breach";
 
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
@@ -39,17 +58,23 @@ pub enum Error {
 }
 
 trait ToStr {
-    fn to_str(&self) -> Cow<String>;
+    fn to_str(&self) -> Cow<'_, str>;
 }
 
 impl ToStr for &String {
-    fn to_str(&self) -> Cow<String> {
+    fn to_str(&self) -> Cow<'_, str> {
+        Cow::Borrowed(self)
+    }
+}
+
+impl ToStr for String {
+    fn to_str(&self) -> Cow<'_, str> {
         Cow::Borrowed(self)
     }
 }
 
 impl ToStr for &u64 {
-    fn to_str(&self) -> Cow<String> {
+    fn to_str(&self) -> Cow<'_, str> {
         Cow::Owned(format!("{self}"))
     }
 }
@@ -81,7 +106,7 @@ impl<'a> CsvLineOutput<'a> {
     {
         for value in row.into_iter() {
             write!(self.out, "{}", self.separator)?;
-            self.write_value(value.to_str().as_str())?;
+            self.write_value(&value.to_str())?;
         }
         writeln!(self.out)?;
         Ok(())
@@ -95,7 +120,7 @@ impl<'a> CsvLineOutput<'a> {
     {
         let mut iter = row.into_iter();
         if let Some(first) = iter.next() {
-            self.write_value(first.to_str().as_str())?;
+            self.write_value(&first.to_str())?;
             self.write_line_rest(iter)?;
         }
         Ok(())
@@ -106,10 +131,30 @@ pub struct CsvExporter {
     separator: char,
     extension: &'static str,
     dir: PathBuf,
+    template: FileNameTemplate,
     count: Option<usize>,
     size: Option<u64>,
-    files: HashMap<pid_t, File>,
+    files: HashMap<pid_t, (PathBuf, BufWriter<File>)>,
+    /// Changes of state of the processes.
+    events: Option<BufWriter<File>>,
+    flush: FlushPolicy,
     header: Vec<String>,
+    /// Header line used to check existing files.
+    columns: Vec<String>,
+    /// Header line of the files of averages that have no breach of limits.
+    average_columns: Vec<String>,
+    /// Whether the breach of the resource limits is exported.
+    with_limit_breach: bool,
+    specs: Vec<HistogramBounds>,
+    histograms: HistogramCollector,
+    retention: Retention,
+    /// Time ranges of the files if they are rotated.
+    index: Option<RotationIndex>,
+    /// Time of the first and last rows still in the buffers by file.
+    unindexed: HashMap<PathBuf, (Duration, Duration)>,
+    /// Averages over longer intervals.
+    downsamplers: Vec<Downsampler>,
+    units: BaseUnits,
 }
 
 impl CsvExporter {
@@ -128,25 +173,149 @@ impl CsvExporter {
             separator,
             extension,
             dir: settings.dir.clone(),
+            template: settings.template.clone(),
             count,
             size: settings.size,
             files: HashMap::new(),
+            events: None,
+            flush: FlushPolicy::new(settings),
             header: Vec::new(),
+            columns: Vec::new(),
+            average_columns: Vec::new(),
+            with_limit_breach: false,
+            specs: settings.histograms.clone(),
+            histograms: HistogramCollector::default(),
+            retention: Retention::new(settings),
+            index: count.map(|_| RotationIndex::new(&settings.dir, separator)),
+            unindexed: HashMap::new(),
+            downsamplers: settings
+                .downsample
+                .iter()
+                .map(|interval| Downsampler::new(*interval))
+                .collect(),
+            units: BaseUnits::new(settings),
         })
     }
 
-    /// Create a file and write the header
+    /// Whether lines can be appended to an existing file.
+    ///
+    /// The file must have the same header and must not be full.
+    fn can_append(&self, filename: &Path) -> io::Result<bool> {
+        if let Some(size) = self.size {
+            if fs::metadata(filename)?.len() >= size {
+                return Ok(false);
+            }
+        }
+        let compatible = schema::is_compatible_file(filename, &self.columns, self.units.is_base())?;
+        if !compatible {
+            log::warn!("{}: incompatible export file", filename.display());
+        }
+        Ok(compatible)
+    }
+
+    /// Create a file and write the header or append to an existing file with the same header.
     fn create_file(&mut self, pid: pid_t, name: &str) -> io::Result<()> {
         let filename = self
             .dir
-            .join(format!("{}_{}.{}", name, pid, self.extension));
+            .join(self.template.render(name, pid, self.extension));
+        if let Some(parent) = filename.parent() {
+            fs::create_dir_all(parent)?;
+        }
         if filename.exists() {
+            if self.can_append(&filename)? {
+                let file = OpenOptions::new().append(true).open(&filename)?;
+                if let Some(ref mut index) = self.index {
+                    if !index.contains(&filename) {
+                        index.load(&filename)?;
+                    }
+                }
+                self.files.insert(pid, (filename, BufWriter::new(file)));
+                return Ok(());
+            }
             self.shift_file(&filename, 0)?;
         }
+        let mut file = File::create(&filename)?;
+        schema::write_header(&mut file, &self.columns, self.units.is_base())?;
+        if let Some(ref mut index) = self.index {
+            index.remove(&filename);
+            index.write()?;
+        }
+        self.files.insert(pid, (filename, BufWriter::new(file)));
+        Ok(())
+    }
+
+    /// Open the file of events, writing the header if it's new.
+    fn open_events_file(&self) -> io::Result<BufWriter<File>> {
+        let filename = self
+            .dir
+            .join(format!("{EVENTS_FILE_STEM}.{}", self.extension));
+        fs::create_dir_all(&self.dir)?;
+        let exists = filename.exists();
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(filename)?;
+        if !exists {
+            let header = ["time", "pid", "name", "event"].map(String::from);
+            CsvLineOutput::new(&mut file, self.separator).write_line(header.iter())?;
+        }
+        Ok(BufWriter::new(file))
+    }
+
+    /// Write the histograms of a process in a separate file.
+    fn write_histograms(&self, pid: pid_t, ph: &ProcessHistograms) -> io::Result<()> {
+        let extension = format!("hist.{}", self.extension);
+        let filename = self
+            .dir
+            .join(self.template.render(ph.name(), pid, &extension));
         let mut file = File::create(filename)?;
+        // The bounds of the histograms are in the collected units.
+        writeln!(file, "{}", schema::metadata_line(false))?;
         let mut lout = CsvLineOutput::new(&mut file, self.separator);
-        lout.write_line(self.header.iter())?;
-        self.files.insert(pid, file);
+        let header = ["metric", "le", "count"].map(String::from);
+        lout.write_line(header.iter())?;
+        for (name, histogram) in ph.iter() {
+            for (bound, count) in histogram.buckets() {
+                let bound = bound.map_or_else(|| String::from("inf"), |b| b.to_string());
+                let line = [name.to_string(), bound, count.to_string()];
+                lout.write_line(line.iter())?;
+            }
+        }
+        file.sync_all()
+    }
+
+    /// Append averaged values in the file of the interval, creating it if necessary.
+    ///
+    /// An incompatible file is replaced.
+    fn write_averages(&self, label: &str, row: &AveragedRow) -> io::Result<()> {
+        let extension = format!("{label}.{}", self.extension);
+        let filename = self
+            .dir
+            .join(self.template.render(&row.name, row.pid, &extension));
+        if let Some(parent) = filename.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let compatible = filename.exists()
+            && schema::is_compatible_file(&filename, &self.average_columns, self.units.is_base())?;
+        let mut file = if compatible {
+            OpenOptions::new().append(true).open(&filename)?
+        } else {
+            let mut file = File::create(filename)?;
+            schema::write_header(&mut file, &self.average_columns, self.units.is_base())?;
+            file
+        };
+        write!(file, "{}", row.time)?;
+        CsvLineOutput::new(&mut file, self.separator)
+            .write_line_rest(self.units.format(row.values.iter().copied()))
+    }
+
+    /// Write the averaged rows of each interval.
+    fn write_downsampled(&self, rows: Vec<(String, Vec<AveragedRow>)>) -> io::Result<()> {
+        for (label, rows) in rows {
+            for row in rows {
+                self.write_averages(&label, &row)?;
+            }
+        }
         Ok(())
     }
 
@@ -161,7 +330,7 @@ impl CsvExporter {
     }
 
     /// Shift all files keeping only the last ones
-    fn shift_file<P>(&self, filename: P, rank: usize) -> io::Result<()>
+    fn shift_file<P>(&mut self, filename: P, rank: usize) -> io::Result<()>
     where
         P: AsRef<Path>,
     {
@@ -176,7 +345,76 @@ impl CsvExporter {
                 if destination.exists() {
                     self.shift_file(filename, rank + 1)?;
                 }
-                fs::rename(source, destination)?;
+                fs::rename(&source, &destination)?;
+                if let Some(ref mut index) = self.index {
+                    index.rename(&source, &destination);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Write the buffered rows without synchronizing the files.
+    fn write_buffers(&mut self) -> io::Result<()> {
+        for file in self
+            .files
+            .values_mut()
+            .map(|(_, file)| file)
+            .chain(self.events.iter_mut())
+        {
+            file.flush()?;
+        }
+        for path in self.unindexed.keys().cloned().collect::<Vec<PathBuf>>() {
+            self.index_written(&path);
+        }
+        Ok(())
+    }
+
+    /// Record in the index the rows of a file once they are written.
+    fn index_written(&mut self, path: &Path) {
+        if let (Some(index), Some((first, last))) =
+            (self.index.as_mut(), self.unindexed.remove(path))
+        {
+            index.record(path, first);
+            index.record(path, last);
+        }
+    }
+
+    /// Whether the file contains the rows of a process, possibly rotated.
+    ///
+    /// The files of histograms and averages are excluded.
+    fn is_data_file(&self, path: &Path) -> io::Result<bool> {
+        let name = match path.file_name().and_then(|name| name.to_str()) {
+            Some(name) if Retention::is_export_file(name, self.extension) => name,
+            _ => return Ok(false),
+        };
+        let suffix = format!(".{}", self.extension);
+        let stem = &name[..name.rfind(&suffix).unwrap_or(name.len())];
+        let is_derived = stem.ends_with(".hist")
+            || self
+                .downsamplers
+                .iter()
+                .any(|ds| stem.ends_with(&format!(".{}", ds.label())));
+        Ok(!is_derived && schema::is_compatible_file(path, &self.columns, self.units.is_base())?)
+    }
+
+    /// Index the files left in the directory and its subdirectories by previous runs.
+    fn load_index(&mut self, dir: &Path) -> io::Result<()> {
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(err) => return Err(err),
+        };
+        for entry in entries {
+            let entry = entry?;
+            let path = entry.path();
+            let file_type = entry.file_type()?;
+            if file_type.is_dir() {
+                self.load_index(&path)?;
+            } else if file_type.is_file() && self.is_data_file(&path)? {
+                if let Some(ref mut index) = self.index {
+                    index.load(&path)?;
+                }
             }
         }
         Ok(())
@@ -185,16 +423,23 @@ impl CsvExporter {
 
 impl Exporter for CsvExporter {
     fn open(&mut self, metrics: SliceIter<FormattedMetric>) -> anyhow::Result<()> {
-        let mut last_id = None;
+        let events = format!("{EVENTS_FILE_STEM}.{}", self.extension);
+        self.retention.apply(self.extension, &[events])?;
+        if !self.specs.is_empty() {
+            self.histograms = HistogramCollector::new(&self.specs, metrics.clone());
+        }
+        let mut last_name = None;
         self.header.push(String::from("time"));
-        Collector::for_each_computed_metric(metrics, |id, ag| {
-            if last_id.is_none() || last_id.unwrap() != id {
-                last_id = Some(id);
-                self.header.push(id.as_str().to_string());
+        self.with_limit_breach = metrics.clone().any(FormattedMetric::has_limit);
+        self.units.open(metrics.clone());
+        Collector::for_each_computed_metric(metrics, |metric, ag| {
+            if last_name != Some(metric.name()) {
+                last_name = Some(metric.name());
+                self.header.push(metric.name().to_string());
             } else {
                 let name = format!(
                     "{} ({})",
-                    id.as_str(),
+                    metric.name(),
                     match ag {
                         Aggregation::None => "none", // never used
                         Aggregation::Min => "min",
@@ -205,31 +450,112 @@ impl Exporter for CsvExporter {
                 self.header.push(name);
             }
         });
+        let header_line = |header: &[String]| -> io::Result<Vec<String>> {
+            let mut line = Vec::new();
+            CsvLineOutput::new(&mut line, self.separator).write_line(header.iter())?;
+            Ok(vec![String::from_utf8_lossy(&line).trim_end().to_string()])
+        };
+        self.average_columns = header_line(&self.header)?;
+        if self.with_limit_breach {
+            self.header.push(LIMIT_BREACH_COLUMN.to_string());
+        }
+        self.columns = header_line(&self.header)?;
+        if self.index.is_some() {
+            self.load_index(&self.dir.clone())?;
+            if let Some(ref mut index) = self.index {
+                index.write()?;
+            }
+        }
         Ok(())
     }
 
     fn close(&mut self) -> anyhow::Result<()> {
-        for (_, file) in self.files.drain() {
-            file.sync_all()?;
+        for (_, (path, mut file)) in self.files.drain().collect::<Vec<_>>() {
+            file.flush()?;
+            file.get_ref().sync_all()?;
+            self.index_written(&path);
         }
+        if let Some(ref mut index) = self.index {
+            index.write()?;
+        }
+        if let Some(mut file) = self.events.take() {
+            file.flush()?;
+            file.get_ref().sync_all()?;
+        }
+        for (pid, ph) in self.histograms.drain() {
+            self.write_histograms(pid, &ph)?;
+        }
+        let rows = self
+            .downsamplers
+            .iter_mut()
+            .map(|ds| (ds.label(), ds.drain()))
+            .collect();
+        self.write_downsampled(rows)?;
         Ok(())
     }
 
-    fn export(&mut self, collector: &Collector, timestamp: &Duration) -> anyhow::Result<()> {
+    fn flush(&mut self) -> anyhow::Result<()> {
+        self.write_buffers()?;
+        for file in self
+            .files
+            .values()
+            .map(|(_, file)| file)
+            .chain(self.events.iter())
+        {
+            file.get_ref().sync_all()?;
+        }
+        if let Some(ref mut index) = self.index {
+            index.write()?;
+        }
+        Ok(())
+    }
+
+    fn event(&mut self, event: &StateEvent, time: &SampleTime) -> anyhow::Result<()> {
+        if self.events.is_none() {
+            self.events = Some(self.open_events_file()?);
+        }
+        if let Some(ref mut file) = self.events {
+            write!(file, "{:.3}", time.wall.as_secs_f64())?;
+            let line = [
+                event.pid().to_string(),
+                event.name().to_string(),
+                event.kind().to_string(),
+            ];
+            CsvLineOutput::new(file, self.separator).write_line_rest(line.iter())?;
+            if !self.flush.is_buffered() {
+                file.flush()?;
+            }
+        }
+        Ok(())
+    }
+
+    fn export(&mut self, collector: &Collector, time: &SampleTime) -> anyhow::Result<()> {
         let mut pids: HashSet<pid_t> = self.files.keys().copied().collect();
+        let mut rows = 0;
         for pstat in collector.lines() {
             let pid = pstat.pid();
             if !pids.remove(&pid) {
                 self.create_file(pid, pstat.name())?;
             }
-            let samples = pstat.samples().flat_map(|sample| sample.values());
-            if let Some(ref mut file) = self.files.get_mut(&pid) {
+            let limit_breach = pstat.limit_breach().level();
+            let samples = self
+                .units
+                .format(pstat.samples().flat_map(Sample::collected_values))
+                .chain(self.with_limit_breach.then(|| limit_breach.to_string()));
+            if let Some((ref path, ref mut file)) = self.files.get_mut(&pid) {
                 // Necessarily true
-                write!(file, "{:.3}", timestamp.as_secs_f64())?;
+                write!(file, "{:.3}", time.wall.as_secs_f64())?;
                 let mut lout = CsvLineOutput::new(file, self.separator);
                 lout.write_line_rest(samples)?;
+                rows += 1;
+                if self.index.is_some() {
+                    self.unindexed
+                        .entry(path.clone())
+                        .and_modify(|(_, last)| *last = time.wall)
+                        .or_insert((time.wall, time.wall));
+                }
                 if let Some(size) = self.size {
-                    let written = file.seek(io::SeekFrom::End(0))?;
+                    let written = file.get_ref().metadata()?.len() + file.buffer().len() as u64;
                     if written >= size {
                         pids.insert(pid); // file will be closed
                     }
@@ -237,8 +563,29 @@ impl Exporter for CsvExporter {
             }
         }
         for pid in pids {
-            self.files.remove(&pid);
+            if let Some((path, mut file)) = self.files.remove(&pid) {
+                file.flush()?;
+                self.index_written(&path);
+            }
+        }
+        if self.flush.record(rows, time.monotonic) {
+            if self.flush.is_buffered() {
+                Exporter::flush(self)?;
+            } else {
+                self.write_buffers()?;
+            }
+        }
+        if !self.histograms.is_empty() {
+            for (pid, ph) in self.histograms.record(collector) {
+                self.write_histograms(pid, &ph)?;
+            }
         }
+        let rows = self
+            .downsamplers
+            .iter_mut()
+            .map(|ds| (ds.label(), ds.record(collector, time.wall)))
+            .collect();
+        self.write_downsampled(rows)?;
         Ok(())
     }
 }
@@ -253,7 +600,7 @@ mod test {
     use super::{CsvLineOutput, ToStr};
 
     impl ToStr for &str {
-        fn to_str(&self) -> Cow<String> {
+        fn to_str(&self) -> Cow<'_, str> {
             Cow::Owned(self.to_string())
         }
     }
@@ -265,7 +612,7 @@ mod test {
     {
         let mut buf = io::Cursor::new(Vec::<u8>::new());
         let mut lout = CsvLineOutput::new(&mut buf, ',');
-        lout.write_line(values.into_iter())?;
+        lout.write_line(values)?;
         buf.rewind()?;
         let mut line = String::new();
         buf.read_line(&mut line)?;
@@ -0,0 +1,207 @@
+// Oprs -- process monitor for Linux
+// Copyright (C) 2025  Laurent Pelecq
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Average the samples over longer intervals like RRD consolidation.
+//!
+//! The intervals are aligned on the wall clock. A row is produced when a
+//! sample falls in the next interval, when the process is gone or when the
+//! export is closed.
+
+use libc::pid_t;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    time::Duration,
+};
+
+use crate::process::{Collector, ProcessIdentity, Sample};
+
+/// Averaged values of a process over an interval.
+#[derive(Debug, PartialEq)]
+pub struct AveragedRow {
+    pub pid: pid_t,
+    pub name: String,
+    /// Start of the interval in seconds since the epoch.
+    pub time: u64,
+    /// Averages, None if the metric is not collected.
+    pub values: Vec<Option<u64>>,
+}
+
+/// Sum and number of the collected values of a process in the current interval.
+#[derive(Debug)]
+struct Bucket {
+    name: String,
+    start: u64,
+    sums: Vec<(u128, u128)>,
+}
+
+impl Bucket {
+    fn new(name: &str, start: u64) -> Self {
+        Self {
+            name: name.to_string(),
+            start,
+            sums: Vec::new(),
+        }
+    }
+
+    fn add<I>(&mut self, values: I)
+    where
+        I: IntoIterator<Item = Option<u64>>,
+    {
+        for (index, value) in values.into_iter().enumerate() {
+            if index == self.sums.len() {
+                self.sums.push((0, 0));
+            }
+            if let Some(value) = value {
+                let (sum, count) = &mut self.sums[index];
+                *sum += value as u128;
+                *count += 1;
+            }
+        }
+    }
+
+    /// Rounded averages.
+    fn into_row(self, pid: pid_t) -> AveragedRow {
+        AveragedRow {
+            pid,
+            name: self.name,
+            time: self.start,
+            values: self
+                .sums
+                .into_iter()
+                .map(|(sum, count)| (count > 0).then(|| ((sum + count / 2) / count) as u64))
+                .collect(),
+        }
+    }
+}
+
+/// Averages of all the processes over an interval.
+#[derive(Debug)]
+pub struct Downsampler {
+    interval: u64,
+    buckets: BTreeMap<pid_t, Bucket>,
+}
+
+impl Downsampler {
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval: interval.as_secs().max(1),
+            buckets: BTreeMap::new(),
+        }
+    }
+
+    /// Short name of the interval (ex: 1m, 1h).
+    pub fn label(&self) -> String {
+        const UNITS: [(u64, &str); 3] = [(86_400, "d"), (3_600, "h"), (60, "m")];
+        UNITS
+            .iter()
+            .find(|(factor, _)| self.interval.is_multiple_of(*factor))
+            .map(|(factor, unit)| format!("{}{unit}", self.interval / factor))
+            .unwrap_or_else(|| format!("{}s", self.interval))
+    }
+
+    /// Add the values of a process at a given time.
+    ///
+    /// Return the row of the previous interval if it's complete.
+    fn add<I>(&mut self, pid: pid_t, name: &str, time: Duration, values: I) -> Option<AveragedRow>
+    where
+        I: IntoIterator<Item = Option<u64>>,
+    {
+        let start = time.as_secs() - time.as_secs() % self.interval;
+        let mut row = None;
+        let bucket = self
+            .buckets
+            .entry(pid)
+            .or_insert_with(|| Bucket::new(name, start));
+        if bucket.start != start {
+            let previous = std::mem::replace(bucket, Bucket::new(name, start));
+            row = Some(previous.into_row(pid));
+        }
+        bucket.add(values);
+        row
+    }
+
+    /// Add the current values and return the rows of the complete intervals.
+    ///
+    /// The rows of the processes that are gone are also returned.
+    pub fn record(&mut self, collector: &Collector, time: Duration) -> Vec<AveragedRow> {
+        let mut gone = self.buckets.keys().copied().collect::<BTreeSet<pid_t>>();
+        let mut rows = Vec::new();
+        for pstat in collector.lines() {
+            let pid = pstat.pid();
+            gone.remove(&pid);
+            let values = pstat.samples().flat_map(Sample::collected_values);
+            if let Some(row) = self.add(pid, pstat.name(), time, values) {
+                rows.push(row);
+            }
+        }
+        rows.extend(
+            gone.iter()
+                .filter_map(|pid| self.buckets.remove(pid).map(|bucket| bucket.into_row(*pid))),
+        );
+        rows
+    }
+
+    /// Return the rows of the incomplete intervals.
+    pub fn drain(&mut self) -> Vec<AveragedRow> {
+        std::mem::take(&mut self.buckets)
+            .into_iter()
+            .map(|(pid, bucket)| bucket.into_row(pid))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::time::Duration;
+
+    use super::{AveragedRow, Downsampler};
+
+    #[test]
+    fn test_label() {
+        let label = |secs| Downsampler::new(Duration::from_secs(secs)).label();
+        assert_eq!("90s", label(90));
+        assert_eq!("1m", label(60));
+        assert_eq!("1h", label(3_600));
+        assert_eq!("2d", label(172_800));
+    }
+
+    #[test]
+    fn test_average() {
+        let mut ds = Downsampler::new(Duration::from_secs(60));
+        let at = |secs| Duration::from_secs(secs);
+        assert_eq!(None, ds.add(1, "a", at(120), [Some(10), Some(1), None]));
+        assert_eq!(None, ds.add(1, "a", at(150), [Some(20), Some(2), None]));
+        assert_eq!(None, ds.add(2, "b", at(150), [Some(5)]));
+        assert_eq!(
+            Some(AveragedRow {
+                pid: 1,
+                name: "a".to_string(),
+                time: 120,
+                values: vec![Some(15), Some(2), None],
+            }),
+            ds.add(1, "a", at(185), [Some(40), None, None])
+        );
+        let rows = ds.drain();
+        assert_eq!(2, rows.len());
+        assert_eq!(
+            (180, vec![Some(40), None, None]),
+            (rows[0].time, rows[0].values.clone())
+        );
+        assert_eq!((120, vec![Some(5)]), (rows[1].time, rows[1].values.clone()));
+        assert!(ds.drain().is_empty());
+    }
+}
@@ -0,0 +1,346 @@
+// Oprs -- process monitor for Linux
+// Copyright (C) 2025  Laurent Pelecq
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Pipe the samples in JSON to a long-running command.
+//!
+//! Each sample is a line on the standard input of the command:
+//! `{"time":MILLISECONDS,"metrics":[NAME,...],"processes":[{"pid":PID,"name":NAME,"values":[VALUE,...]},...]}`.
+//!
+//! If a metric is bounded by a resource limit, each process also has the field
+//! `"limit_breach"`: 0 below 80% of the limit, 1 near the limit and 2 at 95% or more.
+//!
+//! The command is started at the first sample, after the privileges are dropped.
+//!
+//! The input of the command is non-blocking. If the command doesn't read fast
+//! enough, the samples are dropped. If the command exits, it is started again
+//! at the next sample, after a delay that doubles each time it exits quickly.
+
+use std::{
+    io::{self, Write},
+    os::fd::AsRawFd,
+    process::{Child, ChildStdin, Command, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    cfg::ExportSettings,
+    clock::SampleTime,
+    dump::Json,
+    privileges::drop_capabilities,
+    process::{Aggregation, Collector, FormattedMetric, ProcessIdentity, Sample},
+};
+
+use super::{units::BaseUnits, Exporter, SliceIter};
+
+/// Maximum size of the samples not yet read by the command.
+const MAX_PENDING: usize = 1024 * 1024;
+
+/// Time given to the command to exit when the export is closed.
+const EXIT_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// First and last delays before starting the command again.
+const MIN_RESPAWN_DELAY: Duration = Duration::from_secs(1);
+const MAX_RESPAWN_DELAY: Duration = Duration::from_secs(60);
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("exec: missing command")]
+    MissingCommand,
+}
+
+/// Set the non-blocking flag on the input of the command.
+fn set_nonblocking(stdin: &ChildStdin) -> io::Result<()> {
+    let fd = stdin.as_raw_fd();
+    let flags = unsafe { libc::fcntl(fd, libc::F_GETFL) };
+    if flags < 0 || unsafe { libc::fcntl(fd, libc::F_SETFL, flags | libc::O_NONBLOCK) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+pub struct ExecExporter {
+    command: String,
+    metrics: Vec<String>,
+    units: BaseUnits,
+    /// Whether the breach of the resource limits is exported.
+    with_limit_breach: bool,
+    child: Option<Child>,
+    /// Data written partially.
+    pending: Vec<u8>,
+    started: bool,
+    /// When the command was last started.
+    spawned_at: Option<Instant>,
+    /// The command is not started again before this time.
+    respawn_at: Option<Instant>,
+    respawn_delay: Duration,
+}
+
+impl ExecExporter {
+    pub fn new(settings: &ExportSettings) -> anyhow::Result<Self> {
+        let command = settings.command.as_deref().ok_or(Error::MissingCommand)?;
+        let mut exporter = Self::with_command(command);
+        exporter.units = BaseUnits::new(settings);
+        Ok(exporter)
+    }
+
+    fn with_command(command: &str) -> Self {
+        Self {
+            command: command.to_string(),
+            metrics: Vec::new(),
+            units: BaseUnits::default(),
+            with_limit_breach: false,
+            child: None,
+            pending: Vec::new(),
+            started: false,
+            spawned_at: None,
+            respawn_at: None,
+            respawn_delay: MIN_RESPAWN_DELAY,
+        }
+    }
+
+    /// Delay the next start, the delay is reset if the command ran long enough.
+    fn delay_respawn(&mut self) {
+        let now = Instant::now();
+        if self
+            .spawned_at
+            .is_some_and(|spawned_at| now.duration_since(spawned_at) >= MAX_RESPAWN_DELAY)
+        {
+            self.respawn_delay = MIN_RESPAWN_DELAY;
+        }
+        self.respawn_at = Some(now + self.respawn_delay);
+        self.respawn_delay = (self.respawn_delay * 2).min(MAX_RESPAWN_DELAY);
+    }
+
+    fn spawn(&mut self) -> io::Result<()> {
+        if self.started {
+            log::warn!("{}: restarting command", self.command);
+        }
+        self.started = true;
+        self.spawned_at = Some(Instant::now());
+        let child = drop_capabilities(
+            Command::new("sh")
+                .arg("-c")
+                .arg(&self.command)
+                .stdin(Stdio::piped())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null()),
+        )
+        .spawn()?;
+        if let Some(ref stdin) = child.stdin {
+            set_nonblocking(stdin)?;
+        }
+        self.child = Some(child);
+        self.pending.clear();
+        Ok(())
+    }
+
+    /// Forget the command if it has exited.
+    fn check_exit(&mut self) -> io::Result<()> {
+        if let Some(ref mut child) = self.child {
+            if let Some(status) = child.try_wait()? {
+                log::warn!("{}: {status}", self.command);
+                self.child = None;
+                self.delay_respawn();
+            }
+        }
+        Ok(())
+    }
+
+    /// Write as much pending data as possible.
+    fn write_pending(&mut self) -> io::Result<()> {
+        let Some(stdin) = self.child.as_mut().and_then(|child| child.stdin.as_mut()) else {
+            return Ok(());
+        };
+        while !self.pending.is_empty() {
+            match stdin.write(&self.pending) {
+                Ok(0) => break,
+                Ok(count) => {
+                    self.pending.drain(..count);
+                }
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => (),
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(())
+    }
+
+    /// Send a line to the command.
+    fn send(&mut self, line: String) -> io::Result<()> {
+        self.check_exit()?;
+        if self.child.is_none() {
+            if self
+                .respawn_at
+                .is_some_and(|respawn_at| Instant::now() < respawn_at)
+            {
+                return Ok(());
+            }
+            if let Err(err) = self.spawn() {
+                self.delay_respawn();
+                return Err(err);
+            }
+        }
+        if self.pending.len() + line.len() > MAX_PENDING {
+            log::warn!("{}: command is too slow, sample dropped", self.command);
+        } else {
+            self.pending.extend_from_slice(line.as_bytes());
+        }
+        if let Err(err) = self.write_pending() {
+            log::warn!("{}: {err}", self.command);
+            self.stop();
+            self.delay_respawn();
+        }
+        Ok(())
+    }
+
+    /// Close the input of the command and reap it in the background.
+    ///
+    /// The command is killed if it doesn't exit in time.
+    fn stop(&mut self) {
+        if let Some(mut child) = self.child.take() {
+            drop(child.stdin.take());
+            if let Ok(None) = child.try_wait() {
+                thread::spawn(move || {
+                    let deadline = Instant::now() + EXIT_TIMEOUT;
+                    while let Ok(None) = child.try_wait() {
+                        if Instant::now() >= deadline {
+                            let _ = child.kill();
+                            let _ = child.wait();
+                            break;
+                        }
+                        thread::sleep(Duration::from_millis(10));
+                    }
+                });
+            }
+        }
+        self.pending.clear();
+    }
+
+    /// The sample in JSON.
+    fn sample(&self, collector: &Collector, time: &SampleTime) -> Json {
+        let processes = collector
+            .lines()
+            .map(|pstat| {
+                let mut fields = vec![
+                    ("pid", pstat.pid().into()),
+                    ("name", pstat.name().into()),
+                    (
+                        "values",
+                        self.units
+                            .json(pstat.samples().flat_map(Sample::collected_values)),
+                    ),
+                ];
+                if self.with_limit_breach {
+                    fields.push(("limit_breach", pstat.limit_breach().level().into()));
+                }
+                Json::Object(fields)
+            })
+            .collect();
+        Json::Object(vec![
+            ("time", (time.wall.as_millis() as u64).into()),
+            (
+                "metrics",
+                Json::array(self.metrics.iter().map(String::as_str)),
+            ),
+            ("processes", Json::Array(processes)),
+        ])
+    }
+}
+
+impl Exporter for ExecExporter {
+    fn open(&mut self, metrics: SliceIter<FormattedMetric>) -> anyhow::Result<()> {
+        self.with_limit_breach = metrics.clone().any(FormattedMetric::has_limit);
+        self.units.open(metrics.clone());
+        Collector::for_each_computed_metric(metrics, |metric, ag| {
+            self.metrics.push(match ag {
+                Aggregation::None => metric.name().to_string(),
+                Aggregation::Min => format!("{}+min", metric.name()),
+                Aggregation::Max => format!("{}+max", metric.name()),
+                Aggregation::Ratio => format!("{}+ratio", metric.name()),
+            })
+        });
+        Ok(())
+    }
+
+    fn close(&mut self) -> anyhow::Result<()> {
+        let _ = self.write_pending();
+        self.stop();
+        Ok(())
+    }
+
+    fn export(&mut self, collector: &Collector, time: &SampleTime) -> anyhow::Result<()> {
+        let line = format!("{}\n", self.sample(collector, time));
+        self.send(line)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::{borrow::Cow, fs, time::Duration};
+
+    use super::{ExecExporter, Exporter};
+    use crate::{
+        clock::SampleTime,
+        process::{Collector, MetricNamesParser},
+    };
+
+    #[test]
+    fn test_exec_exporter() -> anyhow::Result<()> {
+        let path = std::env::temp_dir().join(format!("oprs-test-exec-{}", std::process::id()));
+        let metrics = MetricNamesParser::new(false).parse(&["fd:all+max"])?;
+        let mut collector = Collector::new(Cow::Borrowed(&metrics));
+        collector.record_pseudo("a \"b\"", -2, &[7]);
+        let time = SampleTime {
+            monotonic: Duration::from_secs(1),
+            wall: Duration::from_millis(1_700_000_000_123),
+        };
+        let mut exporter = ExecExporter::with_command(&format!("cat > {}", path.display()));
+        exporter.open(metrics.iter())?;
+        exporter.export(&collector, &time)?;
+        let mut child = exporter.child.take().unwrap();
+        drop(child.stdin.take());
+        child.wait()?;
+        exporter.close()?;
+        let output = fs::read_to_string(&path)?;
+        fs::remove_file(&path)?;
+        assert_eq!(
+            r#"{"time":1700000000123,"metrics":["fd:all","fd:all+max"],"processes":[{"pid":-2,"name":"a \"b\"","values":[7,7],"limit_breach":0}]}
+"#,
+            output
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_respawn_delay() -> anyhow::Result<()> {
+        let metrics = MetricNamesParser::new(false).parse(&["fd:all"])?;
+        let collector = Collector::new(Cow::Borrowed(&metrics));
+        let time = SampleTime::default();
+        let mut exporter = ExecExporter::with_command("read line");
+        exporter.open(metrics.iter())?;
+        assert!(exporter.child.is_none());
+        exporter.export(&collector, &time)?;
+        exporter.child.as_mut().unwrap().wait()?;
+        exporter.export(&collector, &time)?;
+        assert!(exporter.child.is_none());
+        assert!(exporter.respawn_at.is_some());
+        assert_eq!(Duration::from_secs(2), exporter.respawn_delay);
+        exporter.close()
+    }
+}
@@ -0,0 +1,100 @@
+// Oprs -- process monitor for Linux
+// Copyright (C) 2025  Laurent Pelecq
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Decide when the rows buffered by the exporters are written to the disk.
+//!
+//! Without interval nor row count, the rows are written at each sample but not
+//! synchronized. Otherwise, they are kept in memory and synchronized when the
+//! interval has elapsed or enough rows are pending.
+
+use std::time::Duration;
+
+use crate::cfg::ExportSettings;
+
+/// Flush policy of the export files.
+#[derive(Debug)]
+pub struct FlushPolicy {
+    interval: Option<Duration>,
+    rows: Option<usize>,
+    /// Time of the last flush.
+    last: Option<Duration>,
+    /// Rows written since the last flush.
+    pending: usize,
+}
+
+impl FlushPolicy {
+    pub fn new(settings: &ExportSettings) -> Self {
+        Self::with_limits(settings.flush, settings.flush_rows)
+    }
+
+    fn with_limits(interval: Option<Duration>, rows: Option<usize>) -> Self {
+        Self {
+            interval,
+            rows,
+            last: None,
+            pending: 0,
+        }
+    }
+
+    /// Whether the rows are kept in memory between two flushes.
+    pub fn is_buffered(&self) -> bool {
+        self.interval.is_some() || self.rows.is_some()
+    }
+
+    /// Record the rows written at a given time and tell if they must be flushed.
+    ///
+    /// The interval starts at the first sample.
+    pub fn record(&mut self, rows: usize, now: Duration) -> bool {
+        self.pending += rows;
+        let last = *self.last.get_or_insert(now);
+        let due = match (self.interval, self.rows) {
+            (None, None) => true,
+            (interval, rows) => {
+                interval.is_some_and(|interval| now.saturating_sub(last) >= interval)
+                    || rows.is_some_and(|rows| self.pending >= rows)
+            }
+        };
+        if due {
+            self.last = Some(now);
+            self.pending = 0;
+        }
+        due
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::time::Duration;
+
+    use super::FlushPolicy;
+
+    #[test]
+    fn test_flush_policy() {
+        let secs = Duration::from_secs;
+        let mut policy = FlushPolicy::with_limits(None, None);
+        assert!(!policy.is_buffered());
+        assert!(policy.record(1, secs(0)));
+
+        let mut policy = FlushPolicy::with_limits(Some(secs(10)), Some(5));
+        assert!(policy.is_buffered());
+        assert!(!policy.record(2, secs(0)));
+        assert!(!policy.record(2, secs(5)));
+        assert!(policy.record(2, secs(6))); // 6 rows
+        assert!(!policy.record(1, secs(10)));
+        assert!(policy.record(1, secs(16))); // 10s elapsed
+    }
+}
@@ -0,0 +1,142 @@
+// Oprs -- process monitor for Linux
+// Copyright (C) 2025  Laurent Pelecq
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Snapshot of a process to preserve the evidence before it is killed.
+
+use chrono::{DateTime, Local};
+use libc::pid_t;
+use std::{
+    fs::{self, DirBuilder, File, OpenOptions},
+    io::{self, Write},
+    os::unix::fs::{DirBuilderExt, OpenOptionsExt},
+    path::{Path, PathBuf},
+};
+
+use super::naming::FileNameTemplate;
+
+/// Files of /proc/PID copied as is in the bundle.
+const PROC_FILES: &[&str] = &["cmdline", "environ", "limits", "maps", "status"];
+
+/// Name of the file listing the open files.
+const FD_FILE: &str = "fd";
+
+/// The bundle may contain secrets, only the owner can read it.
+const DIR_MODE: u32 = 0o700;
+const FILE_MODE: u32 = 0o600;
+
+/// Create a file only readable by the owner.
+fn create_private(path: &Path) -> io::Result<File> {
+    OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(FILE_MODE)
+        .open(path)
+}
+
+/// Copy the environment, the open files and the memory maps of a process in a
+/// timestamped directory.
+///
+/// Return the path of the directory.
+pub fn write_bundle(dir: &Path, pid: pid_t, name: &str) -> io::Result<PathBuf> {
+    write_bundle_from(Path::new("/proc"), dir, pid, name, Local::now())
+}
+
+fn write_bundle_from(
+    proc_root: &Path,
+    dir: &Path,
+    pid: pid_t,
+    name: &str,
+    now: DateTime<Local>,
+) -> io::Result<PathBuf> {
+    let proc_dir = proc_root.join(pid.to_string());
+    let bundle = dir.join(format!(
+        "{}_{pid}_{}",
+        FileNameTemplate::sanitize(name),
+        now.format("%Y%m%dT%H%M%S")
+    ));
+    DirBuilder::new()
+        .recursive(true)
+        .mode(DIR_MODE)
+        .create(&bundle)?;
+    for filename in PROC_FILES {
+        match fs::read(proc_dir.join(filename)) {
+            Ok(content) => create_private(&bundle.join(filename))?.write_all(&content)?,
+            Err(err) => log::warn!("{pid}: cannot read {filename}: {err}"),
+        }
+    }
+    let mut fds = fs::read_dir(proc_dir.join(FD_FILE))?
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let fd = entry.file_name().to_str()?.parse::<u32>().ok()?;
+            Some((fd, fs::read_link(entry.path()).ok()?))
+        })
+        .collect::<Vec<(u32, PathBuf)>>();
+    fds.sort();
+    let mut out = create_private(&bundle.join(FD_FILE))?;
+    for (fd, target) in fds {
+        writeln!(out, "{fd}\t{}", target.display())?;
+    }
+    Ok(bundle)
+}
+
+#[cfg(test)]
+mod test {
+
+    use chrono::{Local, TimeZone};
+    use std::{
+        fs, io,
+        os::unix::fs::{symlink, PermissionsExt},
+    };
+
+    use super::write_bundle_from;
+
+    #[test]
+    fn test_write_bundle() -> io::Result<()> {
+        let root = std::env::temp_dir().join(format!("oprs-forensics-{}", std::process::id()));
+        let proc_dir = root.join("proc").join("42");
+        fs::create_dir_all(proc_dir.join("fd"))?;
+        fs::write(proc_dir.join("environ"), b"HOME=/root\0PATH=/bin\0")?;
+        fs::write(
+            proc_dir.join("maps"),
+            b"00400000-00452000 r-xp 00000000 08:02 173521 /bin/sh\n",
+        )?;
+        symlink("/dev/null", proc_dir.join("fd").join("10"))?;
+        symlink("socket:[1234]", proc_dir.join("fd").join("2"))?;
+
+        let now = Local.with_ymd_and_hms(2025, 3, 4, 5, 6, 7).unwrap();
+        let bundle = write_bundle_from(&root.join("proc"), &root, 42, "sh", now)?;
+        assert_eq!(root.join("sh_42_20250304T050607"), bundle);
+        assert_eq!(
+            b"HOME=/root\0PATH=/bin\0".as_slice(),
+            fs::read(bundle.join("environ"))?
+        );
+        assert!(bundle.join("maps").exists());
+        assert!(!bundle.join("status").exists());
+        assert_eq!(0o700, fs::metadata(&bundle)?.permissions().mode() & 0o777);
+        assert_eq!(
+            0o600,
+            fs::metadata(bundle.join("environ"))?.permissions().mode() & 0o777
+        );
+        assert_eq!(
+            "2\tsocket:[1234]\n10\t/dev/null\n",
+            fs::read_to_string(bundle.join("fd"))?
+        );
+        let bundle = write_bundle_from(&root.join("proc"), &root, 42, "../sh", now)?;
+        assert_eq!(root.join(".._sh_42_20250304T050607"), bundle);
+        fs::remove_dir_all(root)
+    }
+}
@@ -0,0 +1,188 @@
+// Oprs -- process monitor for Linux
+// Copyright (C) 2025  Laurent Pelecq
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use libc::pid_t;
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::process::{
+    counter_increase, parsers::HistogramBounds, Collector, FormattedMetric, MetricDataType,
+    MetricId, ProcessIdentity, Sample,
+};
+
+use super::SliceIter;
+
+/// Number of values in buckets.
+///
+/// The last bucket counts the values above the last bound.
+#[derive(Debug, Clone)]
+pub struct Histogram {
+    bounds: Vec<u64>,
+    counts: Vec<u64>,
+}
+
+impl Histogram {
+    pub fn new(bounds: &[u64]) -> Self {
+        Self {
+            bounds: bounds.to_vec(),
+            counts: vec![0; bounds.len() + 1],
+        }
+    }
+
+    /// Count a value in the first bucket whose bound is greater or equal.
+    pub fn add(&mut self, value: u64) {
+        let index = self.bounds.partition_point(|bound| *bound < value);
+        self.counts[index] += 1;
+    }
+
+    /// Upper bound and count of each bucket. The last bucket has no bound.
+    pub fn buckets(&self) -> impl Iterator<Item = (Option<u64>, u64)> + '_ {
+        self.bounds
+            .iter()
+            .map(|bound| Some(*bound))
+            .chain(std::iter::once(None))
+            .zip(self.counts.iter().copied())
+    }
+}
+
+/// Histogram of a metric for a process.
+#[derive(Debug)]
+struct MetricHistogram {
+    /// Index of the metric in the collector.
+    index: usize,
+    id: MetricId,
+    /// For counters, the last value to compute the increase.
+    last_value: Option<u64>,
+    histogram: Histogram,
+}
+
+/// Histograms of a process.
+#[derive(Debug)]
+pub struct ProcessHistograms {
+    name: String,
+    histograms: Vec<MetricHistogram>,
+}
+
+impl ProcessHistograms {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Metric name and histogram.
+    pub fn iter(&self) -> impl Iterator<Item = (&'static str, &Histogram)> {
+        self.histograms
+            .iter()
+            .map(|mh| (mh.id.as_str(), &mh.histogram))
+    }
+}
+
+/// Histograms of selected metrics for each process.
+///
+/// For gauges, the values are counted. For counters, the increase between two samples.
+#[derive(Debug, Default)]
+pub struct HistogramCollector {
+    /// Index of the metric, identifier and bounds.
+    metrics: Vec<(usize, MetricId, Vec<u64>)>,
+    processes: BTreeMap<pid_t, ProcessHistograms>,
+}
+
+impl HistogramCollector {
+    pub fn new(specs: &[HistogramBounds], metrics: SliceIter<FormattedMetric>) -> Self {
+        let metrics = metrics.map(|metric| metric.id).collect::<Vec<MetricId>>();
+        let metrics = specs
+            .iter()
+            .filter_map(|spec| match metrics.iter().position(|id| *id == spec.id) {
+                Some(index) => Some((index, spec.id, spec.bounds.clone())),
+                None => {
+                    log::warn!("{}: histogram of a metric not collected", spec.id.as_str());
+                    None
+                }
+            })
+            .collect();
+        Self {
+            metrics,
+            processes: BTreeMap::new(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.metrics.is_empty()
+    }
+
+    /// Count the current values and return the histograms of the processes that are gone.
+    pub fn record(&mut self, collector: &Collector) -> Vec<(pid_t, ProcessHistograms)> {
+        let mut gone = self.processes.keys().copied().collect::<BTreeSet<pid_t>>();
+        for pstat in collector.lines() {
+            let pid = pstat.pid();
+            gone.remove(&pid);
+            let ph = self
+                .processes
+                .entry(pid)
+                .or_insert_with(|| ProcessHistograms {
+                    name: pstat.name().to_string(),
+                    histograms: self
+                        .metrics
+                        .iter()
+                        .map(|(index, id, bounds)| MetricHistogram {
+                            index: *index,
+                            id: *id,
+                            last_value: None,
+                            histogram: Histogram::new(bounds),
+                        })
+                        .collect(),
+                });
+            let samples = pstat.samples_as_slice();
+            for mh in ph.histograms.iter_mut() {
+                if let Some(value) = samples.get(mh.index).and_then(Sample::collected_raw) {
+                    match mh.id.data_type() {
+                        MetricDataType::Gauge => mh.histogram.add(value),
+                        MetricDataType::Counter => {
+                            if let Some(last_value) = mh.last_value {
+                                mh.histogram.add(counter_increase(last_value, value));
+                            }
+                            mh.last_value = Some(value);
+                        }
+                    }
+                }
+            }
+        }
+        gone.iter()
+            .filter_map(|pid| self.processes.remove(pid).map(|ph| (*pid, ph)))
+            .collect()
+    }
+
+    /// Return all the histograms.
+    pub fn drain(&mut self) -> Vec<(pid_t, ProcessHistograms)> {
+        std::mem::take(&mut self.processes).into_iter().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::Histogram;
+
+    #[test]
+    fn test_histogram() {
+        let mut histogram = Histogram::new(&[10, 100]);
+        for value in [0, 10, 11, 100, 101, 1000] {
+            histogram.add(value);
+        }
+        assert_eq!(
+            vec![(Some(10), 2), (Some(100), 2), (None, 2)],
+            histogram.buckets().collect::<Vec<(Option<u64>, u64)>>()
+        );
+    }
+}
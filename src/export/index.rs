@@ -0,0 +1,279 @@
+// Oprs -- process monitor for Linux
+// Copyright (C) 2025  Laurent Pelecq
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Index of the rotated CSV files.
+//!
+//! The index is a JSON file in the export directory that maps the time range
+//! of each file to its name, so that the file containing a given time can be
+//! found without reading all the rotated files.
+//!
+//! The files left by previous runs are indexed from their first rows and their
+//! end only, assuming that the rows are in time order.
+
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    io::{self, BufRead, BufReader, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use crate::dump::Json;
+
+/// Name of the index file in the export directory.
+pub const INDEX_FILE_NAME: &str = "index.json";
+
+/// Size of the end of a file read to find the last row.
+const TAIL_SIZE: u64 = 64 * 1024;
+
+/// Time of a row or None if the line doesn't start with a time.
+fn parse_time(line: &str, separator: char) -> Option<Duration> {
+    line.split(separator)
+        .next()
+        .and_then(|value| value.parse::<f64>().ok())
+        .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+}
+
+/// Seconds since the Epoch of the first and last rows of a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct TimeRange {
+    start: u64,
+    end: u64,
+}
+
+impl TimeRange {
+    fn new(time: Duration) -> Self {
+        Self {
+            start: time.as_secs(),
+            end: TimeRange::end_of(time),
+        }
+    }
+
+    /// Round up so that the range includes the time.
+    fn end_of(time: Duration) -> u64 {
+        time.as_secs() + u64::from(time.subsec_nanos() > 0)
+    }
+
+    /// Extend the range to include the time.
+    fn extend(&mut self, time: Duration) {
+        self.start = self.start.min(time.as_secs());
+        self.end = self.end.max(TimeRange::end_of(time));
+    }
+
+    /// Range of the rows of a file from the first row and the last one.
+    ///
+    /// Only the beginning and the end of the file are read. The lines that
+    /// don't start with a time are ignored.
+    fn read<R: BufRead + Seek>(mut reader: R, separator: char) -> io::Result<Option<TimeRange>> {
+        let mut line = String::new();
+        let first = loop {
+            line.clear();
+            if reader.read_line(&mut line)? == 0 {
+                return Ok(None);
+            }
+            if let Some(time) = parse_time(line.trim_end(), separator) {
+                break time;
+            }
+        };
+        let position = reader.stream_position()?;
+        let length = reader.seek(SeekFrom::End(0))?;
+        // Start one byte earlier to know if the first line is complete.
+        let offset = length.saturating_sub(TAIL_SIZE).max(position).max(1) - 1;
+        reader.seek(SeekFrom::Start(offset))?;
+        let mut tail = Vec::new();
+        reader.read_to_end(&mut tail)?;
+        let tail = String::from_utf8_lossy(&tail);
+        let tail = tail.split_once('\n').map_or("", |(_, rest)| rest);
+        let mut range = TimeRange::new(first);
+        if let Some(last) = tail
+            .lines()
+            .rev()
+            .find_map(|line| parse_time(line, separator))
+        {
+            range.extend(last);
+        }
+        Ok(Some(range))
+    }
+}
+
+/// Time ranges of the files by path relative to the export directory.
+#[derive(Debug)]
+pub struct RotationIndex {
+    dir: PathBuf,
+    separator: char,
+    files: BTreeMap<PathBuf, TimeRange>,
+    /// Whether the index must be written.
+    modified: bool,
+}
+
+impl RotationIndex {
+    pub fn new(dir: &Path, separator: char) -> Self {
+        Self {
+            dir: dir.to_path_buf(),
+            separator,
+            files: BTreeMap::new(),
+            modified: false,
+        }
+    }
+
+    fn relative<'a>(&self, path: &'a Path) -> &'a Path {
+        path.strip_prefix(&self.dir).unwrap_or(path)
+    }
+
+    /// Add an existing file reading the time range of its rows.
+    pub fn load(&mut self, path: &Path) -> io::Result<()> {
+        let range = TimeRange::read(BufReader::new(File::open(path)?), self.separator)?;
+        let name = self.relative(path).to_path_buf();
+        match range {
+            Some(range) => self.files.insert(name, range),
+            None => self.files.remove(&name),
+        };
+        self.modified = true;
+        Ok(())
+    }
+
+    /// Whether the file is in the index.
+    pub fn contains(&self, path: &Path) -> bool {
+        self.files.contains_key(self.relative(path))
+    }
+
+    /// Record a row written in a file at the given time.
+    pub fn record(&mut self, path: &Path, time: Duration) {
+        let name = self.relative(path);
+        match self.files.get_mut(name) {
+            Some(range) => range.extend(time),
+            None => {
+                self.files.insert(name.to_path_buf(), TimeRange::new(time));
+                self.modified = true;
+            }
+        }
+    }
+
+    /// Follow a file that is renamed, replacing the destination if any.
+    pub fn rename(&mut self, source: &Path, destination: &Path) {
+        let destination = self.relative(destination).to_path_buf();
+        match self.files.remove(self.relative(source)) {
+            Some(range) => self.files.insert(destination, range),
+            None => self.files.remove(&destination),
+        };
+        self.modified = true;
+    }
+
+    /// Forget a file that is replaced.
+    pub fn remove(&mut self, path: &Path) {
+        self.modified |= self.files.remove(self.relative(path)).is_some();
+    }
+
+    /// Files ordered by start time.
+    fn to_json(&self) -> Json {
+        let mut files = self.files.iter().collect::<Vec<_>>();
+        files.sort_by_key(|(name, range)| (range.start, range.end, *name));
+        let files = files
+            .into_iter()
+            .map(|(name, range)| {
+                Json::Object(vec![
+                    ("file", Json::from(name.to_string_lossy().to_string())),
+                    ("start", Json::from(range.start)),
+                    ("end", Json::from(range.end)),
+                ])
+            })
+            .collect();
+        Json::Object(vec![("files", Json::Array(files))])
+    }
+
+    /// Write the index with the last end times.
+    ///
+    /// The file is replaced atomically so that readers never see a partial index.
+    pub fn write(&mut self) -> io::Result<()> {
+        if self.files.is_empty() && !self.modified {
+            return Ok(());
+        }
+        fs::create_dir_all(&self.dir)?;
+        let path = self.dir.join(INDEX_FILE_NAME);
+        let tmp_path = self.dir.join(format!(".{INDEX_FILE_NAME}.tmp"));
+        let mut file = File::create(&tmp_path)?;
+        writeln!(file, "{}", self.to_json())?;
+        file.sync_all()?;
+        fs::rename(tmp_path, path)?;
+        self.modified = false;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::{
+        io::{self, Cursor},
+        path::Path,
+        time::Duration,
+    };
+
+    use super::{RotationIndex, TimeRange};
+
+    #[test]
+    fn test_read_range() -> io::Result<()> {
+        let content = "# oprs-export format=1\ntime,mem:rss\n100.250,1\n160.000,2\n220.500,3\n";
+        let expected = TimeRange {
+            start: 100,
+            end: 221,
+        };
+        assert_eq!(Some(expected), TimeRange::read(Cursor::new(content), ',')?);
+        assert_eq!(None, TimeRange::read(Cursor::new("time,mem:rss\n"), ',')?);
+        let single = TimeRange {
+            start: 100,
+            end: 101,
+        };
+        assert_eq!(
+            Some(single),
+            TimeRange::read(Cursor::new("time,mem:rss\n100.250,1\n"), ',')?
+        );
+        // Only the end of a long file is read.
+        let mut content = String::from("time,mem:rss\n");
+        for secs in 100..10_000 {
+            content.push_str(&format!("{secs}.000,1234567890\n"));
+        }
+        let expected = TimeRange {
+            start: 100,
+            end: 9_999,
+        };
+        assert_eq!(Some(expected), TimeRange::read(Cursor::new(content), ',')?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_rotation() {
+        let dir = Path::new("/tmp/export");
+        let mut index = RotationIndex::new(dir, ',');
+        index.record(&dir.join("sh_1.csv"), Duration::from_secs(10));
+        index.record(&dir.join("sh_1.csv"), Duration::from_millis(20_500));
+        // Rotation: sh_1.csv -> sh_1.csv.1
+        index.rename(&dir.join("sh_1.csv"), &dir.join("sh_1.csv.1"));
+        index.record(&dir.join("sh_1.csv"), Duration::from_secs(30));
+        assert!(index.contains(&dir.join("sh_1.csv.1")));
+        assert_eq!(
+            r#"{"files":[{"file":"sh_1.csv.1","start":10,"end":21},{"file":"sh_1.csv","start":30,"end":30}]}"#,
+            index.to_json().to_string()
+        );
+        // The oldest file is overwritten.
+        index.rename(&dir.join("sh_1.csv"), &dir.join("sh_1.csv.1"));
+        index.remove(&dir.join("sh_1.csv"));
+        assert_eq!(
+            r#"{"files":[{"file":"sh_1.csv.1","start":30,"end":30}]}"#,
+            index.to_json().to_string()
+        );
+    }
+}
@@ -14,13 +14,34 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
-use std::{slice::Iter as SliceIter, time::Duration};
+use std::slice::Iter as SliceIter;
 
-use crate::process::{Collector, FormattedMetric};
+use crate::{
+    clock::SampleTime,
+    process::{Collector, FormattedMetric, StateEvent},
+};
 
+mod broadcast;
+mod capture;
 mod csv;
+mod downsample;
+mod exec;
+mod flush;
+mod forensics;
+mod histogram;
+mod index;
+mod nagios;
+mod naming;
+mod preview;
+mod retention;
+#[cfg(feature = "rrd")]
 mod rrd;
+#[cfg(feature = "rrd")]
 mod rrdtool;
+mod schema;
+mod session;
+mod statsd;
+mod units;
 
 pub trait Exporter {
     /// Initialize the exporter with the metrics.
@@ -29,8 +50,32 @@ pub trait Exporter {
     /// Terminate exporting.
     fn close(&mut self) -> anyhow::Result<()>;
 
-    /// Export the current metrics.
-    fn export(&mut self, collector: &Collector, timestamp: &Duration) -> anyhow::Result<()>;
+    /// Write buffered data.
+    fn flush(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    /// Export the current metrics collected at the given time.
+    fn export(&mut self, collector: &Collector, time: &SampleTime) -> anyhow::Result<()>;
+
+    /// Export a change of state of a process.
+    fn event(&mut self, _: &StateEvent, _: &SampleTime) -> anyhow::Result<()> {
+        Ok(())
+    }
 }
 
-pub use crate::export::{csv::CsvExporter, rrd::RrdExporter};
+pub use crate::export::{
+    broadcast::{follow, Broadcaster},
+    capture::ProcfsCapture,
+    csv::CsvExporter,
+    exec::ExecExporter,
+    forensics::write_bundle,
+    nagios::{CheckStatus, NagiosExporter},
+    naming::FileNameTemplate,
+    preview::Preview,
+    session::{clear_truncated, mark_truncated},
+    statsd::StatsdExporter,
+};
+
+#[cfg(feature = "rrd")]
+pub use crate::export::rrd::RrdExporter;
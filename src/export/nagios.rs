@@ -0,0 +1,201 @@
+// Oprs -- process monitor for Linux
+// Copyright (C) 2025  Laurent Pelecq
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use strum_macros::IntoStaticStr;
+
+use crate::{
+    clock::SampleTime,
+    process::{parsers::Threshold, Collector, FormattedMetric, MetricId, ProcessIdentity, Sample},
+};
+
+use super::{Exporter, SliceIter};
+
+/// Status of a check with its exit code in the Nagios plugin API.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, IntoStaticStr)]
+pub enum CheckStatus {
+    #[strum(serialize = "OK")]
+    Ok = 0,
+    #[strum(serialize = "WARNING")]
+    Warning = 1,
+    #[strum(serialize = "CRITICAL")]
+    Critical = 2,
+    #[strum(serialize = "UNKNOWN")]
+    Unknown = 3,
+}
+
+impl CheckStatus {
+    pub fn as_str(self) -> &'static str {
+        self.into()
+    }
+
+    pub fn exit_code(self) -> i32 {
+        self as i32
+    }
+}
+
+/// Range of a threshold in the performance data.
+fn perfdata_range(threshold: Option<&Threshold>) -> String {
+    match threshold {
+        Some(threshold) if threshold.above => threshold.limit.to_string(),
+        Some(threshold) => format!("{}:", threshold.limit),
+        None => String::new(),
+    }
+}
+
+/// Check the raw values of metrics against warning and critical thresholds.
+///
+/// Only the last exported sample is checked.
+pub struct NagiosExporter {
+    thresholds: Vec<(CheckStatus, Threshold)>,
+    /// Index in the collector and identifier of the metrics.
+    metrics: Vec<(usize, MetricId)>,
+    status: CheckStatus,
+    alerts: Vec<String>,
+    perfdata: Vec<String>,
+    process_count: usize,
+}
+
+impl NagiosExporter {
+    pub fn new(warnings: &[Threshold], criticals: &[Threshold]) -> Self {
+        let thresholds = warnings
+            .iter()
+            .map(|threshold| (CheckStatus::Warning, threshold.clone()))
+            .chain(
+                criticals
+                    .iter()
+                    .map(|threshold| (CheckStatus::Critical, threshold.clone())),
+            )
+            .collect();
+        Self {
+            thresholds,
+            metrics: Vec::new(),
+            status: CheckStatus::Unknown,
+            alerts: Vec::new(),
+            perfdata: Vec::new(),
+            process_count: 0,
+        }
+    }
+
+    /// Metrics to collect.
+    pub fn metric_names(&self) -> Vec<&'static str> {
+        let mut names = Vec::new();
+        self.thresholds.iter().for_each(|(_, threshold)| {
+            let name = threshold.id.as_str();
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        });
+        names
+    }
+
+    fn threshold(&self, status: CheckStatus, id: MetricId) -> Option<&Threshold> {
+        self.thresholds
+            .iter()
+            .find(|(st, threshold)| *st == status && threshold.id == id)
+            .map(|(_, threshold)| threshold)
+    }
+
+    pub fn status(&self) -> CheckStatus {
+        self.status
+    }
+
+    /// Plugin output: status, summary and performance data.
+    pub fn report(&self) -> String {
+        let summary = match (self.status, self.process_count) {
+            (_, 0) => String::from("no process found"),
+            (CheckStatus::Ok, 1) => String::from("1 process"),
+            (CheckStatus::Ok, count) => format!("{count} processes"),
+            _ => self.alerts.join(", "),
+        };
+        let status = self.status.as_str();
+        if self.perfdata.is_empty() {
+            format!("OPRS {status} - {summary}")
+        } else {
+            format!("OPRS {status} - {summary} | {}", self.perfdata.join(" "))
+        }
+    }
+}
+
+impl Exporter for NagiosExporter {
+    fn open(&mut self, metrics: SliceIter<FormattedMetric>) -> anyhow::Result<()> {
+        self.metrics = metrics
+            .enumerate()
+            .map(|(index, metric)| (index, metric.id))
+            .collect();
+        Ok(())
+    }
+
+    fn close(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn export(&mut self, collector: &Collector, _: &SampleTime) -> anyhow::Result<()> {
+        self.status = CheckStatus::Ok;
+        self.alerts.clear();
+        self.perfdata.clear();
+        self.process_count = 0;
+        for pstat in collector.lines().filter(|pstat| pstat.pid() != 0) {
+            self.process_count += 1;
+            let samples = pstat.samples_as_slice();
+            for (index, id) in &self.metrics {
+                let Some(value) = samples.get(*index).and_then(Sample::collected_raw) else {
+                    continue;
+                };
+                let process = format!("{}[{}]", pstat.name(), pstat.pid());
+                for (status, threshold) in self.thresholds.iter().rev() {
+                    if threshold.id == *id && threshold.exceeded(value) {
+                        let op = if threshold.above { '>' } else { '<' };
+                        self.alerts
+                            .push(format!("{process} {id} {value}{op}{}", threshold.limit));
+                        self.status = self.status.max(*status);
+                        break; // critical thresholds are checked first
+                    }
+                }
+                self.perfdata.push(format!(
+                    "'{process} {id}'={value};{};{}",
+                    perfdata_range(self.threshold(CheckStatus::Warning, *id)),
+                    perfdata_range(self.threshold(CheckStatus::Critical, *id)),
+                ));
+            }
+        }
+        if self.process_count == 0 {
+            self.status = CheckStatus::Unknown;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::{perfdata_range, CheckStatus};
+    use crate::process::parsers::parse_threshold;
+
+    #[test]
+    fn test_perfdata_range() {
+        assert_eq!(
+            "100",
+            perfdata_range(parse_threshold("fd:all>100").ok().as_ref())
+        );
+        assert_eq!(
+            "10:",
+            perfdata_range(parse_threshold("fd:all<10").ok().as_ref())
+        );
+        assert_eq!("", perfdata_range(None));
+        assert!(CheckStatus::Critical > CheckStatus::Warning);
+        assert_eq!(2, CheckStatus::Critical.exit_code());
+    }
+}
@@ -0,0 +1,282 @@
+// Oprs -- process monitor for Linux
+// Copyright (C) 2025  Laurent Pelecq
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Names of the export files built from a template such as `{hostname}/{name}-{pid}`.
+//!
+//! The values are sanitized so that a process name can't escape the export directory.
+
+use chrono::{DateTime, Local};
+use libc::pid_t;
+use std::{
+    fmt, fs,
+    path::{Component, Path, PathBuf},
+    str::FromStr,
+};
+use strum_macros::EnumString;
+
+/// File containing the name of the host.
+const HOSTNAME_FILE: &str = "/proc/sys/kernel/hostname";
+
+#[derive(thiserror::Error, Debug, PartialEq)]
+pub enum TemplateError {
+    #[error("{0}: unknown field in file name template")]
+    UnknownField(String),
+    #[error("{0}: unbalanced braces in file name template")]
+    UnbalancedBraces(String),
+    #[error("{0}: file name template must be a relative path in the export directory")]
+    InvalidPath(String),
+}
+
+/// Fields that can be substituted in a template.
+#[derive(Clone, Copy, Debug, PartialEq, EnumString)]
+enum Field {
+    /// Name of the process.
+    #[strum(serialize = "name")]
+    Name,
+    /// Process identifier.
+    #[strum(serialize = "pid")]
+    Pid,
+    /// Default name of a target: `NAME_PID`.
+    #[strum(serialize = "target")]
+    Target,
+    /// Name of the host.
+    #[strum(serialize = "hostname")]
+    Hostname,
+    /// Date when the file is created: `YYYYMMDD`.
+    #[strum(serialize = "date")]
+    Date,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Part {
+    Text(String),
+    Field(Field),
+}
+
+/// Template of the name of the export files without the extension.
+#[derive(Clone, Debug)]
+pub struct FileNameTemplate {
+    spec: String,
+    parts: Vec<Part>,
+    hostname: String,
+}
+
+impl FileNameTemplate {
+    /// Replace the characters that are not allowed in a file name.
+    ///
+    /// The path separator is replaced and the value can't be `.` or `..`.
+    pub(crate) fn sanitize(value: &str) -> String {
+        let value = value
+            .chars()
+            .map(|c| {
+                if c.is_alphanumeric() || "-_.+@".contains(c) {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect::<String>();
+        if value.is_empty() || value.chars().all(|c| c == '.') {
+            "_".repeat(value.len().max(1))
+        } else {
+            value
+        }
+    }
+
+    fn parse_parts(spec: &str) -> Result<Vec<Part>, TemplateError> {
+        let mut parts = Vec::new();
+        let mut rest = spec;
+        while !rest.is_empty() {
+            match rest.find(['{', '}']) {
+                Some(pos) if rest[pos..].starts_with('{') => {
+                    if pos > 0 {
+                        parts.push(Part::Text(rest[..pos].to_string()));
+                    }
+                    let end = rest[pos..]
+                        .find('}')
+                        .ok_or_else(|| TemplateError::UnbalancedBraces(spec.to_string()))?;
+                    let name = &rest[pos + 1..pos + end];
+                    let field = Field::from_str(name)
+                        .map_err(|_| TemplateError::UnknownField(name.to_string()))?;
+                    parts.push(Part::Field(field));
+                    rest = &rest[pos + end + 1..];
+                }
+                Some(_) => return Err(TemplateError::UnbalancedBraces(spec.to_string())),
+                None => {
+                    parts.push(Part::Text(rest.to_string()));
+                    rest = "";
+                }
+            }
+        }
+        Ok(parts)
+    }
+
+    /// Check that the literal parts keep the files in the export directory.
+    fn check_path(spec: &str) -> Result<(), TemplateError> {
+        let path = Path::new(spec);
+        let is_valid = !spec.is_empty()
+            && !spec.ends_with('/')
+            && path
+                .components()
+                .all(|component| matches!(component, Component::Normal(_)));
+        if is_valid {
+            Ok(())
+        } else {
+            Err(TemplateError::InvalidPath(spec.to_string()))
+        }
+    }
+
+    fn render_with(
+        &self,
+        hostname: &str,
+        name: &str,
+        pid: pid_t,
+        now: &DateTime<Local>,
+        extension: &str,
+    ) -> PathBuf {
+        let stem = self
+            .parts
+            .iter()
+            .map(|part| match part {
+                Part::Text(text) => text.to_string(),
+                Part::Field(Field::Name) => FileNameTemplate::sanitize(name),
+                Part::Field(Field::Pid) => pid.to_string(),
+                Part::Field(Field::Target) => {
+                    format!("{}_{pid}", FileNameTemplate::sanitize(name))
+                }
+                Part::Field(Field::Hostname) => FileNameTemplate::sanitize(hostname),
+                Part::Field(Field::Date) => now.format("%Y%m%d").to_string(),
+            })
+            .collect::<String>();
+        // The template may contain the extension of the exporter.
+        let last_extension = extension.rsplit('.').next().unwrap_or(extension);
+        let stem = stem
+            .strip_suffix(&format!(".{last_extension}"))
+            .unwrap_or(&stem);
+        PathBuf::from(format!("{stem}.{extension}"))
+    }
+
+    /// Relative path of the export file of a process with the given extension.
+    pub fn render(&self, name: &str, pid: pid_t, extension: &str) -> PathBuf {
+        self.render_with(&self.hostname, name, pid, &Local::now(), extension)
+    }
+}
+
+impl Default for FileNameTemplate {
+    fn default() -> Self {
+        Self {
+            spec: String::from("{target}"),
+            parts: vec![Part::Field(Field::Target)],
+            hostname: String::new(),
+        }
+    }
+}
+
+impl FromStr for FileNameTemplate {
+    type Err = TemplateError;
+
+    fn from_str(spec: &str) -> Result<Self, Self::Err> {
+        FileNameTemplate::check_path(spec)?;
+        let parts = FileNameTemplate::parse_parts(spec)?;
+        let hostname = if parts.contains(&Part::Field(Field::Hostname)) {
+            fs::read_to_string(HOSTNAME_FILE)
+                .map(|s| s.trim().to_string())
+                .unwrap_or_else(|_| String::from("localhost"))
+        } else {
+            String::new()
+        };
+        Ok(Self {
+            spec: spec.to_string(),
+            parts,
+            hostname,
+        })
+    }
+}
+
+impl fmt::Display for FileNameTemplate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.spec)
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use chrono::{Local, TimeZone};
+    use std::{path::PathBuf, str::FromStr};
+
+    use super::{FileNameTemplate, TemplateError};
+
+    fn render(spec: &str, name: &str, extension: &str) -> PathBuf {
+        let now = Local.with_ymd_and_hms(2025, 3, 4, 5, 6, 7).unwrap();
+        FileNameTemplate::from_str(spec)
+            .unwrap()
+            .render_with("web1", name, 42, &now, extension)
+    }
+
+    #[test]
+    fn test_render() {
+        assert_eq!(
+            PathBuf::from("nginx_42.csv"),
+            FileNameTemplate::default().render_with("", "nginx", 42, &Local::now(), "csv")
+        );
+        assert_eq!(
+            PathBuf::from("nginx-42-20250304.csv"),
+            render("{name}-{pid}-{date}.csv", "nginx", "csv")
+        );
+        assert_eq!(
+            PathBuf::from("web1/nginx_42.rrd"),
+            render("{hostname}/{target}.rrd", "nginx", "rrd")
+        );
+        assert_eq!(
+            PathBuf::from("web1/nginx_42.hist.csv"),
+            render("{hostname}/{target}.csv", "nginx", "hist.csv")
+        );
+        assert_eq!(
+            PathBuf::from("__/42.tsv"),
+            render("{name}/{pid}", "..", "tsv")
+        );
+        assert_eq!(
+            PathBuf::from(".._etc_passwd.csv"),
+            render("{name}", "../etc/passwd", "csv")
+        );
+    }
+
+    #[test]
+    fn test_sanitize() {
+        assert_eq!("__", FileNameTemplate::sanitize(".."));
+        assert_eq!("_", FileNameTemplate::sanitize(""));
+        assert_eq!(".._etc_passwd", FileNameTemplate::sanitize("../etc/passwd"));
+        assert_eq!(
+            "kworker_0_1-events",
+            FileNameTemplate::sanitize("kworker/0:1-events")
+        );
+    }
+
+    #[test]
+    fn test_invalid_template() {
+        assert_eq!(
+            Some(TemplateError::UnknownField(String::from("user"))),
+            FileNameTemplate::from_str("{user}").err()
+        );
+        assert!(FileNameTemplate::from_str("{name").is_err());
+        assert!(FileNameTemplate::from_str("name}").is_err());
+        assert!(FileNameTemplate::from_str("/tmp/{name}").is_err());
+        assert!(FileNameTemplate::from_str("../{name}").is_err());
+        assert!(FileNameTemplate::from_str("").is_err());
+        assert!(FileNameTemplate::from_str("{hostname}/").is_err());
+    }
+}
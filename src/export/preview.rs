@@ -0,0 +1,146 @@
+// Oprs -- process monitor for Linux
+// Copyright (C) 2025  Laurent Pelecq
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Preview of the files written by an exporter.
+//!
+//! The exporter writes in a temporary directory that is printed and removed
+//! afterwards. The names are shown in the actual export directory.
+//!
+//! The temporary directory starts empty, so the files already in the export
+//! directory are ignored.
+
+use std::{
+    fs::{self, DirBuilder},
+    io::{self, Write},
+    os::unix::fs::DirBuilderExt,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Number of names tried for the temporary directory.
+const MAX_ATTEMPTS: u32 = 100;
+
+/// Temporary export directory removed when dropped.
+pub struct Preview {
+    dir: PathBuf,
+}
+
+impl Preview {
+    /// Create a new private directory, never reusing an existing one.
+    pub fn new() -> io::Result<Self> {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.subsec_nanos())
+            .unwrap_or_default();
+        let mut builder = DirBuilder::new();
+        builder.mode(0o700);
+        for attempt in 0..MAX_ATTEMPTS {
+            let dir = std::env::temp_dir().join(format!(
+                "oprs-preview-{}-{:x}",
+                std::process::id(),
+                nanos.wrapping_add(attempt)
+            ));
+            match builder.create(&dir) {
+                Ok(()) => return Ok(Self { dir }),
+                Err(err) if err.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(err) => return Err(err),
+            }
+        }
+        Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            "cannot create a temporary directory",
+        ))
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Paths of the files in a directory and its subdirectories, sorted.
+    fn files(dir: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                Preview::files(&path, files)?;
+            } else {
+                files.push(path);
+            }
+        }
+        files.sort();
+        Ok(())
+    }
+
+    /// Print the name and the content of each file as if it was in the export directory.
+    pub fn print(&self, out: &mut dyn Write, export_dir: &Path) -> io::Result<()> {
+        let mut files = Vec::new();
+        Preview::files(&self.dir, &mut files)?;
+        for (index, path) in files.iter().enumerate() {
+            if index > 0 {
+                writeln!(out)?;
+            }
+            let name = path.strip_prefix(&self.dir).unwrap_or(path);
+            writeln!(out, "==> {} <==", export_dir.join(name).display())?;
+            let content = fs::read(path)?;
+            match std::str::from_utf8(&content) {
+                Ok(text) => write!(out, "{text}")?,
+                Err(_) => writeln!(out, "(binary, {} bytes)", content.len())?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Preview {
+    fn drop(&mut self) {
+        if let Err(err) = fs::remove_dir_all(&self.dir) {
+            log::warn!("{}: {err}", self.dir.display());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::{fs, os::unix::fs::PermissionsExt, path::Path};
+
+    use super::Preview;
+
+    #[test]
+    fn test_preview() {
+        let preview = Preview::new().unwrap();
+        let dir = preview.dir().to_path_buf();
+        assert_eq!(
+            0o700,
+            fs::metadata(&dir).unwrap().permissions().mode() & 0o777
+        );
+        let other = Preview::new().unwrap();
+        assert_ne!(dir, other.dir());
+        fs::create_dir_all(dir.join("web")).unwrap();
+        fs::write(dir.join("web/nginx_1.csv"), "time,mem:vm\n1.000,10\n").unwrap();
+        fs::write(dir.join("index.json"), "{}\n").unwrap();
+        fs::write(dir.join("db.rrd"), [0xff, 0xfe]).unwrap();
+        let mut out = Vec::new();
+        preview.print(&mut out, Path::new("/var/log/oprs")).unwrap();
+        assert_eq!(
+            "==> /var/log/oprs/db.rrd <==\n(binary, 2 bytes)\n\n\
+             ==> /var/log/oprs/index.json <==\n{}\n\n\
+             ==> /var/log/oprs/web/nginx_1.csv <==\ntime,mem:vm\n1.000,10\n",
+            String::from_utf8(out).unwrap()
+        );
+        drop(preview);
+        assert!(!dir.exists());
+    }
+}
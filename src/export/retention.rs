@@ -0,0 +1,249 @@
+// Oprs -- process monitor for Linux
+// Copyright (C) 2025  Laurent Pelecq
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Remove old export files so that the export directory doesn't grow forever.
+//!
+//! Only the files written by oprs are deleted: they start with a metadata line,
+//! or they have a metadata file next to them (ex: name_1.rrd.meta), or they have
+//! a name generated by the exporter.
+
+use std::{
+    ffi::OsString,
+    fs, io,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use crate::cfg::ExportSettings;
+
+use super::schema;
+
+/// Extension of the metadata files next to the binary export files.
+const METADATA_EXTENSION: &str = "meta";
+
+/// Export file found in the directory.
+#[derive(Debug)]
+struct ExportFile {
+    path: PathBuf,
+    modified: SystemTime,
+    size: u64,
+}
+
+/// Retention policy of the export files.
+pub struct Retention {
+    dir: PathBuf,
+    max_age: Option<Duration>,
+    max_total_size: Option<u64>,
+}
+
+impl Retention {
+    pub fn new(settings: &ExportSettings) -> Self {
+        Self {
+            dir: settings.dir.clone(),
+            max_age: settings.max_age,
+            max_total_size: settings.max_total_size,
+        }
+    }
+
+    /// Whether the file name is an export file, possibly rotated (ex: name_1.csv.2).
+    pub fn is_export_file(name: &str, extension: &str) -> bool {
+        let suffix = format!(".{extension}");
+        match name.rfind(&suffix) {
+            Some(pos) => {
+                let rest = &name[pos + suffix.len()..];
+                rest.is_empty()
+                    || rest.strip_prefix('.').is_some_and(|rank| {
+                        !rank.is_empty() && rank.bytes().all(|c| c.is_ascii_digit())
+                    })
+            }
+            None => false,
+        }
+    }
+
+    /// Path of the metadata file next to a binary export file.
+    fn metadata_path(path: &Path) -> PathBuf {
+        let mut name = OsString::from(path.as_os_str());
+        name.push(".");
+        name.push(METADATA_EXTENSION);
+        PathBuf::from(name)
+    }
+
+    /// Whether the file has been written by oprs.
+    fn is_owned(path: &Path) -> bool {
+        schema::has_metadata(path) || schema::has_metadata(&Retention::metadata_path(path))
+    }
+
+    /// List the export files in the directory.
+    ///
+    /// Files in `known_names` are generated by the exporter without metadata.
+    fn list(&self, extension: &str, known_names: &[String]) -> io::Result<Vec<ExportFile>> {
+        let mut files = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let entry = entry?;
+            let is_export = entry.file_name().to_str().is_some_and(|name| {
+                Retention::is_export_file(name, extension)
+                    && (known_names.iter().any(|known| known == name)
+                        || Retention::is_owned(&entry.path()))
+            });
+            if !is_export {
+                continue;
+            }
+            let metadata = entry.metadata()?;
+            if metadata.is_file() {
+                files.push(ExportFile {
+                    path: entry.path(),
+                    modified: metadata.modified()?,
+                    size: metadata.len(),
+                });
+            }
+        }
+        Ok(files)
+    }
+
+    /// Select the files that are too old, then the oldest ones until the total size fits in the budget.
+    fn select(&self, mut files: Vec<ExportFile>, now: SystemTime) -> Vec<PathBuf> {
+        files.sort_by(|a, b| a.modified.cmp(&b.modified).then(a.path.cmp(&b.path)));
+        let mut total_size = files.iter().map(|file| file.size).sum::<u64>();
+        files
+            .into_iter()
+            .filter(|file| {
+                let too_old = self.max_age.is_some_and(|max_age| {
+                    now.duration_since(file.modified)
+                        .is_ok_and(|age| age > max_age)
+                });
+                let over_budget = self
+                    .max_total_size
+                    .is_some_and(|max_total_size| total_size > max_total_size);
+                if too_old || over_budget {
+                    total_size -= file.size;
+                    true
+                } else {
+                    false
+                }
+            })
+            .map(|file| file.path)
+            .collect()
+    }
+
+    /// Delete the export files with the given extension that are beyond the limits.
+    ///
+    /// The metadata file next to a deleted file is also deleted.
+    pub fn apply(&self, extension: &str, known_names: &[String]) -> io::Result<()> {
+        if self.max_age.is_none() && self.max_total_size.is_none() {
+            return Ok(());
+        }
+        let files = self.list(extension, known_names)?;
+        for path in self.select(files, SystemTime::now()) {
+            log::info!("{}: removing old export file", path.display());
+            let metadata_path = Retention::metadata_path(&path);
+            fs::remove_file(path)?;
+            if metadata_path.is_file() {
+                fs::remove_file(metadata_path)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::{
+        env, fs,
+        path::PathBuf,
+        process,
+        time::{Duration, SystemTime},
+    };
+
+    use super::{super::schema, ExportFile, Retention};
+
+    fn export_file(name: &str, age: u64, size: u64, now: SystemTime) -> ExportFile {
+        ExportFile {
+            path: PathBuf::from(name),
+            modified: now - Duration::from_secs(age),
+            size,
+        }
+    }
+
+    fn select(max_age: Option<u64>, max_total_size: Option<u64>) -> Vec<PathBuf> {
+        let now = SystemTime::now();
+        let retention = Retention {
+            dir: PathBuf::from("."),
+            max_age: max_age.map(Duration::from_secs),
+            max_total_size,
+        };
+        let files = vec![
+            export_file("a.csv", 10, 100, now),
+            export_file("b.csv", 300, 200, now),
+            export_file("c.csv", 200, 300, now),
+        ];
+        retention.select(files, now)
+    }
+
+    #[test]
+    fn test_is_export_file() {
+        assert!(Retention::is_export_file("sh_12.csv", "csv"));
+        assert!(Retention::is_export_file("sh_12.csv.3", "csv"));
+        assert!(Retention::is_export_file("sh_12.hist.csv", "csv"));
+        assert!(!Retention::is_export_file("sh_12.csv.bak", "csv"));
+        assert!(!Retention::is_export_file("sh_12.csv.", "csv"));
+        assert!(!Retention::is_export_file("sh_12.tsv", "csv"));
+    }
+
+    #[test]
+    fn test_select_by_age() {
+        assert_eq!(vec![PathBuf::from("b.csv")], select(Some(250), None));
+        assert!(select(Some(1000), None).is_empty());
+    }
+
+    #[test]
+    fn test_select_by_size() {
+        let expected = vec![PathBuf::from("b.csv"), PathBuf::from("c.csv")];
+        assert_eq!(expected, select(None, Some(250)));
+        assert_eq!(vec![PathBuf::from("b.csv")], select(Some(250), Some(400)));
+        assert!(select(None, Some(600)).is_empty());
+    }
+
+    #[test]
+    fn test_only_owned_files() {
+        let dir = env::temp_dir().join(format!("oprs-test-retention-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let mut header = Vec::new();
+        schema::write_header(&mut header, &[String::from("time,mem:rss")], false).unwrap();
+        fs::write(dir.join("sh_12.csv"), &header).unwrap();
+        fs::write(dir.join("sh_12.rrd"), b"binary").unwrap();
+        fs::write(dir.join("sh_12.rrd.meta"), &header).unwrap();
+        fs::write(dir.join("events.csv"), b"time,pid,name,event\n").unwrap();
+        fs::write(dir.join("budget.csv"), b"month,amount\n").unwrap();
+        fs::write(dir.join("photo.rrd"), b"binary").unwrap();
+        let retention = Retention {
+            dir: dir.clone(),
+            max_age: None,
+            max_total_size: Some(0),
+        };
+        retention
+            .apply("csv", &[String::from("events.csv")])
+            .unwrap();
+        retention.apply("rrd", &[]).unwrap();
+        let mut left = fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+            .collect::<Vec<String>>();
+        left.sort();
+        assert_eq!(vec!["budget.csv", "photo.rrd"], left);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -16,19 +16,22 @@
 
 use anyhow::anyhow;
 use libc::pid_t;
-use log::{debug, info};
+use log::{debug, info, warn};
 use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::path::PathBuf;
 use std::rc::Rc;
 use std::time::Duration;
 
 use crate::{
     cfg::{ExportSettings, ExportType},
+    clock::SampleTime,
     process::{
         Aggregation, Collector, FormattedMetric, MetricDataType, ProcessIdentity, ProcessSamples,
     },
 };
 
-use super::{Exporter, SliceIter};
+use super::{retention::Retention, schema, Exporter, FileNameTemplate, SliceIter};
 
 use crate::export::rrdtool::RrdTool;
 
@@ -81,17 +84,27 @@ pub struct RrdExporter {
     rows: usize,
     period: Duration,
     tool: RrdTool,
+    dir: PathBuf,
+    template: FileNameTemplate,
     variables: Vec<String>,
     ds: Vec<String>,
     skip: Vec<bool>,
+    /// Description of the databases to check existing ones.
+    columns: Vec<String>,
     pids: HashMap<pid_t, Rc<ExportInfo>>,
     color_bucket: Vec<u32>,
     graph: bool,
+    retention: Retention,
+    /// Wall-clock time of the last update.
+    last_update: Option<Duration>,
 }
 
 impl RrdExporter {
     pub fn new(settings: &ExportSettings, interval: Duration) -> anyhow::Result<RrdExporter> {
         let rows = settings.count.ok_or(Error::MissingCount)?;
+        if !settings.histograms.is_empty() {
+            log::warn!("rrd: histograms are not supported");
+        }
         let tool = RrdTool::new(settings.dir.as_path())?;
         let period = interval
             .checked_mul(rows as u32)
@@ -104,19 +117,27 @@ impl RrdExporter {
                 rows,
                 period,
                 tool,
+                dir: settings.dir.clone(),
+                template: settings.template.clone(),
                 ds: Vec::new(),
                 variables: Vec::new(),
                 skip: Vec::new(),
+                columns: Vec::new(),
                 pids: HashMap::new(),
                 color_bucket: COLORS.to_vec(),
                 graph: matches!(settings.kind, ExportType::RrdGraph),
+                retention: Retention::new(settings),
+                last_update: None,
             })
         }
     }
 
-    /// File name of a RRD.
-    fn filename(pid: pid_t, name: &str) -> String {
-        format!("{name}_{pid}.rrd")
+    /// File name of a RRD relative to the export directory.
+    fn filename(&self, pid: pid_t, name: &str) -> String {
+        self.template
+            .render(name, pid, "rrd")
+            .to_string_lossy()
+            .to_string()
     }
 
     /// Create process info.
@@ -126,17 +147,33 @@ impl RrdExporter {
         timestamp: &Duration,
     ) -> anyhow::Result<()> {
         let pid = status.pid();
-        let dbname = RrdExporter::filename(pid, status.name());
-        let start_time = timestamp
-            .checked_sub(self.interval)
-            .ok_or(Error::IntervalTooLarge)?;
-        self.tool.create(
-            &dbname,
-            self.ds.iter(),
-            &start_time,
-            &self.interval,
-            self.rows,
-        )?;
+        let dbname = self.filename(pid, status.name());
+        let dbpath = self.dir.join(&dbname);
+        if let Some(parent) = dbpath.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let metapath = self.dir.join(format!("{dbname}.meta"));
+        if dbpath.exists()
+            && metapath.exists()
+            && schema::is_compatible_file(&metapath, &self.columns, false)?
+        {
+            info!("{dbname}: append to existing database");
+        } else {
+            if dbpath.exists() {
+                warn!("{dbname}: incompatible database replaced");
+            }
+            let start_time = timestamp
+                .checked_sub(self.interval)
+                .ok_or(Error::IntervalTooLarge)?;
+            self.tool.create(
+                &dbname,
+                self.ds.iter(),
+                &start_time,
+                &self.interval,
+                self.rows,
+            )?;
+            schema::write_header(&mut File::create(metapath)?, &self.columns, false)?;
+        }
         let color = if self.graph {
             self.color_bucket.pop().ok_or(Error::NoMoreColors)?
         } else {
@@ -150,10 +187,11 @@ impl RrdExporter {
 
 impl Exporter for RrdExporter {
     fn open(&mut self, metrics: SliceIter<FormattedMetric>) -> anyhow::Result<()> {
+        self.retention.apply("rrd", &[])?;
         let heart_beat = self.interval.as_secs() * 2;
-        Collector::for_each_computed_metric(metrics, |id, ag| {
-            let ds_name = id.as_str().replace(':', "_");
-            let ds_type = match id.data_type() {
+        Collector::for_each_computed_metric(metrics, |metric, ag| {
+            let ds_name = metric.name().replace(':', "_");
+            let ds_type = match metric.data_type() {
                 MetricDataType::Counter => "COUNTER",
                 MetricDataType::Gauge => "GAUGE",
             };
@@ -167,6 +205,18 @@ impl Exporter for RrdExporter {
                 self.skip.push(true);
             }
         });
+        if self.graph {
+            let graphs = self
+                .variables
+                .iter()
+                .map(|ds_name| format!("{ds_name}.png"))
+                .collect::<Vec<String>>();
+            self.retention.apply("png", &graphs)?;
+        }
+        self.columns = self.ds.clone();
+        self.columns
+            .push(format!("step={}", self.interval.as_secs()));
+        self.columns.push(format!("rows={}", self.rows));
         Ok(())
     }
 
@@ -175,12 +225,23 @@ impl Exporter for RrdExporter {
         Ok(())
     }
 
-    fn export(&mut self, collector: &Collector, timestamp: &Duration) -> anyhow::Result<()> {
+    fn export(&mut self, collector: &Collector, time: &SampleTime) -> anyhow::Result<()> {
+        // RRDtool rejects updates that are not after the last one.
+        let timestamp = &Duration::from_secs(time.wall.as_secs());
+        if self.last_update.is_some_and(|last| *timestamp <= last) {
+            warn!(
+                "rrd: sample at {}s skipped, not after the last update",
+                timestamp.as_secs()
+            );
+            return Ok(());
+        }
+        self.last_update = Some(*timestamp);
         let mut pids: HashSet<pid_t> = self.pids.keys().copied().collect();
         let mut infos = Vec::new();
         for status in collector.lines() {
             let pid = status.pid();
-            if pid == 0 {
+            if pid <= 0 {
+                // Skip the system and its breakdown.
                 continue;
             }
             if !pids.remove(&pid) {
@@ -195,7 +256,7 @@ impl Exporter for RrdExporter {
                 .samples()
                 .zip(self.skip.iter())
                 .filter(|(_, skip)| !*skip)
-                .map(|(sample, _)| *(sample.values().next().unwrap()));
+                .map(|(sample, _)| sample.collected_raw());
             self.tool.update(&exinfo.db, samples, timestamp)?;
         }
         if self.graph {
@@ -26,17 +26,21 @@ mod process {
 
     pub use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
 
+    use crate::privileges::drop_capabilities;
+
     pub fn spawn<P>(command: &str, working_dir: P) -> Result<Child>
     where
         P: AsRef<Path>,
     {
-        Command::new(command)
-            .arg("-")
-            .current_dir(working_dir.as_ref())
-            .stdin(Stdio::piped())
-            .stdout(Stdio::piped())
-            .stderr(Stdio::null())
-            .spawn()
+        drop_capabilities(
+            Command::new(command)
+                .arg("-")
+                .current_dir(working_dir.as_ref())
+                .stdin(Stdio::piped())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::null()),
+        )
+        .spawn()
     }
 }
 
@@ -219,15 +223,18 @@ impl RrdTool {
         self.read_answer(None)
     }
 
-    /// Update values
+    /// Update values, the missing ones are unknown.
     pub fn update<I>(&mut self, dbname: &str, values: I, timestamp: &Duration) -> Result<(), Error>
     where
-        I: std::iter::Iterator<Item = u64>,
+        I: std::iter::Iterator<Item = Option<u64>>,
     {
         log::debug!("rrd update {}", dbname);
         try_write!(self.child_in, "update {} {}", dbname, timestamp.as_secs());
         for value in values {
-            try_write!(self.child_in, ":{}", value);
+            match value {
+                Some(value) => try_write!(self.child_in, ":{}", value),
+                None => try_write!(self.child_in, ":U"),
+            }
         }
         try_writeln!(self.child_in);
         self.read_answer(None)
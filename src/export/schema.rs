@@ -0,0 +1,159 @@
+// Oprs -- process monitor for Linux
+// Copyright (C) 2025  Laurent Pelecq
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Versioned metadata of the exported files.
+//!
+//! Exported files start with a metadata line followed by the description of
+//! the columns. Data is only appended to an existing file if the format version,
+//! the units and the columns are the same.
+
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, Write},
+    path::Path,
+};
+
+/// Version of the layout of the exported files.
+pub const FORMAT_VERSION: u32 = 1;
+
+const METADATA_TAG: &str = "# oprs-export";
+
+/// Units of the values, as collected or converted in base units.
+fn units_name(base_units: bool) -> &'static str {
+    if base_units {
+        "base"
+    } else {
+        "raw"
+    }
+}
+
+/// Metadata line with the format version, the units and the program version.
+pub fn metadata_line(base_units: bool) -> String {
+    format!(
+        "{METADATA_TAG} format={FORMAT_VERSION} units={} oprs={}",
+        units_name(base_units),
+        env!("CARGO_PKG_VERSION")
+    )
+}
+
+/// Value of a field of a metadata line.
+fn parse_field<'a>(line: &'a str, name: &str) -> Option<&'a str> {
+    line.strip_prefix(METADATA_TAG)?
+        .split_whitespace()
+        .find_map(|field| field.strip_prefix(name)?.strip_prefix('='))
+}
+
+/// Format version of a metadata line.
+fn parse_format_version(line: &str) -> Option<u32> {
+    parse_field(line, "format").and_then(|version| version.parse::<u32>().ok())
+}
+
+/// Units of a metadata line, the files written without units have raw values.
+fn parse_units(line: &str) -> &str {
+    parse_field(line, "units").unwrap_or(units_name(false))
+}
+
+/// Whether the metadata have the same version and units and the next lines match the columns.
+fn is_compatible<R: BufRead>(reader: R, columns: &[String], base_units: bool) -> io::Result<bool> {
+    let mut lines = reader.lines();
+    match lines.next().transpose()? {
+        Some(line)
+            if parse_format_version(&line) == Some(FORMAT_VERSION)
+                && parse_units(&line) == units_name(base_units) => {}
+        _ => return Ok(false),
+    }
+    for column in columns {
+        match lines.next().transpose()? {
+            Some(line) if line == *column => (),
+            _ => return Ok(false),
+        }
+    }
+    Ok(true)
+}
+
+/// Whether a file starts with a metadata line, i.e. it has been written by oprs.
+pub fn has_metadata(path: &Path) -> bool {
+    let mut line = String::new();
+    File::open(path)
+        .and_then(|file| BufReader::new(file).read_line(&mut line))
+        .is_ok_and(|_| line.starts_with(METADATA_TAG))
+}
+
+/// Whether an existing file has the same format version, units and columns.
+///
+/// The program version is informative and may differ.
+pub fn is_compatible_file(path: &Path, columns: &[String], base_units: bool) -> io::Result<bool> {
+    is_compatible(BufReader::new(File::open(path)?), columns, base_units)
+}
+
+/// Write the metadata line followed by the columns.
+pub fn write_header(out: &mut dyn Write, columns: &[String], base_units: bool) -> io::Result<()> {
+    writeln!(out, "{}", metadata_line(base_units))?;
+    for column in columns {
+        writeln!(out, "{column}")?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::io;
+
+    use super::{
+        is_compatible, metadata_line, parse_format_version, parse_units, write_header,
+        FORMAT_VERSION,
+    };
+
+    #[test]
+    fn test_parse_format_version() {
+        assert_eq!(
+            Some(FORMAT_VERSION),
+            parse_format_version(&metadata_line(false))
+        );
+        assert_eq!(Some(7), parse_format_version("# oprs-export format=7"));
+        assert_eq!(None, parse_format_version("time,mem:rss"));
+    }
+
+    #[test]
+    fn test_parse_units() {
+        assert_eq!("base", parse_units(&metadata_line(true)));
+        assert_eq!("raw", parse_units(&metadata_line(false)));
+        assert_eq!("raw", parse_units("# oprs-export format=1 oprs=0.1.0"));
+    }
+
+    #[test]
+    fn test_is_compatible() -> io::Result<()> {
+        let columns = vec!["time,mem:rss".to_string()];
+        let mut buf = Vec::new();
+        write_header(&mut buf, &columns, false)?;
+        assert!(is_compatible(buf.as_slice(), &columns, false)?);
+        assert!(!is_compatible(buf.as_slice(), &columns, true)?);
+        let other_columns = vec!["time,mem:vm".to_string()];
+        assert!(!is_compatible(buf.as_slice(), &other_columns, false)?);
+        assert!(!is_compatible(
+            "time,mem:rss\n".as_bytes(),
+            &columns,
+            false
+        )?);
+        let old_version = "# oprs-export format=0 oprs=0.1.0\ntime,mem:rss\n";
+        assert!(!is_compatible(old_version.as_bytes(), &columns, false)?);
+        let no_units = "# oprs-export format=1 oprs=0.1.0\ntime,mem:rss\n";
+        assert!(is_compatible(no_units.as_bytes(), &columns, false)?);
+        assert!(!is_compatible(no_units.as_bytes(), &columns, true)?);
+        Ok(())
+    }
+}
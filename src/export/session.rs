@@ -0,0 +1,105 @@
+// Oprs -- process monitor for Linux
+// Copyright (C) 2025  Laurent Pelecq
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Marker of an export session that may be truncated.
+//!
+//! When oprs is close to the memory limit of its cgroup, it may be killed
+//! without a chance to close the files. A line is added to the marker after the
+//! data is flushed and removed when the program terminates normally, so a
+//! marker left in the export directory means that the last samples of a
+//! session may be missing. The lines of the other sessions are kept.
+
+use chrono::Local;
+use std::{
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::Path,
+};
+
+/// Name of the marker in the export directory.
+pub const TRUNCATED_MARKER: &str = "oprs-session.truncated";
+
+/// Identifier of the session in the lines of the marker.
+fn session_id() -> String {
+    format!("pid={}", std::process::id())
+}
+
+/// Add a line with the reason to the marker.
+pub fn mark_truncated(dir: &Path, reason: &str) -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(dir.join(TRUNCATED_MARKER))?;
+    writeln!(
+        file,
+        "{} {} {reason}",
+        Local::now().to_rfc3339(),
+        session_id()
+    )?;
+    file.sync_all()
+}
+
+/// Remove the lines of the session from the marker and the marker if it's empty.
+pub fn clear_truncated(dir: &Path) -> io::Result<()> {
+    let path = dir.join(TRUNCATED_MARKER);
+    let content = match fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(err) => return Err(err),
+    };
+    let session_id = session_id();
+    let others = content
+        .lines()
+        .filter(|line| line.split(' ').nth(1) != Some(session_id.as_str()))
+        .map(|line| format!("{line}\n"))
+        .collect::<String>();
+    if others.is_empty() {
+        fs::remove_file(path)
+    } else {
+        fs::write(path, others)
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::fs;
+
+    use super::{clear_truncated, mark_truncated, TRUNCATED_MARKER};
+
+    #[test]
+    fn test_marker() {
+        let dir = std::env::temp_dir().join(format!("oprs-test-session-{}", std::process::id()));
+        mark_truncated(&dir, "memory 95M of 100M").unwrap();
+        let content = fs::read_to_string(dir.join(TRUNCATED_MARKER)).unwrap();
+        assert!(content.ends_with(" memory 95M of 100M\n"), "{content}");
+        clear_truncated(&dir).unwrap();
+        assert!(!dir.join(TRUNCATED_MARKER).exists());
+        clear_truncated(&dir).unwrap();
+
+        // The marker of a previous session is kept.
+        let previous = "2025-01-01T00:00:00+00:00 pid=1 memory 99M of 100M\n";
+        fs::write(dir.join(TRUNCATED_MARKER), previous).unwrap();
+        mark_truncated(&dir, "memory 95M of 100M").unwrap();
+        clear_truncated(&dir).unwrap();
+        assert_eq!(
+            previous,
+            fs::read_to_string(dir.join(TRUNCATED_MARKER)).unwrap()
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,306 @@
+// Oprs -- process monitor for Linux
+// Copyright (C) 2025  Laurent Pelecq
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Send the samples as gauges to a statsd server over UDP.
+//!
+//! With statsd, the process is part of the name of the gauge:
+//! `PREFIX.NAME.PID.METRIC:VALUE|g`. With dogstatsd, the name and the PID are
+//! tags: `PREFIX.METRIC:VALUE|g|#name:NAME,pid:PID`.
+//!
+//! The lines are grouped in datagrams small enough to not be fragmented. If a
+//! minimum delay is set, only the last sample of the interval is sent.
+
+use std::{
+    io,
+    net::{SocketAddr, ToSocketAddrs, UdpSocket},
+    time::Duration,
+};
+
+use crate::{
+    cfg::ExportSettings,
+    clock::SampleTime,
+    process::{Aggregation, Collector, FormattedMetric, ProcessIdentity, Sample},
+};
+
+use super::{units::BaseUnits, Exporter, SliceIter};
+
+/// Default address of the statsd server.
+const DEFAULT_ADDRESS: &str = "localhost:8125";
+
+/// Default prefix of the gauges.
+const DEFAULT_PREFIX: &str = "oprs";
+
+/// Maximum size of a datagram that fits in an Ethernet frame.
+const MAX_DATAGRAM_SIZE: usize = 1432;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("statsd: {0}: cannot resolve address")]
+    UnknownAddress(String),
+}
+
+/// Replace the characters that have a meaning in the statsd protocol.
+fn sanitize(name: &str) -> String {
+    name.chars()
+        .map(|c| match c {
+            ':' | '+' => '.',
+            c if c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-') => c,
+            _ => '_',
+        })
+        .collect()
+}
+
+/// Split lines in datagrams no larger than the maximum size if possible.
+fn datagrams(lines: &[String]) -> Vec<String> {
+    let mut datagrams = Vec::new();
+    let mut current = String::new();
+    for line in lines {
+        if !current.is_empty() && current.len() + line.len() + 1 > MAX_DATAGRAM_SIZE {
+            datagrams.push(std::mem::take(&mut current));
+        }
+        if !current.is_empty() {
+            current.push('\n');
+        }
+        current.push_str(line);
+    }
+    if !current.is_empty() {
+        datagrams.push(current);
+    }
+    datagrams
+}
+
+pub struct StatsdExporter {
+    socket: UdpSocket,
+    prefix: String,
+    /// Whether the process is in tags instead of the name.
+    with_tags: bool,
+    /// Minimum delay between two sends.
+    every: Option<Duration>,
+    /// Time of the last send.
+    last_send: Option<Duration>,
+    metrics: Vec<String>,
+    units: BaseUnits,
+    /// Lines of the last sample not sent yet.
+    pending: Vec<String>,
+}
+
+impl StatsdExporter {
+    pub fn new(settings: &ExportSettings, with_tags: bool) -> anyhow::Result<Self> {
+        let address = settings.address.as_deref().unwrap_or(DEFAULT_ADDRESS);
+        let address = address
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| Error::UnknownAddress(address.to_string()))?;
+        let prefix = settings.prefix.as_deref().unwrap_or(DEFAULT_PREFIX);
+        let mut exporter = Self::with_address(address, prefix, with_tags, settings.send_every)?;
+        exporter.units = BaseUnits::new(settings);
+        Ok(exporter)
+    }
+
+    fn with_address(
+        address: SocketAddr,
+        prefix: &str,
+        with_tags: bool,
+        every: Option<Duration>,
+    ) -> io::Result<Self> {
+        let local: SocketAddr = if address.is_ipv4() {
+            ([0, 0, 0, 0], 0).into()
+        } else {
+            ([0u16; 8], 0).into()
+        };
+        let socket = UdpSocket::bind(local)?;
+        socket.connect(address)?;
+        Ok(Self {
+            socket,
+            prefix: prefix.trim_end_matches('.').to_string(),
+            with_tags,
+            every,
+            last_send: None,
+            metrics: Vec::new(),
+            units: BaseUnits::default(),
+            pending: Vec::new(),
+        })
+    }
+
+    /// Name of a gauge.
+    fn gauge_name(&self, process: Option<(&str, i32)>, metric: &str) -> String {
+        let prefix = if self.prefix.is_empty() {
+            String::new()
+        } else {
+            format!("{}.", self.prefix)
+        };
+        match process {
+            Some((name, pid)) => format!("{prefix}{}.{pid}.{metric}", sanitize(name)),
+            None => format!("{prefix}{metric}"),
+        }
+    }
+
+    /// Lines of the gauges of a sample, the metrics that are not collected are skipped.
+    fn lines(&self, collector: &Collector) -> Vec<String> {
+        collector
+            .lines()
+            .flat_map(|pstat| {
+                let values = self
+                    .units
+                    .format(pstat.samples().flat_map(Sample::collected_values));
+                self.metrics
+                    .iter()
+                    .zip(values)
+                    .filter(|(_, value)| !value.is_empty())
+                    .map(|(metric, value)| {
+                        if self.with_tags {
+                            format!(
+                                "{}:{value}|g|#name:{},pid:{}",
+                                self.gauge_name(None, metric),
+                                sanitize(pstat.name()),
+                                pstat.pid()
+                            )
+                        } else {
+                            format!(
+                                "{}:{value}|g",
+                                self.gauge_name(Some((pstat.name(), pstat.pid())), metric)
+                            )
+                        }
+                    })
+                    .collect::<Vec<String>>()
+            })
+            .collect()
+    }
+
+    /// Send the pending lines. The errors are only logged since the server may be restarted.
+    fn send(&mut self) {
+        for datagram in datagrams(&self.pending) {
+            if let Err(err) = self.socket.send(datagram.as_bytes()) {
+                log::warn!("statsd: {err}");
+                break;
+            }
+        }
+        self.pending.clear();
+    }
+}
+
+impl Exporter for StatsdExporter {
+    fn open(&mut self, metrics: SliceIter<FormattedMetric>) -> anyhow::Result<()> {
+        self.units.open(metrics.clone());
+        Collector::for_each_computed_metric(metrics, |metric, ag| {
+            let name = sanitize(metric.name());
+            self.metrics.push(match ag {
+                Aggregation::None => name,
+                Aggregation::Min => format!("{name}.min"),
+                Aggregation::Max => format!("{name}.max"),
+                Aggregation::Ratio => format!("{name}.ratio"),
+            })
+        });
+        Ok(())
+    }
+
+    fn close(&mut self) -> anyhow::Result<()> {
+        self.send();
+        Ok(())
+    }
+
+    fn flush(&mut self) -> anyhow::Result<()> {
+        self.send();
+        Ok(())
+    }
+
+    fn export(&mut self, collector: &Collector, time: &SampleTime) -> anyhow::Result<()> {
+        self.pending = self.lines(collector);
+        let due = match (self.every, self.last_send) {
+            (Some(every), Some(last)) => time.monotonic.saturating_sub(last) >= every,
+            _ => true,
+        };
+        if due {
+            self.last_send = Some(time.monotonic);
+            self.send();
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use rstest::*;
+    use std::{borrow::Cow, net::UdpSocket, time::Duration};
+
+    use super::{datagrams, sanitize, Exporter, StatsdExporter, MAX_DATAGRAM_SIZE};
+    use crate::{
+        clock::SampleTime,
+        process::{Collector, MetricNamesParser},
+    };
+
+    fn sample_time(secs: u64) -> SampleTime {
+        SampleTime {
+            monotonic: Duration::from_secs(secs),
+            wall: Duration::from_secs(1_700_000_000 + secs),
+        }
+    }
+
+    fn receive(server: &UdpSocket) -> String {
+        let mut buf = [0; MAX_DATAGRAM_SIZE];
+        let count = server.recv(&mut buf).unwrap();
+        String::from_utf8_lossy(&buf[..count]).to_string()
+    }
+
+    #[test]
+    fn test_sanitize() {
+        assert_eq!("fd.all.max", sanitize("fd:all+max"));
+        assert_eq!("a_b_c_", sanitize("a b|c#"));
+    }
+
+    #[test]
+    fn test_datagrams() {
+        let line = "x".repeat(MAX_DATAGRAM_SIZE / 2);
+        let lines = vec![line.clone(), line.clone(), "y".to_string()];
+        let datagrams = datagrams(&lines);
+        assert_eq!(2, datagrams.len());
+        assert_eq!(line, datagrams[0]);
+        assert_eq!(format!("{line}\ny"), datagrams[1]);
+        assert!(super::datagrams(&[]).is_empty());
+    }
+
+    #[rstest]
+    #[case(false, "oprs.a_b.-2.fd.all:7|g\noprs.a_b.-2.fd.all.max:7|g")]
+    #[case(
+        true,
+        "oprs.fd.all:7|g|#name:a_b,pid:-2\noprs.fd.all.max:7|g|#name:a_b,pid:-2"
+    )]
+    fn test_statsd_exporter(#[case] with_tags: bool, #[case] expected: &str) -> anyhow::Result<()> {
+        let server = UdpSocket::bind("127.0.0.1:0")?;
+        server.set_read_timeout(Some(Duration::from_secs(5)))?;
+        let metrics = MetricNamesParser::new(false).parse(&["fd:all+max"])?;
+        let mut collector = Collector::new(Cow::Borrowed(&metrics));
+        collector.record_pseudo("a b", -2, &[7]);
+        let mut exporter = StatsdExporter::with_address(
+            server.local_addr()?,
+            "oprs.",
+            with_tags,
+            Some(Duration::from_secs(10)),
+        )?;
+        exporter.open(metrics.iter())?;
+        exporter.export(&collector, &sample_time(0))?;
+        assert_eq!(expected, receive(&server));
+        // Within the delay, the sample is kept until the next send.
+        collector.rewind();
+        collector.record_pseudo("a b", -2, &[8]);
+        exporter.export(&collector, &sample_time(5))?;
+        exporter.export(&collector, &sample_time(10))?;
+        assert_eq!(expected.replace(":7|", ":8|"), receive(&server));
+        exporter.close()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,177 @@
+// Oprs -- process monitor for Linux
+// Copyright (C) 2025  Laurent Pelecq
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Conversion of the exported values in base units.
+//!
+//! The durations are collected in milliseconds and the ratios in per thousand.
+//! In base units, they are exported in seconds and in fractions of one. The
+//! sizes are always in bytes and the other values are counts.
+
+use std::slice::Iter as SliceIter;
+
+use crate::{
+    cfg::ExportSettings,
+    dump::Json,
+    process::{Aggregation, Collector, FormattedMetric, MetricId},
+};
+
+/// Number of collected units in a base unit.
+fn scale(metric: &FormattedMetric, ag: Aggregation) -> u64 {
+    let is_scaled = matches!(ag, Aggregation::Ratio)
+        || matches!(
+            metric.id,
+            MetricId::CpuThrottledTime
+                | MetricId::DelayBlockIo
+                | MetricId::DelaySwapIn
+                | MetricId::TimeElapsed
+                | MetricId::TimeCpu
+                | MetricId::TimeSystem
+                | MetricId::TimeUser
+                | MetricId::CgroupMemoryRatio
+                | MetricId::FdRatio
+                | MetricId::FsRatio
+        );
+    if is_scaled {
+        1000
+    } else {
+        1
+    }
+}
+
+/// Exact decimal representation of a value divided by a power of ten.
+fn decimal(value: u64, scale: u64) -> String {
+    let (integer, fraction) = (value / scale, value % scale);
+    if fraction == 0 {
+        integer.to_string()
+    } else {
+        let digits = scale.ilog10() as usize;
+        let text = format!("{integer}.{fraction:0digits$}");
+        text.trim_end_matches('0').to_string()
+    }
+}
+
+/// Units of the exported values.
+#[derive(Debug, Default)]
+pub struct BaseUnits {
+    /// Scale of each computed value if the values are converted.
+    scales: Option<Vec<u64>>,
+}
+
+impl BaseUnits {
+    pub fn new(settings: &ExportSettings) -> Self {
+        Self {
+            scales: settings.base_units.then(Vec::new),
+        }
+    }
+
+    /// Whether the values are converted in base units.
+    pub fn is_base(&self) -> bool {
+        self.scales.is_some()
+    }
+
+    /// Compute the scales of the values in the order of the samples.
+    pub fn open(&mut self, metrics: SliceIter<FormattedMetric>) {
+        if let Some(ref mut scales) = self.scales {
+            scales.clear();
+            Collector::for_each_computed_metric(metrics, |metric, ag| {
+                scales.push(scale(metric, ag))
+            });
+        }
+    }
+
+    fn scale(&self, index: usize) -> Option<u64> {
+        self.scales
+            .as_ref()
+            .and_then(|scales| scales.get(index))
+            .copied()
+            .filter(|scale| *scale > 1)
+    }
+
+    /// Values of a line formatted as collected or in base units.
+    ///
+    /// The values that are not collected are empty.
+    pub fn format<'a, I>(&'a self, values: I) -> impl Iterator<Item = String> + 'a
+    where
+        I: IntoIterator<Item = Option<u64>> + 'a,
+    {
+        values
+            .into_iter()
+            .enumerate()
+            .map(|(index, value)| match (value, self.scale(index)) {
+                (Some(value), Some(scale)) => decimal(value, scale),
+                (Some(value), None) => value.to_string(),
+                (None, _) => String::new(),
+            })
+    }
+
+    /// Values of a line in JSON as collected or in base units.
+    ///
+    /// The values that are not collected are null.
+    pub fn json<I>(&self, values: I) -> Json
+    where
+        I: IntoIterator<Item = Option<u64>>,
+    {
+        Json::Array(
+            values
+                .into_iter()
+                .enumerate()
+                .map(|(index, value)| match (value, self.scale(index)) {
+                    (Some(value), Some(scale)) => Json::Float(value as f64 / scale as f64),
+                    (Some(value), None) => Json::from(value),
+                    (None, _) => Json::Null,
+                })
+                .collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::{cfg::Settings, process::MetricNamesParser};
+
+    use super::{decimal, BaseUnits};
+
+    #[test]
+    fn test_decimal() {
+        assert_eq!("12", decimal(12_000, 1000));
+        assert_eq!("1.005", decimal(1_005, 1000));
+        assert_eq!("0.25", decimal(250, 1000));
+    }
+
+    #[test]
+    fn test_base_units() {
+        let metrics = MetricNamesParser::new(true)
+            .parse(&["time:cpu+ratio", "mem:rss/mi", "fault:minor"])
+            .unwrap();
+        let values = [Some(1_500), Some(125), Some(4_096), Some(7), None];
+        let mut settings = Settings::new();
+        let mut units = BaseUnits::new(&settings.export);
+        units.open(metrics.iter());
+        assert_eq!(
+            vec!["1500", "125", "4096", "7", ""],
+            units.format(values).collect::<Vec<_>>()
+        );
+        settings.export.base_units = true;
+        let mut units = BaseUnits::new(&settings.export);
+        units.open(metrics.iter());
+        assert_eq!(
+            vec!["1.5", "0.125", "4096", "7", ""],
+            units.format(values).collect::<Vec<_>>()
+        );
+        assert_eq!("[1.5,0.125,4096,7,null]", units.json(values).to_string());
+    }
+}
@@ -22,10 +22,13 @@ extern crate libc;
 use argh::FromArgs;
 use simplelog::{self, SimpleLogger, TermLogger, WriteLogger};
 use std::{
+    env,
     fs::{self, File},
     panic,
     path::{Path, PathBuf},
+    process::Command,
     str::FromStr,
+    time::Duration,
 };
 
 mod application;
@@ -33,19 +36,34 @@ mod cfg;
 mod clock;
 mod console;
 mod display;
+mod dump;
 mod export;
+mod overhead;
+mod privileges;
 mod process;
 mod sighdr;
 
 use application::Application;
 use cfg::{
-    BuiltinTheme, DisplayMode, ExportType, LoggingLevel, LoggingSettings, MetricFormat,
-    LOG_FILE_NAME,
+    AlertSignal, BuiltinTheme, CpuBasis, DisplayMode, ExportType, LoggingLevel, LoggingSettings,
+    MetricFormat, TextLayout, LOG_FILE_NAME,
+};
+use clock::parse_duration;
+use export::{CheckStatus, FileNameTemplate, NagiosExporter, Preview};
+use process::{
+    matchers,
+    parsers::{
+        parse_external_formatter, parse_histogram, parse_intervals, parse_label,
+        parse_metric_column, parse_size, parse_threshold, Threshold,
+    },
+    TargetId,
 };
-use process::{matchers, parsers::parse_size, TargetId};
 
 const APP_NAME: &str = "oprs";
 
+/// Number of samples written with --export-preview.
+const PREVIEW_COUNT: u64 = 2;
+
 //
 // Options
 //
@@ -59,10 +77,17 @@ macro_rules! make_arg_converter {
 }
 
 make_arg_converter!(theme_from_str, BuiltinTheme);
+make_arg_converter!(bell_from_str, AlertSignal);
+make_arg_converter!(cpu_basis_from_str, CpuBasis);
 make_arg_converter!(export_type_from_str, ExportType);
 make_arg_converter!(display_mode_from_str, DisplayMode);
+make_arg_converter!(text_layout_from_str, TextLayout);
 make_arg_converter!(metric_format_from_str, MetricFormat);
 
+fn duration_from_str(arg: &str) -> Result<Duration, String> {
+    parse_duration(arg).map_err(|err| err.to_string())
+}
+
 #[derive(FromArgs, PartialEq, Debug)]
 /// Display metrics of processes.
 /// Without argument, the command prints the available metrics.
@@ -83,7 +108,7 @@ struct Opt {
         option,
         short = 'T',
         from_str_fn(theme_from_str),
-        description = "display theme (light, dark, light16, dark16)"
+        description = "display theme (light, dark, light16, dark16, high-contrast, mono)"
     )]
     theme: Option<BuiltinTheme>,
 
@@ -93,9 +118,104 @@ struct Opt {
     #[argh(
         option,
         short = 'e',
-        description = "delay between two samples (default: 5.0)"
+        from_str_fn(duration_from_str),
+        description = "delay between two samples, ex: 500ms, 2m, 1h30m (default: 5s)"
+    )]
+    every: Option<Duration>,
+
+    #[argh(
+        option,
+        from_str_fn(duration_from_str),
+        description = "delay between two refreshes of the process panes in the terminal (default: same as the samples)"
+    )]
+    details_every: Option<Duration>,
+
+    #[argh(
+        switch,
+        description = "align samples on wall-clock multiples of the delay"
+    )]
+    align: bool,
+
+    #[argh(
+        switch,
+        description = "reload the configuration on SIGHUP instead of exiting"
+    )]
+    reload: bool,
+
+    #[argh(
+        switch,
+        description = "lengthen the delay when sampling takes longer than the delay"
+    )]
+    adaptive: bool,
+
+    #[argh(
+        switch,
+        description = "disable the interactions that affect the processes or the session"
+    )]
+    read_only: bool,
+
+    #[argh(
+        switch,
+        description = "describe the selected process in the status line for screen readers"
+    )]
+    describe: bool,
+
+    #[argh(
+        switch,
+        description = "show the kernel function in which the processes in uninterruptible sleep are blocked"
+    )]
+    wchan: bool,
+
+    #[argh(
+        switch,
+        description = "reduce the columns of the least important metrics to fit in the terminal"
+    )]
+    fit: bool,
+
+    #[argh(
+        switch,
+        description = "show a status in the title of the terminal window"
+    )]
+    title: bool,
+
+    #[argh(
+        option,
+        from_str_fn(bell_from_str),
+        description = "signal alerts to the terminal (none, bell, osc9)"
+    )]
+    bell: Option<AlertSignal>,
+
+    #[argh(
+        option,
+        from_str_fn(duration_from_str),
+        description = "delay between two samples when the process is in the background, the display is paused"
+    )]
+    background: Option<Duration>,
+
+    #[argh(
+        option,
+        description = "fold the processes deeper than this depth in the tree into their ancestor"
     )]
-    every: Option<f64>,
+    max_depth: Option<usize>,
+
+    #[argh(
+        option,
+        from_str_fn(cpu_basis_from_str),
+        description = "basis of the CPU time ratio (all, single)"
+    )]
+    cpu_basis: Option<CpuBasis>,
+
+    #[argh(
+        option,
+        description = "number of samples kept to step through them while the terminal is paused"
+    )]
+    history: Option<usize>,
+
+    #[argh(
+        option,
+        description = "maximum number of frames drawn per second in the terminal (0 for no limit)"
+    )]
+    max_fps: Option<u32>,
 
     #[argh(
         option,
@@ -105,17 +225,36 @@ struct Opt {
     )]
     display: Option<DisplayMode>,
 
+    #[argh(
+        option,
+        from_str_fn(text_layout_from_str),
+        description = "layout in text mode (table, tsv, csv)"
+    )]
+    layout: Option<TextLayout>,
+
+    #[argh(
+        switch,
+        description = "prefix the lines in text mode with the time and the elapsed seconds"
+    )]
+    timestamps: bool,
+
     #[argh(
         option,
         short = 'X',
         from_str_fn(export_type_from_str),
-        description = "export type (none, csv, rrd)"
+        description = "export type (none, csv, tsv, rrd, rrd-graph, exec, statsd, dogstatsd)"
     )]
     export_type: Option<ExportType>,
 
     #[argh(option, short = 'D', description = "export directory")]
     export_dir: Option<String>,
 
+    #[argh(
+        option,
+        description = "name of the export files with fields {{name}}, {{pid}}, {{target}}, {{hostname}} and {{date}}"
+    )]
+    export_template: Option<String>,
+
     #[argh(
         option,
         short = 'S',
@@ -130,6 +269,90 @@ struct Opt {
     )]
     export_count: Option<usize>,
 
+    #[argh(
+        option,
+        description = "delete the export files older than this age when starting (ex: 7d)."
+    )]
+    export_max_age: Option<String>,
+
+    #[argh(
+        option,
+        description = "delete the oldest export files when starting until they fit in this size."
+    )]
+    export_max_total_size: Option<String>,
+
+    #[argh(
+        option,
+        description = "also export the averages over these intervals (for csv, ex: 1m,1h)."
+    )]
+    export_downsample: Option<String>,
+
+    #[argh(
+        option,
+        description = "shell command reading the samples in JSON on its input (for exec)."
+    )]
+    export_command: Option<String>,
+
+    #[argh(
+        switch,
+        description = "print what the export writes for the first two samples and exit."
+    )]
+    export_preview: bool,
+
+    #[argh(
+        option,
+        description = "address of the server (for statsd, default: localhost:8125)."
+    )]
+    export_address: Option<String>,
+
+    #[argh(
+        option,
+        description = "prefix of the gauges (for statsd, default: oprs)."
+    )]
+    export_prefix: Option<String>,
+
+    #[argh(
+        option,
+        description = "minimum delay between two samples sent (for statsd, ex: 10s)."
+    )]
+    export_send_every: Option<String>,
+
+    #[argh(
+        option,
+        description = "delay between two flushes of the CSV files (ex: 10s)."
+    )]
+    export_flush: Option<String>,
+
+    #[argh(
+        option,
+        description = "number of rows written before the CSV files are flushed."
+    )]
+    export_flush_rows: Option<usize>,
+
+    #[argh(
+        switch,
+        description = "export the durations in seconds and the ratios as fractions."
+    )]
+    export_base_units: bool,
+
+    #[argh(
+        option,
+        description = "export an histogram of a metric (metric=bound,...)"
+    )]
+    histogram: Vec<String>,
+
+    #[argh(
+        option,
+        description = "format the values of a metric with a shell command (metric=command)"
+    )]
+    formatter: Vec<String>,
+
+    #[argh(
+        option,
+        description = "alignment, decimals and width of a metric (ex: time:cpu=right,decimals=1,width=6)"
+    )]
+    column: Vec<String>,
+
     #[argh(
         option,
         short = 'U',
@@ -141,16 +364,42 @@ struct Opt {
     #[argh(switch, short = 's', description = "monitor system")]
     system: bool,
 
+    #[argh(
+        switch,
+        description = "monitor system with rows for the CPU states and the memory categories"
+    )]
+    system_breakdown: bool,
+
     #[argh(switch, description = "monitor the command itself")]
     myself: bool,
 
-    #[argh(option, short = 'p', description = "process id")]
-    pid: Vec<i32>,
+    #[argh(
+        switch,
+        description = "add the CPU times and the faults of the exited processes to their parent"
+    )]
+    fold_exited: bool,
+
+    #[argh(
+        switch,
+        description = "count the processes that start and exit between two samples"
+    )]
+    short_lived: bool,
+
+    #[argh(
+        option,
+        short = 'p',
+        description = "process id with optional label (pid[:label])"
+    )]
+    pid: Vec<String>,
 
     #[argh(option, short = 'f', description = "process id file")]
     file: Vec<String>,
 
-    #[argh(option, short = 'n', description = "process name")]
+    #[argh(
+        option,
+        short = 'n',
+        description = "process name with optional label (name[:label])"
+    )]
     name: Vec<String>,
 
     #[argh(
@@ -160,6 +409,45 @@ struct Opt {
     )]
     glob: Vec<String>,
 
+    #[argh(
+        option,
+        description = "never show the processes with a name matching a pattern in system-wide mode"
+    )]
+    exclude_name: Vec<String>,
+
+    #[argh(
+        option,
+        description = "never show the processes of a user in system-wide mode"
+    )]
+    exclude_user: Vec<String>,
+
+    #[argh(
+        option,
+        description = "wait for processes with a name matching a pattern (ex: nginx*)"
+    )]
+    wait_for_name: Vec<String>,
+
+    #[argh(
+        option,
+        description = "processes satisfying all the conditions (ex: name=java,user=tomcat)"
+    )]
+    r#match: Vec<String>,
+
+    #[argh(option, description = "processes that have the file open or mapped")]
+    watch_file: Vec<String>,
+
+    #[argh(
+        option,
+        description = "processes in the PID namespace of a process or a nsfs path"
+    )]
+    pidns: Vec<String>,
+
+    #[argh(
+        option,
+        description = "processes of a systemd unit (ex: nginx.service)"
+    )]
+    unit: Vec<String>,
+
     #[argh(
         option,
         short = 'r',
@@ -167,10 +455,139 @@ struct Opt {
     )]
     root: Option<i32>,
 
+    #[argh(
+        option,
+        description = "user to switch to after startup when run as root (user[:group])"
+    )]
+    user: Option<String>,
+
+    #[argh(
+        option,
+        description = "publish the samples on a Unix socket for followers"
+    )]
+    share: Option<String>,
+
+    #[argh(
+        option,
+        description = "copy the procfs files of the monitored processes in a directory to replay them in tests"
+    )]
+    capture_procfs: Option<String>,
+
+    #[argh(
+        switch,
+        description = "log the input events in the terminal with the resulting actions"
+    )]
+    record_input: bool,
+
+    #[argh(option, description = "replay the input events recorded in a log file")]
+    replay_input: Option<String>,
+
     #[argh(positional, description = "metric to monitor")]
     metric: Vec<String>,
+
+    #[argh(subcommand)]
+    command: Option<SubCommand>,
+}
+
+#[derive(FromArgs, PartialEq, Debug)]
+#[argh(subcommand)]
+enum SubCommand {
+    Config(ConfigCommand),
+    Check(CheckCommand),
+    Follow(FollowCommand),
+}
+
+#[derive(FromArgs, PartialEq, Debug)]
+/// Display the samples shared by another instance.
+#[argh(subcommand, name = "follow")]
+struct FollowCommand {
+    #[argh(positional, description = "socket of the shared session")]
+    socket: String,
+}
+
+#[derive(FromArgs, PartialEq, Debug)]
+/// Check thresholds on processes as a Nagios plugin.
+#[argh(subcommand, name = "check")]
+struct CheckCommand {
+    #[argh(option, short = 'p', description = "process id")]
+    pid: Vec<String>,
+
+    #[argh(option, short = 'f', description = "process id file")]
+    file: Vec<String>,
+
+    #[argh(option, short = 'n', description = "process name")]
+    name: Vec<String>,
+
+    #[argh(
+        option,
+        description = "warning threshold on a raw value (metric>limit or metric<limit)"
+    )]
+    warn: Vec<String>,
+
+    #[argh(
+        option,
+        description = "critical threshold on a raw value (metric>limit or metric<limit)"
+    )]
+    crit: Vec<String>,
+
+    #[argh(
+        option,
+        short = 'c',
+        default = "1",
+        description = "number of samples, the last one is checked (default: 1)"
+    )]
+    count: u64,
+
+    #[argh(
+        option,
+        short = 'e',
+        from_str_fn(duration_from_str),
+        default = "Duration::from_secs(1)",
+        description = "delay between two samples (default: 1s)"
+    )]
+    every: Duration,
+}
+
+#[derive(FromArgs, PartialEq, Debug)]
+/// Manage the configuration file.
+#[argh(subcommand, name = "config")]
+struct ConfigCommand {
+    #[argh(subcommand)]
+    action: ConfigAction,
+}
+
+#[derive(FromArgs, PartialEq, Debug)]
+#[argh(subcommand)]
+enum ConfigAction {
+    Init(ConfigInit),
+    Show(ConfigShow),
+    Edit(ConfigEdit),
+    Path(ConfigPath),
 }
 
+#[derive(FromArgs, PartialEq, Debug)]
+/// Write a commented configuration file with the default values.
+#[argh(subcommand, name = "init")]
+struct ConfigInit {
+    #[argh(switch, description = "replace an existing file")]
+    force: bool,
+}
+
+#[derive(FromArgs, PartialEq, Debug)]
+/// Print the effective configuration.
+#[argh(subcommand, name = "show")]
+struct ConfigShow {}
+
+#[derive(FromArgs, PartialEq, Debug)]
+/// Open the configuration file in $EDITOR.
+#[argh(subcommand, name = "edit")]
+struct ConfigEdit {}
+
+#[derive(FromArgs, PartialEq, Debug)]
+/// Print the path of the configuration file.
+#[argh(subcommand, name = "path")]
+struct ConfigPath {}
+
 //
 // Logging
 //
@@ -235,6 +652,241 @@ fn configure_logging(settings: &LoggingSettings) {
     });
 }
 
+/// Target for a process id with an optional label.
+fn pid_target(spec: &str) -> anyhow::Result<TargetId> {
+    let (pid, label) = parse_label(spec);
+    let pid = pid
+        .parse::<libc::pid_t>()
+        .map_err(|_| anyhow::anyhow!("{spec}: invalid process id"))?;
+    Ok(TargetId::Pid(pid, label.map(str::to_string)))
+}
+
+//
+// Check command
+//
+
+fn parse_thresholds(specs: &[String]) -> anyhow::Result<Vec<Threshold>> {
+    specs
+        .iter()
+        .map(|spec| parse_threshold(spec).map_err(|err| anyhow::anyhow!("{spec}: {err}")))
+        .collect()
+}
+
+/// Sample the processes and check the thresholds.
+fn run_check(cmd: &CheckCommand) -> anyhow::Result<NagiosExporter> {
+    let warnings = parse_thresholds(&cmd.warn)?;
+    let criticals = parse_thresholds(&cmd.crit)?;
+    if warnings.is_empty() && criticals.is_empty() {
+        return Err(anyhow::anyhow!("no threshold"));
+    }
+    let mut target_ids = Vec::new();
+    for spec in &cmd.pid {
+        target_ids.push(pid_target(spec)?);
+    }
+    for pid_file in &cmd.file {
+        target_ids.push(TargetId::PidFile(PathBuf::from(pid_file)));
+    }
+    for spec in &cmd.name {
+        let (name, label) = parse_label(spec);
+        target_ids.push(TargetId::ProcessName(
+            name.to_string(),
+            label.map(str::to_string),
+        ));
+    }
+    if target_ids.is_empty() {
+        return Err(anyhow::anyhow!("no process specified"));
+    }
+    let mut checker = NagiosExporter::new(&warnings, &criticals);
+    application::check(&target_ids, &mut checker, cmd.count.max(1), cmd.every)?;
+    Ok(checker)
+}
+
+/// Print the plugin output and return the status.
+fn check_command(cmd: &CheckCommand) -> CheckStatus {
+    match run_check(cmd) {
+        Ok(checker) => {
+            println!("{}", checker.report());
+            checker.status()
+        }
+        Err(err) => {
+            println!("OPRS {} - {err}", CheckStatus::Unknown.as_str());
+            CheckStatus::Unknown
+        }
+    }
+}
+
+//
+// Configuration command
+//
+
+/// Default editor if $VISUAL and $EDITOR are not set.
+const DEFAULT_EDITOR: &str = "vi";
+
+fn config_command(action: &ConfigAction) -> anyhow::Result<()> {
+    let dirs = cfg::Directories::new(APP_NAME)?;
+    match action {
+        ConfigAction::Init(init) => {
+            let path = dirs.write_default_config_file(LOG_FILE_NAME, init.force)?;
+            println!("{}", path.display());
+        }
+        ConfigAction::Show(_) => print!("{}", dirs.read_config_file(LOG_FILE_NAME)?),
+        ConfigAction::Edit(_) => {
+            let mut path = dirs.config_file(LOG_FILE_NAME);
+            if !path.exists() {
+                path = dirs.write_default_config_file(LOG_FILE_NAME, false)?;
+            }
+            let editor = env::var("VISUAL")
+                .or_else(|_| env::var("EDITOR"))
+                .unwrap_or_else(|_| DEFAULT_EDITOR.to_string());
+            let mut words = editor.split_whitespace();
+            let program = words.next().unwrap_or(DEFAULT_EDITOR);
+            let status = Command::new(program).args(words).arg(&path).status()?;
+            if !status.success() {
+                return Err(anyhow::anyhow!("{editor}: editor failed ({status})"));
+            }
+            // Check the file once modified.
+            dirs.read_config_file(LOG_FILE_NAME)
+                .map_err(|err| anyhow::anyhow!("{}: {err}", path.display()))?;
+        }
+        ConfigAction::Path(_) => println!("{}", dirs.config_file(LOG_FILE_NAME).display()),
+    }
+    Ok(())
+}
+
+//
+// Sanity check
+//
+
+/// Name of the options that are set.
+fn options_set<'a>(options: &[(&'a str, bool)]) -> Vec<&'a str> {
+    options
+        .iter()
+        .filter_map(|(name, is_set)| if *is_set { Some(*name) } else { None })
+        .collect()
+}
+
+/// Reject the combinations of options that are meaningless once merged with
+/// the configuration.
+fn check_options(opt: &Opt, settings: &cfg::Settings) -> anyhow::Result<()> {
+    if matches!(settings.export.kind, ExportType::None) {
+        let export_options = options_set(&[
+            ("--export-dir", opt.export_dir.is_some()),
+            ("--export-template", opt.export_template.is_some()),
+            ("--export-size", opt.export_size.is_some()),
+            ("--export-count", opt.export_count.is_some()),
+            ("--export-max-age", opt.export_max_age.is_some()),
+            (
+                "--export-max-total-size",
+                opt.export_max_total_size.is_some(),
+            ),
+            ("--export-downsample", opt.export_downsample.is_some()),
+            ("--export-command", opt.export_command.is_some()),
+            ("--export-preview", opt.export_preview),
+            ("--export-address", opt.export_address.is_some()),
+            ("--export-prefix", opt.export_prefix.is_some()),
+            ("--export-send-every", opt.export_send_every.is_some()),
+            ("--export-flush", opt.export_flush.is_some()),
+            ("--export-flush-rows", opt.export_flush_rows.is_some()),
+            ("--export-base-units", opt.export_base_units),
+            ("--histogram", !opt.histogram.is_empty()),
+        ]);
+        if let Some(name) = export_options.first() {
+            return Err(anyhow::anyhow!(
+                "{name}: option requires an export type (-X)"
+            ));
+        }
+    }
+    let (mode_options, mode) = match settings.display.mode {
+        DisplayMode::None => (
+            options_set(&[
+                ("--layout", opt.layout.is_some()),
+                ("--timestamps", opt.timestamps),
+                ("--theme", opt.theme.is_some()),
+                ("--describe", opt.describe),
+                ("--fit", opt.fit),
+                ("--title", opt.title),
+                ("--bell", opt.bell.is_some()),
+                ("--history", opt.history.is_some()),
+                ("--max-fps", opt.max_fps.is_some()),
+                ("--details-every", opt.details_every.is_some()),
+                ("--record-input", opt.record_input),
+                ("--replay-input", opt.replay_input.is_some()),
+            ]),
+            "none",
+        ),
+        DisplayMode::Text => (
+            options_set(&[
+                ("--theme", opt.theme.is_some()),
+                ("--describe", opt.describe),
+                ("--fit", opt.fit),
+                ("--title", opt.title),
+                ("--bell", opt.bell.is_some()),
+                ("--history", opt.history.is_some()),
+                ("--max-fps", opt.max_fps.is_some()),
+                ("--details-every", opt.details_every.is_some()),
+                ("--record-input", opt.record_input),
+                ("--replay-input", opt.replay_input.is_some()),
+            ]),
+            "text",
+        ),
+        DisplayMode::Terminal => (
+            options_set(&[
+                ("--layout", opt.layout.is_some()),
+                ("--timestamps", opt.timestamps),
+            ]),
+            "term",
+        ),
+        DisplayMode::Any => (Vec::new(), "any"),
+    };
+    if let Some(name) = mode_options.first() {
+        return Err(anyhow::anyhow!(
+            "{name}: option has no effect with display mode {mode}"
+        ));
+    }
+    if matches!(settings.display.mode, DisplayMode::None)
+        && matches!(settings.export.kind, ExportType::None)
+        && opt.share.is_none()
+        && opt.capture_procfs.is_none()
+    {
+        return Err(anyhow::anyhow!(
+            "display mode none requires an export, --share or --capture-procfs"
+        ));
+    }
+    let log_file = opt
+        .log_file
+        .as_ref()
+        .map(PathBuf::from)
+        .or_else(|| settings.logging.file.clone());
+    if opt.record_input && log_file.is_none() {
+        return Err(anyhow::anyhow!(
+            "--record-input: option requires a log file (-L)"
+        ));
+    }
+    if log_file.is_some() && log_file == opt.replay_input.as_ref().map(PathBuf::from) {
+        return Err(anyhow::anyhow!(
+            "--replay-input: the log file is rotated on startup, replay the backup file"
+        ));
+    }
+    if opt.system && opt.system_breakdown {
+        return Err(anyhow::anyhow!(
+            "--system and --system-breakdown are mutually exclusive"
+        ));
+    }
+    let mut pids = Vec::new();
+    if opt.myself {
+        pids.push(std::process::id() as libc::pid_t);
+    }
+    for spec in &opt.pid {
+        if let TargetId::Pid(pid, _) = pid_target(spec)? {
+            if pids.contains(&pid) {
+                return Err(anyhow::anyhow!("{pid}: process specified twice"));
+            }
+            pids.push(pid);
+        }
+    }
+    Ok(())
+}
+
 //
 // Main
 //
@@ -259,12 +911,75 @@ fn start(opt: Opt) -> anyhow::Result<()> {
 
     // Override config file with command line
     override_parameter!(settings.display.mode, opt.display);
+    override_parameter!(settings.display.layout, opt.layout);
     override_parameter!(settings.display.every, opt.every);
+    override_parameter!(
+        settings.display.details_every,
+        opt.details_every,
+        every,
+        Some(every)
+    );
+    if opt.timestamps {
+        settings.display.timestamps = true;
+    }
+    if opt.align {
+        settings.display.align = true;
+    }
+    if opt.reload {
+        settings.display.reload = true;
+    }
+    if opt.adaptive {
+        settings.display.adaptive = true;
+    }
+    if opt.read_only {
+        settings.display.read_only = true;
+    }
+    if opt.describe {
+        settings.display.describe = true;
+    }
+    if opt.wchan {
+        settings.display.wchan = true;
+    }
+    if opt.short_lived {
+        settings.notifications.short_lived = true;
+    }
+    if opt.fold_exited {
+        settings.targets.fold_exited = true;
+    }
+    if opt.fit {
+        settings.display.fit = true;
+    }
+    if opt.title {
+        settings.display.title = true;
+    }
+    override_parameter!(settings.display.bell, opt.bell);
+    override_parameter!(
+        settings.display.background,
+        opt.background,
+        delay,
+        Some(delay)
+    );
+    override_parameter!(
+        settings.display.max_depth,
+        opt.max_depth,
+        depth,
+        Some(depth)
+    );
+    override_parameter!(settings.display.cpu_basis, opt.cpu_basis);
+    override_parameter!(settings.display.history, opt.history);
+    override_parameter!(settings.display.max_fps, opt.max_fps);
     override_parameter!(settings.display.format, opt.format);
     override_parameter!(settings.display.count, opt.count, count, Some(count));
     override_parameter!(settings.display.theme, opt.theme, theme, Some(theme));
     override_parameter!(settings.export.kind, opt.export_type);
+    check_options(&opt, &settings)?;
     override_parameter!(settings.export.dir, opt.export_dir, dir, PathBuf::from(dir));
+    override_parameter!(
+        settings.export.template,
+        opt.export_template,
+        template,
+        FileNameTemplate::from_str(&template)?
+    );
     override_parameter!(
         settings.export.size,
         opt.export_size,
@@ -272,6 +987,99 @@ fn start(opt: Opt) -> anyhow::Result<()> {
         Some(parse_size(&size)?)
     );
     override_parameter!(settings.export.count, opt.export_count, count, Some(count));
+    override_parameter!(
+        settings.export.max_age,
+        opt.export_max_age,
+        age,
+        Some(parse_duration(&age)?)
+    );
+    override_parameter!(
+        settings.export.max_total_size,
+        opt.export_max_total_size,
+        size,
+        Some(parse_size(&size)?)
+    );
+    override_parameter!(
+        settings.export.downsample,
+        opt.export_downsample,
+        intervals,
+        parse_intervals(&intervals)?
+    );
+    override_parameter!(
+        settings.export.command,
+        opt.export_command,
+        command,
+        Some(command)
+    );
+    override_parameter!(
+        settings.export.address,
+        opt.export_address,
+        address,
+        Some(address)
+    );
+    override_parameter!(
+        settings.export.prefix,
+        opt.export_prefix,
+        prefix,
+        Some(prefix)
+    );
+    override_parameter!(
+        settings.export.send_every,
+        opt.export_send_every,
+        every,
+        Some(parse_duration(&every)?)
+    );
+    override_parameter!(
+        settings.export.flush,
+        opt.export_flush,
+        flush,
+        Some(parse_duration(&flush)?)
+    );
+    override_parameter!(
+        settings.export.flush_rows,
+        opt.export_flush_rows,
+        rows,
+        Some(rows)
+    );
+    if opt.export_base_units {
+        settings.export.base_units = true;
+    }
+    for spec in &opt.histogram {
+        settings.export.histograms.push(parse_histogram(spec)?);
+    }
+    // The files are written in a temporary directory and printed at the end.
+    let preview = if opt.export_preview {
+        if !settings.export.kind.has_files() {
+            return Err(anyhow::anyhow!(
+                "--export-preview: export type {} doesn't write files",
+                settings.export.kind.as_str()
+            ));
+        }
+        let preview = Preview::new()?;
+        let export_dir = std::mem::replace(&mut settings.export.dir, preview.dir().to_path_buf());
+        settings.display.mode = DisplayMode::None;
+        settings.display.count = Some(PREVIEW_COUNT);
+        Some((preview, export_dir))
+    } else {
+        None
+    };
+    for spec in &opt.formatter {
+        let (id, command) = parse_external_formatter(spec)?;
+        settings.formatters.set(id, command);
+    }
+    for spec in &opt.column {
+        let (id, column) = parse_metric_column(spec)?;
+        settings.columns.set(id, column);
+    }
+    settings
+        .exclusion
+        .names
+        .extend(opt.exclude_name.iter().cloned());
+    settings
+        .exclusion
+        .users
+        .extend(opt.exclude_user.iter().cloned());
+    override_parameter!(settings.privileges.user, opt.user, user, Some(user));
 
     override_parameter!(
         settings.logging.file,
@@ -282,39 +1090,85 @@ fn start(opt: Opt) -> anyhow::Result<()> {
 
     if opt.debug {
         settings.logging.level = LoggingLevel::Debug;
-    } else if opt.verbose {
+    } else if opt.verbose
+        || (opt.record_input
+            && matches!(
+                settings.logging.level,
+                LoggingLevel::Warning | LoggingLevel::Error
+            ))
+    {
         settings.logging.level = LoggingLevel::Info;
     }
 
     // Add targets
     let mut target_ids = Vec::new();
-    if opt.system {
+    if opt.system_breakdown || (opt.system && settings.targets.system_breakdown) {
+        target_ids.push(TargetId::SystemBreakdown);
+    } else if opt.system {
         target_ids.push(TargetId::System);
     }
     if opt.myself {
-        target_ids.push(TargetId::Pid(std::process::id() as libc::pid_t));
+        target_ids.push(TargetId::Pid(std::process::id() as libc::pid_t, None));
     }
-    for pid in opt.pid {
-        target_ids.push(TargetId::Pid(pid));
+    for spec in opt.pid {
+        target_ids.push(pid_target(&spec)?);
     }
     for pid_file in opt.file {
         let path = PathBuf::from(pid_file.as_str());
         target_ids.push(TargetId::PidFile(path));
     }
-    for name in opt.name {
-        target_ids.push(TargetId::ProcessName(name));
+    for spec in opt.name {
+        let (name, label) = parse_label(&spec);
+        target_ids.push(TargetId::ProcessName(
+            name.to_string(),
+            label.map(str::to_string),
+        ));
     }
     if !opt.glob.is_empty() {
         matchers::glob(&opt.glob)?
             .iter()
-            .for_each(|name| target_ids.push(TargetId::ProcessName(name.to_string())));
+            .for_each(|name| target_ids.push(TargetId::ProcessName(name.to_string(), None)));
+    }
+    for pattern in opt.wait_for_name {
+        target_ids.push(TargetId::NamePattern(pattern));
+    }
+    for spec in opt.r#match {
+        target_ids.push(TargetId::Conditions(spec));
+    }
+    for path in opt.watch_file {
+        target_ids.push(TargetId::File(PathBuf::from(path)));
+    }
+    for pidns in opt.pidns {
+        let path = match pidns.parse::<libc::pid_t>() {
+            Ok(pid) => PathBuf::from(format!("/proc/{pid}/ns/pid")),
+            Err(_) => PathBuf::from(pidns),
+        };
+        target_ids.push(TargetId::PidNamespace(path));
+    }
+    for unit in opt.unit {
+        target_ids.push(TargetId::Unit(unit));
     }
     let metric_names = if opt.metric.is_empty() {
         vec!["time:cpu-raw+ratio", "mem:vm", "time:elapsed"]
     } else {
         opt.metric.iter().map(String::as_str).collect::<Vec<&str>>()
     };
-    let app = Application::new(&settings, &metric_names)?;
+    let mut app = Application::new(&settings, &metric_names)?;
+    if let Some(share) = opt.share {
+        app.set_share(PathBuf::from(share));
+    }
+    if let Some(dir) = opt.capture_procfs {
+        app.set_capture(PathBuf::from(dir));
+    }
+    app.set_input_recording(opt.record_input, opt.replay_input.map(PathBuf::from));
+    if settings.display.reload {
+        let every = opt.every;
+        app.set_reloader(Box::new(move || {
+            let mut settings = cfg::Directories::new(APP_NAME)?.read_config_file(LOG_FILE_NAME)?;
+            override_parameter!(settings.display.every, every);
+            Ok(settings)
+        }));
+    }
     configure_logging(&settings.logging);
     let must_print_backtrace = opt.debug;
 
@@ -344,6 +1198,9 @@ fn start(opt: Opt) -> anyhow::Result<()> {
             eprintln!("{err}");
         }
     }
+    if let Some((preview, export_dir)) = preview {
+        preview.print(&mut std::io::stdout().lock(), &export_dir)?;
+    }
     Ok(())
 }
 
@@ -355,10 +1212,88 @@ fn main() {
     }
 
     let opt: Opt = argh::from_env();
-    if opt.list {
+    let res = if let Some(SubCommand::Config(ref config)) = opt.command {
+        config_command(&config.action)
+    } else if let Some(SubCommand::Check(ref check)) = opt.command {
+        std::process::exit(check_command(check).exit_code());
+    } else if let Some(SubCommand::Follow(ref follow)) = opt.command {
+        export::follow(Path::new(&follow.socket))
+    } else if opt.list {
         application::list_metrics();
-    } else if let Err(err) = start(opt) {
+        Ok(())
+    } else {
+        start(opt)
+    };
+    if let Err(err) = res {
         eprintln!("{err}");
         std::process::exit(1);
     }
 }
+
+#[cfg(test)]
+mod test {
+
+    use argh::FromArgs;
+
+    use super::{cfg::Settings, check_options, DisplayMode, ExportType, Opt};
+
+    fn check(args: &[&str], mode: DisplayMode, kind: ExportType) -> Result<(), String> {
+        let opt = Opt::from_args(&["oprs"], args).unwrap();
+        let mut settings = Settings::new();
+        settings.display.mode = mode;
+        settings.export.kind = kind;
+        check_options(&opt, &settings).map_err(|err| err.to_string())
+    }
+
+    #[test]
+    fn test_check_options() {
+        let (any, text, term, none) = (
+            DisplayMode::Any,
+            DisplayMode::Text,
+            DisplayMode::Terminal,
+            DisplayMode::None,
+        );
+        assert!(check(&["-p", "1", "-p", "2"], any, ExportType::None).is_ok());
+        assert!(check(&["-D", "/tmp"], any, ExportType::Csv).is_ok());
+        assert_eq!(
+            Err("--export-dir: option requires an export type (-X)".to_string()),
+            check(&["-D", "/tmp"], any, ExportType::None)
+        );
+        assert!(check(&["--histogram", "mem:vm=1"], text, ExportType::None).is_err());
+        assert!(check(&["--export-base-units"], any, ExportType::None).is_err());
+        assert!(check(&["--export-base-units"], none, ExportType::Statsd).is_ok());
+        assert_eq!(
+            Err("--theme: option has no effect with display mode text".to_string()),
+            check(&["-T", "dark"], text, ExportType::None)
+        );
+        assert!(check(&["--timestamps"], term, ExportType::None).is_err());
+        assert!(check(&["--timestamps"], text, ExportType::None).is_ok());
+        assert!(check(&["-c", "3"], none, ExportType::None).is_err());
+        assert!(check(&["-c", "3"], none, ExportType::Csv).is_ok());
+        assert!(check(&["--share", "/tmp/s"], none, ExportType::None).is_ok());
+        assert!(check(&["-s", "--system-breakdown"], any, ExportType::None).is_err());
+        assert_eq!(
+            Err("1: process specified twice".to_string()),
+            check(&["-p", "1", "-p", "1:init"], any, ExportType::None)
+        );
+        assert_eq!(
+            Err("--record-input: option requires a log file (-L)".to_string()),
+            check(&["--record-input"], term, ExportType::None)
+        );
+        assert!(check(
+            &["--record-input", "-L", "/tmp/oprs.log"],
+            term,
+            ExportType::None
+        )
+        .is_ok());
+        assert!(check(&["--replay-input", "/tmp/oprs.log"], text, ExportType::None).is_err());
+        assert!(check(
+            &["-L", "/tmp/oprs.log", "--replay-input", "/tmp/oprs.log"],
+            term,
+            ExportType::None
+        )
+        .is_err());
+        let myself = std::process::id().to_string();
+        assert!(check(&["--myself", "-p", &myself], any, ExportType::None).is_err());
+    }
+}
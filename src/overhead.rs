@@ -0,0 +1,280 @@
+// Oprs -- process monitor for Linux
+// Copyright (C) 2025  Laurent Pelecq
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Resources used by oprs itself.
+//!
+//! With feature `alloc-stats`, the allocations are counted by a wrapper
+//! around the system allocator.
+
+use std::{
+    mem::MaybeUninit,
+    time::{Duration, Instant},
+};
+
+#[cfg(feature = "alloc-stats")]
+mod counting {
+    use std::{
+        alloc::{GlobalAlloc, Layout, System},
+        sync::atomic::{AtomicU64, Ordering},
+    };
+
+    pub(super) static ALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+    pub(super) static ALLOCATED_BYTES: AtomicU64 = AtomicU64::new(0);
+
+    /// System allocator counting the allocations.
+    struct CountingAllocator;
+
+    unsafe impl GlobalAlloc for CountingAllocator {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+            ALLOCATED_BYTES.fetch_add(layout.size() as u64, Ordering::Relaxed);
+            System.alloc(layout)
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            System.dealloc(ptr, layout)
+        }
+    }
+
+    #[global_allocator]
+    static ALLOCATOR: CountingAllocator = CountingAllocator;
+}
+
+/// Number of allocations and allocated bytes since the start.
+#[cfg(feature = "alloc-stats")]
+fn allocations() -> Option<(u64, u64)> {
+    use std::sync::atomic::Ordering;
+    Some((
+        counting::ALLOCATIONS.load(Ordering::Relaxed),
+        counting::ALLOCATED_BYTES.load(Ordering::Relaxed),
+    ))
+}
+
+/// Allocations are not counted without the instrumented allocator.
+#[cfg(not(feature = "alloc-stats"))]
+fn allocations() -> Option<(u64, u64)> {
+    None
+}
+
+/// User and system CPU time of the current process.
+fn cpu_time() -> Duration {
+    let mut usage = MaybeUninit::<libc::rusage>::uninit();
+    if unsafe { libc::getrusage(libc::RUSAGE_SELF, usage.as_mut_ptr()) } != 0 {
+        return Duration::ZERO;
+    }
+    let usage = unsafe { usage.assume_init() };
+    let to_duration = |tv: libc::timeval| Duration::new(tv.tv_sec as u64, tv.tv_usec as u32 * 1000);
+    to_duration(usage.ru_utime) + to_duration(usage.ru_stime)
+}
+
+/// Statistics on the resources used by oprs.
+///
+/// They are only displayed in the terminal.
+#[derive(Clone, Copy, Debug, Default)]
+#[cfg_attr(not(feature = "tui"), allow(dead_code))]
+pub struct Overhead {
+    /// Number of samples since the start.
+    pub samples: u64,
+    /// Elapsed time since the start.
+    pub elapsed: Duration,
+    /// CPU time since the start.
+    pub cpu_time: Duration,
+    /// Fraction of the last interval spent on the CPU.
+    pub cpu_ratio: f64,
+    /// Number of allocations and allocated bytes, if counted.
+    pub allocations: Option<(u64, u64)>,
+}
+
+impl Overhead {
+    /// Number of samples per second.
+    #[cfg(feature = "tui")]
+    pub fn sample_rate(&self) -> f64 {
+        let elapsed = self.elapsed.as_secs_f64();
+        if elapsed > 0.0 {
+            self.samples as f64 / elapsed
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Reaction when oprs uses more CPU than the limit.
+#[derive(Debug, PartialEq)]
+pub enum Guardrail {
+    /// Notify the message.
+    Warn(String),
+    /// Notify the message and lengthen the interval.
+    Lengthen(String, Duration),
+}
+
+/// Measure the resources used by oprs and warn if it uses too much CPU.
+pub struct OverheadMonitor {
+    start: Instant,
+    /// Time and CPU time at the last sample.
+    last: Option<(Instant, Duration)>,
+    /// Maximum fraction of the interval spent on the CPU.
+    limit: Option<f64>,
+    adaptive: bool,
+    notification_time: Option<Instant>,
+    notification_delay: u64,
+    overhead: Overhead,
+}
+
+impl OverheadMonitor {
+    /// New monitor with an optional CPU limit and a delay between two notifications.
+    ///
+    /// If adaptive, the interval is lengthened when the limit is exceeded.
+    pub fn new(limit: Option<f64>, adaptive: bool, notification_delay: u64) -> Self {
+        Self {
+            start: Instant::now(),
+            last: None,
+            limit,
+            adaptive,
+            notification_time: None,
+            notification_delay,
+            overhead: Overhead::default(),
+        }
+    }
+
+    pub fn overhead(&self) -> &Overhead {
+        &self.overhead
+    }
+
+    /// Record a sample. Return a guardrail if the limit is exceeded.
+    ///
+    /// The first sample, that includes the startup, is not checked. Without adaptive delay, warnings are not repeated until the notification delay elapsed.
+    pub fn update(&mut self, delay: Duration) -> Option<Guardrail> {
+        self.record(Instant::now(), cpu_time(), allocations(), delay)
+    }
+
+    fn record(
+        &mut self,
+        now: Instant,
+        cpu_time: Duration,
+        allocations: Option<(u64, u64)>,
+        delay: Duration,
+    ) -> Option<Guardrail> {
+        let cpu_ratio = match self.last {
+            Some((last_time, last_cpu_time)) if now > last_time => {
+                let elapsed = now.saturating_duration_since(last_time);
+                cpu_time.saturating_sub(last_cpu_time).as_secs_f64() / elapsed.as_secs_f64()
+            }
+            _ => 0.0,
+        };
+        self.last = Some((now, cpu_time));
+        self.overhead = Overhead {
+            samples: self.overhead.samples + 1,
+            elapsed: now.saturating_duration_since(self.start),
+            cpu_time,
+            cpu_ratio,
+            allocations,
+        };
+        match self.limit {
+            Some(limit) if cpu_ratio > limit && !delay.is_zero() => {
+                let percent = cpu_ratio * 100.0;
+                if self.adaptive {
+                    let new_delay = delay * 2;
+                    let msg = format!(
+                        "oprs used {percent:.1}% of the CPU, interval lengthened to {}ms",
+                        new_delay.as_millis()
+                    );
+                    log::warn!("{msg}");
+                    Some(Guardrail::Lengthen(msg, new_delay))
+                } else {
+                    let must_notify = self
+                        .notification_time
+                        .map(|time| now.saturating_duration_since(time))
+                        .map(|elapsed| elapsed.as_secs() >= self.notification_delay)
+                        .unwrap_or(true);
+                    must_notify.then(|| {
+                        self.notification_time = Some(now);
+                        let msg = format!(
+                            "oprs used {percent:.1}% of the CPU, more than {:.1}%",
+                            limit * 100.0
+                        );
+                        log::warn!("{msg}");
+                        Guardrail::Warn(msg)
+                    })
+                }
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::time::Duration;
+
+    use super::{Guardrail, OverheadMonitor};
+
+    #[test]
+    fn test_overhead() {
+        let delay = Duration::from_secs(1);
+        let mut monitor = OverheadMonitor::new(Some(0.1), false, 60);
+        let start = monitor.start;
+        let cpu_time = Duration::from_secs(3);
+        // The startup is not checked.
+        let res = monitor.record(start, cpu_time, None, delay);
+        assert!(res.is_none());
+        assert_eq!(0.0, monitor.overhead().cpu_ratio);
+        let now = start + Duration::from_secs(2);
+        let res = monitor.record(now, cpu_time + Duration::from_millis(100), None, delay);
+        assert!(res.is_none());
+        let overhead = monitor.overhead();
+        assert_eq!(2, overhead.samples);
+        assert!((overhead.cpu_ratio - 0.05).abs() < 1e-9);
+        #[cfg(feature = "tui")]
+        assert!(overhead.sample_rate() > 0.0);
+        // Above the limit, the interval is only lengthened if adaptive.
+        let now = now + Duration::from_secs(1);
+        let res = monitor.record(
+            now,
+            cpu_time + Duration::from_millis(400),
+            Some((3, 64)),
+            delay,
+        );
+        assert_eq!(
+            Some(Guardrail::Warn(
+                "oprs used 30.0% of the CPU, more than 10.0%".to_string()
+            )),
+            res
+        );
+        assert!((monitor.overhead().cpu_ratio - 0.3).abs() < 1e-9);
+        assert_eq!(Some((3, 64)), monitor.overhead().allocations);
+        // The warning is not repeated.
+        let now = now + Duration::from_secs(1);
+        let res = monitor.record(now, cpu_time + Duration::from_millis(700), None, delay);
+        assert!(res.is_none());
+        monitor.adaptive = true;
+        let now = now + Duration::from_secs(1);
+        let res = monitor.record(now, cpu_time + Duration::from_millis(1000), None, delay);
+        assert!(
+            matches!(res, Some(Guardrail::Lengthen(_, delay)) if delay == Duration::from_secs(2))
+        );
+        // Without limit.
+        let mut monitor = OverheadMonitor::new(None, true, 60);
+        monitor.record(start, cpu_time, None, delay);
+        let res = monitor.record(
+            start + Duration::from_secs(1),
+            cpu_time + Duration::from_secs(1),
+            None,
+            delay,
+        );
+        assert!(res.is_none());
+    }
+}
@@ -0,0 +1,325 @@
+// Oprs -- process monitor for Linux
+// Copyright (C) 2025  Laurent Pelecq
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use libc::{gid_t, uid_t};
+#[cfg(feature = "tui")]
+use std::ffi::CStr;
+use std::{
+    ffi::CString, io, mem::MaybeUninit, os::unix::process::CommandExt, process::Command, ptr,
+};
+
+/// Capability to read other processes' files in /proc.
+const CAP_SYS_PTRACE: u32 = 19;
+
+/// Version 3 of the capability structures (64 bits capabilities).
+const LINUX_CAPABILITY_VERSION_3: u32 = 0x2008_0522;
+
+/// Size of the buffer for getpwnam_r and getgrnam_r.
+const NAME_BUFFER_SIZE: usize = 16384;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("{0}: unknown user")]
+    UnknownUser(String),
+    #[error("{0}: unknown group")]
+    UnknownGroup(String),
+    #[error("privileges can only be dropped when running as root")]
+    NotRoot,
+    #[error("{0}: {1}")]
+    SystemCall(&'static str, io::Error),
+}
+
+pub type PrivilegeResult<T> = Result<T, Error>;
+
+/// Header for capget and capset.
+#[repr(C)]
+struct CapUserHeader {
+    version: u32,
+    pid: libc::c_int,
+}
+
+/// Capability sets for capget and capset.
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+struct CapUserData {
+    effective: u32,
+    permitted: u32,
+    inheritable: u32,
+}
+
+/// Return an error with the last OS error if the result is negative.
+fn check_call<T>(name: &'static str, res: T) -> PrivilegeResult<T>
+where
+    T: PartialOrd + Default,
+{
+    if res < T::default() {
+        Err(Error::SystemCall(name, io::Error::last_os_error()))
+    } else {
+        Ok(res)
+    }
+}
+
+/// User and group to switch to.
+#[derive(Debug, PartialEq, Eq)]
+pub struct UserSpec {
+    user: String,
+    group: Option<String>,
+}
+
+impl UserSpec {
+    /// Parse a specification `user[:group]`.
+    pub fn parse(spec: &str) -> Self {
+        match spec.split_once(':') {
+            Some((user, group)) => Self {
+                user: user.to_string(),
+                group: Some(group.to_string()),
+            },
+            None => Self {
+                user: spec.to_string(),
+                group: None,
+            },
+        }
+    }
+
+    /// Resolve the user and group IDs.
+    ///
+    /// Without a group, the primary group of the user is used.
+    fn resolve(&self) -> PrivilegeResult<(uid_t, gid_t)> {
+        let (uid, user_gid) = lookup_user(&self.user)?;
+        let gid = match self.group {
+            Some(ref group) => lookup_group(group)?,
+            None => user_gid,
+        };
+        Ok((uid, gid))
+    }
+}
+
+/// Return the user ID and primary group ID of a user.
+pub(crate) fn lookup_user(name: &str) -> PrivilegeResult<(uid_t, gid_t)> {
+    let cname = CString::new(name).map_err(|_| Error::UnknownUser(name.to_string()))?;
+    let mut pwd = MaybeUninit::<libc::passwd>::uninit();
+    let mut buf = vec![0 as libc::c_char; NAME_BUFFER_SIZE];
+    let mut result = ptr::null_mut();
+    let rc = unsafe {
+        libc::getpwnam_r(
+            cname.as_ptr(),
+            pwd.as_mut_ptr(),
+            buf.as_mut_ptr(),
+            buf.len(),
+            &mut result,
+        )
+    };
+    if rc != 0 || result.is_null() {
+        Err(Error::UnknownUser(name.to_string()))
+    } else {
+        let pwd = unsafe { pwd.assume_init() };
+        Ok((pwd.pw_uid, pwd.pw_gid))
+    }
+}
+
+/// Return the name of a user.
+#[cfg(feature = "tui")]
+pub(crate) fn user_name(uid: uid_t) -> Option<String> {
+    let mut pwd = MaybeUninit::<libc::passwd>::uninit();
+    let mut buf = vec![0 as libc::c_char; NAME_BUFFER_SIZE];
+    let mut result = ptr::null_mut();
+    let rc = unsafe {
+        libc::getpwuid_r(
+            uid,
+            pwd.as_mut_ptr(),
+            buf.as_mut_ptr(),
+            buf.len(),
+            &mut result,
+        )
+    };
+    if rc != 0 || result.is_null() {
+        None
+    } else {
+        let pwd = unsafe { pwd.assume_init() };
+        let name = unsafe { CStr::from_ptr(pwd.pw_name) };
+        Some(name.to_string_lossy().to_string())
+    }
+}
+
+/// Return the group ID of a group.
+fn lookup_group(name: &str) -> PrivilegeResult<gid_t> {
+    let cname = CString::new(name).map_err(|_| Error::UnknownGroup(name.to_string()))?;
+    let mut grp = MaybeUninit::<libc::group>::uninit();
+    let mut buf = vec![0 as libc::c_char; NAME_BUFFER_SIZE];
+    let mut result = ptr::null_mut();
+    let rc = unsafe {
+        libc::getgrnam_r(
+            cname.as_ptr(),
+            grp.as_mut_ptr(),
+            buf.as_mut_ptr(),
+            buf.len(),
+            &mut result,
+        )
+    };
+    if rc != 0 || result.is_null() {
+        Err(Error::UnknownGroup(name.to_string()))
+    } else {
+        let grp = unsafe { grp.assume_init() };
+        Ok(grp.gr_gid)
+    }
+}
+
+/// Restrict the capabilities to CAP_SYS_PTRACE.
+///
+/// The capability is neither inheritable nor ambient, the commands spawned by oprs
+/// don't get it.
+fn keep_ptrace_capability() -> PrivilegeResult<()> {
+    let mask = 1u32 << CAP_SYS_PTRACE;
+    let header = CapUserHeader {
+        version: LINUX_CAPABILITY_VERSION_3,
+        pid: 0,
+    };
+    let data = [
+        CapUserData {
+            effective: mask,
+            permitted: mask,
+            inheritable: 0,
+        },
+        CapUserData::default(),
+    ];
+    check_call("capset", unsafe {
+        libc::syscall(libc::SYS_capset, &header, data.as_ptr())
+    })?;
+    Ok(())
+}
+
+/// Drop the capabilities of a command before it is executed.
+///
+/// Oprs may have been started with capabilities that must not be passed to the
+/// commands it spawns. The ambient and inheritable sets are cleared and, when
+/// oprs is allowed to, the bounding set is emptied so that a command running as
+/// root gets no capabilities either.
+pub(crate) fn drop_capabilities(command: &mut Command) -> &mut Command {
+    unsafe {
+        command.pre_exec(|| {
+            let rc = libc::prctl(
+                libc::PR_CAP_AMBIENT,
+                libc::PR_CAP_AMBIENT_CLEAR_ALL,
+                0,
+                0,
+                0,
+            );
+            match io::Error::last_os_error() {
+                // Kernels without ambient capabilities.
+                err if rc < 0 && err.raw_os_error() != Some(libc::EINVAL) => return Err(err),
+                _ => (),
+            }
+            // The read fails after the last capability and the drop fails
+            // without CAP_SETPCAP.
+            let mut cap = 0;
+            while libc::prctl(libc::PR_CAPBSET_READ, cap, 0, 0, 0) >= 0
+                && libc::prctl(libc::PR_CAPBSET_DROP, cap, 0, 0, 0) == 0
+            {
+                cap += 1;
+            }
+            let header = CapUserHeader {
+                version: LINUX_CAPABILITY_VERSION_3,
+                pid: 0,
+            };
+            let mut data = [CapUserData::default(); 2];
+            if libc::syscall(libc::SYS_capget, &header, data.as_mut_ptr()) < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            data.iter_mut().for_each(|data| data.inheritable = 0);
+            if libc::syscall(libc::SYS_capset, &header, data.as_ptr()) < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(())
+        })
+    }
+}
+
+/// Switch to another user keeping only the capability to read other processes.
+pub fn drop_privileges(spec: &UserSpec) -> PrivilegeResult<()> {
+    if unsafe { libc::geteuid() } != 0 {
+        return Err(Error::NotRoot);
+    }
+    let (uid, gid) = spec.resolve()?;
+    check_call("prctl", unsafe {
+        libc::prctl(libc::PR_SET_KEEPCAPS, 1, 0, 0, 0)
+    })?;
+    check_call("setgroups", unsafe { libc::setgroups(1, &gid) })?;
+    check_call("setgid", unsafe { libc::setgid(gid) })?;
+    check_call("setuid", unsafe { libc::setuid(uid) })?;
+    keep_ptrace_capability()?;
+    check_call("prctl", unsafe {
+        libc::prctl(libc::PR_SET_KEEPCAPS, 0, 0, 0, 0)
+    })?;
+    log::info!("privileges dropped to {uid}:{gid}");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+
+    #[cfg(feature = "tui")]
+    use super::user_name;
+    use super::{drop_capabilities, lookup_group, lookup_user, Error, UserSpec};
+    use std::process::Command;
+
+    #[test]
+    fn test_parse_user_spec() {
+        assert_eq!(
+            UserSpec {
+                user: "nobody".to_string(),
+                group: None
+            },
+            UserSpec::parse("nobody")
+        );
+        assert_eq!(
+            UserSpec {
+                user: "nobody".to_string(),
+                group: Some("nogroup".to_string())
+            },
+            UserSpec::parse("nobody:nogroup")
+        );
+    }
+
+    #[test]
+    fn test_lookup() {
+        assert_eq!((0, 0), lookup_user("root").unwrap());
+        assert_eq!(0, lookup_group("root").unwrap());
+        assert!(matches!(
+            lookup_user("no-such-user-for-oprs"),
+            Err(Error::UnknownUser(_))
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "tui")]
+    fn test_user_name() {
+        assert_eq!(Some("root"), user_name(0).as_deref());
+    }
+
+    #[test]
+    fn test_drop_capabilities() {
+        let output = drop_capabilities(Command::new("grep").args(["^Cap", "/proc/self/status"]))
+            .output()
+            .unwrap();
+        let status = String::from_utf8(output.stdout).unwrap();
+        for set in ["CapInh", "CapPrm", "CapEff", "CapAmb"] {
+            assert!(
+                status.contains(&format!("{set}:\t0000000000000000")),
+                "{status}"
+            );
+        }
+    }
+}
@@ -50,6 +50,11 @@ impl AggregationSet {
         AggregationSet(0)
     }
 
+    /// Only the raw value.
+    pub fn raw() -> AggregationSet {
+        AggregationSet(Aggregation::None.mask())
+    }
+
     pub fn has(self, variant: Aggregation) -> bool {
         self.0 & variant.mask() != 0
     }
@@ -57,6 +62,11 @@ impl AggregationSet {
     pub fn set(&mut self, variant: Aggregation) {
         self.0 |= variant.mask();
     }
+
+    /// Add the aggregations of another set.
+    pub fn merge(&mut self, other: AggregationSet) {
+        self.0 |= other.0;
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,329 @@
+// Oprs -- process monitor for Linux
+// Copyright (C) 2025  Laurent Pelecq
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Throttling of the CPU bandwidth and memory limit in the cgroup of a process.
+//!
+//! When a cgroup exceeds its CFS quota, all its processes are stopped until the
+//! next period. It's reported in file cpu.stat of the cgroup.
+//!
+//! When a cgroup reaches its memory limit, the OOM killer terminates one of its
+//! processes with SIGKILL that can't be caught.
+
+use libc::pid_t;
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// Mount point of the cgroup file systems.
+const CGROUP_ROOT: &str = "/sys/fs/cgroup";
+
+/// File with the CPU statistics of a cgroup.
+const CPU_STAT_FILE: &str = "cpu.stat";
+
+/// Files of the memory usage, limit and events in cgroup v2 and v1.
+const MEMORY_FILES: [MemoryFiles; 2] = [
+    MemoryFiles {
+        current: "memory.current",
+        limit: "memory.max",
+        events: "memory.events",
+    },
+    MemoryFiles {
+        current: "memory.usage_in_bytes",
+        limit: "memory.limit_in_bytes",
+        events: "memory.failcnt",
+    },
+];
+
+/// Percentage of the memory limit considered as close to the limit.
+const MEMORY_PRESSURE_PERCENT: u64 = 90;
+
+/// Number of times the cgroup has been throttled and the time spent throttled.
+#[derive(Debug, Default, PartialEq)]
+pub struct CpuThrottling {
+    pub count: u64,
+    /// Time in milliseconds.
+    pub time: u64,
+}
+
+impl CpuThrottling {
+    /// Parse the content of cpu.stat.
+    ///
+    /// The time is `throttled_usec` in cgroup v2 and `throttled_time` in
+    /// nanoseconds in cgroup v1. Missing fields are zero.
+    fn parse(content: &str) -> Self {
+        content
+            .lines()
+            .filter_map(|line| line.split_once(' '))
+            .fold(CpuThrottling::default(), |mut throttling, (key, value)| {
+                let value = value.trim().parse::<u64>().unwrap_or(0);
+                match key {
+                    "nr_throttled" => throttling.count = value,
+                    "throttled_usec" => throttling.time = value / 1_000,
+                    "throttled_time" => throttling.time = value / 1_000_000,
+                    _ => (),
+                }
+                throttling
+            })
+    }
+}
+
+/// Directory of the cgroup with a controller in the content of /proc/PID/cgroup.
+///
+/// The controller of cgroup v1 has precedence over the unified hierarchy.
+fn cgroup_dir(root: &Path, content: &str, controller: &str) -> Option<PathBuf> {
+    let mut unified = None;
+    for line in content.lines() {
+        match line.splitn(3, ':').collect::<Vec<&str>>()[..] {
+            ["0", "", path] => unified = Some(path),
+            [_, controllers, path] if controllers.split(',').any(|c| c == controller) => {
+                return Some(root.join(controllers).join(path.trim_start_matches('/')));
+            }
+            _ => (),
+        }
+    }
+    unified.map(|path| root.join(path.trim_start_matches('/')))
+}
+
+/// CPU throttling of the cgroup of a process.
+pub fn cpu_throttling(pid: pid_t) -> io::Result<CpuThrottling> {
+    let content = fs::read_to_string(format!("/proc/{pid}/cgroup"))?;
+    let dir = cgroup_dir(Path::new(CGROUP_ROOT), &content, "cpu")
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no cpu cgroup"))?;
+    Ok(CpuThrottling::parse(&fs::read_to_string(
+        dir.join(CPU_STAT_FILE),
+    )?))
+}
+
+/// Parse the content of the file of the memory limit.
+///
+/// There is no limit if it's `max` (cgroup v2) or a huge number (cgroup v1).
+fn parse_memory_limit(content: &str) -> Option<u64> {
+    content
+        .trim()
+        .parse::<u64>()
+        .ok()
+        .filter(|limit| *limit < i64::MAX as u64 / 2)
+}
+
+/// Memory used by the cgroup of a process and its limit if any, in bytes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CgroupMemory {
+    pub usage: u64,
+    pub limit: Option<u64>,
+}
+
+impl CgroupMemory {
+    /// Usage in per thousand of the limit, zero if unlimited.
+    pub fn ratio(&self) -> u64 {
+        match self.limit {
+            Some(limit) if limit > 0 => (u128::from(self.usage) * 1000 / u128::from(limit)) as u64,
+            _ => 0,
+        }
+    }
+}
+
+/// Memory of the cgroup of a process.
+pub fn cgroup_memory(pid: pid_t) -> io::Result<CgroupMemory> {
+    let content = fs::read_to_string(format!("/proc/{pid}/cgroup"))?;
+    let dir = cgroup_dir(Path::new(CGROUP_ROOT), &content, "memory")
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no memory cgroup"))?;
+    let files = MEMORY_FILES
+        .iter()
+        .find(|files| dir.join(files.current).exists())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no memory controller"))?;
+    let usage = fs::read_to_string(dir.join(files.current))?
+        .trim()
+        .parse::<u64>()
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+    let limit = fs::read_to_string(dir.join(files.limit))
+        .ok()
+        .and_then(|content| parse_memory_limit(&content));
+    Ok(CgroupMemory { usage, limit })
+}
+
+/// Memory used by a cgroup and its limit in bytes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MemoryUsage {
+    pub current: u64,
+    pub limit: u64,
+}
+
+impl MemoryUsage {
+    /// Parse the content of the files of usage and limit.
+    ///
+    /// Return None if the memory is unlimited.
+    fn parse(current: &str, limit: &str) -> Option<Self> {
+        let current = current.trim().parse::<u64>().ok()?;
+        let limit = parse_memory_limit(limit)?;
+        Some(Self { current, limit })
+    }
+
+    /// Whether the usage is close to the limit.
+    fn is_high(&self) -> bool {
+        self.current.saturating_mul(100) >= self.limit.saturating_mul(MEMORY_PRESSURE_PERCENT)
+    }
+}
+
+/// Names of the files of the memory controller.
+#[derive(Debug)]
+struct MemoryFiles {
+    current: &'static str,
+    limit: &'static str,
+    /// Events in cgroup v2, count of failures to allocate in cgroup v1.
+    events: &'static str,
+}
+
+/// Number of times the cgroup reached its memory limit in the content of
+/// memory.events or memory.failcnt.
+fn parse_memory_max_events(content: &str) -> u64 {
+    if let Ok(count) = content.trim().parse::<u64>() {
+        return count;
+    }
+    content
+        .lines()
+        .filter_map(|line| line.split_once(' '))
+        .filter(|(key, _)| matches!(*key, "max" | "oom"))
+        .filter_map(|(_, value)| value.trim().parse::<u64>().ok())
+        .sum()
+}
+
+/// Watch the memory of the cgroup of a process to act before it's killed.
+///
+/// The files of the cgroup are polled at each call.
+#[derive(Debug)]
+pub struct MemoryWatch {
+    dir: PathBuf,
+    files: &'static MemoryFiles,
+    /// Number of times the limit has been reached.
+    max_events: u64,
+    /// Whether the memory is already known to be close to the limit.
+    high: bool,
+}
+
+impl MemoryWatch {
+    /// Watch the cgroup of the process if its memory is limited.
+    pub fn new(pid: pid_t) -> Option<Self> {
+        let content = fs::read_to_string(format!("/proc/{pid}/cgroup")).ok()?;
+        let dir = cgroup_dir(Path::new(CGROUP_ROOT), &content, "memory")?;
+        let files = MEMORY_FILES
+            .iter()
+            .find(|files| dir.join(files.current).exists())?;
+        let mut watch = Self {
+            dir,
+            files,
+            max_events: 0,
+            high: false,
+        };
+        watch.max_events = watch.read_max_events();
+        watch.read_usage()?;
+        Some(watch)
+    }
+
+    fn read_usage(&self) -> Option<MemoryUsage> {
+        MemoryUsage::parse(
+            &fs::read_to_string(self.dir.join(self.files.current)).ok()?,
+            &fs::read_to_string(self.dir.join(self.files.limit)).ok()?,
+        )
+    }
+
+    fn read_max_events(&self) -> u64 {
+        fs::read_to_string(self.dir.join(self.files.events))
+            .map(|content| parse_memory_max_events(&content))
+            .unwrap_or(0)
+    }
+
+    /// Return the usage when the memory becomes close to the limit or when the
+    /// limit has been reached since the last call.
+    pub fn update(&mut self) -> Option<MemoryUsage> {
+        let usage = self.read_usage()?;
+        let max_events = self.read_max_events();
+        let limit_reached = max_events > self.max_events;
+        self.max_events = max_events;
+        let was_high = self.high;
+        self.high = usage.is_high();
+        ((self.high && !was_high) || limit_reached).then_some(usage)
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::path::{Path, PathBuf};
+
+    use super::{cgroup_dir, CgroupMemory, CpuThrottling, MemoryUsage};
+
+    #[test]
+    fn test_parse_cpu_stat() {
+        let v2 = "usage_usec 1000\nnr_periods 30\nnr_throttled 12\nthrottled_usec 2500000\n";
+        assert_eq!(
+            CpuThrottling {
+                count: 12,
+                time: 2500
+            },
+            CpuThrottling::parse(v2)
+        );
+        let v1 = "nr_periods 30\nnr_throttled 3\nthrottled_time 40000000\n";
+        assert_eq!(
+            CpuThrottling { count: 3, time: 40 },
+            CpuThrottling::parse(v1)
+        );
+        assert_eq!(
+            CpuThrottling::default(),
+            CpuThrottling::parse("usage_usec 1000\n")
+        );
+    }
+
+    #[test]
+    fn test_memory_usage() {
+        let usage = MemoryUsage::parse("950\n", "1000\n").unwrap();
+        assert!(usage.is_high());
+        assert!(!MemoryUsage::parse("800", "1000").unwrap().is_high());
+        assert_eq!(None, MemoryUsage::parse("800\n", "max\n"));
+        assert_eq!(None, MemoryUsage::parse("800\n", "9223372036854771712\n"));
+        let events = "low 0\nhigh 3\nmax 2\noom 1\noom_kill 1\n";
+        assert_eq!(3, super::parse_memory_max_events(events));
+        assert_eq!(7, super::parse_memory_max_events("7\n"));
+    }
+
+    #[test]
+    fn test_cgroup_memory_ratio() {
+        let memory = |usage, limit| CgroupMemory { usage, limit };
+        assert_eq!(250, memory(256, Some(1024)).ratio());
+        assert_eq!(0, memory(256, None).ratio());
+        assert_eq!(Some(1 << 30), super::parse_memory_limit("1073741824\n"));
+        assert_eq!(None, super::parse_memory_limit("max\n"));
+    }
+
+    #[test]
+    fn test_cgroup_dir() {
+        let root = Path::new("/sys/fs/cgroup");
+        assert_eq!(
+            Some(PathBuf::from("/sys/fs/cgroup/system.slice/nginx.service")),
+            cgroup_dir(root, "0::/system.slice/nginx.service\n", "cpu")
+        );
+        let v1 = "4:memory:/app\n2:cpu,cpuacct:/docker/abc\n0::/\n";
+        assert_eq!(
+            Some(PathBuf::from("/sys/fs/cgroup/cpu,cpuacct/docker/abc")),
+            cgroup_dir(root, v1, "cpu")
+        );
+        assert_eq!(
+            Some(PathBuf::from("/sys/fs/cgroup/memory/app")),
+            cgroup_dir(root, v1, "memory")
+        );
+        assert_eq!(None, cgroup_dir(root, "2:cpuset:/\n", "cpu"));
+    }
+}
@@ -24,8 +24,49 @@ use std::{
     slice::Iter as SliceIter,
 };
 use strum::IntoEnumIterator;
+use strum_macros::{EnumString, IntoStaticStr};
 
-use super::{format, Aggregation, FormattedMetric, MetricId, ProcessInfo, SystemConf, SystemStat};
+use super::{
+    format, watch::systemd_unit, Aggregation, Credentials, FormattedMetric, LimitBreach,
+    MetricDataType, MetricId, MetricSelection, Process, ProcessInfo, SystemConf, SystemStat,
+};
+
+/// Whether the last value of an exited process is added to its ancestor.
+///
+/// The kernel already adds the I/O of the reaped children to their parent, only
+/// the CPU times and the faults are folded. A metric with a ratio is not folded
+/// since the whole value of the child would count in a single interval.
+fn is_folded(metric: &FormattedMetric) -> bool {
+    metric.derived.is_none()
+        && !metric.aggregations.has(Aggregation::Ratio)
+        && matches!(
+            metric.id,
+            MetricId::TimeCpu
+                | MetricId::TimeSystem
+                | MetricId::TimeUser
+                | MetricId::FaultMinor
+                | MetricId::FaultMajor
+        )
+}
+
+/// Basis of the CPU time ratio
+///
+/// With `All`, 100% means that all the online CPUs are busy (solaris mode in htop). With `Single`,
+/// 100% means that one CPU is busy and a process can exceed it (irix mode in top).
+#[derive(Clone, Copy, Debug, Default, EnumString, IntoStaticStr, PartialEq, Eq)]
+pub enum CpuBasis {
+    #[default]
+    #[strum(serialize = "all")]
+    All,
+    #[strum(serialize = "single")]
+    Single,
+}
+
+impl CpuBasis {
+    pub fn as_str(self) -> &'static str {
+        self.into()
+    }
+}
 
 /// Tell if it makes sense to track metric changes
 ///
@@ -37,65 +78,162 @@ fn track_change(id: MetricId) -> bool {
     )
 }
 
+/// Tell if the lowest and highest values are meaningful.
+///
+/// The first computed value of a counter always increases unless it's a ratio.
+fn has_watermarks(metric: &FormattedMetric) -> bool {
+    matches!(metric.data_type(), MetricDataType::Gauge)
+        || !metric.aggregations.has(Aggregation::None)
+}
+
+/// Increase of a counter between two samples.
+///
+/// A counter that goes backward has been reset, for instance when a process
+/// reuses the PID or when the kernel resets the counter. The new value is then
+/// the increase since the reset.
+pub fn counter_increase(old_value: u64, new_value: u64) -> u64 {
+    if new_value >= old_value {
+        new_value - old_value
+    } else {
+        new_value
+    }
+}
+
 /// The raw sample value and the derived aggregations.
 ///
-/// The first value in _values_ is the raw value from the system. The following
-/// are the aggregations if any (min, max, ...).
+/// The raw value is the value from the system. The computed values are the
+/// aggregations if any (raw, min, max, ...). Each computed value has a
+/// formatted string and a trend at the same index.
 ///
-/// Strings are the formatted values. If the samples don't contain the raw value
-/// (i.e. Aggregation::None is not selected), the first element in _values_ is the
-/// raw value that doesn't have a counterpart in _strings_.
-#[derive(Debug, Default)]
+/// The raw value is kept even if Aggregation::None is not selected since it is
+/// required to compute the other aggregations.
+///
+/// The lowest and highest first computed value are tracked for gauges and ratios.
+///
+/// The relative value is the share of the system in per thousand: the RAM size
+/// for memory sizes and the disk I/O during the interval for the storage I/O.
+///
+/// A sample is blank if the metric is not collected for the target. Its values
+/// are zero and must not be exported.
+#[derive(Clone, Debug, Default)]
 pub struct Sample {
+    raw: u64,
+    blank: bool,
     values: Vec<u64>,
     strings: Vec<String>,
     trends: Vec<Ordering>,
+    watermarks: Option<(u64, u64)>,
+    relative: Option<u64>,
 }
 
 impl Sample {
-    fn get_raw_value(&self) -> u64 {
-        self.values[0]
+    /// Return the raw value from the system.
+    pub fn raw(&self) -> u64 {
+        self.raw
     }
 
-    /// Return the numeric values.
-    pub fn values(&self) -> SliceIter<u64> {
+    /// Return the computed values.
+    #[cfg(test)]
+    pub fn values(&self) -> SliceIter<'_, u64> {
         self.values.iter()
     }
 
-    /// Return the formatted strings
-    pub fn strings(&self) -> SliceIter<String> {
-        self.strings.iter()
+    /// Whether the metric is collected for the target.
+    pub fn is_collected(&self) -> bool {
+        !self.blank
     }
 
-    /// Return the trend of formatted strings
-    pub fn trends(&self) -> SliceIter<Ordering> {
-        self.trends.iter()
+    /// Return the raw value if the metric is collected.
+    pub fn collected_raw(&self) -> Option<u64> {
+        self.is_collected().then_some(self.raw)
     }
 
-    fn push_raw(&mut self, value: u64) {
-        assert!(self.values.is_empty());
-        self.values.push(value);
+    /// Return the computed values or None if the metric is not collected.
+    pub fn collected_values(&self) -> impl Iterator<Item = Option<u64>> + '_ {
+        self.values
+            .iter()
+            .map(|value| self.is_collected().then_some(*value))
     }
 
-    fn push(&mut self, metric: &FormattedMetric, ag: Aggregation, value: u64) {
-        self.values.push(value);
-        self.strings.push(match ag {
-            Aggregation::Ratio => format::ratio(value),
-            _ => (metric.format)(value),
-        });
-        self.trends.push(Ordering::Equal);
+    /// Return the formatted strings of the computed values.
+    pub fn strings(&self) -> SliceIter<'_, String> {
+        self.strings.iter()
+    }
+
+    /// Return the share of the system in per thousand if the metric has one.
+    #[cfg(any(feature = "tui", test))]
+    pub fn relative(&self) -> Option<u64> {
+        self.relative
+    }
+
+    /// Whether the first computed value is the highest (Greater) or the lowest (Less)
+    /// observed since the process is monitored.
+    #[cfg(any(feature = "tui", test))]
+    pub fn watermark(&self) -> Ordering {
+        match (self.watermarks, self.values.first()) {
+            (Some((low, high)), Some(value)) if low < high => {
+                if *value == high {
+                    Ordering::Greater
+                } else if *value == low {
+                    Ordering::Less
+                } else {
+                    Ordering::Equal
+                }
+            }
+            _ => Ordering::Equal,
+        }
     }
 
-    fn update_raw(&mut self, value: u64, track_change: bool) {
-        let trend = value.cmp(&self.values[0]);
-        if !matches!(trend, Ordering::Equal) {
-            self.values[0] = value;
+    /// Restart the lowest and highest first computed value after a reset.
+    fn reset_watermarks(&mut self) {
+        if let (Some(watermarks), Some(value)) = (self.watermarks.as_mut(), self.values.first()) {
+            *watermarks = (*value, *value);
         }
-        if track_change {
-            self.trends[0] = trend;
+    }
+
+    /// Update the lowest and highest first computed value.
+    fn update_watermarks(&mut self) {
+        if let (Some((low, high)), Some(value)) = (self.watermarks.as_mut(), self.values.first()) {
+            *low = (*low).min(*value);
+            *high = (*high).max(*value);
         }
     }
 
+    /// Clear the formatted strings of a metric that is not collected.
+    fn blank(&mut self) {
+        self.blank = true;
+        self.strings.iter_mut().for_each(String::clear);
+        self.watermarks = None;
+    }
+
+    /// Return the computed values with their formatted string and trend.
+    #[cfg(any(feature = "tui", test))]
+    pub fn computed(&self) -> impl Iterator<Item = (u64, &str, Ordering)> {
+        izip!(&self.values, &self.strings, &self.trends)
+            .map(|(value, string, trend)| (*value, string.as_str(), *trend))
+    }
+
+    fn format(metric: &FormattedMetric, ag: Aggregation, value: u64) -> String {
+        let text = match ag {
+            Aggregation::Ratio => format::ratio(value),
+            _ => metric
+                .external
+                .as_ref()
+                .and_then(|external| external.format(value))
+                .unwrap_or_else(|| (metric.format)(value)),
+        };
+        metric.column.apply(text)
+    }
+
+    fn push(&mut self, metric: &FormattedMetric, ag: Aggregation, value: u64) {
+        self.values.push(value);
+        self.strings.push(Sample::format(metric, ag, value));
+        self.trends.push(Ordering::Equal);
+    }
+
+    /// Update a computed value.
+    ///
+    /// After a reset, the min and max start over and the trend is not a decrease.
     fn update(
         &mut self,
         metric: &FormattedMetric,
@@ -103,21 +241,22 @@ impl Sample {
         ag: Aggregation,
         value: u64,
         track_change: bool,
+        reset: bool,
     ) {
         if let Some(last_value) = self.values.get_mut(index) {
             let value = match ag {
-                Aggregation::Min if value < *last_value => value,
-                Aggregation::Max if value > *last_value => value,
+                _ if reset => value,
+                Aggregation::Min => value.min(*last_value),
+                Aggregation::Max => value.max(*last_value),
                 _ => value,
             };
-            let trend = value.cmp(last_value);
-            *last_value = value;
-            let offset = self.values.len() - self.strings.len();
-            let index = index - offset;
-            self.strings[index] = match ag {
-                Aggregation::Ratio => format::ratio(value),
-                _ => (metric.format)(value),
+            let trend = if reset {
+                Ordering::Equal
+            } else {
+                value.cmp(last_value)
             };
+            *last_value = value;
+            self.strings[index] = Sample::format(metric, ag, value);
             if track_change {
                 self.trends[index] = trend;
             }
@@ -129,9 +268,13 @@ impl Sample {
 impl From<&[&str]> for Sample {
     fn from(strings: &[&str]) -> Sample {
         Sample {
-            values: Vec::new(),
+            raw: 0,
+            blank: false,
+            values: vec![0; strings.len()],
             strings: strings.iter().map(|s| s.to_string()).collect(),
             trends: vec![Ordering::Equal; strings.len()],
+            watermarks: None,
+            relative: None,
         }
     }
 }
@@ -140,10 +283,12 @@ impl From<&[&str]> for Sample {
 pub trait ProcessIdentity {
     fn name(&self) -> &str;
     fn pid(&self) -> pid_t;
+    #[cfg(feature = "tui")]
+    fn uid(&self) -> Option<u32>;
 }
 
 /// A list of computed samples for a process
-#[derive(Debug, Getters, CopyGetters)]
+#[derive(Clone, Debug, Getters, CopyGetters)]
 pub struct ProcessSamples {
     name: String,
     pid: pid_t,
@@ -151,6 +296,28 @@ pub struct ProcessSamples {
     parent_pid: Option<pid_t>,
     #[getset(get_copy = "pub")]
     state: char,
+    /// Whether the process has a real-time scheduling policy.
+    #[getset(get_copy = "pub")]
+    is_realtime: bool,
+    uid: Option<u32>,
+    /// Systemd unit if units are collected.
+    #[getset(get = "pub")]
+    unit: Option<String>,
+    /// Wait channel if the process is in uninterruptible sleep and wait channels are collected.
+    #[getset(get = "pub")]
+    wchan: Option<String>,
+    /// Use of the watched files if the process is found by watching a file.
+    #[getset(get = "pub")]
+    file_usage: Option<String>,
+    /// Effective user and group if they are collected at each sample.
+    #[getset(get_copy = "pub")]
+    credentials: Option<Credentials>,
+    /// Number of descendants whose metrics are added to the process.
+    #[getset(get_copy = "pub")]
+    folded: usize,
+    /// Highest breach of a resource limit by the metrics.
+    #[getset(get_copy = "pub")]
+    limit_breach: LimitBreach,
     samples: Vec<Sample>,
 }
 
@@ -160,6 +327,7 @@ impl ProcessSamples {
         pid: pid_t,
         parent_pid: Option<pid_t>,
         state: char,
+        uid: Option<u32>,
         samples: Vec<Sample>,
     ) -> ProcessSamples {
         ProcessSamples {
@@ -167,14 +335,32 @@ impl ProcessSamples {
             pid,
             parent_pid,
             state,
+            is_realtime: false,
+            uid,
+            unit: None,
+            wchan: None,
+            file_usage: None,
+            credentials: None,
+            folded: 0,
+            limit_breach: LimitBreach::None,
             samples,
         }
     }
 
-    pub fn samples(&self) -> SliceIter<Sample> {
+    pub fn samples(&self) -> SliceIter<'_, Sample> {
         self.samples.iter()
     }
 
+    /// Set the effective user and group, the owner follows the effective user.
+    ///
+    /// The last known ones are kept if they cannot be read.
+    fn set_credentials(&mut self, credentials: Option<Credentials>) {
+        if let Some(credentials) = credentials {
+            self.uid = Some(credentials.uid);
+            self.credentials = Some(credentials);
+        }
+    }
+
     pub fn samples_as_slice(&self) -> &[Sample] {
         self.samples.as_slice()
     }
@@ -183,6 +369,15 @@ impl ProcessSamples {
         &mut self.samples
     }
 
+    /// Blank the samples of the metrics that are not selected.
+    fn blank_unselected(&mut self, selected: &[bool]) {
+        self.samples
+            .iter_mut()
+            .zip(selected)
+            .filter(|(_, selected)| !**selected)
+            .for_each(|(sample, _)| sample.blank());
+    }
+
     #[cfg(debug_assertions)]
     fn _to_debug_string(&self) -> String {
         format!(
@@ -206,6 +401,11 @@ impl ProcessIdentity for ProcessSamples {
     fn pid(&self) -> pid_t {
         self.pid
     }
+
+    #[cfg(feature = "tui")]
+    fn uid(&self) -> Option<u32> {
+        self.uid
+    }
 }
 
 impl ProcessIdentity for &ProcessSamples {
@@ -216,6 +416,11 @@ impl ProcessIdentity for &ProcessSamples {
     fn pid(&self) -> pid_t {
         self.pid
     }
+
+    #[cfg(feature = "tui")]
+    fn uid(&self) -> Option<u32> {
+        self.uid
+    }
 }
 
 #[cfg(test)]
@@ -225,7 +430,15 @@ impl From<&[Vec<&str>]> for ProcessSamples {
             name: String::new(),
             pid: 0,
             state: ' ',
+            is_realtime: false,
             parent_pid: None,
+            uid: None,
+            unit: None,
+            wchan: None,
+            file_usage: None,
+            credentials: None,
+            folded: 0,
+            limit_breach: LimitBreach::None,
             samples: samples.iter().map(|s| Sample::from(s.as_slice())).collect(),
         }
     }
@@ -237,6 +450,10 @@ impl From<&[Vec<&str>]> for ProcessSamples {
 struct Updater {
     system_values: Vec<u64>,
     total_time: VecDeque<u64>,
+    cpu_factor: u64,
+    ram_size: u64,
+    /// Bytes read and written on the disks.
+    storage_io: VecDeque<(u64, u64)>,
 }
 
 impl Updater {
@@ -244,15 +461,15 @@ impl Updater {
         Updater {
             system_values: Vec::with_capacity(2),
             total_time: VecDeque::with_capacity(2),
+            cpu_factor: 1,
+            ram_size: 0,
+            storage_io: VecDeque::with_capacity(2),
         }
     }
 
     /// Keep current system values
     fn push_samples(&mut self, samples: &[Sample]) {
-        self.system_values = samples
-            .iter()
-            .map(|sample| sample.get_raw_value())
-            .collect();
+        self.system_values = samples.iter().map(|sample| sample.raw()).collect();
     }
 
     /// Remove old values and push new values
@@ -263,25 +480,34 @@ impl Updater {
         self.total_time.push_back(milliseconds);
     }
 
+    /// Keep the last two disk I/O of the system.
+    fn push_storage_io(&mut self, read: u64, written: u64) {
+        while self.storage_io.len() > 1 {
+            let _ = self.storage_io.pop_front();
+        }
+        self.storage_io.push_back((read, written));
+    }
+
     /// Computed values for a new process
     fn new_computed_values(
         &mut self,
         target_name: &str,
+        pid: pid_t,
         pinfo: Option<&ProcessInfo>,
         metrics: &[FormattedMetric],
         values: &[u64],
     ) -> ProcessSamples {
-        let pid = pinfo.map(|pi| pi.pid()).unwrap_or(0);
         let parent_pid = pinfo.map(|pi| pi.parent_pid());
         let state = pinfo.map(|pi| pi.state()).unwrap_or(' ');
+        let uid = pinfo.and_then(|pi| pi.uid());
         let samples = metrics
             .iter()
             .zip(values.iter())
             .map(|(metric, value_ref)| {
-                let mut sample = Sample::default();
-                if !metric.aggregations.has(Aggregation::None) {
-                    sample.push_raw(*value_ref);
-                }
+                let mut sample = Sample {
+                    raw: *value_ref,
+                    ..Default::default()
+                };
                 Aggregation::iter()
                     .filter(|ag| metric.aggregations.has(*ag))
                     .for_each(|ag| match ag {
@@ -290,13 +516,18 @@ impl Updater {
                         }
                         _ => sample.push(metric, ag, 0),
                     });
+                if has_watermarks(metric) {
+                    let value = sample.values.first().copied().unwrap_or_default();
+                    sample.watermarks = Some((value, value));
+                }
+                sample.relative = self.compute_relative(metric, *value_ref, *value_ref);
                 sample
             })
             .collect::<Vec<Sample>>();
         if pid == 0 {
             self.push_samples(&samples); // new system values
         }
-        ProcessSamples::new(target_name, pid, parent_pid, state, samples)
+        ProcessSamples::new(target_name, pid, parent_pid, state, uid, samples)
     }
 
     /// Historical metrics for the system
@@ -320,22 +551,11 @@ impl Updater {
         match metric.id {
             MetricId::TimeCpu | MetricId::TimeSystem | MetricId::TimeUser => {
                 if hlen >= 2 {
-                    let system_delta = self.get_total_time(1) - self.get_total_time(2);
-                    if new_value >= old_value {
-                        let delta = new_value - old_value;
-                        if system_delta == 0 {
-                            0
-                        } else {
-                            delta * PERCENT_FACTOR / system_delta
-                        }
-                    } else {
-                        log::warn!(
-                            "time value goes backward (from {} to {})",
-                            old_value,
-                            new_value,
-                        );
-                        0
-                    }
+                    let system_delta =
+                        counter_increase(self.get_total_time(2), self.get_total_time(1));
+                    (counter_increase(old_value, new_value) * PERCENT_FACTOR * self.cpu_factor)
+                        .checked_div(system_delta)
+                        .unwrap_or(0)
                 } else {
                     0
                 }
@@ -347,6 +567,42 @@ impl Updater {
         }
     }
 
+    /// Share of the system in per thousand
+    ///
+    /// Memory sizes are compared to the RAM size and the storage I/O to the
+    /// disk I/O of the system since the previous sample.
+    fn compute_relative(
+        &self,
+        metric: &FormattedMetric,
+        old_value: u64,
+        new_value: u64,
+    ) -> Option<u64> {
+        const PER_MILLE_FACTOR: u64 = 1000;
+        if metric.derived.is_some() {
+            return None;
+        }
+        let storage_delta = |select: fn(&(u64, u64)) -> u64| match self.storage_io.len() {
+            hlen if hlen >= 2 => Some(counter_increase(
+                select(&self.storage_io[hlen - 2]),
+                select(&self.storage_io[hlen - 1]),
+            )),
+            _ => None,
+        };
+        let system_delta = match metric.id {
+            id if id.is_memory_size() => {
+                return (new_value * PER_MILLE_FACTOR).checked_div(self.ram_size);
+            }
+            MetricId::IoReadStorage => storage_delta(|io| io.0)?,
+            MetricId::IoWriteStorage => storage_delta(|io| io.1)?,
+            _ => return None,
+        };
+        Some(
+            (counter_increase(old_value, new_value) * PER_MILLE_FACTOR)
+                .checked_div(system_delta)
+                .unwrap_or(0),
+        )
+    }
+
     /// Update values for an existing process
     fn update_computed_values(
         &mut self,
@@ -354,28 +610,44 @@ impl Updater {
         pstat: &mut ProcessSamples,
         values: &[u64],
     ) {
+        let pid = pstat.pid();
         for (metric_index, (metric, sample, value_ref)) in
             izip!(metrics, pstat.get_samples_mut(), values).enumerate()
         {
-            let old_value = sample.get_raw_value();
+            let old_value = sample.raw();
             let new_value = *value_ref;
-            let mut ag_index = 0;
-            if !metric.aggregations.has(Aggregation::None) {
-                sample.update_raw(new_value, track_change(metric.id));
-                ag_index += 1;
+            let reset =
+                matches!(metric.data_type(), MetricDataType::Counter) && new_value < old_value;
+            if reset {
+                log::debug!(
+                    "{}: counter {} reset (from {} to {})",
+                    pid,
+                    metric.id.as_str(),
+                    old_value,
+                    new_value,
+                );
             }
-            for ag in Aggregation::iter().filter(|ag| metric.aggregations.has(*ag)) {
+            sample.raw = new_value;
+            sample.relative = self.compute_relative(metric, old_value, new_value);
+            for (ag_index, ag) in Aggregation::iter()
+                .filter(|ag| metric.aggregations.has(*ag))
+                .enumerate()
+            {
                 let value = match ag {
                     Aggregation::Ratio => {
                         self.compute_ratio(metric, metric_index, old_value, new_value)
                     }
                     _ => new_value,
                 };
-                sample.update(metric, ag_index, ag, value, track_change(metric.id));
-                ag_index += 1;
+                sample.update(metric, ag_index, ag, value, track_change(metric.id), reset);
+            }
+            if reset {
+                sample.reset_watermarks();
+            } else {
+                sample.update_watermarks();
             }
         }
-        if pstat.pid() == 0 {
+        if pid == 0 {
             self.push_samples(pstat.samples_as_slice()); // new system values
         }
     }
@@ -407,6 +679,26 @@ pub struct Collector<'a> {
     pids: Vec<pid_t>,
     /// Samples updater.
     updater: Updater,
+    /// PID and name of the root process and its ancestors when the tree is reduced to a subtree.
+    lineage: Vec<(pid_t, String)>,
+    /// Whether the systemd unit of the processes is collected.
+    with_units: bool,
+    /// Whether the wait channel of the blocked processes is collected.
+    with_wchan: bool,
+    /// Whether the use of the watched files is collected.
+    with_file_usage: bool,
+    /// Whether the effective user and group are read again at each sample.
+    with_credentials: bool,
+    /// Whether the targets are not started yet.
+    waiting: bool,
+    /// Metrics collected by target if some targets have specific metrics.
+    selection: Option<MetricSelection>,
+    /// Basis of the CPU time ratio.
+    cpu_basis: CpuBasis,
+    /// Whether the counters of the exited processes are added to their ancestors.
+    fold_exited: bool,
+    /// Last counters of the exited descendants by living process.
+    exited: BTreeMap<pid_t, Vec<u64>>,
 }
 
 impl<'a> Collector<'a> {
@@ -416,17 +708,127 @@ impl<'a> Collector<'a> {
             samples: BTreeMap::new(),
             pids: Vec::new(),
             updater: Updater::new(),
+            lineage: Vec::new(),
+            with_units: false,
+            with_wchan: false,
+            with_file_usage: false,
+            with_credentials: false,
+            waiting: false,
+            selection: None,
+            cpu_basis: CpuBasis::All,
+            fold_exited: false,
+            exited: BTreeMap::new(),
         }
     }
 
+    /// Copy of the samples that doesn't borrow the metrics.
+    ///
+    /// The copy cannot collect new samples.
+    #[cfg(feature = "tui")]
+    pub fn snapshot(&self) -> Collector<'static> {
+        Collector {
+            metrics: Cow::Owned(self.metrics.to_vec()),
+            samples: self.samples.clone(),
+            pids: self.pids.clone(),
+            updater: Updater::new(),
+            lineage: self.lineage.clone(),
+            with_units: self.with_units,
+            with_wchan: self.with_wchan,
+            with_file_usage: self.with_file_usage,
+            with_credentials: self.with_credentials,
+            waiting: self.waiting,
+            selection: None,
+            cpu_basis: self.cpu_basis,
+            fold_exited: false,
+            exited: BTreeMap::new(),
+        }
+    }
+
+    /// Set the basis of the CPU time ratio.
+    pub fn set_cpu_basis(&mut self, cpu_basis: CpuBasis) {
+        self.cpu_basis = cpu_basis;
+    }
+
+    /// Set the metrics collected by target.
+    pub fn set_selection(&mut self, selection: Option<MetricSelection>) {
+        self.selection = selection;
+    }
+
+    /// Set whether the targets are not started yet.
+    pub fn set_waiting(&mut self, waiting: bool) {
+        self.waiting = waiting;
+    }
+
+    pub fn is_waiting(&self) -> bool {
+        self.waiting
+    }
+
+    /// Collect the systemd unit of new processes.
+    pub fn set_with_units(&mut self, with_units: bool) {
+        self.with_units = with_units;
+    }
+
+    pub fn with_units(&self) -> bool {
+        self.with_units
+    }
+
+    /// Collect the wait channel of the processes in uninterruptible sleep.
+    pub fn set_with_wchan(&mut self, with_wchan: bool) {
+        self.with_wchan = with_wchan;
+    }
+
+    pub fn with_wchan(&self) -> bool {
+        self.with_wchan
+    }
+
+    /// Collect how the processes found by watching a file use it.
+    pub fn set_with_file_usage(&mut self, with_file_usage: bool) {
+        self.with_file_usage = with_file_usage;
+    }
+
+    pub fn with_file_usage(&self) -> bool {
+        self.with_file_usage
+    }
+
+    /// Set how a collected process uses the watched files.
+    pub fn set_file_usage(&mut self, pid: pid_t, file_usage: Option<String>) {
+        if let Some(samples) = self.samples.get_mut(&pid) {
+            samples.file_usage = file_usage;
+        }
+    }
+
+    /// Read the effective user and group of the processes at each sample.
+    pub fn set_with_credentials(&mut self, with_credentials: bool) {
+        self.with_credentials = with_credentials;
+    }
+
+    /// Add the CPU times and the faults of the exited processes to their nearest living ancestor.
+    pub fn set_fold_exited(&mut self, fold_exited: bool) {
+        self.fold_exited = fold_exited;
+    }
+
     /// Start collecting from the beginning
     pub fn rewind(&mut self) {
         self.pids.clear();
     }
 
-    /// Set idle system time
+    /// Set idle system time, RAM size and disk I/O
     pub fn collect_system(&mut self, system: &mut SystemStat) {
         self.updater.push_system_time(system.total_time());
+        self.updater.ram_size = system.ram_size();
+        if self.metrics.iter().any(|metric| {
+            matches!(
+                metric.id,
+                MetricId::IoReadStorage | MetricId::IoWriteStorage
+            )
+        }) {
+            let (read, written) = system.storage_io();
+            self.updater.push_storage_io(read, written);
+        }
+        self.updater.cpu_factor = match self.cpu_basis {
+            CpuBasis::All => 1,
+            CpuBasis::Single => SystemStat::online_cpus(),
+        };
     }
 
     /// Check if the process must appear before the last samples.
@@ -454,7 +856,40 @@ impl<'a> Collector<'a> {
     /// Record metrics
     pub fn record(&mut self, target_name: &str, pinfo: Option<&ProcessInfo>, values: &[u64]) {
         let pid = pinfo.map(|pi| pi.pid()).unwrap_or(0);
+        self.record_with_pid(target_name, pid, pinfo, values);
+    }
+
+    /// Record metrics of a pseudo-process that is not a real process.
+    ///
+    /// The PID must be negative to not collide with real processes and the system.
+    pub fn record_pseudo(&mut self, target_name: &str, pid: pid_t, values: &[u64]) {
+        self.record_with_pid(target_name, pid, None, values);
+    }
+
+    fn record_with_pid(
+        &mut self,
+        target_name: &str,
+        pid: pid_t,
+        pinfo: Option<&ProcessInfo>,
+        values: &[u64],
+    ) {
+        let values = match self.exited.get(&pid) {
+            Some(exited) => Cow::Owned(
+                values
+                    .iter()
+                    .zip(exited)
+                    .map(|(value, exited)| value.saturating_add(*exited))
+                    .collect::<Vec<u64>>(),
+            ),
+            None => Cow::Borrowed(values),
+        };
         let parent_pid = pinfo.map(|pi| pi.parent_pid());
+        let wchan = pinfo
+            .filter(|_| self.with_wchan)
+            .and_then(ProcessInfo::wait_channel);
+        let credentials = pinfo
+            .filter(|_| self.with_credentials)
+            .and_then(|pinfo| Credentials::read(pinfo.pid()));
 
         if pinfo
             .map(|pinfo| self.is_before_previous(pinfo))
@@ -466,21 +901,32 @@ impl<'a> Collector<'a> {
         }
         match self.samples.get_mut(&pid) {
             Some(samples) => {
+                if samples.name != target_name {
+                    samples.name = target_name.to_string();
+                }
                 samples.parent_pid = parent_pid;
                 samples.state = pinfo.map(|pi| pi.state()).unwrap_or(' ');
+                samples.is_realtime = pinfo.is_some_and(ProcessInfo::is_realtime);
+                samples.wchan = wchan;
+                samples.set_credentials(credentials);
                 self.updater
-                    .update_computed_values(&self.metrics, samples, values)
+                    .update_computed_values(&self.metrics, samples, &values)
             }
             None => {
-                if self
-                    .samples
-                    .insert(
-                        pid,
-                        self.updater
-                            .new_computed_values(target_name, pinfo, &self.metrics, values),
-                    )
-                    .is_some()
-                {
+                let mut samples = self.updater.new_computed_values(
+                    target_name,
+                    pid,
+                    pinfo,
+                    &self.metrics,
+                    &values,
+                );
+                if self.with_units && pid > 0 {
+                    samples.unit = systemd_unit(pid);
+                }
+                samples.is_realtime = pinfo.is_some_and(ProcessInfo::is_realtime);
+                samples.wchan = wchan;
+                samples.set_credentials(credentials);
+                if self.samples.insert(pid, samples).is_some() {
                     log::error!("{}: PID has been replaced", pid);
                 }
             }
@@ -489,39 +935,142 @@ impl<'a> Collector<'a> {
 
     /// Collect metrics
     pub fn collect(&mut self, target_name: &str, pinfo: &ProcessInfo, sysconf: &SystemConf) {
-        let values = pinfo.extract_metrics(self.metrics(), sysconf);
+        self.collect_folded(target_name, pinfo, &[], sysconf);
+    }
+
+    /// Collect metrics of a process and add the metrics of its descendants.
+    ///
+    /// The metrics that are not additive such as the elapsed time or a ratio
+    /// are the ones of the process.
+    pub fn collect_folded(
+        &mut self,
+        target_name: &str,
+        pinfo: &ProcessInfo,
+        descendants: &[&ProcessInfo],
+        sysconf: &SystemConf,
+    ) {
+        let selected = self
+            .selection
+            .as_ref()
+            .map(|selection| selection.get(target_name));
+        let mut values = pinfo.extract_metrics(self.metrics(), selected.as_deref(), sysconf);
+        let mut limit_breach = pinfo.limit_breach(self.metrics(), &values);
+        for descendant in descendants {
+            let other = descendant.extract_metrics(self.metrics(), selected.as_deref(), sysconf);
+            limit_breach = limit_breach.max(descendant.limit_breach(self.metrics(), &other));
+            izip!(values.iter_mut(), other, self.metrics.iter())
+                .filter(|(_, _, metric)| metric.is_additive())
+                .for_each(|(value, other, _)| *value = value.saturating_add(other));
+        }
         self.record(target_name, Some(pinfo), &values);
+        if let Some(samples) = self.samples.get_mut(&pinfo.pid()) {
+            samples.folded = descendants.len();
+            samples.limit_breach = limit_breach;
+            if let Some(selected) = selected {
+                samples.blank_unselected(&selected);
+            }
+        }
     }
 
     /// Called when there is no more targets
     pub fn finish(&mut self) {
+        self.fold_exited();
         let alive = BTreeSet::from_iter(self.pids.iter());
         self.samples.retain(|pid, _| alive.contains(pid));
     }
 
-    pub fn metrics(&self) -> SliceIter<FormattedMetric> {
+    /// Add the last counters of the exited processes to their nearest living ancestor.
+    ///
+    /// Only the CPU times and the faults are folded, see [`is_folded`]. A
+    /// process that is not collected may be hidden but still running. The
+    /// samples of the exited processes are removed. The counters of a process
+    /// already include the ones of its exited descendants.
+    pub fn fold_exited(&mut self) {
+        if !self.fold_exited {
+            return;
+        }
+        let collected = BTreeSet::from_iter(self.pids.iter().copied());
+        let exited = self
+            .samples
+            .keys()
+            .copied()
+            .filter(|pid| {
+                *pid > 0
+                    && !collected.contains(pid)
+                    && !Process::new(*pid).is_ok_and(|process| process.is_alive())
+            })
+            .collect::<BTreeSet<pid_t>>();
+        for samples in exited.iter().filter_map(|pid| self.samples.get(pid)) {
+            let mut ancestor = samples.parent_pid;
+            for _ in 0..exited.len() {
+                match ancestor {
+                    Some(pid) if exited.contains(&pid) => {
+                        ancestor = self.samples.get(&pid).and_then(ProcessSamples::parent_pid)
+                    }
+                    _ => break,
+                }
+            }
+            let Some(ancestor) =
+                ancestor.filter(|pid| !exited.contains(pid) && self.samples.contains_key(pid))
+            else {
+                continue;
+            };
+            let values = self
+                .exited
+                .entry(ancestor)
+                .or_insert_with(|| vec![0; self.metrics.len()]);
+            izip!(
+                values.iter_mut(),
+                samples.samples.iter(),
+                self.metrics.iter()
+            )
+            .filter(|(_, _, metric)| is_folded(metric))
+            .for_each(|(value, sample, _)| *value = value.saturating_add(sample.raw()));
+        }
+        self.samples.retain(|pid, _| !exited.contains(pid));
+        self.exited.retain(|pid, _| self.samples.contains_key(pid));
+    }
+
+    pub fn metrics(&self) -> SliceIter<'_, FormattedMetric> {
         self.metrics.iter()
     }
 
-    pub fn for_each_computed_metric<F>(iter: SliceIter<FormattedMetric>, mut func: F)
+    pub fn for_each_computed_metric<'m, F>(iter: SliceIter<'m, FormattedMetric>, mut func: F)
     where
-        F: FnMut(MetricId, Aggregation),
+        F: FnMut(&'m FormattedMetric, Aggregation),
     {
         iter.for_each(|metric| {
             Aggregation::iter()
                 .filter(|ag| metric.aggregations.has(*ag))
-                .for_each(|ag| func(metric.id, ag));
+                .for_each(|ag| func(metric, ag));
         });
     }
 
     /// Return lines
-    pub fn lines(&self) -> LineIter {
+    pub fn lines(&self) -> LineIter<'_> {
         LineIter {
             iter: self.pids.iter(),
             samples: &self.samples,
         }
     }
 
+    /// Set the lineage of the root process.
+    pub fn set_lineage<'b, I>(&mut self, lineage: I)
+    where
+        I: Iterator<Item = &'b ProcessInfo>,
+    {
+        self.lineage.clear();
+        self.lineage
+            .extend(lineage.map(|pinfo| (pinfo.pid(), pinfo.name().to_string())));
+    }
+
+    /// PID and name of the root process ancestors from the top down to the root process.
+    #[cfg(feature = "tui")]
+    pub fn lineage(&self) -> SliceIter<'_, (pid_t, String)> {
+        self.lineage.iter()
+    }
+
+    #[cfg(feature = "tui")]
     pub fn line_count(&self) -> usize {
         self.pids.len()
     }
@@ -530,3 +1079,208 @@ impl<'a> Collector<'a> {
         self.pids.is_empty()
     }
 }
+
+#[cfg(test)]
+mod test {
+
+    use super::super::MetricNamesParser;
+    use super::*;
+
+    #[test]
+    /// Computed values are aligned with the strings when the raw value is not displayed.
+    fn test_sample_without_raw_value() {
+        let metrics = MetricNamesParser::new(false)
+            .parse(&["fd:all-raw+min+max"])
+            .unwrap();
+        let mut updater = Updater::new();
+        let mut pstat = updater.new_computed_values("test", 0, None, &metrics, &[10]);
+        updater.update_computed_values(&metrics, &mut pstat, &[5]);
+        updater.update_computed_values(&metrics, &mut pstat, &[8]);
+        let sample = pstat.samples().next().unwrap();
+        assert_eq!(8, sample.raw());
+        assert_eq!(vec![5, 10], sample.values().copied().collect::<Vec<u64>>());
+        assert_eq!(
+            vec!["5", "10"],
+            sample.strings().map(String::as_str).collect::<Vec<&str>>()
+        );
+    }
+
+    #[test]
+    fn test_counter_increase() {
+        assert_eq!(5, counter_increase(10, 15));
+        assert_eq!(0, counter_increase(10, 10));
+        assert_eq!(3, counter_increase(10, 3));
+    }
+
+    #[test]
+    /// A counter that goes backward restarts the min and max without a decreasing trend.
+    fn test_counter_reset() {
+        let metrics = MetricNamesParser::new(false)
+            .parse(&["fault:minor+min+max"])
+            .unwrap();
+        let mut updater = Updater::new();
+        let mut pstat = updater.new_computed_values("test", 0, None, &metrics, &[10]);
+        updater.update_computed_values(&metrics, &mut pstat, &[20]);
+        updater.update_computed_values(&metrics, &mut pstat, &[5]);
+        let sample = pstat.samples().next().unwrap();
+        assert_eq!(5, sample.raw());
+        assert_eq!(
+            vec![5, 5, 5],
+            sample.values().copied().collect::<Vec<u64>>()
+        );
+        assert!(sample
+            .computed()
+            .all(|(_, _, trend)| trend == Ordering::Equal));
+        updater.update_computed_values(&metrics, &mut pstat, &[7]);
+        let sample = pstat.samples().next().unwrap();
+        assert_eq!(
+            vec![7, 5, 7],
+            sample.values().copied().collect::<Vec<u64>>()
+        );
+    }
+
+    #[test]
+    fn test_sample_watermarks() {
+        let metrics = MetricNamesParser::new(false)
+            .parse(&["fd:all", "fault:minor"])
+            .unwrap();
+        let watermarks = |pstat: &ProcessSamples| {
+            pstat
+                .samples()
+                .map(|sample| sample.watermark())
+                .collect::<Vec<Ordering>>()
+        };
+        let mut updater = Updater::new();
+        let mut pstat = updater.new_computed_values("test", 0, None, &metrics, &[10, 1]);
+        assert_eq!(vec![Ordering::Equal, Ordering::Equal], watermarks(&pstat));
+        updater.update_computed_values(&metrics, &mut pstat, &[5, 2]);
+        assert_eq!(vec![Ordering::Less, Ordering::Equal], watermarks(&pstat));
+        updater.update_computed_values(&metrics, &mut pstat, &[12, 3]);
+        assert_eq!(vec![Ordering::Greater, Ordering::Equal], watermarks(&pstat));
+        updater.update_computed_values(&metrics, &mut pstat, &[8, 4]);
+        assert_eq!(vec![Ordering::Equal, Ordering::Equal], watermarks(&pstat));
+    }
+
+    #[test]
+    fn test_sample_relative() {
+        let metrics = MetricNamesParser::new(false)
+            .parse(&["mem:rss", "io:read:storage", "fd:all"])
+            .unwrap();
+        let relative = |pstat: &ProcessSamples| {
+            pstat
+                .samples()
+                .map(|sample| sample.relative())
+                .collect::<Vec<Option<u64>>>()
+        };
+        let mut updater = Updater::new();
+        updater.ram_size = 4000;
+        let mut pstat = updater.new_computed_values("test", 1, None, &metrics, &[1000, 0, 3]);
+        assert_eq!(vec![Some(250), None, None], relative(&pstat));
+        updater.push_storage_io(100, 0);
+        updater.push_storage_io(500, 0);
+        updater.update_computed_values(&metrics, &mut pstat, &[2000, 100, 3]);
+        assert_eq!(vec![Some(500), Some(250), None], relative(&pstat));
+        updater.push_storage_io(500, 0);
+        updater.update_computed_values(&metrics, &mut pstat, &[2000, 100, 3]);
+        assert_eq!(vec![Some(500), Some(0), None], relative(&pstat));
+    }
+
+    #[test]
+    fn test_blank_unselected() {
+        let metrics = MetricNamesParser::new(false)
+            .parse(&["fd:all", "fault:minor+max"])
+            .unwrap();
+        let mut updater = Updater::new();
+        let mut pstat = updater.new_computed_values("test", 0, None, &metrics, &[10, 0]);
+        updater.update_computed_values(&metrics, &mut pstat, &[12, 0]);
+        pstat.blank_unselected(&[true, false]);
+        let strings = pstat
+            .samples()
+            .map(|sample| sample.strings().map(String::as_str).collect::<Vec<&str>>())
+            .collect::<Vec<Vec<&str>>>();
+        assert_eq!(vec![vec!["12"], vec!["", ""]], strings);
+        assert_eq!(Ordering::Equal, pstat.samples().nth(1).unwrap().watermark());
+        let values = pstat
+            .samples()
+            .flat_map(Sample::collected_values)
+            .collect::<Vec<Option<u64>>>();
+        assert_eq!(vec![Some(12), None, None], values);
+        assert_eq!(None, pstat.samples().nth(1).unwrap().collected_raw());
+    }
+
+    #[test]
+    /// The CPU time ratio is multiplied by the number of CPUs on a single CPU basis.
+    fn test_cpu_basis() {
+        let metrics = MetricNamesParser::new(false)
+            .parse(&["time:cpu+ratio"])
+            .unwrap();
+        let ratio = |cpu_factor: u64| {
+            let mut updater = Updater::new();
+            updater.cpu_factor = cpu_factor;
+            updater.push_system_time(1000);
+            let mut pstat = updater.new_computed_values("test", 0, None, &metrics, &[100]);
+            updater.push_system_time(5000);
+            updater.update_computed_values(&metrics, &mut pstat, &[1100]);
+            pstat.samples().next().unwrap().values().nth(1).copied()
+        };
+        assert_eq!(Some(250), ratio(1));
+        assert_eq!(Some(1000), ratio(4));
+    }
+
+    #[test]
+    fn test_record_pseudo() {
+        let metrics = MetricNamesParser::new(false).parse(&["mem:rss"]).unwrap();
+        let mut collector = Collector::new(Cow::Owned(metrics));
+        collector.rewind();
+        collector.record("system", None, &[100]);
+        collector.record_pseudo("system:mem-used", -1, &[60]);
+        collector.record_pseudo("system:mem-cached", -2, &[40]);
+        collector.finish();
+        let lines = collector
+            .lines()
+            .map(|pstat| (pstat.pid(), pstat.name().to_string()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                (0, "system".to_string()),
+                (-1, "system:mem-used".to_string()),
+                (-2, "system:mem-cached".to_string())
+            ],
+            lines
+        );
+        assert_eq!(vec![100], collector.updater.system_values);
+    }
+
+    #[test]
+    fn test_fold_exited() {
+        let metrics = MetricNamesParser::new(false)
+            .parse(&["time:cpu", "io:read:total", "time:user+ratio"])
+            .unwrap();
+        let mut collector = Collector::new(Cow::Owned(metrics));
+        collector.set_fold_exited(true);
+        let mut collect = |records: &[(pid_t, Option<pid_t>, [u64; 3])]| {
+            collector.rewind();
+            for (pid, parent_pid, values) in records {
+                collector.record_pseudo("test", *pid, values);
+                collector.samples.get_mut(pid).unwrap().parent_pid = *parent_pid;
+            }
+            collector.finish();
+            collector
+                .lines()
+                .map(|pstat| (pstat.pid(), pstat.samples().map(Sample::raw).collect()))
+                .collect::<Vec<(pid_t, Vec<u64>)>>()
+        };
+        collect(&[
+            (1, None, [10, 100, 1]),
+            (2, Some(1), [20, 200, 2]),
+            (3, Some(2), [30, 300, 3]),
+        ]);
+        // The grand-child and the child exit, the CPU time is folded in the ancestor
+        // but not the I/O already counted by the kernel nor the ratio.
+        collect(&[(1, None, [11, 100, 1])]);
+        assert_eq!(
+            vec![(1, vec![62, 100, 1])],
+            collect(&[(1, None, [12, 100, 1])])
+        );
+    }
+}
@@ -0,0 +1,377 @@
+// Oprs -- process monitor for Linux
+// Copyright (C) 2025  Laurent Pelecq
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Short-lived processes with the process events connector.
+//!
+//! A process that starts and exits between two samples is never seen in /proc.
+//! The kernel notifies the forks, executions and exits on a netlink socket.
+//! Listening requires the CAP_NET_ADMIN capability.
+
+use libc::pid_t;
+use std::{
+    collections::HashMap,
+    fs, io, mem,
+    os::fd::{AsRawFd, FromRawFd, OwnedFd},
+    sync::mpsc,
+    thread,
+};
+
+use super::{
+    taskstats::{align, read_u32, NLMSG_HDRLEN},
+    StateEvent,
+};
+
+const NLMSG_DONE: u16 = 3;
+
+const CN_IDX_PROC: u32 = 1;
+const CN_VAL_PROC: u32 = 1;
+/// Size of struct cn_msg.
+const CN_MSG_LEN: usize = 20;
+const PROC_CN_MCAST_LISTEN: u32 = 1;
+
+const PROC_EVENT_NONE: u32 = 0;
+const PROC_EVENT_FORK: u32 = 0x1;
+const PROC_EVENT_EXEC: u32 = 0x2;
+const PROC_EVENT_EXIT: u32 = 0x8000_0000;
+/// Offset of event_data in struct proc_event.
+const EVENT_DATA_OFFSET: usize = 16;
+
+const RECV_BUFFER_SIZE: usize = 4096;
+
+/// Name of a process that exited before its name could be read.
+const UNKNOWN_NAME: &str = "<unknown>";
+
+/// Event in the life of a process. Threads are ignored.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ProcEvent {
+    /// Acknowledgement of the subscription with an error number.
+    Ack(i32),
+    Fork(pid_t),
+    Exec(pid_t),
+    Exit(pid_t),
+}
+
+impl ProcEvent {
+    /// Events in the netlink messages received from the connector.
+    fn parse_all(mut data: &[u8]) -> Vec<ProcEvent> {
+        let mut events = Vec::new();
+        while let Some(len) = read_u32(data, 0).map(|len| len as usize) {
+            if len < NLMSG_HDRLEN {
+                break;
+            }
+            if let Some(event) = data
+                .get(NLMSG_HDRLEN + CN_MSG_LEN..len)
+                .and_then(ProcEvent::parse)
+            {
+                events.push(event);
+            }
+            data = data.get(align(len)..).unwrap_or_default();
+        }
+        events
+    }
+
+    /// Event in a struct proc_event.
+    fn parse(data: &[u8]) -> Option<ProcEvent> {
+        let field = |index: usize| read_u32(data, EVENT_DATA_OFFSET + 4 * index).map(|v| v as i32);
+        match read_u32(data, 0)? {
+            PROC_EVENT_NONE => Some(ProcEvent::Ack(field(0)?)),
+            PROC_EVENT_FORK => {
+                let (pid, tgid) = (field(2)?, field(3)?);
+                (pid == tgid).then_some(ProcEvent::Fork(pid))
+            }
+            PROC_EVENT_EXEC => Some(ProcEvent::Exec(field(1)?)),
+            PROC_EVENT_EXIT => {
+                let (pid, tgid) = (field(0)?, field(1)?);
+                (pid == tgid).then_some(ProcEvent::Exit(pid))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Message to subscribe to the process events.
+fn listen_message() -> Vec<u8> {
+    let len = NLMSG_HDRLEN + CN_MSG_LEN + 4;
+    let mut msg = Vec::with_capacity(len);
+    msg.extend_from_slice(&(len as u32).to_ne_bytes());
+    msg.extend_from_slice(&NLMSG_DONE.to_ne_bytes());
+    msg.extend_from_slice(&0u16.to_ne_bytes()); // flags
+    msg.extend_from_slice(&0u32.to_ne_bytes()); // sequence
+    msg.extend_from_slice(&0u32.to_ne_bytes()); // port ID
+    msg.extend_from_slice(&CN_IDX_PROC.to_ne_bytes());
+    msg.extend_from_slice(&CN_VAL_PROC.to_ne_bytes());
+    msg.extend_from_slice(&0u32.to_ne_bytes()); // sequence
+    msg.extend_from_slice(&0u32.to_ne_bytes()); // acknowledge
+    msg.extend_from_slice(&4u16.to_ne_bytes()); // length of the operation
+    msg.extend_from_slice(&0u16.to_ne_bytes()); // flags
+    msg.extend_from_slice(&PROC_CN_MCAST_LISTEN.to_ne_bytes());
+    msg
+}
+
+/// Open a netlink socket and subscribe to the process events.
+fn listen() -> io::Result<OwnedFd> {
+    let fd = unsafe {
+        libc::socket(
+            libc::AF_NETLINK,
+            libc::SOCK_DGRAM | libc::SOCK_CLOEXEC,
+            libc::NETLINK_CONNECTOR,
+        )
+    };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let socket = unsafe { OwnedFd::from_raw_fd(fd) };
+    let mut addr: libc::sockaddr_nl = unsafe { mem::zeroed() };
+    addr.nl_family = libc::AF_NETLINK as libc::sa_family_t;
+    addr.nl_groups = CN_IDX_PROC;
+    let size = mem::size_of::<libc::sockaddr_nl>() as libc::socklen_t;
+    if unsafe { libc::bind(fd, (&addr as *const libc::sockaddr_nl).cast(), size) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let msg = listen_message();
+    if unsafe { libc::send(fd, msg.as_ptr().cast(), msg.len(), 0) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(socket)
+}
+
+/// Name of a running process.
+fn read_name(pid: pid_t) -> Option<String> {
+    fs::read_to_string(format!("/proc/{pid}/comm"))
+        .ok()
+        .map(|name| name.trim_end().to_string())
+}
+
+/// Receive the events and send them with the name of the process.
+///
+/// The name must be read as soon as possible before the process exits.
+fn receive(socket: OwnedFd, sender: mpsc::Sender<(ProcEvent, Option<String>)>) {
+    let mut buffer = vec![0u8; RECV_BUFFER_SIZE];
+    loop {
+        let received = unsafe {
+            libc::recv(
+                socket.as_raw_fd(),
+                buffer.as_mut_ptr().cast(),
+                buffer.len(),
+                0,
+            )
+        };
+        if received < 0 {
+            let err = io::Error::last_os_error();
+            match err.raw_os_error() {
+                Some(libc::EINTR) => continue,
+                Some(libc::ENOBUFS) => {
+                    log::warn!("process events: some events are lost");
+                    continue;
+                }
+                _ => {
+                    log::error!("process events: {err}");
+                    return;
+                }
+            }
+        }
+        for event in ProcEvent::parse_all(&buffer[..received as usize]) {
+            let name = match event {
+                ProcEvent::Fork(pid) | ProcEvent::Exec(pid) => read_name(pid),
+                _ => None,
+            };
+            if sender.send((event, name)).is_err() {
+                return;
+            }
+        }
+    }
+}
+
+/// Processes that started and exited since the last sample.
+#[derive(Debug, Default)]
+struct ShortLivedCounter {
+    /// Processes started since the last sample with their name if known.
+    started: HashMap<pid_t, Option<String>>,
+    /// Processes started and exited since the last sample.
+    exited: Vec<(pid_t, String)>,
+}
+
+impl ShortLivedCounter {
+    fn record(&mut self, event: ProcEvent, name: Option<String>) {
+        match event {
+            ProcEvent::Ack(_) => (),
+            ProcEvent::Fork(pid) => {
+                self.started.insert(pid, name);
+            }
+            ProcEvent::Exec(pid) => {
+                if let (Some(last_name), Some(_)) = (self.started.get_mut(&pid), &name) {
+                    *last_name = name;
+                }
+            }
+            ProcEvent::Exit(pid) => {
+                if let Some(name) = self.started.remove(&pid) {
+                    let name = name.unwrap_or_else(|| UNKNOWN_NAME.to_string());
+                    self.exited.push((pid, name));
+                }
+            }
+        }
+    }
+
+    /// Processes started and exited since the last call.
+    fn take(&mut self) -> Vec<(pid_t, String)> {
+        self.started.clear();
+        mem::take(&mut self.exited)
+    }
+}
+
+/// Detect the processes that start and exit between two samples.
+pub struct ShortLivedMonitor {
+    events: mpsc::Receiver<(ProcEvent, Option<String>)>,
+    counter: ShortLivedCounter,
+}
+
+impl ShortLivedMonitor {
+    pub fn new() -> io::Result<Self> {
+        let socket = listen()?;
+        let (sender, events) = mpsc::channel();
+        thread::spawn(move || receive(socket, sender));
+        Ok(Self {
+            events,
+            counter: ShortLivedCounter::default(),
+        })
+    }
+
+    /// Processes that started and exited since the previous call.
+    ///
+    /// Return an error if the subscription has been refused.
+    pub fn take(&mut self) -> io::Result<Vec<StateEvent>> {
+        while let Ok((event, name)) = self.events.try_recv() {
+            if let ProcEvent::Ack(errno) = event {
+                if errno != 0 {
+                    return Err(io::Error::from_raw_os_error(errno));
+                }
+            }
+            self.counter.record(event, name);
+        }
+        Ok(self
+            .counter
+            .take()
+            .into_iter()
+            .map(|(pid, name)| StateEvent::ShortLived { pid, name })
+            .collect())
+    }
+}
+
+/// Number of short-lived processes by name, the most frequent first.
+pub fn count_by_name(events: &[StateEvent]) -> Vec<(&str, usize)> {
+    let mut counts = HashMap::new();
+    for event in events {
+        *counts.entry(event.name()).or_insert(0) += 1;
+    }
+    let mut counts = counts.into_iter().collect::<Vec<(&str, usize)>>();
+    counts.sort_by(|(name1, count1), (name2, count2)| {
+        count2.cmp(count1).then_with(|| name1.cmp(name2))
+    });
+    counts
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    /// Netlink message with a proc_event.
+    fn event_message(what: u32, fields: &[i32]) -> Vec<u8> {
+        let mut event = Vec::new();
+        event.extend_from_slice(&what.to_ne_bytes());
+        event.extend_from_slice(&0u32.to_ne_bytes()); // CPU
+        event.extend_from_slice(&0u64.to_ne_bytes()); // timestamp
+        for field in fields {
+            event.extend_from_slice(&field.to_ne_bytes());
+        }
+        let len = NLMSG_HDRLEN + CN_MSG_LEN + event.len();
+        let mut msg = (len as u32).to_ne_bytes().to_vec();
+        msg.resize(NLMSG_HDRLEN + CN_MSG_LEN, 0);
+        msg.extend(event);
+        msg.resize(align(len), 0);
+        msg
+    }
+
+    #[test]
+    fn test_listen_message() {
+        let msg = listen_message();
+        assert_eq!(40, msg.len());
+        assert_eq!(Some(40), read_u32(&msg, 0));
+        assert_eq!(Some(CN_IDX_PROC), read_u32(&msg, NLMSG_HDRLEN));
+        assert_eq!(
+            Some(PROC_CN_MCAST_LISTEN),
+            read_u32(&msg, NLMSG_HDRLEN + CN_MSG_LEN)
+        );
+    }
+
+    #[test]
+    fn test_parse_events() {
+        let mut data = event_message(PROC_EVENT_NONE, &[0]);
+        data.extend(event_message(PROC_EVENT_FORK, &[10, 10, 20, 20]));
+        data.extend(event_message(PROC_EVENT_FORK, &[10, 10, 21, 20])); // thread
+        data.extend(event_message(PROC_EVENT_EXEC, &[20, 20]));
+        data.extend(event_message(PROC_EVENT_EXIT, &[21, 20, 0, 0])); // thread
+        data.extend(event_message(PROC_EVENT_EXIT, &[20, 20, 0, 0]));
+        data.extend(event_message(0x4, &[20, 20, 0, 0])); // UID change
+        assert_eq!(
+            vec![
+                ProcEvent::Ack(0),
+                ProcEvent::Fork(20),
+                ProcEvent::Exec(20),
+                ProcEvent::Exit(20)
+            ],
+            ProcEvent::parse_all(&data)
+        );
+    }
+
+    #[test]
+    fn test_short_lived_counter() {
+        let name = |name: &str| Some(name.to_string());
+        let mut counter = ShortLivedCounter::default();
+        counter.record(ProcEvent::Exit(5), None); // started before
+        counter.record(ProcEvent::Fork(10), name("bash"));
+        counter.record(ProcEvent::Exec(10), name("make"));
+        counter.record(ProcEvent::Fork(11), None);
+        counter.record(ProcEvent::Exec(11), None);
+        counter.record(ProcEvent::Fork(12), name("cc"));
+        counter.record(ProcEvent::Exit(11), None);
+        counter.record(ProcEvent::Exit(10), None);
+        assert_eq!(
+            vec![(11, UNKNOWN_NAME.to_string()), (10, "make".to_string())],
+            counter.take()
+        );
+        counter.record(ProcEvent::Exit(12), None); // alive at the last sample
+        assert!(counter.take().is_empty());
+    }
+
+    #[test]
+    fn test_count_by_name() {
+        let event = |pid, name: &str| StateEvent::ShortLived {
+            pid,
+            name: name.to_string(),
+        };
+        let events = [
+            event(1, "cc"),
+            event(2, "sh"),
+            event(3, "cc"),
+            event(4, "as"),
+        ];
+        assert_eq!(
+            vec![("cc", 2), ("as", 1), ("sh", 1)],
+            count_by_name(&events)
+        );
+    }
+}
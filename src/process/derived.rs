@@ -0,0 +1,309 @@
+// Oprs -- process monitor for Linux
+// Copyright (C) 2025  Laurent Pelecq
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Metrics computed with an arithmetic expression over other metrics.
+//!
+//! Expressions use the operators `+`, `-`, `*`, `/`, parentheses, decimal
+//! constants and the names of the metrics (ex: `mem:rss / thread:count`).
+
+use nom::{
+    branch::alt,
+    bytes::complete::take_while1,
+    character::complete::{char, digit1, one_of, space0},
+    combinator::{all_consuming, map, map_res, opt, recognize},
+    multi::many0,
+    sequence::{delimited, pair, preceded},
+    IResult,
+};
+use std::str::FromStr;
+
+use super::{MetricDataType, MetricId};
+
+/// Prefix of the names of derived metrics.
+pub const DERIVED_PREFIX: &str = "derived:";
+
+#[derive(thiserror::Error, Debug)]
+pub enum DerivedError {
+    #[error("{0}: invalid name for a derived metric")]
+    InvalidName(String),
+    #[error("{0}: invalid expression")]
+    InvalidExpression(String),
+    #[error("{0}: expression without metric")]
+    NoMetric(String),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Operator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+}
+
+impl Operator {
+    fn from_char(c: char) -> Self {
+        match c {
+            '+' => Operator::Add,
+            '-' => Operator::Subtract,
+            '*' => Operator::Multiply,
+            '/' => Operator::Divide,
+            _ => panic!("{c}: internal error, invalid operator"),
+        }
+    }
+
+    fn apply(self, left: f64, right: f64) -> f64 {
+        match self {
+            Operator::Add => left + right,
+            Operator::Subtract => left - right,
+            Operator::Multiply => left * right,
+            Operator::Divide if right == 0.0 => 0.0,
+            Operator::Divide => left / right,
+        }
+    }
+}
+
+/// Arithmetic expression
+#[derive(Clone, Debug, PartialEq)]
+pub enum Expression {
+    Constant(f64),
+    Metric(MetricId),
+    Binary(Operator, Box<Expression>, Box<Expression>),
+}
+
+impl Expression {
+    fn evaluate<F>(&self, value_of: &mut F) -> f64
+    where
+        F: FnMut(MetricId) -> u64,
+    {
+        match self {
+            Expression::Constant(value) => *value,
+            Expression::Metric(id) => value_of(*id) as f64,
+            Expression::Binary(op, left, right) => {
+                let left = left.evaluate(value_of);
+                op.apply(left, right.evaluate(value_of))
+            }
+        }
+    }
+
+    /// Metrics in the expression from left to right.
+    fn metrics(&self, ids: &mut Vec<MetricId>) {
+        match self {
+            Expression::Constant(_) => (),
+            Expression::Metric(id) => ids.push(*id),
+            Expression::Binary(_, left, right) => {
+                left.metrics(ids);
+                right.metrics(ids);
+            }
+        }
+    }
+}
+
+fn fold_operations(first: Expression, rest: Vec<(char, Expression)>) -> Expression {
+    rest.into_iter().fold(first, |left, (op, right)| {
+        Expression::Binary(Operator::from_char(op), Box::new(left), Box::new(right))
+    })
+}
+
+fn parse_constant(input: &str) -> IResult<&str, Expression> {
+    map_res(
+        recognize(pair(digit1, opt(pair(char('.'), digit1)))),
+        |s: &str| s.parse::<f64>().map(Expression::Constant),
+    )(input)
+}
+
+fn parse_metric(input: &str) -> IResult<&str, Expression> {
+    map_res(
+        take_while1(|c: char| c == ':' || c.is_ascii_lowercase()),
+        |s: &str| MetricId::from_str(s).map(Expression::Metric),
+    )(input)
+}
+
+fn parse_factor(input: &str) -> IResult<&str, Expression> {
+    delimited(
+        space0,
+        alt((
+            parse_constant,
+            parse_metric,
+            delimited(char('('), parse_sum, char(')')),
+        )),
+        space0,
+    )(input)
+}
+
+fn parse_product(input: &str) -> IResult<&str, Expression> {
+    map(
+        pair(parse_factor, many0(pair(one_of("*/"), parse_factor))),
+        |(first, rest)| fold_operations(first, rest),
+    )(input)
+}
+
+fn parse_sum(input: &str) -> IResult<&str, Expression> {
+    map(
+        pair(parse_product, many0(pair(one_of("+-"), parse_product))),
+        |(first, rest)| fold_operations(first, rest),
+    )(input)
+}
+
+/// Parse an arithmetic expression over metrics.
+pub fn parse_expression(input: &str) -> Result<Expression, DerivedError> {
+    let (_, expression) = all_consuming(preceded(space0, parse_sum))(input)
+        .map_err(|_| DerivedError::InvalidExpression(input.to_string()))?;
+    let mut ids = Vec::new();
+    expression.metrics(&mut ids);
+    if ids.is_empty() {
+        Err(DerivedError::NoMetric(input.to_string()))
+    } else {
+        Ok(expression)
+    }
+}
+
+/// Check that a name is the prefix followed by lowercase letters, digits or underscores.
+pub fn check_name(name: &str) -> Result<(), DerivedError> {
+    match name.strip_prefix(DERIVED_PREFIX) {
+        Some(suffix)
+            if !suffix.is_empty()
+                && suffix
+                    .chars()
+                    .all(|c| c == '_' || c.is_ascii_lowercase() || c.is_ascii_digit()) =>
+        {
+            Ok(())
+        }
+        _ => Err(DerivedError::InvalidName(name.to_string())),
+    }
+}
+
+/// Metric computed from other metrics
+#[derive(Debug)]
+pub struct DerivedMetric {
+    name: &'static str,
+    expression: Expression,
+    operands: Vec<MetricId>,
+}
+
+impl DerivedMetric {
+    /// Metric with a name such as `derived:name`.
+    ///
+    /// The names of the metrics are static strings. Since derived metrics are
+    /// defined once at startup, the name is leaked.
+    pub fn new(name: &str, expression: &str) -> Result<Self, DerivedError> {
+        check_name(name)?;
+        let expression = parse_expression(expression)?;
+        let mut operands = Vec::new();
+        expression.metrics(&mut operands);
+        Ok(Self {
+            name: Box::leak(name.to_string().into_boxed_str()),
+            expression,
+            operands,
+        })
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// The first metric of the expression that gives the default format.
+    pub fn first_operand(&self) -> MetricId {
+        self.operands[0]
+    }
+
+    /// A counter if all the operands are counters, a gauge otherwise.
+    pub fn data_type(&self) -> MetricDataType {
+        if self
+            .operands
+            .iter()
+            .all(|id| matches!(id.data_type(), MetricDataType::Counter))
+        {
+            MetricDataType::Counter
+        } else {
+            MetricDataType::Gauge
+        }
+    }
+
+    /// Compute the value, negative values are replaced by zero.
+    pub fn evaluate<F>(&self, mut value_of: F) -> u64
+    where
+        F: FnMut(MetricId) -> u64,
+    {
+        let value = self.expression.evaluate(&mut value_of);
+        if value.is_finite() && value > 0.0 {
+            value.round() as u64
+        } else {
+            0
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::{check_name, parse_expression, DerivedMetric, Expression, Operator};
+    use crate::process::{MetricDataType, MetricId};
+
+    #[test]
+    fn test_parse_expression() {
+        assert_eq!(
+            Expression::Binary(
+                Operator::Subtract,
+                Box::new(Expression::Metric(MetricId::IoReadTotal)),
+                Box::new(Expression::Metric(MetricId::IoReadStorage)),
+            ),
+            parse_expression("io:read:total - io:read:storage").unwrap()
+        );
+        assert!(parse_expression("(mem:rss+mem:vm) / 2.5").is_ok());
+        assert!(parse_expression("mem:rss +").is_err());
+        assert!(parse_expression("mem:unknown * 2").is_err());
+        assert!(parse_expression("1 + 2").is_err());
+    }
+
+    #[test]
+    fn test_check_name() {
+        assert!(check_name("derived:cache_hit2").is_ok());
+        assert!(check_name("derived:").is_err());
+        assert!(check_name("derived:Cache").is_err());
+        assert!(check_name("cache_hit").is_err());
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let values = |id| match id {
+            MetricId::MemRss => 1000,
+            MetricId::ThreadCount => 3,
+            MetricId::IoReadTotal => 10,
+            MetricId::IoReadStorage => 4,
+            _ => 0,
+        };
+        let per_thread = DerivedMetric::new("derived:rss", "mem:rss / thread:count").unwrap();
+        assert_eq!("derived:rss", per_thread.name());
+        assert_eq!(MetricId::MemRss, per_thread.first_operand());
+        assert!(matches!(per_thread.data_type(), MetricDataType::Gauge));
+        assert_eq!(333, per_thread.evaluate(values));
+
+        let precedence =
+            DerivedMetric::new("derived:p", "2 + mem:rss * (thread:count - 1)").unwrap();
+        assert_eq!(2002, precedence.evaluate(values));
+
+        let cache = DerivedMetric::new("derived:cache", "io:read:total - io:read:storage").unwrap();
+        assert!(matches!(cache.data_type(), MetricDataType::Counter));
+        assert_eq!(6, cache.evaluate(values));
+
+        let negative =
+            DerivedMetric::new("derived:neg", "io:read:storage - io:read:total").unwrap();
+        assert_eq!(0, negative.evaluate(values));
+
+        let zero = DerivedMetric::new("derived:zero", "mem:rss / mem:vm").unwrap();
+        assert_eq!(0, zero.evaluate(values));
+    }
+}
@@ -0,0 +1,180 @@
+// Oprs -- process monitor for Linux
+// Copyright (C) 2025  Laurent Pelecq
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Format values with an external command.
+
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    io::{self, Read, Write},
+    os::fd::AsRawFd,
+    process::{Child, Command, Stdio},
+    time::{Duration, Instant},
+};
+
+use crate::privileges::drop_capabilities;
+
+/// Maximum number of formatted values kept in the cache.
+const CACHE_SIZE: usize = 4096;
+
+/// Time given to the command to print the formatted value.
+const FORMAT_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Run a shell command that reads the raw value on its standard input and
+/// writes the formatted text on its standard output.
+///
+/// The results are cached per value since the same values are usually
+/// formatted again and again. A command that doesn't answer in time is killed
+/// and not run anymore.
+#[derive(Debug)]
+pub struct ExternalFormatter {
+    command: String,
+    cache: RefCell<HashMap<u64, Option<String>>>,
+    disabled: Cell<bool>,
+}
+
+impl ExternalFormatter {
+    pub fn new(command: &str) -> Self {
+        Self {
+            command: command.to_string(),
+            cache: RefCell::new(HashMap::new()),
+            disabled: Cell::new(false),
+        }
+    }
+
+    /// Read the output of the command until it exits or the timeout expires.
+    fn read_output(child: &mut Child, timeout: Duration) -> io::Result<Vec<u8>> {
+        let mut output = Vec::new();
+        let Some(mut stdout) = child.stdout.take() else {
+            return Ok(output);
+        };
+        let deadline = Instant::now() + timeout;
+        let mut buf = [0u8; 256];
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let mut pollfd = libc::pollfd {
+                fd: stdout.as_raw_fd(),
+                events: libc::POLLIN,
+                revents: 0,
+            };
+            let rc = unsafe { libc::poll(&mut pollfd, 1, remaining.as_millis() as libc::c_int) };
+            if rc < 0 {
+                let err = io::Error::last_os_error();
+                if err.kind() != io::ErrorKind::Interrupted {
+                    return Err(err);
+                }
+            } else if rc == 0 {
+                return Err(io::Error::from(io::ErrorKind::TimedOut));
+            } else {
+                match stdout.read(&mut buf)? {
+                    0 => return Ok(output),
+                    count => output.extend_from_slice(&buf[..count]),
+                }
+            }
+        }
+    }
+
+    /// Run the command and return the first line of the output.
+    fn run(&self, value: u64) -> io::Result<String> {
+        let mut child = drop_capabilities(
+            Command::new("sh")
+                .arg("-c")
+                .arg(&self.command)
+                .stdin(Stdio::piped())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::null()),
+        )
+        .spawn()?;
+        if let Some(mut stdin) = child.stdin.take() {
+            // The command may exit without reading its input.
+            let _ = writeln!(stdin, "{value}");
+        }
+        let output = match Self::read_output(&mut child, FORMAT_TIMEOUT) {
+            Ok(output) => output,
+            Err(err) => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(err);
+            }
+        };
+        let status = child.wait()?;
+        if status.success() {
+            let stdout = String::from_utf8_lossy(&output);
+            Ok(stdout.lines().next().unwrap_or_default().trim().to_string())
+        } else {
+            Err(io::Error::other(format!("{}: {}", self.command, status)))
+        }
+    }
+
+    /// Formatted value or None if the command failed.
+    pub fn format(&self, value: u64) -> Option<String> {
+        let mut cache = self.cache.borrow_mut();
+        if let Some(text) = cache.get(&value) {
+            return text.clone();
+        }
+        if self.disabled.get() {
+            return None;
+        }
+        let text = match self.run(value) {
+            Ok(text) => Some(text),
+            Err(err) if err.kind() == io::ErrorKind::TimedOut => {
+                log::warn!("{}: formatter disabled, no answer in time", self.command);
+                self.disabled.set(true);
+                None
+            }
+            Err(err) => {
+                log::warn!("formatter failed: {err}");
+                None
+            }
+        };
+        if cache.len() >= CACHE_SIZE {
+            cache.clear();
+        }
+        cache.insert(value, text.clone());
+        text
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::ExternalFormatter;
+
+    #[test]
+    fn test_external_formatter() {
+        let formatter = ExternalFormatter::new("read v; echo \"$((v / 1000)) K\"");
+        assert_eq!(Some("42 K".to_string()), formatter.format(42_000));
+        assert_eq!(Some("42 K".to_string()), formatter.format(42_000));
+        assert_eq!(Some("1 K".to_string()), formatter.format(1_000));
+        assert_eq!(2, formatter.cache.borrow().len());
+    }
+
+    #[test]
+    fn test_failing_formatter() {
+        let formatter = ExternalFormatter::new("exit 1");
+        assert_eq!(None, formatter.format(1));
+        assert_eq!(Some(&None), formatter.cache.borrow().get(&1));
+    }
+
+    #[test]
+    fn test_slow_formatter() {
+        let formatter = ExternalFormatter::new("exec sleep 10");
+        assert_eq!(None, formatter.format(1));
+        assert!(formatter.disabled.get());
+        assert_eq!(None, formatter.format(2));
+        assert!(formatter.cache.borrow().get(&2).is_none());
+    }
+}
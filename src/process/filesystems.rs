@@ -0,0 +1,252 @@
+// Oprs -- process monitor for Linux
+// Copyright (C) 2020-2024 Laurent Pelecq
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Usage of the filesystems of configured mount points.
+//!
+//! A mount point is defined with a name (ex: `data = /var/lib/data`) and its
+//! usage is selected with the metrics `fs:data:used` and `fs:data:ratio`.
+//!
+//! Since statvfs blocks on an unresponsive network filesystem, the usage is read
+//! in a background thread and the samples take the last value read.
+
+use std::{
+    cell::{Cell, OnceCell},
+    ffi::CString,
+    mem::MaybeUninit,
+    os::unix::ffi::OsStrExt,
+    path::{Path, PathBuf},
+    sync::{Arc, Condvar, Mutex},
+    thread,
+    time::Duration,
+};
+
+use super::MetricId;
+
+/// Prefix of the names of filesystem metrics.
+pub const FS_PREFIX: &str = "fs:";
+
+/// Suffix of the metric of the used size.
+const USED_SUFFIX: &str = ":used";
+
+/// Suffix of the metric of the ratio of used size.
+const RATIO_SUFFIX: &str = ":ratio";
+
+/// Delay between two reads of the usage.
+const USAGE_DELAY: Duration = Duration::from_secs(5);
+
+/// Maximum delay to wait for the first usage.
+const FIRST_USAGE_TIMEOUT: Duration = Duration::from_secs(1);
+
+#[derive(thiserror::Error, Debug)]
+pub enum FilesystemError {
+    #[error("{0}: invalid name for a filesystem")]
+    InvalidName(String),
+    #[error("{0}: mount point must be an absolute path")]
+    InvalidPath(String),
+}
+
+/// Check that a name is made of lowercase letters, digits or underscores.
+pub fn check_name(name: &str) -> Result<(), FilesystemError> {
+    if !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c == '_' || c.is_ascii_lowercase() || c.is_ascii_digit())
+    {
+        Ok(())
+    } else {
+        Err(FilesystemError::InvalidName(name.to_string()))
+    }
+}
+
+/// Split a metric name `fs:NAME:used` or `fs:NAME:ratio` in the name and the metric.
+pub fn split_metric_name(metric_name: &str) -> Option<(&str, MetricId)> {
+    let name = metric_name.strip_prefix(FS_PREFIX)?;
+    let (name, id) = match name.strip_suffix(USED_SUFFIX) {
+        Some(name) => (name, MetricId::FsUsed),
+        None => (name.strip_suffix(RATIO_SUFFIX)?, MetricId::FsRatio),
+    };
+    check_name(name).ok().map(|_| (name, id))
+}
+
+/// Used size in bytes and ratio in per thousand like `df`.
+///
+/// The ratio is computed on the space available to unprivileged users.
+fn usage(path: &Path) -> Option<(u64, u64)> {
+    let path = CString::new(path.as_os_str().as_bytes()).ok()?;
+    let mut buf = MaybeUninit::<libc::statvfs>::uninit();
+    if unsafe { libc::statvfs(path.as_ptr(), buf.as_mut_ptr()) } != 0 {
+        return None;
+    }
+    let buf = unsafe { buf.assume_init() };
+    let used = buf.f_blocks.saturating_sub(buf.f_bfree) * buf.f_frsize;
+    let available = buf.f_bavail * buf.f_frsize;
+    let ratio = (used * 1000).checked_div(used + available).unwrap_or(0);
+    Some((used, ratio))
+}
+
+/// Check the name and the mount point of a filesystem.
+pub fn check(name: &str, mount_point: &str) -> Result<(), FilesystemError> {
+    check_name(name)?;
+    if mount_point.starts_with('/') {
+        Ok(())
+    } else {
+        Err(FilesystemError::InvalidPath(mount_point.to_string()))
+    }
+}
+
+/// Usage of a filesystem read in a background thread.
+#[derive(Debug, Default)]
+struct SharedUsage {
+    /// Last usage: None until it is read, Some(None) if it cannot be read.
+    last: Mutex<Option<Option<(u64, u64)>>>,
+    read: Condvar,
+}
+
+impl SharedUsage {
+    /// Read the usage periodically until the last reference is dropped.
+    fn watch(mount_point: PathBuf) -> Arc<Self> {
+        let shared = Arc::new(Self::default());
+        let weak = Arc::downgrade(&shared);
+        let spawned = thread::Builder::new()
+            .name(String::from("fs-usage"))
+            .spawn(move || loop {
+                let current = usage(&mount_point);
+                if current.is_none() {
+                    log::debug!("{}: cannot get the filesystem usage", mount_point.display());
+                }
+                let Some(shared) = weak.upgrade() else {
+                    break;
+                };
+                *shared.last.lock().unwrap() = Some(current);
+                shared.read.notify_all();
+                drop(shared);
+                thread::sleep(USAGE_DELAY);
+            });
+        if let Err(err) = spawned {
+            log::error!("cannot start the thread of the filesystem usage: {err}");
+        }
+        shared
+    }
+
+    /// Last usage, waiting at most the timeout if it has never been read.
+    fn get(&self, timeout: Duration) -> Option<(u64, u64)> {
+        let last = self.last.lock().unwrap();
+        let (last, _) = self
+            .read
+            .wait_timeout_while(last, timeout, |last| last.is_none())
+            .unwrap();
+        last.flatten()
+    }
+}
+
+/// Filesystem of a mount point
+#[derive(Debug)]
+pub struct Filesystem {
+    used_name: String,
+    ratio_name: String,
+    mount_point: PathBuf,
+    /// Usage read in the background, started with the first value.
+    usage: OnceCell<Arc<SharedUsage>>,
+    /// Whether the first usage has already been waited for.
+    waited: Cell<bool>,
+}
+
+impl Filesystem {
+    /// Filesystem with the metrics `fs:NAME:used` and `fs:NAME:ratio`.
+    pub fn new(name: &str, mount_point: &str) -> Result<Self, FilesystemError> {
+        check(name, mount_point)?;
+        Ok(Self {
+            used_name: format!("{FS_PREFIX}{name}{USED_SUFFIX}"),
+            ratio_name: format!("{FS_PREFIX}{name}{RATIO_SUFFIX}"),
+            mount_point: PathBuf::from(mount_point),
+            usage: OnceCell::new(),
+            waited: Cell::new(false),
+        })
+    }
+
+    /// Name of the metric.
+    pub fn metric_name(&self, id: MetricId) -> &str {
+        match id {
+            MetricId::FsRatio => &self.ratio_name,
+            _ => &self.used_name,
+        }
+    }
+
+    /// Whether this is the filesystem of the metric.
+    pub fn has_metric(&self, metric_name: &str) -> bool {
+        self.used_name == metric_name || self.ratio_name == metric_name
+    }
+
+    /// Last value of the metric, zero if the mount point cannot be read.
+    ///
+    /// Only the first call waits for the usage, for a limited time.
+    pub fn value(&self, id: MetricId) -> u64 {
+        let usage = self
+            .usage
+            .get_or_init(|| SharedUsage::watch(self.mount_point.clone()));
+        let timeout = if self.waited.replace(true) {
+            Duration::ZERO
+        } else {
+            FIRST_USAGE_TIMEOUT
+        };
+        match usage.get(timeout) {
+            Some((_, ratio)) if matches!(id, MetricId::FsRatio) => ratio,
+            Some((used, _)) => used,
+            None => 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::{check, split_metric_name, Filesystem};
+    use crate::process::MetricId;
+
+    #[test]
+    fn test_split_metric_name() {
+        assert_eq!(
+            Some(("data", MetricId::FsUsed)),
+            split_metric_name("fs:data:used")
+        );
+        assert_eq!(
+            Some(("root_2", MetricId::FsRatio)),
+            split_metric_name("fs:root_2:ratio")
+        );
+        assert_eq!(None, split_metric_name("fs:data:free"));
+        assert_eq!(None, split_metric_name("fs::used"));
+        assert_eq!(None, split_metric_name("fs:/var:used"));
+        assert_eq!(None, split_metric_name("mem:rss"));
+    }
+
+    #[test]
+    fn test_filesystem() {
+        let fs = Filesystem::new("root", "/").unwrap();
+        assert_eq!("fs:root:used", fs.metric_name(MetricId::FsUsed));
+        assert_eq!("fs:root:ratio", fs.metric_name(MetricId::FsRatio));
+        assert!(fs.has_metric("fs:root:ratio"));
+        assert!(!fs.has_metric("fs:data:ratio"));
+        assert!(fs.value(MetricId::FsRatio) <= 1000);
+        // The usage is kept until it's read again.
+        assert!(fs.value(MetricId::FsUsed) > 0);
+        assert!(check("root", "/").is_ok());
+        assert!(check("Root", "/").is_err());
+        assert!(check("root", "var").is_err());
+        assert!(Filesystem::new("root", "var").is_err());
+        let missing = Filesystem::new("missing", "/nonexistent/mount/point").unwrap();
+        assert_eq!(0, missing.value(MetricId::FsUsed));
+    }
+}
@@ -38,13 +38,14 @@ pub(crate) use super::mocks::procfs::{
     ProcResult,
 };
 
+#[cfg(feature = "tui")]
 fn format_path(path: PathBuf) -> String {
     path.to_str()
         .map(String::from)
         .unwrap_or_else(|| path.to_string_lossy().to_string())
 }
 
-#[cfg(not(test))]
+#[cfg(all(feature = "tui", not(test)))]
 mod format {
     use super::format_path;
     use procfs::ProcError;
@@ -71,7 +72,7 @@ mod format {
     }
 }
 
-#[cfg(test)]
+#[cfg(all(feature = "tui", test))]
 mod format {
     use super::super::mocks::procfs::ProcError;
 
@@ -80,7 +81,7 @@ mod format {
     }
 }
 
-use super::{FormattedMetric, ProcessStat, SystemConf};
+use super::{FormattedMetric, LimitBreach, ProcessStat, SystemConf, ThreadCreation};
 
 #[derive(thiserror::Error, Debug)]
 pub enum ProcessError {
@@ -93,6 +94,7 @@ pub enum ProcessError {
 pub type ProcessResult<T> = Result<T, ProcessError>;
 
 /// Format a result returned by procfs.
+#[cfg(feature = "tui")]
 pub fn format_result(res: ProcResult<PathBuf>) -> String {
     match res {
         Ok(path) => format_path(path),
@@ -124,6 +126,16 @@ fn new_stat(process: &Process) -> ProcessResult<process::Stat> {
         .map_err(|_| ProcessError::UnknownProcess(process.pid()))
 }
 
+/// Whether the scheduling policy is real-time (SCHED_FIFO or SCHED_RR).
+///
+/// A real-time process that doesn't yield can starve the other processes.
+fn is_realtime(stat: &process::Stat) -> bool {
+    matches!(
+        stat.policy.map(|policy| policy as i32),
+        Some(libc::SCHED_FIFO | libc::SCHED_RR)
+    )
+}
+
 /// Record CPU activity.
 #[derive(Debug, Default)]
 struct CpuActivity {
@@ -161,6 +173,8 @@ pub struct ProcessInfo {
     /// Process name.
     #[getset(get = "pub")]
     name: String,
+    /// Command name in the status, it changes when the process executes another program.
+    comm: String,
     /// Process instance.
     #[getset(get = "pub")]
     process: Process,
@@ -172,11 +186,16 @@ pub struct ProcessInfo {
     /// On Linux, kernel processes are children of process 2.
     #[getset(get_copy = "pub")]
     is_kernel: bool,
+    /// Whether the process has a real-time scheduling policy.
+    #[getset(get_copy = "pub")]
+    is_realtime: bool,
     /// Process exists but is hidden.
     #[getset(get_copy = "pub")]
     hidden: bool,
     /// Activity of the process.
     activity: RefCell<CpuActivity>,
+    /// Threads of the process to count the new ones.
+    threads: RefCell<ThreadCreation>,
 }
 
 impl ProcessInfo {
@@ -188,7 +207,9 @@ impl ProcessInfo {
         let state = stat.state;
         let exe_name = exe_name(&process);
         let is_kernel = exe_name.is_none();
+        let is_realtime = is_realtime(&stat);
         let name = exe_name.unwrap_or_else(|| format!("({})", stat.comm));
+        let comm = stat.comm.clone();
         let mut activity = CpuActivity::default();
         activity.update(&stat);
         let stats = RefCell::new(ProcessStat::with_stat(stat));
@@ -198,11 +219,14 @@ impl ProcessInfo {
             start_time,
             state,
             name,
+            comm,
             process,
             stats,
             is_kernel,
+            is_realtime,
             hidden: true,
             activity: RefCell::new(activity),
+            threads: RefCell::new(ThreadCreation::default()),
         })
     }
 
@@ -215,6 +239,7 @@ impl ProcessInfo {
         self.process.uid().ok()
     }
 
+    #[cfg(feature = "tui")]
     pub fn cmdline(&self) -> String {
         self.process
             .cmdline()
@@ -222,6 +247,14 @@ impl ProcessInfo {
             .unwrap_or_else(|_| String::from("<zombie>"))
     }
 
+    /// Kernel function in which the process is blocked if it's in uninterruptible sleep.
+    pub fn wait_channel(&self) -> Option<String> {
+        (self.state == 'D')
+            .then(|| self.process.wchan().ok())
+            .flatten()
+            .filter(|wchan| !wchan.is_empty() && wchan != "0")
+    }
+
     pub fn hide(&mut self) {
         self.hidden = true;
     }
@@ -241,20 +274,46 @@ impl ProcessInfo {
             Err(ProcessError::UnknownProcess(self.pid))
         } else {
             self.parent_pid = stat.ppid;
+            self.state = stat.state;
+            self.is_realtime = is_realtime(&stat);
+            // The name is only resolved again after an exec.
+            if stat.comm != self.comm {
+                let name = if self.is_kernel {
+                    Some(format!("({})", stat.comm))
+                } else {
+                    exe_name(&self.process)
+                };
+                if let Some(name) = name {
+                    self.name = name;
+                }
+                self.comm = stat.comm.clone();
+            }
             self.activity.borrow_mut().update(&stat);
             self.stats = RefCell::new(ProcessStat::with_stat(stat));
             Ok(())
         }
     }
 
+    /// Highest breach of the limits of the metrics given their values.
+    pub fn limit_breach(&self, metrics: Iter<FormattedMetric>, values: &[u64]) -> LimitBreach {
+        self.stats
+            .borrow_mut()
+            .limit_breach(metrics, values, &self.process)
+    }
+
     pub fn extract_metrics(
         &self,
         metrics: Iter<FormattedMetric>,
+        selected: Option<&[bool]>,
         sysconf: &SystemConf,
     ) -> Vec<u64> {
-        self.stats
-            .borrow_mut()
-            .extract_metrics(metrics, &self.process, sysconf)
+        self.stats.borrow_mut().extract_metrics(
+            metrics,
+            selected,
+            &self.process,
+            sysconf,
+            &mut self.threads.borrow_mut(),
+        )
     }
 }
 
@@ -343,6 +402,8 @@ pub struct Forest {
     arena: Arena<ProcessInfo>,
     roots: BTreeSet<NodeId>,
     processes: BTreeMap<pid_t, NodeId>,
+    /// Only the processes of this user are enumerated.
+    owner: Option<u32>,
 }
 
 impl Forest {
@@ -351,9 +412,15 @@ impl Forest {
             arena: Arena::new(),
             roots: BTreeSet::new(),
             processes: BTreeMap::new(),
+            owner: None,
         }
     }
 
+    /// Restrict the enumerated processes to the ones of a user.
+    pub fn set_owner(&mut self, owner: Option<u32>) {
+        self.owner = owner;
+    }
+
     /// Get a process that is known to be in the arena.
     fn get_known_info(&self, node_id: NodeId) -> &ProcessInfo {
         self.arena
@@ -547,7 +614,7 @@ impl Forest {
     /// Descendants of a pid
     ///
     /// Include the root process itself.
-    pub fn descendants(&self, pid: pid_t) -> ProcessResult<Descendants> {
+    pub fn descendants(&self, pid: pid_t) -> ProcessResult<Descendants<'_, '_>> {
         match self.processes.get(&pid) {
             Some(node_id) => Ok(Descendants {
                 forest: self,
@@ -557,6 +624,21 @@ impl Forest {
         }
     }
 
+    /// Lineage of a pid from the root of its tree down to the process itself.
+    pub fn lineage(&self, pid: pid_t) -> ProcessResult<Vec<&ProcessInfo>> {
+        match self.processes.get(&pid) {
+            Some(node_id) => {
+                let mut lineage = node_id
+                    .ancestors(&self.arena)
+                    .map(|node_id| self.get_known_info(node_id))
+                    .collect::<Vec<&ProcessInfo>>();
+                lineage.reverse();
+                Ok(lineage)
+            }
+            None => Err(ProcessError::UnknownProcess(pid)),
+        }
+    }
+
     /// Root PIDs
     pub fn root_pids(&self) -> Vec<pid_t> {
         self.iter_roots().map(|p| p.pid()).collect::<Vec<pid_t>>()
@@ -600,6 +682,19 @@ impl Forest {
         }
     }
 
+    /// Refresh the known processes without enumerating the processes in the system.
+    ///
+    /// Return false if a process doesn't exist anymore. Then a full refresh is required.
+    pub fn refresh_known(&mut self) -> bool {
+        let mut complete = true;
+        self.arena.iter_mut().for_each(|node| {
+            if !node.is_removed() && node.get_mut().refresh().is_err() {
+                complete = false;
+            }
+        });
+        complete
+    }
+
     /// Refreshes the forest and return if it has changed.
     pub fn refresh_from<I, C>(&mut self, processes: I, classifier: &C) -> bool
     where
@@ -657,10 +752,12 @@ impl Forest {
     where
         C: ProcessClassifier,
     {
+        let owner = self.owner;
         Ok(self.refresh_from(
             all_processes()
                 .map_err(|_| ProcessError::CannotAccessProcesses)?
-                .filter_map(ProcResult::ok),
+                .filter_map(ProcResult::ok)
+                .filter(|process| owner.is_none() || process.uid().ok() == owner),
             classifier,
         ))
     }
@@ -681,8 +778,8 @@ mod tests {
     };
 
     use super::{
-        pid_t, procfs::ProcessBuilder, AcceptAllProcesses, Forest, Process, ProcessClassifier,
-        ProcessInfo,
+        is_realtime, pid_t, procfs::ProcessBuilder, AcceptAllProcesses, Forest, Process,
+        ProcessClassifier, ProcessInfo,
     };
 
     fn sorted<T, I>(input: I) -> Vec<T>
@@ -837,6 +934,22 @@ mod tests {
         assert_eq!(START_TIME, st.starttime);
     }
 
+    #[test]
+    fn test_realtime_policy() {
+        let mut st = ProcessFactory::default()
+            .builder()
+            .pid(10)
+            .build()
+            .stat()
+            .unwrap();
+        for (policy, expected) in [(0, false), (1, true), (2, true), (3, false), (5, false)] {
+            st.policy = Some(policy);
+            assert_eq!(expected, is_realtime(&st), "policy {policy}");
+        }
+        st.policy = None;
+        assert!(!is_realtime(&st));
+    }
+
     #[test]
     /// Create an empty forest.
     fn test_empty() {
@@ -879,6 +992,24 @@ mod tests {
         assert!(forest.get_process(first_pid).is_none());
     }
 
+    #[test]
+    /// Refresh only known processes until one terminates.
+    fn test_refresh_known() {
+        const TTL: u16 = 2;
+        let mut factory = ProcessFactory::default();
+        let mut forest = Forest::new();
+        let processes = vec![factory.build(), factory.builder().ttl(TTL).build()];
+        let last_pid = factory.last_pid();
+        let any_proc = AcceptAllProcesses::default();
+        forest.refresh_from(processes.clone().drain(..), &any_proc);
+        assert_eq!(2, forest.size());
+        assert!(forest.refresh_known());
+        assert!(!forest.refresh_known());
+        forest.refresh_from(processes.clone().drain(..), &any_proc);
+        assert!(forest.get_process(last_pid).is_none());
+        assert!(forest.refresh_known());
+    }
+
     #[test]
     /// Test idleness.
     ///
@@ -1163,6 +1294,36 @@ mod tests {
         assert_eq!(0, forest.get_process(proc3_pid).unwrap().parent_pid());
     }
 
+    #[test]
+    /// Lineage of a process from the root.
+    ///
+    /// Tree:
+    /// 0
+    /// |_1_2
+    /// \_3_4
+    fn test_lineage() {
+        let mut factory = ProcessFactory::default();
+        let processes = factory.with_parent_pids(&[(3, Some(0))], 5);
+        let pids = processes.iter().map(|p| p.pid()).collect::<Vec<pid_t>>();
+
+        let any_proc = AcceptAllProcesses::default();
+        let mut forest = Forest::new();
+        forest.refresh_from(shuffle(processes).drain(..), &any_proc);
+
+        let lineage = |pid| {
+            forest
+                .lineage(pid)
+                .unwrap()
+                .iter()
+                .map(|pinfo| pinfo.pid())
+                .collect::<Vec<pid_t>>()
+        };
+        assert_eq!(vec![pids[0]], lineage(pids[0]));
+        assert_eq!(vec![pids[0], pids[1], pids[2]], lineage(pids[2]));
+        assert_eq!(vec![pids[0], pids[3], pids[4]], lineage(pids[4]));
+        assert!(forest.lineage(pids[4] + 100).is_err());
+    }
+
     #[test]
     /// Refresh a tree with a PID reused.
     ///
@@ -14,7 +14,10 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
+use std::fmt;
+#[cfg(feature = "tui")]
 use std::time::Duration;
+use strum_macros::{EnumString, IntoStaticStr};
 
 pub type Formatter = fn(u64) -> String;
 
@@ -150,7 +153,47 @@ pub fn human_milliseconds(millis: u64) -> String {
     }
 }
 
+/// Number of milliseconds formatted as days, hours and minutes (ex: 1d2h3m).
+///
+/// Durations under a minute are in seconds.
+pub fn compact_milliseconds(millis: u64) -> String {
+    let seconds = millis / 1000;
+    if seconds < 60 {
+        return format!("{seconds}s");
+    }
+    let minutes = seconds / 60;
+    let hours = minutes / 60;
+    let days = hours / 24;
+    if days > 0 {
+        format!("{days}d{}h{}m", hours % 24, minutes % 60)
+    } else if hours > 0 {
+        format!("{hours}h{}m", minutes % 60)
+    } else {
+        format!("{minutes}m{}s", seconds % 60)
+    }
+}
+
+/// Number of milliseconds formatted as a clock HH:MM:SS.
+///
+/// Hours are not wrapped after a day so that the values are aligned.
+pub fn clock_milliseconds(millis: u64) -> String {
+    let seconds = millis / 1000;
+    let minutes = seconds / 60;
+    format!(
+        "{:02}:{:02}:{:02}",
+        minutes / 60,
+        minutes % 60,
+        seconds % 60
+    )
+}
+
+/// Number of milliseconds formatted as whole seconds.
+pub fn whole_seconds(millis: u64) -> String {
+    (millis / 1000).to_string()
+}
+
 /// Duration in human readable format
+#[cfg(feature = "tui")]
 pub fn human_duration(duration: Duration) -> String {
     let ms = duration.as_secs() * 1000 + duration.subsec_millis() as u64;
     human_milliseconds(ms)
@@ -161,7 +204,86 @@ pub fn ratio(value: u64) -> String {
     format!("{:.1}%", (value as f32) / 10.0)
 }
 
+/// Alignment of the values of a metric in their columns.
+#[derive(Clone, Copy, Debug, EnumString, IntoStaticStr, PartialEq, Eq)]
+pub enum ColumnAlignment {
+    #[strum(serialize = "left")]
+    Left,
+    #[strum(serialize = "center")]
+    Center,
+    #[strum(serialize = "right")]
+    Right,
+}
+
+impl ColumnAlignment {
+    pub fn as_str(self) -> &'static str {
+        self.into()
+    }
+}
+
+/// Presentation of the formatted values of a metric.
+///
+/// The values keep the same width as their magnitude changes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ColumnFormat {
+    pub alignment: Option<ColumnAlignment>,
+    /// Number of decimal places of the values that have a fractional part.
+    pub decimals: Option<usize>,
+    /// Minimum width, the values are padded according to the alignment.
+    pub width: Option<usize>,
+}
+
+impl ColumnFormat {
+    /// Round the leading decimal number and pad the text.
+    ///
+    /// Integers and durations are left unchanged by the number of decimals.
+    pub fn apply(&self, text: String) -> String {
+        let text = match self.decimals {
+            Some(decimals) => {
+                let end = text
+                    .find(|c: char| !(c.is_ascii_digit() || c == '.' || c == '-'))
+                    .unwrap_or(text.len());
+                let (number, rest) = text.split_at(end);
+                match number.parse::<f64>() {
+                    Ok(number) if text[..end].contains('.') => {
+                        format!("{number:.decimals$}{rest}")
+                    }
+                    _ => text,
+                }
+            }
+            None => text,
+        };
+        match self.width {
+            Some(width) => match self.alignment.unwrap_or(ColumnAlignment::Right) {
+                ColumnAlignment::Left => format!("{text:<width$}"),
+                ColumnAlignment::Center => format!("{text:^width$}"),
+                ColumnAlignment::Right => format!("{text:>width$}"),
+            },
+            None => text,
+        }
+    }
+}
+
+impl fmt::Display for ColumnFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut sep = "";
+        if let Some(alignment) = self.alignment {
+            write!(f, "{}", alignment.as_str())?;
+            sep = ", ";
+        }
+        if let Some(decimals) = self.decimals {
+            write!(f, "{sep}decimals={decimals}")?;
+            sep = ", ";
+        }
+        if let Some(width) = self.width {
+            write!(f, "{sep}width={width}")?;
+        }
+        Ok(())
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
+#[cfg(feature = "tui")]
 pub enum Unit {
     Number,
     Seconds,
@@ -169,6 +291,7 @@ pub enum Unit {
 }
 
 /// Format according to the unit.
+#[cfg(feature = "tui")]
 pub fn human_format(value: u64, unit: Unit) -> String {
     match unit {
         Unit::Number => value.to_string(),
@@ -180,6 +303,35 @@ pub fn human_format(value: u64, unit: Unit) -> String {
 #[cfg(test)]
 mod tests {
 
+    use super::{ColumnAlignment, ColumnFormat};
+
+    #[test]
+    fn test_column_format() {
+        let one_decimal = ColumnFormat {
+            decimals: Some(1),
+            ..Default::default()
+        };
+        assert_eq!("12.3 M", one_decimal.apply("12.34 M".to_string()));
+        assert_eq!("0.0%", one_decimal.apply("0.0%".to_string()));
+        assert_eq!("512", one_decimal.apply("512".to_string()));
+        assert_eq!("2h 44m 09s", one_decimal.apply("2h 44m 09s".to_string()));
+        let padded = ColumnFormat {
+            decimals: Some(2),
+            width: Some(8),
+            ..Default::default()
+        };
+        assert_eq!("   5.00%", padded.apply("5.0%".to_string()));
+        let left = ColumnFormat {
+            alignment: Some(ColumnAlignment::Left),
+            width: Some(6),
+            ..padded
+        };
+        assert_eq!("1.50 K", left.apply("1.5 K".to_string()));
+        assert_eq!("12    ", left.apply("12".to_string()));
+        assert_eq!("left, decimals=2, width=6", left.to_string());
+        assert_eq!("", ColumnFormat::default().to_string());
+    }
+
     #[test]
     fn test_size() {
         assert_eq!("512", super::size(512));
@@ -192,6 +344,18 @@ mod tests {
     #[test]
     fn test_seconds() {
         assert_eq!("59.150", super::seconds(59150));
+        assert_eq!("59", super::whole_seconds(59150));
+    }
+
+    #[test]
+    fn test_duration_styles() {
+        let millis = (((26 * 60) + 3) * 60 + 4) * 1000;
+        assert_eq!("1d2h3m", super::compact_milliseconds(millis));
+        assert_eq!("26:03:04", super::clock_milliseconds(millis));
+        assert_eq!("2h0m", super::compact_milliseconds(2 * 3600 * 1000 + 999));
+        assert_eq!("3m12s", super::compact_milliseconds(192_500));
+        assert_eq!("45s", super::compact_milliseconds(45_900));
+        assert_eq!("00:00:45", super::clock_milliseconds(45_900));
     }
 
     #[test]
@@ -0,0 +1,239 @@
+// Oprs -- process monitor for Linux
+// Copyright (C) 2025  Laurent Pelecq
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Detection of a /proc file system mounted with option hidepid.
+//!
+//! With hidepid, the processes of the other users are either not readable or
+//! not listed at all, unless the user is root, has capability CAP_SYS_PTRACE
+//! or is member of the group given by option gid.
+
+use std::{fmt, fs};
+
+/// Mounted file systems.
+const MOUNTS_FILE: &str = "/proc/self/mounts";
+
+/// Mount point of procfs.
+const PROC_MOUNT_POINT: &str = "/proc";
+
+/// Status of the current process.
+const STATUS_FILE: &str = "/proc/self/status";
+
+/// Capability to read the files of other processes in /proc.
+const CAP_SYS_PTRACE: u32 = 19;
+
+/// Access to the processes of other users.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HidePid {
+    /// All processes are accessible.
+    Off,
+    /// Processes are listed but their files are not readable.
+    NoAccess,
+    /// Processes are not listed.
+    Invisible,
+    /// Only the processes that can be traced are listed.
+    Ptraceable,
+}
+
+impl HidePid {
+    /// Value of the mount option, either numeric or symbolic.
+    fn parse(value: &str) -> Option<HidePid> {
+        match value {
+            "0" | "off" => Some(HidePid::Off),
+            "1" | "noaccess" => Some(HidePid::NoAccess),
+            "2" | "invisible" => Some(HidePid::Invisible),
+            "4" | "ptraceable" => Some(HidePid::Ptraceable),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for HidePid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let value = match self {
+            HidePid::Off => 0,
+            HidePid::NoAccess => 1,
+            HidePid::Invisible => 2,
+            HidePid::Ptraceable => 4,
+        };
+        write!(f, "hidepid={value}")
+    }
+}
+
+/// Options of the procfs mount point.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ProcMount {
+    pub hidepid: HidePid,
+    /// Group whose members can see all the processes.
+    pub gid: Option<u32>,
+}
+
+impl ProcMount {
+    /// Parse the content of /proc/self/mounts. The last mount on /proc wins.
+    fn parse(content: &str) -> Option<ProcMount> {
+        content
+            .lines()
+            .filter_map(
+                |line| match line.split_whitespace().collect::<Vec<&str>>()[..] {
+                    [_, PROC_MOUNT_POINT, "proc", options, ..] => Some(options),
+                    _ => None,
+                },
+            )
+            .next_back()
+            .map(|options| {
+                options.split(',').fold(
+                    ProcMount {
+                        hidepid: HidePid::Off,
+                        gid: None,
+                    },
+                    |mut mount, option| {
+                        match option.split_once('=') {
+                            Some(("hidepid", value)) => {
+                                mount.hidepid = HidePid::parse(value).unwrap_or(HidePid::Off)
+                            }
+                            Some(("gid", value)) => mount.gid = value.parse::<u32>().ok(),
+                            _ => (),
+                        }
+                        mount
+                    },
+                )
+            })
+    }
+
+    /// Options of the current procfs mount point.
+    pub fn current() -> Option<ProcMount> {
+        ProcMount::parse(&fs::read_to_string(MOUNTS_FILE).ok()?)
+    }
+
+    /// Whether a user with the given UID, groups and capability sees all the processes.
+    fn is_exempted(&self, euid: u32, groups: &[u32], can_ptrace: bool) -> bool {
+        matches!(self.hidepid, HidePid::Off)
+            || euid == 0
+            || can_ptrace
+            || self.gid.is_some_and(|gid| groups.contains(&gid))
+    }
+
+    /// UID of the current user if the processes of other users are hidden.
+    pub fn restricted_uid(&self) -> Option<u32> {
+        let euid = unsafe { libc::geteuid() };
+        if self.is_exempted(euid, &current_groups(), has_ptrace_capability()) {
+            None
+        } else {
+            Some(euid)
+        }
+    }
+
+    /// Explain what is not available when the processes are restricted.
+    pub fn limitations(&self) -> String {
+        let access = match self.hidepid {
+            HidePid::NoAccess => "can't be read",
+            _ => "are hidden",
+        };
+        format!(
+            "/proc is mounted with {}: processes of other users {access}, only the \
+             processes of the current user are monitored",
+            self.hidepid
+        )
+    }
+}
+
+/// Whether CAP_SYS_PTRACE is in the effective capabilities of the current process.
+fn has_ptrace_capability() -> bool {
+    fs::read_to_string(STATUS_FILE)
+        .ok()
+        .and_then(|content| {
+            content
+                .lines()
+                .find_map(|line| line.strip_prefix("CapEff:"))
+                .and_then(|value| u64::from_str_radix(value.trim(), 16).ok())
+        })
+        .is_some_and(|caps| caps & (1 << CAP_SYS_PTRACE) != 0)
+}
+
+/// Effective and supplementary groups of the current process.
+fn current_groups() -> Vec<u32> {
+    let mut groups = vec![unsafe { libc::getegid() }];
+    let count = unsafe { libc::getgroups(0, std::ptr::null_mut()) };
+    if count > 0 {
+        let mut supplementary = vec![0; count as usize];
+        let count = unsafe { libc::getgroups(count, supplementary.as_mut_ptr()) };
+        if count > 0 {
+            supplementary.truncate(count as usize);
+            groups.extend(supplementary);
+        }
+    }
+    groups
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::{HidePid, ProcMount};
+
+    #[test]
+    fn test_parse_mounts() {
+        let mounts = "sysfs /sys sysfs rw,nosuid 0 0\nproc /proc proc rw,nosuid,nodev,noexec,relatime,hidepid=invisible,gid=27 0 0\n";
+        assert_eq!(
+            Some(ProcMount {
+                hidepid: HidePid::Invisible,
+                gid: Some(27)
+            }),
+            ProcMount::parse(mounts)
+        );
+        let mounts = "proc /proc proc rw,hidepid=2 0 0\nproc /proc proc rw,relatime 0 0\n";
+        assert_eq!(
+            Some(ProcMount {
+                hidepid: HidePid::Off,
+                gid: None
+            }),
+            ProcMount::parse(mounts)
+        );
+        let mounts = "proc /proc proc rw,hidepid=1 0 0\nproc /mnt/proc proc rw 0 0\n";
+        assert_eq!(
+            Some(HidePid::NoAccess),
+            ProcMount::parse(mounts).map(|mount| mount.hidepid)
+        );
+        assert_eq!(None, ProcMount::parse("sysfs /sys sysfs rw 0 0\n"));
+    }
+
+    #[test]
+    fn test_is_exempted() {
+        let mount = ProcMount {
+            hidepid: HidePid::Invisible,
+            gid: Some(27),
+        };
+        assert!(mount.is_exempted(0, &[0], false));
+        assert!(mount.is_exempted(1000, &[1000, 27], false));
+        assert!(mount.is_exempted(1000, &[1000], true));
+        assert!(!mount.is_exempted(1000, &[1000], false));
+        let mount = ProcMount {
+            hidepid: HidePid::Off,
+            gid: None,
+        };
+        assert!(mount.is_exempted(1000, &[1000], false));
+    }
+
+    #[test]
+    fn test_limitations() {
+        let mount = ProcMount {
+            hidepid: HidePid::Invisible,
+            gid: None,
+        };
+        assert_eq!(
+            "/proc is mounted with hidepid=2: processes of other users are hidden, only the processes of the current user are monitored",
+            mount.limitations()
+        );
+    }
+}
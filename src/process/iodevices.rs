@@ -0,0 +1,208 @@
+// Oprs -- process monitor for Linux
+// Copyright (C) 2025  Laurent Pelecq
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Bytes read and written by a process attributed to the block devices.
+//!
+//! The kernel only counts the I/O of a process as a whole in /proc/PID/io. The
+//! share of a device is estimated with the offsets of the regular files open on
+//! it, read in /proc/PID/fdinfo/FD. The offset of a file open for reading only
+//! counts as read, otherwise as written. What is left of the characters read and
+//! written by the process (pipes, sockets, closed files) is not attributed.
+
+use procfs::process::{FDPermissions, FDTarget, MountInfo};
+use std::{collections::BTreeMap, fs, os::unix::fs::MetadataExt, path::PathBuf};
+
+use super::{
+    forest::ProcResult,
+    mounts::{find_mount, majmin},
+    Process,
+};
+
+/// Bytes of the files open by a process on a device.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DeviceIo {
+    /// Major and minor numbers of the device.
+    pub device: String,
+    /// Mount point or None if the device is not mounted in the namespace of the process.
+    pub mount_point: Option<PathBuf>,
+    pub source: String,
+    /// Number of open files.
+    pub files: usize,
+    /// Offsets of the files open for reading only.
+    pub read: u64,
+    /// Offsets of the files open for writing.
+    pub written: u64,
+}
+
+/// I/O of a process split by device.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct IoByDevice {
+    pub devices: Vec<DeviceIo>,
+    /// Characters read that are not attributed to a device.
+    pub other_read: u64,
+    /// Characters written that are not attributed to a device.
+    pub other_written: u64,
+}
+
+/// Offset of a file descriptor in /proc/PID/fdinfo/FD.
+fn parse_pos(content: &str) -> Option<u64> {
+    content
+        .lines()
+        .find_map(|line| line.strip_prefix("pos:"))
+        .and_then(|value| value.trim().parse().ok())
+}
+
+/// Group the offsets of the files by device, the most active first.
+///
+/// The files are given by device, whether they are writable and offset. The
+/// totals are the characters read and written by the process.
+fn group_by_device<I>(mounts: &[MountInfo], files: I, totals: (u64, u64)) -> IoByDevice
+where
+    I: IntoIterator<Item = (u64, bool, u64)>,
+{
+    let mut devices = BTreeMap::new();
+    for (dev, writable, pos) in files {
+        let (count, read, written) = devices.entry(dev).or_insert((0, 0, 0));
+        *count += 1;
+        if writable {
+            *written += pos;
+        } else {
+            *read += pos;
+        }
+    }
+    let mut devices = devices
+        .into_iter()
+        .map(|(dev, (files, read, written))| {
+            let device = majmin(dev);
+            let mount = find_mount(mounts, &device);
+            DeviceIo {
+                mount_point: mount.map(|mount| mount.mount_point.clone()),
+                source: mount
+                    .and_then(|mount| mount.mount_source.clone())
+                    .unwrap_or_default(),
+                device,
+                files,
+                read,
+                written,
+            }
+        })
+        .collect::<Vec<DeviceIo>>();
+    devices.sort_by(|d1, d2| {
+        (d2.read + d2.written)
+            .cmp(&(d1.read + d1.written))
+            .then_with(|| d1.device.cmp(&d2.device))
+    });
+    let (read, written) = devices.iter().fold((0, 0), |(read, written), device| {
+        (read + device.read, written + device.written)
+    });
+    IoByDevice {
+        devices,
+        other_read: totals.0.saturating_sub(read),
+        other_written: totals.1.saturating_sub(written),
+    }
+}
+
+/// Bytes read and written by a process on each device.
+pub fn io_by_device(process: &Process) -> ProcResult<IoByDevice> {
+    let pid = process.pid();
+    let io = process.io()?;
+    let mounts = process.mountinfo()?;
+    let files = process
+        .fd()?
+        .flatten()
+        .filter(|fdinfo| matches!(fdinfo.target, FDTarget::Path(_)))
+        .filter_map(|fdinfo| {
+            let metadata = fs::metadata(format!("/proc/{pid}/fd/{}", fdinfo.fd)).ok()?;
+            let content = fs::read_to_string(format!("/proc/{pid}/fdinfo/{}", fdinfo.fd)).ok()?;
+            let writable = fdinfo.mode().contains(FDPermissions::WRITE);
+            metadata
+                .is_file()
+                .then(|| (metadata.dev(), writable, parse_pos(&content).unwrap_or(0)))
+        })
+        .collect::<Vec<(u64, bool, u64)>>();
+    Ok(group_by_device(&mounts.0, files, (io.rchar, io.wchar)))
+}
+
+#[cfg(test)]
+mod tests {
+
+    use procfs::process::MountInfo;
+    use std::path::PathBuf;
+
+    use super::{group_by_device, parse_pos, DeviceIo};
+
+    fn dev(major: u32, minor: u32) -> u64 {
+        libc::makedev(major, minor)
+    }
+
+    #[test]
+    fn test_parse_pos() {
+        assert_eq!(
+            Some(4096),
+            parse_pos("pos:\t4096\nflags:\t0100002\nmnt_id:\t29\n")
+        );
+        assert_eq!(None, parse_pos("flags:\t0100002\n"));
+    }
+
+    #[test]
+    fn test_group_by_device() {
+        let mounts = [
+            "22 1 8:1 / / rw,relatime shared:1 - ext4 /dev/sda1 rw",
+            "30 22 8:2 / /srv rw,relatime shared:2 - xfs /dev/sda2 rw",
+        ]
+        .iter()
+        .map(|line| MountInfo::from_line(line).unwrap())
+        .collect::<Vec<MountInfo>>();
+        let files = [
+            (dev(8, 1), false, 100),
+            (dev(8, 2), true, 1_000),
+            (dev(8, 1), true, 50),
+            (dev(0, 45), false, 7),
+        ];
+        let io = group_by_device(&mounts, files, (500, 800));
+        assert_eq!(
+            vec![
+                DeviceIo {
+                    device: "8:2".to_string(),
+                    mount_point: Some(PathBuf::from("/srv")),
+                    source: "/dev/sda2".to_string(),
+                    files: 1,
+                    read: 0,
+                    written: 1_000,
+                },
+                DeviceIo {
+                    device: "8:1".to_string(),
+                    mount_point: Some(PathBuf::from("/")),
+                    source: "/dev/sda1".to_string(),
+                    files: 2,
+                    read: 100,
+                    written: 50,
+                },
+                DeviceIo {
+                    device: "0:45".to_string(),
+                    mount_point: None,
+                    source: String::new(),
+                    files: 1,
+                    read: 7,
+                    written: 0,
+                },
+            ],
+            io.devices
+        );
+        assert_eq!(393, io.other_read);
+        assert_eq!(0, io.other_written);
+    }
+}
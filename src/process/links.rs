@@ -0,0 +1,469 @@
+// Oprs -- process monitor for Linux
+// Copyright (C) 2025  Laurent Pelecq
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Files open and memory mapped by a process that refer to other processes.
+//!
+//! A pidfd refers to the process in the line `Pid:` of /proc/PID/fdinfo/FD. A
+//! connected unix socket refers to the process that holds the other end. The
+//! peers are listed with the sock_diag netlink interface and their owners are
+//! found by scanning the file descriptors in /proc.
+//!
+//! A shared mapping refers to another process that maps the same file, found
+//! by scanning the maps in /proc. It's the case of the shared memory.
+
+use libc::pid_t;
+use procfs::{
+    process::{FDTarget, MMPermissions, MMapPath, MemoryMaps},
+    FromRead,
+};
+use std::{
+    collections::{HashMap, HashSet},
+    fs, io,
+    os::fd::{AsRawFd, FromRawFd, OwnedFd},
+};
+
+use super::{
+    forest::ProcResult,
+    taskstats::{align, read_u16, read_u32, Attributes, NLMSG_ERROR, NLMSG_HDRLEN, NLM_F_REQUEST},
+    Process,
+};
+
+const NLMSG_DONE: u16 = 3;
+const NLM_F_DUMP: u16 = 0x300;
+const SOCK_DIAG_BY_FAMILY: u16 = 20;
+const UDIAG_SHOW_PEER: u32 = 0x4;
+const UNIX_DIAG_PEER: u16 = 2;
+/// Size of struct unix_diag_msg.
+const UNIX_DIAG_MSG_LEN: usize = 16;
+/// Offset of udiag_ino in struct unix_diag_msg.
+const UDIAG_INO_OFFSET: usize = 4;
+
+const RECV_BUFFER_SIZE: usize = 32768;
+
+/// File descriptor of a process.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OpenFile {
+    pub fd: i32,
+    pub target: String,
+    /// Process the file refers to.
+    pub peer: Option<pid_t>,
+}
+
+/// Memory mapping of a process.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MappedRegion {
+    pub address: String,
+    pub perms: String,
+    pub path: String,
+    /// Other process sharing the mapping.
+    pub peer: Option<pid_t>,
+}
+
+/// Device and inode of a mapped file.
+type MappedFile = ((i32, i32), u64);
+
+/// Text of the target of a file descriptor as in `ls -l /proc/PID/fd`.
+fn target_text(target: &FDTarget) -> String {
+    match target {
+        FDTarget::Path(path) => path.to_string_lossy().to_string(),
+        FDTarget::Socket(inode) => format!("socket:[{inode}]"),
+        FDTarget::Net(inode) => format!("net:[{inode}]"),
+        FDTarget::Pipe(inode) => format!("pipe:[{inode}]"),
+        FDTarget::AnonInode(name) => format!("anon_inode:{name}"),
+        FDTarget::MemFD(name) => format!("/memfd:{name}"),
+        FDTarget::Other(kind, inode) => format!("{kind}:[{inode}]"),
+    }
+}
+
+/// Text of the path of a mapping as in /proc/PID/maps.
+fn map_path_text(path: &MMapPath) -> String {
+    match path {
+        MMapPath::Path(path) => path.to_string_lossy().to_string(),
+        MMapPath::Heap => String::from("[heap]"),
+        MMapPath::Stack => String::from("[stack]"),
+        MMapPath::TStack(tid) => format!("[stack:{tid}]"),
+        MMapPath::Vdso => String::from("[vdso]"),
+        MMapPath::Vvar => String::from("[vvar]"),
+        MMapPath::Vsyscall => String::from("[vsyscall]"),
+        MMapPath::Rollup => String::from("[rollup]"),
+        MMapPath::Anonymous => String::new(),
+        MMapPath::Vsys(key) => format!("/SYSV{key:08x}"),
+        MMapPath::Other(name) => name.clone(),
+    }
+}
+
+/// Process of a pidfd in /proc/PID/fdinfo/FD. It is -1 if the process is gone.
+fn parse_pidfd(content: &str) -> Option<pid_t> {
+    content
+        .lines()
+        .find_map(|line| line.strip_prefix("Pid:"))
+        .and_then(|value| value.trim().parse().ok())
+        .filter(|pid| *pid > 0)
+}
+
+/// Request to dump the unix sockets with their peer.
+fn dump_message() -> Vec<u8> {
+    let len = NLMSG_HDRLEN + 24;
+    let mut msg = Vec::with_capacity(len);
+    msg.extend_from_slice(&(len as u32).to_ne_bytes());
+    msg.extend_from_slice(&SOCK_DIAG_BY_FAMILY.to_ne_bytes());
+    msg.extend_from_slice(&(NLM_F_REQUEST | NLM_F_DUMP).to_ne_bytes());
+    msg.extend_from_slice(&0u32.to_ne_bytes()); // sequence
+    msg.extend_from_slice(&0u32.to_ne_bytes()); // port ID
+    msg.extend_from_slice(&[libc::AF_UNIX as u8, 0, 0, 0]); // family, protocol, padding
+    msg.extend_from_slice(&u32::MAX.to_ne_bytes()); // all states
+    msg.extend_from_slice(&0u32.to_ne_bytes()); // inode
+    msg.extend_from_slice(&UDIAG_SHOW_PEER.to_ne_bytes());
+    msg.extend_from_slice(&[0; 8]); // cookie
+    msg
+}
+
+/// Record the peers of the sockets in a reply. Returns true at the end of the dump.
+fn parse_peers(mut data: &[u8], peers: &mut HashMap<u64, u64>) -> io::Result<bool> {
+    while let (Some(len), Some(msg_type)) = (read_u32(data, 0), read_u16(data, 4)) {
+        let len = len as usize;
+        let Some(msg) = data.get(..len).filter(|_| len >= NLMSG_HDRLEN) else {
+            break;
+        };
+        match msg_type {
+            NLMSG_DONE => return Ok(true),
+            NLMSG_ERROR => {
+                let errno = read_u32(msg, NLMSG_HDRLEN).unwrap_or_default() as i32;
+                return Err(io::Error::from_raw_os_error(-errno));
+            }
+            _ => {
+                let inode = read_u32(msg, NLMSG_HDRLEN + UDIAG_INO_OFFSET);
+                let peer = msg
+                    .get(NLMSG_HDRLEN + UNIX_DIAG_MSG_LEN..)
+                    .and_then(|attrs| Attributes::new(attrs).get(UNIX_DIAG_PEER))
+                    .and_then(|payload| read_u32(payload, 0));
+                if let (Some(inode), Some(peer)) = (inode, peer) {
+                    peers.insert(inode as u64, peer as u64);
+                }
+            }
+        }
+        data = data.get(align(len)..).unwrap_or_default();
+    }
+    Ok(false)
+}
+
+/// Peer of each connected unix socket by inode.
+fn unix_peers() -> io::Result<HashMap<u64, u64>> {
+    let fd = unsafe {
+        libc::socket(
+            libc::AF_NETLINK,
+            libc::SOCK_DGRAM | libc::SOCK_CLOEXEC,
+            libc::NETLINK_SOCK_DIAG,
+        )
+    };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let socket = unsafe { OwnedFd::from_raw_fd(fd) };
+    let msg = dump_message();
+    if unsafe { libc::send(socket.as_raw_fd(), msg.as_ptr().cast(), msg.len(), 0) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let mut peers = HashMap::new();
+    let mut buffer = vec![0u8; RECV_BUFFER_SIZE];
+    loop {
+        let received = unsafe {
+            libc::recv(
+                socket.as_raw_fd(),
+                buffer.as_mut_ptr().cast(),
+                buffer.len(),
+                0,
+            )
+        };
+        if received < 0 {
+            let err = io::Error::last_os_error();
+            if err.raw_os_error() == Some(libc::EINTR) {
+                continue;
+            }
+            return Err(err);
+        }
+        if received == 0 || parse_peers(&buffer[..received as usize], &mut peers)? {
+            return Ok(peers);
+        }
+    }
+}
+
+/// Processes holding the sockets with the given inodes.
+fn socket_owners(mut inodes: HashSet<u64>) -> HashMap<u64, pid_t> {
+    let mut owners = HashMap::new();
+    let Ok(entries) = fs::read_dir("/proc") else {
+        return owners;
+    };
+    let pids = entries
+        .flatten()
+        .filter_map(|entry| entry.file_name().to_str()?.parse::<pid_t>().ok());
+    for pid in pids {
+        if inodes.is_empty() {
+            break;
+        }
+        let Ok(fds) = fs::read_dir(format!("/proc/{pid}/fd")) else {
+            continue;
+        };
+        for entry in fds.flatten() {
+            let inode = fs::read_link(entry.path()).ok().and_then(|link| {
+                link.to_str()?
+                    .strip_prefix("socket:[")?
+                    .strip_suffix(']')?
+                    .parse::<u64>()
+                    .ok()
+            });
+            if let Some(inode) = inode.filter(|inode| inodes.remove(inode)) {
+                owners.insert(inode, pid);
+            }
+        }
+    }
+    owners
+}
+
+/// Processes other than the given one that share a mapping of the given files.
+fn mapping_owners(pid: pid_t, mut files: HashSet<MappedFile>) -> HashMap<MappedFile, pid_t> {
+    let mut owners = HashMap::new();
+    let Ok(entries) = fs::read_dir("/proc") else {
+        return owners;
+    };
+    let pids = entries
+        .flatten()
+        .filter_map(|entry| entry.file_name().to_str()?.parse::<pid_t>().ok())
+        .filter(|other| *other != pid);
+    for other in pids {
+        if files.is_empty() {
+            break;
+        }
+        let Ok(maps) = MemoryMaps::from_file(format!("/proc/{other}/maps")) else {
+            continue;
+        };
+        for map in maps.iter() {
+            let file = (map.dev, map.inode);
+            if map.perms.contains(MMPermissions::SHARED) && files.remove(&file) {
+                owners.insert(file, other);
+            }
+        }
+    }
+    owners
+}
+
+/// Memory mappings of a process with the processes sharing them.
+pub fn memory_maps(process: &Process) -> ProcResult<Vec<MappedRegion>> {
+    let maps = process.maps()?;
+    let is_shared_file =
+        |perms: MMPermissions, inode: u64| perms.contains(MMPermissions::SHARED) && inode != 0;
+    let shared = maps
+        .iter()
+        .filter(|map| is_shared_file(map.perms, map.inode))
+        .map(|map| (map.dev, map.inode))
+        .collect::<HashSet<MappedFile>>();
+    let owners = if shared.is_empty() {
+        HashMap::new()
+    } else {
+        mapping_owners(process.pid(), shared)
+    };
+    Ok(maps
+        .into_iter()
+        .map(|map| MappedRegion {
+            address: format!("{:x}-{:x}", map.address.0, map.address.1),
+            perms: map.perms.as_str(),
+            path: map_path_text(&map.pathname),
+            peer: is_shared_file(map.perms, map.inode)
+                .then(|| owners.get(&(map.dev, map.inode)).copied())
+                .flatten(),
+        })
+        .collect())
+}
+
+/// File descriptors of a process with the processes they refer to.
+pub fn open_files(process: &Process) -> ProcResult<Vec<OpenFile>> {
+    let pid = process.pid();
+    let mut files = process
+        .fd()?
+        .flatten()
+        .map(|fdinfo| {
+            let peer = match fdinfo.target {
+                FDTarget::AnonInode(ref name) if name == "[pidfd]" => {
+                    fs::read_to_string(format!("/proc/{pid}/fdinfo/{}", fdinfo.fd))
+                        .ok()
+                        .and_then(|content| parse_pidfd(&content))
+                }
+                _ => None,
+            };
+            (fdinfo.fd, fdinfo.target, peer)
+        })
+        .collect::<Vec<(i32, FDTarget, Option<pid_t>)>>();
+    files.sort_by_key(|(fd, _, _)| *fd);
+    let has_sockets = files
+        .iter()
+        .any(|(_, target, _)| matches!(target, FDTarget::Socket(_)));
+    let peers = if has_sockets {
+        unix_peers().unwrap_or_else(|err| {
+            log::warn!("cannot list the peers of the unix sockets: {err}");
+            HashMap::new()
+        })
+    } else {
+        HashMap::new()
+    };
+    let wanted = files
+        .iter()
+        .filter_map(|(_, target, _)| match target {
+            FDTarget::Socket(inode) => peers.get(inode).copied(),
+            _ => None,
+        })
+        .collect::<HashSet<u64>>();
+    let owners = socket_owners(wanted);
+    Ok(files
+        .into_iter()
+        .map(|(fd, target, peer)| {
+            let peer = peer.or_else(|| match target {
+                FDTarget::Socket(inode) => {
+                    peers.get(&inode).and_then(|peer| owners.get(peer)).copied()
+                }
+                _ => None,
+            });
+            OpenFile {
+                fd,
+                target: target_text(&target),
+                peer,
+            }
+        })
+        .collect())
+}
+
+/// Name of a running process.
+pub fn process_name(pid: pid_t) -> Option<String> {
+    fs::read_to_string(format!("/proc/{pid}/comm"))
+        .ok()
+        .map(|name| name.trim_end().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+
+    use procfs::process::MMapPath;
+    use std::{
+        collections::{HashMap, HashSet},
+        fs,
+        os::{fd::AsRawFd, unix::fs::MetadataExt, unix::net::UnixStream},
+        path::PathBuf,
+        ptr,
+    };
+
+    use super::{
+        map_path_text, mapping_owners, parse_peers, parse_pidfd, unix_peers, NLMSG_DONE,
+        NLMSG_HDRLEN,
+    };
+
+    fn message(msg_type: u16, payload: &[u8]) -> Vec<u8> {
+        let len = NLMSG_HDRLEN + payload.len();
+        let mut msg = Vec::new();
+        msg.extend_from_slice(&(len as u32).to_ne_bytes());
+        msg.extend_from_slice(&msg_type.to_ne_bytes());
+        msg.extend_from_slice(&[0; 10]);
+        msg.extend_from_slice(payload);
+        msg
+    }
+
+    #[test]
+    fn test_parse_pidfd() {
+        assert_eq!(
+            Some(42),
+            parse_pidfd("pos:\t0\nflags:\t02000002\nPid:\t42\n")
+        );
+        assert_eq!(None, parse_pidfd("pos:\t0\nPid:\t-1\n"));
+        assert_eq!(None, parse_pidfd("pos:\t0\n"));
+    }
+
+    #[test]
+    fn test_parse_peers() {
+        let mut payload = vec![1u8, 1, 1, 0];
+        payload.extend_from_slice(&100u32.to_ne_bytes());
+        payload.extend_from_slice(&[0; 8]);
+        payload.extend_from_slice(&8u16.to_ne_bytes());
+        payload.extend_from_slice(&2u16.to_ne_bytes());
+        payload.extend_from_slice(&101u32.to_ne_bytes());
+        let mut data = message(20, &payload);
+        let mut peers = HashMap::new();
+        assert!(!parse_peers(&data, &mut peers).unwrap());
+        assert_eq!(Some(&101), peers.get(&100));
+        data.extend(message(NLMSG_DONE, &[0; 4]));
+        assert!(parse_peers(&data, &mut peers).unwrap());
+    }
+
+    #[test]
+    fn test_unix_peers() {
+        let (first, second) = UnixStream::pair().unwrap();
+        let inode = |stream: &UnixStream| {
+            fs::metadata(format!("/proc/self/fd/{}", stream.as_raw_fd()))
+                .unwrap()
+                .ino()
+        };
+        let peers = unix_peers().unwrap();
+        assert_eq!(Some(&inode(&second)), peers.get(&inode(&first)));
+    }
+
+    #[test]
+    fn test_mapping_owners() {
+        let dir = std::env::temp_dir().join(format!("oprs-test-maps-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("shared");
+        let file = fs::File::options()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&path)
+            .unwrap();
+        let page_size = procfs::page_size() as usize;
+        file.set_len(page_size as u64).unwrap();
+        let addr = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                page_size,
+                libc::PROT_READ,
+                libc::MAP_SHARED,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        assert_ne!(libc::MAP_FAILED, addr);
+        let maps = procfs::process::Process::myself().unwrap().maps().unwrap();
+        let map = maps
+            .iter()
+            .find(|map| map.pathname == MMapPath::Path(path.clone()))
+            .unwrap();
+        let mapped = (map.dev, map.inode);
+        // The process itself is found when another one is given.
+        let owners = mapping_owners(0, HashSet::from([mapped]));
+        assert_eq!(Some(&(std::process::id() as i32)), owners.get(&mapped));
+        let owners = mapping_owners(std::process::id() as i32, HashSet::from([mapped]));
+        assert!(owners.is_empty());
+        unsafe { libc::munmap(addr, page_size) };
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_map_path_text() {
+        assert_eq!(
+            "/usr/lib/libc.so.6",
+            map_path_text(&MMapPath::Path(PathBuf::from("/usr/lib/libc.so.6")))
+        );
+        assert_eq!("[stack:42]", map_path_text(&MMapPath::TStack(42)));
+        assert_eq!("/SYSV0000002a", map_path_text(&MMapPath::Vsys(42)));
+        assert_eq!("", map_path_text(&MMapPath::Anonymous));
+    }
+}
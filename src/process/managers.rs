@@ -16,33 +16,44 @@
 
 use getset::{Getters, Setters};
 use libc::pid_t;
+use procfs::Current;
 use std::borrow::Cow;
-use strum_macros::Display as StrumDisplay;
+use strum_macros::{Display as StrumDisplay, EnumString};
 
 use super::{
     forest::{ProcessClassifier, ProcessResult},
-    format, Aggregation, Collector, Forest, FormattedMetric, MetricNamesParser, ProcessInfo,
-    Sample, SystemConf, SystemStat, TargetContainer, TargetError, TargetId,
+    format,
+    matchers::ProcessExclusion,
+    Aggregation, Collector, Forest, FormattedMetric, MetricNamesParser, ProcessInfo, SystemConf,
+    SystemStat, TargetContainer, TargetError, TargetId,
 };
 
+#[cfg(feature = "tui")]
+use super::{
+    forest::ProcResult, memory_maps, open_files, Credentials, MappedRegion, OpenFile,
+    ProcessSamples, Sample, ThreadMonitor,
+};
+#[cfg(feature = "tui")]
+use std::cell::OnceCell;
+
 /// Number of idle cycles to be considered as inactive.
 const INACTIVITY: u16 = 5;
 
+/// Maximum number of cycles between two full enumerations of the processes.
+const FULL_REFRESH_PERIOD: u16 = 10;
+
 /// High-level filter on processes
-#[derive(Clone, Copy, Debug, StrumDisplay)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, EnumString, StrumDisplay)]
 pub enum ProcessFilter {
     #[strum(serialize = "none")]
     None,
+    #[default]
     #[strum(serialize = "user")]
     UserLand,
     #[strum(serialize = "active")]
     Active,
-}
-
-impl Default for ProcessFilter {
-    fn default() -> Self {
-        Self::UserLand
-    }
+    #[strum(serialize = "realtime")]
+    RealTime,
 }
 
 /// Context for mananagers.
@@ -52,9 +63,16 @@ pub struct ManagerContext {
     filter: ProcessFilter,
     #[getset(get_copy = "pub", set = "pub")]
     root_pid: Option<pid_t>,
+    /// Only the processes of this user are shown.
+    #[getset(set = "pub")]
+    owner: Option<u32>,
+    /// Processes deeper in the tree are folded into their ancestor at this depth.
+    #[getset(set = "pub")]
+    max_depth: Option<usize>,
 }
 
 /// Specific metrics.
+#[cfg(feature = "tui")]
 pub struct ProcessMetrics<'b> {
     pub time_cpu: &'b Sample,
     pub time_elapsed: &'b Sample,
@@ -76,6 +94,14 @@ pub struct ProcessDetails<'a> {
     #[getset(get = "pub")]
     process: ProcessInfo,
     collector: Collector<'a>,
+    #[cfg(feature = "tui")]
+    threads: ThreadMonitor,
+    /// Open files, listed at most once per refresh.
+    #[cfg(feature = "tui")]
+    files: OnceCell<Vec<OpenFile>>,
+    /// Memory mappings, listed at most once per refresh.
+    #[cfg(feature = "tui")]
+    maps: OnceCell<Vec<MappedRegion>>,
 }
 
 impl ProcessDetails<'_> {
@@ -96,11 +122,18 @@ impl ProcessDetails<'_> {
         let metrics = parser.parse(&metric_names).unwrap();
         let process = ProcessInfo::with_pid(pid)?;
         let name = process.name().to_string();
-        let collector = Collector::new(Cow::Owned(metrics));
+        let mut collector = Collector::new(Cow::Owned(metrics));
+        collector.set_with_credentials(true);
         Ok(Self {
             name,
             process,
             collector,
+            #[cfg(feature = "tui")]
+            threads: ThreadMonitor::default(),
+            #[cfg(feature = "tui")]
+            files: OnceCell::new(),
+            #[cfg(feature = "tui")]
+            maps: OnceCell::new(),
         })
     }
 
@@ -109,16 +142,23 @@ impl ProcessDetails<'_> {
         let parent_pid = self.process.parent_pid();
         let process = ProcessInfo::with_pid(parent_pid)?;
         let name = process.name().to_string();
-        let collector = Collector::new(Cow::Owned(
+        let mut collector = Collector::new(Cow::Owned(
             self.collector
                 .metrics()
                 .cloned()
                 .collect::<Vec<FormattedMetric>>(),
         ));
+        collector.set_with_credentials(true);
         Ok(Self {
             name,
             process,
             collector,
+            #[cfg(feature = "tui")]
+            threads: ThreadMonitor::default(),
+            #[cfg(feature = "tui")]
+            files: OnceCell::new(),
+            #[cfg(feature = "tui")]
+            maps: OnceCell::new(),
         })
     }
 
@@ -126,11 +166,56 @@ impl ProcessDetails<'_> {
     pub fn refresh(&mut self, sysconf: &SystemConf) -> ProcessResult<()> {
         self.process.refresh()?;
         self.collector.collect(&self.name, &self.process, sysconf);
+        #[cfg(feature = "tui")]
+        if let Err(err) = self.threads.refresh(self.process.pid(), sysconf) {
+            log::warn!("{}: cannot read threads: {err}", self.process.pid());
+        }
+        #[cfg(feature = "tui")]
+        {
+            self.files.take();
+            self.maps.take();
+        }
         Ok(())
     }
 
+    /// Files open by the process with the processes they refer to.
+    #[cfg(feature = "tui")]
+    pub fn open_files(&self) -> ProcResult<&[OpenFile]> {
+        if let Some(files) = self.files.get() {
+            return Ok(files);
+        }
+        let files = open_files(self.process.process())?;
+        Ok(self.files.get_or_init(|| files))
+    }
+
+    /// Memory mappings of the process with the processes sharing them.
+    #[cfg(feature = "tui")]
+    pub fn memory_maps(&self) -> ProcResult<&[MappedRegion]> {
+        if let Some(maps) = self.maps.get() {
+            return Ok(maps);
+        }
+        let maps = memory_maps(self.process.process())?;
+        Ok(self.maps.get_or_init(|| maps))
+    }
+
+    /// Effective user and group read at the last refresh.
+    #[cfg(feature = "tui")]
+    pub fn credentials(&self) -> Option<Credentials> {
+        self.collector
+            .lines()
+            .next()
+            .and_then(ProcessSamples::credentials)
+    }
+
+    /// Threads of the process.
+    #[cfg(feature = "tui")]
+    pub fn threads(&self) -> &ThreadMonitor {
+        &self.threads
+    }
+
     /// Process metrics.
-    pub fn metrics(&self) -> Option<ProcessMetrics> {
+    #[cfg(feature = "tui")]
+    pub fn metrics(&self) -> Option<ProcessMetrics<'_>> {
         self.collector.lines().take(1).next().map(|s| {
             let samples = s.samples_as_slice();
             ProcessMetrics {
@@ -183,7 +268,7 @@ impl<'s> FlatProcessManager<'s> {
     pub fn with_pids(sysconf: &'s SystemConf, pids: &[pid_t]) -> Self {
         let mut targets = TargetContainer::new(sysconf, true);
         pids.iter().for_each(|pid| {
-            if let Err(err) = targets.push_by_pid(&TargetId::Pid(*pid)) {
+            if let Err(err) = targets.push_by_pid(&TargetId::Pid(*pid, None)) {
                 log::warn!("{pid}: {err}");
             }
         });
@@ -195,35 +280,113 @@ impl ProcessManager for FlatProcessManager<'_> {
     fn refresh(&mut self, collector: &mut Collector) -> ProcessResult<bool> {
         let targets_updated = self.targets.refresh();
         self.targets.collect(collector);
+        collector.set_waiting(self.targets.is_waiting());
         Ok(targets_updated)
     }
 }
 
-/// Accept all processes in userland.
-#[derive(Debug, Default)]
-struct AcceptUserLand(());
+/// Accept the processes that are not excluded, optionally only in userland.
+struct AcceptIncluded<'a> {
+    userland: bool,
+    exclusion: &'a ProcessExclusion,
+}
 
-impl ProcessClassifier for AcceptUserLand {
+impl ProcessClassifier for AcceptIncluded<'_> {
     fn accept(&self, pi: &ProcessInfo) -> bool {
-        !pi.is_kernel()
+        (!self.userland || !pi.is_kernel()) && !self.exclusion.excludes(pi.name(), pi.uid())
+    }
+}
+
+/// Fingerprint of the process table in /proc/loadavg.
+///
+/// The last PID changes on fork and the number of scheduling entities changes on exit.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct ProcessTableState {
+    entities: u32,
+    last_pid: u32,
+}
+
+impl ProcessTableState {
+    fn current() -> Option<Self> {
+        procfs::LoadAverage::current()
+            .ok()
+            .map(|loadavg| ProcessTableState {
+                entities: loadavg.max,
+                last_pid: loadavg.latest_pid,
+            })
+    }
+}
+
+/// Depth of the processes of a tree visited in pre-order.
+#[derive(Default)]
+struct TreeDepth(Vec<pid_t>);
+
+impl TreeDepth {
+    /// Depth of a process, the root is at depth 0.
+    fn push(&mut self, pid: pid_t, parent_pid: pid_t) -> usize {
+        let Self(ref mut ancestors) = self;
+        while ancestors.last().is_some_and(|pid| *pid != parent_pid) {
+            ancestors.pop();
+        }
+        let depth = ancestors.len();
+        ancestors.push(pid);
+        depth
     }
 }
 
 /// A Process explorer that interactively displays the process tree.
 pub struct ForestProcessManager<'s> {
     sysconf: &'s SystemConf,
+    exclusion: &'s ProcessExclusion,
     forest: Forest,
     context: ManagerContext,
     inactivity: u16,
+    /// Process table state and filter at the last full refresh.
+    last_full_refresh: Option<(ProcessTableState, ProcessFilter)>,
+    /// Number of cycles since the last full refresh.
+    partial_refreshes: u16,
 }
 
 impl<'s> ForestProcessManager<'s> {
-    pub fn new(sysconf: &'s SystemConf) -> Result<Self, TargetError> {
+    pub fn new(
+        sysconf: &'s SystemConf,
+        exclusion: &'s ProcessExclusion,
+    ) -> Result<Self, TargetError> {
         Ok(Self {
             sysconf,
+            exclusion,
             forest: Forest::new(),
             context: ManagerContext::default(),
             inactivity: 0,
+            last_full_refresh: None,
+            partial_refreshes: 0,
+        })
+    }
+
+    /// Refresh the forest.
+    ///
+    /// The processes are not enumerated if no process has been created or has
+    /// terminated since the last full refresh unless it is too old.
+    fn refresh_forest(&mut self) -> ProcessResult<bool> {
+        let table_state = ProcessTableState::current();
+        let filter = self.context.filter;
+        let unchanged = match (table_state, self.last_full_refresh) {
+            (Some(state), Some((last_state, last_filter))) => {
+                state == last_state && filter == last_filter
+            }
+            _ => false,
+        };
+        if unchanged && self.partial_refreshes < FULL_REFRESH_PERIOD && self.forest.refresh_known()
+        {
+            self.partial_refreshes += 1;
+            return Ok(false);
+        }
+        self.partial_refreshes = 0;
+        self.last_full_refresh = table_state.map(|state| (state, filter));
+        self.forest.set_owner(self.context.owner);
+        self.forest.refresh_if(&AcceptIncluded {
+            userland: matches!(filter, ProcessFilter::UserLand | ProcessFilter::Active),
+            exclusion: self.exclusion,
         })
     }
 
@@ -233,13 +396,52 @@ impl<'s> ForestProcessManager<'s> {
         root_pids: &[pid_t],
         ignore_idleness: bool,
     ) -> ProcessResult<()> {
+        let realtime_only = matches!(self.context.filter, ProcessFilter::RealTime);
+        let is_shown = |pinfo: &ProcessInfo| {
+            !pinfo.hidden()
+                && (ignore_idleness || pinfo.idleness() < self.inactivity)
+                && (!realtime_only || pinfo.is_realtime())
+        };
+        let Some(max_depth) = self.context.max_depth else {
+            for root_pid in root_pids {
+                self.forest
+                    .descendants(*root_pid)?
+                    .filter(|pinfo| is_shown(pinfo))
+                    .for_each(|pinfo| collector.collect(pinfo.name(), pinfo, self.sysconf));
+            }
+            return Ok(());
+        };
+        // An ancestor at the maximum depth with all its descendants, including
+        // the hidden ones, and whether one of them is shown.
+        type Folding<'a> = Option<(&'a ProcessInfo, Vec<&'a ProcessInfo>, bool)>;
+        let mut folding: Folding = None;
+        let flush = |collector: &mut Collector, folding: Folding| {
+            if let Some((pinfo, descendants, any_shown)) = folding {
+                // An idle ancestor is shown if it has active descendants.
+                if is_shown(pinfo) || (!pinfo.hidden() && any_shown) {
+                    collector.collect_folded(pinfo.name(), pinfo, &descendants, self.sysconf);
+                }
+            }
+        };
         for root_pid in root_pids {
-            self.forest
-                .descendants(*root_pid)?
-                .filter(|pinfo| {
-                    !pinfo.hidden() && (ignore_idleness || pinfo.idleness() < self.inactivity)
-                })
-                .for_each(|pinfo| collector.collect(pinfo.name(), pinfo, self.sysconf));
+            let mut tree_depth = TreeDepth::default();
+            for pinfo in self.forest.descendants(*root_pid)? {
+                let depth = tree_depth.push(pinfo.pid(), pinfo.parent_pid());
+                if depth > max_depth {
+                    if let Some((_, ref mut descendants, ref mut any_shown)) = folding {
+                        *any_shown |= is_shown(pinfo);
+                        descendants.push(pinfo);
+                    }
+                } else {
+                    flush(collector, folding.take());
+                    if depth == max_depth {
+                        folding = Some((pinfo, Vec::new(), false));
+                    } else if is_shown(pinfo) {
+                        collector.collect(pinfo.name(), pinfo, self.sysconf);
+                    }
+                }
+            }
+            flush(collector, folding.take());
         }
         Ok(())
     }
@@ -269,22 +471,40 @@ impl ProcessManager for ForestProcessManager<'_> {
         if self.inactivity < INACTIVITY {
             self.inactivity += 1;
         }
-        let changed = match self.context.filter {
-            ProcessFilter::None => self.forest.refresh(),
-            ProcessFilter::UserLand | ProcessFilter::Active => {
-                self.forest.refresh_if(&AcceptUserLand::default())
-            }
-        }?;
+        let changed = self.refresh_forest()?;
         let ignore_idleness = !matches!(self.context.filter, ProcessFilter::Active);
         match self.context.root_pid {
             Some(root_pid) if self.forest.has_process(root_pid) => {
+                collector.set_lineage(self.forest.lineage(root_pid)?.into_iter());
                 self.collect_descendants(collector, &[root_pid], ignore_idleness)?
             }
-            Some(_) => (),
+            Some(_) => collector.set_lineage(std::iter::empty()),
             None => {
+                collector.set_lineage(std::iter::empty());
                 self.collect_descendants(collector, &self.forest.root_pids(), ignore_idleness)?
             }
         }
+        collector.fold_exited();
         Ok(changed)
     }
 }
+
+#[cfg(test)]
+mod tests {
+
+    use super::TreeDepth;
+
+    #[test]
+    fn test_tree_depth() {
+        // 1
+        // |_2_3_4
+        // | \_5
+        // \_6
+        let mut tree_depth = TreeDepth::default();
+        let depths = [(1, 0), (2, 1), (3, 2), (4, 3), (5, 2), (6, 1)]
+            .iter()
+            .map(|(pid, parent_pid)| tree_depth.push(*pid, *parent_pid))
+            .collect::<Vec<usize>>();
+        assert_eq!(vec![0, 1, 2, 3, 2, 1], depths);
+    }
+}
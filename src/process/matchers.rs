@@ -16,7 +16,7 @@
 
 use std::collections::HashSet;
 
-use globset::{Glob, GlobSetBuilder};
+use globset::{Glob, GlobSet, GlobSetBuilder};
 
 use super::Forest;
 
@@ -49,3 +49,65 @@ pub fn glob(patterns: &[String]) -> anyhow::Result<HashSet<String>> {
         }
     })))
 }
+
+/// Processes that are never monitored in system-wide mode.
+#[derive(Debug)]
+pub struct ProcessExclusion {
+    names: GlobSet,
+    uids: HashSet<u32>,
+}
+
+impl ProcessExclusion {
+    /// Exclude the processes with a name matching one of the patterns or owned by one of the users.
+    pub fn new(patterns: &[String], uids: &[u32]) -> anyhow::Result<Self> {
+        let mut builder = GlobSetBuilder::new();
+        for pattern in patterns {
+            builder.add(Glob::new(pattern)?);
+        }
+        Ok(Self {
+            names: builder.build()?,
+            uids: HashSet::from_iter(uids.iter().copied()),
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty() && self.uids.is_empty()
+    }
+
+    /// Whether a process with this name and owner is excluded.
+    pub fn excludes(&self, name: &str, uid: Option<u32>) -> bool {
+        self.names.is_match(name) || uid.is_some_and(|uid| self.uids.contains(&uid))
+    }
+}
+
+impl Default for ProcessExclusion {
+    fn default() -> Self {
+        Self {
+            names: GlobSet::empty(),
+            uids: HashSet::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::ProcessExclusion;
+
+    #[test]
+    fn test_exclusion() {
+        let exclusion = ProcessExclusion::new(
+            &["backup*".to_string(), "zabbix_agentd".to_string()],
+            &[998],
+        )
+        .unwrap();
+        assert!(!exclusion.is_empty());
+        assert!(exclusion.excludes("backup-agent", Some(0)));
+        assert!(exclusion.excludes("zabbix_agentd", None));
+        assert!(!exclusion.excludes("zabbix", None));
+        assert!(exclusion.excludes("sh", Some(998)));
+        assert!(!exclusion.excludes("sh", Some(1000)));
+        assert!(ProcessExclusion::default().is_empty());
+        assert!(!ProcessExclusion::default().excludes("sh", Some(0)));
+    }
+}
@@ -14,16 +14,19 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::fmt;
 use std::hash::Hash;
+use std::rc::Rc;
 use std::result;
 use strum_macros::{EnumIter, EnumMessage, EnumString, IntoStaticStr};
 
 use super::{
-    format::{self, Formatter},
+    derived::{DerivedMetric, DERIVED_PREFIX},
+    filesystems::{self, Filesystem},
+    format::{self, ColumnFormat, Formatter},
     parsers::parse_metric_spec,
-    AggregationSet,
+    AggregationSet, ExternalFormatter,
 };
 
 const SHORT_NAME_MAX_LEN: usize = 10;
@@ -63,6 +66,41 @@ pub enum MetricDataType {
     IntoStaticStr,
 )]
 pub enum MetricId {
+    #[strum(
+        serialize = "cgmem:usage",
+        message = "memory used by the cgroup of the process"
+    )]
+    CgroupMemoryUsage,
+    #[strum(
+        serialize = "cgmem:limit",
+        message = "memory limit of the cgroup of the process (zero if unlimited)"
+    )]
+    CgroupMemoryLimit,
+    #[strum(
+        serialize = "cgmem:ratio",
+        message = "memory of the cgroup in percentage of its limit"
+    )]
+    CgroupMemoryRatio,
+    #[strum(
+        serialize = "cpu:throttled:count",
+        message = "number of times the cgroup was throttled by its CPU quota"
+    )]
+    CpuThrottledCount,
+    #[strum(
+        serialize = "cpu:throttled:time",
+        message = "time the cgroup was throttled by its CPU quota"
+    )]
+    CpuThrottledTime,
+    #[strum(
+        serialize = "delay:blkio",
+        message = "time spent waiting for block I/O (requires delay accounting)"
+    )]
+    DelayBlockIo,
+    #[strum(
+        serialize = "delay:swapin",
+        message = "time spent waiting for pages to be swapped in (requires delay accounting)"
+    )]
+    DelaySwapIn,
     #[strum(serialize = "fault:minor", message = "page faults without disk access")]
     FaultMinor,
     #[strum(serialize = "fault:major", message = "page faults with disk access")]
@@ -71,6 +109,11 @@ pub enum MetricId {
     FdAll,
     #[strum(serialize = "fd:high", message = "highest value of file descriptors")]
     FdHigh,
+    #[strum(
+        serialize = "fd:ratio",
+        message = "file descriptors in percentage of the soft limit of open files"
+    )]
+    FdRatio,
     #[strum(serialize = "fd:file", message = "number of files")]
     FdFile,
     #[strum(serialize = "fd:socket", message = "number of sockets")]
@@ -226,6 +269,11 @@ pub enum MetricId {
     MemText,
     #[strum(serialize = "mem:data", message = "data + stack size")]
     MemData,
+    #[strum(
+        serialize = "mem:cache",
+        message = "page cache of the files open or mapped (estimated)"
+    )]
+    MemCache,
     #[strum(
         serialize = "time:elapsed",
         message = "elapsed time since process started"
@@ -242,16 +290,42 @@ pub enum MetricId {
     TimeUser,
     #[strum(serialize = "thread:count", message = "number of threads")]
     ThreadCount,
+    #[strum(
+        serialize = "thread:rate",
+        message = "number of threads created in the last interval"
+    )]
+    ThreadRate,
+    /// Metric computed from others, the name is in the formatted metric.
+    #[strum(disabled)]
+    Derived,
+    /// Used size of a filesystem, the name is in the formatted metric.
+    #[strum(disabled)]
+    FsUsed,
+    /// Used size of a filesystem in per thousand, the name is in the formatted metric.
+    #[strum(disabled)]
+    FsRatio,
 }
 
 impl MetricId {
     pub fn as_str(self) -> &'static str {
-        self.into()
+        match self {
+            MetricId::Derived => "derived",
+            MetricId::FsUsed => "fs:used",
+            MetricId::FsRatio => "fs:ratio",
+            _ => self.into(),
+        }
     }
 
     /// Return a string of less than SHORT_NAME_MAX_LEN characters.
     pub fn to_short_str(self) -> Option<&'static str> {
         match self {
+            MetricId::CgroupMemoryUsage => Some("cgm:usage"),
+            MetricId::CgroupMemoryLimit => Some("cgm:limit"),
+            MetricId::CgroupMemoryRatio => Some("cgm:ratio"),
+            MetricId::CpuThrottledCount => Some("thrtl:cnt"),
+            MetricId::CpuThrottledTime => Some("thrtl:time"),
+            MetricId::DelayBlockIo => Some("dly:blkio"),
+            MetricId::DelaySwapIn => Some("dly:swapin"),
             MetricId::FaultMinor => Some("flt:min"),
             MetricId::FaultMajor => Some("flt:maj"),
             MetricId::IoReadCall => Some("rd:call"),
@@ -285,6 +359,8 @@ impl MetricId {
             MetricId::TimeSystem => Some("tm:sys"),
             MetricId::TimeUser => Some("tm:user"),
             MetricId::ThreadCount => Some("thread:cnt"),
+            MetricId::ThreadRate => Some("thread:rt"),
+            MetricId::Derived | MetricId::FsUsed | MetricId::FsRatio => None,
             _ => {
                 let name: &'static str = self.into();
                 if name.len() > SHORT_NAME_MAX_LEN {
@@ -295,12 +371,64 @@ impl MetricId {
         }
     }
 
+    /// Whether the metric is bounded by a resource limit of the process.
+    pub fn has_limit(self) -> bool {
+        matches!(
+            self,
+            MetricId::CgroupMemoryUsage
+                | MetricId::CgroupMemoryRatio
+                | MetricId::FdAll
+                | MetricId::FdRatio
+                | MetricId::MapStackSize
+                | MetricId::MemData
+                | MetricId::MemVm
+                | MetricId::TimeCpu
+        )
+    }
+
+    /// Whether the value is a memory size that can be compared to the RAM size.
+    pub fn is_memory_size(self) -> bool {
+        matches!(
+            self,
+            MetricId::CgroupMemoryUsage
+                | MetricId::MapAnonSize
+                | MetricId::MapHeapSize
+                | MetricId::MapFileSize
+                | MetricId::MapStackSize
+                | MetricId::MapThreadStackSize
+                | MetricId::MapVdsoSize
+                | MetricId::MapVsysSize
+                | MetricId::MapVsyscallSize
+                | MetricId::MapVvarSize
+                | MetricId::MapOtherSize
+                | MetricId::MemRss
+                | MetricId::MemVm
+                | MetricId::MemText
+                | MetricId::MemData
+                | MetricId::MemCache
+        )
+    }
+
+    /// Whether the value is the usage of a limit in per thousand.
+    pub fn is_per_mille(self) -> bool {
+        matches!(
+            self,
+            MetricId::CgroupMemoryRatio | MetricId::FdRatio | MetricId::FsRatio
+        )
+    }
+
     /// The data type either counter (always increasing) or gauge (varying but positive).
     pub fn data_type(self) -> MetricDataType {
         match self {
+            MetricId::CgroupMemoryUsage
+            | MetricId::CgroupMemoryLimit
+            | MetricId::CgroupMemoryRatio => MetricDataType::Gauge,
+            MetricId::CpuThrottledCount | MetricId::CpuThrottledTime => MetricDataType::Counter,
+            MetricId::DelayBlockIo | MetricId::DelaySwapIn => MetricDataType::Counter,
             MetricId::FaultMinor | MetricId::FaultMajor => MetricDataType::Counter,
             MetricId::FdAll
             | MetricId::FdHigh
+            | MetricId::FdRatio
             | MetricId::FdFile
             | MetricId::FdSocket
             | MetricId::FdNet
@@ -334,14 +462,19 @@ impl MetricId {
             | MetricId::MapVvarCount
             | MetricId::MapOtherSize
             | MetricId::MapOtherCount => MetricDataType::Gauge,
-            MetricId::MemRss | MetricId::MemVm | MetricId::MemText | MetricId::MemData => {
-                MetricDataType::Gauge
-            }
+            MetricId::MemRss
+            | MetricId::MemVm
+            | MetricId::MemText
+            | MetricId::MemData
+            | MetricId::MemCache => MetricDataType::Gauge,
             MetricId::TimeElapsed
             | MetricId::TimeCpu
             | MetricId::TimeSystem
             | MetricId::TimeUser => MetricDataType::Counter,
-            MetricId::ThreadCount => MetricDataType::Gauge,
+            MetricId::ThreadCount | MetricId::ThreadRate | MetricId::Derived => {
+                MetricDataType::Gauge
+            }
+            MetricId::FsUsed | MetricId::FsRatio => MetricDataType::Gauge,
         }
     }
 }
@@ -353,11 +486,18 @@ impl fmt::Display for MetricId {
 }
 
 /// Metric with associated aggregations and a formatter function
+///
+/// An external formatter, if any, takes precedence over the formatter function.
 #[derive(Clone, Debug)]
 pub struct FormattedMetric {
     pub id: MetricId,
     pub aggregations: AggregationSet,
     pub format: Formatter,
+    pub external: Option<Rc<ExternalFormatter>>,
+    pub derived: Option<Rc<DerivedMetric>>,
+    pub filesystem: Option<Rc<Filesystem>>,
+    /// Alignment, decimals and width of the formatted values.
+    pub column: ColumnFormat,
 }
 
 impl FormattedMetric {
@@ -366,21 +506,181 @@ impl FormattedMetric {
             id,
             aggregations,
             format,
+            external: None,
+            derived: None,
+            filesystem: None,
+            column: ColumnFormat::default(),
+        }
+    }
+
+    fn with_derived(derived: &Rc<DerivedMetric>, format: Formatter) -> FormattedMetric {
+        FormattedMetric {
+            derived: Some(Rc::clone(derived)),
+            ..FormattedMetric::new(MetricId::Derived, AggregationSet::raw(), format)
+        }
+    }
+
+    fn with_filesystem(
+        filesystem: &Rc<Filesystem>,
+        id: MetricId,
+        format: Formatter,
+    ) -> FormattedMetric {
+        FormattedMetric {
+            filesystem: Some(Rc::clone(filesystem)),
+            ..FormattedMetric::new(id, AggregationSet::raw(), format)
+        }
+    }
+
+    /// Name of the metric, of the derived metric or of the filesystem metric.
+    pub fn name(&self) -> &str {
+        match (&self.derived, &self.filesystem) {
+            (Some(derived), _) => derived.name(),
+            (None, Some(filesystem)) => filesystem.metric_name(self.id),
+            (None, None) => self.id.as_str(),
+        }
+    }
+
+    /// Short name if the name is too long.
+    pub fn short_name(&self) -> Option<&'static str> {
+        self.id.to_short_str()
+    }
+
+    pub fn data_type(&self) -> MetricDataType {
+        match self.derived {
+            Some(ref derived) => derived.data_type(),
+            None => self.id.data_type(),
+        }
+    }
+
+    /// Whether the metric is bounded by a resource limit of the process.
+    pub fn has_limit(&self) -> bool {
+        self.derived.is_none() && self.id.has_limit()
+    }
+
+    /// Whether the values of several processes can be added.
+    pub fn is_additive(&self) -> bool {
+        self.derived.is_none()
+            && !matches!(
+                self.id,
+                MetricId::CgroupMemoryUsage
+                    | MetricId::CgroupMemoryLimit
+                    | MetricId::CgroupMemoryRatio
+                    | MetricId::CpuThrottledCount
+                    | MetricId::CpuThrottledTime
+                    | MetricId::FdHigh
+                    | MetricId::FdRatio
+                    | MetricId::FsUsed
+                    | MetricId::FsRatio
+                    | MetricId::TimeElapsed
+            )
+    }
+}
+
+/// Metrics collected by target when some targets have their own metrics
+///
+/// The columns are the union of the metrics. A target without specific
+/// metrics only collects the initial columns.
+#[derive(Clone, Debug)]
+pub struct MetricSelection {
+    default: Rc<[bool]>,
+    targets: HashMap<String, Rc<[bool]>>,
+}
+
+impl MetricSelection {
+    /// Add the metrics of the targets to the columns.
+    ///
+    /// A metric already in the columns is shared and its aggregations are merged.
+    pub fn merge(
+        columns: &mut Vec<FormattedMetric>,
+        targets: Vec<(String, Vec<FormattedMetric>)>,
+    ) -> MetricSelection {
+        let default_count = columns.len();
+        let mut indexes = Vec::with_capacity(targets.len());
+        for (name, metrics) in targets {
+            let mut target_indexes = Vec::with_capacity(metrics.len());
+            for metric in metrics {
+                match columns.iter().position(|col| col.name() == metric.name()) {
+                    Some(index) => {
+                        columns[index].aggregations.merge(metric.aggregations);
+                        target_indexes.push(index);
+                    }
+                    None => {
+                        target_indexes.push(columns.len());
+                        columns.push(metric);
+                    }
+                }
+            }
+            indexes.push((name, target_indexes));
         }
+        let count = columns.len();
+        let default = (0..count).map(|index| index < default_count).collect();
+        let targets = indexes
+            .into_iter()
+            .map(|(name, target_indexes)| {
+                let selected = (0..count)
+                    .map(|index| target_indexes.contains(&index))
+                    .collect();
+                (name, selected)
+            })
+            .collect();
+        MetricSelection { default, targets }
+    }
+
+    /// Metrics collected for a target.
+    pub fn get(&self, target_name: &str) -> Rc<[bool]> {
+        Rc::clone(self.targets.get(target_name).unwrap_or(&self.default))
     }
 }
 
 /// Metric names parser
 pub struct MetricNamesParser {
     human_format: bool,
+    derived: Vec<Rc<DerivedMetric>>,
+    filesystems: Vec<Rc<Filesystem>>,
 }
 
 impl MetricNamesParser {
     pub fn new(human_format: bool) -> MetricNamesParser {
-        MetricNamesParser { human_format }
+        MetricNamesParser {
+            human_format,
+            derived: Vec::new(),
+            filesystems: Vec::new(),
+        }
+    }
+
+    /// Define the filesystems that can be selected with `fs:name:used` and `fs:name:ratio`.
+    pub fn set_filesystems(&mut self, filesystems: Vec<Filesystem>) {
+        self.filesystems = filesystems.into_iter().map(Rc::new).collect();
+    }
+
+    /// Define the metrics that can be selected with their name `derived:name`.
+    pub fn set_derived(&mut self, derived: Vec<DerivedMetric>) {
+        self.derived = derived.into_iter().map(Rc::new).collect();
+    }
+
+    // Return the raw format
+    fn get_raw_format(id: MetricId) -> Formatter {
+        match id {
+            MetricId::CpuThrottledTime
+            | MetricId::DelayBlockIo
+            | MetricId::DelaySwapIn
+            | MetricId::TimeElapsed
+            | MetricId::TimeCpu
+            | MetricId::TimeSystem
+            | MetricId::TimeUser => format::seconds,
+            MetricId::CgroupMemoryRatio | MetricId::FdRatio | MetricId::FsRatio => format::ratio,
+            _ => format::identity,
+        }
+    }
+
+    // Without the human feature, the format is the raw one
+    #[cfg(not(feature = "human"))]
+    fn get_human_format(id: MetricId) -> Formatter {
+        MetricNamesParser::get_raw_format(id)
     }
 
     // Return the more readable format for a human
+    #[cfg(feature = "human")]
     fn get_human_format(id: MetricId) -> Formatter {
         match id {
             MetricId::IoReadCall
@@ -398,13 +698,22 @@ impl MetricNamesParser {
             | MetricId::MapVsyscallSize
             | MetricId::MapVvarSize
             | MetricId::MapOtherSize => format::size,
-            MetricId::MemRss | MetricId::MemVm | MetricId::MemText | MetricId::MemData => {
-                format::size
-            }
-            MetricId::TimeElapsed
+            MetricId::CgroupMemoryUsage
+            | MetricId::CgroupMemoryLimit
+            | MetricId::MemRss
+            | MetricId::MemVm
+            | MetricId::MemText
+            | MetricId::MemData
+            | MetricId::MemCache
+            | MetricId::FsUsed => format::size,
+            MetricId::CpuThrottledTime
+            | MetricId::DelayBlockIo
+            | MetricId::DelaySwapIn
+            | MetricId::TimeElapsed
             | MetricId::TimeCpu
             | MetricId::TimeSystem
             | MetricId::TimeUser => format::human_milliseconds,
+            MetricId::CgroupMemoryRatio | MetricId::FdRatio | MetricId::FsRatio => format::ratio,
             _ => format::identity,
         }
     }
@@ -413,13 +722,7 @@ impl MetricNamesParser {
         if self.human_format {
             MetricNamesParser::get_human_format(id)
         } else {
-            match id {
-                MetricId::TimeElapsed
-                | MetricId::TimeCpu
-                | MetricId::TimeSystem
-                | MetricId::TimeUser => format::seconds,
-                _ => format::identity,
-            }
+            MetricNamesParser::get_raw_format(id)
         }
     }
 
@@ -430,9 +733,36 @@ impl MetricNamesParser {
     {
         let mut metrics = Vec::new();
         let mut parsed_ids = HashSet::new();
-        names
-            .iter()
-            .try_for_each(|name| match parse_metric_spec(name.as_ref()) {
+        let mut parsed_derived = HashSet::new();
+        let mut parsed_filesystems = HashSet::new();
+        names.iter().try_for_each(|name| {
+            if name.as_ref().starts_with(DERIVED_PREFIX) {
+                let derived = self
+                    .derived
+                    .iter()
+                    .find(|derived| derived.name() == name.as_ref())
+                    .ok_or_else(|| Error::UnknownMetric(name.to_string()))?;
+                if !parsed_derived.insert(derived.name()) {
+                    return Err(Error::DuplicateMetric(name.to_string()));
+                }
+                let format = self.get_default_formatter(derived.first_operand());
+                metrics.push(FormattedMetric::with_derived(derived, format));
+                return Ok(());
+            }
+            if let Some((_, id)) = filesystems::split_metric_name(name.as_ref()) {
+                let filesystem = self
+                    .filesystems
+                    .iter()
+                    .find(|filesystem| filesystem.has_metric(name.as_ref()))
+                    .ok_or_else(|| Error::UnknownMetric(name.to_string()))?;
+                if !parsed_filesystems.insert(filesystem.metric_name(id)) {
+                    return Err(Error::DuplicateMetric(name.to_string()));
+                }
+                let format = self.get_default_formatter(id);
+                metrics.push(FormattedMetric::with_filesystem(filesystem, id, format));
+                return Ok(());
+            }
+            match parse_metric_spec(name.as_ref()) {
                 Ok((metric_ids, aggs, fmt)) => {
                     if metric_ids.is_empty() {
                         return Err(Error::UnknownMetric(name.to_string()));
@@ -452,7 +782,8 @@ impl MetricNamesParser {
                     Ok(())
                 }
                 Err(_) => Err(Error::InvalidSyntax(format!("{name}: invalid metric"))),
-            })?;
+            }
+        })?;
         Ok(metrics)
     }
 }
@@ -463,7 +794,10 @@ mod tests {
     use std::str::FromStr;
     use strum::{EnumMessage, IntoEnumIterator};
 
-    use super::{MetricDataType, MetricId, MetricNamesParser};
+    use super::{
+        super::Aggregation, DerivedMetric, Filesystem, MetricDataType, MetricId, MetricNamesParser,
+        MetricSelection,
+    };
 
     fn vec_of_string(vstr: &[&str]) -> Vec<String> {
         vstr.iter().map(|s| s.to_string()).collect()
@@ -510,6 +844,10 @@ mod tests {
     #[test]
     fn test_parse_metric_names() {
         let metric_names = vec_of_string(&[
+            "cpu:throttled:count",
+            "cpu:throttled:time/du",
+            "delay:blkio",
+            "delay:swapin/du",
             "fault:minor",
             "fault:major/k",
             "io:read:call",
@@ -526,6 +864,7 @@ mod tests {
             "time:system",
             "time:user",
             "thread:count",
+            "thread:rate",
         ]);
         // Check few metrics
         let mut parser1 = MetricNamesParser::new(false);
@@ -545,7 +884,7 @@ mod tests {
         let metric_names1 = vec_of_string(&["mem:*"]);
         let mut parser1 = MetricNamesParser::new(false);
         let metrics1 = parser1.parse(&metric_names1).unwrap();
-        assert_eq!(4, metrics1.len());
+        assert_eq!(5, metrics1.len());
 
         // Check suffix
         let metric_names2 = vec_of_string(&["*:storage"]);
@@ -573,6 +912,85 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_parse_derived_metric_names() {
+        let mut parser = MetricNamesParser::new(true);
+        parser.set_derived(vec![DerivedMetric::new(
+            "derived:cache",
+            "io:read:total - io:read:storage",
+        )
+        .unwrap()]);
+        let metrics = parser.parse(&["mem:rss", "derived:cache"]).unwrap();
+        assert_eq!(2, metrics.len());
+        assert_eq!("derived:cache", metrics[1].name());
+        assert_eq!(MetricId::Derived, metrics[1].id);
+        assert!(matches!(metrics[1].data_type(), MetricDataType::Counter));
+        assert!(parser.parse(&["derived:unknown"]).is_err());
+        assert!(parser.parse(&["derived:cache", "derived:cache"]).is_err());
+        assert!(MetricId::from_str("derived").is_err());
+    }
+
+    #[test]
+    fn test_parse_filesystem_metric_names() {
+        let mut parser = MetricNamesParser::new(true);
+        parser.set_filesystems(vec![Filesystem::new("root", "/").unwrap()]);
+        let metrics = parser
+            .parse(&["fs:root:used", "fs:root:ratio", "mem:rss"])
+            .unwrap();
+        assert_eq!(3, metrics.len());
+        assert_eq!("fs:root:used", metrics[0].name());
+        assert_eq!(MetricId::FsUsed, metrics[0].id);
+        assert_eq!("fs:root:ratio", metrics[1].name());
+        assert_eq!(MetricId::FsRatio, metrics[1].id);
+        assert!(!metrics[1].is_additive());
+        assert!(parser.parse(&["fs:data:used"]).is_err());
+        assert!(parser.parse(&["fs:root:used", "fs:root:used"]).is_err());
+        assert!(MetricId::from_str("fs:used").is_err());
+    }
+
+    #[test]
+    fn test_additive_metrics() {
+        let metrics = MetricNamesParser::new(false)
+            .parse(&[
+                "mem:rss",
+                "time:elapsed",
+                "cgmem:usage",
+                "cpu:throttled:count",
+                "cpu:throttled:time",
+            ])
+            .unwrap();
+        assert_eq!(
+            vec![true, false, false, false, false],
+            metrics
+                .iter()
+                .map(|metric| metric.is_additive())
+                .collect::<Vec<bool>>()
+        );
+    }
+
+    #[test]
+    fn test_metric_selection() {
+        let mut parser = MetricNamesParser::new(false);
+        let mut columns = parser.parse(&["time:cpu", "mem:rss"]).unwrap();
+        let targets = vec![
+            (
+                "db".to_string(),
+                parser.parse(&["io:read:total", "mem:rss+max"]).unwrap(),
+            ),
+            ("web".to_string(), parser.parse(&["thread:count"]).unwrap()),
+        ];
+        let selection = MetricSelection::merge(&mut columns, targets);
+        let names = columns.iter().map(|m| m.name()).collect::<Vec<&str>>();
+        assert_eq!(
+            vec!["time:cpu", "mem:rss", "io:read:total", "thread:count"],
+            names
+        );
+        assert!(columns[1].aggregations.has(Aggregation::Max));
+        assert_eq!(&[true, true, false, false][..], &*selection.get("other"));
+        assert_eq!(&[false, true, true, false][..], &*selection.get("db"));
+        assert_eq!(&[false, false, false, true][..], &*selection.get("web"));
+    }
+
     #[test]
     fn data_type() {
         assert!(matches!(
@@ -597,5 +1015,9 @@ mod tests {
             MetricId::ThreadCount.data_type(),
             MetricDataType::Gauge
         ));
+        assert!(matches!(
+            MetricId::ThreadRate.data_type(),
+            MetricDataType::Gauge
+        ));
     }
 }
@@ -16,9 +16,9 @@ impl File {
         let content = path
             .as_ref()
             .to_str()
-            .ok_or_else(|| io::Error::new(io::ErrorKind::Other, "wrong string in test"))?;
+            .ok_or_else(|| io::Error::other("wrong string in test"))?;
         if !content.starts_with(PATH_PREFIX) {
-            Err(io::Error::new(io::ErrorKind::Other, "invalid test path"))
+            Err(io::Error::other("invalid test path"))
         } else {
             let content: String = content.chars().skip(PATH_PREFIX.len()).collect();
             Ok(File { content, pos: 0 })
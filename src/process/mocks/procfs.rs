@@ -33,8 +33,17 @@ impl CpuTime {
 pub(crate) mod process {
 
     use libc::pid_t;
-    use procfs::process::{FDInfo, Io, Limits, MemoryMaps, StatM};
-    use std::{cell::RefCell, collections::HashMap, ffi::OsString, io, path::PathBuf, rc::Rc};
+    use procfs::process::{FDInfo, Io, Limits, MemoryMaps, StatM, Task};
+    #[cfg(feature = "tui")]
+    use procfs::process::{MountInfos, Namespaces};
+    use std::{
+        cell::RefCell,
+        fs, io,
+        path::{Path, PathBuf},
+        rc::Rc,
+    };
+    #[cfg(feature = "tui")]
+    use std::{collections::HashMap, ffi::OsString};
 
     pub(crate) use procfs::process::Stat;
 
@@ -50,8 +59,24 @@ pub(crate) mod process {
         }
     }
 
+    #[derive(Debug)]
+    pub(crate) struct TasksIter {}
+
+    impl std::iter::Iterator for TasksIter {
+        type Item = ProcResult<Task>;
+        fn next(&mut self) -> Option<ProcResult<Task>> {
+            None
+        }
+    }
+
     fn new_error(msg: &str) -> ProcError {
-        io::Error::new(io::ErrorKind::Other, msg)
+        io::Error::other(msg)
+    }
+
+    /// Parse a file captured with `--capture-procfs`.
+    fn read_captured<T: procfs::FromRead>(dir: &Path, filename: &str) -> ProcResult<T> {
+        let path = dir.join(filename);
+        T::from_file(&path).map_err(|err| new_error(&format!("{}: {err}", path.display())))
     }
 
     #[derive(Debug, Clone)]
@@ -62,6 +87,7 @@ pub(crate) mod process {
         start_time: u64,
         cpu_time: Rc<RefCell<CpuTime>>,
         ttl: Option<Rc<RefCell<u16>>>,
+        captured: Option<Rc<PathBuf>>,
     }
 
     impl Process {
@@ -80,6 +106,32 @@ pub(crate) mod process {
                 start_time,
                 cpu_time: Rc::new(RefCell::new(cpu_time)),
                 ttl: ttl.map(RefCell::new).map(Rc::new),
+                captured: None,
+            }
+        }
+
+        /// Process replayed from the directory /proc/PID captured with `--capture-procfs`.
+        pub(crate) fn from_capture(dir: &Path) -> ProcResult<Self> {
+            let stat: Stat = read_captured(dir, "stat")?;
+            let exe = fs::read_to_string(dir.join("exe"))?;
+            let cpu_time = CpuTime::new(stat.utime, stat.stime);
+            let mut process = Self::new_fake(
+                stat.pid,
+                stat.ppid,
+                Some(&exe),
+                stat.starttime,
+                cpu_time,
+                None,
+            );
+            process.captured = Some(Rc::new(dir.to_path_buf()));
+            Ok(process)
+        }
+
+        /// Read a captured file or fail if the process is not captured.
+        fn captured<T: procfs::FromRead>(&self, filename: &str) -> ProcResult<T> {
+            match self.captured {
+                Some(ref dir) => read_captured(dir, filename),
+                None => Err(new_error(&format!("Process::{filename} not implemented"))),
             }
         }
 
@@ -146,6 +198,11 @@ pub(crate) mod process {
                 .ok_or_else(|| new_error("no executable"))
         }
 
+        pub(crate) fn wchan(&self) -> ProcResult<String> {
+            Err(new_error("Process::wchan not implemented"))
+        }
+
+        #[cfg(feature = "tui")]
         pub(crate) fn cwd(&self) -> ProcResult<PathBuf> {
             Err(new_error("Process::cwd not implemented"))
         }
@@ -154,20 +211,40 @@ pub(crate) mod process {
             Err(new_error("Process::fd not implemented"))
         }
 
+        pub(crate) fn tasks(&self) -> ProcResult<TasksIter> {
+            Err(new_error("Process::tasks not implemented"))
+        }
+
         pub(crate) fn io(&self) -> ProcResult<Io> {
-            Err(new_error("Process::io not implemented"))
+            self.captured("io")
         }
 
+        #[cfg(feature = "tui")]
         pub(crate) fn environ(&self) -> ProcResult<HashMap<OsString, OsString>> {
             Err(new_error("Process::environ not implemented"))
         }
 
         pub(crate) fn limits(&self) -> ProcResult<Limits> {
-            Err(new_error("Process::limits not implemented"))
+            self.captured("limits")
+        }
+
+        #[cfg(feature = "tui")]
+        pub(crate) fn myself() -> ProcResult<Self> {
+            Self::new(std::process::id() as pid_t)
+        }
+
+        #[cfg(feature = "tui")]
+        pub(crate) fn namespaces(&self) -> ProcResult<Namespaces> {
+            Err(new_error("Process::namespaces not implemented"))
+        }
+
+        #[cfg(feature = "tui")]
+        pub(crate) fn mountinfo(&self) -> ProcResult<MountInfos> {
+            Err(new_error("Process::mountinfo not implemented"))
         }
 
         pub(crate) fn maps(&self) -> ProcResult<MemoryMaps> {
-            Err(new_error("Process::maps not implemented"))
+            self.captured("maps")
         }
 
         pub(crate) fn pid(&self) -> pid_t {
@@ -175,7 +252,9 @@ pub(crate) mod process {
         }
 
         pub(crate) fn stat(&self) -> ProcResult<Stat> {
-            if self.check_if_alive() {
+            if self.captured.is_some() {
+                self.captured("stat")
+            } else if self.check_if_alive() {
                 let cpu_time = self.cpu_time.borrow();
                 let mut st: Stat = procfs::FromRead::from_read(io::Cursor::new(format!(
                     "{} ({}) S {} {}",
@@ -202,7 +281,7 @@ pub(crate) mod process {
         }
 
         pub(crate) fn statm(&self) -> ProcResult<StatM> {
-            Err(new_error("Process::statm not implemented"))
+            self.captured("statm")
         }
 
         /// Simulate CPU.
@@ -226,6 +305,22 @@ pub(crate) mod process {
     pub(crate) fn all_processes() -> ProcResult<ProcessIter> {
         Err(new_error("all_processes not implemented"))
     }
+
+    /// Processes of a sample captured with `--capture-procfs` sorted by PID.
+    pub(crate) fn captured_processes(dir: &Path) -> ProcResult<Vec<Process>> {
+        let mut processes = fs::read_dir(dir)?
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| {
+                entry
+                    .file_name()
+                    .to_str()
+                    .is_some_and(|name| name.parse::<pid_t>().is_ok())
+            })
+            .map(|entry| Process::from_capture(&entry.path()))
+            .collect::<ProcResult<Vec<Process>>>()?;
+        processes.sort_by_key(Process::pid);
+        Ok(processes)
+    }
 }
 
 use libc::pid_t;
@@ -15,26 +15,63 @@
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
 mod agg;
+mod cgroup;
 mod collector;
+mod connector;
+mod external;
 mod forest;
+mod hidepid;
+#[cfg(feature = "tui")]
+mod iodevices;
+#[cfg(feature = "tui")]
+mod links;
 mod managers;
 mod metrics;
+#[cfg(feature = "tui")]
+mod mounts;
+mod pagecache;
 mod stat;
 mod targets;
+mod taskstats;
+mod threads;
+mod transitions;
+mod watch;
 
 #[cfg(test)]
 mod mocks;
 
+pub mod derived;
+pub mod filesystems;
 pub mod format;
 pub mod matchers;
 pub mod parsers;
 
 pub(crate) use self::agg::{Aggregation, AggregationSet};
-pub(crate) use self::collector::{Collector, ProcessIdentity, ProcessSamples, Sample};
-pub(crate) use self::forest::{format_result, Forest, Process, ProcessError, ProcessInfo};
+pub(crate) use self::cgroup::{MemoryUsage, MemoryWatch};
+#[cfg(any(feature = "tui", feature = "rrd"))]
+pub(crate) use self::collector::ProcessSamples;
+pub(crate) use self::collector::{counter_increase, Collector, CpuBasis, ProcessIdentity, Sample};
+pub(crate) use self::connector::{count_by_name, ShortLivedMonitor};
+pub(crate) use self::external::ExternalFormatter;
+#[cfg(feature = "tui")]
+pub(crate) use self::forest::format_result;
+pub(crate) use self::forest::{Forest, Process, ProcessError, ProcessInfo};
+pub(crate) use self::hidepid::ProcMount;
+#[cfg(feature = "tui")]
+pub(crate) use self::iodevices::{io_by_device, IoByDevice};
+#[cfg(feature = "tui")]
+pub(crate) use self::links::{memory_maps, open_files, process_name, MappedRegion, OpenFile};
 pub(crate) use self::managers::{
     FlatProcessManager, ForestProcessManager, ProcessDetails, ProcessFilter, ProcessManager,
 };
-pub(crate) use self::metrics::{FormattedMetric, MetricDataType, MetricId, MetricNamesParser};
-pub(crate) use self::stat::{ProcessStat, SystemConf, SystemStat};
+pub(crate) use self::metrics::{
+    FormattedMetric, MetricDataType, MetricId, MetricNamesParser, MetricSelection,
+};
+#[cfg(feature = "tui")]
+pub(crate) use self::mounts::{mount_usage, MountUsage};
+pub(crate) use self::stat::{LimitBreach, ProcessStat, SystemConf, SystemPart, SystemStat};
 pub(crate) use self::targets::{TargetContainer, TargetError, TargetId};
+pub(crate) use self::threads::ThreadCreation;
+#[cfg(feature = "tui")]
+pub(crate) use self::threads::ThreadMonitor;
+pub(crate) use self::transitions::{read_oom_kills, Credentials, StateEvent, StateMonitor};
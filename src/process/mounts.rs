@@ -0,0 +1,171 @@
+// Oprs -- process monitor for Linux
+// Copyright (C) 2025  Laurent Pelecq
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Size of the files open by a process grouped by mount point.
+//!
+//! The files are identified by the device of /proc/PID/fd/FD that is matched
+//! with the mount points of the process. Deleted files that are still open
+//! are counted.
+
+use procfs::process::{FDTarget, MountInfo};
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs,
+    os::unix::fs::MetadataExt,
+    path::PathBuf,
+};
+
+use super::{forest::ProcResult, Process};
+
+/// Files open by a process on a mount point.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MountUsage {
+    /// Mount point or None if the device is not mounted in the namespace of the process.
+    pub mount_point: Option<PathBuf>,
+    pub fs_type: String,
+    pub source: String,
+    /// Number of distinct files.
+    pub files: usize,
+    /// Sum of the sizes in bytes.
+    pub size: u64,
+}
+
+/// Device number as in /proc/PID/mountinfo.
+pub(super) fn majmin(dev: u64) -> String {
+    format!("{}:{}", libc::major(dev), libc::minor(dev))
+}
+
+/// Mount of a device. If it is mounted several times, the mount of the root of
+/// the file system is preferred.
+pub(super) fn find_mount<'a>(mounts: &'a [MountInfo], majmin: &str) -> Option<&'a MountInfo> {
+    mounts
+        .iter()
+        .filter(|mount| mount.majmin == majmin)
+        .min_by_key(|mount| mount.root != "/")
+}
+
+/// Group the size of the files by mount point, the biggest first.
+///
+/// The files are given by device and size. If a device is mounted several times,
+/// the mount of the root of the file system is preferred.
+fn group_by_mount<I>(mounts: &[MountInfo], files: I) -> Vec<MountUsage>
+where
+    I: IntoIterator<Item = (u64, u64)>,
+{
+    let mut devices = BTreeMap::new();
+    for (dev, size) in files {
+        let (count, total) = devices.entry(dev).or_insert((0, 0));
+        *count += 1;
+        *total += size;
+    }
+    let mut usages = devices
+        .into_iter()
+        .map(|(dev, (files, size))| {
+            let majmin = majmin(dev);
+            let mount = find_mount(mounts, &majmin);
+            MountUsage {
+                mount_point: mount.map(|mount| mount.mount_point.clone()),
+                fs_type: mount.map(|mount| mount.fs_type.clone()).unwrap_or_default(),
+                source: mount
+                    .and_then(|mount| mount.mount_source.clone())
+                    .unwrap_or(majmin),
+                files,
+                size,
+            }
+        })
+        .collect::<Vec<MountUsage>>();
+    usages.sort_by(|u1, u2| {
+        u2.size
+            .cmp(&u1.size)
+            .then_with(|| u1.mount_point.cmp(&u2.mount_point))
+    });
+    usages
+}
+
+/// Size of the regular files open by a process grouped by mount point.
+///
+/// A file open several times is counted once.
+pub fn mount_usage(process: &Process) -> ProcResult<Vec<MountUsage>> {
+    let pid = process.pid();
+    let mounts = process.mountinfo()?;
+    let mut seen = HashSet::new();
+    let files = process
+        .fd()?
+        .flatten()
+        .filter(|fdinfo| matches!(fdinfo.target, FDTarget::Path(_)))
+        .filter_map(|fdinfo| fs::metadata(format!("/proc/{pid}/fd/{}", fdinfo.fd)).ok())
+        .filter(|metadata| metadata.is_file() && seen.insert((metadata.dev(), metadata.ino())))
+        .map(|metadata| (metadata.dev(), metadata.len()))
+        .collect::<Vec<(u64, u64)>>();
+    Ok(group_by_mount(&mounts.0, files))
+}
+
+#[cfg(test)]
+mod test {
+
+    use procfs::process::MountInfo;
+    use std::path::PathBuf;
+
+    use super::{group_by_mount, MountUsage};
+
+    fn dev(major: u32, minor: u32) -> u64 {
+        libc::makedev(major, minor)
+    }
+
+    #[test]
+    fn test_group_by_mount() {
+        let mounts = [
+            "22 1 8:1 / / rw,relatime shared:1 - ext4 /dev/sda1 rw",
+            "30 22 8:2 /data /srv rw,relatime shared:2 - xfs /dev/sda2 rw",
+            "31 22 8:2 / /mnt/data rw,relatime shared:2 - xfs /dev/sda2 rw",
+        ]
+        .iter()
+        .map(|line| MountInfo::from_line(line).unwrap())
+        .collect::<Vec<MountInfo>>();
+        let files = [
+            (dev(8, 1), 100),
+            (dev(8, 2), 1_000),
+            (dev(8, 1), 50),
+            (dev(0, 45), 7),
+        ];
+        assert_eq!(
+            vec![
+                MountUsage {
+                    mount_point: Some(PathBuf::from("/mnt/data")),
+                    fs_type: "xfs".to_string(),
+                    source: "/dev/sda2".to_string(),
+                    files: 1,
+                    size: 1_000,
+                },
+                MountUsage {
+                    mount_point: Some(PathBuf::from("/")),
+                    fs_type: "ext4".to_string(),
+                    source: "/dev/sda1".to_string(),
+                    files: 2,
+                    size: 150,
+                },
+                MountUsage {
+                    mount_point: None,
+                    fs_type: String::new(),
+                    source: "0:45".to_string(),
+                    files: 1,
+                    size: 7,
+                },
+            ],
+            group_by_mount(&mounts, files)
+        );
+    }
+}
@@ -0,0 +1,251 @@
+// Oprs -- process monitor for Linux
+// Copyright (C) 2025  Laurent Pelecq
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Page cache of the files open or mapped by a process.
+//!
+//! The pages in cache are counted with the system call cachestat(2) available
+//! since Linux 6.5 or with mincore(2) on older kernels. The cache is shared
+//! between processes, so it's an estimation of the footprint of the process.
+
+use procfs::process::{FDTarget, MMapPath};
+use std::{
+    collections::HashSet,
+    fs::{self, File, OpenOptions},
+    io,
+    os::{
+        fd::AsRawFd,
+        unix::fs::{MetadataExt, OpenOptionsExt},
+    },
+    path::Path,
+    ptr,
+};
+
+use super::{forest::ProcResult, Process};
+
+/// System call number of cachestat, the same on all architectures.
+const SYS_CACHESTAT: libc::c_long = 451;
+
+/// Size of the parts of a file mapped at once with mincore(2).
+const MINCORE_CHUNK_SIZE: u64 = 64 * 1024 * 1024;
+
+/// Range of a file, a length of zero is up to the end of the file.
+#[repr(C)]
+struct CacheStatRange {
+    offset: u64,
+    length: u64,
+}
+
+/// Number of pages of a file by state.
+#[repr(C)]
+#[derive(Default)]
+#[allow(dead_code)]
+struct CacheStat {
+    cache: u64,
+    dirty: u64,
+    writeback: u64,
+    evicted: u64,
+    recently_evicted: u64,
+}
+
+/// Number of pages of a file in cache with cachestat(2).
+fn cachestat(file: &File) -> io::Result<u64> {
+    let range = CacheStatRange {
+        offset: 0,
+        length: 0,
+    };
+    let mut stat = CacheStat::default();
+    let res = unsafe {
+        libc::syscall(
+            SYS_CACHESTAT,
+            file.as_raw_fd(),
+            &range as *const CacheStatRange,
+            &mut stat as *mut CacheStat,
+            0,
+        )
+    };
+    if res < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(stat.cache)
+    }
+}
+
+/// Number of pages in cache of a part of a file with mincore(2).
+///
+/// The offset is a multiple of the page size.
+fn mincore_chunk(file: &File, offset: u64, length: usize, pages: &mut [u8]) -> io::Result<u64> {
+    let offset =
+        libc::off_t::try_from(offset).map_err(|_| io::Error::from(io::ErrorKind::InvalidInput))?;
+    let addr = unsafe {
+        libc::mmap(
+            ptr::null_mut(),
+            length,
+            libc::PROT_READ,
+            libc::MAP_SHARED,
+            file.as_raw_fd(),
+            offset,
+        )
+    };
+    if addr == libc::MAP_FAILED {
+        return Err(io::Error::last_os_error());
+    }
+    let res = unsafe { libc::mincore(addr, length, pages.as_mut_ptr()) };
+    let err = io::Error::last_os_error();
+    unsafe { libc::munmap(addr, length) };
+    if res < 0 {
+        Err(err)
+    } else {
+        Ok(pages.iter().filter(|page| *page & 1 != 0).count() as u64)
+    }
+}
+
+/// Number of pages of a file in cache with mincore(2).
+///
+/// The file is mapped by chunks to bound the memory used.
+fn mincore(file: &File, size: u64, page_size: u64) -> io::Result<u64> {
+    let chunk_size = MINCORE_CHUNK_SIZE.div_ceil(page_size) * page_size;
+    let mut pages = vec![0u8; (chunk_size / page_size) as usize];
+    let mut count = 0;
+    let mut offset = 0;
+    while offset < size {
+        let length = chunk_size.min(size - offset);
+        let page_count = length.div_ceil(page_size) as usize;
+        count += mincore_chunk(file, offset, length as usize, &mut pages[..page_count])?;
+        offset += length;
+    }
+    Ok(count)
+}
+
+/// Sum of the bytes in cache of distinct regular files.
+#[derive(Debug)]
+struct CacheCounter {
+    page_size: u64,
+    /// Device and inode of the files already counted.
+    seen: HashSet<(u64, u64)>,
+    /// Whether cachestat is available.
+    with_cachestat: bool,
+    bytes: u64,
+}
+
+impl CacheCounter {
+    fn new(page_size: u64) -> Self {
+        Self {
+            page_size,
+            seen: HashSet::new(),
+            with_cachestat: true,
+            bytes: 0,
+        }
+    }
+
+    /// Count a file once. Other files than regular files are not opened.
+    ///
+    /// The file is opened in non-blocking mode in case it is replaced by a FIFO
+    /// and checked again once open.
+    fn add<P: AsRef<Path>>(&mut self, path: P) {
+        let path = path.as_ref();
+        if !fs::metadata(path).is_ok_and(|metadata| metadata.is_file()) {
+            return;
+        }
+        let Ok(file) = OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_NONBLOCK | libc::O_NOCTTY)
+            .open(path)
+        else {
+            return;
+        };
+        let Ok(metadata) = file.metadata() else {
+            return;
+        };
+        if metadata.is_file() && self.seen.insert((metadata.dev(), metadata.ino())) {
+            if let Ok(pages) = self.pages(&file, metadata.len()) {
+                self.bytes += pages * self.page_size;
+            }
+        }
+    }
+
+    fn pages(&mut self, file: &File, size: u64) -> io::Result<u64> {
+        if self.with_cachestat {
+            match cachestat(file) {
+                // Not implemented or forbidden by a seccomp filter.
+                Err(err) if matches!(err.raw_os_error(), Some(libc::ENOSYS | libc::EPERM)) => {
+                    log::debug!("cachestat not available ({err}), using mincore");
+                    self.with_cachestat = false;
+                }
+                res => return res,
+            }
+        }
+        mincore(file, size, self.page_size)
+    }
+}
+
+/// Bytes in the page cache of the regular files open or mapped by a process.
+///
+/// A file open or mapped several times is counted once.
+pub fn page_cache(process: &Process, page_size: u64) -> ProcResult<u64> {
+    let pid = process.pid();
+    let mut counter = CacheCounter::new(page_size);
+    process
+        .fd()?
+        .flatten()
+        .filter(|fdinfo| matches!(fdinfo.target, FDTarget::Path(_)))
+        .for_each(|fdinfo| counter.add(format!("/proc/{pid}/fd/{}", fdinfo.fd)));
+    process
+        .maps()?
+        .into_iter()
+        .filter_map(|map| match map.pathname {
+            MMapPath::Path(path) => Some(path),
+            _ => None,
+        })
+        .for_each(|path| counter.add(path));
+    Ok(counter.bytes)
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::{fs, io::Write};
+
+    use super::{mincore, CacheCounter};
+
+    #[test]
+    fn test_cache_counter() {
+        let page_size = procfs::page_size();
+        let dir = std::env::temp_dir().join(format!("oprs-test-cache-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("data");
+        let mut file = fs::File::create(&path).unwrap();
+        file.write_all(&vec![1u8; 16 * page_size as usize]).unwrap();
+        file.flush().unwrap();
+
+        let mut counter = CacheCounter::new(page_size);
+        counter.add(&path);
+        let bytes = counter.bytes;
+        assert!(
+            bytes > 0 && bytes <= 16 * page_size,
+            "{bytes} bytes in cache"
+        );
+        // Counted once.
+        counter.add(&path);
+        assert_eq!(bytes, counter.bytes);
+        let pages = mincore(&fs::File::open(&path).unwrap(), 16 * page_size, page_size).unwrap();
+        assert!(pages > 0 && pages <= 16, "{pages} pages in cache");
+        // Only regular files.
+        counter.add(&dir);
+        counter.add("/dev/null");
+        assert_eq!(bytes, counter.bytes);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
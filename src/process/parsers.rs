@@ -24,13 +24,17 @@ use nom::{
     sequence::{pair, preceded},
     IResult,
 };
+use std::fmt;
 use std::result;
 use std::str::FromStr;
+use std::time::Duration;
 use strum::IntoEnumIterator;
 
+use crate::clock::parse_duration;
+
 use super::{
-    format::{self, Formatter},
-    Aggregation, AggregationSet, MetricId,
+    format::{self, ColumnAlignment, ColumnFormat, Formatter},
+    Aggregation, AggregationSet, MetricId, ProcessFilter,
 };
 
 const KILO: usize = 1000;
@@ -67,6 +71,22 @@ pub fn parse_size(input: &str) -> result::Result<u64, ParseError> {
     Ok(value * factor)
 }
 
+/// Parse a comma-separated list of non-zero intervals (ex: 1m,1h)
+///
+/// The intervals are sorted without duplicates.
+pub fn parse_intervals(input: &str) -> result::Result<Vec<Duration>, ParseError> {
+    let mut intervals = input
+        .split(',')
+        .map(|item| parse_duration(item.trim()).map_err(|_| ParseError::ValueError))
+        .collect::<result::Result<Vec<Duration>, ParseError>>()?;
+    if intervals.iter().any(|interval| interval.is_zero()) {
+        return Err(ParseError::ValueError);
+    }
+    intervals.sort();
+    intervals.dedup();
+    Ok(intervals)
+}
+
 /// Expands limited globbing
 /// Allowed: prefix mem:*, suffix *:call, middle io:*:call
 fn expand_metric_name(metric_ids: &mut Vec<MetricId>, name: &str) {
@@ -144,6 +164,9 @@ fn parse_formatter(input: &str) -> IResult<&str, Option<Formatter>> {
             tag("t"),
             tag("sz"),
             tag("du"),
+            tag("dhm"),
+            tag("hms"),
+            tag("sec"),
         )),
     ))(input)?;
     Ok((
@@ -159,6 +182,9 @@ fn parse_formatter(input: &str) -> IResult<&str, Option<Formatter>> {
             "t" => format::tera,
             "sz" => format::size,
             "du" => format::human_milliseconds,
+            "dhm" => format::compact_milliseconds,
+            "hms" => format::clock_milliseconds,
+            "sec" => format::whole_seconds,
             _ => panic!("not reachable"),
         }),
     ))
@@ -187,10 +213,191 @@ pub fn parse_metric_spec(
     }
 }
 
+/// Bucket upper bounds of a histogram for a metric.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HistogramBounds {
+    pub id: MetricId,
+    pub bounds: Vec<u64>,
+}
+
+impl fmt::Display for HistogramBounds {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}=", self.id)?;
+        for (index, bound) in self.bounds.iter().enumerate() {
+            if index > 0 {
+                write!(f, ",")?;
+            }
+            write!(f, "{bound}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Parse histogram specification metric=bound,bound,... (ex: mem:rss=10m,100m,1g)
+pub fn parse_histogram(input: &str) -> result::Result<HistogramBounds, ParseError> {
+    let (name, bounds) = input.split_once('=').ok_or(ParseError::SyntaxError)?;
+    let id = MetricId::from_str(name).map_err(|_| ParseError::ValueError)?;
+    let mut bounds = bounds
+        .split(',')
+        .map(|bound| parse_size(bound.trim()))
+        .collect::<result::Result<Vec<u64>, ParseError>>()?;
+    bounds.sort_unstable();
+    bounds.dedup();
+    Ok(HistogramBounds { id, bounds })
+}
+
+/// Parse an external formatter metric=command (ex: mem:rss=numfmt --to=iec)
+pub fn parse_external_formatter(input: &str) -> result::Result<(MetricId, &str), ParseError> {
+    let (name, command) = input.split_once('=').ok_or(ParseError::SyntaxError)?;
+    let id = MetricId::from_str(name.trim()).map_err(|_| ParseError::ValueError)?;
+    let command = command.trim();
+    if command.is_empty() {
+        Err(ParseError::SyntaxError)
+    } else {
+        Ok((id, command))
+    }
+}
+
+/// Parse the presentation of a metric (ex: right, decimals=1, width=8)
+pub fn parse_column_format(input: &str) -> result::Result<ColumnFormat, ParseError> {
+    let mut column = ColumnFormat::default();
+    for item in input.split(',').map(str::trim) {
+        match item.split_once('=') {
+            Some(("decimals", value)) => {
+                column.decimals = Some(value.trim().parse().map_err(|_| ParseError::ValueError)?)
+            }
+            Some(("width", value)) => {
+                column.width = Some(value.trim().parse().map_err(|_| ParseError::ValueError)?)
+            }
+            Some(_) => return Err(ParseError::SyntaxError),
+            None => {
+                column.alignment =
+                    Some(ColumnAlignment::from_str(item).map_err(|_| ParseError::ValueError)?)
+            }
+        }
+    }
+    Ok(column)
+}
+
+/// Parse the presentation of a metric metric=format (ex: time:cpu=right,decimals=1)
+pub fn parse_metric_column(input: &str) -> result::Result<(MetricId, ColumnFormat), ParseError> {
+    let (name, column) = input.split_once('=').ok_or(ParseError::SyntaxError)?;
+    let id = MetricId::from_str(name.trim()).map_err(|_| ParseError::ValueError)?;
+    Ok((id, parse_column_format(column)?))
+}
+
+/// Limit on the raw value of a metric.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Threshold {
+    pub id: MetricId,
+    /// True if values above the limit are out of range, false if values below are.
+    pub above: bool,
+    pub limit: u64,
+}
+
+impl Threshold {
+    /// Whether the value is out of range.
+    pub fn exceeded(&self, value: u64) -> bool {
+        if self.above {
+            value > self.limit
+        } else {
+            value < self.limit
+        }
+    }
+}
+
+/// Parse threshold specification metric>limit or metric<limit (ex: mem:rss>1g)
+pub fn parse_threshold(input: &str) -> result::Result<Threshold, ParseError> {
+    let (name, limit, above) = match input.split_once('>') {
+        Some((name, limit)) => (name, limit, true),
+        None => match input.split_once('<') {
+            Some((name, limit)) => (name, limit, false),
+            None => return Err(ParseError::SyntaxError),
+        },
+    };
+    let id = MetricId::from_str(name.trim()).map_err(|_| ParseError::ValueError)?;
+    let limit = parse_size(&limit.trim().to_lowercase())?;
+    Ok(Threshold { id, above, limit })
+}
+
+/// Search and filter applied with a single key in the terminal.
+///
+/// The pattern has the syntax of the search bar: a substring of the name, a
+/// prefix of the PID or `user:NAME`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SavedSearch {
+    pub pattern: Option<String>,
+    pub filter: Option<ProcessFilter>,
+}
+
+impl fmt::Display for SavedSearch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let pattern = self.pattern.as_ref().map(|pattern| {
+            if let Some(user) = pattern.strip_prefix("user:") {
+                format!("user ~ {user}")
+            } else if pattern.starts_with(|c: char| c.is_ascii_digit()) {
+                format!("pid ~ {pattern}")
+            } else {
+                format!("name ~ {pattern}")
+            }
+        });
+        let filter = self.filter.map(|filter| format!("filter = {filter}"));
+        let clauses = pattern.into_iter().chain(filter).collect::<Vec<String>>();
+        write!(f, "{}", clauses.join("; "))
+    }
+}
+
+/// Parse a saved search with clauses separated by semicolons (ex: name ~ postgres; filter = active)
+///
+/// The clauses are `name ~ TEXT`, `pid ~ PREFIX`, `user ~ NAME` and `filter = none|user|active`.
+pub fn parse_saved_search(input: &str) -> result::Result<SavedSearch, ParseError> {
+    let mut saved = SavedSearch::default();
+    for clause in input.split(';').map(str::trim) {
+        if let Some((field, value)) = clause.split_once('~') {
+            let value = value.trim();
+            let pattern = match field.trim() {
+                "name" if !value.starts_with(|c: char| c.is_ascii_digit()) => value.to_string(),
+                "pid" if value.chars().all(|c| c.is_ascii_digit()) => value.to_string(),
+                "user" => format!("user:{value}"),
+                _ => return Err(ParseError::ValueError),
+            };
+            if value.is_empty() || saved.pattern.replace(pattern).is_some() {
+                return Err(ParseError::ValueError);
+            }
+        } else if let Some(("filter", value)) = clause.split_once('=').map(|(k, v)| (k.trim(), v)) {
+            let filter =
+                ProcessFilter::from_str(value.trim()).map_err(|_| ParseError::ValueError)?;
+            if saved.filter.replace(filter).is_some() {
+                return Err(ParseError::ValueError);
+            }
+        } else {
+            return Err(ParseError::SyntaxError);
+        }
+    }
+    Ok(saved)
+}
+
+/// Split a target specification value[:label]
+///
+/// The label follows the last colon. A value containing a colon must be
+/// followed by a colon even without label.
+pub fn parse_label(input: &str) -> (&str, Option<&str>) {
+    match input.rsplit_once(':') {
+        Some((value, label)) if !label.is_empty() => (value, Some(label)),
+        Some((value, _)) => (value, None),
+        None => (input, None),
+    }
+}
+
 #[cfg(test)]
 mod tests {
 
-    use super::{parse_metric_spec, parse_size, Aggregation, MetricId, ParseError};
+    use super::{
+        parse_external_formatter, parse_histogram, parse_intervals, parse_label,
+        parse_metric_column, parse_metric_spec, parse_saved_search, parse_size, parse_threshold,
+        Aggregation, ColumnAlignment, MetricId, ParseError, ProcessFilter, SavedSearch,
+    };
+    use std::time::Duration;
 
     #[test]
     fn test_wo_raw_w_max() {
@@ -227,6 +434,17 @@ mod tests {
         assert_eq!("0.98 Ki", fmt(1000)); // 1000 divided by 1024
     }
 
+    #[test]
+    fn test_duration_format() {
+        let (metric_ids, _, fmt) = parse_metric_spec("time:elapsed/hms").unwrap();
+        assert_eq!(&[MetricId::TimeElapsed], metric_ids.as_slice());
+        assert_eq!("01:01:05", fmt.unwrap()(3_665_000));
+        let (_, _, fmt) = parse_metric_spec("time:elapsed/dhm").unwrap();
+        assert_eq!("1h1m", fmt.unwrap()(3_665_000));
+        let (_, _, fmt) = parse_metric_spec("time:cpu/sec").unwrap();
+        assert_eq!("3665", fmt.unwrap()(3_665_000));
+    }
+
     #[test]
     fn test_name_only() {
         let (metric_ids, aggs, fmt) = parse_metric_spec("fault:minor").unwrap();
@@ -255,4 +473,133 @@ mod tests {
         assert_eq!(2_000_000_000, parse_size("2g")?);
         Ok(())
     }
+
+    #[test]
+    fn parse_interval_lists() -> Result<(), ParseError> {
+        assert_eq!(
+            vec![Duration::from_secs(60), Duration::from_secs(3_600)],
+            parse_intervals("1h, 1m,60")?
+        );
+        assert!(parse_intervals("1m,0").is_err());
+        assert!(parse_intervals("1m,").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn parse_external_formatters() -> Result<(), ParseError> {
+        let (id, command) = parse_external_formatter("mem:rss=numfmt --to=iec")?;
+        assert_eq!(MetricId::MemRss, id);
+        assert_eq!("numfmt --to=iec", command);
+        assert!(parse_external_formatter("mem:rss").is_err());
+        assert!(parse_external_formatter("mem:rss=").is_err());
+        assert!(parse_external_formatter("mem:none=cat").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn parse_metric_columns() -> Result<(), ParseError> {
+        let (id, column) = parse_metric_column("time:cpu=right, decimals=1, width=7")?;
+        assert_eq!(MetricId::TimeCpu, id);
+        assert_eq!(Some(ColumnAlignment::Right), column.alignment);
+        assert_eq!(Some(1), column.decimals);
+        assert_eq!(Some(7), column.width);
+        let (_, column) = parse_metric_column("mem:rss=decimals=0")?;
+        assert_eq!(None, column.alignment);
+        assert_eq!(Some(0), column.decimals);
+        assert!(matches!(
+            parse_metric_column("mem:rss=middle"),
+            Err(ParseError::ValueError)
+        ));
+        assert!(matches!(
+            parse_metric_column("mem:rss=width=x"),
+            Err(ParseError::ValueError)
+        ));
+        assert!(matches!(
+            parse_metric_column("mem:rss=digits=2"),
+            Err(ParseError::SyntaxError)
+        ));
+        assert!(parse_metric_column("mem:rss").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn parse_histograms() -> Result<(), ParseError> {
+        let spec = parse_histogram("mem:rss=100m,10m,1g")?;
+        assert_eq!(MetricId::MemRss, spec.id);
+        assert_eq!(vec![10_000_000, 100_000_000, 1_000_000_000], spec.bounds);
+        assert!(matches!(
+            parse_histogram("mem:rss"),
+            Err(ParseError::SyntaxError)
+        ));
+        assert!(matches!(
+            parse_histogram("mem:none=1k"),
+            Err(ParseError::ValueError)
+        ));
+        assert!(parse_histogram("mem:rss=1k,x").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn parse_thresholds() -> Result<(), ParseError> {
+        let threshold = parse_threshold("mem:rss>1G")?;
+        assert_eq!(MetricId::MemRss, threshold.id);
+        assert!(threshold.above);
+        assert_eq!(1_000_000_000, threshold.limit);
+        assert!(threshold.exceeded(1_000_000_001));
+        assert!(!threshold.exceeded(1_000_000_000));
+        let threshold = parse_threshold("fd:all<10")?;
+        assert!(threshold.exceeded(9));
+        assert!(!threshold.exceeded(10));
+        assert!(matches!(
+            parse_threshold("mem:rss=1g"),
+            Err(ParseError::SyntaxError)
+        ));
+        assert!(matches!(
+            parse_threshold("mem:none>1g"),
+            Err(ParseError::ValueError)
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn parse_labels() {
+        assert_eq!(("1234", Some("db-primary")), parse_label("1234:db-primary"));
+        assert_eq!(("nginx", None), parse_label("nginx"));
+        assert_eq!(("nginx", None), parse_label("nginx:"));
+        assert_eq!(("kworker/0:1", None), parse_label("kworker/0:1:"));
+        assert_eq!(("kworker/0:1", Some("kw")), parse_label("kworker/0:1:kw"));
+    }
+
+    #[test]
+    fn test_parse_saved_search() {
+        let saved = parse_saved_search("name ~ postgres").unwrap();
+        assert_eq!(Some("postgres"), saved.pattern.as_deref());
+        assert_eq!(None, saved.filter);
+        assert_eq!(
+            SavedSearch {
+                pattern: Some(String::from("user:www-data")),
+                filter: Some(ProcessFilter::Active),
+            },
+            parse_saved_search("user ~ www-data; filter = active").unwrap()
+        );
+        assert_eq!(
+            Some("12"),
+            parse_saved_search("pid~12").unwrap().pattern.as_deref()
+        );
+        let filter_only = parse_saved_search("filter = none").unwrap();
+        assert_eq!(Some(ProcessFilter::None), filter_only.filter);
+        for saved in ["name ~ nginx; filter = user", "pid ~ 42", "filter = active"] {
+            assert_eq!(saved, parse_saved_search(saved).unwrap().to_string());
+        }
+        assert!(matches!(
+            parse_saved_search("postgres"),
+            Err(ParseError::SyntaxError)
+        ));
+        assert!(parse_saved_search("pid ~ abc").is_err());
+        assert!(parse_saved_search("name ~ 1abc").is_err());
+        assert!(parse_saved_search("name ~ a; name ~ b").is_err());
+        assert!(parse_saved_search("filter = all").is_err());
+        assert!(parse_saved_search("cmd ~ x").is_err());
+        assert!(parse_saved_search("name ~ ").is_err());
+    }
 }
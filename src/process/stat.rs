@@ -16,14 +16,24 @@
 
 // Extract metrics from procfs interface.
 
-use std::{collections::HashMap, fmt, slice::Iter, time::SystemTime};
+use std::{cell::OnceCell, collections::HashMap, fmt, path::Path, slice::Iter, time::SystemTime};
+
+use strum_macros::{EnumIter, IntoStaticStr};
 
 use procfs::{
-    process::{FDTarget, Io, MMapPath, Stat, StatM},
+    process::{FDTarget, Io, LimitValue, Limits, MMapPath, Stat, StatM},
     CpuInfo, CpuTime, Current, CurrentSI, KernelStats, Meminfo, ProcResult,
 };
 
-use super::{FormattedMetric, MetricId, Process};
+/// Size of a sector in /proc/diskstats whatever the device.
+const DISK_SECTOR_SIZE: u64 = 512;
+
+use super::{
+    cgroup::{self, CgroupMemory, CpuThrottling},
+    pagecache,
+    taskstats::{Delays, TaskStats},
+    FormattedMetric, MetricId, Process, ThreadCreation,
+};
 
 #[derive(thiserror::Error, Debug)]
 pub enum StatError {
@@ -33,6 +43,39 @@ pub enum StatError {
 
 pub type StatResult<T> = Result<T, StatError>;
 
+/// Usage of a limit in per thousand that is near the limit.
+const NEAR_LIMIT: u64 = 800;
+
+/// Usage of a limit in per thousand that is considered reached.
+const AT_LIMIT: u64 = 950;
+
+/// How close a metric is to its resource limit.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LimitBreach {
+    #[default]
+    None,
+    Near,
+    At,
+}
+
+impl LimitBreach {
+    /// Breach for a usage of a limit in per thousand.
+    pub fn with_per_mille(usage: u64) -> Self {
+        if usage >= AT_LIMIT {
+            LimitBreach::At
+        } else if usage >= NEAR_LIMIT {
+            LimitBreach::Near
+        } else {
+            LimitBreach::None
+        }
+    }
+
+    /// Level exported: 0 for none, 1 near the limit, 2 at the limit.
+    pub fn level(self) -> u64 {
+        self as u64
+    }
+}
+
 /// Elapsed time since a start time
 /// Since the boot time is in seconds since the Epoch, no need to be more precise than the second.
 fn elapsed_seconds_since(start_time: u64) -> u64 {
@@ -47,6 +90,7 @@ pub struct SystemConf {
     ticks_per_second: u64,
     boot_time_seconds: u64,
     page_size: u64,
+    taskstats: OnceCell<Option<TaskStats>>,
 }
 
 impl SystemConf {
@@ -60,9 +104,23 @@ impl SystemConf {
             ticks_per_second,
             boot_time_seconds: kstat.btime,
             page_size,
+            taskstats: OnceCell::new(),
         })
     }
 
+    /// Client of the delay accounting interface, opened on first use.
+    fn taskstats(&self) -> Option<&TaskStats> {
+        self.taskstats
+            .get_or_init(|| match TaskStats::new() {
+                Ok(taskstats) => Some(taskstats),
+                Err(err) => {
+                    log::warn!("delay accounting is not available: {err}");
+                    None
+                }
+            })
+            .as_ref()
+    }
+
     /// Convert a number of ticks in milliseconds.
     /// A u64 can hold more than 10 millions years
     pub fn ticks_to_millis(&self, ticks: u64) -> u64 {
@@ -70,6 +128,60 @@ impl SystemConf {
     }
 }
 
+/// Part of the system in the breakdown of the system target.
+///
+/// CPU parts are reported as CPU time, memory parts as memory size.
+#[derive(Clone, Copy, Debug, EnumIter, IntoStaticStr, PartialEq, Eq)]
+pub enum SystemPart {
+    #[strum(serialize = "cpu-user")]
+    CpuUser,
+    #[strum(serialize = "cpu-sys")]
+    CpuSystem,
+    #[strum(serialize = "cpu-iowait")]
+    CpuIoWait,
+    #[strum(serialize = "cpu-irq")]
+    CpuIrq,
+    #[strum(serialize = "mem-used")]
+    MemUsed,
+    #[strum(serialize = "mem-buffers")]
+    MemBuffers,
+    #[strum(serialize = "mem-cached")]
+    MemCached,
+    #[strum(serialize = "mem-swap")]
+    MemSwap,
+}
+
+impl SystemPart {
+    pub fn as_str(self) -> &'static str {
+        self.into()
+    }
+
+    /// Ticks spent in a CPU state.
+    fn cpu_ticks(self, ct: &CpuTime) -> Option<u64> {
+        match self {
+            SystemPart::CpuUser => Some(ct.user + ct.nice),
+            SystemPart::CpuSystem => Some(ct.system),
+            SystemPart::CpuIoWait => Some(ct.iowait.unwrap_or(0)),
+            SystemPart::CpuIrq => Some(ct.irq.unwrap_or(0) + ct.softirq.unwrap_or(0)),
+            _ => None,
+        }
+    }
+
+    /// Size of a memory category in bytes.
+    fn mem_bytes(self, mi: &Meminfo) -> Option<u64> {
+        match self {
+            SystemPart::MemUsed => Some(
+                mi.mem_total
+                    .saturating_sub(mi.mem_free + mi.buffers + mi.cached),
+            ),
+            SystemPart::MemBuffers => Some(mi.buffers),
+            SystemPart::MemCached => Some(mi.cached),
+            SystemPart::MemSwap => Some(mi.swap_total.saturating_sub(mi.swap_free)),
+            _ => None,
+        }
+    }
+}
+
 /// System info
 pub struct SystemStat<'a> {
     sysconf: &'a SystemConf,
@@ -132,34 +244,109 @@ impl<'a> SystemStat<'a> {
 
     pub fn extract_metrics(&mut self, metrics: Iter<FormattedMetric>) -> Vec<u64> {
         metrics
-            .map(|metric| match metric.id {
-                MetricId::MemVm => self
-                    .with_meminfo(|mi| mi.mem_total - mi.mem_free + mi.swap_total - mi.swap_free),
-                MetricId::MemRss => self.with_meminfo(|mi| mi.mem_total - mi.mem_free),
-                MetricId::TimeElapsed => {
-                    elapsed_seconds_since(self.sysconf.boot_time_seconds) * 1000
-                }
-                MetricId::TimeCpu => self.sysconf.ticks_to_millis(self.non_idle_ticks()),
-                MetricId::TimeSystem => self
-                    .sysconf
-                    .ticks_to_millis(self.with_cputime(|ct| ct.system)),
-                MetricId::TimeUser => self
-                    .sysconf
-                    .ticks_to_millis(self.with_cputime(|ct| ct.user)),
-                _ => 0,
+            .map(|metric| match (&metric.derived, &metric.filesystem) {
+                (Some(derived), _) => derived.evaluate(|id| self.extract_metric(id)),
+                (None, Some(filesystem)) => filesystem.value(metric.id),
+                (None, None) => self.extract_metric(metric.id),
             })
             .collect()
     }
 
+    fn extract_metric(&mut self, id: MetricId) -> u64 {
+        match id {
+            MetricId::MemVm => {
+                self.with_meminfo(|mi| mi.mem_total - mi.mem_free + mi.swap_total - mi.swap_free)
+            }
+            MetricId::MemRss => self.with_meminfo(|mi| mi.mem_total - mi.mem_free),
+            MetricId::TimeElapsed => elapsed_seconds_since(self.sysconf.boot_time_seconds) * 1000,
+            MetricId::TimeCpu => self.sysconf.ticks_to_millis(self.non_idle_ticks()),
+            MetricId::TimeSystem => self
+                .sysconf
+                .ticks_to_millis(self.with_cputime(|ct| ct.system)),
+            MetricId::TimeUser => self
+                .sysconf
+                .ticks_to_millis(self.with_cputime(|ct| ct.user)),
+            _ => 0,
+        }
+    }
+
+    /// Metrics of a part of the system. Only the CPU time or the memory size is set.
+    pub fn extract_part_metrics(
+        &mut self,
+        part: SystemPart,
+        metrics: Iter<FormattedMetric>,
+    ) -> Vec<u64> {
+        metrics
+            .map(|metric| match metric.derived {
+                Some(ref derived) => derived.evaluate(|id| self.extract_part_metric(part, id)),
+                None => self.extract_part_metric(part, metric.id),
+            })
+            .collect()
+    }
+
+    fn extract_part_metric(&mut self, part: SystemPart, id: MetricId) -> u64 {
+        match id {
+            MetricId::TimeElapsed => elapsed_seconds_since(self.sysconf.boot_time_seconds) * 1000,
+            MetricId::TimeCpu => {
+                let ticks = self.with_cputime(|ct| part.cpu_ticks(ct).unwrap_or(0));
+                self.sysconf.ticks_to_millis(ticks)
+            }
+            MetricId::MemRss | MetricId::MemVm => {
+                self.with_meminfo(|mi| part.mem_bytes(mi).unwrap_or(0))
+            }
+            _ => 0,
+        }
+    }
+
     /// Number of cores
     pub fn num_cores() -> Option<usize> {
         CpuInfo::current().ok().as_ref().map(CpuInfo::num_cores)
     }
 
+    /// Number of online CPUs
+    pub fn online_cpus() -> u64 {
+        match unsafe { libc::sysconf(libc::_SC_NPROCESSORS_ONLN) } {
+            n if n > 0 => n as u64,
+            _ => 1,
+        }
+    }
+
     /// RAM size
     pub fn mem_total() -> Option<u64> {
         Meminfo::current().ok().map(|m| m.mem_total)
     }
+
+    /// RAM size read with the other memory metrics.
+    pub fn ram_size(&mut self) -> u64 {
+        self.with_meminfo(|mi| mi.mem_total)
+    }
+
+    /// Bytes read and written on the disks since the boot.
+    ///
+    /// Only the devices backed by hardware are counted. Partitions, loop and
+    /// device-mapper devices would count the same I/O twice.
+    pub fn storage_io(&mut self) -> (u64, u64) {
+        match procfs::diskstats() {
+            Ok(disks) => disks
+                .iter()
+                .filter(|disk| {
+                    Path::new("/sys/block")
+                        .join(&disk.name)
+                        .join("device")
+                        .exists()
+                })
+                .fold((0, 0), |(read, written), disk| {
+                    (
+                        read + disk.sectors_read * DISK_SECTOR_SIZE,
+                        written + disk.sectors_written * DISK_SECTOR_SIZE,
+                    )
+                }),
+            Err(err) => {
+                log::debug!("cannot read disk statistics: {err}");
+                (0, 0)
+            }
+        }
+    }
 }
 
 /// Statistics about file descriptors
@@ -311,21 +498,35 @@ impl MapsStats {
 /// even if it's only precise in seconds.
 #[derive(Default)]
 pub struct ProcessStat {
+    cgroup_memory: Option<CgroupMemory>,
+    cpu_throttling: Option<CpuThrottling>,
+    delays: Option<Delays>,
     fd_stats: Option<FdStats>,
+    /// Resource limits.
+    limits: Option<Limits>,
     maps_stats: Option<MapsStats>,
+    /// Bytes in the page cache of the files open or mapped.
+    page_cache: Option<u64>,
     io: Option<Io>,
     stat: Option<Stat>,
     statm: Option<StatM>,
+    threads_created: Option<u64>,
 }
 
 impl ProcessStat {
     pub fn with_stat(stat: Stat) -> Self {
         ProcessStat {
+            cgroup_memory: None,
+            cpu_throttling: None,
+            delays: None,
             fd_stats: None,
+            limits: None,
             io: None,
             maps_stats: None,
+            page_cache: None,
             stat: Some(stat),
             statm: None,
+            threads_created: None,
         }
     }
 
@@ -339,6 +540,38 @@ impl ProcessStat {
         self.stat.as_ref().map(func)
     }
 
+    fn on_cgroup_memory<F>(&mut self, process: &Process, func: F) -> Option<u64>
+    where
+        F: Fn(&CgroupMemory) -> Option<u64>,
+    {
+        if self.cgroup_memory.is_none() {
+            self.cgroup_memory = cgroup::cgroup_memory(process.pid()).ok();
+        }
+        self.cgroup_memory.as_ref().and_then(func)
+    }
+
+    fn on_cpu_throttling<F>(&mut self, process: &Process, func: F) -> u64
+    where
+        F: Fn(&CpuThrottling) -> u64,
+    {
+        if self.cpu_throttling.is_none() {
+            self.cpu_throttling = cgroup::cpu_throttling(process.pid()).ok();
+        }
+        self.cpu_throttling.as_ref().map_or(0, func)
+    }
+
+    fn on_delays<F>(&mut self, process: &Process, sysconf: &SystemConf, func: F) -> u64
+    where
+        F: Fn(&Delays) -> u64,
+    {
+        if self.delays.is_none() {
+            self.delays = sysconf
+                .taskstats()
+                .and_then(|taskstats| taskstats.delays(process.pid()).ok());
+        }
+        self.delays.as_ref().map_or(0, func)
+    }
+
     fn on_fd_stats<F>(&mut self, process: &Process, func: F) -> u64
     where
         F: Fn(&FdStats) -> u64,
@@ -349,6 +582,80 @@ impl ProcessStat {
         self.fd_stats.as_ref().map_or(0, func)
     }
 
+    /// Soft limit of a resource if any.
+    fn on_soft_limit<F>(&mut self, process: &Process, func: F) -> Option<u64>
+    where
+        F: Fn(&Limits) -> LimitValue,
+    {
+        if self.limits.is_none() {
+            self.limits = process.limits().ok();
+        }
+        match self.limits.as_ref().map(func) {
+            Some(LimitValue::Value(limit)) if limit > 0 => Some(limit),
+            _ => None,
+        }
+    }
+
+    /// Open file descriptors per thousand of the soft limit, zero if unlimited.
+    fn on_fd_ratio(&mut self, process: &Process) -> u64 {
+        match self.on_soft_limit(process, |limits| limits.max_open_files.soft_limit) {
+            Some(limit) => self.on_fd_stats(process, |stat| stat.total as u64) * 1000 / limit,
+            None => 0,
+        }
+    }
+
+    /// Usage of the resource limit of a metric in per thousand.
+    ///
+    /// The value is the value of the metric. Return None if the metric has no
+    /// limit or if it's unlimited.
+    fn limit_usage(&mut self, id: MetricId, value: u64, process: &Process) -> Option<u64> {
+        // Limit of the metric and factor to convert the limit in the unit of the metric.
+        let (limit, factor) = match id {
+            MetricId::CgroupMemoryRatio | MetricId::FdRatio => return Some(value),
+            MetricId::CgroupMemoryUsage => {
+                (self.on_cgroup_memory(process, |memory| memory.limit), 1)
+            }
+            MetricId::FdAll => (
+                self.on_soft_limit(process, |limits| limits.max_open_files.soft_limit),
+                1,
+            ),
+            MetricId::MapStackSize => (
+                self.on_soft_limit(process, |limits| limits.max_stack_size.soft_limit),
+                1,
+            ),
+            MetricId::MemData => (
+                self.on_soft_limit(process, |limits| limits.max_data_size.soft_limit),
+                1,
+            ),
+            MetricId::MemVm => (
+                self.on_soft_limit(process, |limits| limits.max_address_space.soft_limit),
+                1,
+            ),
+            MetricId::TimeCpu => (
+                self.on_soft_limit(process, |limits| limits.max_cpu_time.soft_limit),
+                1000,
+            ),
+            _ => return None,
+        };
+        limit.map(|limit| (u128::from(value) * 1000 / (u128::from(limit) * factor)) as u64)
+    }
+
+    /// Highest breach of the limits of the metrics given their values.
+    pub fn limit_breach(
+        &mut self,
+        metrics: Iter<FormattedMetric>,
+        values: &[u64],
+        process: &Process,
+    ) -> LimitBreach {
+        metrics
+            .zip(values)
+            .filter(|(metric, _)| metric.has_limit())
+            .filter_map(|(metric, value)| self.limit_usage(metric.id, *value, process))
+            .map(LimitBreach::with_per_mille)
+            .max()
+            .unwrap_or_default()
+    }
+
     fn on_io<F>(&mut self, process: &Process, func: F) -> u64
     where
         F: Fn(&Io) -> u64,
@@ -369,6 +676,13 @@ impl ProcessStat {
         self.maps_stats.as_ref().map_or(0, func)
     }
 
+    fn on_page_cache(&mut self, process: &Process, sysconf: &SystemConf) -> u64 {
+        if self.page_cache.is_none() {
+            self.page_cache = pagecache::page_cache(process, sysconf.page_size).ok();
+        }
+        self.page_cache.unwrap_or(0)
+    }
+
     fn on_stat<F>(&mut self, process: &Process, func: F) -> u64
     where
         F: Fn(&Stat) -> u64,
@@ -396,89 +710,135 @@ impl ProcessStat {
         self.statm.as_ref().map_or(0, |statm| func(statm, sysconf))
     }
 
+    fn on_thread_creation(&mut self, process: &Process, creation: &mut ThreadCreation) -> u64 {
+        if self.threads_created.is_none() {
+            self.threads_created = process.tasks().ok().map(|tasks| {
+                creation.update(tasks.filter_map(|task| task.ok()).map(|task| task.tid))
+            });
+        }
+        self.threads_created.unwrap_or(0)
+    }
+
     /// Elapsed seconds of the process
     fn elapsed_seconds(stat: &Stat, sysconf: &SystemConf) -> u64 {
         let process_start = sysconf.boot_time_seconds + stat.starttime / sysconf.ticks_per_second;
         elapsed_seconds_since(process_start)
     }
 
+    /// Extract the values of the metrics.
+    ///
+    /// If a selection is given, the metrics that are not selected are not read and are zero.
     pub fn extract_metrics(
         &mut self,
         metrics: Iter<FormattedMetric>,
+        selected: Option<&[bool]>,
         process: &Process,
         sysconf: &SystemConf,
+        creation: &mut ThreadCreation,
     ) -> Vec<u64> {
         metrics
-            .map(|metric| match metric.id {
-                MetricId::FaultMinor => self.on_stat(process, |stat| stat.minflt),
-                MetricId::FaultMajor => self.on_stat(process, |stat| stat.majflt),
-                MetricId::FdAll => self.on_fd_stats(process, |stat| stat.total as u64),
-                MetricId::FdHigh => self.on_fd_stats(process, |stat| stat.highest as u64),
-                MetricId::FdAnon
-                | MetricId::FdFile
-                | MetricId::FdMemFile
-                | MetricId::FdNet
-                | MetricId::FdOther
-                | MetricId::FdPipe
-                | MetricId::FdSocket => {
-                    self.on_fd_stats(process, |stat| stat.kinds[&metric.id] as u64)
-                }
-                MetricId::IoReadCall => self.on_io(process, |io| io.rchar),
-                MetricId::IoReadTotal => self.on_io(process, |io| io.syscr),
-                MetricId::IoReadStorage => self.on_io(process, |io| io.read_bytes),
-                MetricId::IoWriteCall => self.on_io(process, |io| io.wchar),
-                MetricId::IoWriteTotal => self.on_io(process, |io| io.syscw),
-                MetricId::IoWriteStorage => self.on_io(process, |io| io.write_bytes),
-                MetricId::MapAnonCount
-                | MetricId::MapHeapCount
-                | MetricId::MapFileCount
-                | MetricId::MapStackCount
-                | MetricId::MapThreadStackCount
-                | MetricId::MapVdsoCount
-                | MetricId::MapVsysCount
-                | MetricId::MapVsyscallCount
-                | MetricId::MapVvarCount
-                | MetricId::MapOtherCount => {
-                    self.on_maps_stats(process, |stat| stat.counts[&metric.id] as u64)
-                }
-                MetricId::MapAnonSize
-                | MetricId::MapHeapSize
-                | MetricId::MapFileSize
-                | MetricId::MapStackSize
-                | MetricId::MapThreadStackSize
-                | MetricId::MapVdsoSize
-                | MetricId::MapVsysSize
-                | MetricId::MapVsyscallSize
-                | MetricId::MapVvarSize
-                | MetricId::MapOtherSize => {
-                    self.on_maps_stats(process, |stat| stat.sizes[&metric.id])
-                }
-                MetricId::MemVm => self.on_stat(process, |stat| stat.vsize),
-                MetricId::MemRss => {
-                    self.on_system_stat(process, sysconf, |stat, sc| stat.rss * sc.page_size)
+            .enumerate()
+            .map(|(index, metric)| {
+                if selected.is_some_and(|selected| !selected[index]) {
+                    return 0;
                 }
-                MetricId::MemText => {
-                    self.on_system_statm(process, sysconf, |statm, sc| statm.text * sc.page_size)
+                match metric.derived {
+                    Some(ref derived) => {
+                        derived.evaluate(|id| self.extract_metric(id, process, sysconf, creation))
+                    }
+                    None => self.extract_metric(metric.id, process, sysconf, creation),
                 }
-                MetricId::MemData => {
-                    self.on_system_statm(process, sysconf, |statm, sc| statm.data * sc.page_size)
-                }
-                MetricId::TimeElapsed => {
-                    self.on_system_stat(process, sysconf, ProcessStat::elapsed_seconds) * 1000
-                }
-                MetricId::TimeCpu => {
-                    sysconf.ticks_to_millis(self.on_stat(process, |stat| stat.stime + stat.utime))
-                }
-                MetricId::TimeSystem => {
-                    sysconf.ticks_to_millis(self.on_stat(process, |stat| stat.stime))
-                }
-                MetricId::TimeUser => {
-                    sysconf.ticks_to_millis(self.on_stat(process, |stat| stat.utime))
-                }
-                MetricId::ThreadCount => self.on_stat(process, |stat| stat.num_threads as u64),
             })
             .collect()
     }
+
+    fn extract_metric(
+        &mut self,
+        id: MetricId,
+        process: &Process,
+        sysconf: &SystemConf,
+        creation: &mut ThreadCreation,
+    ) -> u64 {
+        match id {
+            MetricId::CgroupMemoryUsage => self
+                .on_cgroup_memory(process, |memory| Some(memory.usage))
+                .unwrap_or(0),
+            MetricId::CgroupMemoryLimit => self
+                .on_cgroup_memory(process, |memory| memory.limit)
+                .unwrap_or(0),
+            MetricId::CgroupMemoryRatio => self
+                .on_cgroup_memory(process, |memory| Some(memory.ratio()))
+                .unwrap_or(0),
+            MetricId::CpuThrottledCount => self.on_cpu_throttling(process, |cpu| cpu.count),
+            MetricId::CpuThrottledTime => self.on_cpu_throttling(process, |cpu| cpu.time),
+            MetricId::DelayBlockIo => self.on_delays(process, sysconf, |delays| delays.blkio),
+            MetricId::DelaySwapIn => self.on_delays(process, sysconf, |delays| delays.swapin),
+            MetricId::FaultMinor => self.on_stat(process, |stat| stat.minflt),
+            MetricId::FaultMajor => self.on_stat(process, |stat| stat.majflt),
+            MetricId::FdAll => self.on_fd_stats(process, |stat| stat.total as u64),
+            MetricId::FdHigh => self.on_fd_stats(process, |stat| stat.highest as u64),
+            MetricId::FdRatio => self.on_fd_ratio(process),
+            MetricId::FdAnon
+            | MetricId::FdFile
+            | MetricId::FdMemFile
+            | MetricId::FdNet
+            | MetricId::FdOther
+            | MetricId::FdPipe
+            | MetricId::FdSocket => self.on_fd_stats(process, |stat| stat.kinds[&id] as u64),
+            MetricId::IoReadCall => self.on_io(process, |io| io.syscr),
+            MetricId::IoReadTotal => self.on_io(process, |io| io.rchar),
+            MetricId::IoReadStorage => self.on_io(process, |io| io.read_bytes),
+            MetricId::IoWriteCall => self.on_io(process, |io| io.syscw),
+            MetricId::IoWriteTotal => self.on_io(process, |io| io.wchar),
+            MetricId::IoWriteStorage => self.on_io(process, |io| io.write_bytes),
+            MetricId::MapAnonCount
+            | MetricId::MapHeapCount
+            | MetricId::MapFileCount
+            | MetricId::MapStackCount
+            | MetricId::MapThreadStackCount
+            | MetricId::MapVdsoCount
+            | MetricId::MapVsysCount
+            | MetricId::MapVsyscallCount
+            | MetricId::MapVvarCount
+            | MetricId::MapOtherCount => {
+                self.on_maps_stats(process, |stat| stat.counts[&id] as u64)
+            }
+            MetricId::MapAnonSize
+            | MetricId::MapHeapSize
+            | MetricId::MapFileSize
+            | MetricId::MapStackSize
+            | MetricId::MapThreadStackSize
+            | MetricId::MapVdsoSize
+            | MetricId::MapVsysSize
+            | MetricId::MapVsyscallSize
+            | MetricId::MapVvarSize
+            | MetricId::MapOtherSize => self.on_maps_stats(process, |stat| stat.sizes[&id]),
+            MetricId::MemVm => self.on_stat(process, |stat| stat.vsize),
+            MetricId::MemRss => {
+                self.on_system_stat(process, sysconf, |stat, sc| stat.rss * sc.page_size)
+            }
+            MetricId::MemText => {
+                self.on_system_statm(process, sysconf, |statm, sc| statm.text * sc.page_size)
+            }
+            MetricId::MemData => {
+                self.on_system_statm(process, sysconf, |statm, sc| statm.data * sc.page_size)
+            }
+            MetricId::MemCache => self.on_page_cache(process, sysconf),
+            MetricId::TimeElapsed => {
+                self.on_system_stat(process, sysconf, ProcessStat::elapsed_seconds) * 1000
+            }
+            MetricId::TimeCpu => {
+                sysconf.ticks_to_millis(self.on_stat(process, |stat| stat.stime + stat.utime))
+            }
+            MetricId::TimeSystem => {
+                sysconf.ticks_to_millis(self.on_stat(process, |stat| stat.stime))
+            }
+            MetricId::TimeUser => sysconf.ticks_to_millis(self.on_stat(process, |stat| stat.utime)),
+            MetricId::ThreadCount => self.on_stat(process, |stat| stat.num_threads as u64),
+            MetricId::ThreadRate => self.on_thread_creation(process, creation),
+            MetricId::Derived | MetricId::FsUsed | MetricId::FsRatio => 0,
+        }
+    }
 }
 
 macro_rules! anonymous_option {
@@ -493,11 +853,86 @@ macro_rules! anonymous_option {
 impl fmt::Debug for ProcessStat {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
         f.debug_struct("ProcessStat")
+            .field("cgroup_memory", &self.cgroup_memory)
+            .field("cpu_throttling", &self.cpu_throttling)
+            .field("delays", &self.delays)
             .field("fd_stats", anonymous_option!(self.fd_stats))
+            .field("limits", anonymous_option!(self.limits))
             .field("maps_stats", anonymous_option!(self.maps_stats))
+            .field("page_cache", &self.page_cache)
             .field("io", anonymous_option!(self.io))
             .field("stat", anonymous_option!(self.stat))
             .field("statm", anonymous_option!(self.statm))
+            .field("threads_created", &self.threads_created)
             .finish()
     }
 }
+
+#[cfg(test)]
+mod test {
+
+    use std::path::Path;
+
+    use super::{LimitBreach, ProcessStat, SystemConf, ThreadCreation};
+    use crate::process::{mocks::procfs::process::captured_processes, MetricNamesParser};
+
+    #[test]
+    fn test_captured_process() {
+        let fixture =
+            Path::new(env!("CARGO_MANIFEST_DIR")).join("src/process/mocks/fixtures/sample");
+        let processes = captured_processes(&fixture).unwrap();
+        assert_eq!(1, processes.len());
+        let process = &processes[0];
+        assert_eq!(1281, process.pid());
+
+        let metrics = MetricNamesParser::new(false)
+            .parse(&[
+                "mem:vm",
+                "mem:rss",
+                "mem:text",
+                "thread:count",
+                "io:read:call",
+                "map:file:count",
+                "fd:ratio",
+            ])
+            .unwrap();
+        let sysconf = SystemConf::new().unwrap();
+        let page_size = procfs::page_size();
+        let mut pstat = ProcessStat::default();
+        let values = pstat.extract_metrics(
+            metrics.iter(),
+            None,
+            process,
+            &sysconf,
+            &mut ThreadCreation::default(),
+        );
+        assert_eq!(
+            vec![2_560_000, 345 * page_size, 5 * page_size, 1, 9, 15, 0],
+            values
+        );
+
+        let selected = [false, true, false, true, false, false, false];
+        let mut pstat = ProcessStat::default();
+        let values = pstat.extract_metrics(
+            metrics.iter(),
+            Some(&selected),
+            process,
+            &sysconf,
+            &mut ThreadCreation::default(),
+        );
+        assert_eq!(vec![0, 345 * page_size, 0, 1, 0, 0, 0], values);
+        assert!(pstat.io.is_none());
+
+        // Soft limits: 20000 open files, 8M of stack, unlimited address space.
+        let metrics = MetricNamesParser::new(false)
+            .parse(&["fd:all", "map:stack:size", "mem:vm", "mem:rss"])
+            .unwrap();
+        let mut breach = |values: &[u64]| pstat.limit_breach(metrics.iter(), values, process);
+        assert_eq!(
+            LimitBreach::None,
+            breach(&[100, 1 << 20, u64::MAX, u64::MAX])
+        );
+        assert_eq!(LimitBreach::Near, breach(&[16_000, 1 << 20, 0, 0]));
+        assert_eq!(LimitBreach::At, breach(&[100, 8 << 20, 0, 0]));
+    }
+}
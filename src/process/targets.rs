@@ -17,9 +17,11 @@
 use libc::pid_t;
 use log::error;
 use std::{
+    collections::BTreeSet,
     io::{self, Read},
     path::{Path, PathBuf},
 };
+use strum::IntoEnumIterator;
 
 #[cfg(not(test))]
 use std::fs;
@@ -28,7 +30,12 @@ use std::fs;
 use super::mocks::fs;
 
 use super::{
-    Collector, Forest as ProcessForest, ProcessError, ProcessInfo, SystemConf, SystemStat,
+    watch::{
+        ConditionError, FileWatcher, MatchWatcher, NameWatcher, PidNamespaceWatcher, PidWatcher,
+        UnitWatcher,
+    },
+    Collector, Forest as ProcessForest, ProcessError, ProcessInfo, SystemConf, SystemPart,
+    SystemStat,
 };
 
 #[derive(thiserror::Error, Debug)]
@@ -39,6 +46,10 @@ pub enum TargetError {
     InvalidPath(PathBuf),
     #[error("{0}: invalid process id file")]
     InvalidPidFile(PathBuf),
+    #[error("{0}: invalid pattern")]
+    InvalidPattern(String),
+    #[error("{0}: {1}")]
+    InvalidConditions(String, ConditionError),
     #[error("{0}")]
     ProcessError(ProcessError),
 }
@@ -48,10 +59,19 @@ pub type TargetResult<T> = Result<T, TargetError>;
 /// Different way of identifying processes
 #[derive(Debug)]
 pub enum TargetId {
-    Pid(pid_t),
+    Pid(pid_t, Option<String>),
     PidFile(PathBuf),
-    ProcessName(String),
+    ProcessName(String, Option<String>),
+    /// Processes with a name matching a pattern, including the ones started later.
+    NamePattern(String),
+    /// Processes satisfying all the conditions, including the ones started later.
+    Conditions(String),
+    File(PathBuf),
+    PidNamespace(PathBuf),
+    Unit(String),
     System,
+    /// The system with pseudo-processes for the CPU states and the memory categories.
+    SystemBreakdown,
 }
 
 /// Base name of a file with or without extension
@@ -89,6 +109,8 @@ fn read_pid_file(pid_file: &Path) -> TargetResult<pid_t> {
 /// Once the process is gone, the target returns no metrics.
 struct Target<'a> {
     name: String,
+    /// Whether the name follows the process name when it changes.
+    follow_name: bool,
     pinfo: Option<ProcessInfo>,
     pid_file: Option<PathBuf>,
     sysconf: &'a SystemConf,
@@ -99,6 +121,7 @@ impl<'a> Target<'a> {
         let pinfo = ProcessInfo::with_pid(pid).map_err(|_| TargetError::InvalidProcessId(pid))?;
         Ok(Self {
             name: pinfo.name().to_string(),
+            follow_name: true,
             pinfo: Some(pinfo),
             pid_file: None,
             sysconf,
@@ -113,12 +136,22 @@ impl<'a> Target<'a> {
         Ok(Self {
             name: basename(pid_file, true)
                 .ok_or_else(|| TargetError::InvalidPath(pid_file.to_path_buf()))?,
+            follow_name: false,
             pinfo: None,
             pid_file: Some(pid_file.to_path_buf()),
             sysconf,
         })
     }
 
+    /// Replace the process name by a label.
+    fn with_label(mut self, label: &Option<String>) -> Self {
+        if let Some(label) = label {
+            self.name = label.to_string();
+            self.follow_name = false;
+        }
+        self
+    }
+
     fn is_alive(&self) -> bool {
         self.pinfo
             .as_ref()
@@ -126,6 +159,13 @@ impl<'a> Target<'a> {
             .unwrap_or(false)
     }
 
+    /// Read the process statistics again. Return false if the process is gone.
+    fn refresh_process(&mut self) -> bool {
+        self.pinfo
+            .as_mut()
+            .is_some_and(|pinfo| pinfo.refresh().is_ok())
+    }
+
     fn set_process(&mut self, pid: pid_t) -> TargetResult<()> {
         let pinfo = ProcessInfo::with_pid(pid).map_err(|_| TargetError::InvalidProcessId(pid))?;
         self.pinfo = Some(pinfo);
@@ -144,7 +184,12 @@ impl<'a> Target<'a> {
 
     fn collect(&self, collector: &mut Collector) {
         if let Some(pinfo) = &self.pinfo {
-            collector.collect(&self.name, pinfo, self.sysconf);
+            let name = if self.follow_name {
+                pinfo.name()
+            } else {
+                &self.name
+            };
+            collector.collect(name, pinfo, self.sysconf);
         }
     }
 }
@@ -154,6 +199,14 @@ pub struct TargetContainer<'a> {
     targets: Vec<Target<'a>>,
     sysconf: &'a SystemConf,
     with_system: bool,
+    /// Whether the system is broken down in pseudo-processes.
+    with_breakdown: bool,
+    /// Watched files and namespaces.
+    watchers: Vec<Box<dyn PidWatcher>>,
+    /// Processes found by the watchers.
+    watched: Vec<Target<'a>>,
+    /// Whether some targets may start later.
+    may_wait: bool,
 }
 
 impl<'a> TargetContainer<'a> {
@@ -162,13 +215,63 @@ impl<'a> TargetContainer<'a> {
             targets: Vec::new(),
             sysconf,
             with_system,
+            with_breakdown: false,
+            watchers: Vec::new(),
+            watched: Vec::new(),
+            may_wait: false,
         }
     }
 
-    pub fn refresh(&mut self) -> bool {
+    /// Whether no process is monitored yet and some are expected to start.
+    pub fn is_waiting(&self) -> bool {
+        self.may_wait
+            && self.watched.is_empty()
+            && self.targets.iter().all(|target| target.pinfo.is_none())
+    }
+
+    /// Update the processes found by the watchers.
+    fn refresh_watched(&mut self) -> bool {
         let mut changed = false;
+        self.watchers.iter_mut().for_each(|watcher| {
+            if watcher.refresh() {
+                changed = true;
+            }
+        });
+        if changed {
+            let pids = self
+                .watchers
+                .iter()
+                .flat_map(|watcher| watcher.pids())
+                .collect::<BTreeSet<pid_t>>();
+            self.watched.retain(|target| {
+                target
+                    .pinfo
+                    .as_ref()
+                    .map(|pinfo| pids.contains(&pinfo.pid()))
+                    .unwrap_or(false)
+            });
+            let known = self
+                .watched
+                .iter()
+                .filter_map(|target| target.pinfo.as_ref().map(|pinfo| pinfo.pid()))
+                .collect::<BTreeSet<pid_t>>();
+            for pid in pids.difference(&known) {
+                match Target::new(*pid, self.sysconf) {
+                    Ok(target) => self.watched.push(target),
+                    Err(err) => error!("{pid}: {err}"),
+                }
+            }
+        }
+        changed
+    }
+
+    pub fn refresh(&mut self) -> bool {
+        let mut changed = self.refresh_watched();
+        self.watched.iter_mut().for_each(|target| {
+            target.refresh_process();
+        });
         self.targets.iter_mut().for_each(|target| {
-            if !target.is_alive() && target.clear_process() {
+            if !(target.is_alive() && target.refresh_process()) && target.clear_process() {
                 changed = true;
             }
             if let Some(pid_file) = target.pid_file() {
@@ -186,14 +289,37 @@ impl<'a> TargetContainer<'a> {
 
     pub fn collect(&self, collector: &mut Collector) {
         collector.rewind();
+        // The system values are also needed to show the processes relative to the system.
+        let mut system = SystemStat::new(self.sysconf);
+        collector.collect_system(&mut system);
         if self.with_system {
-            let mut system = SystemStat::new(self.sysconf);
-            collector.collect_system(&mut system);
             collector.record("system", None, &system.extract_metrics(collector.metrics()));
+            if self.with_breakdown {
+                for (index, part) in SystemPart::iter().enumerate() {
+                    let name = format!("system:{}", part.as_str());
+                    let values = system.extract_part_metrics(part, collector.metrics());
+                    collector.record_pseudo(&name, -(index as pid_t) - 1, &values);
+                }
+            }
         }
         self.targets
             .iter()
+            .chain(self.watched.iter())
             .for_each(|target| target.collect(collector));
+        if collector.with_file_usage() {
+            for pid in self
+                .watched
+                .iter()
+                .filter_map(|target| target.pinfo.as_ref().map(ProcessInfo::pid))
+            {
+                let usages = self
+                    .watchers
+                    .iter()
+                    .filter_map(|watcher| watcher.file_usage(pid))
+                    .collect::<Vec<&str>>();
+                collector.set_file_usage(pid, (!usages.is_empty()).then(|| usages.join("; ")));
+            }
+        }
         collector.finish();
     }
 
@@ -202,7 +328,7 @@ impl<'a> TargetContainer<'a> {
     /// Panic if the target is not a PID or a PID file.
     pub fn push_by_pid(&mut self, target_id: &TargetId) -> TargetResult<()> {
         let target = match target_id {
-            TargetId::Pid(pid) => Target::new(*pid, self.sysconf)?,
+            TargetId::Pid(pid, label) => Target::new(*pid, self.sysconf)?.with_label(label),
             TargetId::PidFile(pid_file) => Target::with_pid_file(pid_file, self.sysconf)?,
             _ => panic!("already matched"),
         };
@@ -216,13 +342,44 @@ impl<'a> TargetContainer<'a> {
             TargetId::System => {
                 self.with_system = true;
             }
-            TargetId::ProcessName(name) => {
+            TargetId::SystemBreakdown => {
+                self.with_system = true;
+                self.with_breakdown = true;
+            }
+            TargetId::File(path) => {
+                self.watchers.push(Box::new(FileWatcher::new(path)));
+            }
+            TargetId::PidNamespace(path) => {
+                let watcher = PidNamespaceWatcher::new(path)
+                    .map_err(|_| TargetError::InvalidPath(path.to_path_buf()))?;
+                self.watchers.push(Box::new(watcher));
+            }
+            TargetId::Unit(unit) => {
+                self.watchers.push(Box::new(UnitWatcher::new(unit)));
+            }
+            TargetId::NamePattern(pattern) => {
+                let watcher = NameWatcher::new(pattern)
+                    .map_err(|_| TargetError::InvalidPattern(pattern.to_string()))?;
+                self.watchers.push(Box::new(watcher));
+                self.may_wait = true;
+            }
+            TargetId::Conditions(spec) => {
+                let watcher = MatchWatcher::new(spec)
+                    .map_err(|err| TargetError::InvalidConditions(spec.to_string(), err))?;
+                self.watchers.push(Box::new(watcher));
+                self.may_wait = true;
+            }
+            TargetId::PidFile(_) => {
+                self.push_by_pid(target_id)?;
+                self.may_wait = true;
+            }
+            TargetId::ProcessName(name, label) => {
                 forest.iter_roots().for_each(|p| {
                     if let Ok(descendants) = forest.descendants(p.pid()) {
                         descendants.for_each(|p| {
                             if name == p.name() {
                                 match Target::new(p.pid(), self.sysconf) {
-                                    Ok(target) => self.targets.push(target),
+                                    Ok(target) => self.targets.push(target.with_label(label)),
                                     Err(err) => error!("{name}: {err}"),
                                 }
                             }
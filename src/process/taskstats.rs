@@ -0,0 +1,317 @@
+// Oprs -- process monitor for Linux
+// Copyright (C) 2025  Laurent Pelecq
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Delay accounting with the taskstats generic netlink interface.
+//!
+//! Requesting statistics requires the CAP_NET_ADMIN capability and delay accounting must
+//! be enabled in the kernel (sysctl kernel.task_delayacct=1), otherwise delays are null.
+
+use libc::pid_t;
+use std::{
+    io, mem,
+    os::fd::{AsRawFd, FromRawFd, OwnedFd},
+};
+
+/// Size of struct nlmsghdr.
+pub(super) const NLMSG_HDRLEN: usize = 16;
+/// Size of struct genlmsghdr.
+const GENL_HDRLEN: usize = 4;
+/// Size of struct nlattr.
+const NLA_HDRLEN: usize = 4;
+/// Mask to remove the flags NLA_F_NESTED and NLA_F_NET_BYTEORDER from the attribute type.
+const NLA_TYPE_MASK: u16 = 0x3fff;
+
+pub(super) const NLMSG_ERROR: u16 = 2;
+pub(super) const NLM_F_REQUEST: u16 = 1;
+
+const GENL_ID_CTRL: u16 = 0x10;
+const CTRL_CMD_GETFAMILY: u8 = 3;
+const CTRL_ATTR_FAMILY_ID: u16 = 1;
+const CTRL_ATTR_FAMILY_NAME: u16 = 2;
+
+const TASKSTATS_GENL_NAME: &[u8] = b"TASKSTATS\0";
+const TASKSTATS_GENL_VERSION: u8 = 1;
+const TASKSTATS_CMD_GET: u8 = 1;
+const TASKSTATS_CMD_ATTR_TGID: u16 = 2;
+const TASKSTATS_TYPE_STATS: u16 = 3;
+const TASKSTATS_TYPE_AGGR_TGID: u16 = 5;
+
+/// Offset of blkio_delay_total in struct taskstats.
+const BLKIO_DELAY_OFFSET: usize = 40;
+/// Offset of swapin_delay_total in struct taskstats.
+const SWAPIN_DELAY_OFFSET: usize = 56;
+
+const RECV_BUFFER_SIZE: usize = 4096;
+
+const NANOS_PER_MILLI: u64 = 1_000_000;
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("taskstats: {msg}"))
+}
+
+/// Netlink attributes are aligned on 4 bytes.
+pub(super) fn align(len: usize) -> usize {
+    (len + 3) & !3
+}
+
+pub(super) fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    data.get(offset..offset + 2)
+        .map(|bytes| u16::from_ne_bytes(bytes.try_into().unwrap()))
+}
+
+pub(super) fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    data.get(offset..offset + 4)
+        .map(|bytes| u32::from_ne_bytes(bytes.try_into().unwrap()))
+}
+
+fn read_u64(data: &[u8], offset: usize) -> Option<u64> {
+    data.get(offset..offset + 8)
+        .map(|bytes| u64::from_ne_bytes(bytes.try_into().unwrap()))
+}
+
+/// Generic netlink message with a single attribute.
+fn request_message(family_id: u16, cmd: u8, version: u8, attr_type: u16, attr: &[u8]) -> Vec<u8> {
+    let attr_len = NLA_HDRLEN + attr.len();
+    let len = NLMSG_HDRLEN + GENL_HDRLEN + align(attr_len);
+    let mut msg = Vec::with_capacity(len);
+    msg.extend_from_slice(&(len as u32).to_ne_bytes());
+    msg.extend_from_slice(&family_id.to_ne_bytes());
+    msg.extend_from_slice(&NLM_F_REQUEST.to_ne_bytes());
+    msg.extend_from_slice(&0u32.to_ne_bytes()); // sequence
+    msg.extend_from_slice(&0u32.to_ne_bytes()); // port ID
+    msg.extend_from_slice(&[cmd, version, 0, 0]);
+    msg.extend_from_slice(&(attr_len as u16).to_ne_bytes());
+    msg.extend_from_slice(&attr_type.to_ne_bytes());
+    msg.extend_from_slice(attr);
+    msg.resize(len, 0);
+    msg
+}
+
+/// Attributes of a generic netlink reply or an error.
+fn reply_attributes(reply: &[u8]) -> io::Result<&[u8]> {
+    let len = read_u32(reply, 0).ok_or_else(|| invalid_data("truncated reply"))? as usize;
+    let msg_type = read_u16(reply, 4).ok_or_else(|| invalid_data("truncated reply"))?;
+    let reply = reply
+        .get(..len)
+        .ok_or_else(|| invalid_data("truncated reply"))?;
+    if msg_type == NLMSG_ERROR {
+        let errno = read_u32(reply, NLMSG_HDRLEN).ok_or_else(|| invalid_data("truncated error"))?;
+        return Err(io::Error::from_raw_os_error(-(errno as i32)));
+    }
+    reply
+        .get(NLMSG_HDRLEN + GENL_HDRLEN..)
+        .ok_or_else(|| invalid_data("truncated reply"))
+}
+
+/// Iterator on netlink attributes.
+pub(super) struct Attributes<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Attributes<'a> {
+    pub(super) fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+
+    /// Payload of the first attribute of a given type.
+    pub(super) fn get(self, attr_type: u16) -> Option<&'a [u8]> {
+        self.into_iter()
+            .find_map(|(atype, payload)| (atype == attr_type).then_some(payload))
+    }
+}
+
+impl<'a> Iterator for Attributes<'a> {
+    type Item = (u16, &'a [u8]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let len = read_u16(self.data, 0)? as usize;
+        let attr_type = read_u16(self.data, 2)? & NLA_TYPE_MASK;
+        let payload = self.data.get(NLA_HDRLEN..len)?;
+        self.data = self.data.get(align(len)..).unwrap_or_default();
+        Some((attr_type, payload))
+    }
+}
+
+/// Time spent by a process waiting for resources, in milliseconds.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Delays {
+    /// Waiting for block I/O to complete.
+    pub blkio: u64,
+    /// Waiting for pages to be swapped in.
+    pub swapin: u64,
+}
+
+impl Delays {
+    /// Extract the delays from the attributes of a TASKSTATS_CMD_GET reply.
+    fn from_attributes(data: &[u8]) -> io::Result<Self> {
+        let stats = Attributes::new(data)
+            .get(TASKSTATS_TYPE_AGGR_TGID)
+            .and_then(|aggr| Attributes::new(aggr).get(TASKSTATS_TYPE_STATS))
+            .ok_or_else(|| invalid_data("no statistics in reply"))?;
+        let delay = |offset| {
+            read_u64(stats, offset)
+                .map(|nanos| nanos / NANOS_PER_MILLI)
+                .ok_or_else(|| invalid_data("statistics are truncated"))
+        };
+        Ok(Self {
+            blkio: delay(BLKIO_DELAY_OFFSET)?,
+            swapin: delay(SWAPIN_DELAY_OFFSET)?,
+        })
+    }
+}
+
+/// Client of the taskstats interface.
+#[derive(Debug)]
+pub struct TaskStats {
+    socket: OwnedFd,
+    family_id: u16,
+}
+
+impl TaskStats {
+    pub fn new() -> io::Result<Self> {
+        let fd = unsafe {
+            libc::socket(
+                libc::AF_NETLINK,
+                libc::SOCK_RAW | libc::SOCK_CLOEXEC,
+                libc::NETLINK_GENERIC,
+            )
+        };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let mut client = Self {
+            socket: unsafe { OwnedFd::from_raw_fd(fd) },
+            family_id: GENL_ID_CTRL,
+        };
+        let reply = client.request(
+            CTRL_CMD_GETFAMILY,
+            1,
+            CTRL_ATTR_FAMILY_NAME,
+            TASKSTATS_GENL_NAME,
+        )?;
+        client.family_id = Attributes::new(reply_attributes(&reply)?)
+            .get(CTRL_ATTR_FAMILY_ID)
+            .and_then(|id| read_u16(id, 0))
+            .ok_or_else(|| invalid_data("unknown family"))?;
+        Ok(client)
+    }
+
+    /// Send a request to the kernel and return the reply.
+    fn request(&self, cmd: u8, version: u8, attr_type: u16, attr: &[u8]) -> io::Result<Vec<u8>> {
+        let msg = request_message(self.family_id, cmd, version, attr_type, attr);
+        let mut kernel: libc::sockaddr_nl = unsafe { mem::zeroed() };
+        kernel.nl_family = libc::AF_NETLINK as libc::sa_family_t;
+        let sent = unsafe {
+            libc::sendto(
+                self.socket.as_raw_fd(),
+                msg.as_ptr().cast(),
+                msg.len(),
+                0,
+                (&kernel as *const libc::sockaddr_nl).cast(),
+                mem::size_of::<libc::sockaddr_nl>() as libc::socklen_t,
+            )
+        };
+        if sent < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let mut reply = vec![0u8; RECV_BUFFER_SIZE];
+        let received = unsafe {
+            libc::recv(
+                self.socket.as_raw_fd(),
+                reply.as_mut_ptr().cast(),
+                reply.len(),
+                0,
+            )
+        };
+        if received < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        reply.truncate(received as usize);
+        Ok(reply)
+    }
+
+    /// Delays of all the threads of a process.
+    pub fn delays(&self, tgid: pid_t) -> io::Result<Delays> {
+        let reply = self.request(
+            TASKSTATS_CMD_GET,
+            TASKSTATS_GENL_VERSION,
+            TASKSTATS_CMD_ATTR_TGID,
+            &(tgid as u32).to_ne_bytes(),
+        )?;
+        Delays::from_attributes(reply_attributes(&reply)?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::*;
+
+    fn attribute(attr_type: u16, payload: &[u8]) -> Vec<u8> {
+        let len = NLA_HDRLEN + payload.len();
+        let mut attr = Vec::new();
+        attr.extend_from_slice(&(len as u16).to_ne_bytes());
+        attr.extend_from_slice(&attr_type.to_ne_bytes());
+        attr.extend_from_slice(payload);
+        attr.resize(align(len), 0);
+        attr
+    }
+
+    #[test]
+    fn test_request_message() {
+        let msg = request_message(
+            22,
+            TASKSTATS_CMD_GET,
+            1,
+            TASKSTATS_CMD_ATTR_TGID,
+            &[1, 2, 3],
+        );
+        assert_eq!(28, msg.len());
+        assert_eq!(Some(28), read_u32(&msg, 0));
+        assert_eq!(Some(22), read_u16(&msg, 4));
+        assert_eq!(Some(7), read_u16(&msg, NLMSG_HDRLEN + GENL_HDRLEN));
+        let attrs = Attributes::new(&msg[NLMSG_HDRLEN + GENL_HDRLEN..]).collect::<Vec<_>>();
+        assert_eq!(vec![(TASKSTATS_CMD_ATTR_TGID, &[1u8, 2, 3][..])], attrs);
+    }
+
+    #[test]
+    fn test_error_reply() {
+        let mut reply = request_message(NLMSG_ERROR, 0, 0, 0, &[]);
+        reply[NLMSG_HDRLEN..NLMSG_HDRLEN + 4].copy_from_slice(&(-libc::EPERM).to_ne_bytes());
+        let err = reply_attributes(&reply).unwrap_err();
+        assert_eq!(Some(libc::EPERM), err.raw_os_error());
+    }
+
+    #[test]
+    fn test_delays_from_attributes() {
+        let mut stats = vec![0u8; 64];
+        stats[BLKIO_DELAY_OFFSET..BLKIO_DELAY_OFFSET + 8]
+            .copy_from_slice(&(1_500 * NANOS_PER_MILLI).to_ne_bytes());
+        stats[SWAPIN_DELAY_OFFSET..SWAPIN_DELAY_OFFSET + 8]
+            .copy_from_slice(&(20 * NANOS_PER_MILLI + 123).to_ne_bytes());
+        let mut aggr = attribute(1, &1234u32.to_ne_bytes());
+        aggr.extend(attribute(TASKSTATS_TYPE_STATS, &stats));
+        let data = attribute(TASKSTATS_TYPE_AGGR_TGID | 0x8000, &aggr);
+        assert_eq!(
+            Delays {
+                blkio: 1_500,
+                swapin: 20
+            },
+            Delays::from_attributes(&data).unwrap()
+        );
+        assert!(Delays::from_attributes(&aggr).is_err());
+    }
+}
@@ -0,0 +1,198 @@
+// Oprs -- process monitor for Linux
+// Copyright (C) 2025  Laurent Pelecq
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+#[cfg(feature = "tui")]
+use getset::{CopyGetters, Getters};
+use libc::pid_t;
+use std::collections::HashSet;
+#[cfg(feature = "tui")]
+use std::{collections::HashMap, slice::Iter as SliceIter};
+
+#[cfg(feature = "tui")]
+use super::SystemConf;
+
+/// Maximum number of threads considered as hot.
+#[cfg(feature = "tui")]
+const HOT_THREADS_MAX: usize = 3;
+
+/// CPU consumption of a thread.
+#[derive(Debug, Getters, CopyGetters)]
+#[cfg(feature = "tui")]
+pub struct ThreadSample {
+    /// Thread identifier.
+    #[getset(get_copy = "pub")]
+    tid: pid_t,
+    /// Thread name.
+    #[getset(get = "pub")]
+    name: String,
+    /// Thread state.
+    #[getset(get_copy = "pub")]
+    state: char,
+    /// Total CPU time in milliseconds.
+    #[getset(get_copy = "pub")]
+    cpu_time: u64,
+    /// CPU time in milliseconds during the last interval.
+    #[getset(get_copy = "pub")]
+    cpu_delta: u64,
+}
+
+#[cfg(feature = "tui")]
+impl ThreadSample {
+    pub fn new(tid: pid_t, name: &str, state: char, cpu_time: u64) -> Self {
+        Self {
+            tid,
+            name: name.to_string(),
+            state,
+            cpu_time,
+            cpu_delta: 0,
+        }
+    }
+}
+
+/// Threads of a process sorted by CPU consumption in the last interval.
+#[derive(Debug, Default)]
+#[cfg(feature = "tui")]
+pub struct ThreadMonitor {
+    threads: Vec<ThreadSample>,
+}
+
+#[cfg(feature = "tui")]
+impl ThreadMonitor {
+    /// Replace the threads and compute the CPU time since the last update.
+    ///
+    /// Threads are sorted by decreasing CPU time in the interval, then by thread id.
+    pub fn update<I>(&mut self, threads: I)
+    where
+        I: Iterator<Item = ThreadSample>,
+    {
+        let last_times = self
+            .threads
+            .iter()
+            .map(|thread| (thread.tid, thread.cpu_time))
+            .collect::<HashMap<pid_t, u64>>();
+        self.threads = threads
+            .map(|mut thread| {
+                thread.cpu_delta = last_times
+                    .get(&thread.tid)
+                    .map(|last_time| thread.cpu_time.saturating_sub(*last_time))
+                    .unwrap_or(0);
+                thread
+            })
+            .collect();
+        self.threads
+            .sort_by(|a, b| b.cpu_delta.cmp(&a.cpu_delta).then(a.tid.cmp(&b.tid)));
+    }
+
+    /// Read the threads of a process.
+    pub fn refresh(&mut self, pid: pid_t, sysconf: &SystemConf) -> procfs::ProcResult<()> {
+        let tasks = procfs::process::Process::new(pid)?.tasks()?;
+        self.update(tasks.filter_map(|task| task.ok()?.stat().ok()).map(|stat| {
+            ThreadSample::new(
+                stat.pid,
+                &stat.comm,
+                stat.state,
+                sysconf.ticks_to_millis(stat.utime + stat.stime),
+            )
+        }));
+        Ok(())
+    }
+
+    /// Threads sorted by decreasing CPU usage.
+    pub fn threads(&self) -> SliceIter<'_, ThreadSample> {
+        self.threads.iter()
+    }
+
+    /// Whether a thread is among the ones that consumed the most CPU in the last interval.
+    pub fn is_hot(&self, index: usize) -> bool {
+        index < HOT_THREADS_MAX && self.threads.get(index).is_some_and(|t| t.cpu_delta > 0)
+    }
+}
+
+/// Count the threads created between two samples.
+///
+/// Threads are identified by their thread id. A thread that starts and terminates
+/// between two samples is not seen.
+#[derive(Debug, Default)]
+pub struct ThreadCreation {
+    tids: Option<HashSet<pid_t>>,
+}
+
+impl ThreadCreation {
+    /// Replace the known threads and return the number of new ones.
+    ///
+    /// The first time, all the threads are known and the result is 0.
+    pub fn update<I>(&mut self, tids: I) -> u64
+    where
+        I: Iterator<Item = pid_t>,
+    {
+        let tids = tids.collect::<HashSet<pid_t>>();
+        let created = self
+            .tids
+            .as_ref()
+            .map_or(0, |last_tids| tids.difference(last_tids).count() as u64);
+        self.tids = Some(tids);
+        created
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use super::ThreadCreation;
+    #[cfg(feature = "tui")]
+    use super::{ThreadMonitor, ThreadSample};
+
+    #[test]
+    fn test_thread_creation() {
+        let mut creation = ThreadCreation::default();
+        assert_eq!(0, creation.update([10, 11].into_iter()));
+        assert_eq!(0, creation.update([10, 11].into_iter()));
+        assert_eq!(2, creation.update([10, 12, 13].into_iter()));
+        assert_eq!(1, creation.update([10, 11].into_iter()));
+    }
+
+    #[test]
+    #[cfg(feature = "tui")]
+    fn test_hot_threads() {
+        let mut monitor = ThreadMonitor::default();
+        monitor.update(
+            [
+                ThreadSample::new(10, "main", 'S', 100),
+                ThreadSample::new(11, "worker", 'R', 200),
+                ThreadSample::new(12, "io", 'S', 50),
+            ]
+            .into_iter(),
+        );
+        assert!(!monitor.is_hot(0));
+        monitor.update(
+            [
+                ThreadSample::new(10, "main", 'S', 110),
+                ThreadSample::new(11, "worker", 'R', 500),
+                ThreadSample::new(12, "io", 'S', 50),
+                ThreadSample::new(13, "new", 'R', 30),
+            ]
+            .into_iter(),
+        );
+        let threads = monitor
+            .threads()
+            .map(|t| (t.tid(), t.cpu_delta()))
+            .collect::<Vec<_>>();
+        assert_eq!(vec![(11, 300), (10, 10), (12, 0), (13, 0)], threads);
+        assert!(monitor.is_hot(0));
+        assert!(monitor.is_hot(1));
+        assert!(!monitor.is_hot(2));
+    }
+}
@@ -0,0 +1,499 @@
+// Oprs -- process monitor for Linux
+// Copyright (C) 2025  Laurent Pelecq
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Notable changes of the state of the processes between two samples.
+//!
+//! A process stuck in uninterruptible sleep, becoming a zombie or killed by the
+//! OOM killer is easily missed when looking at the table. So is a process that
+//! executes another program or a daemon that drops its privileges.
+
+use libc::{gid_t, pid_t, uid_t};
+use std::{collections::HashMap, fmt, fs};
+
+/// File with the virtual memory statistics of the kernel.
+const VMSTAT_FILE: &str = "/proc/vmstat";
+
+/// State of a process in uninterruptible sleep.
+const STATE_UNINTERRUPTIBLE: char = 'D';
+
+/// State of a zombie process.
+const STATE_ZOMBIE: char = 'Z';
+
+/// Effective user and group of a process.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Credentials {
+    pub uid: uid_t,
+    pub gid: gid_t,
+}
+
+impl Credentials {
+    /// Parse the effective IDs, the second ones on lines `Uid:` and `Gid:` of /proc/PID/status.
+    fn parse(content: &str) -> Option<Self> {
+        let effective = |prefix| {
+            content
+                .lines()
+                .find_map(|line| line.strip_prefix(prefix))
+                .and_then(|ids| ids.split_whitespace().nth(1))
+                .and_then(|id| id.parse().ok())
+        };
+        Some(Self {
+            uid: effective("Uid:")?,
+            gid: effective("Gid:")?,
+        })
+    }
+
+    /// Read the effective IDs of a process.
+    ///
+    /// The owner of /proc/PID is not reliable since it is root when the
+    /// process is not dumpable, for instance after dropping its privileges.
+    pub fn read(pid: pid_t) -> Option<Self> {
+        fs::read_to_string(format!("/proc/{pid}/status"))
+            .ok()
+            .as_deref()
+            .and_then(Self::parse)
+    }
+}
+
+impl fmt::Display for Credentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.uid, self.gid)
+    }
+}
+
+/// Change of state of a process.
+#[derive(Clone, Debug, PartialEq)]
+pub enum StateEvent {
+    /// The process has been in uninterruptible sleep for a number of intervals.
+    Uninterruptible {
+        pid: pid_t,
+        name: String,
+        intervals: usize,
+    },
+    /// The process has become a zombie.
+    Zombie { pid: pid_t, name: String },
+    /// The process exited while the OOM killer was killing processes.
+    OomKilled { pid: pid_t, name: String },
+    /// The process started and exited between two samples.
+    ShortLived { pid: pid_t, name: String },
+    /// The name of the process has changed.
+    Renamed {
+        pid: pid_t,
+        name: String,
+        previous: String,
+    },
+    /// The effective user or group of the process has changed.
+    CredentialsChanged {
+        pid: pid_t,
+        name: String,
+        credentials: Credentials,
+        previous: Credentials,
+    },
+}
+
+impl StateEvent {
+    pub fn pid(&self) -> pid_t {
+        match self {
+            StateEvent::Uninterruptible { pid, .. }
+            | StateEvent::Zombie { pid, .. }
+            | StateEvent::OomKilled { pid, .. }
+            | StateEvent::ShortLived { pid, .. }
+            | StateEvent::Renamed { pid, .. }
+            | StateEvent::CredentialsChanged { pid, .. } => *pid,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            StateEvent::Uninterruptible { name, .. }
+            | StateEvent::Zombie { name, .. }
+            | StateEvent::OomKilled { name, .. }
+            | StateEvent::ShortLived { name, .. }
+            | StateEvent::Renamed { name, .. }
+            | StateEvent::CredentialsChanged { name, .. } => name,
+        }
+    }
+
+    /// Short name of the event for exports.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            StateEvent::Uninterruptible { .. } => "uninterruptible",
+            StateEvent::Zombie { .. } => "zombie",
+            StateEvent::OomKilled { .. } => "oom-kill",
+            StateEvent::ShortLived { .. } => "short-lived",
+            StateEvent::Renamed { .. } => "renamed",
+            StateEvent::CredentialsChanged { .. } => "credentials",
+        }
+    }
+}
+
+impl fmt::Display for StateEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({}): ", self.name(), self.pid())?;
+        match self {
+            StateEvent::Uninterruptible { intervals, .. } => {
+                write!(f, "in uninterruptible sleep for {intervals} intervals")
+            }
+            StateEvent::Zombie { .. } => write!(f, "zombie"),
+            StateEvent::OomKilled { .. } => write!(f, "probably killed by the OOM killer"),
+            StateEvent::ShortLived { .. } => write!(f, "started and exited between two samples"),
+            StateEvent::Renamed { previous, .. } => write!(f, "renamed from {previous}"),
+            StateEvent::CredentialsChanged {
+                credentials,
+                previous,
+                ..
+            } => write!(f, "uid:gid changed from {previous} to {credentials}"),
+        }
+    }
+}
+
+/// Last known state of a process.
+struct TrackedProcess {
+    name: String,
+    state: char,
+    /// Number of consecutive intervals in uninterruptible sleep.
+    uninterruptible: usize,
+    credentials: Option<Credentials>,
+}
+
+/// Detect the changes of state of the processes.
+pub struct StateMonitor {
+    /// Number of intervals in uninterruptible sleep before notifying. Zero to disable.
+    uninterruptible: usize,
+    /// Whether the changes of name are notified.
+    renames: bool,
+    /// Whether the changes of effective user or group are notified.
+    credentials: bool,
+    processes: HashMap<pid_t, TrackedProcess>,
+    /// Number of processes killed by the OOM killer since boot.
+    oom_kills: Option<u64>,
+    /// Whether the processes have been seen at least once.
+    started: bool,
+}
+
+impl StateMonitor {
+    pub fn new(uninterruptible: usize) -> Self {
+        Self {
+            uninterruptible,
+            renames: false,
+            credentials: false,
+            processes: HashMap::new(),
+            oom_kills: None,
+            started: false,
+        }
+    }
+
+    /// Notify the changes of name.
+    pub fn with_renames(mut self, renames: bool) -> Self {
+        self.renames = renames;
+        self
+    }
+
+    /// Notify the changes of effective user or group.
+    pub fn with_credentials(mut self, credentials: bool) -> Self {
+        self.credentials = credentials;
+        self
+    }
+
+    /// Replace the processes by the new ones given as PID, name, state and credentials.
+    ///
+    /// The processes that disappeared and are no longer alive are considered
+    /// killed by the OOM killer if the OOM kill counter has increased.
+    pub fn update<'a, I, F>(
+        &mut self,
+        processes: I,
+        oom_kills: Option<u64>,
+        is_alive: F,
+    ) -> Vec<StateEvent>
+    where
+        I: Iterator<Item = (pid_t, &'a str, char, Option<Credentials>)>,
+        F: Fn(pid_t) -> bool,
+    {
+        let mut events = Vec::new();
+        let mut current = HashMap::new();
+        for (pid, name, state, credentials) in processes.filter(|(pid, _, _, _)| *pid > 0) {
+            let last = self.processes.remove(&pid);
+            let uninterruptible = if state == STATE_UNINTERRUPTIBLE {
+                last.as_ref()
+                    .filter(|last| last.state == STATE_UNINTERRUPTIBLE)
+                    .map_or(0, |last| last.uninterruptible)
+                    + 1
+            } else {
+                0
+            };
+            if self.uninterruptible > 0 && uninterruptible == self.uninterruptible {
+                events.push(StateEvent::Uninterruptible {
+                    pid,
+                    name: name.to_string(),
+                    intervals: uninterruptible,
+                });
+            }
+            let was_zombie = match last {
+                Some(ref last) => last.state == STATE_ZOMBIE,
+                None => !self.started,
+            };
+            if state == STATE_ZOMBIE && !was_zombie {
+                events.push(StateEvent::Zombie {
+                    pid,
+                    name: name.to_string(),
+                });
+            }
+            // Unknown credentials are transient, the last known ones are kept.
+            let last_credentials = last.as_ref().and_then(|last| last.credentials);
+            if let (true, Some(previous), Some(credentials)) =
+                (self.credentials, last_credentials, credentials)
+            {
+                if previous != credentials {
+                    events.push(StateEvent::CredentialsChanged {
+                        pid,
+                        name: name.to_string(),
+                        credentials,
+                        previous,
+                    });
+                }
+            }
+            if let Some(last) = last.filter(|last| self.renames && last.name != name) {
+                events.push(StateEvent::Renamed {
+                    pid,
+                    name: name.to_string(),
+                    previous: last.name,
+                });
+            }
+            current.insert(
+                pid,
+                TrackedProcess {
+                    name: name.to_string(),
+                    state,
+                    uninterruptible,
+                    credentials: credentials.or(last_credentials),
+                },
+            );
+        }
+        if let (Some(last_kills), Some(kills)) = (self.oom_kills, oom_kills) {
+            if kills > last_kills {
+                let mut killed = self
+                    .processes
+                    .drain()
+                    .filter(|(pid, _)| !is_alive(*pid))
+                    .map(|(pid, process)| StateEvent::OomKilled {
+                        pid,
+                        name: process.name,
+                    })
+                    .collect::<Vec<StateEvent>>();
+                killed.sort_by_key(StateEvent::pid);
+                events.extend(killed);
+            }
+        }
+        self.processes = current;
+        self.oom_kills = oom_kills.or(self.oom_kills);
+        self.started = true;
+        events
+    }
+}
+
+/// Number of processes killed by the OOM killer since boot.
+pub fn read_oom_kills() -> Option<u64> {
+    fs::read_to_string(VMSTAT_FILE)
+        .ok()?
+        .lines()
+        .find_map(|line| line.strip_prefix("oom_kill "))
+        .and_then(|value| value.trim().parse::<u64>().ok())
+}
+
+#[cfg(test)]
+mod test {
+
+    use libc::pid_t;
+
+    use super::{Credentials, StateEvent, StateMonitor};
+
+    fn update(
+        monitor: &mut StateMonitor,
+        processes: &[(pid_t, &'static str, char)],
+        oom_kills: u64,
+    ) -> Vec<StateEvent> {
+        monitor.update(
+            processes
+                .iter()
+                .map(|(pid, name, state)| (*pid, *name, *state, None)),
+            Some(oom_kills),
+            |_| false,
+        )
+    }
+
+    #[test]
+    fn test_uninterruptible() {
+        let mut monitor = StateMonitor::new(2);
+        assert!(update(&mut monitor, &[(10, "cp", 'D')], 0).is_empty());
+        assert_eq!(
+            vec![StateEvent::Uninterruptible {
+                pid: 10,
+                name: String::from("cp"),
+                intervals: 2
+            }],
+            update(&mut monitor, &[(10, "cp", 'D')], 0)
+        );
+        // Notified only once.
+        assert!(update(&mut monitor, &[(10, "cp", 'D')], 0).is_empty());
+        assert!(update(&mut monitor, &[(10, "cp", 'S')], 0).is_empty());
+        assert!(update(&mut monitor, &[(10, "cp", 'D')], 0).is_empty());
+        assert_eq!(1, update(&mut monitor, &[(10, "cp", 'D')], 0).len());
+
+        let mut monitor = StateMonitor::new(0);
+        for _ in 0..3 {
+            assert!(update(&mut monitor, &[(10, "cp", 'D')], 0).is_empty());
+        }
+    }
+
+    #[test]
+    fn test_zombie() {
+        let mut monitor = StateMonitor::new(0);
+        // Zombies at startup are not notified.
+        assert!(update(&mut monitor, &[(10, "sh", 'Z'), (11, "sh", 'S')], 0).is_empty());
+        assert_eq!(
+            vec![
+                StateEvent::Zombie {
+                    pid: 11,
+                    name: String::from("sh")
+                },
+                StateEvent::Zombie {
+                    pid: 12,
+                    name: String::from("make")
+                }
+            ],
+            update(
+                &mut monitor,
+                &[(10, "sh", 'Z'), (11, "sh", 'Z'), (12, "make", 'Z')],
+                0
+            )
+        );
+    }
+
+    #[test]
+    fn test_oom_killed() {
+        let mut monitor = StateMonitor::new(0);
+        let processes = [(0, "system", ' '), (10, "java", 'S'), (11, "sh", 'S')];
+        assert!(update(&mut monitor, &processes, 4).is_empty());
+        // Processes exiting normally.
+        assert!(update(&mut monitor, &processes[..2], 4).is_empty());
+        assert_eq!(
+            vec![StateEvent::OomKilled {
+                pid: 10,
+                name: String::from("java")
+            }],
+            update(&mut monitor, &processes[..1], 5)
+        );
+        // A process that is still alive is not reported.
+        let mut monitor = StateMonitor::new(0);
+        let with_credentials = |processes: &[(pid_t, &'static str, char)]| {
+            processes
+                .iter()
+                .map(|(pid, name, state)| (*pid, *name, *state, None))
+                .collect::<Vec<_>>()
+        };
+        monitor.update(with_credentials(&processes).into_iter(), Some(1), |_| true);
+        assert!(monitor
+            .update(
+                with_credentials(&processes[..1]).into_iter(),
+                Some(2),
+                |_| true
+            )
+            .is_empty());
+    }
+
+    #[test]
+    fn test_renamed() {
+        let mut monitor = StateMonitor::new(0).with_renames(true);
+        assert!(update(&mut monitor, &[(10, "postgres", 'S')], 0).is_empty());
+        assert!(update(&mut monitor, &[(10, "postgres", 'S')], 0).is_empty());
+        assert_eq!(
+            vec![StateEvent::Renamed {
+                pid: 10,
+                name: String::from("postgres: checkpointer"),
+                previous: String::from("postgres")
+            }],
+            update(&mut monitor, &[(10, "postgres: checkpointer", 'S')], 0)
+        );
+        // Not notified by default.
+        let mut monitor = StateMonitor::new(0);
+        assert!(update(&mut monitor, &[(10, "postgres", 'S')], 0).is_empty());
+        assert!(update(&mut monitor, &[(10, "postgres: checkpointer", 'S')], 0).is_empty());
+    }
+
+    #[test]
+    fn test_credentials_changed() {
+        let root = Credentials { uid: 0, gid: 0 };
+        let www = Credentials { uid: 33, gid: 33 };
+        let mut monitor = StateMonitor::new(0).with_credentials(true);
+        let mut update = |credentials| {
+            monitor.update(
+                [(10, "nginx", 'S', credentials)].into_iter(),
+                Some(0),
+                |_| false,
+            )
+        };
+        assert!(update(Some(root)).is_empty());
+        assert!(update(Some(root)).is_empty());
+        assert_eq!(
+            vec![StateEvent::CredentialsChanged {
+                pid: 10,
+                name: String::from("nginx"),
+                credentials: www,
+                previous: root,
+            }],
+            update(Some(www))
+        );
+        // Unknown credentials are not a change and don't reset the last ones.
+        assert!(update(None).is_empty());
+        assert_eq!(
+            "nginx (10): uid:gid changed from 33:33 to 0:0",
+            update(Some(root))[0].to_string()
+        );
+    }
+
+    #[test]
+    fn test_parse_credentials() {
+        let content = "Name:\tnginx\nUid:\t0\t33\t33\t33\nGid:\t0\t34\t34\t34\n";
+        assert_eq!(
+            Some(Credentials { uid: 33, gid: 34 }),
+            Credentials::parse(content)
+        );
+        assert_eq!(
+            None,
+            Credentials::parse("Name:\tnginx\nUid:\t0\t33\t33\t33\n")
+        );
+    }
+
+    #[test]
+    fn test_event_display() {
+        assert_eq!(
+            "cp (10): in uninterruptible sleep for 3 intervals",
+            StateEvent::Uninterruptible {
+                pid: 10,
+                name: String::from("cp"),
+                intervals: 3
+            }
+            .to_string()
+        );
+        assert_eq!(
+            "java (12): probably killed by the OOM killer",
+            StateEvent::OomKilled {
+                pid: 12,
+                name: String::from("java")
+            }
+            .to_string()
+        );
+    }
+}
@@ -0,0 +1,706 @@
+// Oprs -- process monitor for Linux
+// Copyright (C) 2025  Laurent Pelecq
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+use globset::{Glob, GlobMatcher};
+use itertools::Itertools;
+use libc::pid_t;
+use procfs::process::{FDTarget, MMPermissions, MMapPath};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt, io,
+    os::unix::fs::MetadataExt,
+    path::{Path, PathBuf},
+};
+
+use crate::privileges::lookup_user;
+
+use super::{forest::all_processes, format, Process};
+
+/// Source of processes that changes over time.
+pub trait PidWatcher {
+    /// Scan all processes and return true if the list of processes has changed.
+    fn refresh(&mut self) -> bool;
+
+    /// PIDs of the processes found by the last refresh.
+    fn pids(&self) -> Vec<pid_t>;
+
+    /// How a process found by the last refresh uses the watched file, if any.
+    fn file_usage(&self, _pid: pid_t) -> Option<&str> {
+        None
+    }
+}
+
+/// Access mode of a file descriptor.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FileMode {
+    Read,
+    Write,
+    ReadWrite,
+}
+
+impl FileMode {
+    /// Mode from the flags in /proc/PID/fdinfo/FD.
+    fn from_flags(flags: u32) -> Self {
+        match flags as i32 & libc::O_ACCMODE {
+            libc::O_WRONLY => FileMode::Write,
+            libc::O_RDWR => FileMode::ReadWrite,
+            _ => FileMode::Read,
+        }
+    }
+}
+
+impl fmt::Display for FileMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        let s = match self {
+            FileMode::Read => "r",
+            FileMode::Write => "w",
+            FileMode::ReadWrite => "rw",
+        };
+        write!(f, "{s}")
+    }
+}
+
+/// Parse the content of /proc/PID/fdinfo/FD and return the position and the flags.
+fn parse_fdinfo(content: &str) -> Option<(u64, u32)> {
+    let mut pos = None;
+    let mut flags = None;
+    for line in content.lines() {
+        if let Some((key, value)) = line.split_once(':') {
+            match key {
+                "pos" => pos = value.trim().parse::<u64>().ok(),
+                "flags" => flags = u32::from_str_radix(value.trim(), 8).ok(),
+                _ => (),
+            }
+        }
+    }
+    pos.zip(flags)
+}
+
+/// A file descriptor on the watched file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct FileDescriptor {
+    fd: i32,
+    mode: FileMode,
+    pos: u64,
+}
+
+/// How a process uses the watched file.
+#[derive(Debug, Default)]
+struct FileUsage {
+    name: String,
+    fds: Vec<FileDescriptor>,
+    mapped: Option<MMPermissions>,
+}
+
+impl FileUsage {
+    /// Scan the file descriptors and the memory maps of a process.
+    fn scan(process: &Process, path: &Path) -> Option<Self> {
+        let pid = process.pid();
+        let mut fds = Vec::new();
+        if let Ok(fdinfos) = process.fd() {
+            for fdinfo in fdinfos.flatten() {
+                if matches!(fdinfo.target, FDTarget::Path(ref target) if target == path) {
+                    let fdinfo_path = format!("/proc/{pid}/fdinfo/{}", fdinfo.fd);
+                    if let Some((pos, flags)) = std::fs::read_to_string(fdinfo_path)
+                        .ok()
+                        .and_then(|content| parse_fdinfo(&content))
+                    {
+                        fds.push(FileDescriptor {
+                            fd: fdinfo.fd,
+                            mode: FileMode::from_flags(flags),
+                            pos,
+                        });
+                    }
+                }
+            }
+        }
+        let mapped = process.maps().ok().and_then(|maps| {
+            maps.into_iter()
+                .filter(|map| matches!(map.pathname, MMapPath::Path(ref target) if target == path))
+                .map(|map| map.perms)
+                .reduce(|acc, perms| acc | perms)
+        });
+        if fds.is_empty() && mapped.is_none() {
+            None
+        } else {
+            let name = process
+                .stat()
+                .map(|stat| stat.comm)
+                .unwrap_or_else(|_| pid.to_string());
+            Some(Self { name, fds, mapped })
+        }
+    }
+
+    /// Short description of the descriptors and the mapping with the bytes moved
+    /// since the previous scan, for instance `fd 3 (r) +4.1K, mapped (r--s)`.
+    fn summary(&self, previous: Option<&FileUsage>) -> String {
+        self.fds
+            .iter()
+            .map(|desc| {
+                let moved = previous
+                    .and_then(|previous| previous.fds.iter().find(|prev| prev.fd == desc.fd))
+                    .map(|prev| desc.pos.abs_diff(prev.pos))
+                    .filter(|moved| *moved > 0);
+                match moved {
+                    Some(moved) => {
+                        format!("fd {} ({}) +{}", desc.fd, desc.mode, format::size(moved))
+                    }
+                    None => format!("fd {} ({})", desc.fd, desc.mode),
+                }
+            })
+            .chain(
+                self.mapped
+                    .map(|perms| format!("mapped ({})", perms.as_str())),
+            )
+            .join(", ")
+    }
+
+    /// Log the read or write activity since the previous scan.
+    fn report(&self, path: &Path, pid: pid_t, previous: Option<&FileUsage>) {
+        let path = path.display();
+        let name = &self.name;
+        match previous {
+            None => {
+                self.fds.iter().for_each(|desc| {
+                    log::info!(
+                        "{path}: {name}[{pid}] opened fd {} ({})",
+                        desc.fd,
+                        desc.mode
+                    )
+                });
+                if let Some(perms) = self.mapped {
+                    log::info!("{path}: {name}[{pid}] mapped ({})", perms.as_str());
+                }
+            }
+            Some(previous) => {
+                for desc in &self.fds {
+                    match previous.fds.iter().find(|prev| prev.fd == desc.fd) {
+                        Some(prev) if prev.pos != desc.pos => {
+                            let activity = match desc.mode {
+                                FileMode::Read => "read",
+                                FileMode::Write => "written",
+                                FileMode::ReadWrite => "accessed",
+                            };
+                            log::info!(
+                                "{path}: {name}[{pid}] fd {} {activity} {} bytes",
+                                desc.fd,
+                                desc.pos.abs_diff(prev.pos)
+                            );
+                        }
+                        Some(_) => (),
+                        None => log::info!(
+                            "{path}: {name}[{pid}] opened fd {} ({})",
+                            desc.fd,
+                            desc.mode
+                        ),
+                    }
+                }
+                if self.mapped.is_some() && previous.mapped.is_none() {
+                    log::info!("{path}: {name}[{pid}] mapped");
+                }
+            }
+        }
+    }
+}
+
+/// Watch which processes have a file open or mapped.
+#[derive(Debug)]
+pub struct FileWatcher {
+    path: PathBuf,
+    usages: BTreeMap<pid_t, FileUsage>,
+    /// Summary of the use of the file by process.
+    summaries: BTreeMap<pid_t, String>,
+}
+
+impl FileWatcher {
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        let path = path.as_ref();
+        Self {
+            path: path.canonicalize().unwrap_or_else(|_| path.to_path_buf()),
+            usages: BTreeMap::new(),
+            summaries: BTreeMap::new(),
+        }
+    }
+}
+
+impl PidWatcher for FileWatcher {
+    fn refresh(&mut self) -> bool {
+        let mut usages = BTreeMap::new();
+        self.summaries.clear();
+        if let Ok(processes) = all_processes() {
+            for process in processes.flatten() {
+                if let Some(usage) = FileUsage::scan(&process, &self.path) {
+                    let pid = process.pid();
+                    let previous = self.usages.get(&pid);
+                    usage.report(&self.path, pid, previous);
+                    self.summaries.insert(pid, usage.summary(previous));
+                    usages.insert(pid, usage);
+                }
+            }
+        }
+        for (pid, usage) in &self.usages {
+            if !usages.contains_key(pid) {
+                log::info!("{}: {}[{pid}] released", self.path.display(), usage.name);
+            }
+        }
+        let changed = !usages.keys().eq(self.usages.keys());
+        self.usages = usages;
+        changed
+    }
+
+    fn pids(&self) -> Vec<pid_t> {
+        self.usages.keys().copied().collect()
+    }
+
+    fn file_usage(&self, pid: pid_t) -> Option<&str> {
+        self.summaries.get(&pid).map(String::as_str)
+    }
+}
+
+/// Suffixes of the systemd units that contain processes.
+const UNIT_SUFFIXES: [&str; 5] = [".service", ".scope", ".socket", ".mount", ".swap"];
+
+/// Name of the systemd unit in the content of /proc/PID/cgroup.
+///
+/// The unit is the deepest unit in the cgroup path, for instance the user service
+/// rather than the user manager in `/user.slice/user-1000.slice/user@1000.service/app.slice/app.service`.
+fn parse_systemd_unit(content: &str) -> Option<&str> {
+    content
+        .lines()
+        .filter_map(
+            |line| match line.splitn(3, ':').collect::<Vec<&str>>()[..] {
+                // Unified hierarchy or named systemd hierarchy in cgroup v1.
+                ["0", "", path] | [_, "name=systemd", path] => Some(path),
+                _ => None,
+            },
+        )
+        .find_map(|path| {
+            path.rsplit('/').find(|name| {
+                UNIT_SUFFIXES
+                    .iter()
+                    .any(|suffix| name.len() > suffix.len() && name.ends_with(suffix))
+            })
+        })
+}
+
+/// Name of the systemd unit of a process.
+pub fn systemd_unit(pid: pid_t) -> Option<String> {
+    std::fs::read_to_string(format!("/proc/{pid}/cgroup"))
+        .ok()
+        .and_then(|content| parse_systemd_unit(&content).map(str::to_string))
+}
+
+/// Watch the processes of a systemd unit.
+#[derive(Debug)]
+pub struct UnitWatcher {
+    unit: String,
+    pids: BTreeSet<pid_t>,
+}
+
+impl UnitWatcher {
+    /// Unit name with its suffix. Without suffix, it's a service.
+    pub fn new(unit: &str) -> Self {
+        let unit = if unit.contains('.') {
+            unit.to_string()
+        } else {
+            format!("{unit}.service")
+        };
+        Self {
+            unit,
+            pids: BTreeSet::new(),
+        }
+    }
+}
+
+impl PidWatcher for UnitWatcher {
+    fn refresh(&mut self) -> bool {
+        let mut pids = BTreeSet::new();
+        if let Ok(processes) = all_processes() {
+            for process in processes.flatten() {
+                let pid = process.pid();
+                if systemd_unit(pid).is_some_and(|unit| unit == self.unit) {
+                    pids.insert(pid);
+                }
+            }
+        }
+        if pids.is_empty() && !self.pids.is_empty() {
+            log::info!("{}: no more processes in unit", self.unit);
+        }
+        let changed = pids != self.pids;
+        self.pids = pids;
+        changed
+    }
+
+    fn pids(&self) -> Vec<pid_t> {
+        self.pids.iter().copied().collect()
+    }
+}
+
+/// Watch the processes with a name matching a pattern.
+#[derive(Debug)]
+pub struct NameWatcher {
+    pattern: String,
+    matcher: GlobMatcher,
+    pids: BTreeSet<pid_t>,
+}
+
+impl NameWatcher {
+    pub fn new(pattern: &str) -> Result<Self, globset::Error> {
+        Ok(Self {
+            pattern: pattern.to_string(),
+            matcher: Glob::new(pattern)?.compile_matcher(),
+            pids: BTreeSet::new(),
+        })
+    }
+
+    fn is_match(&self, process: &Process) -> bool {
+        is_name_match(&self.matcher, process)
+    }
+}
+
+/// Whether the process name or the first argument of the command line is matching.
+fn is_name_match(matcher: &GlobMatcher, process: &Process) -> bool {
+    process.stat().is_ok_and(|stat| matcher.is_match(stat.comm))
+        || process
+            .cmdline()
+            .ok()
+            .and_then(|cmdline| cmdline.into_iter().next())
+            .is_some_and(|path| {
+                matcher.is_match(&path)
+                    || Path::new(&path)
+                        .file_name()
+                        .is_some_and(|name| matcher.is_match(name))
+            })
+}
+
+impl PidWatcher for NameWatcher {
+    fn refresh(&mut self) -> bool {
+        let mut pids = BTreeSet::new();
+        if let Ok(processes) = all_processes() {
+            for process in processes.flatten() {
+                if self.is_match(&process) {
+                    pids.insert(process.pid());
+                }
+            }
+        }
+        if self.pids.is_empty() && !pids.is_empty() {
+            log::info!("{}: process started", self.pattern);
+        } else if pids.is_empty() && !self.pids.is_empty() {
+            log::info!("{}: no more processes", self.pattern);
+        }
+        let changed = pids != self.pids;
+        self.pids = pids;
+        changed
+    }
+
+    fn pids(&self) -> Vec<pid_t> {
+        self.pids.iter().copied().collect()
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum ConditionError {
+    #[error("{0}: unknown condition")]
+    UnknownKey(String),
+    #[error("{0}: duplicate condition")]
+    DuplicateKey(String),
+    #[error("{0}: missing value")]
+    MissingValue(String),
+    #[error("{0}: unknown user")]
+    UnknownUser(String),
+    #[error("{0}")]
+    InvalidPattern(#[from] globset::Error),
+}
+
+/// Conditions that a process must all satisfy, for instance `name=java,user=tomcat`.
+#[derive(Debug)]
+struct ProcessConditions {
+    /// Pattern of the name as in option --glob.
+    name: Option<GlobMatcher>,
+    /// Owner of the process.
+    uid: Option<u32>,
+}
+
+impl ProcessConditions {
+    /// Parse comma-separated conditions `key=value`. The user is a name or a UID.
+    fn parse<F>(spec: &str, lookup_uid: F) -> Result<Self, ConditionError>
+    where
+        F: Fn(&str) -> Option<u32>,
+    {
+        let mut conditions = Self {
+            name: None,
+            uid: None,
+        };
+        for condition in spec.split(',') {
+            let (key, value) = condition
+                .split_once('=')
+                .filter(|(_, value)| !value.is_empty())
+                .ok_or_else(|| ConditionError::MissingValue(condition.to_string()))?;
+            let is_set = match key.trim() {
+                "name" => conditions
+                    .name
+                    .replace(Glob::new(value)?.compile_matcher())
+                    .is_some(),
+                "user" => {
+                    let uid = value
+                        .parse::<u32>()
+                        .ok()
+                        .or_else(|| lookup_uid(value))
+                        .ok_or_else(|| ConditionError::UnknownUser(value.to_string()))?;
+                    conditions.uid.replace(uid).is_some()
+                }
+                _ => return Err(ConditionError::UnknownKey(key.to_string())),
+            };
+            if is_set {
+                return Err(ConditionError::DuplicateKey(key.to_string()));
+            }
+        }
+        Ok(conditions)
+    }
+
+    fn is_match(&self, process: &Process) -> bool {
+        self.uid
+            .is_none_or(|uid| process.uid().is_ok_and(|owner| owner == uid))
+            && self
+                .name
+                .as_ref()
+                .is_none_or(|matcher| is_name_match(matcher, process))
+    }
+}
+
+/// Watch the processes satisfying all the conditions.
+#[derive(Debug)]
+pub struct MatchWatcher {
+    spec: String,
+    conditions: ProcessConditions,
+    pids: BTreeSet<pid_t>,
+}
+
+impl MatchWatcher {
+    pub fn new(spec: &str) -> Result<Self, ConditionError> {
+        let conditions =
+            ProcessConditions::parse(spec, |user| lookup_user(user).ok().map(|(uid, _)| uid))?;
+        Ok(Self {
+            spec: spec.to_string(),
+            conditions,
+            pids: BTreeSet::new(),
+        })
+    }
+}
+
+impl PidWatcher for MatchWatcher {
+    fn refresh(&mut self) -> bool {
+        let mut pids = BTreeSet::new();
+        if let Ok(processes) = all_processes() {
+            for process in processes.flatten() {
+                if self.conditions.is_match(&process) {
+                    pids.insert(process.pid());
+                }
+            }
+        }
+        if self.pids.is_empty() && !pids.is_empty() {
+            log::info!("{}: process started", self.spec);
+        } else if pids.is_empty() && !self.pids.is_empty() {
+            log::info!("{}: no more processes", self.spec);
+        }
+        let changed = pids != self.pids;
+        self.pids = pids;
+        changed
+    }
+
+    fn pids(&self) -> Vec<pid_t> {
+        self.pids.iter().copied().collect()
+    }
+}
+
+/// Identifier of a namespace: device and inode of the nsfs file.
+type NamespaceId = (u64, u64);
+
+/// Identifier of the namespace referenced by a path.
+fn namespace_id<P: AsRef<Path>>(path: P) -> io::Result<NamespaceId> {
+    let metadata = std::fs::metadata(path)?;
+    Ok((metadata.dev(), metadata.ino()))
+}
+
+/// Watch the processes in a PID namespace.
+#[derive(Debug)]
+pub struct PidNamespaceWatcher {
+    path: PathBuf,
+    id: NamespaceId,
+    pids: BTreeSet<pid_t>,
+}
+
+impl PidNamespaceWatcher {
+    /// Namespace referenced by a nsfs path such as /proc/PID/ns/pid.
+    pub fn new<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let path = path.as_ref();
+        Ok(Self {
+            path: path.to_path_buf(),
+            id: namespace_id(path)?,
+            pids: BTreeSet::new(),
+        })
+    }
+}
+
+impl PidWatcher for PidNamespaceWatcher {
+    fn refresh(&mut self) -> bool {
+        let mut pids = BTreeSet::new();
+        if let Ok(processes) = all_processes() {
+            for process in processes.flatten() {
+                let pid = process.pid();
+                if matches!(namespace_id(format!("/proc/{pid}/ns/pid")), Ok(id) if id == self.id) {
+                    pids.insert(pid);
+                }
+            }
+        }
+        if pids.is_empty() && !self.pids.is_empty() {
+            log::info!("{}: no more processes in namespace", self.path.display());
+        }
+        let changed = pids != self.pids;
+        self.pids = pids;
+        changed
+    }
+
+    fn pids(&self) -> Vec<pid_t> {
+        self.pids.iter().copied().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use procfs::process::MMPermissions;
+
+    use super::{
+        namespace_id, parse_fdinfo, parse_systemd_unit, ConditionError, FileDescriptor, FileMode,
+        FileUsage, NameWatcher, PidNamespaceWatcher, PidWatcher, ProcessConditions, UnitWatcher,
+    };
+
+    #[test]
+    fn test_parse_fdinfo() {
+        let content = "pos:\t1234\nflags:\t0100002\nmnt_id:\t25\nino:\t1311\n";
+        let (pos, flags) = parse_fdinfo(content).unwrap();
+        assert_eq!(1234, pos);
+        assert_eq!(FileMode::ReadWrite, FileMode::from_flags(flags));
+        let (_, flags) = parse_fdinfo("pos:\t0\nflags:\t02100001\n").unwrap();
+        assert_eq!(FileMode::Write, FileMode::from_flags(flags));
+        let (_, flags) = parse_fdinfo("pos:\t0\nflags:\t02100000\n").unwrap();
+        assert_eq!(FileMode::Read, FileMode::from_flags(flags));
+        assert!(parse_fdinfo("mnt_id:\t25\n").is_none());
+    }
+
+    #[test]
+    fn test_file_usage_summary() {
+        let usage = |positions: &[(i32, FileMode, u64)], mapped| FileUsage {
+            name: String::from("db"),
+            fds: positions
+                .iter()
+                .map(|(fd, mode, pos)| FileDescriptor {
+                    fd: *fd,
+                    mode: *mode,
+                    pos: *pos,
+                })
+                .collect(),
+            mapped,
+        };
+        let previous = usage(&[(3, FileMode::Read, 0), (5, FileMode::Write, 100)], None);
+        assert_eq!("fd 3 (r), fd 5 (w)", previous.summary(None));
+        let current = usage(
+            &[
+                (3, FileMode::Read, 2048),
+                (5, FileMode::Write, 100),
+                (7, FileMode::ReadWrite, 10),
+            ],
+            Some(MMPermissions::READ | MMPermissions::SHARED),
+        );
+        assert_eq!(
+            "fd 3 (r) +2.05 K, fd 5 (w), fd 7 (rw), mapped (r--s)",
+            current.summary(Some(&previous))
+        );
+    }
+
+    #[test]
+    fn test_parse_systemd_unit() {
+        assert_eq!(
+            Some("nginx.service"),
+            parse_systemd_unit("0::/system.slice/nginx.service\n")
+        );
+        assert_eq!(
+            Some("app.service"),
+            parse_systemd_unit(
+                "0::/user.slice/user-1000.slice/user@1000.service/app.slice/app.service\n"
+            )
+        );
+        assert_eq!(
+            Some("session-2.scope"),
+            parse_systemd_unit(
+                "12:cpu,cpuacct:/user.slice\n1:name=systemd:/user.slice/user-1000.slice/session-2.scope\n"
+            )
+        );
+        assert_eq!(None, parse_systemd_unit("0::/\n"));
+        assert_eq!(None, parse_systemd_unit("0::/system.slice\n"));
+        assert_eq!("cron.service", UnitWatcher::new("cron").unit);
+        assert_eq!("cron.service", UnitWatcher::new("cron.service").unit);
+    }
+
+    #[test]
+    fn test_name_watcher() {
+        let watcher = NameWatcher::new("nginx*").unwrap();
+        assert!(watcher.matcher.is_match("nginx-worker"));
+        assert!(!watcher.matcher.is_match("apache"));
+        assert!(NameWatcher::new("nginx[").is_err());
+    }
+
+    #[test]
+    fn test_process_conditions() {
+        let lookup_uid = |user: &str| (user == "tomcat").then_some(91);
+        let conditions = ProcessConditions::parse("name=java,user=tomcat", lookup_uid).unwrap();
+        assert!(conditions.name.unwrap().is_match("java"));
+        assert_eq!(Some(91), conditions.uid);
+        let conditions = ProcessConditions::parse("user=1000", lookup_uid).unwrap();
+        assert!(conditions.name.is_none());
+        assert_eq!(Some(1000), conditions.uid);
+        assert!(matches!(
+            ProcessConditions::parse("name=java,user=nobody", lookup_uid),
+            Err(ConditionError::UnknownUser(_))
+        ));
+        assert!(matches!(
+            ProcessConditions::parse("name=java,name=sh", lookup_uid),
+            Err(ConditionError::DuplicateKey(_))
+        ));
+        assert!(matches!(
+            ProcessConditions::parse("pid=12", lookup_uid),
+            Err(ConditionError::UnknownKey(_))
+        ));
+        assert!(matches!(
+            ProcessConditions::parse("name=", lookup_uid),
+            Err(ConditionError::MissingValue(_))
+        ));
+        assert!(matches!(
+            ProcessConditions::parse("java", lookup_uid),
+            Err(ConditionError::MissingValue(_))
+        ));
+    }
+
+    #[test]
+    fn test_pid_namespace() {
+        let pid = std::process::id();
+        let watcher = PidNamespaceWatcher::new(format!("/proc/{pid}/ns/pid")).unwrap();
+        assert_eq!(namespace_id("/proc/self/ns/pid").unwrap(), watcher.id);
+        assert!(watcher.pids().is_empty());
+        assert!(PidNamespaceWatcher::new("/no/such/namespace").is_err());
+    }
+}
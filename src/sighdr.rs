@@ -14,18 +14,53 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
-use std::sync::{
-    atomic::{AtomicBool, Ordering},
-    Arc,
+use std::{
+    io,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
 };
 
-/// Catch SIGINT and SIGTERM.
+/// Set when SIGHUP is received and the configuration must be reloaded.
+static HANGUP: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn on_hangup(_: libc::c_int) {
+    HANGUP.store(true, Ordering::SeqCst);
+}
+
+/// Set when SIGCONT is received after the process has been stopped.
+static CONTINUED: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn on_continue(_: libc::c_int) {
+    CONTINUED.store(true, Ordering::SeqCst);
+}
+
+/// Set when SIGUSR1 is received and the internal state must be dumped.
+static DUMP: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn on_dump(_: libc::c_int) {
+    DUMP.store(true, Ordering::SeqCst);
+}
+
+/// Install a signal handler.
+fn set_handler(signum: libc::c_int, handler: extern "C" fn(libc::c_int)) -> io::Result<()> {
+    if unsafe { libc::signal(signum, handler as libc::sighandler_t) } == libc::SIG_ERR {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
+
+/// Catch SIGINT, SIGTERM, SIGHUP, SIGCONT and SIGUSR1.
+///
+/// If SIGHUP is used to reload the configuration, it doesn't terminate the program.
 pub struct SignalHandler {
     caught: Arc<AtomicBool>,
 }
 
 impl SignalHandler {
-    pub fn new() -> Result<SignalHandler, ctrlc::Error> {
+    pub fn new(reload_on_hangup: bool) -> anyhow::Result<SignalHandler> {
         let caught = Arc::new(AtomicBool::new(false));
         let handler = SignalHandler {
             caught: caught.clone(),
@@ -33,10 +68,47 @@ impl SignalHandler {
         ctrlc::set_handler(move || {
             caught.store(true, Ordering::SeqCst);
         })?;
+        if reload_on_hangup {
+            // Override the handler set by ctrlc for SIGHUP.
+            set_handler(libc::SIGHUP, on_hangup)?;
+        }
+        set_handler(libc::SIGCONT, on_continue)?;
+        set_handler(libc::SIGUSR1, on_dump)?;
         Ok(handler)
     }
 
+    /// Whether a termination signal has been caught.
     pub fn caught(&self) -> bool {
         self.caught.load(Ordering::SeqCst)
     }
+
+    /// Whether SIGHUP has been caught and not yet handled.
+    pub fn hangup_pending(&self) -> bool {
+        HANGUP.load(Ordering::SeqCst)
+    }
+
+    /// Whether SIGHUP has been caught since the last call.
+    pub fn hangup(&self) -> bool {
+        HANGUP.swap(false, Ordering::SeqCst)
+    }
+
+    /// Whether SIGCONT has been caught and not yet handled.
+    pub fn continued_pending(&self) -> bool {
+        CONTINUED.load(Ordering::SeqCst)
+    }
+
+    /// Whether the process has been continued since the last call.
+    pub fn continued(&self) -> bool {
+        CONTINUED.swap(false, Ordering::SeqCst)
+    }
+
+    /// Whether SIGUSR1 has been caught and not yet handled.
+    pub fn dump_pending(&self) -> bool {
+        DUMP.load(Ordering::SeqCst)
+    }
+
+    /// Whether a dump of the state has been requested since the last call.
+    pub fn dump(&self) -> bool {
+        DUMP.swap(false, Ordering::SeqCst)
+    }
 }